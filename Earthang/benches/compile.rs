@@ -0,0 +1,35 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::path::Path;
+use earthang::bench::{self, BenchReport};
+
+// `cargo bench` times parsing and compiling a large generated program and
+// every program in examples/programs on every backend, and records output
+// sizes. The report goes to target/bench/compile.json; the report already
+// there, from the previous run, is what the changes are shown against.
+
+const ITERATIONS: usize = 10;
+
+fn main() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut programs = vec![("generated".to_string(), bench::generated_source(2000))];
+    programs.extend(bench::corpus(&root.join("examples").join("programs")).unwrap());
+
+    let report = bench::run(&programs, ITERATIONS);
+    let path = root.join("target").join("bench").join("compile.json");
+    let baseline = BenchReport::load(&path).ok();
+    print!("{}", report.render(baseline.as_ref()));
+    report.save(&path).unwrap();
+    println!("Saved {}", path.display());
+}
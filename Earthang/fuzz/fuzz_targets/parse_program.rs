@@ -0,0 +1,35 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Run with `cargo +nightly fuzz run parse_program`, or for a smoke run
+// `cargo +nightly fuzz run parse_program -- -max_total_time=10`. Seeding the
+// corpus with examples/programs gets past the lexer sooner.
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    match earthang::parser::parse_bytes(data) {
+        Ok(program) => {
+            let _ = earthang::lua_frontend::format_source(&text);
+            let _ = earthang::analysis::analyze(&program, &text);
+        }
+        Err(errors) => {
+            for error in errors {
+                let _ = error.render("fuzz.eg", &text);
+            }
+        }
+    }
+});
@@ -0,0 +1,301 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::collections::HashSet;
+use crate::lua_frontend::{render_diagnostic, Expr, FStringPart, Program, Span, Statement};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A finding of the analysis pass. `code` is the name `# noqa: <code>` silences it with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    fn warning(code: &'static str, message: String, span: Span) -> Self {
+        Self { severity: Severity::Warning, code, message, span }
+    }
+
+    pub fn render(&self, file: &str, source: &str) -> String {
+        let level = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        let note = format!("help: add `# noqa: {}` to the line to silence this", self.code);
+        render_diagnostic(level, file, source, &self.message, Some(self.span), &[note])
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}", self.message, self.span.start)
+    }
+}
+
+/// Look for variables that are never read and statements that can never run.
+/// `source` is only consulted for `# noqa` pragmas
+pub fn analyze(program: &Program, source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    check_scope(&program.body, &[], program.span, &mut diagnostics);
+    check_unreachable(&program.body, &mut diagnostics);
+
+    diagnostics.retain(|d| !is_suppressed(source, d));
+    diagnostics.sort_by_key(|d| (d.span.start.line, d.span.start.column));
+    diagnostics
+}
+
+/// Names bound and read directly in one function body or at the top level
+#[derive(Default)]
+struct Scope {
+    bound: Vec<(String, Span, &'static str)>, // (name, first binding, "variable" or "parameter")
+    reads: HashSet<String>,
+}
+
+impl Scope {
+    fn bind(&mut self, name: &str, span: Span, kind: &'static str) {
+        if !self.bound.iter().any(|(bound, _, _)| bound == name) {
+            self.bound.push((name.to_string(), span, kind));
+        }
+    }
+
+    fn visit_block(&mut self, body: &[Statement], diagnostics: &mut Vec<Diagnostic>) {
+        for stmt in body {
+            self.visit_statement(stmt, diagnostics);
+        }
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement, diagnostics: &mut Vec<Diagnostic>) {
+        match stmt {
+            Statement::VarDecl { name, value, span, .. } => {
+                self.visit_expr(value);
+                self.bind(name, *span, "variable");
+            }
+            Statement::ConstDecl { name, value, span } => {
+                self.visit_expr(value);
+                self.bind(name, *span, "constant");
+            }
+            Statement::Assign { target, value, span } => {
+                self.visit_expr(value);
+                self.bind(target, *span, "variable");
+            }
+            Statement::AugAssign { target, value, .. } => {
+                self.visit_expr(value);
+                self.reads.insert(target.clone());
+            }
+            Statement::IndexAssign { target, index, value, .. } => {
+                self.visit_expr(target);
+                self.visit_expr(index);
+                self.visit_expr(value);
+            }
+            Statement::FieldAssign { target, value, .. } => {
+                self.visit_expr(target);
+                self.visit_expr(value);
+            }
+            Statement::Expr(expr) | Statement::Return(Some(expr), _) => self.visit_expr(expr),
+            Statement::If { condition, then_block, elif_blocks, else_block, .. } => {
+                self.visit_expr(condition);
+                self.visit_block(then_block, diagnostics);
+                for (cond, block) in elif_blocks {
+                    self.visit_expr(cond);
+                    self.visit_block(block, diagnostics);
+                }
+                if let Some(block) = else_block {
+                    self.visit_block(block, diagnostics);
+                }
+            }
+            Statement::While { condition, body, orelse, .. } => {
+                self.visit_expr(condition);
+                self.visit_block(body, diagnostics);
+                if let Some(block) = orelse {
+                    self.visit_block(block, diagnostics);
+                }
+            }
+            Statement::For { var, iter, body, span } => {
+                self.visit_expr(iter);
+                self.bind(var, *span, "variable");
+                self.visit_block(body, diagnostics);
+            }
+            Statement::FunctionDef { args, body, span, .. } | Statement::HardwareFunctionDef { args, body, span, .. } => {
+                // What a nested function reads from outside belongs to this scope
+                let free = check_scope(body, args, *span, diagnostics);
+                self.reads.extend(free);
+            }
+            Statement::HardwareDecl { config, .. } => config.values().for_each(|e| self.visit_expr(e)),
+            _ => {}
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Var(name, _) => {
+                self.reads.insert(name.clone());
+            }
+            Expr::BinOp { left, right, .. } => {
+                self.visit_expr(left);
+                self.visit_expr(right);
+            }
+            Expr::UnaryOp { operand, .. } => self.visit_expr(operand),
+            Expr::BoolOp { values, .. } | Expr::List { elements: values, .. } => values.iter().for_each(|e| self.visit_expr(e)),
+            Expr::Compare { left, comparators, .. } => {
+                self.visit_expr(left);
+                comparators.iter().for_each(|e| self.visit_expr(e));
+            }
+            Expr::Call { func, args, kwargs, span } => {
+                // The `{name}`s of inline assembly read those variables
+                if let (true, [Expr::String(template, _)]) = (func == "asm", args.as_slice()) {
+                    let _ = crate::backend::expand_inline_asm(template, *span, |name| {
+                        self.reads.insert(name.to_string());
+                        Ok(String::new())
+                    });
+                }
+                args.iter().for_each(|e| self.visit_expr(e));
+                kwargs.values().for_each(|e| self.visit_expr(e));
+            }
+            Expr::HardwareCall { args, .. } => args.iter().for_each(|e| self.visit_expr(e)),
+            Expr::FString { parts, .. } => {
+                for part in parts {
+                    if let FStringPart::Expr(e) = part {
+                        self.visit_expr(e);
+                    }
+                }
+            }
+            Expr::Dict { entries, .. } => {
+                for (key, value) in entries {
+                    self.visit_expr(key);
+                    self.visit_expr(value);
+                }
+            }
+            Expr::Index { value, index, .. } => {
+                self.visit_expr(value);
+                self.visit_expr(index);
+            }
+            Expr::Slice { value, start, stop, .. } => {
+                self.visit_expr(value);
+                [start, stop].into_iter().flatten().for_each(|bound| self.visit_expr(bound));
+            }
+            Expr::FieldAccess { object, .. } => self.visit_expr(object),
+            Expr::MethodCall { receiver, args, .. } => {
+                self.visit_expr(receiver);
+                args.iter().for_each(|e| self.visit_expr(e));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Report the unread names of one scope and return the names it reads
+/// without binding them, which an enclosing scope provides
+fn check_scope(body: &[Statement], params: &[String], span: Span, diagnostics: &mut Vec<Diagnostic>) -> HashSet<String> {
+    let mut scope = Scope::default();
+    for param in params {
+        scope.bind(param, span, "parameter");
+    }
+    scope.visit_block(body, diagnostics);
+
+    // A leading underscore marks a name as intentionally unused
+    for (name, span, kind) in &scope.bound {
+        if !scope.reads.contains(name) && !name.starts_with('_') {
+            diagnostics.push(Diagnostic::warning("unused", format!("{} '{}' is never read", kind, name), *span));
+        }
+    }
+
+    let bound: HashSet<&String> = scope.bound.iter().map(|(name, _, _)| name).collect();
+    scope.reads.iter().filter(|name| !bound.contains(name)).cloned().collect()
+}
+
+/// Flag the first statement after a `return`, `break` or `continue` in each block
+fn check_unreachable(body: &[Statement], diagnostics: &mut Vec<Diagnostic>) {
+    let mut exit = None;
+    for stmt in body {
+        if let Some(keyword) = exit {
+            diagnostics.push(Diagnostic::warning("unreachable", format!("unreachable code after '{}'", keyword), stmt.span()));
+            return;
+        }
+
+        match stmt {
+            Statement::If { then_block, elif_blocks, else_block, .. } => {
+                check_unreachable(then_block, diagnostics);
+                elif_blocks.iter().for_each(|(_, block)| check_unreachable(block, diagnostics));
+                if let Some(block) = else_block {
+                    check_unreachable(block, diagnostics);
+                }
+            }
+            Statement::While { body, .. }
+            | Statement::For { body, .. }
+            | Statement::FunctionDef { body, .. }
+            | Statement::HardwareFunctionDef { body, .. } => check_unreachable(body, diagnostics),
+            _ => {}
+        }
+
+        exit = match stmt {
+            Statement::Return(..) => Some("return"),
+            Statement::Break => Some("break"),
+            Statement::Continue => Some("continue"),
+            _ => None,
+        };
+    }
+}
+
+/// `# noqa` silences every diagnostic on its line, `# noqa: unused, unreachable` only those codes
+fn is_suppressed(source: &str, diagnostic: &Diagnostic) -> bool {
+    let Some(line) = diagnostic.span.start.line.checked_sub(1).and_then(|i| source.lines().nth(i)) else {
+        return false;
+    };
+
+    line.match_indices('#').any(|(at, _)| {
+        match line[at + 1..].trim_start().strip_prefix("noqa") {
+            Some(rest) => match rest.trim_start().strip_prefix(':') {
+                Some(codes) => codes.split(',').any(|code| code.trim() == diagnostic.code),
+                None => rest.is_empty() || rest.starts_with(char::is_whitespace),
+            },
+            None => false,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_program;
+
+    fn warnings(source: &str) -> Vec<String> {
+        analyze(&parse_program(source).unwrap(), source).iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn test_unused_and_unreachable() {
+        let source = "var a = 1\nvar b = 2\nvar _c = 3\nprint(b)\ndef f(x, y): return x\nfor i in range(3):\n    break\n    print(i)\nend\n";
+        assert_eq!(warnings(source), vec![
+            "variable 'a' is never read at 1:1",
+            "parameter 'y' is never read at 5:1",
+            "unreachable code after 'break' at 8:5",
+        ]);
+
+        // Reads from a function count for the scope that binds the name
+        assert!(warnings("var total = 0\ndef get(): return total\nprint(get())\n").is_empty());
+        // So do the variables inline assembly names
+        assert_eq!(warnings("var x = 1\nvar y = 2\nasm(\"mov rax, {x}\")\n"), vec!["variable 'y' is never read at 2:1"]);
+
+        // Pragmas only silence the codes they name
+        let source = "var a = 1  # noqa: unused\nvar b = 2  # noqa: unreachable\nvar c = 3  # noqa\n";
+        assert_eq!(warnings(source), vec!["variable 'b' is never read at 2:1"]);
+    }
+}
@@ -11,23 +11,66 @@
     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
     GNU General Public License for more details.
 */
-use crate::parser::{Program, Statement, Expr, Op, CompareOp};
+use crate::parser::{Program, Statement, Expr, Op, CompareOp, UnaryOp, BoolOp, FStringPart, Span};
 use crate::dsl::{HardwareDSL, DeviceType};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::cell::RefCell;
 use std::any::Any;
+use serde::{Deserialize, Serialize};
 
 // Target platforms
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Target {
     Linux64,
+    RiscV64,
+    Aarch64,
+}
+
+impl Target {
+    pub const ALL: [Target; 3] = [Target::Linux64, Target::RiscV64, Target::Aarch64];
+    
+    /// Name on the command line, and of the backend compiling for the target
+    pub fn name(&self) -> &'static str {
+        match self {
+            Target::Linux64 => "linux64",
+            Target::RiscV64 => "riscv64",
+            Target::Aarch64 => "aarch64",
+        }
+    }
+    
+    pub fn description(&self) -> &'static str {
+        match self {
+            Target::Linux64 => "64-bit Linux ELF executable",
+            Target::RiscV64 => "64-bit RISC-V Linux ELF executable",
+            Target::Aarch64 => "64-bit ARM Linux ELF executable",
+        }
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl std::str::FromStr for Target {
+    type Err = String;
+    
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Target::ALL.into_iter().find(|target| target.name() == name).ok_or_else(|| {
+            let valid: Vec<&str> = Target::ALL.iter().map(Target::name).collect();
+            format!("Unknown target '{}'; valid targets are {}", name, valid.join(", "))
+        })
+    }
 }
 
 // Capabilities for backend selection
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Capability {
     // Architecture
     LongMode64,
+    RiscV64,
+    AArch64,
     
     // Extensions
     SSE,
@@ -43,9 +86,16 @@ pub enum Capability {
     VirtualMemory,
     _MultiCore,
     Graphics,
+    PortIO, // in/out instructions, so ring 0 without an OS
+    DiskIO, // direct access to the boot disk's sectors
+    Interrupts, // an IDT and remapped PICs, so IRQs arrive instead of being polled
     
     // Environment
     Linux,
+    Heap,
+    Filesystem,
+    Float,
+    WritableMemory,
     
     // Constraints
     _NoFloat,
@@ -54,6 +104,44 @@ pub enum Capability {
     _ReadOnly,
 }
 
+impl Capability {
+    /// Instruction set extensions that depend on the CPU rather than the environment
+    pub fn is_cpu_extension(&self) -> bool {
+        matches!(
+            self,
+            Capability::SSE | Capability::SSE2 | Capability::_SSE3 | Capability::_SSE4
+                | Capability::AVX | Capability::_AVX2 | Capability::AVX512
+        )
+    }
+    
+    /// Constraints describe an environment's limits rather than a feature it offers
+    pub fn is_constraint(&self) -> bool {
+        self.excluded_feature().is_some()
+    }
+    
+    /// The feature a constraint rules out
+    pub fn excluded_feature(&self) -> Option<Capability> {
+        match self {
+            Capability::_NoFloat => Some(Capability::Float),
+            Capability::NoHeap => Some(Capability::Heap),
+            Capability::NoFilesystem => Some(Capability::Filesystem),
+            Capability::_ReadOnly => Some(Capability::WritableMemory),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a backend offering `backend_caps` can host a module needing `module_caps`.
+/// Required features and constraints must be offered by the backend, and a
+/// constraint of the backend rejects modules needing the feature it rules out.
+pub fn capabilities_compatible(backend_caps: &[Capability], module_caps: &[Capability]) -> bool {
+    let provided = module_caps.iter().all(|cap| backend_caps.contains(cap));
+    let permitted = backend_caps.iter()
+        .filter_map(|cap| cap.excluded_feature())
+        .all(|excluded| !module_caps.contains(&excluded));
+    provided && permitted
+}
+
 #[derive(Debug, Clone)]
 pub struct BackendModule {
     #[allow(dead_code)]
@@ -402,6 +490,9 @@ pub trait Backend {
     /// Backend name for debugging
     fn name(&self) -> &str;
     
+    /// Platform the generated assembly is for
+    fn target(&self) -> Target;
+    
     /// Generate assembly header/setup
     #[allow(dead_code)]
     fn generate_header(&self) -> String;
@@ -419,9 +510,13 @@ pub trait Backend {
     #[allow(dead_code)]
     fn format(&self) -> &'static str;
 
+    /// How specialised this backend is; the registry prefers higher scores
+    fn specificity(&self) -> usize {
+        self.supported_capabilities().len()
+    }
+
     fn can_compile(&self, module: &BackendModule) -> bool {
-        module.required_capabilities.iter()
-            .all(|cap| self.supported_capabilities().contains(cap))
+        capabilities_compatible(&self.supported_capabilities(), &module.required_capabilities)
     }
     
     /// Generate assembly from program AST
@@ -457,46 +552,360 @@ impl BackendRegistry {
     }
     
     pub fn find_backend(&self, module: &BackendModule) -> Option<&dyn Backend> {
-        self.backends.iter()
-            .find(|b| {
-                b.can_compile(module) && self.capabilities_match(b.as_ref(), &module.required_capabilities)
-            })
-            .map(|b| b.as_ref())
+        self.find_best_backend(module, None)
+    }
+    
+    /// Pick the most specific backend for `module`. When `host_capabilities` is given,
+    /// backends using CPU extensions the host lacks are skipped. Ties go to the
+    /// backend registered first.
+    pub fn find_best_backend(&self, module: &BackendModule, host_capabilities: Option<&[Capability]>) -> Option<&dyn Backend> {
+        let mut best: Option<(&dyn Backend, usize)> = None;
+        
+        for backend in &self.backends {
+            let backend = backend.as_ref();
+            if !backend.can_compile(module) || !self.capabilities_match(backend, &module.required_capabilities) {
+                continue;
+            }
+            if let Some(host) = host_capabilities {
+                if !self.available_on_host(backend, host) {
+                    continue;
+                }
+            }
+            
+            let score = backend.specificity();
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((backend, score));
+            }
+        }
+        
+        best.map(|(backend, _)| backend)
+    }
+
+    /// Why `backend` cannot compile `module`, naming the capabilities it
+    /// lacks or rules out, or None when it can
+    pub fn rejection(&self, backend: &dyn Backend, module: &BackendModule, host_capabilities: Option<&[Capability]>) -> Option<String> {
+        let offered = backend.supported_capabilities();
+        let mut reasons = Vec::new();
+        let missing: Vec<String> = module.required_capabilities.iter()
+            .filter(|cap| !offered.contains(cap))
+            .map(|cap| format!("{:?}", cap))
+            .collect();
+        if !missing.is_empty() {
+            reasons.push(format!("lacks {}", missing.join(", ")));
+        }
+        let excluded: Vec<String> = offered.iter()
+            .filter_map(|cap| cap.excluded_feature())
+            .filter(|feature| module.required_capabilities.contains(feature))
+            .map(|feature| format!("{:?}", feature))
+            .collect();
+        if !excluded.is_empty() {
+            reasons.push(format!("rules out {}", excluded.join(", ")));
+        }
+        if let Some(host) = host_capabilities {
+            let unavailable: Vec<String> = offered.iter()
+                .filter(|cap| cap.is_cpu_extension() && !host.contains(cap))
+                .map(|cap| format!("{:?}", cap))
+                .collect();
+            if !unavailable.is_empty() {
+                reasons.push(format!("needs {} the host lacks", unavailable.join(", ")));
+            }
+        }
+        (!reasons.is_empty()).then(|| reasons.join("; "))
     }
 
     fn capabilities_match(&self, backend: &dyn Backend, module_caps: &[Capability]) -> bool {
-        let backend_caps = backend.supported_capabilities();
-        module_caps.iter().all(|cap| backend_caps.contains(cap))
+        capabilities_compatible(&backend.supported_capabilities(), module_caps)
+    }
+    
+    fn available_on_host(&self, backend: &dyn Backend, host_caps: &[Capability]) -> bool {
+        backend.supported_capabilities().iter()
+            .filter(|cap| cap.is_cpu_extension())
+            .all(|cap| host_caps.contains(cap))
     }
     
     pub fn default_registry() -> Self {
         let mut registry = Self::new();
         
-        // Only register Linux64 backend
         registry.register(Box::new(Linux64Backend::new()));
+        registry.register(Box::new(RiscV64Backend::new()));
+        registry.register(Box::new(Aarch64LinuxBackend::new()));
         
         registry
     }
 }
 
+/// Integer argument registers of the System V AMD64 calling convention
+const SYSV_ARG_REGISTERS: [&str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+
+/// Builtins compiled inline that a user function of the same name could never be called over
+const LINUX64_RESERVED_NAMES: [&str; 8] = ["print", "asm", "write_register", "read_register", "dma_transfer", "port_in", "port_out", "main"];
+
+/// User functions are emitted under a prefix so they cannot collide with runtime labels
+fn mangle_function_name(name: &str) -> String {
+    format!("fn_{}", name)
+}
+
+/// Value of an integer literal, including negated ones like `-1`
+pub(crate) fn literal_integer(expr: &Expr) -> Option<i64> {
+    match expr {
+        Expr::Number(n, _) => Some(*n),
+        Expr::UnaryOp { op: UnaryOp::Minus, operand, .. } => literal_integer(operand)?.checked_neg(),
+        Expr::UnaryOp { op: UnaryOp::Plus, operand, .. } => literal_integer(operand),
+        _ => None,
+    }
+}
+
+/// Start, stop and step of the `range(...)` a for loop iterates, with defaults filled in
+fn range_arguments(iter: &Expr, span: Span) -> Result<(Expr, Expr, Expr), String> {
+    let args = match iter {
+        Expr::Call { func, args, .. } if func == "range" => args,
+        _ => return Err(format!("Only range() can be iterated at {}", span)),
+    };
+    let (start, stop, step) = match args.as_slice() {
+        [stop] => (Expr::Number(0, span), stop.clone(), Expr::Number(1, span)),
+        [start, stop] => (start.clone(), stop.clone(), Expr::Number(1, span)),
+        [start, stop, step] => (start.clone(), stop.clone(), step.clone()),
+        _ => return Err(format!("range() takes 1 to 3 arguments at {}", span)),
+    };
+    if literal_integer(&step) == Some(0) {
+        return Err(format!("range() step must not be zero at {}", span));
+    }
+    Ok((start, stop, step))
+}
+
+/// The `end=` print() was given, whose string replaces the closing newline
+pub(crate) fn print_end(kwargs: &HashMap<String, Expr>, span: Span) -> Result<Option<&Expr>, String> {
+    let mut unexpected: Vec<&String> = kwargs.keys().filter(|name| *name != "end").collect();
+    unexpected.sort();
+    match unexpected.first() {
+        Some(name) => Err(format!("print() got an unexpected keyword argument '{}' at {}", name, span)),
+        None => Ok(kwargs.get("end")),
+    }
+}
+
+/// `template` with each `{name}` replaced by `operand(name)`; `{{` and `}}` stand
+/// for literal braces, as AVX-512 masks need
+pub(crate) fn expand_inline_asm(template: &str, span: Span, mut operand: impl FnMut(&str) -> Result<String, String>) -> Result<String, String> {
+    let mut text = String::new();
+    let mut rest = template;
+    while let Some(at) = rest.find(['{', '}']) {
+        text.push_str(&rest[..at]);
+        let tail = &rest[at..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            text.push_str(&tail[..1]);
+            rest = &tail[2..];
+        } else if tail.starts_with('}') {
+            return Err(format!("Unmatched '}}' in asm(); write '}}}}' for a literal brace at {}", span));
+        } else {
+            let end = tail.find('}').ok_or_else(|| format!("Unclosed '{{' in asm(); write '{{{{' for a literal brace at {}", span))?;
+            text.push_str(&operand(tail[1..end].trim())?);
+            rest = &tail[end + 1..];
+        }
+    }
+    text.push_str(rest);
+    Ok(text)
+}
+
+/// The lines of `asm("...")` between comments marking them as written by the user.
+/// `location` gives where a variable lives, as an operand the instructions can read
+fn inline_asm(args: &[Expr], span: Span, comment: &str, location: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let [Expr::String(template, _)] = args else {
+        return Err(format!("asm() takes one string literal at {}", span));
+    };
+    let text = expand_inline_asm(template, span, |name| {
+        location(name).ok_or_else(|| format!("Undefined variable '{}' in asm() at {}", name, span))
+    })?;
+    let mut code = format!("    {} asm() from line {}, user-provided\n", comment, span.start.line);
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        code.push_str(&format!("    {}\n", line));
+    }
+    code.push_str(&format!("    {} end of asm()\n", comment));
+    Ok(code)
+}
+
+/// Only print() takes keyword arguments
+pub(crate) fn reject_kwargs(func: &str, kwargs: &HashMap<String, Expr>, span: Span) -> Result<(), String> {
+    let mut names: Vec<&String> = kwargs.keys().collect();
+    names.sort();
+    match names.first() {
+        Some(name) => Err(format!("{}() got an unexpected keyword argument '{}' at {}", func, name, span)),
+        None => Ok(()),
+    }
+}
+
+/// The fields of every top-level `struct`, by name. A struct is declared once,
+/// with distinct fields, and cannot share its name with a function
+pub(crate) fn struct_declarations(program: &Program) -> Result<HashMap<String, Vec<String>>, String> {
+    let mut structs: HashMap<String, (Vec<String>, Span)> = HashMap::new();
+    for stmt in &program.body {
+        let Statement::StructDef { name, fields, span } = stmt else { continue };
+        if let Some((_, first)) = structs.get(name) {
+            return Err(format!("Struct '{}' is already declared at {}; redeclared at {}", name, first, span));
+        }
+        if let Some((_, field)) = fields.iter().enumerate().find(|(at, field)| fields[..*at].contains(field)) {
+            return Err(format!("Struct '{}' declares field '{}' twice at {}", name, field, span));
+        }
+        if program.body.iter().any(|other| matches!(other, Statement::FunctionDef { name: function, .. } if function == name)) {
+            return Err(format!("'{}' names both a struct and a function at {}", name, span));
+        }
+        structs.insert(name.clone(), (fields.clone(), *span));
+    }
+    Ok(structs.into_iter().map(|(name, (fields, _))| (name, fields)).collect())
+}
+
+/// Index of `field` in the instances of struct `known`, or when which struct
+/// an expression holds is not known, the index every struct declaring `field` gives it
+fn field_index(structs: &HashMap<String, Vec<String>>, known: Option<&str>, field: &str, span: Span) -> Result<usize, String> {
+    let position = |fields: &Vec<String>| fields.iter().position(|name| name == field);
+    if let Some(name) = known {
+        return position(&structs[name]).ok_or_else(|| format!("Struct '{}' has no field '{}' at {}", name, field, span));
+    }
+    let mut declaring: Vec<(&String, usize)> = structs.iter()
+        .filter_map(|(name, fields)| position(fields).map(|index| (name, index)))
+        .collect();
+    declaring.sort();
+    let Some(&(first, index)) = declaring.first() else {
+        return Err(format!("No struct has a field '{}' at {}", field, span));
+    };
+    match declaring.iter().find(|(_, other)| *other != index) {
+        None => Ok(index),
+        Some((second, _)) => Err(format!(
+            "Field '{}' is at different positions in structs '{}' and '{}' and the struct read here is not known at {}",
+            field, first, second, span
+        )),
+    }
+}
+
+/// Reported when a range() step computed at runtime turns out to be zero
+const RANGE_STEP_ERROR: &str = "range() step must not be zero";
+
+/// String literals interned during a single `compile_program` call
+#[derive(Default, Clone)]
+struct StringPool {
+    indices: HashMap<String, usize>,
+    entries: Vec<(String, String)>, // (content, label) in first-use order
+}
+
+impl StringPool {
+    fn intern(&mut self, content: &str) -> String {
+        if let Some(&index) = self.indices.get(content) {
+            return self.entries[index].1.clone();
+        }
+        
+        // Labels derive from the content so rebuilding a program yields identical output
+        let base = format!("str_{:016x}", content.hash_code());
+        let mut label = base.clone();
+        let mut suffix = 1;
+        while self.entries.iter().any(|(_, existing)| *existing == label) {
+            label = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        self.indices.insert(content.to_string(), self.entries.len());
+        self.entries.push((content.to_string(), label.clone()));
+        label
+    }
+    
+    /// GNU as directives defining every interned string, NUL terminated.
+    /// `comment` is the target's line comment marker.
+    fn data_directives(&self, comment: &str) -> String {
+        let mut data = String::new();
+        for (content, label) in &self.entries {
+            // Emitted as raw bytes so quotes, newlines and UTF-8 need no escaping
+            let bytes: Vec<String> = content.bytes()
+                .chain(std::iter::once(0))
+                .map(|b| format!("0x{:02x}", b))
+                .collect();
+            data.push_str(&format!("{}:  {} {:?}\n", label, comment, content));
+            data.push_str(&format!("    .byte {}\n", bytes.join(", ")));
+        }
+        data
+    }
+}
+
+/// Number of a generated label. Each user function numbers its labels from
+/// zero in a scope of its own, its index in the program, so a function's code
+/// does not depend on how many labels the functions before it used, nor on
+/// which thread compiled it
+#[derive(Debug, Clone, Copy)]
+struct LabelId {
+    scope: Option<usize>,
+    id: u32,
+}
+
+impl std::fmt::Display for LabelId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.scope {
+            Some(scope) => write!(f, "f{}_{}", scope, self.id),
+            None => write!(f, "{}", self.id),
+        }
+    }
+}
+
+/// Annotated parameter types and the return type of a user function
+type FunctionTypes = (Vec<Option<String>>, Option<String>);
+
 pub struct Linux64Backend {
-    string_counter: RefCell<u32>,
-    string_literals: RefCell<HashMap<String, String>>,
+    strings: StringPool,
     symbol_table: RefCell<HashMap<String, VariableInfo>>,
     current_stack_offset: RefCell<i32>,
     label_counter: RefCell<u32>,
+    loop_labels: RefCell<Vec<(String, String)>>, // (continue target, break target) per enclosing loop
+    range_depth: RefCell<usize>, // for loops enclosing the code being allocated or compiled
+    pushed: RefCell<usize>, // temporaries the code being compiled has pushed and not yet popped
+    current_epilogue: RefCell<String>,
+    current_function: RefCell<Option<String>>, // user function being compiled, None in main
+    user_functions: RefCell<HashSet<String>>,
+    signatures: RefCell<HashMap<String, FunctionTypes>>, // parameter and return types the type checker resolved
+    structs: RefCell<HashMap<String, Vec<String>>>, // fields of each declared struct
+    module_symbols: Option<HashSet<String>>, // labels the linked modules define, the built-in ones' when None
     hardware_dsl: RefCell<Option<HardwareDSL>>, // Changed to RefCell<Option<HardwareDSL>>
+    bios_graphics: Option<(crate::framebuffer::Framebuffer, crate::framebuffer::SimdLevel)>,
+    memory: crate::framebuffer::MemoryLayout, // where --bios-mode code finds its heap
+    embedded_images: Vec<crate::image::EmbeddedImage>,
+    debug_serial: bool,
+    refcounting: bool,
+    counts_references: bool, // refcounting, and the program being compiled allocates
+    stack_allocation: bool, // list literals escape analysis keeps in the frame skip the heap
+    stack_lists: RefCell<HashMap<String, i32>>, // frame offset of each such list in the current frame
+    register_allocation: bool, // integer expressions are evaluated in scratch registers instead of pushed
+    frame_elision: bool, // leaf functions skip the frame, tail calls reuse it
+    logger: crate::logging::Logger, // reports each function compiled
+    jobs: usize, // threads compiling user functions
+    label_scope: Option<usize>, // index of the user function being compiled, which its labels carry
+    debug_file: Option<String>, // source file named in the DWARF line table
 }
 
 impl Linux64Backend {
     pub fn new() -> Self {
         Self {
-            string_counter: RefCell::new(0),
-            string_literals: RefCell::new(HashMap::new()),
+            strings: StringPool::default(),
             symbol_table: RefCell::new(HashMap::new()),
             current_stack_offset: RefCell::new(0),
             label_counter: RefCell::new(0),
+            loop_labels: RefCell::new(Vec::new()),
+            range_depth: RefCell::new(0),
+            pushed: RefCell::new(0),
+            current_epilogue: RefCell::new(String::from(".main_epilogue")),
+            current_function: RefCell::new(None),
+            user_functions: RefCell::new(HashSet::new()),
+            signatures: RefCell::new(HashMap::new()),
+            structs: RefCell::new(HashMap::new()),
+            module_symbols: None,
             hardware_dsl: RefCell::new(None), // Initialize as None in RefCell
+            bios_graphics: None,
+            memory: crate::framebuffer::MemoryLayout::default(),
+            embedded_images: Vec::new(),
+            debug_serial: false,
+            refcounting: true,
+            counts_references: false,
+            stack_allocation: false,
+            register_allocation: false,
+            frame_elision: false,
+            logger: crate::logging::Logger::default(),
+            stack_lists: RefCell::new(HashMap::new()),
+            jobs: 1,
+            label_scope: None,
+            debug_file: None,
         }
     }
 
@@ -505,6 +914,384 @@ impl Linux64Backend {
         self
     }
 
+    /// Compile a freestanding payload for `--bios-mode`: no syscalls, a halt loop
+    /// instead of exit, and the fb_* builtins drawing into the framebuffer
+    pub fn with_bios_graphics(mut self, framebuffer: crate::framebuffer::Framebuffer, simd: crate::framebuffer::SimdLevel) -> Self {
+        self.bios_graphics = Some((framebuffer, simd));
+        self
+    }
+
+    /// Heap bounds of a `--bios-mode` payload
+    pub fn with_memory_layout(mut self, memory: crate::framebuffer::MemoryLayout) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    /// Images `image(name, x, y)` can draw in `--bios-mode`
+    pub fn with_embedded_images(mut self, images: Vec<crate::image::EmbeddedImage>) -> Self {
+        self.embedded_images = images;
+        self
+    }
+
+    /// Send print() output of a `--bios-mode` payload to COM1
+    pub fn with_debug_serial(mut self, enabled: bool) -> Self {
+        self.debug_serial = enabled;
+        self
+    }
+
+    /// Count references to heap strings, lists and dictionaries and free them
+    /// once no variable holds them any more (the default); `--no-rc` leaks
+    /// instead, for payloads where code size matters more
+    pub fn with_refcounting(mut self, enabled: bool) -> Self {
+        self.refcounting = enabled;
+        self
+    }
+
+    /// Build the list literals that cannot outlive their function, as
+    /// `escape::stack_lists` finds them, in its frame instead of on the heap
+    pub fn with_stack_allocation(mut self, enabled: bool) -> Self {
+        self.stack_allocation = enabled;
+        self
+    }
+
+    /// Evaluate integer expression trees in scratch registers, as
+    /// `regalloc::compile_expression` lays them out, instead of pushing
+    /// every left operand
+    pub fn with_register_allocation(mut self, enabled: bool) -> Self {
+        self.register_allocation = enabled;
+        self
+    }
+
+    /// Let leaf functions with no stack slots skip the frame setup, and
+    /// compile `return f(x)` ending a function as a jump reusing the frame.
+    /// Ignored with debug info, whose frames must stay walkable
+    pub fn with_frame_elision(mut self, enabled: bool) -> Self {
+        self.frame_elision = enabled;
+        self
+    }
+
+    /// Report the code generation of each function to `logger` as detail
+    pub fn with_logger(mut self, logger: crate::logging::Logger) -> Self {
+        self.logger = logger;
+        self
+    }
+
+    /// Emit `.loc` directives mapping each statement's code to its line in
+    /// `file`, from which the assembler builds a DWARF `.debug_line` table
+    pub fn with_debug_info(mut self, file: &str) -> Self {
+        self.debug_file = Some(file.to_string());
+        self
+    }
+
+    /// Marker before a statement's code, and its line table entry with debug info
+    fn statement_marker(&self, stmt: &Statement) -> String {
+        let mut marker = crate::size::statement_marker("#", stmt);
+        if self.debug_file.is_some() && !marker.is_empty() {
+            marker.push_str(&format!("    .loc 1 {}\n", stmt.span().start.line));
+        }
+        marker
+    }
+
+    /// Marker after a body's last statement; with debug info, the code that
+    /// follows maps to no line
+    fn body_end_marker(&self) -> String {
+        let mut marker = crate::size::body_end_marker("#");
+        if self.debug_file.is_some() {
+            marker.push_str("    .loc 1 0\n");
+        }
+        marker
+    }
+
+    /// Compile user functions on up to `jobs` threads. The output is the same as
+    /// with one, which is the default
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Names a call may use besides the program's own functions: the labels
+    /// of the modules the program is linked with
+    pub fn with_module_symbols(mut self, symbols: HashSet<String>) -> Self {
+        self.module_symbols = Some(symbols);
+        self
+    }
+
+    /// A backend for a worker thread: this one's settings, with fresh state
+    fn worker(&self) -> Self {
+        Self {
+            user_functions: self.user_functions.clone(),
+            signatures: self.signatures.clone(),
+            structs: self.structs.clone(),
+            module_symbols: self.module_symbols.clone(),
+            hardware_dsl: self.hardware_dsl.clone(),
+            bios_graphics: self.bios_graphics,
+            memory: self.memory,
+            embedded_images: self.embedded_images.clone(),
+            debug_serial: self.debug_serial,
+            refcounting: self.refcounting,
+            counts_references: self.counts_references,
+            stack_allocation: self.stack_allocation,
+            register_allocation: self.register_allocation,
+            frame_elision: self.frame_elision,
+            logger: self.logger.clone(),
+            debug_file: self.debug_file.clone(),
+            ..Self::new()
+        }
+    }
+
+    /// Compile the user functions in program order, on worker threads when
+    /// `jobs` allows. Labels are scoped by function, so workers need no
+    /// renumbering. Each worker interns into a pool of its own; its code is
+    /// kept when the pool merged in program order gives every literal the
+    /// label the worker used, which only a hash collision between literals
+    /// of different functions prevents. Such a function is compiled again
+    /// against the merged pool, as a serial compile would
+    fn compile_functions(&mut self, functions: &[(&String, &Vec<String>, &Vec<Statement>, Span)], program: &Program) -> Result<String, String> {
+        // Hardware intrinsics update the DSL's state as they are compiled
+        let intrinsics = self.hardware_dsl.borrow().is_some()
+            && crate::extension::program_calls(program).iter()
+                .any(|func| ["write_register", "read_register", "dma_transfer", "port_in", "port_out"].contains(&func.as_str()));
+        let workers = self.jobs.min(functions.len());
+        let top_level_labels = *self.label_counter.borrow();
+        let mut asm = String::new();
+        if workers < 2 || intrinsics {
+            for (index, function) in functions.iter().enumerate() {
+                asm.push_str(&self.compile_scoped_function(index, function)?);
+            }
+        } else {
+            let per_worker = functions.len().div_ceil(workers);
+            let compiled: Vec<Result<(String, StringPool), String>> = std::thread::scope(|scope| {
+                let handles: Vec<_> = functions.chunks(per_worker).enumerate()
+                    .map(|(chunk_index, chunk)| {
+                        let mut worker = self.worker();
+                        scope.spawn(move || {
+                            chunk.iter().enumerate().map(|(offset, function)| {
+                                worker.strings = StringPool::default();
+                                let asm = worker.compile_scoped_function(chunk_index * per_worker + offset, function)?;
+                                Ok((asm, std::mem::take(&mut worker.strings)))
+                            }).collect::<Vec<_>>()
+                        })
+                    })
+                    .collect();
+                handles.into_iter().flat_map(|handle| handle.join().expect("function compiler thread panicked")).collect()
+            });
+
+            for (index, result) in compiled.into_iter().enumerate() {
+                let (function, strings) = result?;
+                let mut merged = self.strings.clone();
+                if strings.entries.iter().all(|(content, label)| merged.intern(content) == *label) {
+                    self.strings = merged;
+                    asm.push_str(&function);
+                } else {
+                    asm.push_str(&self.compile_scoped_function(index, &functions[index])?);
+                }
+            }
+        }
+        self.label_scope = None;
+        self.label_counter.replace(top_level_labels);
+        Ok(asm)
+    }
+
+    /// Compile the `index`th user function with labels of its own
+    fn compile_scoped_function(&mut self, index: usize, (name, args, body, span): &(&String, &Vec<String>, &Vec<Statement>, Span)) -> Result<String, String> {
+        self.label_scope = Some(index);
+        self.label_counter.replace(0);
+        self.compile_function(name, args, body, *span)
+    }
+
+    /// Whether stores and function returns count references: only with
+    /// refcounting on and something in the program handing out heap objects
+    fn wants_refcounting(&self, program: &Program) -> bool {
+        let calls = crate::extension::program_calls(program);
+        let defined = |func: &str| program.body.iter()
+            .any(|stmt| matches!(stmt, Statement::FunctionDef { name, .. } if name == func));
+        self.refcounting
+            && crate::extension::ALLOCATING_BUILTINS.iter().any(|func| calls.contains(*func) && !defined(func))
+    }
+
+    /// Zero the locals of a new frame so the first store and the epilogue never
+    /// drop a reference the variable did not hold
+    fn clear_frame(&self, stack_space: i32) -> String {
+        if !self.counts_references {
+            return String::new();
+        }
+        (1..=stack_space / 8)
+            .map(|slot| format!("    mov QWORD PTR [rbp - {}], 0\n", slot * 8))
+            .collect()
+    }
+
+    /// Whether the program needs the IDT: BIOS mode programs calling ticks(),
+    /// key_pressed() or the interrupts module's routines
+    fn uses_interrupts(&self, program: &Program) -> bool {
+        use crate::extension::EarthngModule;
+        let calls = crate::extension::program_calls(program);
+        let defined = |func: &str| program.body.iter()
+            .any(|stmt| matches!(stmt, Statement::FunctionDef { name, .. } if name == func));
+        let interrupts = crate::extension::InterruptsModule::new();
+        self.bios_graphics.is_some()
+            && crate::extension::INTERRUPT_BUILTINS.iter().copied().chain(interrupts.functions())
+                .any(|func| calls.contains(func) && !defined(func))
+    }
+
+    /// Whether print() writes to COM1 instead of stdout
+    fn prints_to_serial(&self) -> bool {
+        self.bios_graphics.is_some() && self.debug_serial
+    }
+
+    /// input(prompt): print the prompt, then read a line into a fresh heap block.
+    /// BIOS mode reads the PS/2 keyboard and echoes to the framebuffer console,
+    /// or to COM1 under --debug-serial; elsewhere the line comes from stdin
+    fn compile_input(&mut self, args: &[Expr], span: Span) -> Result<String, String> {
+        if args.len() > 1 {
+            return Err(format!("input() takes at most 1 argument but {} were given at {}", args.len(), span));
+        }
+        let echo = match self.bios_graphics {
+            None => "print_string",
+            Some(_) if self.debug_serial => "serial_write_string_64",
+            Some(_) => "fb_print",
+        };
+        let mut code = String::from("    # input()\n");
+        if let Some(prompt) = args.first() {
+            if !self.is_string_expr(prompt) {
+                return Err(format!("input() prompts must be strings at {}", span));
+            }
+            code.push_str(&self.compile_expression(prompt)?);
+            code.push_str("    mov rdi, rax\n");
+            code.push_str(&format!("    call {}\n", echo));
+        }
+        if self.bios_graphics.is_none() {
+            code.push_str("    call io_read_line_64\n");
+            return Ok(code);
+        }
+        code.push_str(&format!("    mov edi, {}\n", crate::extension::INPUT_LINE_BYTES));
+        code.push_str("    call heap_alloc_64\n");
+        code.push_str("    mov rdi, rax\n");
+        code.push_str(&format!("    mov esi, {}\n", crate::extension::INPUT_LINE_BYTES));
+        code.push_str(&format!("    lea rdx, [{}]\n", echo));
+        code.push_str("    call kbd_read_line_64\n");
+        Ok(code)
+    }
+
+    /// disk_read(lba, count[, drive]) returns a heap buffer of the sectors, or 0 when
+    /// the drive fails (NUL terminated, so a `str` variable prints it); disk_write(lba, buffer[, drive]) writes one back and returns
+    /// the ATA status code. The drive is 0 for the master (default) or 1 for the slave
+    fn compile_disk(&mut self, func: &str, args: &[Expr], span: Span) -> Result<String, String> {
+        if self.bios_graphics.is_none() {
+            return Err(format!("{}() drives the ATA controller and needs --bios-mode at {}", func, span));
+        }
+        if !(2..=3).contains(&args.len()) {
+            return Err(format!("{}() takes 2 or 3 arguments but {} were given at {}", func, args.len(), span));
+        }
+        if let Some(Expr::Number(count, _)) = args.get(1).filter(|_| func == "disk_read") {
+            if !(1..=255).contains(count) {
+                return Err(format!("disk_read() reads 1 to 255 sectors, not {} at {}", count, span));
+            }
+        }
+        let mut args = args.to_vec();
+        if args.len() == 2 {
+            args.push(Expr::Number(0, span));
+        }
+        self.compile_call(&format!("{}_64", func), &args)
+    }
+
+    /// open(path, flags), read(fd, n), write(fd, s) and close(fd) on Linux file
+    /// descriptors. open() takes O_* flags or one of Python's mode strings;
+    /// read() returns a string, the others the syscall's result or -errno
+    fn compile_os_call(&mut self, func: &str, args: &[Expr], span: Span) -> Result<String, String> {
+        if self.bios_graphics.is_some() {
+            return Err(format!("{}() uses Linux file descriptors and is not available with --bios-mode at {}", func, span));
+        }
+        let arity = if func == "close" { 1 } else { 2 };
+        if args.len() != arity {
+            return Err(format!("{}() takes {} argument{} but {} were given at {}", func, arity, if arity == 1 { "" } else { "s" }, args.len(), span));
+        }
+        match func {
+            "open" => {
+                if !self.is_string_expr(&args[0]) {
+                    return Err(format!("open() paths must be strings at {}", span));
+                }
+                let flags = match &args[1] {
+                    Expr::String(mode, _) => crate::extension::open_flags(mode)
+                        .map(|flags| Expr::Number(flags, span))
+                        .ok_or_else(|| format!("Unknown open() mode {:?} at {}", mode, span))?,
+                    flags if self.is_string_expr(flags) => {
+                        return Err(format!("open() modes must be string literals at {}", span));
+                    }
+                    flags => flags.clone(),
+                };
+                self.compile_call("sys_open_64", &[args[0].clone(), flags, Expr::Number(crate::extension::OPEN_CREATE_MODE, span)])
+            }
+            "read" => self.compile_call("os_read_string_64", args),
+            "write" => {
+                if !self.is_string_expr(&args[1]) {
+                    return Err(format!("write() takes a string to write at {}", span));
+                }
+                self.compile_call("os_write_string_64", args)
+            }
+            _ => self.compile_call("sys_close_64", args),
+        }
+    }
+
+    /// replace(s, old, new) returns a fresh string with every occurrence of old
+    /// replaced; split(s, sep) returns a list of fresh strings
+    fn compile_string_call(&mut self, func: &str, args: &[Expr], span: Span) -> Result<String, String> {
+        let arity = if func == "replace" { 3 } else { 2 };
+        if args.len() != arity {
+            return Err(format!("{}() takes {} arguments but {} were given at {}", func, arity, args.len(), span));
+        }
+        if args.iter().any(|arg| matches!(arg, Expr::Number(..) | Expr::Float(..) | Expr::Boolean(..))) {
+            return Err(format!("{}() takes strings at {}", func, span));
+        }
+        self.compile_call(if func == "replace" { "str_replace_64" } else { "str_split_64" }, args)
+    }
+
+    /// `s.method(args)` calls the string module routine for the method with
+    /// the string and the arguments
+    fn compile_method_call(&mut self, receiver: &Expr, method: &str, args: &[Expr], span: Span) -> Result<String, String> {
+        let (arity, routine) = crate::extension::string_method(method)
+            .ok_or_else(|| crate::extension::unknown_string_method(method, span))?;
+        let not_string = matches!(receiver, Expr::Number(..) | Expr::Float(..) | Expr::Boolean(..) | Expr::None(_) | Expr::List { .. })
+            || self.is_dict_expr(receiver)
+            || self.struct_of(receiver).is_some();
+        if not_string {
+            return Err(format!("Only strings have methods such as {}() at {}", method, span));
+        }
+        if args.len() != arity {
+            return Err(format!("str.{}() takes {} argument{} but {} were given at {}", method, arity, if arity == 1 { "" } else { "s" }, args.len(), span));
+        }
+        if args.iter().any(|arg| matches!(arg, Expr::Number(..) | Expr::Float(..) | Expr::Boolean(..))) {
+            return Err(format!("str.{}() takes strings at {}", method, span));
+        }
+        // The list module is not linked into payloads
+        if routine == "str_split_64" && self.bios_graphics.is_some() {
+            return Err(format!("str.split() builds a list, which --bios-mode programs cannot use at {}", span));
+        }
+        let mut code = format!("    # str.{}()\n", method);
+        let operands: Vec<Expr> = std::iter::once(receiver.clone()).chain(args.iter().cloned()).collect();
+        code.push_str(&self.compile_call(routine, &operands)?);
+        Ok(code)
+    }
+
+    /// Blit an embedded image: its pixels, then x, y and its size in fb_blit's registers
+    fn compile_image(&mut self, args: &[Expr], span: Span) -> Result<String, String> {
+        let index = crate::framebuffer::image_index(args, &self.embedded_images, span)?;
+        let (name, width, height) = {
+            let image = &self.embedded_images[index];
+            (image.name.clone(), image.width, image.height)
+        };
+        let mut code = format!("    # image({:?})\n", name);
+        code.push_str(&self.compile_expression(&args[2])?);
+        code.push_str(&self.push("rax"));
+        code.push_str(&self.compile_expression(&args[1])?);
+        code.push_str("    mov rsi, rax\n");
+        code.push_str(&self.pop("rdx"));
+        code.push_str(&format!("    lea rdi, [fb_image_{}]\n", index));
+        code.push_str(&format!("    mov ecx, {}\n", width));
+        code.push_str(&format!("    mov r8d, {}\n", height));
+        code.push_str("    call fb_blit\n");
+        Ok(code)
+    }
+
     // RBP-RELATIVE ADDRESSING (FIXED VERSION)
     fn allocate_variable_rbp_relative(&self, name: &str) -> i32 {
         let mut offset = self.current_stack_offset.borrow_mut();
@@ -523,78 +1310,830 @@ impl Linux64Backend {
     }
     
     fn ensure_variable_exists_rbp_relative(&self, name: &str) -> i32 {
-        if let Some(var_info) = self.symbol_table.borrow().get(name) {
-            var_info.offset
-        } else {
-            self.allocate_variable_rbp_relative(name)
-        }
+        let existing = self.get_variable_offset_rbp_relative(name);
+        existing.unwrap_or_else(|| self.allocate_variable_rbp_relative(name))
     }
     
     fn get_variable_offset_rbp_relative(&self, name: &str) -> Option<i32> {
         self.symbol_table.borrow().get(name).map(|v| v.offset)
     }
     
-    fn get_absolute_offset(&self, offset: i32) -> i32 {
-        if offset < 0 { -offset } else { offset }
+    fn set_variable_type(&self, name: &str, type_hint: &str) {
+        if let Some(var_info) = self.symbol_table.borrow_mut().get_mut(name) {
+            var_info.type_hint = Some(type_hint.to_string());
+        }
+    }
+    
+    /// The type a call to a user function returns, when the type checker found one
+    fn return_type(&self, expr: &Expr) -> Option<String> {
+        let Expr::Call { func, .. } = expr else { return None };
+        self.signatures.borrow().get(func)?.1.clone()
     }
     
-    fn get_string_label(&self, content: &str) -> String {
-        let mut literals = self.string_literals.borrow_mut();
-        if let Some(label) = literals.get(content) {
-            return label.clone();
+    fn is_string_expr(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::String(_, _) | Expr::FString { .. } | Expr::Slice { .. } => true,
+            Expr::Index { value, .. } => self.is_string_expr(value),
+            Expr::MethodCall { method, .. } => !matches!(method.as_str(), "find" | "split"),
+            Expr::Call { func, .. } if func == "input" || func == "read" || func == "replace" => !self.user_functions.borrow().contains(func),
+            Expr::Call { .. } => self.return_type(expr).as_deref() == Some("str"),
+            Expr::Var(name, _) => self.symbol_table.borrow().get(name)
+                .is_some_and(|v| v.type_hint.as_deref() == Some("str")),
+            _ => false,
         }
-        
-        let mut counter = self.string_counter.borrow_mut();
-        let label = format!("str_{}", *counter);
-        *counter += 1;
-        literals.insert(content.to_string(), label.clone());
-        label
     }
     
-    fn generate_string_data(&self) -> String {
-    let literals = self.string_literals.borrow();
-    let mut data = String::new();
-    for (content, label) in &*literals {
-        data.push_str(&format!("{}:\n", label));
-        data.push_str(&format!("    .asciz \"{}\"\n", content.replace("\"", "\\\"")));
+    fn is_numeric_expr(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Number(_, _) | Expr::Float(_, _) | Expr::Boolean(_, _) | Expr::BinOp { .. } => true,
+            Expr::Var(_, _) => !self.is_string_expr(expr),
+            _ => false,
+        }
     }
-    data
-}
     
-    fn get_next_label_id(&self) -> u32 {
-        let mut counter = self.label_counter.borrow_mut();
-        let id = *counter;
-        *counter += 1;
-        id
+    /// Whether `expr` evaluates to a double; any float operand promotes the whole operation
+    fn is_float_expr(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Float(_, _) => true,
+            Expr::Var(name, _) => self.symbol_table.borrow().get(name)
+                .is_some_and(|v| v.type_hint.as_deref() == Some("float")),
+            Expr::BinOp { left, right, .. } => self.is_float_expr(left) || self.is_float_expr(right),
+            Expr::UnaryOp { op: UnaryOp::Plus | UnaryOp::Minus, operand, .. } => self.is_float_expr(operand),
+            Expr::Call { .. } => self.return_type(expr).as_deref() == Some("float"),
+            _ => false,
+        }
     }
     
-    fn generate_helper_function(&self) -> String {
-    let mut helpers = String::new();
+    fn is_dict_expr(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Dict { .. } => true,
+            Expr::Call { .. } => self.return_type(expr).as_deref() == Some("dict"),
+            Expr::Var(name, _) => self.symbol_table.borrow().get(name)
+                .is_some_and(|v| v.type_hint.as_deref() == Some("dict")),
+            _ => false,
+        }
+    }
     
-    helpers.push_str("print_string:\n");
-    helpers.push_str("    push rax\n");
-    helpers.push_str("    push rdi\n");
-    helpers.push_str("    push rsi\n");
-    helpers.push_str("    push rdx\n");
-    helpers.push_str("    #\n");
-    helpers.push_str("    mov rsi, rdi\n");
-    helpers.push_str("    xor rdx, rdx\n");
-    helpers.push_str(".count_loop:\n");
-    helpers.push_str("    cmp BYTE PTR [rsi + rdx], 0\n");
-    helpers.push_str("    je .count_done\n");
-    helpers.push_str("    inc rdx\n");
-    helpers.push_str("    jmp .count_loop\n");
-    helpers.push_str(".count_done:\n");
-    helpers.push_str("    #\n");
-    helpers.push_str("    mov rax, 1\n");
-    helpers.push_str("    mov rdi, 1\n");
-    helpers.push_str("    syscall\n");
-    helpers.push_str("    #\n");
-    helpers.push_str("    pop rdx\n");
-    helpers.push_str("    pop rsi\n");
-    helpers.push_str("    pop rdi\n");
-    helpers.push_str("    pop rax\n");
-    helpers.push_str("    ret\n\n");
+    /// The struct `expr` is an instance of, when that is known here: a
+    /// constructor call, a function returning one, or a variable last assigned
+    /// from either
+    fn struct_of(&self, expr: &Expr) -> Option<String> {
+        let name = match expr {
+            Expr::Call { func, .. } => self.return_type(expr).unwrap_or_else(|| func.clone()),
+            Expr::Var(name, _) => self.symbol_table.borrow().get(name)?.type_hint.clone()?,
+            _ => return None,
+        };
+        self.structs.borrow().contains_key(&name).then_some(name)
+    }
+    
+    fn field_index(&self, object: &Expr, field: &str, span: Span) -> Result<usize, String> {
+        field_index(&self.structs.borrow(), self.struct_of(object).as_deref(), field, span)
+    }
+    
+    /// `Name(args)`: the fields in a fresh heap block, 8 bytes each in declaration order
+    fn compile_struct(&mut self, name: &str, args: &[Expr], span: Span) -> Result<String, String> {
+        let fields = self.structs.borrow()[name].len();
+        if args.len() != fields {
+            return Err(format!("{}() takes {} argument{} but {} were given at {}", name, fields, if fields == 1 { "" } else { "s" }, args.len(), span));
+        }
+        let mut code = format!("    # Struct {}\n", name);
+        code.push_str(&format!("    mov edi, {}\n", 8 * fields));
+        code.push_str("    call heap_alloc_64\n");
+        if self.counts_references {
+            // Releasing the struct drops what its fields hold
+            code.push_str("    mov BYTE PTR [rax - 15], 3  # kind: struct\n");
+            code.push_str(&format!("    mov WORD PTR [rax - 14], {}  # fields\n", fields));
+        }
+        code.push_str(&self.push("rax"));
+        for (index, arg) in args.iter().enumerate() {
+            code.push_str(&self.compile_expression(arg)?);
+            if self.counts_references {
+                code.push_str("    mov rdi, rax\n");
+                code.push_str("    call __rc_inc\n");
+            }
+            code.push_str("    mov rdi, QWORD PTR [rsp]\n");
+            code.push_str(&format!("    mov QWORD PTR [rdi + {}], rax\n", 8 * index));
+        }
+        code.push_str(&self.pop("rax"));
+        Ok(code)
+    }
+    
+    /// Compile `expr` leaving the bits of a double in rax, converting integers
+    fn compile_as_float(&mut self, expr: &Expr) -> Result<String, String> {
+        let mut code = self.compile_expression(expr)?;
+        if !self.is_float_expr(expr) {
+            code.push_str("    cvtsi2sd xmm0, rax\n");
+            code.push_str("    movq rax, xmm0\n");
+        }
+        Ok(code)
+    }
+    
+    /// Compile `expr` leaving a value in rax that is nonzero exactly when it is truthy:
+    /// non-empty strings and nonzero numbers, with -0.0 counting as zero
+    fn compile_truthiness(&mut self, expr: &Expr) -> Result<String, String> {
+        let mut code = self.compile_expression(expr)?;
+        if self.is_string_expr(expr) {
+            code.push_str("    movzx rax, BYTE PTR [rax]\n");
+        } else if self.is_float_expr(expr) {
+            code.push_str("    shl rax, 1\n");
+        }
+        Ok(code)
+    }
+    
+    /// General function call: System V argument registers, then `call`
+    fn compile_call(&mut self, func: &str, args: &[Expr]) -> Result<String, String> {
+        let mut code = String::new();
+        
+        code.push_str(&format!("    # Function call: {}\n", func));
+        // An odd number of slots on the stack, counting the temporaries of
+        // the expression this call sits in, would leave rsp off the 16-byte
+        // boundary the ABI wants at the call, so pad above the arguments
+        let stack_args = args.len().saturating_sub(SYSV_ARG_REGISTERS.len());
+        let padding = if (*self.pushed.borrow() + stack_args) % 2 == 1 { 8 } else { 0 };
+        if padding > 0 {
+            code.push_str("    sub rsp, 8\n");
+        }
+        *self.pushed.borrow_mut() += padding / 8;
+        code.push_str(&self.compile_arguments(func, args)?);
+        
+        // Call the function
+        let label = if self.user_functions.borrow().contains(func) {
+            mangle_function_name(func)
+        } else {
+            func.to_string()
+        };
+        code.push_str(&format!("    call {}\n", label));
+        
+        // Clean up stack arguments and the padding
+        if stack_args + padding > 0 {
+            code.push_str(&format!("    add rsp, {}\n", stack_args * 8 + padding));
+        }
+        *self.pushed.borrow_mut() -= stack_args + padding / 8;
+        
+        Ok(code)
+    }
+    
+    /// Push `register` as a temporary that calls compiled before the
+    /// matching `pop` must realign around
+    fn push(&self, register: &str) -> String {
+        *self.pushed.borrow_mut() += 1;
+        format!("    push {}\n", register)
+    }
+    
+    fn pop(&self, register: &str) -> String {
+        *self.pushed.borrow_mut() -= 1;
+        format!("    pop {}\n", register)
+    }
+    
+    /// Place `args` of a call to `func` as the System V convention passes them
+    fn compile_arguments(&mut self, func: &str, args: &[Expr]) -> Result<String, String> {
+        let mut code = String::new();
+        
+        // Evaluate right-to-left onto the stack so later arguments
+        // cannot clobber registers already holding earlier ones
+        for (index, arg) in args.iter().enumerate().rev() {
+            let arg_code = self.compile_argument(func, index, arg)?;
+            code.push_str(&arg_code);
+            code.push_str(&self.push("rax"));
+        }
+        
+        // First six arguments go in registers (System V ABI), the rest stay on the stack
+        for reg in SYSV_ARG_REGISTERS.iter().take(args.len()) {
+            code.push_str(&self.pop(reg));
+        }
+        
+        Ok(code)
+    }
+    
+    /// Argument `index` of a call to `func`. Floats travel as their bits in
+    /// integer registers, so a user function must declare the parameter
+    /// float to know it holds one; an int passed there is converted
+    fn compile_argument(&mut self, func: &str, index: usize, arg: &Expr) -> Result<String, String> {
+        if !self.user_functions.borrow().contains(func) {
+            return self.compile_expression(arg);
+        }
+        let declared = self.signatures.borrow().get(func).and_then(|(arg_types, _)| arg_types.get(index).cloned().flatten());
+        if declared.as_deref() == Some("float") {
+            self.compile_as_float(arg)
+        } else if self.is_float_expr(arg) {
+            Err(format!("Argument {} of '{}' is a float, which needs a parameter declared float at {}", index + 1, func, arg.span()))
+        } else {
+            self.compile_expression(arg)
+        }
+    }
+    
+    /// Whether the user function being compiled returns a float
+    fn returns_float(&self) -> bool {
+        let function = self.current_function.borrow();
+        function.as_ref().and_then(|name| self.signatures.borrow().get(name)?.1.clone()).as_deref() == Some("float")
+    }
+    
+    /// What `return expr` leaves in rax: for the same reason as arguments, a
+    /// float only when the function's return type says so
+    fn compile_return_value(&mut self, expr: &Expr, span: Span) -> Result<String, String> {
+        if self.returns_float() {
+            return self.compile_as_float(expr);
+        }
+        if let Some(name) = self.current_function.borrow().as_deref().filter(|_| self.is_float_expr(expr)) {
+            return Err(format!("'{}' returns a float, which needs a float return type at {}", name, span));
+        }
+        self.compile_expression(expr)
+    }
+    
+    /// `return func(args)` ending a function body: the arguments are placed,
+    /// the frame torn down and the callee jumped to, returning straight to
+    /// our caller. None when the call cannot reuse the frame: with frame
+    /// elision off, a callee that is not a user function, arguments on the
+    /// stack, or an epilogue with references to drop
+    fn compile_tail_call(&mut self, stmt: &Statement) -> Result<Option<String>, String> {
+        let Statement::Return(Some(call @ Expr::Call { func, args, kwargs, .. }), _) = stmt else { return Ok(None) };
+        if !self.elides_frames() || !kwargs.is_empty() || args.len() > SYSV_ARG_REGISTERS.len()
+            || !self.user_functions.borrow().contains(func) {
+            return Ok(None);
+        }
+        // The callee's result is ours untouched, so it must be a float exactly when ours is
+        if self.is_float_expr(call) != self.returns_float() {
+            return Ok(None);
+        }
+        let mut code = format!("    # Tail call: {}\n", func);
+        code.push_str(&self.compile_arguments(func, args)?);
+        code.push_str("    mov rsp, rbp\n");
+        code.push_str("    pop rbp\n");
+        code.push_str(&format!("    jmp {}\n", mangle_function_name(func)));
+        Ok(Some(code))
+    }
+    
+    /// Whether functions may skip or reuse their frames. Debug info keeps
+    /// every frame walkable, and counted references are dropped in the epilogue
+    fn elides_frames(&self) -> bool {
+        self.frame_elision && self.debug_file.is_none() && !self.counts_references
+    }
+    
+    /// Lower `and`/`or` to a 0/1 result, skipping the remaining operands
+    /// as soon as one decides the outcome
+    fn compile_bool_op(&mut self, op: &BoolOp, values: &[Expr]) -> Result<String, String> {
+        let label_id = self.get_next_label_id();
+        let short_label = format!("bool_short_{}", label_id);
+        let end_label = format!("bool_end_{}", label_id);
+        
+        // `and` stops at the first false operand, `or` at the first true one
+        let (skip_jump, short_value, full_value) = match op {
+            BoolOp::And => ("jz", 0, 1),
+            BoolOp::Or => ("jnz", 1, 0),
+        };
+        
+        let mut code = format!("    # Short-circuit {:?}\n", op);
+        for value in values {
+            code.push_str(&self.compile_truthiness(value)?);
+            code.push_str("    test rax, rax\n");
+            code.push_str(&format!("    {} {}\n", skip_jump, short_label));
+        }
+        code.push_str(&format!("    mov rax, {}\n", full_value));
+        code.push_str(&format!("    jmp {}\n", end_label));
+        code.push_str(&format!("{}:\n", short_label));
+        code.push_str(&format!("    mov rax, {}\n", short_value));
+        code.push_str(&format!("{}:\n", end_label));
+        Ok(code)
+    }
+    
+    /// Store `value` into the slot of `name`, promoting it if the variable holds floats
+    fn compile_store(&mut self, name: &str, value: &Expr) -> Result<String, String> {
+        let offset = self.ensure_variable_exists_rbp_relative(name);
+        let is_float_var = self.symbol_table.borrow().get(name)
+            .is_some_and(|v| v.type_hint.as_deref() == Some("float"));
+        let stack_list = self.stack_lists.borrow().get(name).copied();
+        let mut code = match (stack_list, value) {
+            (Some(block), Expr::List { elements, .. }) => self.compile_stack_list(elements, block)?,
+            _ if is_float_var => self.compile_as_float(value)?,
+            _ => self.compile_expression(value)?,
+        };
+        let abs_offset = self.get_absolute_offset(offset);
+        if self.counts_references && !is_float_var {
+            // Count the new value before dropping the old one, which may be the same object
+            if !matches!(value, Expr::Number(..) | Expr::Float(..) | Expr::Boolean(..) | Expr::String(..) | Expr::None(..)) {
+                code.push_str("    mov rdi, rax\n");
+                code.push_str("    call __rc_inc\n");
+            }
+            code.push_str(&format!("    mov rdi, QWORD PTR [rbp - {}]\n", abs_offset));
+            code.push_str("    call __rc_dec\n");
+        }
+        code.push_str(&format!("    mov QWORD PTR [rbp - {}], rax\n", abs_offset));
+        Ok(code)
+    }
+    
+    /// Reserve frame space for the list literals of `body` that escape
+    /// analysis finds cannot outlive it: a 24-byte header and then the elements
+    fn allocate_stack_lists(&self, body: &[Statement], params: &[String], max_negative_offset: &mut i32) {
+        self.stack_lists.borrow_mut().clear();
+        if !self.stack_allocation {
+            return;
+        }
+        let mut lists: Vec<(String, usize)> = crate::escape::stack_lists(body, params, &self.user_functions.borrow()).into_iter().collect();
+        lists.sort();
+        for (name, elements) in lists {
+            let mut offset = self.current_stack_offset.borrow_mut();
+            *offset -= 24 + 8 * elements as i32;
+            *max_negative_offset = (*max_negative_offset).min(*offset);
+            self.stack_lists.borrow_mut().insert(name, *offset);
+        }
+    }
+    
+    /// A list literal laid out like list_create_64's at the frame offset
+    /// `block`, full to its capacity
+    fn compile_stack_list(&mut self, elements: &[Expr], block: i32) -> Result<String, String> {
+        let header = self.get_absolute_offset(block);
+        let mut code = format!("    # List literal with {} elements in the frame
+", elements.len());
+        code.push_str(&format!("    mov QWORD PTR [rbp - {}], {}
+", header, elements.len()));
+        code.push_str(&format!("    mov QWORD PTR [rbp - {}], {}
+", header - 8, elements.len()));
+        code.push_str(&format!("    lea rax, [rbp - {}]
+", header - 24));
+        code.push_str(&format!("    mov QWORD PTR [rbp - {}], rax
+", header - 16));
+        for (index, element) in elements.iter().enumerate() {
+            code.push_str(&self.compile_expression(element)?);
+            code.push_str(&format!("    mov QWORD PTR [rbp - {}], rax
+", header - 24 - 8 * index as i32));
+        }
+        code.push_str(&format!("    lea rax, [rbp - {}]
+", header));
+        Ok(code)
+    }
+    
+    fn get_absolute_offset(&self, offset: i32) -> i32 {
+        if offset < 0 { -offset } else { offset }
+    }
+    
+    fn get_string_label(&mut self, content: &str, span: Span) -> Result<String, String> {
+        // print_string stops at the first NUL, so it cannot appear inside a literal
+        if content.contains('\0') {
+            return Err(format!("String literal contains a null byte at {}", span));
+        }
+        
+        Ok(self.strings.intern(content))
+    }
+    
+    fn generate_string_data(&self) -> String {
+        self.strings.data_directives("#")
+    }
+    
+    fn get_next_label_id(&self) -> LabelId {
+        let mut counter = self.label_counter.borrow_mut();
+        let id = *counter;
+        *counter += 1;
+        LabelId { scope: self.label_scope, id }
+    }
+    
+    fn allocate_block_variables(&self, stmts: &[Statement], max_negative_offset: &mut i32) {
+        for stmt in stmts {
+            let offset = match stmt {
+                Statement::VarDecl { name, value, type_hint, .. } => {
+                    // Redeclaring a variable reuses its existing slot
+                    let offset = self.ensure_variable_exists_rbp_relative(name);
+                    let is_string = self.is_string_expr(value)
+                        || matches!(type_hint.as_deref(), Some("str") | Some("string"));
+                    if is_string {
+                        self.set_variable_type(name, "str");
+                    } else if self.is_float_expr(value) || type_hint.as_deref() == Some("float") {
+                        self.set_variable_type(name, "float");
+                    } else if matches!(value, Expr::Dict { .. }) || self.return_type(value).as_deref() == Some("dict") || type_hint.as_deref() == Some("dict") {
+                        self.set_variable_type(name, "dict");
+                    } else if let Some(instance) = self.struct_of(value).or_else(|| type_hint.clone().filter(|hint| self.structs.borrow().contains_key(hint))) {
+                        self.set_variable_type(name, &instance);
+                    }
+                    offset
+                }
+                Statement::Assign { target, value, .. } | Statement::AugAssign { target, value, .. } => {
+                    let offset = self.ensure_variable_exists_rbp_relative(target);
+                    if self.is_float_expr(value) {
+                        self.set_variable_type(target, "float");
+                    } else if self.is_string_expr(value) {
+                        self.set_variable_type(target, "str");
+                    } else if matches!(value, Expr::Dict { .. }) || self.return_type(value).as_deref() == Some("dict") {
+                        self.set_variable_type(target, "dict");
+                    } else if let Some(instance) = self.struct_of(value) {
+                        self.set_variable_type(target, &instance);
+                    }
+                    offset
+                }
+                Statement::If { then_block, elif_blocks, else_block, .. } => {
+                    self.allocate_block_variables(then_block, max_negative_offset);
+                    for (_, elif_body) in elif_blocks {
+                        self.allocate_block_variables(elif_body, max_negative_offset);
+                    }
+                    if let Some(else_body) = else_block {
+                        self.allocate_block_variables(else_body, max_negative_offset);
+                    }
+                    continue;
+                }
+                Statement::While { body, .. } => {
+                    self.allocate_block_variables(body, max_negative_offset);
+                    continue;
+                }
+                Statement::For { var, body, .. } => {
+                    // The loop variable is an ordinary slot, so it stays readable after the loop
+                    let mut offset = self.ensure_variable_exists_rbp_relative(var);
+                    for slot in self.range_slots() {
+                        offset = offset.min(self.ensure_variable_exists_rbp_relative(&slot));
+                    }
+                    *self.range_depth.borrow_mut() += 1;
+                    self.allocate_block_variables(body, max_negative_offset);
+                    *self.range_depth.borrow_mut() -= 1;
+                    offset
+                }
+                _ => continue,
+            };
+            if offset < *max_negative_offset {
+                *max_negative_offset = offset;
+            }
+        }
+    }
+    
+    fn compile_if(
+        &mut self,
+        condition: &Expr,
+        then_block: &[Statement],
+        elif_blocks: &[(Expr, Vec<Statement>)],
+        else_block: &Option<Vec<Statement>>,
+    ) -> Result<String, String> {
+        let mut asm = String::new();
+        let label_id = self.get_next_label_id();
+        let end_label = format!("if_end_{}", label_id);
+        
+        // The if and every elif form one chain of branches; each one falls
+        // through to the next branch label when its condition is false
+        let mut branches: Vec<(&Expr, &[Statement])> = vec![(condition, then_block)];
+        branches.extend(elif_blocks.iter().map(|(cond, body)| (cond, body.as_slice())));
+        
+        for (i, (cond, body)) in branches.iter().enumerate() {
+            let is_last = i == branches.len() - 1;
+            let next_label = if is_last && else_block.is_none() {
+                end_label.clone()
+            } else if i == 0 {
+                format!("if_else_{}", label_id)
+            } else {
+                format!("if_elif_{}_{}", label_id, i)
+            };
+            
+            asm.push_str(if i == 0 { "    # If condition\n" } else { "    # Elif condition\n" });
+            asm.push_str(&self.compile_condition_jump(cond, &next_label)?);
+            
+            asm.push_str(if i == 0 { "    # Then block\n" } else { "    # Elif body\n" });
+            for stmt in body.iter() {
+                let stmt_code = self.compile_statement_in_context(stmt)?;
+                asm.push_str(&stmt_code);
+            }
+            
+            if next_label != end_label {
+                asm.push_str(&format!("    jmp {}\n", end_label));
+                asm.push_str(&format!("{}:\n", next_label));
+            }
+        }
+        
+        // Process else block
+        if let Some(else_body) = else_block {
+            asm.push_str("    # Else block\n");
+            for stmt in else_body {
+                let stmt_code = self.compile_statement_in_context(stmt)?;
+                asm.push_str(&stmt_code);
+            }
+        }
+        
+        asm.push_str(&format!("{}:\n", end_label));
+        
+        Ok(asm)
+    }
+    
+    /// Evaluate a condition and jump to `false_label` when it does not hold.
+    /// Single comparisons branch directly on the signed flags instead of
+    /// materializing a boolean first.
+    fn compile_condition_jump(&mut self, condition: &Expr, false_label: &str) -> Result<String, String> {
+        let mut code = String::new();
+        
+        // Every operand of an `and` must hold, so each one can branch out on its own
+        if let Expr::BoolOp { op: BoolOp::And, values, .. } = condition {
+            for value in values {
+                code.push_str(&self.compile_condition_jump(value, false_label)?);
+            }
+            return Ok(code);
+        }
+        
+        if let Expr::Compare { left, ops, comparators, span: _ } = condition {
+            let jump = match (ops.as_slice(), comparators.as_slice()) {
+                ([CompareOp::Lt], [_]) => Some("jge"),
+                ([CompareOp::Gt], [_]) => Some("jle"),
+                ([CompareOp::Le], [_]) => Some("jg"),
+                ([CompareOp::Ge], [_]) => Some("jl"),
+                ([CompareOp::Eq], [_]) => Some("jne"),
+                ([CompareOp::Ne], [_]) => Some("je"),
+                _ => None,
+            };
+            
+            // Strings and floats need their own comparison sequence, so they take the generic path
+            let operands = || comparators.iter().chain([left.as_ref()]);
+            let is_special = operands().any(|e| self.is_string_expr(e) || self.is_float_expr(e));
+            if let (Some(jump), false) = (jump, is_special) {
+                code.push_str(&self.compile_expression(left)?);
+                code.push_str(&self.push("rax"));
+                code.push_str(&self.compile_expression(&comparators[0])?);
+                code.push_str("    mov rbx, rax\n");
+                code.push_str(&self.pop("rax"));
+                code.push_str("    cmp rax, rbx\n");
+                code.push_str(&format!("    {} {}\n", jump, false_label));
+                return Ok(code);
+            }
+        }
+        
+        code.push_str(&self.compile_truthiness(condition)?);
+        code.push_str("    test rax, rax\n");
+        code.push_str(&format!("    jz {}\n", false_label));
+        Ok(code)
+    }
+    
+    fn compile_while(&mut self, condition: &Expr, body: &[Statement]) -> Result<String, String> {
+        let mut asm = String::new();
+        let label_id = self.get_next_label_id();
+        let while_start = format!("while_start_{}", label_id);
+        let while_end = format!("while_end_{}", label_id);
+        
+        asm.push_str("    # While loop\n");
+        asm.push_str(&format!("{}:\n", while_start));
+        
+        // Compile condition
+        let cond_code = self.compile_condition_jump(condition, &while_end)?;
+        asm.push_str(&cond_code);
+        
+        asm.push_str("    # While body\n");
+        self.loop_labels.borrow_mut().push((while_start.clone(), while_end.clone()));
+        let mut body_result = Ok(());
+        for stmt in body {
+            match self.compile_statement_in_context(stmt) {
+                Ok(stmt_code) => asm.push_str(&stmt_code),
+                Err(e) => {
+                    body_result = Err(e);
+                    break;
+                }
+            }
+        }
+        self.loop_labels.borrow_mut().pop();
+        body_result?;
+        
+        // Jump back to start
+        asm.push_str(&format!("    jmp {}\n", while_start));
+        
+        // End label
+        asm.push_str(&format!("{}:\n", while_end));
+        
+        Ok(asm)
+    }
+    
+    /// Hidden counter, stop and step slots of the for loop at the current nesting depth;
+    /// loops that follow each other share them
+    fn range_slots(&self) -> [String; 3] {
+        let depth = *self.range_depth.borrow();
+        ["counter", "stop", "step"].map(|slot| format!("@range{}.{}", depth, slot))
+    }
+    
+    /// Lower `for var in range(...)`. The bounds are evaluated once and the
+    /// variable is assigned from a hidden counter, so the body may reassign it
+    fn compile_for(&mut self, var: &str, iter: &Expr, body: &[Statement], span: Span) -> Result<String, String> {
+        let (start, stop, step) = range_arguments(iter, span)?;
+        if [&start, &stop, &step].iter().any(|e| self.is_float_expr(e) || self.is_string_expr(e)) {
+            return Err(format!("range() arguments must be integers at {}", span));
+        }
+        
+        let label_id = self.get_next_label_id();
+        let for_start = format!("for_start_{}", label_id);
+        let for_next = format!("for_next_{}", label_id);
+        let for_end = format!("for_end_{}", label_id);
+        let [counter, stop_slot, step_slot] = self.range_slots()
+            .map(|slot| self.get_absolute_offset(self.ensure_variable_exists_rbp_relative(&slot)));
+        let var_slot = self.get_absolute_offset(self.ensure_variable_exists_rbp_relative(var));
+        let step_value = literal_integer(&step);
+        
+        let mut asm = format!("    # For loop over range() into {}\n", var);
+        asm.push_str(&self.compile_expression(&start)?);
+        asm.push_str(&format!("    mov QWORD PTR [rbp - {}], rax\n", counter));
+        asm.push_str(&self.compile_expression(&stop)?);
+        asm.push_str(&format!("    mov QWORD PTR [rbp - {}], rax\n", stop_slot));
+        if step_value.is_none() {
+            let message = self.get_string_label(RANGE_STEP_ERROR, span)?;
+            asm.push_str(&self.compile_expression(&step)?);
+            asm.push_str("    test rax, rax\n");
+            asm.push_str(&format!("    jnz for_step_ok_{}\n", label_id));
+            asm.push_str(&format!("    lea rdi, [{}]\n", message));
+            asm.push_str("    call runtime_error_64\n");
+            asm.push_str(&format!("for_step_ok_{}:\n", label_id));
+            asm.push_str(&format!("    mov QWORD PTR [rbp - {}], rax\n", step_slot));
+        }
+        
+        // Counting up stops once the counter reaches the bound, counting down once it gets there from above
+        asm.push_str(&format!("{}:\n", for_start));
+        asm.push_str(&format!("    mov rax, QWORD PTR [rbp - {}]\n", counter));
+        match step_value {
+            Some(n) => {
+                asm.push_str(&format!("    cmp rax, QWORD PTR [rbp - {}]\n", stop_slot));
+                asm.push_str(&format!("    {} {}\n", if n > 0 { "jge" } else { "jle" }, for_end));
+            }
+            None => {
+                asm.push_str(&format!("    cmp QWORD PTR [rbp - {}], 0\n", step_slot));
+                asm.push_str(&format!("    jl for_down_{}\n", label_id));
+                asm.push_str(&format!("    cmp rax, QWORD PTR [rbp - {}]\n", stop_slot));
+                asm.push_str(&format!("    jge {}\n", for_end));
+                asm.push_str(&format!("    jmp for_body_{}\n", label_id));
+                asm.push_str(&format!("for_down_{}:\n", label_id));
+                asm.push_str(&format!("    cmp rax, QWORD PTR [rbp - {}]\n", stop_slot));
+                asm.push_str(&format!("    jle {}\n", for_end));
+                asm.push_str(&format!("for_body_{}:\n", label_id));
+            }
+        }
+        asm.push_str(&format!("    mov QWORD PTR [rbp - {}], rax\n", var_slot));
+        
+        asm.push_str("    # For body\n");
+        self.loop_labels.borrow_mut().push((for_next.clone(), for_end.clone()));
+        *self.range_depth.borrow_mut() += 1;
+        let mut body_result = Ok(());
+        for stmt in body {
+            match self.compile_statement_in_context(stmt) {
+                Ok(stmt_code) => asm.push_str(&stmt_code),
+                Err(e) => {
+                    body_result = Err(e);
+                    break;
+                }
+            }
+        }
+        *self.range_depth.borrow_mut() -= 1;
+        self.loop_labels.borrow_mut().pop();
+        body_result?;
+        
+        asm.push_str(&format!("{}:\n", for_next));
+        match step_value {
+            Some(n) => asm.push_str(&format!("    mov rax, {}\n", n)),
+            None => asm.push_str(&format!("    mov rax, QWORD PTR [rbp - {}]\n", step_slot)),
+        }
+        asm.push_str(&format!("    add QWORD PTR [rbp - {}], rax\n", counter));
+        asm.push_str(&format!("    jmp {}\n", for_start));
+        asm.push_str(&format!("{}:\n", for_end));
+        
+        Ok(asm)
+    }
+    
+    fn compile_function(&mut self, name: &str, args: &[String], body: &[Statement], span: Span) -> Result<String, String> {
+        if LINUX64_RESERVED_NAMES.contains(&name) || name.starts_with("hw_") {
+            return Err(format!("Function name '{}' is reserved at {}", name, span));
+        }
+        
+        // Each function gets its own frame and symbol table
+        let saved_symbols = self.symbol_table.replace(HashMap::new());
+        let saved_lists = self.stack_lists.replace(HashMap::new());
+        let saved_offset = self.current_stack_offset.replace(0);
+        let saved_epilogue = self.current_epilogue.replace(format!(".{}_epilogue", mangle_function_name(name)));
+        let saved_function = self.current_function.replace(Some(name.to_string()));
+        let saved_pushed = self.pushed.replace(0);
+        
+        let phase = self.logger.detail_phase("codegen");
+        let result = self.compile_function_body(name, args, body);
+        if let Ok(asm) = &result {
+            phase.end(format!("function {}: {} lines", name, asm.lines().count()));
+        }
+        
+        *self.symbol_table.borrow_mut() = saved_symbols;
+        *self.stack_lists.borrow_mut() = saved_lists;
+        *self.current_stack_offset.borrow_mut() = saved_offset;
+        *self.current_epilogue.borrow_mut() = saved_epilogue;
+        *self.current_function.borrow_mut() = saved_function;
+        *self.pushed.borrow_mut() = saved_pushed;
+        
+        result
+    }
+    
+    fn compile_function_body(&mut self, name: &str, args: &[String], body: &[Statement]) -> Result<String, String> {
+        let mut asm = String::new();
+        
+        // Parameters get the first slots, locals follow. Those annotated as
+        // strings, dicts, structs or floats are known to hold one; callers
+        // convert an int passed for a float
+        let mut max_negative_offset = 0;
+        let arg_types = self.signatures.borrow().get(name).map(|(arg_types, _)| arg_types.clone()).unwrap_or_default();
+        for (index, arg) in args.iter().enumerate() {
+            max_negative_offset = max_negative_offset.min(self.allocate_variable_rbp_relative(arg));
+            match arg_types.get(index).cloned().flatten().as_deref() {
+                Some("str" | "string") => self.set_variable_type(arg, "str"),
+                Some("float") => self.set_variable_type(arg, "float"),
+                Some(hint) if hint == "dict" || self.structs.borrow().contains_key(hint) => self.set_variable_type(arg, hint),
+                _ => {}
+            }
+        }
+        self.allocate_block_variables(body, &mut max_negative_offset);
+        self.allocate_stack_lists(body, args, &mut max_negative_offset);
+        
+        if max_negative_offset < 0 {
+            let stack_space = (-max_negative_offset + 15) & !15;
+            asm.push_str(&format!("    sub rsp, {}        # Allocate {} bytes for locals\n", stack_space, stack_space));
+            asm.push_str(&self.clear_frame(stack_space));
+        }
+        
+        // Spill incoming parameters so the body can address them by name
+        for (i, arg) in args.iter().enumerate() {
+            let offset = self.get_variable_offset_rbp_relative(arg)
+                .ok_or_else(|| format!("Parameter {} not allocated", arg))?;
+            let abs_offset = self.get_absolute_offset(offset);
+            if let Some(reg) = SYSV_ARG_REGISTERS.get(i) {
+                asm.push_str(&format!("    mov QWORD PTR [rbp - {}], {}\n", abs_offset, reg));
+            } else {
+                let stack_offset = 16 + (i - SYSV_ARG_REGISTERS.len()) * 8;
+                asm.push_str(&format!("    mov rax, QWORD PTR [rbp + {}]\n", stack_offset));
+                asm.push_str(&format!("    mov QWORD PTR [rbp - {}], rax\n", abs_offset));
+            }
+            if self.counts_references {
+                asm.push_str(&format!("    mov rdi, QWORD PTR [rbp - {}]\n", abs_offset));
+                asm.push_str("    call __rc_inc\n");
+            }
+        }
+        
+        for (index, stmt) in body.iter().enumerate() {
+            asm.push_str(&self.statement_marker(stmt));
+            let tail_call = if index + 1 == body.len() { self.compile_tail_call(stmt)? } else { None };
+            match tail_call {
+                Some(code) => asm.push_str(&code),
+                None => asm.push_str(&self.compile_statement_in_context(stmt)?),
+            }
+        }
+        asm.push_str(&self.body_end_marker());
+        
+        // Functions that fall off the end return 0
+        asm.push_str("    xor rax, rax\n");
+        asm.push_str(&format!("{}:\n", self.current_epilogue.borrow()));
+        if self.counts_references {
+            // The result outlives the locals it may come from, then goes back uncounted
+            asm.push_str("    mov rdi, rax\n");
+            asm.push_str("    call __rc_inc\n");
+            let mut locals: Vec<i32> = self.symbol_table.borrow().values()
+                .filter(|v| v.type_hint.as_deref() != Some("float"))
+                .map(|v| self.get_absolute_offset(v.offset))
+                .collect();
+            locals.sort_unstable();
+            for offset in locals {
+                asm.push_str(&format!("    mov rdi, QWORD PTR [rbp - {}]\n", offset));
+                asm.push_str("    call __rc_dec\n");
+            }
+            asm.push_str("    mov rdi, rax\n");
+            asm.push_str("    call __rc_disown\n");
+        }
+        
+        // A leaf function with nothing in its frame needs no frame at all
+        let label = format!("{}:\n", mangle_function_name(name));
+        if self.elides_frames() && max_negative_offset == 0 && !asm.contains("call ") && !asm.contains("rbp") {
+            return Ok(format!("{}{}    ret\n\n", label, asm));
+        }
+        asm.insert_str(0, &format!("{}    push rbp\n    mov rbp, rsp\n", label));
+        asm.push_str("    mov rsp, rbp\n");
+        asm.push_str("    pop rbp\n");
+        asm.push_str("    ret\n\n");
+        
+        Ok(asm)
+    }
+    
+    fn compile_loop_jump(&self, is_break: bool) -> Result<String, String> {
+        let keyword = if is_break { "break" } else { "continue" };
+        let loops = self.loop_labels.borrow();
+        let (start, end) = loops.last()
+            .ok_or_else(|| format!("'{}' outside of loop", keyword))?;
+        let target = if is_break { end } else { start };
+        Ok(format!("    jmp {}        # {}\n", target, keyword))
+    }
+    
+    fn generate_helper_function(&self) -> String {
+    let mut helpers = String::new();
+    // The write helpers pass stdout's buffer and length in rsi and rdx either way
+    let write = if self.prints_to_serial() { "    call serial_write_64\n" } else { "    syscall\n" };
+    
+    helpers.push_str("print_string:\n");
+    helpers.push_str("    push rax\n");
+    helpers.push_str("    push rdi\n");
+    helpers.push_str("    push rsi\n");
+    helpers.push_str("    push rdx\n");
+    helpers.push_str("    #\n");
+    helpers.push_str("    mov rsi, rdi\n");
+    helpers.push_str("    xor rdx, rdx\n");
+    helpers.push_str(".count_loop:\n");
+    helpers.push_str("    cmp BYTE PTR [rsi + rdx], 0\n");
+    helpers.push_str("    je .count_done\n");
+    helpers.push_str("    inc rdx\n");
+    helpers.push_str("    jmp .count_loop\n");
+    helpers.push_str(".count_done:\n");
+    helpers.push_str("    #\n");
+    helpers.push_str("    mov rax, 1\n");
+    helpers.push_str("    mov rdi, 1\n");
+    helpers.push_str(write);
+    helpers.push_str("    #\n");
+    helpers.push_str("    pop rdx\n");
+    helpers.push_str("    pop rsi\n");
+    helpers.push_str("    pop rdi\n");
+    helpers.push_str("    pop rax\n");
+    helpers.push_str("    ret\n\n");
 
     helpers.push_str("print_decimal:\n");
     helpers.push_str("    # Input: rax = integer\n");
@@ -637,20 +2176,14 @@ impl Linux64Backend {
     helpers.push_str("    mov BYTE PTR [rdi], '-'\n");
     helpers.push_str("    #\n");
     helpers.push_str(".print_it:\n");
-    helpers.push_str("    lea rsi, [rsp + 32]\n");
+    helpers.push_str("    lea rsi, [rsp + 31]\n");
     helpers.push_str("    sub rsi, rdi\n");
     helpers.push_str("    #\n");
     helpers.push_str("    mov rax, 1\n");
     helpers.push_str("    mov rdx, rsi\n");
     helpers.push_str("    mov rsi, rdi\n");
     helpers.push_str("    mov rdi, 1\n");
-    helpers.push_str("    syscall\n");
-    helpers.push_str("    #\n");
-    helpers.push_str("    mov rax, 1\n");
-    helpers.push_str("    mov rdi, 1\n");
-    helpers.push_str("    lea rsi, [newline]\n");
-    helpers.push_str("    mov rdx, 1\n");
-    helpers.push_str("    syscall\n");
+    helpers.push_str(write);
     helpers.push_str("    #\n");
     helpers.push_str("    pop rdi\n");
     helpers.push_str("    pop rsi\n");
@@ -662,86 +2195,275 @@ impl Linux64Backend {
     helpers.push_str("    pop rbp\n");
     helpers.push_str("    ret\n\n");
     
-    helpers.push_str("print_newline:\n");
-    helpers.push_str("    push rax\n");
+    helpers.push_str("str_cmp:\n");
+    helpers.push_str("    # Input: rdi, rsi = strings; output: rax = difference at first mismatch\n");
+    helpers.push_str("    push rcx\n");
     helpers.push_str("    push rdi\n");
     helpers.push_str("    push rsi\n");
-    helpers.push_str("    push rdx\n");
-    helpers.push_str("    #\n");
-    helpers.push_str("    mov rax, 1\n");
-    helpers.push_str("    mov rdi, 1\n");
-    helpers.push_str("    lea rsi, [newline]\n");
-    helpers.push_str("    mov rdx, 1\n");
-    helpers.push_str("    syscall\n");
-    helpers.push_str("    #\n");
-    helpers.push_str("    pop rdx\n");
+    helpers.push_str(".str_cmp_loop:\n");
+    helpers.push_str("    movzx eax, BYTE PTR [rdi]\n");
+    helpers.push_str("    movzx ecx, BYTE PTR [rsi]\n");
+    helpers.push_str("    cmp eax, ecx\n");
+    helpers.push_str("    jne .str_cmp_done\n");
+    helpers.push_str("    test eax, eax\n");
+    helpers.push_str("    jz .str_cmp_done\n");
+    helpers.push_str("    inc rdi\n");
+    helpers.push_str("    inc rsi\n");
+    helpers.push_str("    jmp .str_cmp_loop\n");
+    helpers.push_str(".str_cmp_done:\n");
+    helpers.push_str("    sub rax, rcx\n");
     helpers.push_str("    pop rsi\n");
     helpers.push_str("    pop rdi\n");
-    helpers.push_str("    pop rax\n");
-    helpers.push_str("    ret\n");
-    
-    helpers
-}
-    
-    fn compile_statement_in_context(&mut self, stmt: &Statement) -> Result<String, String> {
-    let mut code = String::new();
+    helpers.push_str("    pop rcx\n");
+    helpers.push_str("    ret\n\n");
     
-    match stmt {
-        Statement::Expr(expr) => {
-            code.push_str(&self.compile_expression(&expr)?);
+    helpers.push_str("print_float:\n");
+    helpers.push_str("    # Input: rax = IEEE double, printed with up to six decimals\n");
+    helpers.push_str("    push rbp\n");
+    helpers.push_str("    mov rbp, rsp\n");
+    helpers.push_str("    sub rsp, 704\n");
+    helpers.push_str("    push rcx\n");
+    helpers.push_str("    push rdx\n");
+    helpers.push_str("    push rdi\n");
+    helpers.push_str("    push rsi\n");
+    helpers.push_str("    push r8\n");
+    helpers.push_str("    #\n");
+    helpers.push_str("    mov rcx, 0x7ff0000000000000\n");
+    helpers.push_str("    mov rdx, rax\n");
+    helpers.push_str("    btr rdx, 63\n");
+    helpers.push_str("    cmp rdx, rcx\n");
+    helpers.push_str("    jbe .float_signed\n");
+    helpers.push_str("    mov DWORD PTR [rbp - 32], 0x4e614e    # \"NaN\"\n");
+    helpers.push_str("    jmp .float_word\n");
+    helpers.push_str("    #\n");
+    helpers.push_str(".float_signed:\n");
+    helpers.push_str("    btr rax, 63\n");
+    helpers.push_str("    jnc .float_positive\n");
+    helpers.push_str("    mov WORD PTR [rbp - 32], 0x2d    # \"-\"\n");
+    helpers.push_str("    lea rdi, [rbp - 32]\n");
+    helpers.push_str("    call print_string\n");
+    helpers.push_str(".float_positive:\n");
+    helpers.push_str("    mov rcx, 0x7ff0000000000000     # print_string clobbers rcx\n");
+    helpers.push_str("    cmp rax, rcx\n");
+    helpers.push_str("    jne .float_number\n");
+    helpers.push_str("    mov DWORD PTR [rbp - 32], 0x666e69    # \"inf\"\n");
+    helpers.push_str(".float_word:\n");
+    helpers.push_str("    lea rdi, [rbp - 32]\n");
+    helpers.push_str("    call print_string\n");
+    helpers.push_str("    jmp .float_done\n");
+    helpers.push_str("    #\n");
+    helpers.push_str(".float_number:\n");
+    helpers.push_str("    mov rcx, rax\n");
+    helpers.push_str("    shr rcx, 52                # biased exponent\n");
+    helpers.push_str("    cmp ecx, 1076\n");
+    helpers.push_str("    jae .float_integral\n");
+    helpers.push_str("    # Below 2^53 the integer part fits a register and taking it away leaves the exact fraction\n");
+    helpers.push_str("    movq xmm0, rax\n");
+    helpers.push_str("    cvttsd2si rax, xmm0\n");
+    helpers.push_str("    cvtsi2sd xmm1, rax\n");
+    helpers.push_str("    subsd xmm0, xmm1\n");
+    helpers.push_str("    mov rcx, 1000000\n");
+    helpers.push_str("    cvtsi2sd xmm1, rcx\n");
+    helpers.push_str("    mulsd xmm0, xmm1\n");
+    helpers.push_str("    cvtsd2si rdx, xmm0         # round to the nearest millionth\n");
+    helpers.push_str("    cmp rdx, rcx\n");
+    helpers.push_str("    jb .float_split\n");
+    helpers.push_str("    inc rax                    # the fraction rounded up to a whole one\n");
+    helpers.push_str("    xor edx, edx\n");
+    helpers.push_str(".float_split:\n");
+    helpers.push_str("    mov QWORD PTR [rbp - 8], rdx\n");
+    helpers.push_str("    call print_decimal\n");
+    helpers.push_str("    mov WORD PTR [rbp - 32], 0x2e    # \".\"\n");
+    helpers.push_str("    lea rdi, [rbp - 32]\n");
+    helpers.push_str("    call print_string\n");
+    helpers.push_str("    #\n");
+    helpers.push_str("    # Six fraction digits go to [rbp - 24 .. rbp - 19]\n");
+    helpers.push_str("    mov rax, QWORD PTR [rbp - 8]\n");
+    helpers.push_str("    lea rdi, [rbp - 18]\n");
+    helpers.push_str("    mov BYTE PTR [rdi], 0\n");
+    helpers.push_str("    mov rcx, 10\n");
+    helpers.push_str(".float_digit_loop:\n");
+    helpers.push_str("    xor rdx, rdx\n");
+    helpers.push_str("    div rcx\n");
+    helpers.push_str("    add dl, '0'\n");
+    helpers.push_str("    dec rdi\n");
+    helpers.push_str("    mov BYTE PTR [rdi], dl\n");
+    helpers.push_str("    lea rdx, [rbp - 24]\n");
+    helpers.push_str("    cmp rdi, rdx\n");
+    helpers.push_str("    jne .float_digit_loop\n");
+    helpers.push_str("    #\n");
+    helpers.push_str("    # Drop trailing zeros but keep at least one digit\n");
+    helpers.push_str("    lea rdi, [rbp - 19]\n");
+    helpers.push_str(".float_trim_loop:\n");
+    helpers.push_str("    cmp rdi, rdx\n");
+    helpers.push_str("    je .float_print\n");
+    helpers.push_str("    cmp BYTE PTR [rdi], '0'\n");
+    helpers.push_str("    jne .float_print\n");
+    helpers.push_str("    mov BYTE PTR [rdi], 0\n");
+    helpers.push_str("    dec rdi\n");
+    helpers.push_str("    jmp .float_trim_loop\n");
+    helpers.push_str(".float_print:\n");
+    helpers.push_str("    mov rdi, rdx\n");
+    helpers.push_str("    call print_string\n");
+    helpers.push_str("    #\n");
+    helpers.push_str("    jmp .float_done\n");
+    helpers.push_str("    #\n");
+    helpers.push_str(".float_integral:\n");
+    helpers.push_str("    # From 2^53 up a double is the whole number mantissa * 2^(exponent - 1075).\n");
+    helpers.push_str("    # Its decimal digits, least significant first from [rbp - 352], start as\n");
+    helpers.push_str("    # the mantissa's and are doubled once per power of two\n");
+    helpers.push_str("    sub ecx, 1075\n");
+    helpers.push_str("    mov rdx, 0xfffffffffffff\n");
+    helpers.push_str("    and rax, rdx\n");
+    helpers.push_str("    bts rax, 52\n");
+    helpers.push_str("    lea rdi, [rbp - 352]\n");
+    helpers.push_str("    xor esi, esi\n");
+    helpers.push_str("    mov r8, 10\n");
+    helpers.push_str(".float_integral_digits:\n");
+    helpers.push_str("    xor edx, edx\n");
+    helpers.push_str("    div r8\n");
+    helpers.push_str("    mov BYTE PTR [rdi + rsi], dl\n");
+    helpers.push_str("    inc rsi\n");
+    helpers.push_str("    test rax, rax\n");
+    helpers.push_str("    jnz .float_integral_digits\n");
+    helpers.push_str(".float_double:\n");
+    helpers.push_str("    xor edx, edx               # carry\n");
+    helpers.push_str("    xor r8d, r8d\n");
+    helpers.push_str(".float_double_digit:\n");
+    helpers.push_str("    movzx eax, BYTE PTR [rdi + r8]\n");
+    helpers.push_str("    add eax, eax\n");
+    helpers.push_str("    add eax, edx\n");
+    helpers.push_str("    xor edx, edx\n");
+    helpers.push_str("    cmp eax, 10\n");
+    helpers.push_str("    jb .float_double_store\n");
+    helpers.push_str("    sub eax, 10\n");
+    helpers.push_str("    inc edx\n");
+    helpers.push_str(".float_double_store:\n");
+    helpers.push_str("    mov BYTE PTR [rdi + r8], al\n");
+    helpers.push_str("    inc r8\n");
+    helpers.push_str("    cmp r8, rsi\n");
+    helpers.push_str("    jb .float_double_digit\n");
+    helpers.push_str("    test edx, edx\n");
+    helpers.push_str("    jz .float_doubled\n");
+    helpers.push_str("    mov BYTE PTR [rdi + rsi], 1\n");
+    helpers.push_str("    inc rsi\n");
+    helpers.push_str(".float_doubled:\n");
+    helpers.push_str("    dec ecx\n");
+    helpers.push_str("    jnz .float_double\n");
+    helpers.push_str("    # Most significant digit first into [rbp - 688], then \".0\"\n");
+    helpers.push_str("    lea rdx, [rbp - 688]\n");
+    helpers.push_str(".float_integral_text:\n");
+    helpers.push_str("    dec rsi\n");
+    helpers.push_str("    movzx eax, BYTE PTR [rdi + rsi]\n");
+    helpers.push_str("    add al, '0'\n");
+    helpers.push_str("    mov BYTE PTR [rdx], al\n");
+    helpers.push_str("    inc rdx\n");
+    helpers.push_str("    test rsi, rsi\n");
+    helpers.push_str("    jnz .float_integral_text\n");
+    helpers.push_str("    mov DWORD PTR [rdx], 0x302e      # \".0\"\n");
+    helpers.push_str("    lea rdi, [rbp - 688]\n");
+    helpers.push_str("    call print_string\n");
+    helpers.push_str("    #\n");
+    helpers.push_str(".float_done:\n");
+    helpers.push_str("    pop r8\n");
+    helpers.push_str("    pop rsi\n");
+    helpers.push_str("    pop rdi\n");
+    helpers.push_str("    pop rdx\n");
+    helpers.push_str("    pop rcx\n");
+    helpers.push_str("    mov rsp, rbp\n");
+    helpers.push_str("    pop rbp\n");
+    helpers.push_str("    ret\n\n");
+    
+    helpers.push_str("print_newline:\n");
+    helpers.push_str("    push rax\n");
+    helpers.push_str("    push rdi\n");
+    helpers.push_str("    push rsi\n");
+    helpers.push_str("    push rdx\n");
+    helpers.push_str("    #\n");
+    helpers.push_str("    mov rax, 1\n");
+    helpers.push_str("    mov rdi, 1\n");
+    helpers.push_str("    lea rsi, [newline]\n");
+    helpers.push_str("    mov rdx, 1\n");
+    helpers.push_str(write);
+    helpers.push_str("    #\n");
+    helpers.push_str("    pop rdx\n");
+    helpers.push_str("    pop rsi\n");
+    helpers.push_str("    pop rdi\n");
+    helpers.push_str("    pop rax\n");
+    helpers.push_str("    ret\n");
+    
+    helpers
+}
+    
+    fn compile_statement_in_context(&mut self, stmt: &Statement) -> Result<String, String> {
+    let mut code = String::new();
+    
+    match stmt {
+        Statement::Expr(expr) => {
+            code.push_str(&self.compile_expression(&expr)?);
         }
         Statement::VarDecl { name, value, type_hint: _, span: _ } => {
             code.push_str(&format!("    # Variable declaration: {}\n", name));
-            let offset = self.allocate_variable_rbp_relative(&name);
-            let value_code = self.compile_expression(&value)?;
-            code.push_str(&value_code);
-            let abs_offset = self.get_absolute_offset(offset);
-            code.push_str(&format!("    mov QWORD PTR [rbp - {}], rax\n", abs_offset));
+            code.push_str(&self.compile_store(name, value)?);
         }
         Statement::Assign { target, value, span: _ } => {
             code.push_str(&format!("    # Assignment to {}\n", target));
-            let offset = self.ensure_variable_exists_rbp_relative(&target);
-            let value_code = self.compile_expression(&value)?;
-            code.push_str(&value_code);
-            let abs_offset = self.get_absolute_offset(offset);
-            code.push_str(&format!("    mov QWORD PTR [rbp - {}], rax\n", abs_offset));
+            code.push_str(&self.compile_store(target, value)?);
         }
-        Statement::AugAssign { target, op, value, span: _ } => {
+        Statement::AugAssign { target, op, value, span } => {
             code.push_str(&format!("    # Augmented assignment to {}\n", target));
-            let offset = self.ensure_variable_exists_rbp_relative(&target);
-            let abs_offset = self.get_absolute_offset(offset);
-            
-            // Load current value
-            code.push_str(&format!("    mov rax, QWORD PTR [rbp - {}]\n", abs_offset));
-            code.push_str("    push rax\n");
-            
-            // Compile right-hand side
-            let value_code = self.compile_expression(&value)?;
-            code.push_str(&value_code);
-            
-            code.push_str("    mov rbx, rax\n");
-            code.push_str("    pop rax\n");
-            
-            // Perform operation
-            match op {
-                Op::Add => code.push_str("    add rax, rbx\n"),
-                Op::Sub => code.push_str("    sub rax, rbx\n"),
-                Op::Mul => code.push_str("    imul rax, rbx\n"),
-                Op::Div => {
-                    code.push_str("    xor rdx, rdx\n");
-                    code.push_str("    idiv rbx\n");
-                }
-                Op::Mod => {
-                    code.push_str("    xor rdx, rdx\n");
-                    code.push_str("    div rbx\n");
-                    code.push_str("    mov rax, rdx\n");
-                }
-                _ => return Err(format!("Unsupported augmented assignment operator: {:?}", op)),
+            if !matches!(op, Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Mod) {
+                return Err(format!("Unsupported augmented assignment operator: {:?}", op));
             }
             
-            // Store back
-            code.push_str(&format!("    mov QWORD PTR [rbp - {}], rax\n", abs_offset));
+            // `x op= v` is evaluated exactly like `x = x op v`
+            let combined = Expr::BinOp {
+                left: Box::new(Expr::Var(target.clone(), *span)),
+                op: op.clone(),
+                right: Box::new(value.clone()),
+                span: *span,
+            };
+            code.push_str(&self.compile_store(target, &combined)?);
+        }
+        Statement::IndexAssign { target, index, value, span } => {
+            if self.is_dict_expr(target) && !self.is_string_expr(index) {
+                return Err(format!("Dictionary keys must be strings at {}", span));
+            }
+            code.push_str("    # Subscript assignment\n");
+            code.push_str(&self.compile_expression(value)?);
+            code.push_str(&self.push("rax"));
+            code.push_str(&self.compile_expression(index)?);
+            code.push_str(&self.push("rax"));
+            code.push_str(&self.compile_expression(target)?);
+            code.push_str("    mov rdi, rax\n");
+            code.push_str(&self.pop("rsi"));
+            code.push_str(&self.pop("rdx"));
+            if self.is_dict_expr(target) || self.is_string_expr(index) {
+                code.push_str("    call dict_set_64\n");
+            } else {
+                code.push_str("    call list_set_64\n");
+            }
+        }
+        Statement::FieldAssign { target, field, value, span } => {
+            let offset = 8 * self.field_index(target, field, *span)?;
+            code.push_str(&format!("    # Field assignment .{}\n", field));
+            code.push_str(&self.compile_expression(value)?);
+            code.push_str(&self.push("rax"));
+            code.push_str(&self.compile_expression(target)?);
+            code.push_str("    mov rdi, rax\n");
+            code.push_str(&self.pop("rax"));
+            if self.counts_references {
+                // Count the new value before dropping the old one, which may be the same object
+                code.push_str(&self.push("rdi"));
+                code.push_str("    mov rdi, rax\n");
+                code.push_str("    call __rc_inc\n");
+                code.push_str(&format!("    mov rdi, QWORD PTR [rsp]\n    mov rdi, QWORD PTR [rdi + {}]\n", offset));
+                code.push_str("    call __rc_dec\n");
+                code.push_str(&self.pop("rdi"));
+            }
+            code.push_str(&format!("    mov QWORD PTR [rdi + {}], rax\n", offset));
         }
+        Statement::StructDef { span, .. } => return Err(format!("Structs must be declared at the top level at {}", span)),
         Statement::HardwareFunctionDef { device, name, args: _, body, span: _ } => {
             // Compile hardware function using DSL
             code.push_str(&format!("    # Hardware function: {} for device {}\n", name, device));
@@ -773,6 +2495,27 @@ impl Linux64Backend {
             // Handle hardware declaration
             code.push_str("    # Hardware declaration (ignored in context)\n");
         }
+        Statement::ConstDecl { name, span, .. } => return Err(format!("Constant '{}' must be resolved before code generation at {}", name, span)),
+        Statement::If { condition, then_block, elif_blocks, else_block, span: _ } => {
+            code.push_str(&self.compile_if(condition, then_block, elif_blocks, else_block)?);
+        }
+        Statement::While { condition, body, orelse: _, span: _ } => {
+            code.push_str(&self.compile_while(condition, body)?);
+        }
+        Statement::For { var, iter, body, span } => {
+            code.push_str(&self.compile_for(var, iter, body, *span)?);
+        }
+        Statement::Break => code.push_str(&self.compile_loop_jump(true)?),
+        Statement::Continue => code.push_str(&self.compile_loop_jump(false)?),
+        Statement::Return(expr, span) => {
+            code.push_str("    # Return statement\n");
+            if let Some(expr) = expr {
+                code.push_str(&self.compile_return_value(expr, *span)?);
+            } else {
+                code.push_str("    xor rax, rax\n");
+            }
+            code.push_str(&format!("    jmp {}\n", self.current_epilogue.borrow()));
+        }
         _ => {
             code.push_str(&format!("    # [Statement type not handled in context: {:?}]\n", stmt));
         }
@@ -784,7 +2527,11 @@ impl Linux64Backend {
 
 impl Backend for Linux64Backend {
     fn name(&self) -> &str {
-        "linux64"
+        Target::Linux64.name()
+    }
+    
+    fn target(&self) -> Target {
+        Target::Linux64
     }
     
     fn generate_header(&self) -> String {
@@ -810,26 +2557,57 @@ _start:
             Capability::Linux,
             Capability::LongMode64,
             Capability::VirtualMemory,
+            Capability::Graphics, // via the hardware DSL
+            Capability::Heap,
+            Capability::Filesystem,
+            Capability::WritableMemory,
+            Capability::Float, // SSE2 scalar doubles
         ]
     }
     
     fn compile_program(&mut self, program: &Program) -> Result<String, String> {
     let mut asm = String::new();
+    
+    // Literals from a previous program must not end up in this one's data section
+    self.strings = StringPool::default();
+    self.counts_references = self.wants_refcounting(program);
 
     // GAS directives for Intel syntax
     asm.push_str("    .intel_syntax noprefix\n");
     asm.push_str("    .section .text\n");
+    if let Some(file) = &self.debug_file {
+        asm.push_str(&format!("    .file 1 \"{}\"\n", file.replace('\\', "\\\\").replace('"', "\\\"")));
+    }
     asm.push_str("    .globl _start\n\n");
     
     asm.push_str("_start:\n");
     asm.push_str("    mov rbp, rsp\n");
     asm.push_str("    and rsp, -16        # 16-byte align stack\n");
     asm.push_str("    \n");
+    let palette_images = !self.embedded_images.is_empty()
+        && matches!(&self.bios_graphics, Some((framebuffer, _)) if framebuffer.bytes_per_pixel == 1);
+    if palette_images {
+        asm.push_str("    call fb_load_palette\n");
+    }
+    if self.prints_to_serial() {
+        asm.push_str("    call serial_init_64\n");
+    }
+    let interrupt_driven = self.uses_interrupts(program);
+    if interrupt_driven {
+        asm.push_str("    call idt_init_64\n");
+    }
     asm.push_str("    call main\n");
     asm.push_str("    \n");
-    asm.push_str("    mov rdi, rax        # exit code\n");
-    asm.push_str("    mov rax, 60         # syscall: exit\n");
-    asm.push_str("    syscall\n\n");
+    if self.bios_graphics.is_some() {
+        // Nothing to return to once the loader has jumped here
+        asm.push_str(".halt:\n");
+        asm.push_str("    hlt\n");
+        asm.push_str("    jmp .halt\n\n");
+    } else {
+        asm.push_str("    mov rdi, rax        # exit code\n");
+        asm.push_str("    mov rax, 60         # syscall: exit\n");
+        asm.push_str("    syscall\n\n");
+    }
     
     asm.push_str("main:\n");
     asm.push_str("    push rbp\n");
@@ -842,33 +2620,29 @@ _start:
         self.symbol_table.borrow_mut().clear();
     }
     
+    *self.current_epilogue.borrow_mut() = String::from(".main_epilogue");
+    
     // Track the most negative offset need
     let mut max_negative_offset = 0;
+    let mut functions = Vec::new();
+    
+    *self.user_functions.borrow_mut() = program.body.iter()
+        .filter_map(|stmt| match stmt {
+            Statement::FunctionDef { name, .. } => Some(name.clone()),
+            _ => None,
+        })
+        .collect();
+    *self.signatures.borrow_mut() = program.body.iter()
+        .filter_map(|stmt| match stmt {
+            Statement::FunctionDef { name, arg_types, return_type, .. } => Some((name.clone(), (arg_types.clone(), return_type.clone()))),
+            _ => None,
+        })
+        .collect();
+    *self.structs.borrow_mut() = struct_declarations(program)?;
     
     // Walk through program to allocate all variables
-    for stmt in &program.body {
-        match stmt {
-            Statement::VarDecl { name, .. } => {
-                let offset = self.allocate_variable_rbp_relative(name);
-                if offset < max_negative_offset {
-                    max_negative_offset = offset;
-                }
-            }
-            Statement::Assign { target, .. } => {
-                let offset = self.ensure_variable_exists_rbp_relative(target);
-                if offset < max_negative_offset {
-                    max_negative_offset = offset;
-                }
-            }
-            Statement::AugAssign { target, .. } => {
-                let offset = self.ensure_variable_exists_rbp_relative(target);
-                if offset < max_negative_offset {
-                    max_negative_offset = offset;
-                }
-            }
-            _ => {}
-        }
-    }
+    self.allocate_block_variables(&program.body, &mut max_negative_offset);
+    self.allocate_stack_lists(&program.body, &[], &mut max_negative_offset);
     
     // Allocate stack space based on the most negative offset
     // Since offsets are negative, need to allocate -max_negative_offset bytes
@@ -876,162 +2650,39 @@ _start:
         let stack_space = (-max_negative_offset + 15) & !15;
         asm.push_str(&format!("    sub rsp, {}        # Allocate {} bytes for locals\n", stack_space, stack_space));
         asm.push_str(&format!("    # Variables span from [rbp - 8] to [rbp - {}]\n", -max_negative_offset));
+        asm.push_str(&self.clear_frame(stack_space));
     }
     
     asm.push_str("\n");
     
     for stmt in &program.body {
+        asm.push_str(&self.statement_marker(stmt));
         match stmt {
-            Statement::Expr(expr) => {
-                let expr_code = self.compile_expression(expr)?;
-                asm.push_str(&expr_code);
-            }
-            Statement::VarDecl { name, value, type_hint: _, span: _ } => {
-                if let Some(offset) = self.get_variable_offset_rbp_relative(name) {
-                    // Compile the value
-                    let value_code = self.compile_expression(value)?;
-                    asm.push_str(&value_code);
-                    
-                    // Store to stack at [rbp - abs_offset]
-                    let abs_offset = self.get_absolute_offset(offset);
-                    asm.push_str(&format!("    mov QWORD PTR [rbp - {}], rax\n", abs_offset));
-                } else {
-                    return Err(format!("Variable {} not allocated", name));
-                }
-            }
-            Statement::Assign { target, value, span: _ } => {
-                asm.push_str(&format!("    # Assignment to {}\n", target));
-                
-                if let Some(offset) = self.get_variable_offset_rbp_relative(target) {
-                    let value_code = self.compile_expression(value)?;
-                    asm.push_str(&value_code);
-                    
-                    let abs_offset = self.get_absolute_offset(offset);
-                    asm.push_str(&format!("    mov QWORD PTR [rbp - {}], rax\n", abs_offset));
-                } else {
-                    return Err(format!("Variable {} not found", target));
-                }
-            }
-            Statement::AugAssign { target, op, value, span: _ } => {
-                if let Some(offset) = self.get_variable_offset_rbp_relative(target) {
-                    let abs_offset = self.get_absolute_offset(offset);
-                    
-                    asm.push_str(&format!("    mov rax, QWORD PTR [rbp - {}]\n", abs_offset));
-                    asm.push_str("    push rax\n");
-                    
-                    // Compile right-hand side
-                    let value_code = self.compile_expression(value)?;
-                    asm.push_str(&value_code);
-                    
-                    asm.push_str("    mov rbx, rax\n");
-                    asm.push_str("    pop rax\n");
-                    
-                    // Perform operation
-                    match op {
-                        Op::Add => asm.push_str("    add rax, rbx\n"),
-                        Op::Sub => asm.push_str("    sub rax, rbx\n"),
-                        Op::Mul => asm.push_str("    imul rax, rbx\n"),
-                        Op::Div => {
-                            asm.push_str("    xor rdx, rdx\n");
-                            asm.push_str("    idiv rbx\n");
-                        }
-                        Op::Mod => {
-                            asm.push_str("    xor rdx, rdx\n");
-                            asm.push_str("    div rbx\n");
-                            asm.push_str("    mov rax, rdx\n");
-                        }
-                        _ => return Err(format!("Unsupported augmented assignment operator: {:?}", op)),
-                    }
-                    
-                    // Store back
-                    asm.push_str(&format!("    mov QWORD PTR [rbp - {}], rax\n", abs_offset));
-                } else {
-                    return Err(format!("Variable {} not found", target));
-                }
+            Statement::Expr(..)
+            | Statement::VarDecl { .. }
+            | Statement::ConstDecl { .. }
+            | Statement::Assign { .. }
+            | Statement::AugAssign { .. }
+            | Statement::IndexAssign { .. }
+            | Statement::FieldAssign { .. } => {
+                asm.push_str(&self.compile_statement_in_context(stmt)?);
             }
             Statement::If { condition, then_block, elif_blocks, else_block, span: _ } => {
-                let label_id = self.get_next_label_id();
-                let else_label = format!("if_else_{}", label_id);
-                let end_label = format!("if_end_{}", label_id);
-                
-                asm.push_str("    # If condition\n");
-                let cond_code = self.compile_expression(condition)?;
-                asm.push_str(&cond_code);
-                
-                asm.push_str("    test rax, rax\n");
-                asm.push_str(&format!("    jz {}\n", else_label));
-                
-                asm.push_str("    # Then block\n");
-                for stmt in then_block {
-                    let stmt_code = self.compile_statement_in_context(stmt)?;
-                    asm.push_str(&stmt_code);
-                }
-                asm.push_str(&format!("    jmp {}\n", end_label));
-                
-                // Process elif blocks
-                for (elif_cond, elif_body) in elif_blocks {
-                    asm.push_str(&format!("{}:\n", else_label));
-                    let elif_cond_code = self.compile_expression(elif_cond)?;
-                    asm.push_str(&elif_cond_code);
-                    asm.push_str("    test rax, rax\n");
-                    asm.push_str(&format!("    jz {}_elif\n", else_label));
-                    
-                    asm.push_str("    # Elif body\n");
-                    for stmt in elif_body {
-                        let stmt_code = self.compile_statement_in_context(stmt)?;
-                        asm.push_str(&stmt_code);
-                    }
-                    asm.push_str(&format!("    jmp {}\n", end_label));
-                    asm.push_str(&format!("{}_elif:\n", else_label));
-                }
-                
-                // Process else block
-                if let Some(else_body) = else_block {
-                    if elif_blocks.is_empty() {
-                        asm.push_str(&format!("{}:\n", else_label));
-                    }
-                    
-                    asm.push_str("    # Else block\n");
-                    for stmt in else_body {
-                        let stmt_code = self.compile_statement_in_context(stmt)?;
-                        asm.push_str(&stmt_code);
-                    }
-                } else if elif_blocks.is_empty() {
-                    asm.push_str(&format!("{}:\n", else_label));
-                }
-                
-                asm.push_str(&format!("{}:\n", end_label));
+                asm.push_str(&self.compile_if(condition, then_block, elif_blocks, else_block)?);
             }
             Statement::While { condition, body, orelse: _, span: _ } => {
-                let label_id = self.get_next_label_id();
-                let while_start = format!("while_start_{}", label_id);
-                let while_end = format!("while_end_{}", label_id);
-                
-                asm.push_str("    # While loop\n");
-                asm.push_str(&format!("{}:\n", while_start));
-                
-                // Compile condition
-                let cond_code = self.compile_expression(condition)?;
-                asm.push_str(&cond_code);
-                
-                asm.push_str("    test rax, rax\n");
-                asm.push_str(&format!("    jz {}\n", while_end));
-                
-                asm.push_str("    # While body\n");
-                for stmt in body {
-                    let stmt_code = self.compile_statement_in_context(stmt)?;
-                    asm.push_str(&stmt_code);
-                }
-                
-                // Jump back to start
-                asm.push_str(&format!("    jmp {}\n", while_start));
-                
-                // End label
-                asm.push_str(&format!("{}:\n", while_end));
+                asm.push_str(&self.compile_while(condition, body)?);
             }
-            Statement::FunctionDef { name, args, body, span: _ } => {
-                // Skip function compilation for now
+            Statement::For { var, iter, body, span } => {
+                asm.push_str(&self.compile_for(var, iter, body, *span)?);
+            }
+            Statement::FunctionDef { name, args, body, span, .. } => {
+                // Functions are emitted after main
                 asm.push_str(&format!("    # Function definition: {}\n", name));
+                functions.push((name, args, body, *span));
+            }
+            Statement::StructDef { name, fields, .. } => {
+                asm.push_str(&format!("    # Struct {}: {}\n", name, fields.join(", ")));
             }
             Statement::HardwareFunctionDef { device, name, args: _, body, span: _ } => {
                 // Handle hardware function definition
@@ -1067,33 +2718,89 @@ _start:
                 // We might need to generate configuration code here
                 // For now, just ignore
             }
-            Statement::Return(expr) => {
-                asm.push_str("    # Return statement\n");
-                if let Some(expr) = expr {
-                    let expr_code = self.compile_expression(expr)?;
-                    asm.push_str(&expr_code);
-                } else {
-                    asm.push_str("    xor rax, rax\n");
-                }
-                asm.push_str("    jmp .main_epilogue\n");
+            Statement::Return(..) => {
+                // Returning from top-level code becomes the process exit status
+                asm.push_str(&self.compile_statement_in_context(stmt)?);
             }
             Statement::Pass => asm.push_str("    # pass\n"),
-            Statement::Break => asm.push_str("    # break\n"),
-            Statement::Continue => asm.push_str("    # continue\n"),
+            Statement::Break => asm.push_str(&self.compile_loop_jump(true)?),
+            Statement::Continue => asm.push_str(&self.compile_loop_jump(false)?),
             Statement::Include { filename, span: _ } => {
                 asm.push_str(&format!("    # Include: {}\n", filename));
             }
+            Statement::Import { module, .. } => {
+                asm.push_str(&format!("    # Import: {}\n", module));
+            }
         }
     }
     
+    asm.push_str(&self.body_end_marker());
+    
     // Main function epilogue
+    // Falling off the end exits with status 0
+    asm.push_str("    xor rax, rax\n");
     asm.push_str("\n.main_epilogue:\n");
     asm.push_str("    mov rsp, rbp\n");
     asm.push_str("    pop rbp\n");
     asm.push_str("    ret\n\n");
     
+    asm.push_str(&self.compile_functions(&functions, program)?);
+    
     // Generate helper functions
     asm.push_str(&self.generate_helper_function());
+    let calls = crate::extension::program_calls(program);
+    let builtin = |func: &str| calls.contains(func) && !self.user_functions.borrow().contains(func);
+    // Without --debug-serial, input() echoes to the framebuffer console
+    let reads_input = self.bios_graphics.is_some() && builtin("input");
+    let uses_disk = self.bios_graphics.is_some()
+        && crate::extension::DISK_BUILTINS.iter().any(|func| builtin(func));
+    let uses_strings = self.bios_graphics.is_some()
+        && crate::extension::STRING_METHODS.iter().any(|(_, _, routine)| builtin(routine));
+    // Structs are allocated straight from the heap
+    let uses_heap = reads_input || uses_disk || uses_strings || (self.bios_graphics.is_some() && builtin("heap_alloc_64"));
+    let uses_text = self.bios_graphics.is_some()
+        && (crate::framebuffer::TEXT_BUILTINS.iter().any(|func| builtin(func)) || (reads_input && !self.debug_serial));
+    if let Some((framebuffer, simd)) = &self.bios_graphics {
+        asm.push_str(&crate::framebuffer::runtime_routines(framebuffer, *simd));
+        if uses_text {
+            asm.push_str(&crate::framebuffer::text_routines());
+        }
+        if !self.embedded_images.is_empty() {
+            asm.push_str(&crate::framebuffer::blit_routine());
+        }
+        if palette_images {
+            asm.push_str(&crate::framebuffer::palette_routine());
+        }
+        if uses_heap {
+            asm.push_str(&crate::framebuffer::heap_routine(self.counts_references, &self.memory));
+        }
+        if builtin("sleep") {
+            asm.push_str(&crate::extension::pit_sleep_routine());
+        }
+        if uses_strings {
+            asm.push_str(crate::extension::STRING_LIBRARY_X86_64);
+        }
+        // Driver modules are linked in for print() under --debug-serial and
+        // input(), or when the program calls their routines directly
+        use crate::extension::EarthngModule;
+        let serial = crate::extension::SerialModule::new();
+        if self.debug_serial || serial.functions().iter().any(|func| builtin(func)) {
+            asm.push_str(&serial.library_code(&Target::Linux64).unwrap_or_default());
+        }
+        // With interrupts on, IRQ1 owns the controller and the keyboard reads its queue
+        let mut keyboard = crate::extension::KeyboardModule::new();
+        if interrupt_driven {
+            keyboard.init(&[Capability::Interrupts]);
+            asm.push_str(&crate::extension::InterruptsModule::new().library_code(&Target::Linux64).unwrap_or_default());
+        }
+        if reads_input || interrupt_driven || keyboard.functions().iter().any(|func| builtin(func)) {
+            asm.push_str(&keyboard.library_code(&Target::Linux64).unwrap_or_default());
+        }
+        let disk = crate::extension::DiskModule::new();
+        if uses_disk || disk.functions().iter().any(|func| builtin(func)) {
+            asm.push_str(&disk.library_code(&Target::Linux64).unwrap_or_default());
+        }
+    }
     
     // Generate hardware library if DSL is available
     if let Some(ref dsl) = *self.hardware_dsl.borrow() {
@@ -1105,6 +2812,20 @@ _start:
     asm.push_str("    .section .data\n");
     asm.push_str("newline:\n");
     asm.push_str("    .byte 10, 0\n\n");
+    asm.push_str("rc_enabled:\n");
+    asm.push_str(&format!("    .byte {}\n\n", self.counts_references as u8));
+    if let Some((framebuffer, _)) = &self.bios_graphics {
+        asm.push_str(&crate::framebuffer::fb_info_data(framebuffer));
+        if uses_text {
+            asm.push_str(&crate::framebuffer::text_data());
+        }
+        if !self.embedded_images.is_empty() {
+            asm.push_str(&crate::framebuffer::image_data(&self.embedded_images, framebuffer));
+        }
+        if uses_heap {
+            asm.push_str(&crate::framebuffer::heap_data(&self.memory));
+        }
+    }
     
     asm.push_str("# String literals\n");
     asm.push_str(&self.generate_string_data());
@@ -1120,55 +2841,103 @@ _start:
         Expr::Number(n, _) => {
             Ok(format!("    # Number: {}\n    mov rax, {}\n", n, n))
         }
-        Expr::String(s, _) => {
-            let label = self.get_string_label(s);
-            Ok(format!("    # String: '{}'\n    lea rax, [{}]\n", s, label))
+        Expr::Boolean(b, _) => {
+            Ok(format!("    # Boolean: {}\n    mov rax, {}\n", b, *b as i32))
+        }
+        Expr::Float(f, _) => {
+            // Doubles travel in rax as raw bits and only enter xmm registers for arithmetic
+            Ok(format!("    # Float: {:?}\n    mov rax, 0x{:016x}\n", f, f.to_bits()))
+        }
+        Expr::String(s, span) => {
+            let label = self.get_string_label(s, *span)?;
+            Ok(format!("    # String: {:?}\n    lea rax, [{}]\n", s, label))
         }
-        Expr::Var(name, _) => {
+        Expr::Var(name, span) => {
             // Use RBP-relative addressing ONLY
             if let Some(offset) = self.get_variable_offset_rbp_relative(name) {
                 let abs_offset = self.get_absolute_offset(offset);
                 Ok(format!("    # Variable: {} at [rbp - {}]\n    mov rax, QWORD PTR [rbp - {}]\n", 
                            name, abs_offset, abs_offset))
             } else {
-                Err(format!("Undefined variable: {}", name))
+                Err(format!("Undefined variable '{}' at {}", name, span))
             }
         }
-        Expr::Call { func, args, kwargs: _, span: _ } if func == "print" => {
+        Expr::Call { func, span, .. } if func == "print" && self.bios_graphics.is_some() && !self.debug_serial => {
+            Err(format!("--bios-mode programs have no stdout for print(); draw text with fb_print() or pass --debug-serial at {}", span))
+        }
+        Expr::Call { func, args, kwargs, span } if func == "print" => {
+            let end = print_end(kwargs, *span)?;
             let mut code = String::new();
             
-            for arg in args {
+            // Like Python: arguments separated by spaces, then a newline
+            for (i, arg) in args.iter().enumerate() {
+                if i > 0 {
+                    let space_label = self.get_string_label(" ", *span)?;
+                    code.push_str(&format!("    lea rdi, [{}]\n", space_label));
+                    code.push_str("    call print_string\n");
+                }
+                
                 match arg {
-                    Expr::String(s, _) => {
-                        let label = self.get_string_label(s);
-                        code.push_str(&format!("    # String: '{}'\n", s));
+                    Expr::String(s, span) => {
+                        let label = self.get_string_label(s, *span)?;
+                        code.push_str(&format!("    # String: {:?}\n", s));
                         code.push_str(&format!("    lea rdi, [{}]\n", label));
                         code.push_str("    call print_string\n");
-                        code.push_str("    call print_newline\n");
                     }
-                    Expr::Number(n, _) => {
-                        code.push_str(&format!("    # Number: {}\n", n));
-                        code.push_str(&format!("    mov rax, {}\n", n));
-                        code.push_str("    call print_decimal\n");
-                    }
-                    Expr::Var(name, _) => {
-                        if let Some(offset) = self.get_variable_offset_rbp_relative(name) {
-                            let abs_offset = self.get_absolute_offset(offset);
-                            code.push_str(&format!("    # Variable: {}\n", name));
-                            code.push_str(&format!("    mov rax, QWORD PTR [rbp - {}]\n", abs_offset));
-                            code.push_str("    call print_decimal\n");
-                        } else {
-                            return Err(format!("Undefined variable: {}", name));
+                    Expr::FString { parts, span } => {
+                        // Interpolation is lowered to one print per part
+                        code.push_str("    # f-string\n");
+                        for part in parts {
+                            match part {
+                                FStringPart::Literal(s) => {
+                                    let label = self.get_string_label(s, *span)?;
+                                    code.push_str(&format!("    lea rdi, [{}]\n", label));
+                                    code.push_str("    call print_string\n");
+                                }
+                                FStringPart::Expr(e) => {
+                                    code.push_str(&self.compile_expression(e)?);
+                                    if self.is_string_expr(e) {
+                                        code.push_str("    mov rdi, rax\n");
+                                        code.push_str("    call print_string\n");
+                                    } else if self.is_float_expr(e) {
+                                        code.push_str("    call print_float\n");
+                                    } else {
+                                        code.push_str("    call print_decimal\n");
+                                    }
+                                }
+                            }
                         }
                     }
                     _ => {
-                        return Err(format!("Unsupported argument type in print: {:?}", arg));
+                        code.push_str(&self.compile_expression(arg)?);
+                        if self.is_string_expr(arg) {
+                            code.push_str("    mov rdi, rax\n");
+                            code.push_str("    call print_string\n");
+                        } else if self.is_float_expr(arg) {
+                            code.push_str("    call print_float\n");
+                        } else {
+                            code.push_str("    call print_decimal\n");
+                        }
                     }
                 }
             }
+            match end {
+                None => code.push_str("    call print_newline\n"),
+                Some(Expr::String(s, _)) if s.is_empty() => {}
+                Some(end) if self.is_string_expr(end) => {
+                    code.push_str(&self.compile_expression(end)?);
+                    code.push_str("    mov rdi, rax\n");
+                    code.push_str("    call print_string\n");
+                }
+                Some(_) => return Err(format!("print() end must be a string at {}", span)),
+            }
             
             Ok(code)
         }
+        Expr::Call { func, kwargs, span, .. } if !kwargs.is_empty() => reject_kwargs(func, kwargs, *span).map(|_| String::new()),
+        Expr::Call { func, args, span, .. } if func == "asm" => inline_asm(args, *span, "#", |name| {
+            self.get_variable_offset_rbp_relative(name).map(|offset| format!("QWORD PTR [rbp - {}]", self.get_absolute_offset(offset)))
+        }),
         Expr::Call { func, args, kwargs: _, span: _ } if func.starts_with("hw_") || 
                                                           func == "write_register" || 
                                                           func == "read_register" ||
@@ -1217,80 +2986,260 @@ _start:
                 Err("Hardware DSL not available for hardware intrinsic".to_string())
             }
         }
-        Expr::Call { func, args, kwargs: _, span: _ } => {
-            // General function call
+        Expr::Call { func, args, kwargs: _, span } if func == "append" && !self.user_functions.borrow().contains(func) => {
+            let [list, value] = args.as_slice() else {
+                return Err(format!("append() takes exactly two arguments at {}", span));
+            };
+            let mut code = self.compile_expression(value)?;
+            code.push_str(&self.push("rax"));
+            code.push_str(&self.compile_expression(list)?);
+            code.push_str("    mov rdi, rax\n");
+            code.push_str(&self.pop("rsi"));
+            code.push_str("    call list_append_64\n");
+            Ok(code)
+        }
+        Expr::Call { func, args, kwargs: _, span } if self.structs.borrow().contains_key(func) => {
+            self.compile_struct(func, args, *span)
+        }
+        Expr::FieldAccess { object, field, span } => {
+            let offset = 8 * self.field_index(object, field, *span)?;
+            let mut code = self.compile_expression(object)?;
+            code.push_str(&format!("    mov rax, QWORD PTR [rax + {}]  # .{}\n", offset, field));
+            Ok(code)
+        }
+        Expr::Call { func, args, kwargs: _, span } if func == "len" && !self.user_functions.borrow().contains(func) => {
+            let [list] = args.as_slice() else {
+                return Err(format!("len() takes exactly one argument at {}", span));
+            };
+            let mut code = self.compile_expression(list)?;
+            code.push_str("    mov rdi, rax\n");
+            if self.is_string_expr(list) {
+                code.push_str("    call str_length_64\n");
+            } else {
+                code.push_str("    call list_len_64\n");
+            }
+            Ok(code)
+        }
+        Expr::List { elements, span: _ } => {
             let mut code = String::new();
-            
-            code.push_str(&format!("    # Function call: {}\n", func));
-            
-            // Process arguments
-            for (i, arg) in args.iter().enumerate() {
-                let arg_code = self.compile_expression(arg)?;
-                code.push_str(&arg_code);
-                
-                // Move to appropriate register (System V ABI)
-                match i {
-                    0 => code.push_str("    mov rdi, rax\n"),
-                    1 => code.push_str("    mov rsi, rax\n"),
-                    2 => code.push_str("    mov rdx, rax\n"),
-                    3 => code.push_str("    mov rcx, rax\n"),
-                    4 => code.push_str("    mov r8, rax\n"),
-                    5 => code.push_str("    mov r9, rax\n"),
-                    _ => code.push_str("    push rax\n"),
+            code.push_str(&format!("    # List literal with {} elements\n", elements.len()));
+            code.push_str(&format!("    mov rdi, {}\n", elements.len()));
+            code.push_str("    call list_create_64\n");
+            code.push_str(&self.push("rax"));
+            for element in elements {
+                code.push_str(&self.compile_expression(element)?);
+                code.push_str("    mov rsi, rax\n");
+                code.push_str("    mov rdi, QWORD PTR [rsp]\n");
+                code.push_str("    call list_append_64\n");
+            }
+            code.push_str(&self.pop("rax"));
+            Ok(code)
+        }
+        Expr::Dict { entries, span } => {
+            let mut code = String::new();
+            code.push_str(&format!("    # Dict literal with {} entries\n", entries.len()));
+            code.push_str("    call dict_create_64\n");
+            code.push_str(&self.push("rax"));
+            for (key, value) in entries {
+                if !self.is_string_expr(key) {
+                    return Err(format!("Dictionary keys must be strings at {}", span));
                 }
+                code.push_str(&self.compile_expression(value)?);
+                code.push_str(&self.push("rax"));
+                code.push_str(&self.compile_expression(key)?);
+                code.push_str("    mov rsi, rax\n");
+                code.push_str(&self.pop("rdx"));
+                code.push_str("    mov rdi, QWORD PTR [rsp]\n");
+                code.push_str("    call dict_set_64\n");
             }
-            
-            // Align stack if needed
-            if args.len() > 6 {
-                let extra_args = args.len() - 6;
-                if extra_args % 2 != 0 {
-                    code.push_str("    sub rsp, 8\n");
+            code.push_str(&self.pop("rax"));
+            Ok(code)
+        }
+        Expr::MethodCall { receiver, method, args, span } => self.compile_method_call(receiver, method, args, *span),
+        Expr::Index { value, index, span: _ } if self.is_string_expr(value) => {
+            let mut code = String::from("    # String subscript\n");
+            code.push_str(&self.compile_call("str_index_64", &[value.as_ref().clone(), index.as_ref().clone()])?);
+            Ok(code)
+        }
+        Expr::Slice { value, start, stop, span } => {
+            if !self.is_string_expr(value) {
+                return Err(format!("Only strings can be sliced at {}", span));
+            }
+            // A missing bound is the start or, clamped, the end
+            let start = start.as_deref().cloned().unwrap_or(Expr::Number(0, *span));
+            let stop = stop.as_deref().cloned().unwrap_or(Expr::Number(i64::MAX, *span));
+            let mut code = String::from("    # String slice\n");
+            code.push_str(&self.compile_call("str_slice_64", &[value.as_ref().clone(), start, stop])?);
+            Ok(code)
+        }
+        Expr::Index { value, index, span } => {
+            // The runtime reads a dict key as a string pointer
+            if self.is_dict_expr(value) && !self.is_string_expr(index) {
+                return Err(format!("Dictionary keys must be strings at {}", span));
+            }
+            let mut code = String::new();
+            code.push_str("    # Subscript\n");
+            code.push_str(&self.compile_expression(value)?);
+            code.push_str(&self.push("rax"));
+            code.push_str(&self.compile_expression(index)?);
+            code.push_str("    mov rsi, rax\n");
+            code.push_str(&self.pop("rdi"));
+            if self.is_dict_expr(value) || self.is_string_expr(index) {
+                // rdx is the found flag; a missing key is a runtime error
+                code.push_str("    call dict_get_64\n");
+                code.push_str("    test rdx, rdx\n");
+                code.push_str("    jz dict_key_error_64\n");
+            } else {
+                code.push_str("    call list_get_64\n");
+            }
+            Ok(code)
+        }
+        Expr::Call { func, args, span, .. } if func == "input" && !self.user_functions.borrow().contains(func) => {
+            self.compile_input(args, *span)
+        }
+        Expr::Call { func, args, span, .. } if crate::extension::OS_BUILTINS.contains(&func.as_str()) && !self.user_functions.borrow().contains(func) => {
+            self.compile_os_call(func, args, *span)
+        }
+        Expr::Call { func, args, span, .. } if crate::extension::STRING_BUILTINS.contains(&func.as_str()) && !self.user_functions.borrow().contains(func) => {
+            self.compile_string_call(func, args, *span)
+        }
+        Expr::Call { func, args, span, .. } if func == "sleep" && !self.user_functions.borrow().contains(func) => {
+            let [ms] = args.as_slice() else {
+                return Err(format!("sleep() takes 1 argument (milliseconds) but {} were given at {}", args.len(), span));
+            };
+            let mut code = self.compile_expression(ms)?;
+            code.push_str("    mov rdi, rax\n");
+            code.push_str("    call sleep_ms_64\n");
+            Ok(code)
+        }
+        Expr::Call { func, args, span, .. } if crate::extension::INTERRUPT_BUILTINS.contains(&func.as_str()) && !self.user_functions.borrow().contains(func) => {
+            if self.bios_graphics.is_none() {
+                return Err(format!("{}() needs the interrupts --bios-mode sets up at {}", func, span));
+            }
+            if !args.is_empty() {
+                return Err(format!("{}() takes no arguments but {} were given at {}", func, args.len(), span));
+            }
+            Ok(format!("    call {}_64\n", func))
+        }
+        Expr::Call { func, args, span, .. } if crate::extension::DISK_BUILTINS.contains(&func.as_str()) && !self.user_functions.borrow().contains(func) => {
+            self.compile_disk(func, args, *span)
+        }
+        Expr::Call { func, args, span, .. } if func == "image" && self.bios_graphics.is_some() && !self.user_functions.borrow().contains(func) => {
+            self.compile_image(args, *span)
+        }
+        Expr::Call { func, args, span, .. } if crate::framebuffer::COLOR_BUILTINS.contains(&func.as_str()) && !self.user_functions.borrow().contains(func) => {
+            let (framebuffer, _) = self.bios_graphics
+                .ok_or_else(|| format!("{}() converts to the BIOS framebuffer's pixel format and needs --bios-mode at {}", func, span))?;
+            let value = crate::framebuffer::color_value(func, args, &framebuffer, *span)?;
+            Ok(format!("    mov rax, {}        # {}()\n", value, func))
+        }
+        Expr::Call { func, args, span, .. } if crate::framebuffer::FRAMEBUFFER_BUILTINS.contains(&func.as_str()) && !self.user_functions.borrow().contains(func) => {
+            let (framebuffer, simd) = self.bios_graphics
+                .ok_or_else(|| format!("{}() draws into the BIOS framebuffer and needs --bios-mode at {}", func, span))?;
+            match crate::framebuffer::inline_call(func, args, &framebuffer, simd, *span)? {
+                Some(code) => Ok(code),
+                // Runtime arguments: call the routine emitted after the helpers
+                None => self.compile_call(func, args),
+            }
+        }
+        Expr::Call { func, args, span, .. } => {
+            // Anything else would only fail in ld, without a position
+            let symbols = self.module_symbols.as_ref().unwrap_or_else(|| crate::extension::builtin_symbols());
+            if !self.user_functions.borrow().contains(func) && !symbols.contains(func) {
+                return Err(format!("Undefined function '{}' at {}", func, span));
+            }
+            self.compile_call(func, args)
+        }
+        Expr::FString { span, .. } => {
+            Err(format!("f-strings can only be used as print arguments at {}", span))
+        }
+        Expr::BoolOp { op, values, span: _ } => self.compile_bool_op(op, values),
+        Expr::UnaryOp { op, operand, span } => {
+            let is_float = self.is_float_expr(operand);
+            let mut code = match op {
+                UnaryOp::Not => self.compile_truthiness(operand)?,
+                _ => self.compile_expression(operand)?,
+            };
+            match op {
+                UnaryOp::Plus => {}
+                UnaryOp::Minus if is_float => code.push_str("    btc rax, 63\n"),
+                UnaryOp::Minus => code.push_str("    neg rax\n"),
+                UnaryOp::Invert if is_float => {
+                    return Err(format!("Bitwise operators need integer operands at {}", span));
+                }
+                UnaryOp::Invert => code.push_str("    not rax\n"),
+                UnaryOp::Not => {
+                    code.push_str("    test rax, rax\n");
+                    code.push_str("    sete al\n");
+                    code.push_str("    movzx rax, al\n");
                 }
             }
+            Ok(code)
+        }
+        Expr::BinOp { left, op, right, span } if self.is_float_expr(expr) => {
+            let mut code = String::new();
+            code.push_str("    # Floating point operation\n");
             
-            // Call the function
-            code.push_str(&format!("    call {}\n", func));
+            code.push_str(&self.compile_as_float(left)?);
+            code.push_str(&self.push("rax"));
+            code.push_str(&self.compile_as_float(right)?);
+            code.push_str("    movq xmm1, rax\n");
+            code.push_str(&self.pop("rax"));
+            code.push_str("    movq xmm0, rax\n");
             
-            // Clean up stack
-            if args.len() > 6 {
-                let extra_args = args.len() - 6;
-                let stack_adjust = extra_args * 8;
-                if extra_args % 2 != 0 {
-                    code.push_str(&format!("    add rsp, {}\n", stack_adjust + 8));
-                } else {
-                    code.push_str(&format!("    add rsp, {}\n", stack_adjust));
-                }
-            }
+            let instruction = match op {
+                Op::Add => "addsd",
+                Op::Sub => "subsd",
+                Op::Mul => "mulsd",
+                Op::Div => "divsd",
+                _ => return Err(format!("Operator {:?} is not supported for floats at {}", op, span)),
+            };
+            code.push_str(&format!("    {} xmm0, xmm1\n", instruction));
+            code.push_str("    movq rax, xmm0\n");
             
             Ok(code)
         }
         Expr::BinOp { left, op, right, span: _ } => {
+            if self.register_allocation {
+                // String variables keep the plain lowering
+                let operand = |name: &str| {
+                    let symbols = self.symbol_table.borrow();
+                    let variable = symbols.get(name).filter(|v| v.type_hint.as_deref() != Some("str"))?;
+                    Some(format!("QWORD PTR [rbp - {}]", self.get_absolute_offset(variable.offset)))
+                };
+                if let Some(code) = crate::regalloc::compile_expression(expr, &crate::regalloc::REGISTERS, &operand) {
+                    return Ok(code);
+                }
+            }
+            
             let mut code = String::new();
             code.push_str("    # Binary operation\n");
             
             // Compile left operand
             let left_code = self.compile_expression(left)?;
             code.push_str(&left_code);
-            code.push_str("    push rax\n");
+            code.push_str(&self.push("rax"));
             
             // Compile right operand
             let right_code = self.compile_expression(right)?;
             code.push_str(&right_code);
             code.push_str("    mov rbx, rax\n");
-            code.push_str("    pop rax\n");
+            code.push_str(&self.pop("rax"));
             
             // Perform operation
             match op {
                 Op::Add => code.push_str("    add rax, rbx\n"),
                 Op::Sub => code.push_str("    sub rax, rbx\n"),
                 Op::Mul => code.push_str("    imul rax, rbx\n"),
+                // Signed, truncating toward zero like the interpreter: cqo
+                // sign-extends rax into rdx before the 128-bit divide
                 Op::Div => {
-                    code.push_str("    xor rdx, rdx\n");
+                    code.push_str("    cqo\n");
                     code.push_str("    idiv rbx\n");
                 }
                 Op::Mod => {
-                    code.push_str("    xor rdx, rdx\n");
-                    code.push_str("    div rbx\n");
+                    code.push_str("    cqo\n");
+                    code.push_str("    idiv rbx\n");
                     code.push_str("    mov rax, rdx\n");
                 }
                 Op::BitAnd => code.push_str("    and rax, rbx\n"),
@@ -1301,22 +3250,94 @@ _start:
             
             Ok(code)
         }
-        Expr::Compare { left, ops, comparators, span: _ } if ops.len() == 1 => {
+        Expr::Compare { left, ops, comparators, span }
+            if matches!(ops.as_slice(), [CompareOp::In | CompareOp::NotIn]) && comparators.len() == 1 =>
+        {
+            if !self.is_string_expr(left) {
+                return Err(format!("Only string keys can be looked up with 'in' at {}", span));
+            }
+            
+            // Membership is a lookup that does not fault on a missing key
             let mut code = String::new();
-            code.push_str("    # Comparison operation\n");
+            code.push_str("    # Dictionary membership test\n");
+            code.push_str(&self.compile_expression(&comparators[0])?);
+            code.push_str(&self.push("rax"));
+            code.push_str(&self.compile_expression(left)?);
+            code.push_str("    mov rsi, rax\n");
+            code.push_str(&self.pop("rdi"));
+            code.push_str("    call dict_find_index_64\n");
+            code.push_str("    cmp rax, -1\n");
+            let set = if ops[0] == CompareOp::In { "setne" } else { "sete" };
+            code.push_str(&format!("    {} al\n", set));
+            code.push_str("    movzx rax, al\n");
+            Ok(code)
+        }
+        Expr::Compare { left, ops, comparators, span }
+            if ops.len() == 1 && comparators.len() == 1 && comparators.iter().chain([left.as_ref()]).any(|e| self.is_float_expr(e)) =>
+        {
+            let right_expr = &comparators[0];
+            if self.is_string_expr(left) || self.is_string_expr(right_expr) {
+                return Err(format!("Cannot compare a string with a number at {}", span));
+            }
             
-            // Compile left operand
-            let left_code = self.compile_expression(left)?;
-            code.push_str(&left_code);
-            code.push_str("    push rax\n");
+            let mut code = String::new();
+            code.push_str("    # Floating point comparison\n");
+            code.push_str(&self.compile_as_float(left)?);
+            code.push_str(&self.push("rax"));
+            code.push_str(&self.compile_as_float(right_expr)?);
+            code.push_str("    movq xmm1, rax\n");
+            code.push_str(&self.pop("rax"));
+            code.push_str("    movq xmm0, rax\n");
+            code.push_str("    comisd xmm0, xmm1\n");
             
-            if let Some(right_expr) = comparators.get(0) {
+            // comisd reports through the unsigned flags
+            let set = match ops[0] {
+                CompareOp::Lt => "setb",
+                CompareOp::Gt => "seta",
+                CompareOp::Le => "setbe",
+                CompareOp::Ge => "setae",
+                CompareOp::Eq => "sete",
+                CompareOp::Ne => "setne",
+                CompareOp::In | CompareOp::NotIn | CompareOp::Is | CompareOp::IsNot => {
+                    return Err(format!("Comparison operator {:?} not supported", ops[0]));
+                }
+            };
+            code.push_str(&format!("    {} al\n", set));
+            code.push_str("    movzx rax, al\n");
+            
+            Ok(code)
+        }
+        Expr::Compare { left, ops, comparators, span } if ops.len() == 1 => {
+            let mut code = String::new();
+            code.push_str("    # Comparison operation\n");
+            
+            // Compile left operand
+            let left_code = self.compile_expression(left)?;
+            code.push_str(&left_code);
+            code.push_str(&self.push("rax"));
+            
+            if let Some(right_expr) = comparators.get(0) {
+                let left_is_string = self.is_string_expr(left);
+                let right_is_string = self.is_string_expr(right_expr);
+                if (left_is_string && self.is_numeric_expr(right_expr))
+                    || (right_is_string && self.is_numeric_expr(left)) {
+                    return Err(format!("Cannot compare a string with a number at {}", span));
+                }
+                
                 let right_code = self.compile_expression(right_expr)?;
                 code.push_str(&right_code);
-                code.push_str("    mov rbx, rax\n");
-                code.push_str("    pop rax\n");
                 
-                code.push_str("    cmp rax, rbx\n");
+                if left_is_string || right_is_string {
+                    // Compare contents, not pointers
+                    code.push_str("    mov rsi, rax\n");
+                    code.push_str(&self.pop("rdi"));
+                    code.push_str("    call str_cmp\n");
+                    code.push_str("    cmp rax, 0\n");
+                } else {
+                    code.push_str("    mov rbx, rax\n");
+                    code.push_str(&self.pop("rax"));
+                    code.push_str("    cmp rax, rbx\n");
+                }
                 
                 match ops[0] {
                     CompareOp::Lt => {
@@ -1348,7 +3369,7 @@ _start:
                     }
                 }
             } else {
-                code.push_str("    pop rax\n");
+                code.push_str(&self.pop("rax"));
                 return Err("Missing comparator".to_string());
             }
             
@@ -1389,6 +3410,1100 @@ fn function_epilogue(&self, _func: &BackendFunction) -> String {
     }
 }
 
+/// RISC-V Linux system call numbers
+const RISCV_SYS_WRITE: i32 = 64;
+const RISCV_SYS_EXIT: i32 = 93;
+const RISCV_SYS_NANOSLEEP: i32 = 101;
+
+/// RV64GC Linux backend emitting GNU as syntax.
+///
+/// Code is accumulator style: every expression leaves its value in `a0`,
+/// and variables live below the saved `ra`/`s0` pair of `main`'s frame.
+pub struct RiscV64Backend {
+    strings: StringPool,
+    variables: HashMap<String, i32>, // offset from s0
+    string_variables: HashSet<String>,
+    label_counter: u32,
+}
+
+impl RiscV64Backend {
+    pub fn new() -> Self {
+        Self {
+            strings: StringPool::default(),
+            variables: HashMap::new(),
+            string_variables: HashSet::new(),
+            label_counter: 0,
+        }
+    }
+    
+    fn next_label_id(&mut self) -> u32 {
+        self.label_counter += 1;
+        self.label_counter
+    }
+    
+    fn variable_offset(&mut self, name: &str) -> i32 {
+        // ra and s0 occupy s0-8 and s0-16
+        let next = -24 - 8 * self.variables.len() as i32;
+        *self.variables.entry(name.to_string()).or_insert(next)
+    }
+    
+    fn is_string_expr(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::String(_, _) => true,
+            Expr::Var(name, _) => self.string_variables.contains(name),
+            _ => false,
+        }
+    }
+    
+    fn compile_store(&mut self, name: &str, value: &Expr) -> Result<String, String> {
+        let mut code = self.compile_expression(value)?;
+        if self.is_string_expr(value) {
+            self.string_variables.insert(name.to_string());
+        } else {
+            self.string_variables.remove(name);
+        }
+        let offset = self.variable_offset(name);
+        code.push_str(&format!("    sd a0, {}(s0)\n", offset));
+        Ok(code)
+    }
+    
+    /// Leaves a0 nonzero exactly when `expr` is truthy; strings are true when non-empty
+    fn compile_truthiness(&mut self, expr: &Expr) -> Result<String, String> {
+        let mut code = self.compile_expression(expr)?;
+        if self.is_string_expr(expr) {
+            code.push_str("    lbu a0, 0(a0)\n");
+        }
+        Ok(code)
+    }
+    
+    fn compile_bool_op(&mut self, op: &BoolOp, values: &[Expr]) -> Result<String, String> {
+        let label_id = self.next_label_id();
+        let short_label = format!(".Lbool_short_{}", label_id);
+        let end_label = format!(".Lbool_end_{}", label_id);
+        let (skip_branch, short_value, full_value) = match op {
+            BoolOp::And => ("beqz", 0, 1),
+            BoolOp::Or => ("bnez", 1, 0),
+        };
+        
+        let mut code = String::new();
+        for value in values {
+            code.push_str(&self.compile_truthiness(value)?);
+            code.push_str(&format!("    {} a0, {}\n", skip_branch, short_label));
+        }
+        code.push_str(&format!("    li a0, {}\n", full_value));
+        code.push_str(&format!("    j {}\n", end_label));
+        code.push_str(&format!("{}:\n", short_label));
+        code.push_str(&format!("    li a0, {}\n", short_value));
+        code.push_str(&format!("{}:\n", end_label));
+        Ok(code)
+    }
+    
+    fn compile_statement(&mut self, stmt: &Statement) -> Result<String, String> {
+        match stmt {
+            Statement::Expr(expr) => self.compile_expression(expr),
+            Statement::VarDecl { name, value, .. } => {
+                let mut code = format!("    # Variable declaration: {}\n", name);
+                code.push_str(&self.compile_store(name, value)?);
+                Ok(code)
+            }
+            Statement::Assign { target, value, .. } => {
+                let mut code = format!("    # Assignment to {}\n", target);
+                code.push_str(&self.compile_store(target, value)?);
+                Ok(code)
+            }
+            Statement::AugAssign { target, op, value, span } => {
+                let current = Expr::Var(target.clone(), *span);
+                let combined = Expr::BinOp {
+                    left: Box::new(current),
+                    op: op.clone(),
+                    right: Box::new(value.clone()),
+                    span: *span,
+                };
+                let mut code = format!("    # Augmented assignment to {}\n", target);
+                code.push_str(&self.compile_store(target, &combined)?);
+                Ok(code)
+            }
+            Statement::For { var, iter, body, span } => self.compile_for(var, iter, body, *span),
+            Statement::Pass => Ok(String::new()),
+            Statement::Import { module, .. } => Ok(format!("    # Import: {}\n", module)),
+            Statement::ConstDecl { name, span, .. } => Err(format!("Constant '{}' must be resolved before code generation at {}", name, span)),
+            other => Err(format!("{} are not supported by the riscv64 backend at {}", other.construct(), other.span())),
+        }
+    }
+    
+    /// Lower `for var in range(...)` onto hidden counter, stop and step slots
+    fn compile_for(&mut self, var: &str, iter: &Expr, body: &[Statement], span: Span) -> Result<String, String> {
+        let (start, stop, step) = range_arguments(iter, span)?;
+        let label_id = self.next_label_id();
+        let [counter, stop_slot, step_slot] = ["counter", "stop", "step"]
+            .map(|slot| self.variable_offset(&format!("@range{}.{}", label_id, slot)));
+        let var_slot = self.variable_offset(var);
+        self.string_variables.remove(var);
+        let step_value = literal_integer(&step);
+        
+        let mut code = format!("    # For loop over range() into {}\n", var);
+        code.push_str(&self.compile_expression(&start)?);
+        code.push_str(&format!("    sd a0, {}(s0)\n", counter));
+        code.push_str(&self.compile_expression(&stop)?);
+        code.push_str(&format!("    sd a0, {}(s0)\n", stop_slot));
+        if step_value.is_none() {
+            let message = self.strings.intern(RANGE_STEP_ERROR);
+            code.push_str(&self.compile_expression(&step)?);
+            code.push_str(&format!("    bnez a0, .Lfor_step_ok_{}\n", label_id));
+            code.push_str(&format!("    la a0, {}\n", message));
+            code.push_str("    j runtime_error\n");
+            code.push_str(&format!(".Lfor_step_ok_{}:\n", label_id));
+            code.push_str(&format!("    sd a0, {}(s0)\n", step_slot));
+        }
+        
+        code.push_str(&format!(".Lfor_start_{}:\n", label_id));
+        code.push_str(&format!("    ld t0, {}(s0)\n", counter));
+        code.push_str(&format!("    ld t1, {}(s0)\n", stop_slot));
+        match step_value {
+            Some(n) if n > 0 => code.push_str(&format!("    bge t0, t1, .Lfor_end_{}\n", label_id)),
+            Some(_) => code.push_str(&format!("    ble t0, t1, .Lfor_end_{}\n", label_id)),
+            None => {
+                code.push_str(&format!("    ld t2, {}(s0)\n", step_slot));
+                code.push_str(&format!("    bltz t2, .Lfor_down_{}\n", label_id));
+                code.push_str(&format!("    bge t0, t1, .Lfor_end_{}\n", label_id));
+                code.push_str(&format!("    j .Lfor_body_{}\n", label_id));
+                code.push_str(&format!(".Lfor_down_{}:\n", label_id));
+                code.push_str(&format!("    ble t0, t1, .Lfor_end_{}\n", label_id));
+                code.push_str(&format!(".Lfor_body_{}:\n", label_id));
+            }
+        }
+        code.push_str(&format!("    sd t0, {}(s0)\n", var_slot));
+        
+        for stmt in body {
+            code.push_str(&self.compile_statement(stmt)?);
+        }
+        
+        match step_value {
+            Some(n) => code.push_str(&format!("    li t1, {}\n", n)),
+            None => code.push_str(&format!("    ld t1, {}(s0)\n", step_slot)),
+        }
+        code.push_str(&format!("    ld t0, {}(s0)\n", counter));
+        code.push_str("    add t0, t0, t1\n");
+        code.push_str(&format!("    sd t0, {}(s0)\n", counter));
+        code.push_str(&format!("    j .Lfor_start_{}\n", label_id));
+        code.push_str(&format!(".Lfor_end_{}:\n", label_id));
+        Ok(code)
+    }
+    
+    fn compile_print(&mut self, args: &[Expr], end: Option<&Expr>, span: Span) -> Result<String, String> {
+        let mut code = String::new();
+        
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                let space = self.strings.intern(" ");
+                code.push_str(&format!("    la a0, {}\n", space));
+                code.push_str("    call print_string\n");
+            }
+            
+            code.push_str(&self.compile_expression(arg)?);
+            if self.is_string_expr(arg) {
+                code.push_str("    call print_string\n");
+            } else {
+                code.push_str("    call print_decimal\n");
+            }
+        }
+        
+        match end {
+            None => code.push_str("    call print_newline\n"),
+            Some(Expr::String(s, _)) if s.is_empty() => {}
+            Some(end) if self.is_string_expr(end) => {
+                code.push_str(&self.compile_expression(end)?);
+                code.push_str("    call print_string\n");
+            }
+            Some(_) => return Err(format!("print() end must be a string at {}", span)),
+        }
+        Ok(code)
+    }
+    
+    fn generate_runtime(&self) -> String {
+        let mut rt = String::new();
+        
+        // print_string: a0 = NUL terminated string
+        rt.push_str("print_string:\n");
+        rt.push_str("    mv a1, a0\n");
+        rt.push_str("    li a2, 0\n");
+        rt.push_str("1:\n");
+        rt.push_str("    add t0, a1, a2\n");
+        rt.push_str("    lbu t1, 0(t0)\n");
+        rt.push_str("    beqz t1, 2f\n");
+        rt.push_str("    addi a2, a2, 1\n");
+        rt.push_str("    j 1b\n");
+        rt.push_str("2:\n");
+        rt.push_str("    li a0, 1\n");
+        rt.push_str(&format!("    li a7, {}\n", RISCV_SYS_WRITE));
+        rt.push_str("    ecall\n");
+        rt.push_str("    ret\n\n");
+        
+        // print_decimal: a0 = signed value, digits are built backwards on the stack
+        rt.push_str("print_decimal:\n");
+        rt.push_str("    addi sp, sp, -32\n");
+        rt.push_str("    mv t0, a0\n");
+        rt.push_str("    addi t1, sp, 32\n");
+        rt.push_str("    li t3, 0\n");
+        rt.push_str("    bgez t0, 1f\n");
+        rt.push_str("    li t3, 1\n");
+        rt.push_str("    neg t0, t0\n");
+        rt.push_str("1:\n");
+        rt.push_str("    li t2, 10\n");
+        rt.push_str("2:\n");
+        rt.push_str("    remu t4, t0, t2\n");
+        rt.push_str("    divu t0, t0, t2\n");
+        rt.push_str("    addi t4, t4, 48\n");
+        rt.push_str("    addi t1, t1, -1\n");
+        rt.push_str("    sb t4, 0(t1)\n");
+        rt.push_str("    bnez t0, 2b\n");
+        rt.push_str("    beqz t3, 3f\n");
+        rt.push_str("    li t4, 45\n");
+        rt.push_str("    addi t1, t1, -1\n");
+        rt.push_str("    sb t4, 0(t1)\n");
+        rt.push_str("3:\n");
+        rt.push_str("    li a0, 1\n");
+        rt.push_str("    mv a1, t1\n");
+        rt.push_str("    addi a2, sp, 32\n");
+        rt.push_str("    sub a2, a2, t1\n");
+        rt.push_str(&format!("    li a7, {}\n", RISCV_SYS_WRITE));
+        rt.push_str("    ecall\n");
+        rt.push_str("    addi sp, sp, 32\n");
+        rt.push_str("    ret\n\n");
+        
+        rt.push_str("print_newline:\n");
+        rt.push_str("    li a0, 1\n");
+        rt.push_str("    la a1, newline\n");
+        rt.push_str("    li a2, 1\n");
+        rt.push_str(&format!("    li a7, {}\n", RISCV_SYS_WRITE));
+        rt.push_str("    ecall\n");
+        rt.push_str("    ret\n\n");
+        
+        // runtime_error: a0 = message, reported on stderr before exiting with status 1
+        rt.push_str("runtime_error:\n");
+        rt.push_str("    mv t3, a0\n");
+        rt.push_str("    li a0, 2\n");
+        rt.push_str("    la a1, runtime_error_prefix\n");
+        rt.push_str("    li a2, 15\n");
+        rt.push_str(&format!("    li a7, {}\n", RISCV_SYS_WRITE));
+        rt.push_str("    ecall\n");
+        rt.push_str("    mv a1, t3\n");
+        rt.push_str("    li a2, 0\n");
+        rt.push_str("1:\n");
+        rt.push_str("    add t0, a1, a2\n");
+        rt.push_str("    lbu t1, 0(t0)\n");
+        rt.push_str("    beqz t1, 2f\n");
+        rt.push_str("    addi a2, a2, 1\n");
+        rt.push_str("    j 1b\n");
+        rt.push_str("2:\n");
+        rt.push_str("    li a0, 2\n");
+        rt.push_str("    ecall\n");
+        rt.push_str("    li a0, 2\n");
+        rt.push_str("    la a1, newline\n");
+        rt.push_str("    li a2, 1\n");
+        rt.push_str("    ecall\n");
+        rt.push_str("    li a0, 1\n");
+        rt.push_str(&format!("    li a7, {}\n", RISCV_SYS_EXIT));
+        rt.push_str("    ecall\n\n");
+        
+        // sleep_ms: a0 = milliseconds, slept through a struct timespec on the stack;
+        // returns 0 or -errno like nanosleep
+        rt.push_str("sleep_ms:\n");
+        rt.push_str("    bgtz a0, 1f\n");
+        rt.push_str("    li a0, 0\n");
+        rt.push_str("    ret\n");
+        rt.push_str("1:\n");
+        rt.push_str("    addi sp, sp, -16\n");
+        rt.push_str("    li t0, 1000\n");
+        rt.push_str("    div t1, a0, t0\n");
+        rt.push_str("    rem t2, a0, t0\n");
+        rt.push_str("    li t0, 1000000\n");
+        rt.push_str("    mul t2, t2, t0\n");
+        rt.push_str("    sd t1, 0(sp)\n");
+        rt.push_str("    sd t2, 8(sp)\n");
+        rt.push_str("    mv a0, sp\n");
+        rt.push_str("    li a1, 0\n");
+        rt.push_str(&format!("    li a7, {}\n", RISCV_SYS_NANOSLEEP));
+        rt.push_str("    ecall\n");
+        rt.push_str("    addi sp, sp, 16\n");
+        rt.push_str("    ret\n\n");
+        
+        rt
+    }
+}
+
+impl Default for RiscV64Backend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for RiscV64Backend {
+    fn name(&self) -> &str {
+        Target::RiscV64.name()
+    }
+    
+    fn target(&self) -> Target {
+        Target::RiscV64
+    }
+    
+    fn generate_header(&self) -> String {
+        let mut header = String::new();
+        header.push_str("    .text\n");
+        header.push_str("    .globl _start\n\n");
+        header.push_str("_start:\n");
+        header.push_str("    call main\n");
+        header.push_str(&format!("    li a7, {}         # exit with main's result\n", RISCV_SYS_EXIT));
+        header.push_str("    ecall\n\n");
+        header
+    }
+    
+    fn format(&self) -> &'static str {
+        "elf64"
+    }
+    
+    fn supported_capabilities(&self) -> Vec<Capability> {
+        vec![
+            Capability::Linux,
+            Capability::RiscV64,
+            Capability::VirtualMemory,
+            Capability::Heap,
+            Capability::Filesystem,
+            Capability::WritableMemory,
+        ]
+    }
+    
+    fn compile_program(&mut self, program: &Program) -> Result<String, String> {
+        self.strings = StringPool::default();
+        self.variables.clear();
+        self.string_variables.clear();
+        self.label_counter = 0;
+        
+        // The body decides how many slots the frame needs, so it is compiled first
+        let mut body = String::new();
+        for stmt in &program.body {
+            body.push_str(&crate::size::statement_marker("#", stmt));
+            body.push_str(&self.compile_statement(stmt)?);
+        }
+        body.push_str(&crate::size::body_end_marker("#"));
+        let frame_size = (16 + 8 * self.variables.len() as i32 + 15) & !15;
+        
+        let mut asm = self.generate_header();
+        
+        asm.push_str("main:\n");
+        asm.push_str(&format!("    addi sp, sp, -{}\n", frame_size));
+        asm.push_str(&format!("    sd ra, {}(sp)\n", frame_size - 8));
+        asm.push_str(&format!("    sd s0, {}(sp)\n", frame_size - 16));
+        asm.push_str(&format!("    addi s0, sp, {}\n\n", frame_size));
+        asm.push_str(&body);
+        asm.push_str("\n    li a0, 0\n");
+        asm.push_str(&format!("    ld ra, {}(sp)\n", frame_size - 8));
+        asm.push_str(&format!("    ld s0, {}(sp)\n", frame_size - 16));
+        asm.push_str(&format!("    addi sp, sp, {}\n", frame_size));
+        asm.push_str("    ret\n\n");
+        
+        asm.push_str(&self.generate_runtime());
+        
+        asm.push_str("    .section .rodata\n");
+        asm.push_str("newline:\n");
+        asm.push_str("    .byte 10, 0\n");
+        asm.push_str("runtime_error_prefix:\n");
+        asm.push_str("    .ascii \"Runtime error: \"\n\n");
+        asm.push_str("# String literals\n");
+        asm.push_str(&self.strings.data_directives("#"));
+        
+        Ok(asm)
+    }
+    
+    fn compile_expression(&mut self, expr: &Expr) -> Result<String, String> {
+        match expr {
+            Expr::Number(n, _) => Ok(format!("    li a0, {}\n", n)),
+            Expr::Boolean(b, _) => Ok(format!("    li a0, {}\n", *b as i32)),
+            Expr::String(s, span) => {
+                if s.contains('\0') {
+                    return Err(format!("String literal contains a null byte at {}", span));
+                }
+                let label = self.strings.intern(s);
+                Ok(format!("    la a0, {}  # {:?}\n", label, s))
+            }
+            Expr::Var(name, span) => match self.variables.get(name) {
+                Some(offset) => Ok(format!("    ld a0, {}(s0)  # {}\n", offset, name)),
+                None => Err(format!("Undefined variable '{}' at {}", name, span)),
+            },
+            Expr::Call { func, args, kwargs, span } if func == "print" => self.compile_print(args, print_end(kwargs, *span)?, *span),
+            Expr::Call { func, kwargs, span, .. } if !kwargs.is_empty() => reject_kwargs(func, kwargs, *span).map(|_| String::new()),
+            Expr::Call { func, args, span, .. } if func == "asm" => {
+                inline_asm(args, *span, "#", |name| self.variables.get(name).map(|offset| format!("{}(s0)", offset)))
+            }
+            Expr::Call { func, args, span, .. } if func == "sleep" => {
+                let [ms] = args.as_slice() else {
+                    return Err(format!("sleep() takes 1 argument (milliseconds) but {} were given at {}", args.len(), span));
+                };
+                let mut code = self.compile_expression(ms)?;
+                code.push_str("    call sleep_ms\n");
+                Ok(code)
+            }
+            Expr::UnaryOp { op: UnaryOp::Minus, operand, .. } => {
+                let mut code = self.compile_expression(operand)?;
+                code.push_str("    neg a0, a0\n");
+                Ok(code)
+            }
+            Expr::UnaryOp { op: UnaryOp::Not, operand, .. } => {
+                let mut code = self.compile_truthiness(operand)?;
+                code.push_str("    seqz a0, a0\n");
+                Ok(code)
+            }
+            Expr::BoolOp { op, values, .. } => self.compile_bool_op(op, values),
+            Expr::UnaryOp { op: UnaryOp::Plus, operand, .. } => self.compile_expression(operand),
+            Expr::BinOp { left, op, right, span } => {
+                let instruction = match op {
+                    Op::Add => "add",
+                    Op::Sub => "sub",
+                    Op::Mul => "mul",
+                    Op::Div | Op::FloorDiv => "div",
+                    Op::Mod => "rem",
+                    Op::BitAnd => "and",
+                    Op::BitOr => "or",
+                    Op::BitXor => "xor",
+                    Op::Pow => return Err(format!("The ** operator is not supported by the riscv64 backend at {}", span)),
+                };
+                
+                // Left operand is kept on the stack while the right one is evaluated
+                let mut code = self.compile_expression(left)?;
+                code.push_str("    addi sp, sp, -16\n");
+                code.push_str("    sd a0, 0(sp)\n");
+                code.push_str(&self.compile_expression(right)?);
+                code.push_str("    ld t0, 0(sp)\n");
+                code.push_str("    addi sp, sp, 16\n");
+                code.push_str(&format!("    {} a0, t0, a0\n", instruction));
+                Ok(code)
+            }
+            other => Err(format!("{} are not supported by the riscv64 backend at {}", other.construct(), other.span())),
+        }
+    }
+    
+    fn function_prologue(&self, func: &BackendFunction) -> String {
+        format!("{}:\n    addi sp, sp, -16\n    sd ra, 8(sp)\n    sd s0, 0(sp)\n    addi s0, sp, 16\n", func.name)
+    }
+    
+    fn function_epilogue(&self, _func: &BackendFunction) -> String {
+        "    ld ra, 8(sp)\n    ld s0, 0(sp)\n    addi sp, sp, 16\n    ret\n".to_string()
+    }
+    
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// AArch64 Linux system call numbers
+const AARCH64_SYS_WRITE: i32 = 64;
+const AARCH64_SYS_EXIT: i32 = 93;
+const AARCH64_SYS_NANOSLEEP: i32 = 101;
+
+/// Integer argument registers of the AArch64 procedure call standard
+const AAPCS64_ARG_REGISTERS: usize = 8;
+
+/// AArch64 Linux backend emitting GNU as syntax.
+///
+/// Like the x86 backend the code is accumulator style, with every expression
+/// leaving its value in `x0`. Each function saves `x29`/`x30` at the bottom of
+/// its frame and keeps its variables just above them, addressed from `x29`.
+pub struct Aarch64LinuxBackend {
+    strings: StringPool,
+    variables: HashMap<String, i32>, // offset from x29
+    string_variables: HashSet<String>,
+    user_functions: HashSet<String>,
+    current_epilogue: String,
+    label_counter: u32,
+    logger: crate::logging::Logger,
+}
+
+impl Aarch64LinuxBackend {
+    pub fn new() -> Self {
+        Self {
+            strings: StringPool::default(),
+            variables: HashMap::new(),
+            string_variables: HashSet::new(),
+            user_functions: HashSet::new(),
+            current_epilogue: String::from(".Lmain_epilogue"),
+            label_counter: 0,
+            logger: crate::logging::Logger::default(),
+        }
+    }
+    
+    /// Report the code generation of each function to `logger` as detail
+    pub fn with_logger(mut self, logger: crate::logging::Logger) -> Self {
+        self.logger = logger;
+        self
+    }
+    
+    fn next_label_id(&mut self) -> u32 {
+        self.label_counter += 1;
+        self.label_counter
+    }
+    
+    fn variable_offset(&mut self, name: &str) -> i32 {
+        let next = 16 + 8 * self.variables.len() as i32;
+        *self.variables.entry(name.to_string()).or_insert(next)
+    }
+    
+    fn is_string_expr(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::String(_, _) => true,
+            Expr::Var(name, _) => self.string_variables.contains(name),
+            _ => false,
+        }
+    }
+    
+    /// Materialise a 64-bit constant with movz/movk
+    fn load_immediate(n: i64) -> String {
+        let bits = n as u64;
+        let mut code = format!("    movz x0, #{}\n", bits & 0xFFFF);
+        for shift in [16, 32, 48] {
+            let chunk = (bits >> shift) & 0xFFFF;
+            if chunk != 0 {
+                code.push_str(&format!("    movk x0, #{}, lsl #{}\n", chunk, shift));
+            }
+        }
+        code
+    }
+    
+    fn compile_store(&mut self, name: &str, value: &Expr) -> Result<String, String> {
+        let mut code = self.compile_expression(value)?;
+        if self.is_string_expr(value) {
+            self.string_variables.insert(name.to_string());
+        } else {
+            self.string_variables.remove(name);
+        }
+        let offset = self.variable_offset(name);
+        code.push_str(&format!("    str x0, [x29, #{}]\n", offset));
+        Ok(code)
+    }
+    
+    /// Leaves x0 nonzero exactly when `expr` is truthy; strings are true when non-empty
+    fn compile_truthiness(&mut self, expr: &Expr) -> Result<String, String> {
+        let mut code = self.compile_expression(expr)?;
+        if self.is_string_expr(expr) {
+            code.push_str("    ldrb w0, [x0]\n");
+        }
+        Ok(code)
+    }
+    
+    fn compile_bool_op(&mut self, op: &BoolOp, values: &[Expr]) -> Result<String, String> {
+        let label_id = self.next_label_id();
+        let short_label = format!(".Lbool_short_{}", label_id);
+        let end_label = format!(".Lbool_end_{}", label_id);
+        let (skip_branch, short_value, full_value) = match op {
+            BoolOp::And => ("cbz", 0, 1),
+            BoolOp::Or => ("cbnz", 1, 0),
+        };
+        
+        let mut code = String::new();
+        for value in values {
+            code.push_str(&self.compile_truthiness(value)?);
+            code.push_str(&format!("    {} x0, {}\n", skip_branch, short_label));
+        }
+        code.push_str(&format!("    mov x0, #{}\n", full_value));
+        code.push_str(&format!("    b {}\n", end_label));
+        code.push_str(&format!("{}:\n", short_label));
+        code.push_str(&format!("    mov x0, #{}\n", short_value));
+        code.push_str(&format!("{}:\n", end_label));
+        Ok(code)
+    }
+    
+    fn compile_statement(&mut self, stmt: &Statement) -> Result<String, String> {
+        match stmt {
+            Statement::Expr(expr) => self.compile_expression(expr),
+            Statement::VarDecl { name, value, .. } => {
+                let mut code = format!("    // Variable declaration: {}\n", name);
+                code.push_str(&self.compile_store(name, value)?);
+                Ok(code)
+            }
+            Statement::Assign { target, value, .. } => {
+                let mut code = format!("    // Assignment to {}\n", target);
+                code.push_str(&self.compile_store(target, value)?);
+                Ok(code)
+            }
+            Statement::AugAssign { target, op, value, span } => {
+                let combined = Expr::BinOp {
+                    left: Box::new(Expr::Var(target.clone(), *span)),
+                    op: op.clone(),
+                    right: Box::new(value.clone()),
+                    span: *span,
+                };
+                let mut code = format!("    // Augmented assignment to {}\n", target);
+                code.push_str(&self.compile_store(target, &combined)?);
+                Ok(code)
+            }
+            Statement::Return(value, _) => {
+                let mut code = match value {
+                    Some(expr) => self.compile_expression(expr)?,
+                    None => String::from("    mov x0, #0\n"),
+                };
+                code.push_str(&format!("    b {}\n", self.current_epilogue));
+                Ok(code)
+            }
+            Statement::For { var, iter, body, span } => self.compile_for(var, iter, body, *span),
+            Statement::Pass => Ok(String::new()),
+            Statement::Import { module, .. } => Ok(format!("    // Import: {}\n", module)),
+            Statement::ConstDecl { name, span, .. } => Err(format!("Constant '{}' must be resolved before code generation at {}", name, span)),
+            Statement::FunctionDef { span, .. } => Err(format!("Nested function definitions are not supported at {}", span)),
+            other => Err(format!("{} are not supported by the aarch64 backend at {}", other.construct(), other.span())),
+        }
+    }
+    
+    /// Lower `for var in range(...)` onto hidden counter, stop and step slots
+    fn compile_for(&mut self, var: &str, iter: &Expr, body: &[Statement], span: Span) -> Result<String, String> {
+        let (start, stop, step) = range_arguments(iter, span)?;
+        let label_id = self.next_label_id();
+        let [counter, stop_slot, step_slot] = ["counter", "stop", "step"]
+            .map(|slot| self.variable_offset(&format!("@range{}.{}", label_id, slot)));
+        let var_slot = self.variable_offset(var);
+        self.string_variables.remove(var);
+        let step_value = literal_integer(&step);
+        
+        let mut code = format!("    // For loop over range() into {}\n", var);
+        code.push_str(&self.compile_expression(&start)?);
+        code.push_str(&format!("    str x0, [x29, #{}]\n", counter));
+        code.push_str(&self.compile_expression(&stop)?);
+        code.push_str(&format!("    str x0, [x29, #{}]\n", stop_slot));
+        if step_value.is_none() {
+            let message = self.strings.intern(RANGE_STEP_ERROR);
+            code.push_str(&self.compile_expression(&step)?);
+            code.push_str(&format!("    cbnz x0, .Lfor_step_ok_{}\n", label_id));
+            code.push_str(&format!("    adrp x0, {}\n    add x0, x0, :lo12:{}\n", message, message));
+            code.push_str("    b runtime_error\n");
+            code.push_str(&format!(".Lfor_step_ok_{}:\n", label_id));
+            code.push_str(&format!("    str x0, [x29, #{}]\n", step_slot));
+        }
+        
+        code.push_str(&format!(".Lfor_start_{}:\n", label_id));
+        code.push_str(&format!("    ldr x9, [x29, #{}]\n", counter));
+        code.push_str(&format!("    ldr x10, [x29, #{}]\n", stop_slot));
+        code.push_str("    cmp x9, x10\n");
+        match step_value {
+            Some(n) if n > 0 => code.push_str(&format!("    b.ge .Lfor_end_{}\n", label_id)),
+            Some(_) => code.push_str(&format!("    b.le .Lfor_end_{}\n", label_id)),
+            None => {
+                // Neither the load nor tbnz touch the flags of the comparison
+                code.push_str(&format!("    ldr x11, [x29, #{}]\n", step_slot));
+                code.push_str(&format!("    tbnz x11, #63, .Lfor_down_{}\n", label_id));
+                code.push_str(&format!("    b.ge .Lfor_end_{}\n", label_id));
+                code.push_str(&format!("    b .Lfor_body_{}\n", label_id));
+                code.push_str(&format!(".Lfor_down_{}:\n", label_id));
+                code.push_str(&format!("    b.le .Lfor_end_{}\n", label_id));
+                code.push_str(&format!(".Lfor_body_{}:\n", label_id));
+            }
+        }
+        code.push_str(&format!("    str x9, [x29, #{}]\n", var_slot));
+        
+        for stmt in body {
+            code.push_str(&self.compile_statement(stmt)?);
+        }
+        
+        match step_value {
+            Some(n) => code.push_str(&Self::load_immediate(n)),
+            None => code.push_str(&format!("    ldr x0, [x29, #{}]\n", step_slot)),
+        }
+        code.push_str(&format!("    ldr x9, [x29, #{}]\n", counter));
+        code.push_str("    add x9, x9, x0\n");
+        code.push_str(&format!("    str x9, [x29, #{}]\n", counter));
+        code.push_str(&format!("    b .Lfor_start_{}\n", label_id));
+        code.push_str(&format!(".Lfor_end_{}:\n", label_id));
+        Ok(code)
+    }
+    
+    /// Emit `label` with a frame holding `args` followed by every variable `body` assigns
+    fn compile_function(&mut self, label: &str, args: &[String], body: &[Statement]) -> Result<String, String> {
+        if args.len() > AAPCS64_ARG_REGISTERS {
+            return Err(format!("Function '{}' takes more than {} arguments", label, AAPCS64_ARG_REGISTERS));
+        }
+        
+        let saved_variables = std::mem::take(&mut self.variables);
+        let saved_strings = std::mem::take(&mut self.string_variables);
+        self.current_epilogue = format!(".L{}_epilogue", label);
+        
+        let mut spills = String::new();
+        for (i, arg) in args.iter().enumerate() {
+            let offset = self.variable_offset(arg);
+            spills.push_str(&format!("    str x{}, [x29, #{}]\n", i, offset));
+        }
+        
+        // The body decides how many slots the frame needs, so it is compiled first
+        let mut code = String::new();
+        for stmt in body {
+            code.push_str(&crate::size::statement_marker("//", stmt));
+            match self.compile_statement(stmt) {
+                Ok(stmt_code) => code.push_str(&stmt_code),
+                Err(e) => {
+                    self.variables = saved_variables;
+                    self.string_variables = saved_strings;
+                    return Err(e);
+                }
+            }
+        }
+        code.push_str(&crate::size::body_end_marker("//"));
+        let frame_size = (16 + 8 * self.variables.len() as i32 + 15) & !15;
+        self.variables = saved_variables;
+        self.string_variables = saved_strings;
+        
+        if frame_size > 4095 {
+            return Err(format!("Function '{}' has too many variables", label));
+        }
+        
+        let mut asm = format!("{}:\n", label);
+        asm.push_str(&format!("    sub sp, sp, #{}\n", frame_size));
+        asm.push_str("    stp x29, x30, [sp]\n");
+        asm.push_str("    mov x29, sp\n");
+        asm.push_str(&spills);
+        asm.push('\n');
+        asm.push_str(&code);
+        asm.push_str("\n    mov x0, #0\n");
+        asm.push_str(&format!("{}:\n", self.current_epilogue));
+        asm.push_str("    mov sp, x29\n");
+        asm.push_str("    ldp x29, x30, [sp]\n");
+        asm.push_str(&format!("    add sp, sp, #{}\n", frame_size));
+        asm.push_str("    ret\n\n");
+        Ok(asm)
+    }
+    
+    fn compile_print(&mut self, args: &[Expr], end: Option<&Expr>, span: Span) -> Result<String, String> {
+        let mut code = String::new();
+        
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                let space = self.strings.intern(" ");
+                code.push_str(&format!("    adrp x0, {}\n    add x0, x0, :lo12:{}\n", space, space));
+                code.push_str("    bl print_string\n");
+            }
+            
+            code.push_str(&self.compile_expression(arg)?);
+            if self.is_string_expr(arg) {
+                code.push_str("    bl print_string\n");
+            } else {
+                code.push_str("    bl print_decimal\n");
+            }
+        }
+        
+        match end {
+            None => code.push_str("    bl print_newline\n"),
+            Some(Expr::String(s, _)) if s.is_empty() => {}
+            Some(end) if self.is_string_expr(end) => {
+                code.push_str(&self.compile_expression(end)?);
+                code.push_str("    bl print_string\n");
+            }
+            Some(_) => return Err(format!("print() end must be a string at {}", span)),
+        }
+        Ok(code)
+    }
+    
+    fn compile_call(&mut self, func: &str, args: &[Expr], span: Span) -> Result<String, String> {
+        if !self.user_functions.contains(func) {
+            return Err(format!("Undefined function '{}' at {}", func, span));
+        }
+        if args.len() > AAPCS64_ARG_REGISTERS {
+            return Err(format!("Calls with more than {} arguments are not supported at {}", AAPCS64_ARG_REGISTERS, span));
+        }
+        
+        let mut code = String::new();
+        for arg in args {
+            code.push_str(&self.compile_expression(arg)?);
+            code.push_str("    str x0, [sp, #-16]!\n");
+        }
+        for i in (0..args.len()).rev() {
+            code.push_str(&format!("    ldr x{}, [sp], #16\n", i));
+        }
+        code.push_str(&format!("    bl {}\n", mangle_function_name(func)));
+        Ok(code)
+    }
+    
+    fn generate_runtime(&self) -> String {
+        let mut rt = String::new();
+        
+        // print_string: x0 = NUL terminated string
+        rt.push_str("print_string:\n");
+        rt.push_str("    mov x1, x0\n");
+        rt.push_str("    mov x2, #0\n");
+        rt.push_str("1:\n");
+        rt.push_str("    ldrb w9, [x1, x2]\n");
+        rt.push_str("    cbz w9, 2f\n");
+        rt.push_str("    add x2, x2, #1\n");
+        rt.push_str("    b 1b\n");
+        rt.push_str("2:\n");
+        rt.push_str("    mov x0, #1\n");
+        rt.push_str(&format!("    mov x8, #{}\n", AARCH64_SYS_WRITE));
+        rt.push_str("    svc #0\n");
+        rt.push_str("    ret\n\n");
+        
+        // print_decimal: x0 = signed value, digits are built backwards on the stack
+        rt.push_str("print_decimal:\n");
+        rt.push_str("    sub sp, sp, #32\n");
+        rt.push_str("    mov x9, x0\n");
+        rt.push_str("    add x10, sp, #32\n");
+        rt.push_str("    mov x11, #0\n");
+        rt.push_str("    cmp x9, #0\n");
+        rt.push_str("    b.ge 1f\n");
+        rt.push_str("    mov x11, #1\n");
+        rt.push_str("    neg x9, x9\n");
+        rt.push_str("1:\n");
+        rt.push_str("    mov x12, #10\n");
+        rt.push_str("2:\n");
+        rt.push_str("    udiv x13, x9, x12\n");
+        rt.push_str("    msub x14, x13, x12, x9\n");
+        rt.push_str("    add x14, x14, #48\n");
+        rt.push_str("    strb w14, [x10, #-1]!\n");
+        rt.push_str("    mov x9, x13\n");
+        rt.push_str("    cbnz x9, 2b\n");
+        rt.push_str("    cbz x11, 3f\n");
+        rt.push_str("    mov x14, #45\n");
+        rt.push_str("    strb w14, [x10, #-1]!\n");
+        rt.push_str("3:\n");
+        rt.push_str("    mov x0, #1\n");
+        rt.push_str("    mov x1, x10\n");
+        rt.push_str("    add x2, sp, #32\n");
+        rt.push_str("    sub x2, x2, x10\n");
+        rt.push_str(&format!("    mov x8, #{}\n", AARCH64_SYS_WRITE));
+        rt.push_str("    svc #0\n");
+        rt.push_str("    add sp, sp, #32\n");
+        rt.push_str("    ret\n\n");
+        
+        rt.push_str("print_newline:\n");
+        rt.push_str("    mov x0, #1\n");
+        rt.push_str("    adrp x1, newline\n");
+        rt.push_str("    add x1, x1, :lo12:newline\n");
+        rt.push_str("    mov x2, #1\n");
+        rt.push_str(&format!("    mov x8, #{}\n", AARCH64_SYS_WRITE));
+        rt.push_str("    svc #0\n");
+        rt.push_str("    ret\n\n");
+        
+        // runtime_error: x0 = message, reported on stderr before exiting with status 1
+        rt.push_str("runtime_error:\n");
+        rt.push_str("    mov x12, x0\n");
+        rt.push_str("    mov x0, #2\n");
+        rt.push_str("    adrp x1, runtime_error_prefix\n");
+        rt.push_str("    add x1, x1, :lo12:runtime_error_prefix\n");
+        rt.push_str("    mov x2, #15\n");
+        rt.push_str(&format!("    mov x8, #{}\n", AARCH64_SYS_WRITE));
+        rt.push_str("    svc #0\n");
+        rt.push_str("    mov x1, x12\n");
+        rt.push_str("    mov x2, #0\n");
+        rt.push_str("1:\n");
+        rt.push_str("    ldrb w9, [x1, x2]\n");
+        rt.push_str("    cbz w9, 2f\n");
+        rt.push_str("    add x2, x2, #1\n");
+        rt.push_str("    b 1b\n");
+        rt.push_str("2:\n");
+        rt.push_str("    mov x0, #2\n");
+        rt.push_str("    svc #0\n");
+        rt.push_str("    mov x0, #2\n");
+        rt.push_str("    adrp x1, newline\n");
+        rt.push_str("    add x1, x1, :lo12:newline\n");
+        rt.push_str("    mov x2, #1\n");
+        rt.push_str("    svc #0\n");
+        rt.push_str("    mov x0, #1\n");
+        rt.push_str(&format!("    mov x8, #{}\n", AARCH64_SYS_EXIT));
+        rt.push_str("    svc #0\n\n");
+        
+        // sleep_ms: x0 = milliseconds, slept through a struct timespec on the stack;
+        // returns 0 or -errno like nanosleep
+        rt.push_str("sleep_ms:\n");
+        rt.push_str("    cmp x0, #0\n");
+        rt.push_str("    b.gt 1f\n");
+        rt.push_str("    mov x0, #0\n");
+        rt.push_str("    ret\n");
+        rt.push_str("1:\n");
+        rt.push_str("    sub sp, sp, #16\n");
+        rt.push_str("    mov x9, #1000\n");
+        rt.push_str("    sdiv x10, x0, x9\n");
+        rt.push_str("    msub x11, x10, x9, x0\n");
+        rt.push_str("    mov x9, #0x4240\n");
+        rt.push_str("    movk x9, #0xF, lsl #16\n");
+        rt.push_str("    mul x11, x11, x9\n");
+        rt.push_str("    stp x10, x11, [sp]\n");
+        rt.push_str("    mov x0, sp\n");
+        rt.push_str("    mov x1, #0\n");
+        rt.push_str(&format!("    mov x8, #{}\n", AARCH64_SYS_NANOSLEEP));
+        rt.push_str("    svc #0\n");
+        rt.push_str("    add sp, sp, #16\n");
+        rt.push_str("    ret\n\n");
+        
+        rt
+    }
+}
+
+impl Default for Aarch64LinuxBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for Aarch64LinuxBackend {
+    fn name(&self) -> &str {
+        Target::Aarch64.name()
+    }
+    
+    fn target(&self) -> Target {
+        Target::Aarch64
+    }
+    
+    fn generate_header(&self) -> String {
+        let mut header = String::new();
+        header.push_str("    .text\n");
+        header.push_str("    .globl _start\n\n");
+        header.push_str("_start:\n");
+        header.push_str("    bl main\n");
+        header.push_str(&format!("    mov x8, #{}         // exit with main's result\n", AARCH64_SYS_EXIT));
+        header.push_str("    svc #0\n\n");
+        header
+    }
+    
+    fn format(&self) -> &'static str {
+        "elf64"
+    }
+    
+    fn supported_capabilities(&self) -> Vec<Capability> {
+        vec![
+            Capability::Linux,
+            Capability::AArch64,
+            Capability::VirtualMemory,
+            Capability::Heap,
+            Capability::Filesystem,
+            Capability::WritableMemory,
+        ]
+    }
+    
+    fn compile_program(&mut self, program: &Program) -> Result<String, String> {
+        self.strings = StringPool::default();
+        self.variables.clear();
+        self.string_variables.clear();
+        self.label_counter = 0;
+        
+        let mut main_body = Vec::new();
+        let mut functions = Vec::new();
+        for stmt in &program.body {
+            match stmt {
+                Statement::FunctionDef { name, args, body, span, .. } => {
+                    if LINUX64_RESERVED_NAMES.contains(&name.as_str()) {
+                        return Err(format!("Function name '{}' is reserved at {}", name, span));
+                    }
+                    functions.push((name, args, body));
+                }
+                other => main_body.push(other.clone()),
+            }
+        }
+        self.user_functions = functions.iter().map(|(name, _, _)| (*name).clone()).collect();
+        
+        let mut asm = self.generate_header();
+        asm.push_str(&self.compile_function("main", &[], &main_body)?);
+        for (name, args, body) in functions {
+            let phase = self.logger.detail_phase("codegen");
+            let code = self.compile_function(&mangle_function_name(name), args, body)?;
+            phase.end(format!("function {}: {} lines", name, code.lines().count()));
+            asm.push_str(&code);
+        }
+        
+        asm.push_str(&self.generate_runtime());
+        
+        asm.push_str("    .section .rodata\n");
+        asm.push_str("newline:\n");
+        asm.push_str("    .byte 10, 0\n");
+        asm.push_str("runtime_error_prefix:\n");
+        asm.push_str("    .ascii \"Runtime error: \"\n\n");
+        asm.push_str("// String literals\n");
+        asm.push_str(&self.strings.data_directives("//"));
+        
+        Ok(asm)
+    }
+    
+    fn compile_expression(&mut self, expr: &Expr) -> Result<String, String> {
+        match expr {
+            Expr::Number(n, _) => Ok(Self::load_immediate(*n)),
+            Expr::Boolean(b, _) => Ok(format!("    mov x0, #{}\n", *b as i32)),
+            Expr::String(s, span) => {
+                if s.contains('\0') {
+                    return Err(format!("String literal contains a null byte at {}", span));
+                }
+                let label = self.strings.intern(s);
+                Ok(format!("    adrp x0, {}  // {:?}\n    add x0, x0, :lo12:{}\n", label, s, label))
+            }
+            Expr::Var(name, span) => match self.variables.get(name) {
+                Some(offset) => Ok(format!("    ldr x0, [x29, #{}]  // {}\n", offset, name)),
+                None => Err(format!("Undefined variable '{}' at {}", name, span)),
+            },
+            Expr::Call { func, args, kwargs, span } if func == "print" => self.compile_print(args, print_end(kwargs, *span)?, *span),
+            Expr::Call { func, kwargs, span, .. } if !kwargs.is_empty() => reject_kwargs(func, kwargs, *span).map(|_| String::new()),
+            Expr::Call { func, args, span, .. } if func == "asm" => {
+                inline_asm(args, *span, "//", |name| self.variables.get(name).map(|offset| format!("[x29, #{}]", offset)))
+            }
+            Expr::Call { func, args, span, .. } if func == "sleep" && !self.user_functions.contains(func) => {
+                let [ms] = args.as_slice() else {
+                    return Err(format!("sleep() takes 1 argument (milliseconds) but {} were given at {}", args.len(), span));
+                };
+                let mut code = self.compile_expression(ms)?;
+                code.push_str("    bl sleep_ms\n");
+                Ok(code)
+            }
+            Expr::Call { func, args, span, .. } => self.compile_call(func, args, *span),
+            Expr::UnaryOp { op: UnaryOp::Minus, operand, .. } => {
+                let mut code = self.compile_expression(operand)?;
+                code.push_str("    neg x0, x0\n");
+                Ok(code)
+            }
+            Expr::UnaryOp { op: UnaryOp::Not, operand, .. } => {
+                let mut code = self.compile_truthiness(operand)?;
+                code.push_str("    cmp x0, #0\n");
+                code.push_str("    cset x0, eq\n");
+                Ok(code)
+            }
+            Expr::BoolOp { op, values, .. } => self.compile_bool_op(op, values),
+            Expr::UnaryOp { op: UnaryOp::Plus, operand, .. } => self.compile_expression(operand),
+            Expr::BinOp { left, op, right, span } => {
+                // Left operand is kept on the stack while the right one is evaluated
+                let mut code = self.compile_expression(left)?;
+                code.push_str("    str x0, [sp, #-16]!\n");
+                code.push_str(&self.compile_expression(right)?);
+                code.push_str("    ldr x1, [sp], #16\n");
+                match op {
+                    Op::Add => code.push_str("    add x0, x1, x0\n"),
+                    Op::Sub => code.push_str("    sub x0, x1, x0\n"),
+                    Op::Mul => code.push_str("    mul x0, x1, x0\n"),
+                    Op::Div | Op::FloorDiv => code.push_str("    sdiv x0, x1, x0\n"),
+                    Op::Mod => {
+                        code.push_str("    sdiv x2, x1, x0\n");
+                        code.push_str("    msub x0, x2, x0, x1\n");
+                    }
+                    Op::BitAnd => code.push_str("    and x0, x1, x0\n"),
+                    Op::BitOr => code.push_str("    orr x0, x1, x0\n"),
+                    Op::BitXor => code.push_str("    eor x0, x1, x0\n"),
+                    Op::Pow => return Err(format!("The ** operator is not supported by the aarch64 backend at {}", span)),
+                }
+                Ok(code)
+            }
+            other => Err(format!("{} are not supported by the aarch64 backend at {}", other.construct(), other.span())),
+        }
+    }
+    
+    fn function_prologue(&self, func: &BackendFunction) -> String {
+        format!("{}:\n    stp x29, x30, [sp, #-16]!\n    mov x29, sp\n", func.name)
+    }
+    
+    fn function_epilogue(&self, _func: &BackendFunction) -> String {
+        "    ldp x29, x30, [sp], #16\n    ret\n".to_string()
+    }
+    
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
 // Helper trait for string hashing
 trait HashCode {
     fn hash_code(&self) -> u64;
@@ -1408,4 +4523,660 @@ impl HashCode for String {
     fn hash_code(&self) -> u64 {
         self.as_str().hash_code()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_program;
+
+    #[test]
+    fn test_nested_while_labels() {
+        let source = "var i = 0\nwhile i < 10:\n    var j = 0\n    while j < 3:\n        j += 1\n    end\n    i += 1\nend\n";
+        let program = parse_program(source).unwrap();
+        let asm = Linux64Backend::new().compile_program(&program).unwrap();
+
+        assert!(asm.contains("while_start_0:"));
+        assert!(asm.contains("while_start_1:"));
+        assert!(asm.contains("jmp while_start_1"));
+    }
+
+    /// 500 functions with loops, branches, short-circuits and literals, some
+    /// shared between functions: threads must reproduce the serial output
+    #[test]
+    fn test_parallel_functions_match_serial() {
+        let mut source = String::new();
+        for i in 0..500 {
+            source.push_str(&format!(
+                "def f{i}(n): {{\n    var t = 0\n    for k in range(n):\n        if k > {i} and n < 9:\n            t += k\n        else:\n            print(\"f{i}\", \"shared\")\n    end\n    while t > 3:\n        t = t - 3\n    end\n    return t\n}}\n"
+            ));
+        }
+        source.push_str("if f1(5) > 0 or f2(3) > 0: print(\"shared\")\nprint(f499(4))\n");
+        let program = parse_program(&source).unwrap();
+
+        let serial = Linux64Backend::new().compile_program(&program).unwrap();
+        assert_eq!(serial, Linux64Backend::new().with_jobs(8).compile_program(&program).unwrap());
+
+        // Code that looks like a label or a placeholder is left alone
+        let source = "def a(n): {\n    if n > 1: {\n        asm(\"nop # \\x01 9 if_end_f1_0\")\n    }\n    return n\n}\ndef b(n): {\n    while n > 0: {\n        n = n - 1\n    }\n    return n\n}\nprint(a(2), b(3))\n";
+        let program = parse_program(source).unwrap();
+        let serial = Linux64Backend::new().compile_program(&program).unwrap();
+        assert_eq!(serial, Linux64Backend::new().with_jobs(4).compile_program(&program).unwrap());
+        let function = &serial[serial.find("\nfn_a:").unwrap()..serial.find("\nfn_b:").unwrap()];
+        assert!(function.contains("if_end_f0_0:") && function.trim_end().ends_with("ret"), "{}", function);
+
+        // The first failing function in program order is reported either way
+        let program = parse_program("def a(): {\n    break\n}\ndef b(): {\n    continue\n}\n").unwrap();
+        let serial = Linux64Backend::new().compile_program(&program).unwrap_err();
+        assert_eq!(Linux64Backend::new().with_jobs(2).compile_program(&program).unwrap_err(), serial);
+        assert!(serial.contains("'break'"), "{}", serial);
+    }
+
+    #[test]
+    fn test_break_outside_loop() {
+        let program = parse_program("break\n").unwrap();
+        let err = Linux64Backend::new().compile_program(&program).unwrap_err();
+        assert!(err.contains("outside of loop"));
+    }
+
+    #[test]
+    fn test_if_elif_else_signed() {
+        let source = "var x = -5\nif x > 3: print(1)\nelif x < -3: print(2)\nelif x == 0: print(3)\nelse: print(4)\nif x >= -5: print(5)\n";
+        let program = parse_program(source).unwrap();
+        let asm = Linux64Backend::new().compile_program(&program).unwrap();
+
+        assert!(asm.contains("neg rax"));
+        assert!(asm.contains("jle if_else_0"));
+        assert!(asm.contains("jge if_elif_0_1"));
+        assert!(asm.contains("jne if_elif_0_2"));
+        // Without an else the false branch goes straight to the end label
+        assert!(asm.contains("jl if_end_1"));
+        assert!(!asm.contains("if_else_1"));
+    }
+
+    #[test]
+    fn test_string_comparison() {
+        let program = parse_program("var name = \"bob\"\nif name == \"bob\": print(1)\n").unwrap();
+        let asm = Linux64Backend::new().compile_program(&program).unwrap();
+        assert!(asm.contains("call str_cmp"));
+
+        let program = parse_program("var name = \"bob\"\nif name == 3: print(1)\n").unwrap();
+        assert!(Linux64Backend::new().compile_program(&program).is_err());
+    }
+
+    #[test]
+    fn test_variable_slots() {
+        let program = parse_program("var x = 5\nvar x = 6\nprint(x)\n").unwrap();
+        let asm = Linux64Backend::new().compile_program(&program).unwrap();
+        assert!(asm.contains("mov QWORD PTR [rbp - 8], rax"));
+        assert!(!asm.contains("[rbp - 16]"));
+
+        let program = parse_program("print(y)\n").unwrap();
+        let err = Linux64Backend::new().compile_program(&program).unwrap_err();
+        assert!(err.contains("Undefined variable 'y'"));
+    }
+
+    #[test]
+    fn test_function_arguments() {
+        let program = parse_program("def add(a, b): return a + b\nvar r = add(2, 3)\nprint(r)\n").unwrap();
+        let asm = Linux64Backend::new().compile_program(&program).unwrap();
+        assert!(asm.contains("fn_add:\n"));
+        assert!(asm.contains("    pop rdi\n    pop rsi\n    call fn_add\n"));
+        assert!(asm.contains("mov QWORD PTR [rbp - 16], rsi"));
+        assert!(asm.contains("jmp .fn_add_epilogue"));
+
+        // Runtime helper names are free for user code once mangled
+        let program = parse_program("def print_string(a): return a\nvar r = print_string(1)\n").unwrap();
+        assert!(Linux64Backend::new().compile_program(&program).is_ok());
+
+        let program = parse_program("def print(a): return a\n").unwrap();
+        assert!(Linux64Backend::new().compile_program(&program).unwrap_err().contains("reserved"));
+
+        // A name no function or linked module defines is reported here, not by ld
+        let error = |source: &str| Linux64Backend::new().compile_program(&parse_program(source).unwrap()).unwrap_err();
+        assert_eq!(error("print(foo(1))\n"), "Undefined function 'foo' at 1:7");
+        assert_eq!(error("str(1)\n"), "Undefined function 'str' at 1:1");
+        let program = parse_program("print(foo(1))\n").unwrap();
+        let symbols = ["foo".to_string()].into_iter().collect();
+        assert!(Linux64Backend::new().with_module_symbols(symbols).compile_program(&program).unwrap().contains("    call foo\n"));
+    }
+
+    #[test]
+    fn test_stack_arguments() {
+        // Each function reports rbp modulo 16, which is 0 only if the call was aligned
+        let probe = "    var r = 0\n    asm(\"mov rax, rbp\\nand rax, 15\\nmov {r}, rax\")\n";
+        let source = format!(
+            "def f7(a, b, c, d, e, f, g): {{\n{}    return r * 1000 + a + b + c + d + e + f + g\n}}\n\
+             def f8(a, b, c, d, e, f, g, h): {{\n{}    return r * 1000 + a + b + c + d + e + f + g + h\n}}\n\
+             print(f7(1, 2, 3, 4, 5, 6, 7), f8(1, 2, 3, 4, 5, 6, 7, 8))\n",
+            probe, probe
+        );
+        let asm = Linux64Backend::new().compile_program(&parse_program(&source).unwrap()).unwrap();
+        assert!(asm.contains("    # Function call: f7\n    sub rsp, 8\n"), "{}", asm);
+        assert!(asm.contains("    call fn_f7\n    add rsp, 16\n"), "{}", asm);
+        assert!(!asm.contains("    # Function call: f8\n    sub rsp, 8\n"), "{}", asm);
+        assert!(asm.contains("    call fn_f8\n    add rsp, 16\n"), "{}", asm);
+
+        // A pushed left operand or earlier argument shifts the parity
+        let nested = format!(
+            "{}print(1 + f7(1, 2, 3, 4, 5, 6, 7), 1 + f8(1, 2, 3, 4, 5, 6, 7, 8), f7(f8(1, 2, 3, 4, 5, 6, 7, 8), 2, 3, 4, 5, 6, 7))\n",
+            source.rsplit_once("print(").unwrap().0
+        );
+        let asm = Linux64Backend::new().compile_program(&parse_program(&nested).unwrap()).unwrap();
+        assert!(asm.contains("    call fn_f7\n    add rsp, 8\n"), "{}", asm);
+        assert!(asm.contains("    push rax\n    # Function call: f8\n    sub rsp, 8\n"), "{}", asm);
+        assert!(asm.contains("    call fn_f8\n    add rsp, 24\n"), "{}", asm);
+
+        let output = std::env::temp_dir().join(format!("earthang_stack_args_{}", std::process::id()));
+        let binary = match crate::compiler::compile_to_executable(&source, &output, Target::Linux64) {
+            Ok(binary) => binary,
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        };
+        let run = std::process::Command::new(&binary).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "28 36\n");
+
+        let binary = crate::compiler::compile_to_executable(&nested, &output, Target::Linux64).unwrap();
+        let run = std::process::Command::new(&binary).output().unwrap();
+        let _ = std::fs::remove_file(&binary);
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "29 37 63\n");
+    }
+
+    #[test]
+    fn test_fstring_print() {
+        let program = parse_program("var x = 1\nprint(f\"x = {x + 1} {{ok}}\")\n").unwrap();
+        let asm = Linux64Backend::new().compile_program(&program).unwrap();
+        assert!(asm.contains(":  # \"x = \"\n"));
+        assert!(asm.contains(":  # \" {ok}\"\n"));
+
+        let errors = parse_program("print(f\"x = {x\")\n").unwrap_err();
+        assert!(matches!(&errors[0], crate::parser::ParseError::SyntaxError { span, .. } if span.start.column == 13));
+    }
+
+    #[test]
+    fn test_print_output() {
+        let output = std::env::temp_dir().join(format!("earthang_print_{}", std::process::id()));
+        // Strings are written as bytes, never used as a format, so '%' needs no escaping
+        let source = "print(42)\nprint(1, -2, \"hi\")\nprint()\nprint(\"100%\")\nprint(\"%s %d\", \"%\")\nprint(\"a\")\n";
+        
+        // Only meaningful where binutils are installed
+        let binary = match crate::compiler::compile_to_executable(source, &output, Target::Linux64) {
+            Ok(binary) => binary,
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        };
+        
+        let run = std::process::Command::new(&binary).output().unwrap();
+        let _ = std::fs::remove_file(&binary);
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "42\n1 -2 hi\n\n100%\n%s %d %\na\n");
+    }
+
+    #[test]
+    fn test_inline_asm() {
+        // Passed through line by line between markers, here into a --bios-mode payload
+        let program = parse_program("fb_init(320, 200)\nasm(\"in al, 0x92\\n    or al, 2\\nout 0x92, al\")\n").unwrap();
+        let asm = Linux64Backend::new().with_bios_graphics(crate::framebuffer::Framebuffer::vga_mode_13h(), crate::framebuffer::SimdLevel::Sse2).compile_program(&program).unwrap();
+        assert!(asm.contains("    # asm() from line 2, user-provided\n    in al, 0x92\n    or al, 2\n    out 0x92, al\n    # end of asm()\n"), "{}", asm);
+        
+        // `{name}` is where the variable lives, `{{` a brace
+        let program = parse_program("var x = 41\nasm(\"mov rax, {x}\\nvpaddd zmm0{{k1}}, zmm1, zmm2\")\n").unwrap();
+        let asm = Linux64Backend::new().compile_program(&program).unwrap();
+        assert!(asm.contains("    mov rax, QWORD PTR [rbp - 8]\n    vpaddd zmm0{k1}, zmm1, zmm2\n"), "{}", asm);
+        let program = parse_program("var x = 41\nasm(\"ld t0, {x}\")\n").unwrap();
+        assert!(RiscV64Backend::new().compile_program(&program).unwrap().contains("    ld t0, -24(s0)\n    # end of asm()\n"));
+        let program = parse_program("var x = 41\nasm(\"ldr x1, {x}\")\n").unwrap();
+        assert!(Aarch64LinuxBackend::new().compile_program(&program).unwrap().contains("    ldr x1, [x29, #16]\n    // end of asm()\n"));
+        
+        let error = |source: &str| Linux64Backend::new().compile_program(&parse_program(source).unwrap()).unwrap_err();
+        assert!(error("asm(\"mov rax, {y}\")\n").starts_with("Undefined variable 'y' in asm()"));
+        assert!(error("var x = 1\nasm(\"mov rax, {x\")\n").starts_with("Unclosed '{' in asm()"));
+        assert!(error("var x = 1\nasm(\"mov rax, x}\")\n").starts_with("Unmatched '}' in asm()"));
+        assert!(error("var s = \"nop\"\nasm(s)\n").starts_with("asm() takes one string literal"));
+        assert!(error("def asm(x): return x\n").starts_with("Function name 'asm' is reserved"));
+        
+        let output = std::env::temp_dir().join(format!("earthang_asm_{}", std::process::id()));
+        let source = "var x = 41\nvar y = 0\nasm(\"mov rax, {x}\\ninc rax\\nmov {y}, rax\")\nprint(y)\n";
+        let binary = match crate::compiler::compile_to_executable(source, &output, Target::Linux64) {
+            Ok(binary) => binary,
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        };
+        let run = std::process::Command::new(&binary).output().unwrap();
+        let _ = std::fs::remove_file(&binary);
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "42\n");
+    }
+
+    #[test]
+    fn test_structs() {
+        let program = parse_program("struct Point: x, y\nvar p = Point(3, 4)\np.y = p.x\n").unwrap();
+        let asm = Linux64Backend::new().compile_program(&program).unwrap();
+        assert!(asm.contains("    mov edi, 16\n    call heap_alloc_64\n"), "{}", asm);
+        assert!(asm.contains("    mov rax, QWORD PTR [rax + 0]  # .x\n"), "{}", asm);
+        // A BIOS payload links the heap in for structs alone
+        let asm = Linux64Backend::new().with_bios_graphics(crate::framebuffer::Framebuffer::vga_mode_13h(), crate::framebuffer::SimdLevel::Sse2).compile_program(&program).unwrap();
+        assert!(asm.contains("heap_alloc_64:\n"));
+        
+        let error = |source: &str| Linux64Backend::new().compile_program(&parse_program(source).unwrap()).unwrap_err();
+        assert_eq!(error("struct P: x\nvar p = P(1)\nprint(p.z)\n"), "Struct 'P' has no field 'z' at 3:7");
+        assert_eq!(error("struct P: x\ndef f(p): return p.z\n"), "No struct has a field 'z' at 2:11");
+        assert!(error("struct P: x, y\nstruct Q: y, x\ndef f(p): return p.x\n").starts_with("Field 'x' is at different positions in structs 'P' and 'Q'"));
+        assert_eq!(error("struct P: x\nvar p = P(1, 2)\n"), "P() takes 1 argument but 2 were given at 2:1");
+        assert!(error("struct P: x, x\n").starts_with("Struct 'P' declares field 'x' twice"));
+        assert!(error("struct P: x\ndef P(): return 1\n").starts_with("'P' names both a struct and a function"));
+        assert!(error("if True: {\n    struct P: x\n}\n").starts_with("Structs must be declared at the top level"));
+    }
+
+    #[test]
+    fn test_target_names() {
+        for target in Target::ALL {
+            assert_eq!(target.to_string().parse::<Target>(), Ok(target));
+        }
+        assert_eq!("x86_64".parse::<Target>(), Err("Unknown target 'x86_64'; valid targets are linux64, riscv64, aarch64".to_string()));
+        
+        // Backends report the target they compile for, which is what the compiler dispatches on
+        let registry = BackendRegistry::default_registry();
+        let targets: Vec<Target> = registry.backends.iter().map(|backend| backend.target()).collect();
+        assert_eq!(targets, Target::ALL);
+        assert!(registry.backends.iter().all(|backend| backend.name() == backend.target().name()));
+    }
+
+    #[test]
+    fn test_print_end() {
+        // `end=` replaces the newline, and an empty one writes nothing at all
+        let program = parse_program("print(1, end=\"\")\nprint(2, end=\" | \")\n").unwrap();
+        let backends = [
+            ("linux64", Linux64Backend::new().compile_program(&program).unwrap(), "    lea rax, [str_000000000b874b21]\n    mov rdi, rax\n    call print_string\n"),
+            ("riscv64", RiscV64Backend::new().compile_program(&program).unwrap(), "    la a0, str_000000000b874b21  # \" | \"\n    call print_string\n"),
+            ("aarch64", Aarch64LinuxBackend::new().compile_program(&program).unwrap(), "    add x0, x0, :lo12:str_000000000b874b21\n    bl print_string\n"),
+        ];
+        for (name, asm, end) in backends {
+            assert!(!asm.contains("call print_newline") && !asm.contains("bl print_newline"), "{}:\n{}", name, asm);
+            assert!(asm.contains(end), "{}:\n{}", name, asm);
+            assert!(asm.contains("\nstr_000000000b874b21:"), "{}:\n{}", name, asm);
+        }
+        
+        let err = Linux64Backend::new().compile_program(&parse_program("print(1, sep=\",\")\n").unwrap()).unwrap_err();
+        assert!(err.starts_with("print() got an unexpected keyword argument 'sep'"), "{}", err);
+        let err = Linux64Backend::new().compile_program(&parse_program("print(1, end=2)\n").unwrap()).unwrap_err();
+        assert!(err.starts_with("print() end must be a string"), "{}", err);
+        
+        let output = std::env::temp_dir().join(format!("earthang_print_end_{}", std::process::id()));
+        let source = "var sep = \", \"\nfor i in range(3):\n    print(i, end=sep)\nend\nprint(\"done\", end=\"!\\n\")\nprint(\"x\", end=\"\")\nprint()\n";
+        let binary = match crate::compiler::compile_to_executable(source, &output, Target::Linux64) {
+            Ok(binary) => binary,
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        };
+        let run = std::process::Command::new(&binary).output().unwrap();
+        let _ = std::fs::remove_file(&binary);
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "0, 1, 2, done!\nx\n");
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        let output = std::env::temp_dir().join(format!("earthang_bool_{}", std::process::id()));
+        let source = "var x = 0\nvar e = \"\"\n\
+                      if x != 0 and 10 / x > 2:\n    print(\"divided\")\n\
+                      var a = x and print(\"and rhs\")\nvar b = \"s\" or print(\"or rhs\")\n\
+                      print(a, b, not e, not \"s\", e or 7 and 1)\n";
+        
+        // The other backends share the truthiness rules: strings test their first byte
+        let program = parse_program("var s = \"\"\nvar t = s or 2\n").unwrap();
+        let riscv = RiscV64Backend::new().compile_program(&program).unwrap();
+        assert!(riscv.contains("    lbu a0, 0(a0)\n    bnez a0, .Lbool_short_1\n"));
+        let aarch64 = Aarch64LinuxBackend::new().compile_program(&program).unwrap();
+        assert!(aarch64.contains("    ldrb w0, [x0]\n    cbnz x0, .Lbool_short_1\n"));
+        
+        // Only meaningful where binutils are installed
+        let binary = match crate::compiler::compile_to_executable(source, &output, Target::Linux64) {
+            Ok(binary) => binary,
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        };
+        
+        let run = std::process::Command::new(&binary).output().unwrap();
+        let _ = std::fs::remove_file(&binary);
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "0 1 1 0 1\n");
+        assert_eq!(run.status.code(), Some(0));
+    }
+
+    #[test]
+    fn test_for_range() {
+        let zero_step = parse_program("for i in range(0, 3, 0):\n    print(i)\nend\n").unwrap();
+        assert!(Linux64Backend::new().compile_program(&zero_step).unwrap_err().contains("step must not be zero"));
+        
+        let program = parse_program("for i in range(3, 0, -1):\n    print(i)\nend\n").unwrap();
+        let riscv = RiscV64Backend::new().compile_program(&program).unwrap();
+        assert!(riscv.contains("    ble t0, t1, .Lfor_end_1\n"));
+        let aarch64 = Aarch64LinuxBackend::new().compile_program(&program).unwrap();
+        assert!(aarch64.contains("    cmp x9, x10\n    b.le .Lfor_end_1\n"));
+        
+        let output = std::env::temp_dir().join(format!("earthang_for_{}", std::process::id()));
+        let source = "var total = 0\nfor i in range(5):\n    total += i\nend\nprint(total, i)\n\
+                      for j in range(10, 0, -4):\n    if j == 6: continue\n    print(j)\nend\n\
+                      var step = 0\nfor k in range(1, 2, step):\n    print(k)\nend\n";
+        
+        // Only meaningful where binutils are installed
+        let binary = match crate::compiler::compile_to_executable(source, &output, Target::Linux64) {
+            Ok(binary) => binary,
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        };
+        
+        let run = std::process::Command::new(&binary).output().unwrap();
+        let _ = std::fs::remove_file(&binary);
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "10 4\n10\n2\n");
+        assert_eq!(String::from_utf8_lossy(&run.stderr), "Runtime error: range() step must not be zero\n");
+        assert_eq!(run.status.code(), Some(1));
+    }
+
+    #[test]
+    fn test_list_indexing() {
+        let program = parse_program("var xs = [4, 5]\nxs[1] = xs[0]\n").unwrap();
+        let asm = Linux64Backend::new().compile_program(&program).unwrap();
+        assert!(asm.contains("    mov rdi, 2\n    call list_create_64\n    push rax\n"));
+        assert!(asm.contains("    mov rdi, QWORD PTR [rsp]\n    call list_append_64\n"));
+        assert!(asm.contains("    pop rdi\n    call list_get_64\n"));
+        assert!(asm.contains("    pop rsi\n    pop rdx\n    call list_set_64\n"));
+        
+        let output = std::env::temp_dir().join(format!("earthang_list_{}", std::process::id()));
+        let source = "var xs = [1, 2, 3]\nxs[2] += 10\nprint(xs[2], len(xs))\nprint(xs[3])\nprint(\"after\")\n";
+        
+        // Only meaningful where binutils are installed
+        let binary = match crate::compiler::compile_to_executable(source, &output, Target::Linux64) {
+            Ok(binary) => binary,
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        };
+        
+        let run = std::process::Command::new(&binary).output().unwrap();
+        let _ = std::fs::remove_file(&binary);
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "13 3\n");
+        assert_eq!(String::from_utf8_lossy(&run.stderr), "Runtime error: list index out of range\n");
+        assert_eq!(run.status.code(), Some(1));
+    }
+
+    #[test]
+    fn test_dict_literals() {
+        let program = parse_program("var d = {\"a\": 1}\nd[\"b\"] = d[\"a\"]\nprint(\"b\" in d)\n").unwrap();
+        let asm = Linux64Backend::new().compile_program(&program).unwrap();
+        assert!(asm.contains("    call dict_create_64\n    push rax\n"));
+        assert!(asm.contains("    pop rdi\n    call dict_get_64\n    test rdx, rdx\n    jz dict_key_error_64\n"));
+        assert!(asm.contains("    pop rsi\n    pop rdx\n    call dict_set_64\n"));
+        assert!(asm.contains("    call dict_find_index_64\n    cmp rax, -1\n    setne al\n"));
+        
+        let bad_key = parse_program("var d = {1: 2}\n").unwrap();
+        assert!(Linux64Backend::new().compile_program(&bad_key).unwrap_err().contains("keys must be strings"));
+        // Subscripts on a dict need string keys too: the runtime would read 1 as a string pointer
+        let error = |source: &str| Linux64Backend::new().compile_program(&parse_program(source).unwrap()).unwrap_err();
+        assert_eq!(error("var d = {}\nd[1] = 2\nprint(d[1])\n"), "Dictionary keys must be strings at 2:1");
+        assert_eq!(error("var d = {\"a\": 1}\nprint(d[1])\n"), "Dictionary keys must be strings at 2:7");
+        
+        // Twenty keys force the table past its initial 16 slots
+        let output = std::env::temp_dir().join(format!("earthang_dict_{}", std::process::id()));
+        let mut source = String::from("var d = {\"a\": 1}\nd[\"a\"] += 10\n");
+        for i in 0..20 {
+            source.push_str(&format!("d[\"k{}\"] = {}\n", i, i * 2));
+        }
+        source.push_str("print(d[\"a\"], d[\"k3\"], d[\"k19\"])\nprint(\"k7\" in d, \"x\" in d, \"x\" not in d)\nprint(d[\"x\"])\n");
+        
+        let binary = match crate::compiler::compile_to_executable(&source, &output, Target::Linux64) {
+            Ok(binary) => binary,
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        };
+        
+        let run = std::process::Command::new(&binary).output().unwrap();
+        let _ = std::fs::remove_file(&binary);
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "11 6 38\n1 0 1\n");
+        assert_eq!(String::from_utf8_lossy(&run.stderr), "Runtime error: key not found in dictionary\n");
+        assert_eq!(run.status.code(), Some(1));
+    }
+
+    #[test]
+    fn test_strings_do_not_leak_between_programs() {
+        let mut backend = Linux64Backend::new();
+        let first = backend.compile_program(&parse_program("print(\"first\")\n").unwrap()).unwrap();
+        let second = backend.compile_program(&parse_program("print(\"second\")\n").unwrap()).unwrap();
+        
+        assert!(!second.contains("\"first\""));
+        assert!(second.contains(&format!("str_{:016x}:", "second".hash_code())));
+        assert!(first.contains(&format!("str_{:016x}:", "first".hash_code())));
+    }
+
+    /// Every `str_` label the assembly mentions, split into the ones it
+    /// defines and the ones it only refers to
+    fn string_labels(asm: &str) -> (std::collections::HashSet<&str>, std::collections::HashSet<&str>) {
+        let is_label_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        let (mut defined, mut referenced) = (std::collections::HashSet::new(), std::collections::HashSet::new());
+        let mut rest = asm;
+        while let Some(start) = rest.find("str_") {
+            let starts_word = !rest[..start].ends_with(is_label_char);
+            rest = &rest[start..];
+            let end = rest.find(|c: char| !is_label_char(c)).unwrap_or(rest.len());
+            let (label, after) = rest.split_at(end);
+            if starts_word {
+                if after.starts_with(':') { &mut defined } else { &mut referenced }.insert(label);
+            }
+            rest = after;
+        }
+        (defined, referenced)
+    }
+
+    #[test]
+    fn test_colliding_string_hashes_get_their_own_labels() {
+        // djb2 maps both to the same 64-bit value
+        assert_eq!("ab".hash_code(), "bA".hash_code());
+        let source = "def f(): {\n    print(\"bA\")\n    return 1\n}\ndef g(): {\n    print(\"ab\")\n    return 2\n}\nprint(\"ab\")\nprint(\"bA\", f(), g())\n";
+        let program = parse_program(source).unwrap();
+
+        let outputs = [
+            ("linux64", Linux64Backend::new().compile_program(&program).unwrap()),
+            ("linux64 parallel", Linux64Backend::new().with_jobs(2).compile_program(&program).unwrap()),
+            ("riscv64", RiscV64Backend::new().compile_program(&parse_program("print(\"ab\")\nprint(\"bA\")\n").unwrap()).unwrap()),
+            ("aarch64", Aarch64LinuxBackend::new().compile_program(&program).unwrap()),
+        ];
+        for (name, asm) in outputs {
+            let (defined, referenced) = string_labels(&asm);
+            assert!(!referenced.is_empty(), "{}", name);
+            let undefined: Vec<_> = referenced.difference(&defined).collect();
+            assert!(undefined.is_empty(), "{}: {:?} referenced but never defined", name, undefined);
+            // One definition per literal, each holding its own bytes
+            let label_of = |content: &str| {
+                let comment = format!("{:?}", content);
+                let line = asm.lines().find(|line| line.starts_with("str_") && line.ends_with(&comment)).unwrap_or_else(|| panic!("{}: no data for {}", name, comment));
+                line.split(':').next().unwrap().to_string()
+            };
+            assert_ne!(label_of("ab"), label_of("bA"), "{}", name);
+            assert_eq!(asm.lines().filter(|line| line.starts_with(&format!("{}:", label_of("ab")))).count(), 1, "{}", name);
+        }
+    }
+
+    struct MockBackend {
+        name: &'static str,
+        capabilities: Vec<Capability>,
+    }
+
+    impl Backend for MockBackend {
+        fn name(&self) -> &str { self.name }
+        fn target(&self) -> Target { Target::Linux64 }
+        fn generate_header(&self) -> String { String::new() }
+        fn supported_capabilities(&self) -> Vec<Capability> { self.capabilities.clone() }
+        fn format(&self) -> &'static str { "elf64" }
+        fn compile_program(&mut self, _program: &Program) -> Result<String, String> { Ok(String::new()) }
+        fn function_prologue(&self, _func: &BackendFunction) -> String { String::new() }
+        fn function_epilogue(&self, _func: &BackendFunction) -> String { String::new() }
+        fn compile_expression(&mut self, _expr: &Expr) -> Result<String, String> { Ok(String::new()) }
+        fn as_any(&self) -> &dyn Any { self }
+        fn as_any_mut(&mut self) -> &mut dyn Any { self }
+    }
+
+    fn mock(name: &'static str, extra: &[Capability]) -> Box<dyn Backend> {
+        let mut capabilities = vec![Capability::Linux, Capability::LongMode64];
+        capabilities.extend_from_slice(extra);
+        Box::new(MockBackend { name, capabilities })
+    }
+
+    fn module(required_capabilities: Vec<Capability>) -> BackendModule {
+        BackendModule { functions: Vec::new(), globals: Vec::new(), required_capabilities }
+    }
+
+    #[test]
+    fn test_backend_selection() {
+        use Capability::*;
+        let mut registry = BackendRegistry::new();
+        registry.register(mock("base", &[]));
+        registry.register(mock("sse", &[SSE, SSE2]));
+        registry.register(mock("avx512", &[SSE, SSE2, AVX, AVX512]));
+        registry.register(mock("avx", &[SSE, SSE2, AVX]));
+        
+        let plain = module(vec![Linux]);
+        let pick = |m: &BackendModule, host: Option<&[Capability]>| registry.find_best_backend(m, host).map(|b| b.name().to_string());
+        
+        // Most specific wins regardless of registration order
+        assert_eq!(pick(&plain, None).as_deref(), Some("avx512"));
+        assert_eq!(pick(&module(vec![AVX]), None).as_deref(), Some("avx512"));
+        
+        // Host capabilities limit what is available
+        assert_eq!(pick(&plain, Some(&[SSE, SSE2, AVX])).as_deref(), Some("avx"));
+        assert_eq!(pick(&plain, Some(&[SSE, SSE2])).as_deref(), Some("sse"));
+        assert_eq!(pick(&plain, Some(&[])).as_deref(), Some("base"));
+        
+        // No backend can satisfy the request
+        assert_eq!(pick(&module(vec![AVX512]), Some(&[SSE, SSE2, AVX])), None);
+        assert_eq!(pick(&module(vec![Graphics]), None), None);
+        
+        // Ties go to the first registered backend
+        let mut registry = BackendRegistry::new();
+        registry.register(mock("first", &[SSE]));
+        registry.register(mock("second", &[SSE2]));
+        assert_eq!(registry.find_backend(&plain).map(|b| b.name()), Some("first"));
+    }
+
+    #[test]
+    fn test_constraint_matrix() {
+        use Capability::*;
+        let hosted = mock("hosted", &[Heap, Filesystem]);
+        let bare = mock("bare", &[NoHeap, NoFilesystem]);
+        
+        // (module requirements, hosted accepts, bare accepts)
+        let matrix: [(Vec<Capability>, bool, bool); 6] = [
+            (vec![], true, true),
+            (vec![Heap], true, false),
+            (vec![Filesystem], true, false),
+            (vec![NoHeap], false, true),
+            (vec![NoHeap, Heap], false, false),
+            (vec![NoFilesystem, Filesystem], false, false),
+        ];
+        for (caps, on_hosted, on_bare) in matrix {
+            let m = module(caps.clone());
+            assert_eq!(hosted.can_compile(&m), on_hosted, "hosted with {:?}", caps);
+            assert_eq!(bare.can_compile(&m), on_bare, "bare with {:?}", caps);
+        }
+        
+        let mut registry = BackendRegistry::new();
+        registry.register(bare);
+        registry.register(hosted);
+        assert_eq!(registry.find_backend(&module(vec![Heap])).map(|b| b.name()), Some("hosted"));
+        assert_eq!(registry.find_backend(&module(vec![NoHeap])).map(|b| b.name()), Some("bare"));
+    }
+
+    #[test]
+    fn test_deterministic_output() {
+        let source = "print(\"a\")\nprint(\"b\", \"c\")\nvar s = \"d\"\nprint(f\"{s} e\")\n";
+        let program = parse_program(source).unwrap();
+        
+        let mut backend = Linux64Backend::new();
+        let first = backend.compile_program(&program).unwrap();
+        assert_eq!(first, backend.compile_program(&program).unwrap());
+        assert_eq!(first, Linux64Backend::new().compile_program(&program).unwrap());
+        
+        // Data blocks follow first use in the source
+        let positions: Vec<usize> = ["\"a\"", "\"b\"", "\" \"", "\"c\"", "\"d\"", "\" e\""].iter()
+            .map(|s| first.find(&format!(":  # {}\n", s)).unwrap())
+            .collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_riscv64_output() {
+        let program = parse_program("var x = 6\nx *= 7\nprint(\"x is\", x)\n").unwrap();
+        let asm = RiscV64Backend::new().compile_program(&program).unwrap();
+        
+        assert!(asm.starts_with("    .text\n    .globl _start\n\n_start:\n    call main\n    li a7, 93"));
+        assert!(asm.contains("    li a0, 6\n    sd a0, -24(s0)\n"));
+        assert!(asm.contains("    ld t0, 0(sp)\n    addi sp, sp, 16\n    mul a0, t0, a0\n    sd a0, -24(s0)\n"));
+        assert!(asm.contains("    call print_string\n    ld a0, -24(s0)  # x\n    call print_decimal\n    call print_newline\n"));
+        assert!(asm.contains("    li a7, 64\n    ecall\n"));
+        
+        let program = parse_program("while 1: pass\nend\n").unwrap();
+        let err = RiscV64Backend::new().compile_program(&program).unwrap_err();
+        assert_eq!(err, "while loops are not supported by the riscv64 backend at 1:1");
+        let program = parse_program("def f(): return 1\n").unwrap();
+        let err = RiscV64Backend::new().compile_program(&program).unwrap_err();
+        assert_eq!(err, "function definitions are not supported by the riscv64 backend at 1:1");
+    }
+
+    #[test]
+    fn test_aarch64_output() {
+        let source = "def add(a, b): return a + b\nvar x = add(2, 3) % 4\nprint(\"x is\", x)\n";
+        let asm = Aarch64LinuxBackend::new().compile_program(&parse_program(source).unwrap()).unwrap();
+        
+        assert!(asm.contains("main:\n    sub sp, sp, #32\n    stp x29, x30, [sp]\n    mov x29, sp\n"));
+        assert!(asm.contains("    ldr x1, [sp], #16\n    ldr x0, [sp], #16\n    bl fn_add\n"));
+        assert!(asm.contains("    sdiv x2, x1, x0\n    msub x0, x2, x0, x1\n    str x0, [x29, #16]\n"));
+        assert!(asm.contains("fn_add:\n    sub sp, sp, #32\n    stp x29, x30, [sp]\n    mov x29, sp\n    str x0, [x29, #16]\n    str x1, [x29, #24]\n"));
+        assert!(asm.contains("    b .Lfn_add_epilogue\n"));
+        assert!(asm.contains("    mov x8, #64\n    svc #0\n"));
+        assert!(asm.contains(":  // \"x is\"\n"));
+        
+        let source = "def f(a, b, c, d, e, f, g, h, i): return a\n";
+        let err = Aarch64LinuxBackend::new().compile_program(&parse_program(source).unwrap()).unwrap_err();
+        assert!(err.contains("more than 8 arguments"));
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let source = "print(\"a\\nb\\t\\\"q\\\" 'x' \\\\ \\x41 🚀\")\n";
+        let program = parse_program(source).unwrap();
+        let asm = Linux64Backend::new().compile_program(&program).unwrap();
+        
+        // Newline, tab, quote, backslash and the UTF-8 rocket all land in the byte list
+        assert!(asm.contains(".byte 0x61, 0x0a, 0x62, 0x09, 0x22, 0x71, 0x22, 0x20, 0x27, 0x78, 0x27, 0x20, 0x5c, 0x20, 0x41, 0x20, 0xf0, 0x9f, 0x9a, 0x80, 0x00\n"));
+        
+        let program = parse_program("print(\"a\\x00b\")\n").unwrap();
+        let err = Linux64Backend::new().compile_program(&program).unwrap_err();
+        assert!(err.contains("null byte"));
+    }
+    
+    #[test]
+    fn test_float_arithmetic() {
+        let source = "var x = 1.5\nvar y = 2\nx += y\nprint(x * y, x > 2)\n";
+        let program = parse_program(source).unwrap();
+        let asm = Linux64Backend::new().compile_program(&program).unwrap();
+        
+        assert!(asm.contains(&format!("mov rax, 0x{:016x}\n", 1.5f64.to_bits())));
+        // The integer operand is promoted before the SSE2 operation
+        assert!(asm.contains("    cvtsi2sd xmm0, rax\n    movq rax, xmm0\n    movq xmm1, rax\n"));
+        assert!(asm.contains("    addsd xmm0, xmm1\n"));
+        assert!(asm.contains("    mulsd xmm0, xmm1\n"));
+        assert!(asm.contains("    comisd xmm0, xmm1\n    seta al\n"));
+        assert!(asm.contains("    call print_float\n"));
+        
+        let program = parse_program("var x = 1.5 % 2\n").unwrap();
+        let err = Linux64Backend::new().compile_program(&program).unwrap_err();
+        assert!(err.contains("not supported for floats"));
+
+        // Floats cross calls as bits, so only where the signature says float
+        let program = parse_program("def half(v: float) -> float: return v / 2.0\nprint(half(3))\n").unwrap();
+        let asm = Linux64Backend::new().compile_program(&program).unwrap();
+        assert!(asm.contains("    cvtsi2sd xmm0, rax\n    movq rax, xmm0\n    push rax\n    pop rdi\n    call fn_half\n    call print_float\n"), "{}", asm);
+        let error = |source: &str| Linux64Backend::new().compile_program(&parse_program(source).unwrap()).unwrap_err();
+        assert_eq!(error("def half(v): return v\nprint(half(3.0))\n"), "Argument 1 of 'half' is a float, which needs a parameter declared float at 2:12");
+        assert_eq!(error("def half(v: float): return v / 2\nprint(half(3.0))\n"), "'half' returns a float, which needs a float return type at 1:21");
+
+        // Backends without an FPU are never picked for float code
+        let needs_float = [Capability::Linux, Capability::Float];
+        assert!(capabilities_compatible(&Linux64Backend::new().supported_capabilities(), &needs_float));
+        assert!(!capabilities_compatible(&[Capability::Linux, Capability::_NoFloat], &needs_float));
+    }
+}
@@ -0,0 +1,237 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use crate::backend::{Backend, Linux64Backend, Target};
+use crate::compiler::{CompilerConfig, ConstantFoldingPass, EarthangCompiler, OptimizationPass};
+use crate::framebuffer::{Framebuffer, SimdLevel};
+use crate::size::SizeReport;
+
+// Measurements of how long compilation takes and how many bytes come out,
+// shared by `cargo bench` and `earthang bench`. Three kinds are taken:
+//
+//   parse/<program>            time to parse a program
+//   compile/<backend>/<program> time from source to assembly
+//   size/<backend>/<program>   estimated bytes of the output, as
+//                              SizeReport::measure counts them
+//
+// Times are the median of a number of runs, in nanoseconds. Sizes do not
+// vary between runs, since the backends emit the same assembly every time.
+// A report is saved as JSON so the next run can be compared with it.
+
+/// The backends measured: each hosted target, and the payload of a
+/// `--bios-mode` disk image
+pub const BACKENDS: [&str; 4] = ["linux64", "riscv64", "aarch64", "bios64"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Measurement {
+    pub name: String,
+    /// "ns" or "bytes"
+    pub unit: String,
+    pub value: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchReport {
+    pub version: String,
+    pub iterations: usize,
+    pub measurements: Vec<Measurement>,
+}
+
+impl BenchReport {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&text).map_err(|e| format!("{} is not a benchmark report: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json + "\n").map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Measurement> {
+        self.measurements.iter().find(|m| m.name == name)
+    }
+
+    /// One line per measurement, with the change from `baseline` when it
+    /// has the same measurement
+    pub fn render(&self, baseline: Option<&BenchReport>) -> String {
+        let width = self.measurements.iter().map(|m| m.name.len()).max().unwrap_or(0);
+        let mut output = String::new();
+        for measurement in &self.measurements {
+            output.push_str(&format!("{:<width$}  {:>12} {:<5}", measurement.name, measurement.value, measurement.unit, width = width));
+            let previous = baseline.and_then(|baseline| baseline.get(&measurement.name)).filter(|m| m.unit == measurement.unit);
+            if let Some(previous) = previous.filter(|m| m.value > 0) {
+                let change = (measurement.value as f64 - previous.value as f64) / previous.value as f64 * 100.0;
+                output.push_str(&format!("  {:+.1}%", change));
+            }
+            output.push('\n');
+        }
+        output
+    }
+}
+
+/// A program of `blocks` blocks of arithmetic and prints, fifty to a loop,
+/// the same every time. It is written with what every backend compiles, so
+/// each one can be timed on a large input
+pub fn generated_source(blocks: usize) -> String {
+    // Few variables and loops, since the aarch64 backend's frames are small
+    let mut source = String::from("# Generated by earthang::bench::generated_source\nvar total = 0\n");
+    for start in (0..blocks).step_by(50) {
+        source.push_str(&format!("for k in range({}): {{\n", start % 7 + 2));
+        for i in start..blocks.min(start + 50) {
+            source.push_str(&format!(
+                "    total = {i}\n    total += (k * {m} + 3) % 7\n    print(\"block {i}\", total - (total & 5) * 2)\n",
+                i = i, m = i % 11 + 1
+            ));
+        }
+        source.push_str("}\n");
+    }
+    source
+}
+
+/// Median wall time of `iterations` runs of `run`, in nanoseconds
+pub fn time<T>(iterations: usize, mut run: impl FnMut() -> T) -> u64 {
+    let mut samples: Vec<u64> = (0..iterations.max(1))
+        .map(|_| {
+            let start = Instant::now();
+            std::hint::black_box(run());
+            start.elapsed().as_nanos() as u64
+        })
+        .collect();
+    samples.sort_unstable();
+    samples[samples.len() / 2]
+}
+
+/// Compile `source` with `backend`, one of BACKENDS, the way `earthang
+/// compile` would, returning the assembly and the target it is measured as
+pub fn compile(backend: &str, source: &str) -> Result<(String, Target), String> {
+    if backend == "bios64" {
+        // What compile_bios_image runs before linking, printing to COM1
+        let mut program = crate::compiler::parse(source)?;
+        ConstantFoldingPass.optimize(&mut program)?;
+        let assembly = Linux64Backend::new()
+            .with_bios_graphics(Framebuffer::vga_mode_13h(), SimdLevel::Sse2)
+            .with_debug_serial(true)
+            .compile_program(&program)?;
+        return Ok((assembly, Target::Linux64));
+    }
+    let target: Target = backend.parse()?;
+    let config = CompilerConfig::default().with_target(target).with_hardware_dsl(false);
+    let result = EarthangCompiler::new(config).compile_source(source, None)?;
+    Ok((result.assembly, target))
+}
+
+/// Measure every program in `programs`, a name and source each: parse time,
+/// then compile time and output size for each backend accepting it
+pub fn run(programs: &[(String, String)], iterations: usize) -> BenchReport {
+    let mut measurements = Vec::new();
+    let ns = |name: String, value: u64| Measurement { name, unit: "ns".to_string(), value };
+    for (name, source) in programs {
+        measurements.push(ns(format!("parse/{}", name), time(iterations, || crate::parser::parse_program(source).is_ok())));
+    }
+    for backend in BACKENDS {
+        for (name, source) in programs {
+            // Programs a backend rejects are left out rather than timed failing
+            let Ok((assembly, target)) = compile(backend, source) else { continue };
+            measurements.push(ns(format!("compile/{}/{}", backend, name), time(iterations, || compile(backend, source).is_ok())));
+            measurements.push(Measurement {
+                name: format!("size/{}/{}", backend, name),
+                unit: "bytes".to_string(),
+                value: SizeReport::measure(&assembly, &target).total as u64,
+            });
+        }
+    }
+    BenchReport { version: env!("CARGO_PKG_VERSION").to_string(), iterations, measurements }
+}
+
+/// The `.eg` programs in `dir`, named by file stem, in name order
+pub fn corpus(dir: &Path) -> Result<Vec<(String, String)>, String> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "eg"))
+        .collect();
+    paths.sort();
+    paths.iter()
+        .map(|path| {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            std::fs::read_to_string(path).map(|source| (name, source)).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Estimated bytes of the code compiled from statements, then of the
+    /// whole output, for programs of examples/programs and `generated`,
+    /// generated_source(20). There is room for ordinary growth; a backend
+    /// losing an optimization or doubling its runtime goes over
+    const SIZE_BUDGETS: [(&str, &str, u64, u64); 11] = [
+        ("linux64", "arithmetic", 760, 1600),
+        ("linux64", "collections", 900, 6200),
+        ("linux64", "control_flow", 530, 1350),
+        ("linux64", "expressions", 540, 1400),
+        ("linux64", "functions", 560, 1450),
+        ("linux64", "generated", 3000, 4200),
+        ("riscv64", "generated", 6700, 8000),
+        ("aarch64", "generated", 4800, 6000),
+        ("bios64", "arithmetic", 880, 2700),
+        ("bios64", "control_flow", 540, 2300),
+        ("bios64", "generated", 4500, 6800),
+    ];
+
+    #[test]
+    fn test_output_sizes_stay_within_budget() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples").join("programs");
+        let mut programs = corpus(&dir).unwrap();
+        programs.push(("generated".to_string(), generated_source(20)));
+        for (backend, program, code_budget, budget) in SIZE_BUDGETS {
+            let (_, source) = programs.iter().find(|(name, _)| name == program).unwrap();
+            let (assembly, target) = compile(backend, source).unwrap();
+            let report = SizeReport::measure(&assembly, &target);
+            let code: usize = report.statements.values().sum();
+            assert!(code as u64 <= code_budget, "{}'s code on {} is {} bytes, over its budget of {}", program, backend, code, code_budget);
+            assert!(report.total as u64 <= budget, "{} on {} is {} bytes, over its budget of {}", program, backend, report.total, budget);
+        }
+    }
+
+    #[test]
+    fn test_reports_round_trip_and_compare() {
+        let programs = vec![("generated".to_string(), generated_source(3))];
+        let report = run(&programs, 1);
+        let names: Vec<&str> = report.measurements.iter().map(|m| m.name.as_str()).collect();
+        assert!(names.contains(&"parse/generated") && BACKENDS.iter().all(|backend| names.contains(&format!("size/{}/generated", backend).as_str())), "{:?}", names);
+        // Output is deterministic, so sizes repeat exactly
+        let again = run(&programs, 1);
+        assert_eq!(report.get("size/linux64/generated"), again.get("size/linux64/generated"));
+
+        let path = std::env::temp_dir().join(format!("earthang_bench_{}.json", std::process::id()));
+        report.save(&path).unwrap();
+        let loaded = BenchReport::load(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(loaded.unwrap(), report);
+
+        let mut bigger = report.clone();
+        bigger.measurements.iter_mut().find(|m| m.name == "size/linux64/generated").unwrap().value *= 2;
+        assert!(bigger.render(Some(&report)).lines().any(|line| line.starts_with("size/linux64/generated") && line.ends_with("+100.0%")));
+    }
+}
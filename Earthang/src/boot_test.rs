@@ -0,0 +1,227 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// COM1 data and line status ports
+pub const COM1: u16 = 0x3F8;
+pub const COM1_LINE_STATUS: u16 = COM1 + 5;
+
+/// Stage of the climb from the BIOS to 64-bit code with SIMD enabled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootPhase {
+    RealMode,
+    ProtectedMode,
+    LongMode,
+    SimdEnabled,
+}
+
+impl BootPhase {
+    pub const ALL: [BootPhase; 4] = [BootPhase::RealMode, BootPhase::ProtectedMode, BootPhase::LongMode, BootPhase::SimdEnabled];
+
+    /// Line written to COM1 when boot code reaches this phase
+    pub fn marker(&self) -> &'static str {
+        match self {
+            BootPhase::RealMode => "EG:REAL",
+            BootPhase::ProtectedMode => "EG:PM32",
+            BootPhase::LongMode => "EG:LM64",
+            BootPhase::SimdEnabled => "EG:SIMD",
+        }
+    }
+}
+
+impl std::fmt::Display for BootPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            BootPhase::RealMode => "real mode",
+            BootPhase::ProtectedMode => "protected mode",
+            BootPhase::LongMode => "long mode",
+            BootPhase::SimdEnabled => "SIMD enabled",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Intel-syntax GAS that programs COM1 for 115200 baud, 8N1, FIFOs enabled
+pub fn serial_init_gas() -> String {
+    let mut asm = String::new();
+    for (port, value) in [(1, 0x00), (3, 0x80), (0, 0x01), (1, 0x00), (3, 0x03), (2, 0xC7)] {
+        asm.push_str(&format!("    mov dx, 0x{:X}\n", COM1 + port));
+        asm.push_str(&format!("    mov al, 0x{:02X}\n", value));
+        asm.push_str("    out dx, al\n");
+    }
+    asm
+}
+
+/// Intel-syntax GAS writing the marker of `phase` and a newline to COM1.
+/// It only uses immediates, AL and DX, so it assembles and runs unchanged
+/// in 16, 32 and 64-bit code wherever it is placed
+pub fn serial_marker_gas(phase: BootPhase) -> String {
+    let mut asm = format!("    # Boot progress marker: {}\n", phase);
+    for byte in phase.marker().bytes().chain([b'\n']) {
+        asm.push_str(&format!("    mov dx, 0x{:X}\n", COM1_LINE_STATUS));
+        asm.push_str("1:  in al, dx\n");
+        asm.push_str("    test al, 0x20\n");
+        asm.push_str("    jz 1b\n");
+        asm.push_str(&format!("    mov dx, 0x{:X}\n", COM1));
+        asm.push_str(&format!("    mov al, 0x{:02X}\n", byte));
+        asm.push_str("    out dx, al\n");
+    }
+    asm
+}
+
+/// Phases of `expected` whose marker is not on a line of `serial_output`
+pub fn missing_phases(serial_output: &str, expected: &[BootPhase]) -> Vec<BootPhase> {
+    expected.iter()
+        .filter(|phase| !serial_output.lines().any(|line| line.trim() == phase.marker()))
+        .copied()
+        .collect()
+}
+
+/// What a boot under QEMU printed and which expected markers never showed up
+#[derive(Debug, Clone)]
+pub struct BootReport {
+    pub serial_output: String,
+    pub missing: Vec<BootPhase>,
+    pub elapsed: Duration,
+}
+
+impl BootReport {
+    pub fn passed(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+/// Boots an image under QEMU with COM1 on stdout and watches for phase markers
+#[derive(Debug, Clone)]
+pub struct BootTest {
+    pub qemu: String,
+    pub timeout: Duration,
+    pub expected: Vec<BootPhase>,
+}
+
+impl BootTest {
+    pub fn new() -> Self {
+        Self { qemu: "qemu-system-x86_64".to_string(), timeout: Duration::from_secs(10), expected: BootPhase::ALL.to_vec() }
+    }
+
+    pub fn with_qemu(mut self, qemu: &str) -> Self {
+        self.qemu = qemu.to_string();
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_expected(mut self, expected: Vec<BootPhase>) -> Self {
+        self.expected = expected;
+        self
+    }
+
+    /// Images ending in .iso are attached as a CD-ROM, everything else as a raw disk
+    fn qemu_args(image: &Path) -> Vec<String> {
+        let mut args: Vec<String> = ["-display", "none", "-serial", "stdio", "-no-reboot", "-monitor", "none"]
+            .iter().map(|s| s.to_string()).collect();
+        let is_iso = image.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("iso"));
+        if is_iso {
+            args.extend(["-cdrom".to_string(), image.display().to_string()]);
+        } else {
+            args.extend(["-drive".to_string(), format!("format=raw,file={}", image.display())]);
+        }
+        args
+    }
+
+    /// Boot `image` until every expected marker was printed, QEMU exits or the timeout passes.
+    /// Failing to start QEMU is an error; a boot that misses markers is a report that did not pass
+    pub fn run(&self, image: &Path) -> Result<BootReport, String> {
+        if !image.exists() {
+            return Err(format!("Boot image '{}' does not exist", image.display()));
+        }
+
+        let mut child = Command::new(&self.qemu)
+            .args(Self::qemu_args(image))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => format!(
+                    "QEMU ('{}') is not installed or not on PATH; install QEMU or pass its path with --qemu", self.qemu),
+                _ => format!("Failed to start {}: {}", self.qemu, e),
+            })?;
+
+        // The serial port is read on a thread so the deadline holds even when the guest prints nothing
+        let mut stdout = child.stdout.take().ok_or("QEMU stdout was not captured")?;
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            while let Ok(n) = stdout.read(&mut buffer) {
+                if n == 0 || sender.send(buffer[..n].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let start = Instant::now();
+        let mut output = Vec::new();
+        let mut missing = self.expected.clone();
+        while !missing.is_empty() {
+            let Some(remaining) = self.timeout.checked_sub(start.elapsed()) else {
+                break;
+            };
+            match receiver.recv_timeout(remaining) {
+                Ok(bytes) => {
+                    output.extend(bytes);
+                    missing = missing_phases(&String::from_utf8_lossy(&output), &self.expected);
+                }
+                Err(_) => break,
+            }
+        }
+
+        let _ = child.kill();
+        let _ = child.wait();
+        Ok(BootReport { serial_output: String::from_utf8_lossy(&output).to_string(), missing, elapsed: start.elapsed() })
+    }
+}
+
+impl Default for BootTest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markers_and_missing_qemu() {
+        let output = "SeaBIOS\r\nEG:REAL\r\nEG:PM32\nEG:LM64x\n";
+        assert_eq!(missing_phases(output, &BootPhase::ALL), vec![BootPhase::LongMode, BootPhase::SimdEnabled]);
+
+        let gas = serial_marker_gas(BootPhase::SimdEnabled);
+        assert_eq!(gas.matches("out dx, al").count(), "EG:SIMD\n".len());
+
+        let image = std::env::temp_dir().join(format!("earthang_boot_{}.img", std::process::id()));
+        std::fs::write(&image, [0u8; 512]).unwrap();
+        let result = BootTest::new().with_qemu("earthang-no-such-qemu").run(&image);
+        let _ = std::fs::remove_file(&image);
+        assert!(result.unwrap_err().contains("not installed"));
+    }
+}
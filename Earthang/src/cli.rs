@@ -12,11 +12,13 @@
     GNU General Public License for more details.
 */
 use clap::{Parser, Subcommand, Args, ValueEnum};
-use std::path::PathBuf;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
 use colored::*;
 use std::time::Instant;
-use crate::compiler::{EarthangCompiler, CompilerConfig};
-use crate::backend::Backend;
+use crate::compiler::{EarthangCompiler, CompilerConfig, CompileError};
+use crate::messages::JsonMessage;
+use crate::backend::{Backend, Target};
 
 /// Terminal output styling
 pub mod style {
@@ -87,10 +89,15 @@ Examples:
 
 For more information, see https://github.com/Bit-Jumper-Studio/earthang
 "#)]
+#[command(arg_required_else_help = true, subcommand_required = true)]
 pub struct Cli {
-    /// Enable verbose output
-    #[arg(short, long)]
-    pub verbose: bool,
+    /// Report each compiler phase and its timing on stderr; -vv adds per-function detail
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+    
+    /// Report compiler phases as JSON lines on stderr
+    #[arg(long)]
+    pub log_json: bool,
     
     /// Enable quiet mode (suppress all output except errors)
     #[arg(short, long)]
@@ -102,7 +109,7 @@ pub struct Cli {
     
     /// Command to execute
     #[command(subcommand)]
-    pub command: Option<Commands>,
+    pub command: Commands,
 }
 
 /// Available commands
@@ -128,28 +135,70 @@ pub enum Commands {
     
     /// Hardware DSL commands
     Hardware(HardwareArgs),
+    
+    /// Show CPU features detected on this machine
+    Features,
+    
+    /// List extension modules and the targets they support
+    Modules,
+    
+    /// Rewrite source files in canonical format
+    Fmt(FmtArgs),
+    
+    /// Resolve addresses in a disk image through its symbol map
+    Inspect(InspectArgs),
+    
+    /// Run a language server for editors on stdin and stdout
+    Lsp,
+    
+    /// Compile and run snippets interactively
+    Repl,
+    
+    /// Run a program, interpreting it unless another backend is chosen
+    Run(RunArgs),
+    
+    /// Measure parse and compile times and output sizes
+    Bench(BenchArgs),
 }
 
-/// System target platforms
+// Targets are named on the command line as they are everywhere else
+impl ValueEnum for Target {
+    fn value_variants<'a>() -> &'a [Self] {
+        &Target::ALL
+    }
+    
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(clap::builder::PossibleValue::new(self.name()).help(self.description()))
+    }
+}
+
+/// Kind of file produced by the compile command
 #[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
-pub enum CliTarget {
-    Linux64,
+pub enum CliEmit {
+    /// GAS assembly text
+    Asm,
+    /// Relocatable ELF object
+    Obj,
+    /// Linked executable
+    Exe,
 }
 
-impl From<CliTarget> for crate::backend::Target {
-    fn from(val: CliTarget) -> Self {
-        match val {
-            CliTarget::Linux64 => crate::backend::Target::Linux64,
-        }
-    }
+/// How the compile command reports diagnostics
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum CliMessageFormat {
+    /// Rendered reports with the source line and a caret
+    Human,
+    /// One JSON object per line on stdout, ending with a build summary
+    Json,
 }
 
-impl CliTarget {
-    fn description(&self) -> &'static str {
-        match self {
-            CliTarget::Linux64 => "64-bit Linux ELF executable",
-        }
-    }
+/// How the run command executes a program
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum CliBackend {
+    /// Walk the AST; needs no assembler or linker
+    Interp,
+    /// Compile for Linux64, link and run the executable
+    Native,
 }
 
 /// Arguments for compile command
@@ -165,6 +214,15 @@ Examples:
   Build with optimization disabled:
     earthang compile program.lua --target linux64 --no-optimize
 
+  Build a relocatable object for ld or cc:
+    earthang compile program.lua --emit obj --output program.o
+
+  Build for a RISC-V board (needs riscv64-linux-gnu binutils):
+    earthang compile program.lua --target riscv64 --emit exe --output program
+
+  Compile a program piped in on stdin:
+    generate-program | earthang compile - --output program.s
+
 Notes:
   - Linux targets produce ELF executables
   - Use --keep-assembly to save intermediate assembly files
@@ -172,21 +230,30 @@ Notes:
   - Use --hardware to enable hardware DSL for device access
 "#)]
 pub struct CompileArgs {
-    /// Input file
-    pub file: PathBuf,
+    /// Input files, `-` for stdin; the first one's top-level code runs, the others provide functions
+    #[arg(required = true)]
+    pub files: Vec<PathBuf>,
     
     /// Output file
     #[arg(short, long)]
     pub output: Option<PathBuf>,
     
     /// Target platform
-    #[arg(short, long, value_enum, default_value_t = CliTarget::Linux64)]
-    pub target: CliTarget,
+    #[arg(short, long, value_enum, default_value_t = Target::Linux64)]
+    pub target: Target,
+    
+    /// Output kind
+    #[arg(long, value_enum, default_value_t = CliEmit::Asm, help = "What to emit: asm, obj or exe")]
+    pub emit: CliEmit,
     
     /// Keep assembly file
     #[arg(long, help = "Keep intermediate assembly file")]
     pub keep_assembly: bool,
     
+    /// Emit source line information
+    #[arg(short = 'g', long = "debug", help = "Emit DWARF line tables so gdb and objdump can map code to source lines (linux64)")]
+    pub debug: bool,
+    
     /// Disable optimization
     #[arg(long, help = "Disable code optimization")]
     pub no_optimize: bool,
@@ -195,17 +262,212 @@ pub struct CompileArgs {
     #[arg(long, help = "Enable hardware DSL for device access")]
     pub hardware: bool,
     
+    /// Maximum size of the generated code and data in bytes
+    #[arg(long, value_name = "BYTES", help = "Fail when the estimated output exceeds this many bytes")]
+    pub size_limit: Option<usize>,
+    
+    /// Print the size breakdown
+    #[arg(long, help = "Print the estimated size of each statement and string literal")]
+    pub size_report: bool,
+    
+    /// Treat analysis warnings as errors
+    #[arg(long, help = "Fail when the analysis pass reports warnings")]
+    pub deny_warnings: bool,
+    
+    /// Select backends for the host CPU
+    #[arg(long, help = "Only use CPU extensions detected on this machine")]
+    pub native: bool,
+    
     /// Show memory usage
     #[arg(long, help = "Show memory usage statistics")]
     pub memory: bool,
+    
+    /// Build a bootable disk image running the program freestanding in 64-bit mode
+    #[arg(long, help = "Boot into 64-bit mode and run the program, drawing with the fb_* builtins; writes a disk image")]
+    pub bios_mode: bool,
+    
+    /// Vector stores fb_fill and fb_present use in BIOS mode
+    #[arg(long, value_enum, default_value_t = CliSimd::Sse2, help = "Vector stores for fb_fill and fb_present; the booting CPU must support them")]
+    pub simd: CliSimd,
+    
+    /// Off-screen buffer BIOS mode drawing targets until fb_present
+    #[arg(long, value_parser = parse_address, help = "Back buffer address, e.g. 0x200000; programs calling fb_present get one by default")]
+    pub back_buffer: Option<u64>,
+    
+    /// Synchronize fb_present to the VGA vertical retrace
+    #[arg(long, help = "Make fb_present wait for vertical blank")]
+    pub vsync: bool,
+    
+    /// Where the boot sector loads the rest of a BIOS image
+    #[arg(long, value_parser = parse_address, requires = "bios_mode", help = "Load address of stage 2 and the program after it, e.g. 0x8000; defaults to 0x7E00")]
+    pub load_address: Option<u64>,
+    
+    /// Initial stack pointer of a BIOS image
+    #[arg(long, value_parser = parse_address, requires = "bios_mode", help = "Stack top below the load address; defaults to 0x7C00")]
+    pub stack_top: Option<u64>,
+    
+    /// Start of the BIOS mode heap
+    #[arg(long, value_parser = parse_address, requires = "bios_mode", help = "Heap address above the first MiB; defaults to 0x1000000")]
+    pub heap_base: Option<u64>,
+    
+    /// Limit of the BIOS mode heap
+    #[arg(long, value_parser = parse_address, requires = "bios_mode", help = "Heap size in bytes; by default the heap ends with the identity-mapped first GiB")]
+    pub heap_size: Option<u64>,
+    
+    /// BMP files image() can draw in BIOS mode, referred to by file name
+    #[arg(long = "embed-image", value_name = "BMP", help = "Embed a 24- or 32-bit BMP for image(\"name.bmp\", x, y); repeatable")]
+    pub embed_images: Vec<PathBuf>,
+    
+    /// How embedded images are rounded to the framebuffer's colors
+    #[arg(long, value_enum, default_value_t = CliDither::None, help = "Dithering for --embed-image colors the framebuffer cannot show")]
+    pub dither: CliDither,
+    
+    /// Send print() to the serial port in BIOS mode
+    #[arg(long, help = "Route print() to COM1 in --bios-mode programs, e.g. for qemu -serial stdio")]
+    pub debug_serial: bool,
+    
+    /// Leak heap objects instead of counting references to them
+    #[arg(long, help = "Disable reference counting; smaller code that never frees strings, lists or dictionaries")]
+    pub no_rc: bool,
+    
+    /// Require annotated function signatures
+    #[arg(long, help = "Fail when a function's parameters or return type are not annotated")]
+    pub strict_types: bool,
+    
+    /// Threads compiling functions
+    #[arg(short, long, help = "Compile functions on this many threads (default: one per CPU); the output does not change")]
+    pub jobs: Option<usize>,
+    
+    /// Dump the parsed program
+    #[arg(long, value_name = "FILE", num_args = 0..=1, require_equals = true, help = "Write the parsed program with its spans as JSON to FILE, or to stdout")]
+    pub dump_ast: Option<Option<PathBuf>>,
+    
+    /// Dump the assembly with the source it came from
+    #[arg(long, value_name = "FILE", num_args = 0..=1, require_equals = true, conflicts_with = "bios_mode", help = "Write the assembly with each statement's source line above its code to FILE, or to stdout")]
+    pub dump_asm_annotated: Option<Option<PathBuf>>,
+    
+    /// Write the disk image's symbol map
+    #[arg(long, value_name = "FILE", num_args = 0..=1, require_equals = true, requires = "bios_mode", help = "With --bios-mode, write each label's address, image offset, size and phase as TSV to FILE, or next to the image with a .map extension")]
+    pub map: Option<Option<PathBuf>>,
+    
+    /// Rebuild whenever a source file changes
+    #[arg(long, help = "Stay running and recompile when the sources, their includes or imported libraries change")]
+    pub watch: bool,
+    
+    /// Command to run after each successful rebuild
+    #[arg(long, value_name = "CMD", requires = "watch", help = "Shell command started after each successful --watch build and stopped before the next, e.g. a qemu invocation")]
+    pub on_success: Option<String>,
+    
+    /// Diagnostic output format
+    #[arg(long, value_enum, default_value = "human", conflicts_with = "watch", help = "Report errors, warnings and the produced files as rendered text or as JSON lines for editors")]
+    pub message_format: CliMessageFormat,
+}
+
+/// Write a --dump-* output to its file, or to stdout when none was named
+fn write_dump(path: &Option<PathBuf>, text: &str) -> Result<(), String> {
+    match path {
+        Some(path) => std::fs::write(path, text).map_err(|e| format!("Failed to write '{}': {}", path.display(), e)),
+        None => {
+            print!("{}", text);
+            Ok(())
+        }
+    }
+}
+
+/// Whether a command line names stdin, `-`, in place of a source file
+fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// How diagnostics name a source file
+fn source_name(path: &Path) -> String {
+    if is_stdin(path) { "<stdin>".to_string() } else { path.display().to_string() }
+}
+
+/// Read the source file `path` names, or all of `stdin` for `-`. Someone
+/// typing the program at a terminal, `interactive`, is told on `prompt` how
+/// to end it; a pipe gets no prompt
+fn read_source(path: &Path, stdin: &mut dyn Read, interactive: bool, prompt: &mut dyn Write) -> Result<String, String> {
+    if !is_stdin(path) {
+        return std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read source file '{}': {}", path.display(), e));
+    }
+    if interactive {
+        let _ = writeln!(prompt, "Reading the program from stdin; end it with Ctrl+D");
+    }
+    let mut source = String::new();
+    stdin.read_to_string(&mut source).map_err(|e| format!("Failed to read the program from stdin: {}", e))?;
+    Ok(source)
+}
+
+/// Read a source file, or the process's stdin for `-`
+fn read_source_or_stdin(path: &Path) -> Result<String, String> {
+    let stdin = std::io::stdin();
+    let interactive = stdin.is_terminal();
+    read_source(path, &mut stdin.lock(), interactive, &mut std::io::stderr())
+}
+
+/// Where compile writes: --output, else next to the first source, or in the
+/// current directory as `stdin.*` for a program read from stdin
+fn output_path(args: &CompileArgs) -> PathBuf {
+    if let Some(output) = &args.output {
+        return output.clone();
+    }
+    let input = &args.files[0];
+    let mut path = if is_stdin(input) { PathBuf::from("stdin") } else { input.clone() };
+    path.set_extension(if args.bios_mode { "img" } else { "elf" });
+    path
+}
+
+/// Decimal or 0x-prefixed hexadecimal address
+fn parse_address(text: &str) -> Result<u64, String> {
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| format!("'{}' is not an address", text))
+}
+
+/// SIMD level of BIOS mode drawing code
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum CliSimd {
+    Sse2,
+    Avx2,
+    Avx512,
+}
+
+impl From<CliSimd> for crate::framebuffer::SimdLevel {
+    fn from(val: CliSimd) -> Self {
+        match val {
+            CliSimd::Sse2 => crate::framebuffer::SimdLevel::Sse2,
+            CliSimd::Avx2 => crate::framebuffer::SimdLevel::Avx2,
+            CliSimd::Avx512 => crate::framebuffer::SimdLevel::Avx512,
+        }
+    }
+}
+
+/// Dithering of embedded images
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum CliDither {
+    None,
+    Ordered,
+}
+
+impl From<CliDither> for crate::image::Dither {
+    fn from(val: CliDither) -> Self {
+        match val {
+            CliDither::None => crate::image::Dither::None,
+            CliDither::Ordered => crate::image::Dither::Ordered,
+        }
+    }
 }
 
 /// Arguments for generate command
 #[derive(Args)]
 pub struct GenerateArgs {
     /// Type to generate
-    #[arg(short, long, value_enum, default_value_t = CliTarget::Linux64)]
-    pub r#type: CliTarget,
+    #[arg(short, long, value_enum, default_value_t = Target::Linux64)]
+    pub r#type: Target,
     
     /// Output file
     #[arg(short, long)]
@@ -214,13 +476,92 @@ pub struct GenerateArgs {
     /// Include hardware DSL example
     #[arg(long, help = "Include hardware DSL example")]
     pub hardware_example: bool,
+    
+    /// Disk image to generate instead of example code
+    #[command(subcommand)]
+    pub image: Option<GenerateCommands>,
+}
+
+/// Generate subcommands
+#[derive(Subcommand)]
+pub enum GenerateCommands {
+    /// FAT12 floppy or FAT16 disk image carrying a kernel and other files
+    FatImage(FatImageArgs),
+    /// El Torito bootable ISO9660 image
+    Iso(IsoArgs),
+}
+
+/// File system of a generated disk image
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum CliFat {
+    /// 1.44 MB floppy
+    Fat12,
+    /// Hard disk or USB image
+    Fat16,
+}
+
+/// Arguments for generate fat-image
+#[derive(Args)]
+#[command(after_help = r#"
+Examples:
+  Floppy with a kernel and an asset:
+    earthang generate fat-image --kernel kernel.bin --embed logo.bmp --output floppy.img
+
+  16 MB FAT16 image with custom boot code:
+    earthang generate fat-image --fat fat16 --sectors 32768 --boot boot.bin --output disk.img
+"#)]
+pub struct FatImageArgs {
+    /// Kernel stored as KERNEL.BIN
+    #[arg(long)]
+    pub kernel: Option<PathBuf>,
+    
+    /// Extra file stored under its own 8.3 name
+    #[arg(long, value_name = "PATH")]
+    pub embed: Vec<PathBuf>,
+    
+    /// Boot code placed behind the BIOS parameter block
+    #[arg(long, help = "Boot code placed behind the BIOS parameter block; it runs at 0x7C3E")]
+    pub boot: Option<PathBuf>,
+    
+    /// File system type
+    #[arg(long, value_enum, default_value_t = CliFat::Fat12)]
+    pub fat: CliFat,
+    
+    /// Size of a FAT16 image in sectors
+    #[arg(long, default_value_t = 32768)]
+    pub sectors: u32,
+    
+    /// Output file
+    #[arg(short, long, default_value = "disk.img")]
+    pub output: PathBuf,
+}
+
+/// Arguments for generate iso
+#[derive(Args)]
+#[command(after_help = r#"
+Examples:
+  CD image booting a stage-1 + stage-2 loader:
+    earthang generate iso --boot boot.bin --kernel kernel.bin --output boot.iso
+"#)]
+pub struct IsoArgs {
+    /// Boot image loaded to 0x7C00 without emulation
+    #[arg(long)]
+    pub boot: PathBuf,
+    
+    /// Kernel stored as KERNEL.BIN
+    #[arg(long)]
+    pub kernel: Option<PathBuf>,
+    
+    /// Output file
+    #[arg(short, long, default_value = "boot.iso")]
+    pub output: PathBuf,
 }
 
 /// Arguments for test command
 #[derive(Args)]
 pub struct TestArgs {
     /// Test suite
-    #[arg(short, long, default_value = "basic")]
+    #[arg(short, long, default_value = "basic", value_parser = ["all", "basic", "linux", "lua", "hardware", "full"])]
     pub suite: String,
     
     /// Verbose test output
@@ -234,6 +575,136 @@ pub struct TestArgs {
     /// Test hardware DSL
     #[arg(long, help = "Test hardware DSL functionality")]
     pub hardware: bool,
+    
+    /// Boot test to run instead of a suite
+    #[command(subcommand)]
+    pub command: Option<TestCommands>,
+}
+
+/// Test subcommands
+#[derive(Subcommand)]
+pub enum TestCommands {
+    /// Boot an image under QEMU and check its COM1 progress markers
+    Boot(BootTestArgs),
+    
+    /// Run example programs through the interpreter and the compiler and compare their output
+    Examples(ExamplesTestArgs),
+}
+
+/// Boot phase whose serial marker a boot test expects
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum CliBootPhase {
+    Real,
+    Protected,
+    Long,
+    Simd,
+}
+
+impl From<CliBootPhase> for crate::boot_test::BootPhase {
+    fn from(val: CliBootPhase) -> Self {
+        match val {
+            CliBootPhase::Real => crate::boot_test::BootPhase::RealMode,
+            CliBootPhase::Protected => crate::boot_test::BootPhase::ProtectedMode,
+            CliBootPhase::Long => crate::boot_test::BootPhase::LongMode,
+            CliBootPhase::Simd => crate::boot_test::BootPhase::SimdEnabled,
+        }
+    }
+}
+
+/// Arguments for test examples
+#[derive(Args)]
+pub struct ExamplesTestArgs {
+    /// Directory of .eg programs, with optional NAME.in input and NAME.out expected output
+    #[arg(default_value = "examples/programs")]
+    pub dir: PathBuf,
+    
+    /// Only run the interpreter, even when the toolchain is installed
+    #[arg(long)]
+    pub interp_only: bool,
+}
+
+/// Arguments for test boot
+#[derive(Args)]
+#[command(after_help = r#"
+Examples:
+  Check every phase of a disk image:
+    earthang test boot disk.img
+
+  Only the real-mode marker, with a custom QEMU and a longer timeout:
+    earthang test boot boot.iso --expect real --qemu /opt/qemu/bin/qemu-system-x86_64 --timeout 30
+"#)]
+pub struct BootTestArgs {
+    /// Disk image, or an ISO when the name ends in .iso
+    pub image: PathBuf,
+    
+    /// QEMU binary
+    #[arg(long, default_value = "qemu-system-x86_64")]
+    pub qemu: String,
+    
+    /// Seconds to wait for all markers
+    #[arg(long, default_value_t = 10)]
+    pub timeout: u64,
+    
+    /// Phases that must be reached; all of them by default
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub expect: Vec<CliBootPhase>,
+}
+
+/// Arguments for the fmt command
+#[derive(Args)]
+pub struct FmtArgs {
+    /// Source files to format in place
+    #[arg(required = true)]
+    pub files: Vec<PathBuf>,
+    
+    /// Only report files that are not formatted, failing if there are any
+    #[arg(long)]
+    pub check: bool,
+}
+
+/// Arguments for the inspect command
+#[derive(Args)]
+pub struct InspectArgs {
+    /// Disk image built with --bios-mode
+    pub image: PathBuf,
+    
+    /// Symbol map written by `compile --map`; defaults to the image with a .map extension
+    #[arg(long, value_name = "FILE")]
+    pub map: Option<PathBuf>,
+    
+    /// Address to resolve, e.g. 0x7C63; repeatable. Without one the whole map is listed
+    #[arg(long = "addr", value_name = "ADDRESS", value_parser = parse_address)]
+    pub addrs: Vec<u64>,
+}
+
+/// Arguments for the run command
+#[derive(Args)]
+pub struct RunArgs {
+    /// Source file to run, `-` for stdin
+    pub file: PathBuf,
+    
+    /// How to execute the program
+    #[arg(long, value_enum, default_value = "interp")]
+    pub backend: CliBackend,
+}
+
+/// Arguments for the bench command
+#[derive(Args)]
+pub struct BenchArgs {
+    /// Programs to measure; a large generated program when none are given
+    pub files: Vec<PathBuf>,
+    
+    /// Runs per time measurement, of which the median is reported
+    #[arg(long, default_value_t = 5)]
+    pub iterations: usize,
+    
+    /// Save the results as JSON to FILE
+    #[arg(long, value_name = "FILE")]
+    pub json: Option<PathBuf>,
+    
+    /// Show the change from results an earlier run saved with --json
+    #[arg(long, value_name = "FILE")]
+    pub baseline: Option<PathBuf>,
 }
 
 /// Arguments for hardware commands
@@ -297,102 +768,315 @@ impl Cli {
         // Set colored output control
         colored::control::set_override(true);
         
+        let verbose = self.verbose > 0;
         match &self.command {
-            Some(command) => match command {
-                Commands::Compile(args) => self.handle_compile(args, self.verbose),
-                Commands::Test(args) => self.handle_test(args, self.verbose),
-                Commands::Version => self.handle_version(),
-                Commands::Check => self.handle_check(self.verbose),
-                Commands::Targets => self.handle_targets(self.verbose),
-                Commands::Generate(args) => self.handle_generate(args, self.verbose),
-                Commands::Hardware(args) => self.handle_hardware(args, self.verbose),
-            },
-            None => {
-                if !self.quiet {
-                    println!("{}", "No command specified. Use --help for usage information.".yellow());
-                }
-                Ok(())
-            }
+            Commands::Compile(args) => self.handle_compile(args, verbose),
+            Commands::Test(args) => self.handle_test(args, verbose),
+            Commands::Version => self.handle_version(),
+            Commands::Check => self.handle_check(verbose),
+            Commands::Targets => self.handle_targets(verbose),
+            Commands::Generate(args) => self.handle_generate(args, verbose),
+            Commands::Hardware(args) => self.handle_hardware(args, verbose),
+            Commands::Features => self.handle_features(),
+            Commands::Modules => self.handle_modules(),
+            Commands::Fmt(args) => self.handle_fmt(args),
+            Commands::Inspect(args) => self.handle_inspect(args),
+            Commands::Lsp => crate::lsp::serve(std::io::stdin().lock(), std::io::stdout().lock()),
+            Commands::Repl => self.handle_repl(),
+            Commands::Run(args) => self.handle_run(args),
+            Commands::Bench(args) => self.handle_bench(args),
+        }
+    }
+    
+    /// Where compiler phases are reported: nowhere unless -v or --log-json asks
+    fn logger(&self) -> crate::logging::Logger {
+        let mut level = crate::logging::Level::from_verbosity(self.verbose);
+        if self.log_json {
+            level = level.max(crate::logging::Level::Phases);
         }
+        crate::logging::Logger::stderr(level, self.log_json)
     }
     
     fn handle_compile(&self, args: &CompileArgs, verbose: bool) -> Result<(), String> {
+        if args.message_format == CliMessageFormat::Json {
+            // Nothing but JSON goes to stdout, so progress output and colors are off
+            colored::control::set_override(false);
+            return match self.compile_once(args, false) {
+                Ok(artifacts) => {
+                    println!("{}", JsonMessage::success(&artifacts).to_line());
+                    Ok(())
+                }
+                Err(e) => {
+                    let message = e.trim_start_matches("✗ ").to_string();
+                    println!("{}", JsonMessage::failure(message.clone()).to_line());
+                    Err(message)
+                }
+            };
+        }
+        if !args.watch {
+            return self.compile_once(args, verbose).map(|_| ());
+        }
+        if args.files.iter().any(|file| is_stdin(file)) {
+            return Err("--watch needs source files; stdin cannot be watched for changes".to_string());
+        }
+        let search_paths = [PathBuf::from("."), PathBuf::from("stdlib")];
+        crate::watch::WatchCommand::new()
+            .with_on_success(args.on_success.clone())
+            .run(|| (self.compile_once(args, verbose).map(|_| ()), crate::watch::source_dependencies(&args.files, &search_paths)))
+    }
+    
+    /// Compile `args.files`, returning the files written
+    fn compile_once(&self, args: &CompileArgs, verbose: bool) -> Result<Vec<PathBuf>, String> {
     let progress = Progress::new(verbose);
+    let json = args.message_format == CliMessageFormat::Json;
+    let quiet = self.quiet || json;
+    let emit = |message: JsonMessage| println!("{}", message.to_line());
+    let mut artifacts = Vec::new();
     
-    if !self.quiet {
+    if !quiet {
         println!("{}", style::section("COMPILATION"));
-        println!("  {} {}", "Source:".cyan(), style::path(&args.file));
-        println!("  {} {}", "Target:".cyan(), style::target(&format!("{:?}", args.target)));
+        let sources: Vec<String> = args.files.iter().map(style::path).collect();
+        println!("  {} {}", "Source:".cyan(), sources.join(", "));
+        println!("  {} {}", "Target:".cyan(), style::target(args.target.name()));
         if args.hardware {
             println!("  {} {}", "Hardware DSL:".cyan(), "Enabled".green().bold());
         }
     }
     
-    let input_file = &args.file;
+    let input_file = &args.files[0];
     
-    // Check if file exists
-    if !input_file.exists() {
-        return Err(progress.error(&format!("File '{}' not found. Please check the filename and path.", input_file.display())));
+    if args.files.iter().filter(|file| is_stdin(file)).count() > 1 {
+        return Err(progress.error("stdin, '-', can only be read once"));
     }
-    
-    // Check if it's actually a file
-    if !input_file.is_file() {
-        return Err(progress.error(&format!("'{}' is not a valid file.", input_file.display())));
+    for file in args.files.iter().filter(|file| !is_stdin(file)) {
+        // Check if file exists
+        if !file.exists() {
+            return Err(progress.error(&format!("File '{}' not found. Please check the filename and path.", file.display())));
+        }
+        
+        // Check if it's actually a file
+        if !file.is_file() {
+            return Err(progress.error(&format!("'{}' is not a valid file.", file.display())));
+        }
     }
     
-    let output_file = args.output.as_ref().map_or_else(|| {
-        let mut path = input_file.clone();
-        path.set_extension("elf");
-        path
-    }, |p| p.clone());
-    
-    let target: crate::backend::Target = args.target.into();
+    let output_file = output_path(args);
     
-    progress.step("Reading source file...");
-    let source = std::fs::read_to_string(input_file)
-        .map_err(|e| progress.error(&format!("Failed to read source file '{}': {}", input_file.display(), e)))?;
+    let target = args.target;
     
-    progress.step("Parsing syntax...");
-    crate::parser::parse_program(&source)
-        .map_err(|e| progress.error(&format!("Parse error in '{}': {:?}", input_file.display(), e)))?;
+    let read_and_parse = |path: &PathBuf| {
+        progress.step(&format!("Reading {}...", source_name(path)));
+        let source = read_source_or_stdin(path).map_err(|e| progress.error(&e))?;
+        
+        progress.step("Parsing syntax...");
+        let file_name = source_name(path);
+        let program = crate::parser::parse_program(&source).map_err(|errors| {
+            let summary = format!("{} parse error{} in '{}'", errors.len(), if errors.len() == 1 { "" } else { "s" }, file_name);
+            if json {
+                errors.iter().for_each(|e| emit(JsonMessage::from_parse_error(e, &file_name)));
+                return progress.error(&summary);
+            }
+            let rendered: Vec<String> = errors.iter().map(|e| e.render(&file_name, &source)).collect();
+            format!("{}\n\n{}", progress.error(&summary), rendered.join("\n"))
+        })?;
+        Ok::<_, String>((source, program))
+    };
+    let (source, program) = read_and_parse(input_file)?;
+    let file_name = source_name(input_file);
+    let linked_files = args.files[1..].iter()
+        .map(|path| read_and_parse(path).map(|(_, program)| (path.clone(), program)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if !linked_files.is_empty() && (args.bios_mode || args.dump_asm_annotated.is_some() || args.size_report) {
+        return Err(progress.error("--bios-mode, --dump-asm-annotated and --size-report take a single source file"));
+    }
+    if json && (matches!(args.dump_ast, Some(None)) || matches!(args.dump_asm_annotated, Some(None)) || args.size_report) {
+        return Err(progress.error("--message-format json keeps stdout for messages; give --dump-ast and --dump-asm-annotated a FILE and leave out --size-report"));
+    }
+    // Analysis findings go with the result, so a failed compilation reports them itself
+    let compile_failed = |e: String| {
+        let error = CompileError::from_message(e);
+        let summary = format!("Compilation of '{}' failed", file_name);
+        if json {
+            crate::analysis::analyze(&program, &source).iter().for_each(|d| emit(JsonMessage::from_diagnostic(d, Some(&file_name))));
+            emit(JsonMessage::from_compile_error(&error, &file_name));
+            return progress.error(&summary);
+        }
+        format!("{}\n\n{}", progress.error(&summary), error.render(&file_name, &source))
+    };
+    if let Some(path) = &args.dump_ast {
+        let json = serde_json::to_string_pretty(&program).map_err(|e| progress.error(&format!("Failed to dump the AST: {}", e)))?;
+        write_dump(path, &format!("{}\n", json)).map_err(|e| progress.error(&e))?;
+        artifacts.extend(path.clone());
+    }
     
-    if !self.quiet {
+    if !quiet {
         println!("  {} {}", "Output:".cyan(), style::path(&output_file));
     }
     
+    if args.bios_mode {
+        progress.step("Building BIOS disk image...");
+        let mut framebuffer = crate::framebuffer::Framebuffer::vga_mode_13h().with_vsync(args.vsync);
+        if let Some(address) = args.back_buffer {
+            framebuffer = framebuffer.with_back_buffer(address);
+        }
+        let format = crate::image::PixelFormat::for_bytes_per_pixel(framebuffer.bytes_per_pixel).map_err(|e| progress.error(&e))?;
+        let images = args.embed_images.iter()
+            .map(|path| crate::image::EmbeddedImage::load(path, format, args.dither.into()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| progress.error(&e))?;
+        let mut memory = crate::framebuffer::MemoryLayout::default();
+        let below_4g = |flag: &str, address: u64| u32::try_from(address)
+            .map_err(|_| progress.error(&format!("{} 0x{:X} must be below 0x100000000", flag, address)));
+        if let Some(address) = args.load_address {
+            memory = memory.with_load_address(below_4g("--load-address", address)?);
+        }
+        if let Some(address) = args.stack_top {
+            memory = memory.with_stack_top(below_4g("--stack-top", address)?);
+        }
+        if let Some(address) = args.heap_base {
+            memory = memory.with_heap_base(address);
+        }
+        if let Some(bytes) = args.heap_size {
+            memory = memory.with_heap_size(bytes);
+        }
+        let work_dir = std::env::temp_dir().join(format!("earthang_bios_{}", std::process::id()));
+        std::fs::create_dir_all(&work_dir)
+            .map_err(|e| progress.error(&format!("Failed to create '{}': {}", work_dir.display(), e)))?;
+        let mut config = CompilerConfig::default()
+            .with_refcounting(!args.no_rc)
+            .with_strict_types(args.strict_types)
+            .with_memory_layout(memory);
+        // Includes are found next to the source first
+        if let Some(dir) = input_file.parent().filter(|_| !is_stdin(input_file)) {
+            config.search_paths.insert(0, dir.to_path_buf());
+        }
+        let options = crate::framebuffer::BiosOptions::default()
+            .with_framebuffer(framebuffer)
+            .with_simd(args.simd.into())
+            .with_images(images)
+            .with_debug_serial(args.debug_serial);
+        let image = crate::framebuffer::compile_bios_image(&source, &config, &options, &work_dir);
+        let _ = std::fs::remove_dir(&work_dir);
+        let image = image.map_err(compile_failed)?;
+        std::fs::write(&output_file, &image.bytes)
+            .map_err(|e| progress.error(&format!("Failed to write output file '{}': {}", output_file.display(), e)))?;
+        artifacts.push(output_file.clone());
+        if let Some(path) = &args.map {
+            let path = path.clone().unwrap_or_else(|| output_file.with_extension("map"));
+            image.symbols.save(&path).map_err(|e| progress.error(&e))?;
+            progress.step(&format!("Wrote symbol map {}", path.display()));
+            artifacts.push(path);
+        }
+        if !quiet {
+            progress.done("Disk image created!");
+            print!("{}", image.manifest_text());
+        }
+        return Ok(artifacts);
+    }
+    
     let config = CompilerConfig {
         target,
         verbose,
         keep_assembly: args.keep_assembly,
         optimize: !args.no_optimize,
         modules: Vec::new(),
-        debug_info: false,
+        debug_info: args.debug,
         include_stdlib: false,
         hardware_dsl_enabled: args.hardware,
-        code_size_limit: None,
+        code_size_limit: args.size_limit,
+        refcounting: !args.no_rc,
+        strict_types: args.strict_types,
+        jobs: args.jobs,
         search_paths: vec![PathBuf::from("."), PathBuf::from("stdlib")],
+        host_capabilities: args.native.then(crate::hardware::detect_capabilities),
+        logger: self.logger(),
+        // --load-address and the other layout flags require --bios-mode
+        memory: crate::framebuffer::MemoryLayout::default(),
     };
+    let logger = config.logger.clone();
     
     progress.step("Compiling to assembly...");
     let mut compiler = EarthangCompiler::new(config);
-    let result = compiler.compile_source(&source, Some(&input_file))?;
+    let result = if linked_files.is_empty() {
+        compiler.compile_source(&source, Some(input_file)).map_err(compile_failed)?
+    } else {
+        // Spans of a linked program do not say which file they are in, so nothing is quoted
+        progress.step(&format!("Linking {} files...", args.files.len()));
+        let mut files = vec![(input_file.clone(), program)];
+        files.extend(linked_files);
+        compiler.compile_linked(files).map_err(|e| {
+            if json {
+                emit(JsonMessage::error("compile", e, None, None));
+                return progress.error("Compilation of the linked files failed");
+            }
+            format!("{}\n\n{}", progress.error("Compilation of the linked files failed"), e)
+        })?
+    };
+    
+    for diagnostic in &result.diagnostics {
+        if json {
+            emit(JsonMessage::from_diagnostic(diagnostic, (args.files.len() == 1).then_some(file_name.as_str())));
+        } else if args.files.len() == 1 {
+            eprintln!("{}", diagnostic.render(&file_name, &source).yellow());
+        } else {
+            eprintln!("{}", format!("warning: {}", diagnostic).yellow());
+        }
+    }
+    if args.deny_warnings && !result.diagnostics.is_empty() {
+        let count = result.diagnostics.len();
+        return Err(progress.error(&format!("{} warning{} denied by --deny-warnings", count, if count == 1 { "" } else { "s" })));
+    }
     
-    progress.step("Writing output file...");
-    std::fs::write(&output_file, result.assembly)
-        .map_err(|e| progress.error(&format!("Failed to write output file '{}': {}", output_file.display(), e)))?;
+    if let Some(path) = &args.dump_asm_annotated {
+        write_dump(path, &crate::size::annotate_assembly(&result.assembly, &source)).map_err(|e| progress.error(&e))?;
+        artifacts.extend(path.clone());
+    }
+    
+    if args.size_report {
+        let report = crate::size::SizeReport::measure(&result.assembly, &target);
+        println!("{}", report.render(&source, usize::MAX));
+    }
+    
+    match args.emit {
+        CliEmit::Asm => {
+            progress.step("Writing output file...");
+            std::fs::write(&output_file, result.assembly)
+                .map_err(|e| progress.error(&format!("Failed to write output file '{}': {}", output_file.display(), e)))?;
+        }
+        CliEmit::Obj => {
+            progress.step("Assembling object file...");
+            crate::compiler::assemble_object_with_logger(&result.assembly, &output_file, target, args.keep_assembly, &logger)
+                .map_err(|e| progress.error(&e))?;
+        }
+        CliEmit::Exe => {
+            progress.step("Assembling and linking...");
+            crate::compiler::assemble_and_link_with_logger(&result.assembly, &output_file, target, args.keep_assembly, &logger)
+                .map_err(|e| progress.error(&e))?;
+        }
+    }
+    artifacts.push(output_file.clone());
+    if args.keep_assembly && args.emit != CliEmit::Asm {
+        artifacts.push(output_file.with_extension("s"));
+    }
     
-    if !self.quiet {
+    if !quiet {
         progress.done("Compilation successful!");
         println!();
         
-        let target_type = "Linux ELF executable";
+        let target_type = match args.emit {
+            CliEmit::Asm => "Linux assembly file",
+            CliEmit::Obj => "Linux ELF object",
+            CliEmit::Exe => "Linux ELF executable",
+        };
         
         println!("  {} {} {} created", "✓".green(), target_type, style::path(&output_file).bold());
         
-        println!("  {} Make executable: {}", ">".blue(), format!("chmod +x {}", output_file.display()).cyan());
+        if args.emit == CliEmit::Exe {
+            println!("  {} Make executable: {}", ">".blue(), format!("chmod +x {}", output_file.display()).cyan());
+        }
         
-        if args.keep_assembly {
-            let asm_file = output_file.with_extension("asm");
+        if args.keep_assembly && args.emit != CliEmit::Asm {
+            let asm_file = output_file.with_extension("s");
             println!("  {} {}", "Assembly saved to:".dimmed(), style::path(&asm_file));
         }
         
@@ -401,23 +1085,100 @@ impl Cli {
         }
     }
     
-    Ok(())
+    Ok(artifacts)
 }
     
+    fn handle_fat_image(&self, args: &FatImageArgs, verbose: bool) -> Result<(), String> {
+        let progress = Progress::new(verbose);
+        
+        if !self.quiet {
+            println!("{}", style::section("FAT IMAGE"));
+            println!("  {} {:?}", "File system:".cyan(), args.fat);
+        }
+        
+        let bpb = match args.fat {
+            CliFat::Fat12 => crate::fat::BiosParameterBlock::floppy_1440(),
+            CliFat::Fat16 => crate::fat::BiosParameterBlock::fat16(args.sectors).map_err(|e| progress.error(&e))?,
+        };
+        let mut builder = crate::fat::FatImageBuilder::new(bpb);
+        
+        let read = |path: &PathBuf| std::fs::read(path)
+            .map_err(|e| progress.error(&format!("Failed to read '{}': {}", path.display(), e)));
+        if let Some(boot) = &args.boot {
+            builder = builder.with_boot_code(read(boot)?);
+        }
+        let mut files = Vec::new();
+        if let Some(kernel) = &args.kernel {
+            files.push(("KERNEL.BIN".to_string(), read(kernel)?));
+        }
+        for path in &args.embed {
+            let name = path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .ok_or_else(|| progress.error(&format!("'{}' has no file name", path.display())))?;
+            files.push((name, read(path)?));
+        }
+        for (name, data) in files {
+            progress.step(&format!("Adding {} ({} bytes)", name, data.len()));
+            builder = builder.with_file(&name, data);
+        }
+        
+        progress.step("Laying out the volume...");
+        let image = builder.build().map_err(|e| progress.error(&e))?;
+        std::fs::write(&args.output, &image)
+            .map_err(|e| progress.error(&format!("Failed to write '{}': {}", args.output.display(), e)))?;
+        
+        if !self.quiet {
+            progress.done("Disk image created!");
+            println!("  {} {} ({} bytes)", "Output written to:".green(), style::path(&args.output).bold(), image.len());
+        }
+        Ok(())
+    }
+    
+    fn handle_iso(&self, args: &IsoArgs, verbose: bool) -> Result<(), String> {
+        let progress = Progress::new(verbose);
+        
+        if !self.quiet {
+            println!("{}", style::section("ISO IMAGE"));
+        }
+        
+        let read = |path: &PathBuf| std::fs::read(path)
+            .map_err(|e| progress.error(&format!("Failed to read '{}': {}", path.display(), e)));
+        let boot = read(&args.boot)?;
+        let kernel = match &args.kernel {
+            Some(path) => read(path)?,
+            None => Vec::new(),
+        };
+        
+        progress.step(&format!("Writing El Torito image ({} byte boot image, {} byte kernel)...", boot.len(), kernel.len()));
+        crate::iso::write_bootable_iso(&boot, &kernel, &args.output).map_err(|e| progress.error(&e))?;
+        
+        if !self.quiet {
+            progress.done("ISO image created!");
+            println!("  {} {}", "Output written to:".green(), style::path(&args.output).bold());
+        }
+        Ok(())
+    }
+    
     fn handle_generate(&self, args: &GenerateArgs, verbose: bool) -> Result<(), String> {
+        match &args.image {
+            Some(GenerateCommands::FatImage(image_args)) => return self.handle_fat_image(image_args, verbose),
+            Some(GenerateCommands::Iso(image_args)) => return self.handle_iso(image_args, verbose),
+            None => {}
+        }
+        
         let progress = Progress::new(verbose);
-        let target: crate::backend::Target = args.r#type.into();
+        let target = args.r#type;
         
         if !self.quiet {
             println!("{}", style::section("CODE GENERATION"));
-            println!("  {} {}", "Target:".cyan(), style::target(&format!("{:?}", target)));
+            println!("  {} {}", "Target:".cyan(), style::target(target.name()));
             if args.hardware_example {
                 println!("  {} {}", "Hardware example:".cyan(), "Included".green().bold());
             }
         }
         
         let output = match target {
-            crate::backend::Target::Linux64 => {
+            Target::Linux64 => {
                 progress.step("Generating 64-bit Linux ELF executable...");
                 let mut code = String::new();
                 
@@ -473,6 +1234,19 @@ impl Cli {
                 
                 code
             }
+            Target::RiscV64 | Target::Aarch64 => {
+                progress.step(&format!("Generating {} Linux assembly...", target));
+                if args.hardware_example {
+                    progress.warn("The hardware DSL example is x86 only and was skipped");
+                }
+                let program = crate::parser::parse_program("print(\"Hello earthang!\")\n")
+                    .map_err(|e| progress.error(&format!("{:?}", e)))?;
+                if target == Target::RiscV64 {
+                    crate::backend::RiscV64Backend::new().compile_program(&program)?
+                } else {
+                    crate::backend::Aarch64LinuxBackend::new().compile_program(&program)?
+                }
+            }
         };
         
         if let Some(output_path) = &args.output {
@@ -485,8 +1259,18 @@ impl Cli {
                 println!("  {} {}", "Output written to:".green(), style::path(output_path).bold());
                 
                 // Show compilation command
-                println!("  {} {}", "Compile with NASM:".dimmed(), format!("nasm -f elf64 {} -o {}.o", output_path.display(), output_path.with_extension("").display()).cyan());
-                println!("  {} {}", "Link with GCC:".dimmed(), format!("gcc -no-pie {}.o -o {}.elf", output_path.with_extension("").display(), output_path.with_extension("").display()).cyan());
+                let stem = output_path.with_extension("");
+                match target {
+                    Target::Linux64 => {
+                        println!("  {} {}", "Compile with NASM:".dimmed(), format!("nasm -f elf64 {} -o {}.o", output_path.display(), stem.display()).cyan());
+                        println!("  {} {}", "Link with GCC:".dimmed(), format!("gcc -no-pie {}.o -o {}.elf", stem.display(), stem.display()).cyan());
+                    }
+                    _ => {
+                        let prefix = crate::compiler::toolchain_prefix(target);
+                        println!("  {} {}", "Assemble with:".dimmed(), format!("{}as {} -o {}.o", prefix, output_path.display(), stem.display()).cyan());
+                        println!("  {} {}", "Link with:".dimmed(), format!("{}ld {}.o -o {}.elf", prefix, stem.display(), stem.display()).cyan());
+                    }
+                }
             }
         } else {
             println!("\n{}", output);
@@ -585,7 +1369,104 @@ impl Cli {
         }
     }
     
+    fn handle_boot_test(&self, args: &BootTestArgs, verbose: bool) -> Result<(), String> {
+        let progress = Progress::new(verbose);
+        let expected: Vec<crate::boot_test::BootPhase> = match args.expect.is_empty() {
+            true => crate::boot_test::BootPhase::ALL.to_vec(),
+            false => args.expect.iter().map(|&phase| phase.into()).collect(),
+        };
+        
+        if !self.quiet {
+            println!("{}", style::section("BOOT TEST"));
+            println!("  {} {}", "Image:".cyan(), style::path(&args.image));
+            println!("  {} {}", "QEMU:".cyan(), args.qemu);
+        }
+        
+        progress.step(&format!("Booting for up to {}s...", args.timeout));
+        let report = crate::boot_test::BootTest::new()
+            .with_qemu(&args.qemu)
+            .with_timeout(std::time::Duration::from_secs(args.timeout))
+            .with_expected(expected.clone())
+            .run(&args.image)
+            .map_err(|e| progress.error(&e))?;
+        
+        if !self.quiet {
+            for phase in &expected {
+                let mark = if report.missing.contains(phase) { "✗".red() } else { "✓".green() };
+                println!("  {} {:<16} {}", mark, phase.to_string(), phase.marker().dimmed());
+            }
+        }
+        if verbose {
+            println!("{}", style::section("SERIAL OUTPUT"));
+            println!("{}", report.serial_output);
+        }
+        
+        if !report.passed() {
+            let missing: Vec<String> = report.missing.iter().map(|phase| phase.to_string()).collect();
+            return Err(progress.error(&format!("Boot did not reach {} within {}s", missing.join(", "), args.timeout)));
+        }
+        progress.done(&format!("All {} boot phases reached in {:.2}s", expected.len(), report.elapsed.as_secs_f64()));
+        Ok(())
+    }
+    
+    fn handle_examples_test(&self, args: &ExamplesTestArgs) -> Result<(), String> {
+        let harness = match args.interp_only {
+            true => crate::differential::Harness::with_native(false),
+            false => crate::differential::Harness::new(),
+        };
+        let programs = crate::differential::Harness::corpus(&args.dir)?;
+        
+        if !self.quiet {
+            println!("{}", style::section("EXAMPLE PROGRAMS"));
+            println!("  {} {}", "Directory:".cyan(), style::path(&args.dir));
+            if !harness.native() {
+                println!("  {}", "Only the interpreter runs; programs are checked against their .out files".yellow());
+            }
+        }
+        
+        let mut divergences = Vec::new();
+        for program in &programs {
+            let name = program.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            match harness.check(program) {
+                Ok(checked) => {
+                    let compiled = if checked.known_divergence { "compiled's known divergence" } else { "compiled" };
+                    let against: Vec<&str> = [(checked.expected_file, "expected"), (checked.compiled, compiled)].into_iter()
+                        .filter_map(|(checked, what)| checked.then_some(what))
+                        .collect();
+                    if self.quiet {
+                        continue;
+                    }
+                    match against.is_empty() {
+                        true => println!("  {} {} {}", "?".yellow(), name, "(nothing to compare with)".dimmed()),
+                        false => println!("  {} {} {}", "✓".green(), name, format!("(matches {})", against.join(" and ")).dimmed()),
+                    }
+                }
+                Err(divergence) => {
+                    if !self.quiet {
+                        println!("  {} {} {}", "✗".red(), name, format!("(diverged at {})", divergence.stage).red());
+                    }
+                    divergences.push(divergence);
+                }
+            }
+        }
+        
+        if !divergences.is_empty() {
+            let details: Vec<String> = divergences.iter().map(ToString::to_string).collect();
+            return Err(format!("{} of {} programs diverged\n\n{}", divergences.len(), programs.len(), details.join("\n\n")));
+        }
+        if !self.quiet {
+            println!("  {} {} programs agree", "✓".green(), programs.len());
+        }
+        Ok(())
+    }
+    
     fn handle_test(&self, args: &TestArgs, verbose: bool) -> Result<(), String> {
+        match &args.command {
+            Some(TestCommands::Boot(boot_args)) => return self.handle_boot_test(boot_args, verbose || args.verbose),
+            Some(TestCommands::Examples(examples_args)) => return self.handle_examples_test(examples_args),
+            None => {}
+        }
+        
         let progress = Progress::new(verbose || args.verbose);
         let suite = if args.all { "all" } else { &args.suite };
         
@@ -831,6 +1712,181 @@ print("Factorial of 5 is: " .. tostring(result))
         Ok(())
     }
     
+    fn handle_features(&self) -> Result<(), String> {
+        if !self.quiet {
+            println!("{}", style::section("CPU FEATURES"));
+        }
+        
+        let detected = crate::hardware::detect_capabilities();
+        for feature in &crate::hardware::KNOWN_FEATURES {
+            let name = crate::hardware::feature_name(feature);
+            if detected.contains(feature) {
+                println!("    {} {}", "✓".green(), name.green());
+            } else {
+                println!("    {} {}", "✗".red(), name.dimmed());
+            }
+        }
+        
+        Ok(())
+    }
+    
+    fn handle_modules(&self) -> Result<(), String> {
+        if !self.quiet {
+            println!("{}", style::section("EXTENSION MODULES"));
+        }
+        
+        let compiler = EarthangCompiler::new(CompilerConfig::default().with_hardware_dsl(false));
+        for module in compiler.extension_registry().modules() {
+            println!("\n  {} {} - {}", ">".blue(), module.name().green().bold(), module.description().dimmed());
+            let capabilities: Vec<String> = module.required_capabilities().iter().map(|cap| format!("{:?}", cap)).collect();
+            println!("    Capabilities: {}", if capabilities.is_empty() { "none".to_string() } else { capabilities.join(", ") });
+            let targets: Vec<String> = Target::ALL.iter()
+                .map(|target| {
+                    if module.supports_target(target) {
+                        format!("{} {}", "✓".green(), target.name().green())
+                    } else {
+                        format!("{} {}", "✗".red(), target.name().dimmed())
+                    }
+                })
+                .collect();
+            println!("    Targets: {}", targets.join("  "));
+        }
+        
+        Ok(())
+    }
+    
+    fn handle_repl(&self) -> Result<(), String> {
+        if let Some(missing) = crate::repl::missing_toolchain() {
+            return Err(missing);
+        }
+        if !self.quiet {
+            println!("{}", style::section("REPL"));
+            println!("  {}", "Each entry reruns everything entered before it and shows only the new output.".dimmed());
+            println!("  {}", "End a block with an empty line. :asm shows the assembly, :reset forgets every entry, :quit leaves.".dimmed());
+        }
+        crate::repl::run(&mut crate::repl::Repl::new(), std::io::stdin().lock(), std::io::stdout())
+    }
+    
+    fn handle_bench(&self, args: &BenchArgs) -> Result<(), String> {
+        let mut programs = Vec::new();
+        for file in &args.files {
+            let source = std::fs::read_to_string(file)
+                .map_err(|e| format!("Failed to read source file '{}': {}", file.display(), e))?;
+            programs.push((file.file_stem().unwrap_or_default().to_string_lossy().into_owned(), source));
+        }
+        if programs.is_empty() {
+            programs.push(("generated".to_string(), crate::bench::generated_source(500)));
+        }
+        let baseline = args.baseline.as_deref().map(crate::bench::BenchReport::load).transpose()?;
+        
+        let report = crate::bench::run(&programs, args.iterations);
+        print!("{}", report.render(baseline.as_ref()));
+        if let Some(path) = &args.json {
+            report.save(path)?;
+            if !self.quiet {
+                println!("  {} {}", "Saved".green(), style::path(path));
+            }
+        }
+        Ok(())
+    }
+    
+    fn handle_run(&self, args: &RunArgs) -> Result<(), String> {
+        let source = read_source_or_stdin(&args.file)?;
+        let file_name = source_name(&args.file);
+        let program = crate::parser::parse_program(&source).map_err(|errors| {
+            errors.iter().map(|error| error.render(&file_name, &source)).collect::<Vec<_>>().join("\n")
+        })?;
+        
+        match args.backend {
+            CliBackend::Interp => {
+                let base_dir = args.file.parent().map(|dir| dir.to_path_buf());
+                let program = crate::lua_frontend::IncludeProcessor::new().process_includes(&program, base_dir.as_ref())
+                    .map_err(|e| format!("Include processing error: {}", e))?;
+                crate::interp::run_program(&program)
+                    .map_err(|e| CompileError::from_message(e).render(&file_name, &source))
+            }
+            CliBackend::Native => {
+                let logger = self.logger();
+                let config = CompilerConfig::default().with_target(Target::Linux64).with_hardware_dsl(false).with_logger(logger.clone());
+                let result = EarthangCompiler::new(config).compile_program(program, Some(&args.file))
+                    .map_err(|e| CompileError::from_message(e).render(&file_name, &source))?;
+                let work_dir = std::env::temp_dir().join(format!("earthang_run_{}", std::process::id()));
+                std::fs::create_dir_all(&work_dir)
+                    .map_err(|e| format!("Failed to create '{}': {}", work_dir.display(), e))?;
+                let executable = crate::compiler::assemble_and_link_with_logger(&result.assembly, &work_dir.join("program"), Target::Linux64, false, &logger);
+                let status = executable.and_then(|executable| std::process::Command::new(&executable).status()
+                    .map_err(|e| format!("Failed to run {}: {}", executable.display(), e)));
+                let _ = std::fs::remove_dir_all(&work_dir);
+                match status? {
+                    status if status.success() => Ok(()),
+                    status => Err(format!("The program exited with {}", status)),
+                }
+            }
+        }
+    }
+    
+    fn handle_fmt(&self, args: &FmtArgs) -> Result<(), String> {
+        let mut unformatted = Vec::new();
+        for file in &args.files {
+            let source = std::fs::read_to_string(file)
+                .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+            let file_name = file.display().to_string();
+            let formatted = crate::lua_frontend::format_source(&source).map_err(|errors| {
+                errors.iter().map(|error| error.render(&file_name, &source)).collect::<Vec<_>>().join("\n")
+            })?;
+            if formatted == source {
+                continue;
+            }
+            if args.check {
+                if !self.quiet {
+                    println!("{}", style::warning(&format!("{} is not formatted", style::path(file))));
+                }
+                unformatted.push(file_name);
+            } else {
+                std::fs::write(file, formatted)
+                    .map_err(|e| format!("Failed to write {}: {}", file.display(), e))?;
+                if !self.quiet {
+                    println!("{}", style::success(&format!("Formatted {}", style::path(file))));
+                }
+            }
+        }
+        
+        if unformatted.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("{} file(s) need formatting: {}", unformatted.len(), unformatted.join(", ")))
+        }
+    }
+    
+    fn handle_inspect(&self, args: &InspectArgs) -> Result<(), String> {
+        let map_path = args.map.clone().unwrap_or_else(|| args.image.with_extension("map"));
+        let map = crate::symbol_map::SymbolMap::load(&map_path)?;
+        let bytes = std::fs::read(&args.image)
+            .map_err(|e| format!("Failed to read {}: {}", args.image.display(), e))?;
+        if let Some(symbol) = map.symbols.iter().find(|symbol| symbol.offset + symbol.size > bytes.len() as u64) {
+            return Err(format!(
+                "{} does not match {}: '{}' ends at offset 0x{:X} but the image has {} bytes",
+                map_path.display(), args.image.display(), symbol.name, symbol.offset + symbol.size, bytes.len()
+            ));
+        }
+        
+        if args.addrs.is_empty() {
+            print!("{}", map.to_tsv());
+            return Ok(());
+        }
+        for address in &args.addrs {
+            let (symbol, distance) = map.resolve(*address)
+                .ok_or_else(|| format!("0x{:X} is outside every region of {}", address, map_path.display()))?;
+            let offset = symbol.offset + distance;
+            let code: Vec<String> = bytes.iter().skip(offset as usize).take(8).map(|byte| format!("{:02X}", byte)).collect();
+            println!(
+                "0x{:X}  {}+0x{:X}  {} in {}, image offset 0x{:X}: {}",
+                address, symbol.name.green().bold(), distance, symbol.kind, symbol.phase.cyan(), offset, code.join(" ")
+            );
+        }
+        Ok(())
+    }
+    
     fn handle_targets(&self, verbose: bool) -> Result<(), String> {
         let progress = Progress::new(verbose);
         
@@ -840,11 +1896,15 @@ print("Factorial of 5 is: " .. tostring(result))
         }
         
         println!("\n  {} OS Targets:", style::info(""));
-        println!("    {} {} - {}", 
-            ">".blue(), 
-            "linux64".green().bold(),
-            "64-bit Linux ELF executable with hardware DSL support".dimmed()
-        );
+        for target in Target::ALL {
+            let prefix = crate::compiler::toolchain_prefix(target);
+            let description = if prefix.is_empty() {
+                target.description().to_string()
+            } else {
+                format!("{} ({} binutils)", target.description(), prefix.trim_end_matches('-'))
+            };
+            println!("    {} {} - {}", ">".blue(), target.name().green().bold(), description.dimmed());
+        }
         
         println!("\n  {} Hardware Support:", style::info(""));
         println!("    {} {} - {}", "•".blue(), "GPU".green(), "VGA/Graphics card access".dimmed());
@@ -868,8 +1928,111 @@ print("Factorial of 5 is: " .. tostring(result))
     }
 }
 
-/// Parse CLI arguments and run
-pub fn run() -> Result<(), String> {
-    let cli = Cli::parse();
-    cli.run()
-}
\ No newline at end of file
+/// Exit status of a command that succeeded, including --help and --version
+pub const EXIT_SUCCESS: i32 = 0;
+/// Exit status of a command that failed, such as a compile error
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit status of a command line that could not be parsed
+pub const EXIT_USAGE: i32 = 2;
+
+/// Parse a command line, program name first. Unknown options, missing
+/// values, a missing command and conflicting flags are errors whose
+/// `exit_code` is EXIT_USAGE; --help and --version come back as errors
+/// that print their text and exit with EXIT_SUCCESS
+pub fn parse_args<I, T>(args: I) -> Result<Cli, clap::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString> + Clone,
+{
+    Cli::try_parse_from(args)
+}
+
+/// Parse the process's arguments and run, returning its exit status
+pub fn run() -> i32 {
+    let cli = match parse_args(std::env::args_os()) {
+        Ok(cli) => cli,
+        Err(e) => {
+            let _ = e.print();
+            return e.exit_code();
+        }
+    };
+    match cli.run() {
+        Ok(()) => EXIT_SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            EXIT_FAILURE
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Cli, clap::Error> {
+        parse_args(std::iter::once("earthang").chain(line.split_whitespace()))
+    }
+
+    #[test]
+    fn test_command_lines_parse_or_fail_as_usage_errors() {
+        let compiles: [(&str, &[&str], Option<&str>); 5] = [
+            ("compile a.eg -o out.s", &["a.eg"], Some("out.s")),
+            ("compile -o out.s a.eg b.eg", &["a.eg", "b.eg"], Some("out.s")),
+            ("-v compile a.eg --emit exe -o out", &["a.eg"], Some("out")),
+            // Everything after -- is a file, even when it looks like a flag
+            ("compile -o out -- -weird.eg", &["-weird.eg"], Some("out")),
+            ("compile a.eg", &["a.eg"], None),
+        ];
+        for (line, files, output) in compiles {
+            let Commands::Compile(args) = parse(line).unwrap_or_else(|e| panic!("{}: {}", line, e)).command else { panic!("{}", line) };
+            assert_eq!(args.files, files.iter().map(PathBuf::from).collect::<Vec<_>>(), "{}", line);
+            assert_eq!(args.output, output.map(PathBuf::from), "{}", line);
+        }
+        assert_eq!(parse("-vv --log-json targets").map(|cli| (cli.verbose, cli.log_json)).unwrap(), (2, true));
+
+        let usage_errors = [
+            ("compile a.eg --keep-asembly", Some("--keep-assembly")),
+            ("compil a.eg", Some("compile")),
+            ("compile", None),
+            ("compile a.eg -o", None),
+            ("compile a.eg --target z80", None),
+            ("compile a.eg --map", None),
+            ("compile a.eg --bios-mode --dump-asm-annotated", None),
+            ("test --suite everything", None),
+            ("-q", None),
+            ("", None),
+        ];
+        for (line, suggestion) in usage_errors {
+            let Err(error) = parse(line) else { panic!("{} parsed", line) };
+            assert_eq!(error.exit_code(), EXIT_USAGE, "{}: {}", line, error);
+            if let Some(suggestion) = suggestion {
+                assert!(error.to_string().contains(&format!("'{}'", suggestion)), "{}", error);
+            }
+        }
+        for line in ["--help", "compile --help", "--version"] {
+            assert_eq!(parse(line).err().map(|e| e.exit_code()), Some(EXIT_SUCCESS), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_piped_programs_compile_without_a_prompt() {
+        let Commands::Compile(args) = parse("compile - -o piped.s").unwrap().command else { panic!() };
+        let input = &args.files[0];
+        assert!(is_stdin(input) && source_name(input) == "<stdin>");
+
+        let mut prompt = Vec::new();
+        let source = read_source(input, &mut "print(1)\n".as_bytes(), false, &mut prompt).unwrap();
+        assert_eq!((source.as_str(), prompt.len()), ("print(1)\n", 0));
+        read_source(input, &mut "".as_bytes(), true, &mut prompt).unwrap();
+        assert!(String::from_utf8(prompt).unwrap().contains("Ctrl+D"));
+
+        // -o is used as given; without one the output is named after stdin
+        let output = |line: &str| match parse(line).unwrap().command {
+            Commands::Compile(args) => output_path(&args),
+            _ => unreachable!(),
+        };
+        assert_eq!(output("compile - -o piped.s"), PathBuf::from("piped.s"));
+        assert_eq!(output("compile -o piped.s -"), PathBuf::from("piped.s"));
+        assert_eq!(output("compile -"), PathBuf::from("stdin.elf"));
+        assert_eq!(output("compile dir/prog.eg --bios-mode"), PathBuf::from("dir/prog.img"));
+    }
+}
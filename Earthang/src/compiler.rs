@@ -13,11 +13,13 @@
 */
 use std::collections::HashMap;
 use std::path::PathBuf;
-use crate::parser::{Program, Statement, Expr};
+use crate::parser::{Program, Statement, Expr, Position, Span, Op, CompareOp, BoolOp, UnaryOp, FStringPart};
+use crate::analysis::Diagnostic;
+use crate::size::SizeReport;
 use crate::backend::{Backend, BackendRegistry, BackendModule, Target, Capability};
 use crate::emitter::NasmEmitter;
 use crate::dsl::{HardwareDSL, DeviceType};
-use crate::extension::{ExtensionRegistry, EarthngModule, BasicAssemblyEmitter, MathModule, StringModule, SystemModule};
+use crate::extension::{ExtensionRegistry, EarthngModule, BasicAssemblyEmitter};
 
 #[derive(Debug, Clone)]
 pub struct CompilerConfig {
@@ -27,10 +29,17 @@ pub struct CompilerConfig {
     pub include_stdlib: bool,
     pub hardware_dsl_enabled: bool,
     pub code_size_limit: Option<usize>,
+    pub refcounting: bool,
+    pub strict_types: bool,
+    pub jobs: Option<usize>,
     pub verbose: bool,
     pub keep_assembly: bool,
     pub modules: Vec<String>,
     pub search_paths: Vec<PathBuf>,
+    pub host_capabilities: Option<Vec<Capability>>,
+    pub logger: crate::logging::Logger,
+    /// Where a `--bios-mode` image loads its code and keeps its stack and heap
+    pub memory: crate::framebuffer::MemoryLayout,
 }
 
 impl Default for CompilerConfig {
@@ -42,10 +51,16 @@ impl Default for CompilerConfig {
             include_stdlib: true,
             hardware_dsl_enabled: true,
             code_size_limit: None,
+            refcounting: true,
+            strict_types: false,
+            jobs: None,
             verbose: false,
             keep_assembly: false,
             modules: Vec::new(),
             search_paths: vec![PathBuf::from("."), PathBuf::from("stdlib")],
+            host_capabilities: None,
+            logger: crate::logging::Logger::default(),
+            memory: crate::framebuffer::MemoryLayout::default(),
         }
     }
 }
@@ -70,6 +85,59 @@ impl CompilerConfig {
         self.search_paths.push(path.into());
         self
     }
+
+    /// Fail compilation when the estimated output exceeds `limit` bytes
+    pub fn with_code_size_limit(mut self, limit: usize) -> Self {
+        self.code_size_limit = Some(limit);
+        self
+    }
+    
+    /// Free heap objects once nothing refers to them (the default) or leak them
+    pub fn with_refcounting(mut self, enabled: bool) -> Self {
+        self.refcounting = enabled;
+        self
+    }
+    
+    /// Require every function to annotate its parameters and return type
+    pub fn with_strict_types(mut self, strict: bool) -> Self {
+        self.strict_types = strict;
+        self
+    }
+    
+    /// Compile functions on `jobs` threads instead of one per CPU
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = Some(jobs);
+        self
+    }
+    
+    pub fn with_keep_assembly(mut self, keep: bool) -> Self {
+        self.keep_assembly = keep;
+        self
+    }
+    
+    /// Emit DWARF line tables mapping code to source lines (linux64 only)
+    pub fn with_debug_info(mut self, debug: bool) -> Self {
+        self.debug_info = debug;
+        self
+    }
+    
+    /// Only select backends whose CPU extensions are in `capabilities`
+    pub fn with_host_capabilities(mut self, capabilities: Vec<Capability>) -> Self {
+        self.host_capabilities = Some(capabilities);
+        self
+    }
+    
+    /// Report each phase of compilation, and how long it took, to `logger`
+    pub fn with_logger(mut self, logger: crate::logging::Logger) -> Self {
+        self.logger = logger;
+        self
+    }
+    
+    /// Load a `--bios-mode` image, and place its stack and heap, by `memory`
+    pub fn with_memory_layout(mut self, memory: crate::framebuffer::MemoryLayout) -> Self {
+        self.memory = memory;
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -77,9 +145,60 @@ pub struct CompilationResult {
     pub assembly: String,
     pub warnings: Vec<String>,
     pub errors: Vec<String>,
+    /// Findings of the analysis pass over the source as written
+    pub diagnostics: Vec<Diagnostic>,
     pub stats: CompilationStats,
 }
 
+/// An error from a compilation stage after parsing, with the source position
+/// it refers to when one is known
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError {
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+impl CompileError {
+    pub fn new(message: impl Into<String>, span: Option<Span>) -> Self {
+        Self { message: message.into(), span }
+    }
+    
+    /// Backends report errors as strings ending in "at <line>:<col>"; recover
+    /// the position from that suffix so the error can point into the source
+    pub fn from_message(message: impl Into<String>) -> Self {
+        let message = message.into();
+        let located = message.rsplit_once(" at ").and_then(|(text, location)| {
+            let (line, rest) = location.split_once(':')?;
+            let column: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+            let valid_rest = rest[column.len()..].chars().all(|c| c.is_ascii_digit() || c == ':' || c == '-');
+            match (line.parse::<usize>(), column.parse::<usize>()) {
+                (Ok(line), Ok(column)) if valid_rest && line > 0 => {
+                    Some((text.to_string(), Span::single(Position::new(line, column, 0))))
+                }
+                _ => None,
+            }
+        });
+        
+        match located {
+            Some((text, span)) => Self::new(text, Some(span)),
+            None => Self::new(message, None),
+        }
+    }
+    
+    pub fn render(&self, file: &str, source: &str) -> String {
+        crate::lua_frontend::render_diagnostic("error", file, source, &self.message, self.span, &[])
+    }
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "{} at {}", self.message, span),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CompilationStats {
     pub lines_of_code: usize,
@@ -134,11 +253,10 @@ impl EarthangCompiler {
             symbol_table: HashMap::new(),
             current_function: None,
             optimization_passes: Vec::new(),
-            extension_registry: ExtensionRegistry::new(),
+            extension_registry: ExtensionRegistry::with_builtin_modules(),
         };
         
         compiler.register_optimization_passes();
-        compiler.register_builtin_extensions();
         
         compiler
     }
@@ -149,12 +267,6 @@ impl EarthangCompiler {
         self.optimization_passes.push(Box::new(InlineExpansionPass));
     }
     
-    fn register_builtin_extensions(&mut self) {
-        self.extension_registry.register_module(Box::new(MathModule::new()));
-        self.extension_registry.register_module(Box::new(StringModule::new()));
-        self.extension_registry.register_module(Box::new(SystemModule::new()));
-    }
-    
     fn statement_has_extension_call(&self, stmt: &Statement) -> bool {
         match stmt {
             Statement::Expr(expr) => self.expression_has_extension_call(expr),
@@ -181,7 +293,11 @@ impl EarthangCompiler {
                 self.expression_has_extension_call(condition) ||
                 body.iter().any(|s| self.statement_has_extension_call(s))
             }
-            Statement::Return(expr) => {
+            Statement::For { iter, body, .. } => {
+                self.expression_has_extension_call(iter) ||
+                body.iter().any(|s| self.statement_has_extension_call(s))
+            }
+            Statement::Return(expr, _) => {
                 expr.as_ref().map_or(false, |e| self.expression_has_extension_call(e))
             }
             _ => false,
@@ -254,7 +370,54 @@ impl EarthangCompiler {
     
     pub fn compile_source(&mut self, source: &str, source_path: Option<&std::path::Path>) -> Result<CompilationResult, String> {
         let start_time = std::time::Instant::now();
+        let phase = self.config.logger.phase("parse");
+        let program = match crate::lua_frontend::parse_program(source) {
+            Ok(program) => program,
+            Err(parse_errors) => {
+                phase.end(format!("{} errors", parse_errors.len()));
+                let error_messages: Vec<String> = parse_errors
+                    .iter()
+                    .map(|e| e.format_error(source))
+                    .collect();
+                return Err(format!("Parse errors:\n{}", error_messages.join("\n")));
+            }
+        };
+        phase.end(format!("{} lines, {} top-level statements", source.lines().count(), program.body.len()));
         
+        self.compile_parsed(program, source, source_path, start_time)
+    }
+    
+    /// Compile a program built without source text, such as with
+    /// `dsl::ProgramBuilder`. Diagnostics and size reports quote the program
+    /// as printed back to source; `source_path` only locates includes and imports
+    pub fn compile_program(&mut self, program: Program, source_path: Option<&std::path::Path>) -> Result<CompilationResult, String> {
+        let start_time = std::time::Instant::now();
+        let source = program.to_string();
+        self.compile_parsed(program, &source, source_path, start_time)
+    }
+    
+    /// Compile parsed source files as one program joined by `linker::link_with_registry`:
+    /// the first file's top-level code runs and the others provide functions.
+    /// Includes and imports resolve against the directory of the file naming them
+    pub fn compile_linked(&mut self, files: Vec<(PathBuf, Program)>) -> Result<CompilationResult, String> {
+        let mut include_processor = crate::lua_frontend::IncludeProcessor::new();
+        for path in &self.config.search_paths {
+            include_processor.add_search_path(path);
+        }
+        
+        let mut processed = Vec::with_capacity(files.len());
+        for (path, program) in files {
+            let base_dir = path.parent().map(|dir| dir.to_path_buf());
+            self.load_imported_libraries(&program, base_dir.as_deref())?;
+            let program = include_processor.process_includes(&program, base_dir.as_ref())
+                .map_err(|e| format!("Include processing error in {}: {}", path.display(), e))?;
+            processed.push((path, program));
+        }
+        let linked = crate::linker::link_with_registry(processed, &self.extension_registry).map_err(|e| e.to_string())?;
+        self.compile_program(linked.program, Some(&linked.entry))
+    }
+    
+    fn compile_parsed(&mut self, mut program: Program, source: &str, source_path: Option<&std::path::Path>, start_time: std::time::Instant) -> Result<CompilationResult, String> {
         self.warnings.clear();
         self.errors.clear();
         self.symbol_table.clear();
@@ -264,35 +427,73 @@ impl EarthangCompiler {
             include_processor.add_search_path(path);
         }
         
-        let mut program = match crate::lua_frontend::parse_program(source) {
-            Ok(program) => program,
-            Err(parse_errors) => {
-                let error_messages: Vec<String> = parse_errors
-                    .iter()
-                    .map(|e| e.format_error(source))
-                    .collect();
-                return Err(format!("Parse errors:\n{}", error_messages.join("\n")));
-            }
-        };
+        // Analyse before includes and optimization so spans and pragmas match the user's file
+        let logger = self.config.logger.clone();
+        let phase = logger.phase("analysis");
+        let diagnostics = crate::analysis::analyze(&program, source);
         
         let base_dir = source_path.and_then(|p| p.parent().map(|p| p.to_path_buf()));
         program = include_processor.process_includes(&program, base_dir.as_ref())
             .map_err(|e| format!("Include processing error: {}", e))?;
+        // Unoptimized builds need their constants resolved too
+        resolve_constants(&mut program)?;
+        crate::typecheck::check_types(&mut program, self.config.strict_types)?;
         
         if self.config.optimize {
             for pass in &self.optimization_passes {
+                let pass_phase = logger.detail_phase("analysis");
                 if let Err(err) = pass.optimize(&mut program) {
                     self.warnings.push(format!("Optimization pass '{}' failed: {}", pass.name(), err));
                 }
+                pass_phase.end(format!("pass {}", pass.name()));
             }
         }
+        phase.end(format!("{} diagnostics", diagnostics.len()));
+        
+        let phase = logger.phase("modules");
+        self.load_imported_libraries(&program, base_dir.as_deref())?;
+        let required_modules = self.extension_registry.extract_required_modules(&program)?;
+        self.extension_registry.check_signatures(&program)?;
+        self.extension_registry.check_implemented(&program, &self.config.target)?;
+        self.extension_registry.check_targets(&required_modules, &self.config.target)?;
+        phase.end(if required_modules.is_empty() { "none required".to_string() } else { format!("required {}", required_modules.join(", ")) });
         
         // Create backend with hardware DSL if enabled
-        let _backend_module = self.create_backend_module(&program);
+        let phase = logger.phase("backend");
+        let mut backend_module = self.create_backend_module(&program);
+        backend_module.required_capabilities.extend(self.extension_registry.required_capabilities(&required_modules));
+        let host = self.config.host_capabilities.as_deref();
+        let backend_target = self.backend_registry
+            .find_best_backend(&backend_module, host)
+            .map(|backend| backend.target())
+            .ok_or_else(|| format!(
+                "No backend for {} supports the required capabilities {:?}",
+                self.config.target, backend_module.required_capabilities
+            ))?;
+        for backend in &self.backend_registry.backends {
+            if let Some(reason) = self.backend_registry.rejection(backend.as_ref(), &backend_module, host) {
+                logger.info("backend", format!("{} rejected: {}", backend.target(), reason));
+            }
+        }
+        phase.end(format!("selected {} for {:?}", backend_target, backend_module.required_capabilities));
         
-        let assembly_result = match self.config.target {
+        let phase = logger.phase("codegen");
+        let assembly_result = match backend_target {
             Target::Linux64 => {
-                let mut backend = crate::backend::Linux64Backend::new();
+                let jobs = self.config.jobs
+                    .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+                let mut backend = crate::backend::Linux64Backend::new()
+                    .with_refcounting(self.config.refcounting)
+                    .with_stack_allocation(self.config.optimize)
+                    .with_register_allocation(self.config.optimize)
+                    .with_frame_elision(self.config.optimize)
+                    .with_logger(logger.clone())
+                    .with_jobs(jobs)
+                    .with_module_symbols(self.extension_registry.defined_symbols());
+                if self.config.debug_info {
+                    let file = source_path.map_or_else(|| "<source>".to_string(), |path| path.display().to_string());
+                    backend = backend.with_debug_info(&file);
+                }
                 
                 // Pass hardware DSL to backend if enabled
                 if self.config.hardware_dsl_enabled {
@@ -303,9 +504,34 @@ impl EarthangCompiler {
                 
                 backend.compile_program(&program)
             }
+            Target::RiscV64 => crate::backend::RiscV64Backend::new().compile_program(&program),
+            Target::Aarch64 => crate::backend::Aarch64LinuxBackend::new().with_logger(logger.clone()).compile_program(&program),
         };
         
-        let assembly = assembly_result?;
+        let mut assembly = assembly_result?;
+        let required_modules = self.extension_registry.add_referenced_modules(&required_modules, &assembly)?;
+        self.extension_registry.check_targets(&required_modules, &self.config.target)?;
+        
+        let library = self.extension_registry.library_code(&required_modules, &self.config.target);
+        if !library.is_empty() {
+            assembly.push_str("\n.intel_syntax noprefix\n");
+            assembly.push_str(&library);
+            assembly.push_str(".att_syntax\n");
+        }
+        phase.end(format!("{} lines of assembly", assembly.lines().count()));
+        
+        if self.config.code_size_limit.is_some() || logger.enabled(crate::logging::Level::Phases) {
+            let phase = logger.phase("size");
+            let report = SizeReport::measure(&assembly, &self.config.target);
+            phase.end(format!("estimated {} bytes, {} of them runtime", report.total, report.runtime));
+            if let Some(limit) = self.config.code_size_limit.filter(|limit| report.total > *limit) {
+                return Err(format!(
+                    "Program needs an estimated {} bytes but the limit is {}; the biggest contributors are:\n{}",
+                    report.total, limit, report.render(source, 5)
+                ));
+            }
+        }
+        self.config.modules = required_modules;
         
         let compilation_time = start_time.elapsed().as_millis();
         
@@ -326,10 +552,34 @@ impl EarthangCompiler {
             assembly,
             warnings: self.warnings.clone(),
             errors: self.errors.clone(),
+            diagnostics,
             stats,
         })
     }
     
+    /// Register module library files for imports no built-in module answers,
+    /// looking next to the source first and then in the search paths
+    fn load_imported_libraries(&mut self, program: &Program, base_dir: Option<&std::path::Path>) -> Result<(), String> {
+        for stmt in &program.body {
+            let Statement::Import { module, span, .. } = stmt else { continue };
+            if self.extension_registry.find_module(module).is_some() {
+                continue;
+            }
+            let file_name = format!("{}.{}", module, crate::module_library::LIBRARY_EXTENSION);
+            let found = base_dir.into_iter()
+                .chain(self.config.search_paths.iter().map(|path| path.as_path()))
+                .map(|dir| dir.join(&file_name))
+                .find(|path| path.is_file());
+            if let Some(path) = found {
+                let name = self.extension_registry.register_from_library(&path)?;
+                if name != *module {
+                    return Err(format!("{} defines the {} module, not {} imported at {}", path.display(), name, module, span));
+                }
+            }
+        }
+        Ok(())
+    }
+    
     fn compile_with_emitter(&mut self, program: &Program) -> Result<String, String> {
         let mut emitter = NasmEmitter::new();
         
@@ -348,7 +598,7 @@ impl EarthangCompiler {
     ) -> Result<String, String> {
         match self.config.target {
             Target::Linux64 => {
-                let mut backend = crate::backend::Linux64Backend::new();
+                let mut backend = crate::backend::Linux64Backend::new().with_refcounting(self.config.refcounting);
                 
                 // Pass hardware DSL to backend if enabled
                 if self.config.hardware_dsl_enabled {
@@ -359,10 +609,12 @@ impl EarthangCompiler {
                 
                 backend.compile_program(program)
             }
+            Target::RiscV64 => crate::backend::RiscV64Backend::new().compile_program(program),
+            Target::Aarch64 => crate::backend::Aarch64LinuxBackend::new().compile_program(program),
         }
     }
     
-    fn create_backend_module(&self, _program: &Program) -> BackendModule {
+    fn create_backend_module(&self, program: &Program) -> BackendModule {
         let mut required_capabilities = Vec::new();
         
         match self.config.target {
@@ -376,6 +628,21 @@ impl EarthangCompiler {
                     required_capabilities.push(Capability::Graphics);
                 }
             }
+            Target::RiscV64 => {
+                // The hardware DSL emits x86 port I/O, so it is not offered here
+                required_capabilities.push(Capability::Linux);
+                required_capabilities.push(Capability::RiscV64);
+                required_capabilities.push(Capability::VirtualMemory);
+            }
+            Target::Aarch64 => {
+                required_capabilities.push(Capability::Linux);
+                required_capabilities.push(Capability::AArch64);
+                required_capabilities.push(Capability::VirtualMemory);
+            }
+        }
+        
+        if program.body.iter().any(statement_uses_floats) {
+            required_capabilities.push(Capability::Float);
         }
         
         BackendModule {
@@ -556,14 +823,16 @@ impl EarthangCompiler {
     }
 }
 
-struct ConstantFoldingPass;
+pub struct ConstantFoldingPass;
 
 impl OptimizationPass for ConstantFoldingPass {
     fn name(&self) -> &str {
         "constant_folding"
     }
     
-    fn optimize(&self, _program: &mut Program) -> Result<(), String> {
+    fn optimize(&self, program: &mut Program) -> Result<(), String> {
+        resolve_constants(program)?;
+        for_each_expression(&mut program.body, &mut fold_expression);
         Ok(())
     }
 }
@@ -575,7 +844,8 @@ impl OptimizationPass for DeadCodeEliminationPass {
         "dead_code_elimination"
     }
     
-    fn optimize(&self, _program: &mut Program) -> Result<(), String> {
+    fn optimize(&self, program: &mut Program) -> Result<(), String> {
+        eliminate_dead_code(&mut program.body);
         Ok(())
     }
 }
@@ -592,6 +862,407 @@ impl OptimizationPass for InlineExpansionPass {
     }
 }
 
+/// Apply `f` to every top-level expression of every statement in `body`, nested blocks included
+fn for_each_expression(body: &mut [Statement], f: &mut impl FnMut(&mut Expr)) {
+    for stmt in body {
+        match stmt {
+            Statement::VarDecl { value, .. }
+            | Statement::Assign { value, .. }
+            | Statement::AugAssign { value, .. }
+            | Statement::Expr(value)
+            | Statement::Return(Some(value), _) => f(value),
+            Statement::IndexAssign { target, index, value, .. } => {
+                f(target);
+                f(index);
+                f(value);
+            }
+            Statement::FieldAssign { target, value, .. } => {
+                f(target);
+                f(value);
+            }
+            Statement::If { condition, then_block, elif_blocks, else_block, .. } => {
+                f(condition);
+                for_each_expression(then_block, f);
+                for (cond, block) in elif_blocks {
+                    f(cond);
+                    for_each_expression(block, f);
+                }
+                if let Some(block) = else_block {
+                    for_each_expression(block, f);
+                }
+            }
+            Statement::While { condition, body, orelse, .. } => {
+                f(condition);
+                for_each_expression(body, f);
+                if let Some(block) = orelse {
+                    for_each_expression(block, f);
+                }
+            }
+            Statement::For { iter, body, .. } => {
+                f(iter);
+                for_each_expression(body, f);
+            }
+            Statement::FunctionDef { body, .. } | Statement::HardwareFunctionDef { body, .. } => for_each_expression(body, f),
+            _ => {}
+        }
+    }
+}
+
+/// Apply `f` to each operand of `expr`, not to `expr` itself
+fn for_each_operand(expr: &mut Expr, f: &mut impl FnMut(&mut Expr)) {
+    match expr {
+        Expr::BinOp { left, right, .. } => {
+            f(left);
+            f(right);
+        }
+        Expr::UnaryOp { operand, .. } => f(operand),
+        Expr::BoolOp { values, .. } | Expr::List { elements: values, .. } => values.iter_mut().for_each(f),
+        Expr::Compare { left, comparators, .. } => {
+            f(left);
+            comparators.iter_mut().for_each(f);
+        }
+        Expr::Call { args, kwargs, .. } => {
+            args.iter_mut().for_each(&mut *f);
+            kwargs.values_mut().for_each(f);
+        }
+        Expr::HardwareCall { args, .. } => args.iter_mut().for_each(f),
+        Expr::FString { parts, .. } => {
+            for part in parts {
+                if let FStringPart::Expr(e) = part {
+                    f(e);
+                }
+            }
+        }
+        Expr::Dict { entries, .. } => {
+            for (key, value) in entries {
+                f(key);
+                f(value);
+            }
+        }
+        Expr::Index { value, index, .. } => {
+            f(value);
+            f(index);
+        }
+        Expr::Slice { value, start, stop, .. } => {
+            f(value);
+            [start, stop].into_iter().flatten().for_each(|bound| f(bound));
+        }
+        Expr::FieldAccess { object, .. } => f(object),
+        Expr::MethodCall { receiver, args, .. } => {
+            f(receiver);
+            args.iter_mut().for_each(f);
+        }
+        _ => {}
+    }
+}
+
+/// Replace every read of a `const` with its value and drop the declarations.
+/// Constants are declared once at the top level and fold to a number, float,
+/// string or boolean; they may use constants declared above them. String
+/// values end up in the string pool like any other literal, so every use
+/// refers to the same labelled data. Nothing may rebind a constant's name,
+/// which is what lets reads skip the variable slots altogether
+pub fn resolve_constants(program: &mut Program) -> Result<(), String> {
+    let mut constants: HashMap<String, (Expr, Span)> = HashMap::new();
+    for stmt in &program.body {
+        let Statement::ConstDecl { name, value, span } = stmt else { continue };
+        if let Some((_, first)) = constants.get(name) {
+            return Err(format!("Constant '{}' is already defined at {}; redefined at {}", name, first, span));
+        }
+        let mut value = value.clone();
+        substitute_constants(&mut value, &constants);
+        fold_expression(&mut value);
+        if !matches!(value, Expr::Number(..) | Expr::Float(..) | Expr::String(..) | Expr::Boolean(..)) {
+            return Err(format!("Constant '{}' must be a number, string or boolean known at compile time at {}", name, span));
+        }
+        constants.insert(name.clone(), (value, *span));
+    }
+    
+    program.body.retain(|stmt| !matches!(stmt, Statement::ConstDecl { .. }));
+    check_constant_bindings(&program.body, &constants)?;
+    if !constants.is_empty() {
+        for_each_expression(&mut program.body, &mut |expr| substitute_constants(expr, &constants));
+    }
+    Ok(())
+}
+
+fn substitute_constants(expr: &mut Expr, constants: &HashMap<String, (Expr, Span)>) {
+    if let Expr::Var(name, span) = expr {
+        if let Some((value, _)) = constants.get(name) {
+            // The literal takes the place, and so the span, of the read
+            let span = *span;
+            *expr = value.clone();
+            crate::lua_frontend::visit_expression_spans(expr, &mut |s| *s = span);
+        }
+        return;
+    }
+    for_each_operand(expr, &mut |operand| substitute_constants(operand, constants));
+}
+
+/// Reject a `const` below the top level and anything binding a constant's name
+fn check_constant_bindings(body: &[Statement], constants: &HashMap<String, (Expr, Span)>) -> Result<(), String> {
+    let check = |name: &str, span: Span| match constants.get(name) {
+        Some((_, defined)) => Err(format!("Constant '{}' defined at {} cannot be reassigned at {}", name, defined, span)),
+        None => Ok(()),
+    };
+    for stmt in body {
+        match stmt {
+            Statement::ConstDecl { span, .. } => return Err(format!("Constants must be declared at the top level at {}", span)),
+            Statement::VarDecl { name, span, .. }
+            | Statement::Assign { target: name, span, .. }
+            | Statement::AugAssign { target: name, span, .. } => check(name, *span)?,
+            Statement::If { then_block, elif_blocks, else_block, .. } => {
+                check_constant_bindings(then_block, constants)?;
+                for (_, block) in elif_blocks {
+                    check_constant_bindings(block, constants)?;
+                }
+                if let Some(block) = else_block {
+                    check_constant_bindings(block, constants)?;
+                }
+            }
+            Statement::While { body, orelse, .. } => {
+                check_constant_bindings(body, constants)?;
+                if let Some(block) = orelse {
+                    check_constant_bindings(block, constants)?;
+                }
+            }
+            Statement::For { var, body, span, .. } => {
+                check(var, *span)?;
+                check_constant_bindings(body, constants)?;
+            }
+            Statement::FunctionDef { name, args, body, span, .. } | Statement::HardwareFunctionDef { name, args, body, span, .. } => {
+                check(name, *span)?;
+                for arg in args {
+                    check(arg, *span)?;
+                }
+                check_constant_bindings(body, constants)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Whether a literal is true under the backends' truthiness rules, `None` if it is not a literal
+fn literal_truthiness(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::Number(n, _) => Some(*n != 0),
+        Expr::Boolean(b, _) => Some(*b),
+        Expr::String(s, _) => Some(!s.is_empty()),
+        _ => None,
+    }
+}
+
+/// Fold integer arithmetic, comparisons and boolean operators on literals, bottom up.
+/// Results follow the backends: comparisons and `and`/`or` give 0 or 1, arithmetic wraps,
+/// and operators a backend would reject or compute differently are left alone
+fn fold_expression(expr: &mut Expr) {
+    for_each_operand(expr, &mut fold_expression);
+    
+    if let Some(value) = folded_value(expr) {
+        *expr = Expr::Number(value, expr.span());
+    }
+}
+
+/// The value of an operator whose operands are already folded, if it is known at compile time
+fn folded_value(expr: &Expr) -> Option<i64> {
+    let int = |e: &Expr| match e {
+        Expr::Number(n, _) => Some(*n),
+        _ => None,
+    };
+    
+    match expr {
+        Expr::BinOp { left, op, right, .. } => {
+            let (a, b) = (int(left)?, int(right)?);
+            match op {
+                Op::Add => Some(a.wrapping_add(b)),
+                Op::Sub => Some(a.wrapping_sub(b)),
+                Op::Mul => Some(a.wrapping_mul(b)),
+                // Division is only the same for every rounding rule with non-negative operands
+                Op::Div if a >= 0 && b > 0 => Some(a / b),
+                Op::Mod if a >= 0 && b > 0 => Some(a % b),
+                Op::BitAnd => Some(a & b),
+                Op::BitOr => Some(a | b),
+                Op::BitXor => Some(a ^ b),
+                _ => None,
+            }
+        }
+        Expr::UnaryOp { op: UnaryOp::Not, operand, .. } => literal_truthiness(operand).map(|t| !t as i64),
+        Expr::UnaryOp { op, operand, .. } => {
+            let n = int(operand)?;
+            match op {
+                UnaryOp::Plus => Some(n),
+                UnaryOp::Minus => Some(n.wrapping_neg()),
+                UnaryOp::Invert => Some(!n),
+                UnaryOp::Not => None,
+            }
+        }
+        Expr::Compare { left, ops, comparators, .. } => {
+            let mut a = int(left)?;
+            let mut result = true;
+            for (op, right) in ops.iter().zip(comparators) {
+                let b = int(right)?;
+                result &= match op {
+                    CompareOp::Eq => a == b,
+                    CompareOp::Ne => a != b,
+                    CompareOp::Lt => a < b,
+                    CompareOp::Le => a <= b,
+                    CompareOp::Gt => a > b,
+                    CompareOp::Ge => a >= b,
+                    _ => return None,
+                };
+                a = b;
+            }
+            Some(result as i64)
+        }
+        Expr::BoolOp { op, values, .. } => {
+            // `and` is decided by the first false operand, `or` by the first true one;
+            // anything unknown before that point has to run
+            let decisive = matches!(op, BoolOp::Or);
+            for value in values {
+                if literal_truthiness(value)? == decisive {
+                    return Some(decisive as i64);
+                }
+            }
+            Some(!decisive as i64)
+        }
+        _ => None,
+    }
+}
+
+/// Keep only the live branch of `if`/`while` statements with literal conditions and drop
+/// statements that follow a `return`, `break` or `continue` in the same block
+fn eliminate_dead_code(body: &mut Vec<Statement>) {
+    let mut live = Vec::with_capacity(body.len());
+    for mut stmt in body.drain(..) {
+        match &mut stmt {
+            Statement::If { then_block, elif_blocks, else_block, .. } => {
+                eliminate_dead_code(then_block);
+                for (_, block) in elif_blocks.iter_mut() {
+                    eliminate_dead_code(block);
+                }
+                if let Some(block) = else_block {
+                    eliminate_dead_code(block);
+                }
+            }
+            Statement::While { body, orelse, .. } => {
+                eliminate_dead_code(body);
+                if let Some(block) = orelse {
+                    eliminate_dead_code(block);
+                }
+            }
+            Statement::For { body, .. } | Statement::FunctionDef { body, .. } | Statement::HardwareFunctionDef { body, .. } => {
+                eliminate_dead_code(body);
+            }
+            _ => {}
+        }
+        
+        match stmt {
+            Statement::If { condition, then_block, elif_blocks, else_block, span } => {
+                live.extend(prune_if(condition, then_block, elif_blocks, else_block, span));
+            }
+            Statement::While { condition, orelse, .. } if literal_truthiness(&condition) == Some(false) => {
+                live.extend(orelse.unwrap_or_default());
+            }
+            stmt => live.push(stmt),
+        }
+        // A spliced branch can end the enclosing block as well
+        if matches!(live.last(), Some(Statement::Return(..) | Statement::Break | Statement::Continue)) {
+            break;
+        }
+    }
+    *body = live;
+}
+
+/// Drop the arms of an `if` that can never be taken. An arm that is always taken becomes
+/// the else block, and an `if` reduced to its else block is replaced by that block
+fn prune_if(
+    condition: Expr,
+    then_block: Vec<Statement>,
+    elif_blocks: Vec<(Expr, Vec<Statement>)>,
+    else_block: Option<Vec<Statement>>,
+    span: Span,
+) -> Vec<Statement> {
+    let mut arms = Vec::new();
+    let mut else_block = else_block;
+    for (cond, block) in std::iter::once((condition, then_block)).chain(elif_blocks) {
+        match literal_truthiness(&cond) {
+            Some(false) => {}
+            Some(true) => {
+                else_block = Some(block);
+                break;
+            }
+            None => arms.push((cond, block)),
+        }
+    }
+    
+    if arms.is_empty() {
+        return else_block.unwrap_or_default();
+    }
+    let (condition, then_block) = arms.remove(0);
+    vec![Statement::If { condition, then_block, elif_blocks: arms, else_block, span }]
+}
+
+/// Whether a statement contains a float literal or declares a float variable
+fn statement_uses_floats(stmt: &Statement) -> bool {
+    let block_uses_floats = |block: &[Statement]| block.iter().any(statement_uses_floats);
+    match stmt {
+        Statement::Expr(expr) => expression_uses_floats(expr),
+        Statement::VarDecl { value, type_hint, .. } => {
+            type_hint.as_deref() == Some("float") || expression_uses_floats(value)
+        }
+        Statement::Assign { value, .. } | Statement::AugAssign { value, .. } => expression_uses_floats(value),
+        Statement::IndexAssign { target, index, value, .. } => {
+            [target.as_ref(), index.as_ref(), value].into_iter().any(expression_uses_floats)
+        }
+        Statement::FieldAssign { target, value, .. } => expression_uses_floats(target) || expression_uses_floats(value),
+        Statement::Return(Some(expr), _) => expression_uses_floats(expr),
+        Statement::If { condition, then_block, elif_blocks, else_block, .. } => {
+            expression_uses_floats(condition)
+                || block_uses_floats(then_block)
+                || elif_blocks.iter().any(|(cond, block)| expression_uses_floats(cond) || block_uses_floats(block))
+                || else_block.as_deref().is_some_and(block_uses_floats)
+        }
+        Statement::While { condition, body, orelse, .. } => {
+            expression_uses_floats(condition)
+                || block_uses_floats(body)
+                || orelse.as_deref().is_some_and(block_uses_floats)
+        }
+        Statement::For { iter, body, .. } => expression_uses_floats(iter) || block_uses_floats(body),
+        Statement::FunctionDef { body, .. } | Statement::HardwareFunctionDef { body, .. } => block_uses_floats(body),
+        _ => false,
+    }
+}
+
+fn expression_uses_floats(expr: &Expr) -> bool {
+    match expr {
+        Expr::Float(_, _) => true,
+        Expr::BinOp { left, right, .. } => expression_uses_floats(left) || expression_uses_floats(right),
+        Expr::UnaryOp { operand, .. } => expression_uses_floats(operand),
+        Expr::BoolOp { values, .. } => values.iter().any(expression_uses_floats),
+        Expr::Compare { left, comparators, .. } => {
+            expression_uses_floats(left) || comparators.iter().any(expression_uses_floats)
+        }
+        Expr::Call { args, kwargs, .. } => {
+            args.iter().any(expression_uses_floats) || kwargs.values().any(expression_uses_floats)
+        }
+        Expr::HardwareCall { args, .. } => args.iter().any(expression_uses_floats),
+        Expr::List { elements, .. } => elements.iter().any(expression_uses_floats),
+        Expr::Dict { entries, .. } => entries.iter().any(|(k, v)| expression_uses_floats(k) || expression_uses_floats(v)),
+        Expr::Index { value, index, .. } => expression_uses_floats(value) || expression_uses_floats(index),
+        Expr::Slice { value, start, stop, .. } => {
+            expression_uses_floats(value) || [start, stop].into_iter().flatten().any(|bound| expression_uses_floats(bound))
+        }
+        Expr::FieldAccess { object, .. } => expression_uses_floats(object),
+        Expr::MethodCall { receiver, args, .. } => expression_uses_floats(receiver) || args.iter().any(expression_uses_floats),
+        Expr::FString { parts, .. } => parts.iter().any(|part| match part {
+            crate::parser::FStringPart::Expr(e) => expression_uses_floats(e),
+            _ => false,
+        }),
+        _ => false,
+    }
+}
+
 pub fn compile<P: AsRef<std::path::Path>>(source_path: P, target: Target) -> Result<CompilationResult, String> {
     let config = CompilerConfig::default().with_target(target);
     let mut compiler = EarthangCompiler::new(config);
@@ -601,7 +1272,8 @@ pub fn compile<P: AsRef<std::path::Path>>(source_path: P, target: Target) -> Res
 pub fn compile_with_hardware<P: AsRef<std::path::Path>>(source_path: P, target: Target) -> Result<CompilationResult, String> {
     let config = CompilerConfig::default()
         .with_target(target)
-        .with_hardware_dsl(true);
+        .with_hardware_dsl(true)
+        .with_host_capabilities(crate::hardware::detect_capabilities());
     
     let mut compiler = EarthangCompiler::new(config);
     compiler.compile(source_path)
@@ -612,6 +1284,160 @@ pub fn compile_with_config<P: AsRef<std::path::Path>>(source_path: P, config: Co
     compiler.compile(source_path)
 }
 
+/// Compile source text all the way to a linked executable at `output`.
+pub fn compile_to_executable(source: &str, output: &std::path::Path, target: Target) -> Result<PathBuf, String> {
+    // The hardware library targets bare metal and cannot be linked into a hosted executable
+    let config = CompilerConfig::default()
+        .with_target(target)
+        .with_hardware_dsl(false);
+    compile_to_executable_with_config(source, output, config)
+}
+
+pub fn compile_to_executable_with_config(source: &str, output: &std::path::Path, config: CompilerConfig) -> Result<PathBuf, String> {
+    let keep_assembly = config.keep_assembly;
+    let target = config.target;
+    let logger = config.logger.clone();
+    let mut compiler = EarthangCompiler::new(config);
+    let result = compiler.compile_source(source, None)?;
+    assemble_and_link_with_logger(&result.assembly, output, target, keep_assembly, &logger)
+}
+
+/// Compile source text to a relocatable object file at `output`.
+pub fn compile_to_object(source: &str, output: &std::path::Path, target: Target) -> Result<PathBuf, String> {
+    let config = CompilerConfig::default()
+        .with_target(target)
+        .with_hardware_dsl(false);
+    let keep_assembly = config.keep_assembly;
+    let mut compiler = EarthangCompiler::new(config);
+    let result = compiler.compile_source(source, None)?;
+    
+    // Every current target is ELF based
+    assemble_object(&result.assembly, output, target, keep_assembly)
+}
+
+/// Assemble generated assembly with GNU `as` and link it with `ld`.
+///
+/// The `.s` and `.o` files go to unique temporary paths and are removed
+/// afterwards, or are kept next to `output` when `keep_intermediates` is set.
+pub fn assemble_and_link(assembly: &str, output: &std::path::Path, target: Target, keep_intermediates: bool) -> Result<PathBuf, String> {
+    assemble_and_link_with_logger(assembly, output, target, keep_intermediates, &crate::logging::Logger::default())
+}
+
+/// `assemble_and_link`, reporting the assemble and link phases to `logger`
+pub fn assemble_and_link_with_logger(assembly: &str, output: &std::path::Path, target: Target, keep_intermediates: bool, logger: &crate::logging::Logger) -> Result<PathBuf, String> {
+    // The kept .s is named after the object, so it must not be the output either
+    intermediate_path(output, "s", keep_intermediates)?;
+    let obj_path = intermediate_path(output, "o", keep_intermediates)?;
+
+    let linker = format!("{}ld", toolchain_prefix(target));
+    let linked = assemble_object_with_logger(assembly, &obj_path, target, keep_intermediates, logger).and_then(|_| {
+        let phase = logger.phase("link");
+        let linked = run_tool(&linker, &[
+            "-o".as_ref(),
+            output.as_os_str(),
+            obj_path.as_os_str(),
+        ]);
+        phase.end(format!("{} with {}", output.display(), linker));
+        linked
+    });
+
+    if !keep_intermediates {
+        let _ = std::fs::remove_file(&obj_path);
+    }
+
+    linked.map(|_| output.to_path_buf())
+}
+
+/// Assemble generated assembly into a relocatable ELF object at `output`,
+/// ready to be linked with `ld` or `cc`.
+pub fn assemble_object(assembly: &str, output: &std::path::Path, target: Target, keep_intermediates: bool) -> Result<PathBuf, String> {
+    assemble_object_with_logger(assembly, output, target, keep_intermediates, &crate::logging::Logger::default())
+}
+
+/// `assemble_object`, reporting the assemble phase to `logger`
+pub fn assemble_object_with_logger(assembly: &str, output: &std::path::Path, target: Target, keep_intermediates: bool, logger: &crate::logging::Logger) -> Result<PathBuf, String> {
+    let asm_path = intermediate_path(output, "s", keep_intermediates)?;
+
+    std::fs::write(&asm_path, assembly)
+        .map_err(|e| format!("Failed to write assembly file {}: {}", asm_path.display(), e))?;
+
+    let phase = logger.phase("assemble");
+    let assembler = format!("{}as", toolchain_prefix(target));
+    let mut args: Vec<&std::ffi::OsStr> = Vec::new();
+    if target == Target::Linux64 {
+        args.push("--64".as_ref());
+    }
+    args.extend(["-o".as_ref(), output.as_os_str(), asm_path.as_os_str()]);
+    let assembled = run_tool(&assembler, &args);
+    phase.end(format!("{} with {}", output.display(), assembler));
+
+    if !keep_intermediates {
+        let _ = std::fs::remove_file(&asm_path);
+    }
+
+    assembled.map(|_| output.to_path_buf())
+}
+
+/// Where the intermediate `extension` file for `output` is written: next to it
+/// when kept, else a temporary path that cannot clash with any output name
+fn intermediate_path(output: &std::path::Path, extension: &str, keep: bool) -> Result<PathBuf, String> {
+    static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    if !keep {
+        let n = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        return Ok(std::env::temp_dir().join(format!("earthang_{}_{}.{}", std::process::id(), n, extension)));
+    }
+    let path = output.with_extension(extension);
+    if path == output {
+        return Err(format!("Cannot keep the .{} file next to {}: it would overwrite the output", extension, output.display()));
+    }
+    Ok(path)
+}
+
+/// Prefix of the GNU binutils able to handle `target`'s assembly
+pub fn toolchain_prefix(target: Target) -> &'static str {
+    match target {
+        Target::Linux64 => "",
+        Target::RiscV64 => "riscv64-linux-gnu-",
+        Target::Aarch64 => "aarch64-linux-gnu-",
+    }
+}
+
+/// Find a toolchain binary, preferring a project-local `bin/` copy over `PATH`.
+pub fn find_tool(name: &str) -> PathBuf {
+    let local = PathBuf::from("bin").join(name);
+    if local.is_file() {
+        local
+    } else {
+        PathBuf::from(name)
+    }
+}
+
+pub(crate) fn run_tool(name: &str, args: &[&std::ffi::OsStr]) -> Result<(), String> {
+    tool_output(name, args).map(|_| ())
+}
+
+/// Run a binutils tool and return what it printed
+pub(crate) fn tool_output(name: &str, args: &[&std::ffi::OsStr]) -> Result<String, String> {
+    let tool = find_tool(name);
+    let output = std::process::Command::new(&tool)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", tool.display(), e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(format!("{} failed:\n{}", tool.display(), String::from_utf8_lossy(&output.stderr)))
+    }
+}
+
+/// The tools `assemble_and_link` needs for a hosted Linux64 build that cannot be run here
+pub fn missing_linux_toolchain() -> Vec<&'static str> {
+    ["as", "ld"].into_iter()
+        .filter(|tool| run_tool(tool, &["--version".as_ref()]).is_err())
+        .collect()
+}
+
 pub fn parse(source: &str) -> Result<Program, String> {
     crate::parser::parse_program(source)
         .map_err(|errors| {
@@ -633,6 +1459,14 @@ pub fn format_result(result: &CompilationResult) -> String {
         output.push('\n');
     }
     
+    if !result.diagnostics.is_empty() {
+        output.push_str(&format!("Diagnostics ({}):\n", result.diagnostics.len()));
+        for diagnostic in &result.diagnostics {
+            output.push_str(&format!("  - {}\n", diagnostic));
+        }
+        output.push('\n');
+    }
+    
     if !result.errors.is_empty() {
         output.push_str(&format!("Errors ({}):\n", result.errors.len()));
         for error in &result.errors {
@@ -656,4 +1490,407 @@ pub fn compile_with_extensions<P: AsRef<std::path::Path>>(source_path: P, target
     let config = CompilerConfig::default().with_target(target);
     let mut compiler = EarthangCompiler::new(config);
     compiler.compile(source_path)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assemble_object_is_relocatable_elf() {
+        let mut compiler = EarthangCompiler::new(CompilerConfig::default().with_hardware_dsl(false));
+        let result = compiler.compile_source("print(1)\n", None).unwrap();
+        let output = std::env::temp_dir().join(format!("earthang_obj_{}.o", std::process::id()));
+
+        // Only meaningful where binutils are installed
+        match assemble_object(&result.assembly, &output, Target::Linux64, false) {
+            Ok(_) => {}
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        }
+
+        let bytes = std::fs::read(&output).unwrap();
+        let _ = std::fs::remove_file(&output);
+        assert_eq!(&bytes[0..4], b"\x7fELF");
+        assert_eq!(bytes[4], 2); // ELFCLASS64
+        assert_eq!(u16::from_le_bytes([bytes[16], bytes[17]]), 1); // ET_REL
+        assert_eq!(u16::from_le_bytes([bytes[18], bytes[19]]), 0x3E); // EM_X86_64
+    }
+
+    #[test]
+    fn test_compile_to_executable() {
+        let output = std::env::temp_dir().join(format!("earthang_exe_{}", std::process::id()));
+        let binary = match compile_to_executable("print(6 * 7)\n", &output, Target::Linux64) {
+            Ok(binary) => binary,
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        };
+        assert_eq!(binary, output);
+        let run = std::process::Command::new(&binary).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "42\n");
+        // The .s and .o next to the output only stay when asked for
+        assert!(!output.with_extension("s").exists());
+        assert!(!output.with_extension("o").exists());
+
+        let config = CompilerConfig::default().with_hardware_dsl(false).with_keep_assembly(true);
+        compile_to_executable_with_config("print(1)\n", &output, config).unwrap();
+        assert!(std::fs::read_to_string(output.with_extension("s")).unwrap().contains("main:"));
+        assert!(output.with_extension("o").exists());
+        for path in [output.with_extension("s"), output.with_extension("o"), output.clone()] {
+            let _ = std::fs::remove_file(path);
+        }
+
+        // Assembler complaints come back with what it printed
+        let error = assemble_and_link("    bogus_instruction\n", &output, Target::Linux64, false).unwrap_err();
+        assert!(error.starts_with("as failed:") && error.contains("bogus_instruction"), "{}", error);
+        assert!(!output.exists());
+        assert!(!output.with_extension("s").exists());
+
+        // An output named like an intermediate is not clobbered by it
+        let named_obj = output.with_extension("o");
+        compile_to_executable("print(7)\n", &named_obj, Target::Linux64).unwrap();
+        let run = std::process::Command::new(&named_obj).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "7\n");
+        let named_asm = output.with_extension("s");
+        assemble_object("main:\n    ret\n", &named_asm, Target::Linux64, false).unwrap();
+        assert_eq!(&std::fs::read(&named_asm).unwrap()[0..4], b"\x7fELF");
+        let error = assemble_object("main:\n    ret\n", &named_asm, Target::Linux64, true).unwrap_err();
+        assert!(error.contains("would overwrite the output"), "{}", error);
+        for path in [named_obj, named_asm] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    /// With debug info every top-level statement's line shows up in the DWARF line table
+    #[test]
+    fn test_debug_line_table() {
+        let output = std::env::temp_dir().join(format!("earthang_debug_{}", std::process::id()));
+        let source = "x = 1\ndef greet(n): {\n    print(n)\n}\ngreet(x)\nprint(x + 1)\n";
+        let config = CompilerConfig::default().with_hardware_dsl(false).with_debug_info(true);
+        match compile_to_executable_with_config(source, &output, config) {
+            Ok(_) => {}
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        }
+        let dump = std::process::Command::new("objdump").arg("--dwarf=decodedline").arg(&output).output();
+        let _ = std::fs::remove_file(&output);
+        let Ok(dump) = dump else { return };
+        let dump = String::from_utf8_lossy(&dump.stdout);
+        let lines: Vec<u32> = dump.lines()
+            .filter(|line| line.starts_with("<source>"))
+            .filter_map(|line| line.split_whitespace().nth(1)?.parse().ok())
+            .collect();
+        for line in [1, 2, 3, 5, 6] {
+            assert!(lines.contains(&line), "line {} missing from:\n{}", line, dump);
+        }
+    }
+
+    /// Functions defined in one file are called from another once the files are linked
+    #[test]
+    fn test_compile_linked_files() {
+        let parse = |name: &str, source: &str| (PathBuf::from(name), crate::parser::parse_program(source).unwrap());
+        let mut compiler = EarthangCompiler::new(CompilerConfig::default().with_hardware_dsl(false));
+        let error = compiler.compile_linked(vec![parse("a.eg", "print(twice(2))\n"), parse("b.eg", "def twice(n): return n\n"), parse("c.eg", "def twice(n): return n * 2\n")]).unwrap_err();
+        assert_eq!(error, "Function 'twice' is defined in both b.eg at 1:1 and c.eg at 1:1");
+
+        let result = compiler.compile_linked(vec![
+            parse("main.eg", "print(twice(4), square(3))\n"),
+            parse("util.eg", "def twice(n): return add(n, n)\ndef square(n): return n * n\n"),
+            parse("add.eg", "def add(a, b): return a + b\n"),
+        ]).unwrap();
+        let output = std::env::temp_dir().join(format!("earthang_linked_{}", std::process::id()));
+        match assemble_and_link(&result.assembly, &output, Target::Linux64, false) {
+            Ok(_) => {}
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        }
+        let run = std::process::Command::new(&output).output().unwrap();
+        let _ = std::fs::remove_file(&output);
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "8 9\n");
+    }
+
+    #[test]
+    fn test_tail_calls_reuse_the_frame() {
+        let source = "def countdown(n): {\n    if n == 0: {\n        return 7\n    }\n    return countdown(n - 1)\n}\nprint(countdown(1000000))\n";
+        let compile = |debug_info: bool| {
+            let config = CompilerConfig::default().with_hardware_dsl(false).with_debug_info(debug_info);
+            EarthangCompiler::new(config).compile_source(source, None).unwrap().assembly
+        };
+        assert!(compile(false).contains("    jmp fn_countdown\n"));
+        // Debug info keeps every frame walkable
+        assert!(!compile(true).contains("    jmp fn_countdown\n"));
+
+        // A million frames would overflow the stack
+        let output = std::env::temp_dir().join(format!("earthang_tail_{}", std::process::id()));
+        match compile_to_executable(source, &output, Target::Linux64) {
+            Ok(_) => {}
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        }
+        let run = std::process::Command::new(&output).output().unwrap();
+        let _ = std::fs::remove_file(&output);
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "7\n");
+    }
+
+    #[test]
+    fn test_input_reads_stdin_lines() {
+        let output = std::env::temp_dir().join(format!("earthang_input_{}", std::process::id()));
+        let source = "name = input(\"Name? \")\nprint(\"hi\", name)\nprint(input())\nprint(input())\n";
+        match compile_to_executable(source, &output, Target::Linux64) {
+            Ok(_) => {}
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        }
+        let mut child = std::process::Command::new(&output)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        // The second line is longer than the buffer, the third has no newline before EOF
+        let long = "x".repeat(300);
+        std::io::Write::write_all(child.stdin.as_mut().unwrap(), format!("Ada\n{}\nend", long).as_bytes()).unwrap();
+        let result = child.wait_with_output().unwrap();
+        let _ = std::fs::remove_file(&output);
+        assert_eq!(String::from_utf8_lossy(&result.stdout), format!("Name? hi Ada\n{}\nend\n", &long[..255]));
+    }
+
+    #[test]
+    fn test_os_file_round_trip() {
+        let output = std::env::temp_dir().join(format!("earthang_os_{}", std::process::id()));
+        let file = std::env::temp_dir().join(format!("earthang_os_{}.txt", std::process::id()));
+        let source = format!(
+            "fd = open({path:?}, \"w\")\nprint(write(fd, \"hello file\\n\"))\nprint(close(fd))\nfd = open({path:?}, \"r\")\nprint(read(fd, 5))\nclose(fd)\nprint(open(\"/nonexistent/earthang\", \"r\"))\n",
+            path = file.display().to_string()
+        );
+        match compile_to_executable(&source, &output, Target::Linux64) {
+            Ok(_) => {}
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        }
+        let result = std::process::Command::new(&output).output().unwrap();
+        let written = std::fs::read_to_string(&file);
+        let _ = std::fs::remove_file(&output);
+        let _ = std::fs::remove_file(&file);
+        assert_eq!(written.unwrap(), "hello file\n");
+        // open() fails with -ENOENT
+        assert_eq!(String::from_utf8_lossy(&result.stdout), "11\n0\nhello\n-2\n");
+    }
+
+    /// A module saved as a library file is found by `import` in the search paths
+    /// and linked like a built-in one, its signatures checked at call sites
+    #[test]
+    fn test_import_module_library() {
+        let dir = std::env::temp_dir().join(format!("earthang_library_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        crate::module_library::ModuleLibrary::new("answer", "The answer")
+            .with_signature(crate::extension::FunctionSignature::parse("answer_64(offset: int) -> int").unwrap())
+            .with_code(Target::Linux64, "    .section .text\nanswer_64:\n    lea rax, [rdi + 42]\n    ret\n")
+            .save(dir.join("answer.egm"))
+            .unwrap();
+        crate::module_library::ModuleLibrary::new("question", "Misnamed").save(dir.join("asked.egm")).unwrap();
+
+        let config = || CompilerConfig::default().with_hardware_dsl(false).add_search_path(&dir);
+        let error = EarthangCompiler::new(config()).compile_source("import asked\n", None).unwrap_err();
+        assert!(error.ends_with("asked.egm defines the question module, not asked imported at 1:1"), "{}", error);
+        let error = EarthangCompiler::new(config()).compile_source("import unknown\n", None).unwrap_err();
+        assert!(error.starts_with("Unknown module 'unknown'"), "{}", error);
+        let error = EarthangCompiler::new(config()).compile_source("from answer import answer_64, answer_65\n", None).unwrap_err();
+        assert!(error.starts_with("Module 'answer' has no function 'answer_65' imported at 1:1"), "{}", error);
+        let error = EarthangCompiler::new(config()).compile_source("import answer\nprint(answer_64())\n", None).unwrap_err();
+        assert_eq!(error, "answer_64() takes 1 argument but 0 were given at 2:7; it is declared as answer_64(offset: int) -> int");
+
+        let output = dir.join("answer");
+        let built = compile_to_executable_with_config("from answer import answer_64\nprint(answer_64(0), answer_64(8))\n", &output, config());
+        let result = built.as_ref().ok().map(|_| std::process::Command::new(&output).output().unwrap());
+        let _ = std::fs::remove_dir_all(&dir);
+        match built {
+            Ok(_) => {}
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        }
+        assert_eq!(String::from_utf8_lossy(&result.unwrap().stdout), "42 50\n");
+    }
+
+    /// replace() handles repeated matches and replacements of another length, split()
+    /// keeps empty pieces; string module stubs are refused at compile time
+    #[test]
+    fn test_string_replace_and_split() {
+        let mut compiler = EarthangCompiler::new(CompilerConfig::default().with_hardware_dsl(false));
+        let error = compiler.compile_source("print(concat(\"a\", \"b\"))\n", None).unwrap_err();
+        assert_eq!(error, "concat() from the string module is not implemented for linux64");
+        assert!(compiler.compile_source("def concat(a, b): return a\nprint(concat(1, 2))\n", None).is_ok());
+
+        let output = std::env::temp_dir().join(format!("earthang_strings_{}", std::process::id()));
+        let source = "print(replace(\"a-b--c-\", \"-\", \"+=\"))\nprint(replace(\"aaaa\", \"aa\", \"b\"))\nprint(replace(\"hello world\", \"o\", \"\"))\nvar parts = split(\"a,b,,c\", \",\")\nvar p: str = \"\"\nfor i in range(len(parts)):\n    p = parts[i]\n    print(p)\nend\nprint(len(split(\"\", \",\")))\n";
+        match compile_to_executable(source, &output, Target::Linux64) {
+            Ok(_) => {}
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        }
+        let result = std::process::Command::new(&output).output().unwrap();
+        let _ = std::fs::remove_file(&output);
+        assert_eq!(String::from_utf8_lossy(&result.stdout), "a+=b+=+=c+=\nbb\nhell wrld\na\nb\n\nc\n1\n");
+    }
+
+    /// Appending 2000 elements moves the buffer through every size class from
+    /// 64 bytes to 16 KiB; each move has to carry all elements along
+    #[test]
+    fn test_list_append_growth() {
+        let output = std::env::temp_dir().join(format!("earthang_append_{}", std::process::id()));
+        let source = "var xs = []\nfor i in range(2000):\n    append(xs, i * 3)\nend\nvar total = 0\nfor i in range(2000):\n    if xs[i] != i * 3:\n        print(i)\n    total = total + xs[i]\nend\nprint(len(xs), total)\n";
+        match compile_to_executable(source, &output, Target::Linux64) {
+            Ok(_) => {}
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        }
+        let result = std::process::Command::new(&output).output().unwrap();
+        let _ = std::fs::remove_file(&output);
+        assert_eq!(String::from_utf8_lossy(&result.stdout), "2000 5997000\n");
+    }
+
+    /// Strings and lists dropped every iteration have to be reused: without
+    /// reference counting this loop needs over 20 MiB of heap, more than the
+    /// data segment limit it runs under
+    #[test]
+    fn test_refcounting_reuses_heap() {
+        let output = std::env::temp_dir().join(format!("earthang_rc_{}", std::process::id()));
+        let source = "def pair(n): {\n    var xs = [n, [n]]\n    return xs\n}\nvar s: str = \"\"\nvar xs = []\nfor i in range(10000):\n    s = read(0, 1000)\n    xs = pair(i)\nend\nprint(s)\nprint(xs[0])\n";
+        match compile_to_executable(source, &output, Target::Linux64) {
+            Ok(_) => {}
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        }
+        let result = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("ulimit -d 4096; exec {}", output.display()))
+            .stdin(std::process::Stdio::null())
+            .output()
+            .unwrap();
+        let _ = std::fs::remove_file(&output);
+        assert_eq!(String::from_utf8_lossy(&result.stderr), "");
+        assert_eq!(String::from_utf8_lossy(&result.stdout), "\n9999\n");
+    }
+
+    /// sleep() must actually block for the requested time and report success
+    #[test]
+    fn test_sleep_elapsed_time() {
+        let output = std::env::temp_dir().join(format!("earthang_sleep_{}", std::process::id()));
+        let source = "print(sleep(50))\nprint(sleep(0), sleep(-5))\n";
+        match compile_to_executable(source, &output, Target::Linux64) {
+            Ok(_) => {}
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        }
+        let started = std::time::Instant::now();
+        let result = std::process::Command::new(&output).output().unwrap();
+        let elapsed = started.elapsed();
+        let _ = std::fs::remove_file(&output);
+        assert_eq!(String::from_utf8_lossy(&result.stdout), "0\n0 0\n");
+        assert!(elapsed >= std::time::Duration::from_millis(50), "slept {:?}", elapsed);
+        assert!(elapsed < std::time::Duration::from_millis(1000), "slept {:?}", elapsed);
+    }
+
+    #[test]
+    fn test_error_rendering() {
+        // Parsing resumes after a broken statement, so both mistakes are reported
+        let source = "var x = 1\nwhile x > 1 x\n    print(x)\nend\nvar y = )\nprint(y)\n";
+        let errors = crate::parser::parse_program(source).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(&errors[0], crate::parser::ParseError::UnexpectedToken { found, expected, .. }
+            if found == "x" && expected == &["':'"]));
+        assert_eq!(
+            errors[0].render("demo.eg", source),
+            "error: expected ':', found 'x'\n --> demo.eg:2:13\n  |\n2 | while x > 1 x\n  |             ^\n"
+        );
+        assert!(errors[1].render("demo.eg", source).contains("5 | var y = )\n  |         ^\n"));
+
+        // Backend errors point at the statement they came from
+        let source = "var s = \"a\"\nprint(1)\nif s < 3: print(s)\n";
+        let mut compiler = EarthangCompiler::new(CompilerConfig::default().with_hardware_dsl(false));
+        let error = CompileError::from_message(compiler.compile_source(source, None).unwrap_err());
+        assert_eq!(error.span.map(|span| span.start.line), Some(3));
+        assert!(error.render("demo.eg", source).contains(" --> demo.eg:3:1\n  |\n3 | if s < 3: print(s)\n  | ^\n"));
+        assert_eq!(CompileError::from_message("No backend found").span, None);
+    }
+
+    #[test]
+    fn test_constant_folding_shrinks_output() {
+        let source = "print(2 + 3 * 4)\nvar x = -(7 - 10) < 5 and not False\nif False: print(1)\nelse: print(x)\nif 1 > 2: print(2)\ndef f(a): return a\nprint(f(x))\n";
+        let compile = |optimize: bool| {
+            let config = CompilerConfig { optimize, ..CompilerConfig::default().with_hardware_dsl(false) };
+            EarthangCompiler::new(config).compile_source(source, None).unwrap().assembly
+        };
+        let (folded, unfolded) = (compile(true), compile(false));
+        assert!(folded.contains("mov rax, 14\n"));
+        assert!(!folded.contains("imul rax, rbx") && unfolded.contains("imul rax, rbx"));
+        assert!(!folded.contains("Short-circuit"));
+        assert!(folded.len() < unfolded.len(), "{} >= {} bytes", folded.len(), unfolded.len());
+
+        // Nothing after an unconditional return survives
+        let mut program = crate::parser::parse_program("var y = 1\nreturn y\nprint(y)\n").unwrap();
+        ConstantFoldingPass.optimize(&mut program).unwrap();
+        DeadCodeEliminationPass.optimize(&mut program).unwrap();
+        assert_eq!(program.body.len(), 2);
+
+        // Folded nodes keep the span of the expression they replace; negative division is left to the backend
+        let mut program = crate::parser::parse_program("print(10 / 3, -7 / 2, 3 >= 4)\n").unwrap();
+        ConstantFoldingPass.optimize(&mut program).unwrap();
+        let Statement::Expr(Expr::Call { args, .. }) = &program.body[0] else { panic!() };
+        assert!(matches!(args[0], Expr::Number(3, span) if span.start.line == 1));
+        assert!(matches!(args[1], Expr::BinOp { .. }));
+        assert!(matches!(args[2], Expr::Number(0, _)));
+    }
+
+    #[test]
+    fn test_constants() {
+        let source = "const GREETING = \"hi there\"\nconst SIDE = 3\nconst AREA = SIDE * SIDE\ndef show(n): {\n    print(GREETING, n + AREA)\n}\nshow(1)\nprint(GREETING)\n";
+        let compile = |source: &str, optimize: bool| {
+            let config = CompilerConfig { optimize, ..CompilerConfig::default().with_hardware_dsl(false) };
+            EarthangCompiler::new(config).compile_source(source, None)
+        };
+        let labels = |assembly: &str| assembly.lines().filter(|line| line.starts_with("str_") && line.contains("hi there")).count();
+        for optimize in [true, false] {
+            let assembly = compile(source, optimize).unwrap().assembly;
+            // Register allocation adds the folded constant in place
+            let area = if optimize { "add rax, 9\n" } else { "mov rax, 9\n" };
+            assert!(assembly.contains(area), "{}", assembly);
+            // Both uses print the one labelled string
+            assert_eq!(labels(&assembly), 1, "{}", assembly);
+        }
+
+        // The BIOS payload goes through the folding pass only
+        let mut program = crate::parser::parse_program(source).unwrap();
+        ConstantFoldingPass.optimize(&mut program).unwrap();
+        let payload = crate::backend::Linux64Backend::new()
+            .with_bios_graphics(crate::framebuffer::Framebuffer::vga_mode_13h(), crate::framebuffer::SimdLevel::Sse2)
+            .with_debug_serial(true)
+            .compile_program(&program)
+            .unwrap();
+        assert!(payload.contains("mov rax, 9\n"), "{}", payload);
+        assert_eq!(labels(&payload), 1, "{}", payload);
+
+        let error = |source: &str| compile(source, true).unwrap_err();
+        assert!(error("const A = 1\nvar x = 2\nconst A = 3\n").contains("Constant 'A' is already defined at 1:1; redefined at 3:1"));
+        assert!(error("const A = 1\ndef f(): {\n    A = 2\n}\n").contains("Constant 'A' defined at 1:1 cannot be reassigned at 3:5"));
+        assert!(error("const A = [1]\n").contains("must be a number, string or boolean"));
+        assert!(error("if True: {\n    const A = 1\n}\n").contains("Constants must be declared at the top level"));
+    }
+
+    #[test]
+    fn test_code_size_limit() {
+        let source = "var greeting = \"hello, world\"\nprint(greeting)\ndef twice(n): return n * 2\nprint(twice(21))\n";
+        let compile = |limit: usize| {
+            let config = CompilerConfig::default().with_hardware_dsl(false).with_code_size_limit(limit);
+            EarthangCompiler::new(config).compile_source(source, None)
+        };
+
+        let assembly = compile(usize::MAX).unwrap().assembly;
+        let report = SizeReport::measure(&assembly, &Target::Linux64);
+        assert!(report.statements.contains_key(&1) && report.statements.contains_key(&3));
+        assert!(report.strings.iter().any(|(literal, bytes)| literal == "\"hello, world\"" && *bytes == 13));
+
+        assert!(compile(report.total).is_ok());
+        let error = compile(report.total - 1).unwrap_err();
+        assert!(error.contains(&format!("estimated {} bytes but the limit is {}", report.total, report.total - 1)));
+        assert!(error.contains("print(twice(21))") && error.contains("\"hello, world\""));
+    }
+}
@@ -0,0 +1,288 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::path::{Path, PathBuf};
+use crate::backend::Target;
+use crate::compiler::{CompilerConfig, EarthangCompiler};
+use crate::interp::Interpreter;
+
+// Runs a corpus of programs through the interpreter and through the Linux64
+// backend and checks that both print the same thing. Next to `NAME.eg` a
+// corpus may hold
+//
+//   NAME.in          text fed to the program's stdin, empty when missing
+//   NAME.out         what the program must print
+//   NAME.error       the interpreter's message when the program must stop
+//                    with a runtime error; compiled code must fail too
+//   NAME.native.out  what compiled code prints instead, for a known
+//                    divergence between the two
+//
+// The interpreter's output is checked against NAME.out whenever it exists.
+// When GNU as and ld can be run, the program is also compiled, linked and
+// run, and its output must equal the interpreter's, or NAME.native.out
+// when there is one. Without them the expected files are all that is
+// checked, so a program needs either the toolchain or a NAME.out to be
+// checked at all.
+
+/// Where a program stopped agreeing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Parse,
+    Compile,
+    Assemble,
+    Run,
+    Output,
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Stage::Parse => "parse",
+            Stage::Compile => "compile",
+            Stage::Assemble => "assemble",
+            Stage::Run => "run",
+            Stage::Output => "output",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub program: PathBuf,
+    pub stage: Stage,
+    pub detail: String,
+    /// Assembly the program compiled to, kept when the compiled half failed
+    pub assembly: Option<PathBuf>,
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: diverged at {}: {}", self.program.display(), self.stage, self.detail)?;
+        if let Some(assembly) = &self.assembly {
+            write!(f, "\n  assembly kept in {}", assembly.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// What checking a program compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checked {
+    pub expected_file: bool,
+    pub compiled: bool,
+    /// Compiled code was checked against NAME.native.out
+    pub known_divergence: bool,
+}
+
+pub struct Harness {
+    /// Whether programs are also compiled and run
+    native: bool,
+    work_dir: PathBuf,
+}
+
+impl Harness {
+    /// Compile programs too when the toolchain is there
+    pub fn new() -> Self {
+        Self::with_native(crate::compiler::missing_linux_toolchain().is_empty())
+    }
+
+    pub fn with_native(native: bool) -> Self {
+        Self { native, work_dir: std::env::temp_dir().join(format!("earthang_differential_{}", std::process::id())) }
+    }
+
+    pub fn native(&self) -> bool {
+        self.native
+    }
+
+    /// Every `.eg` file directly in `dir`, sorted by name
+    pub fn corpus(dir: &Path) -> Result<Vec<PathBuf>, String> {
+        let entries = std::fs::read_dir(dir).map_err(|e| format!("Failed to read '{}': {}", dir.display(), e))?;
+        let mut programs: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "eg"))
+            .collect();
+        programs.sort();
+        Ok(programs)
+    }
+
+    pub fn check(&self, program: &Path) -> Result<Checked, Divergence> {
+        let diverged = |stage: Stage, detail: String| Divergence { program: program.to_path_buf(), stage, detail, assembly: None };
+        let source = std::fs::read_to_string(program).map_err(|e| diverged(Stage::Parse, format!("cannot read the program: {}", e)))?;
+        let input = std::fs::read_to_string(program.with_extension("in")).unwrap_or_default();
+        let expected = std::fs::read_to_string(program.with_extension("out")).ok();
+        let expected_error = std::fs::read_to_string(program.with_extension("error")).ok().map(|error| error.trim_end().to_string());
+        let native_expected = std::fs::read_to_string(program.with_extension("native.out")).ok();
+
+        let file_name = program.display().to_string();
+        let parsed = crate::parser::parse_program(&source).map_err(|errors| {
+            diverged(Stage::Parse, errors.iter().map(|error| error.render(&file_name, &source)).collect::<Vec<_>>().join("\n"))
+        })?;
+        let base_dir = program.parent().map(Path::to_path_buf);
+        let included = crate::lua_frontend::IncludeProcessor::new().process_includes(&parsed, base_dir.as_ref())
+            .map_err(|e| diverged(Stage::Parse, format!("Include processing error: {}", e)))?;
+
+        let (interpreted, stopped) = crate::interp::with_large_stack(|| {
+            let mut output = Vec::new();
+            let stopped = Interpreter::new(input.as_bytes(), &mut output).run(&included).err();
+            (String::from_utf8_lossy(&output).into_owned(), stopped)
+        });
+        match (&stopped, &expected_error) {
+            (Some(error), Some(expected_error)) if error != expected_error => {
+                return Err(diverged(Stage::Run, format!("the interpreter stopped with '{}', expected '{}'", error, expected_error)));
+            }
+            (Some(error), None) => return Err(diverged(Stage::Run, format!("the interpreter stopped: {}", error))),
+            (None, Some(expected_error)) => return Err(diverged(Stage::Run, format!("the interpreter finished, expected it to stop with '{}'", expected_error))),
+            _ => {}
+        }
+        if let Some(expected) = &expected {
+            if &interpreted != expected {
+                return Err(diverged(Stage::Output, mismatch("expected", expected, "interpreter", &interpreted)));
+            }
+        }
+        if !self.native {
+            return Ok(Checked { expected_file: expected.is_some(), compiled: false, known_divergence: false });
+        }
+
+        let config = CompilerConfig::default().with_target(Target::Linux64).with_hardware_dsl(false);
+        let result = EarthangCompiler::new(config).compile_program(parsed, Some(program))
+            .map_err(|e| diverged(Stage::Compile, e))?;
+        let name = program.file_stem().map_or_else(|| "program".into(), |stem| stem.to_string_lossy().into_owned());
+        std::fs::create_dir_all(&self.work_dir)
+            .map_err(|e| diverged(Stage::Assemble, format!("Failed to create '{}': {}", self.work_dir.display(), e)))?;
+        let assembly_path = self.work_dir.join(format!("{}.s", name));
+        let with_assembly = |mut divergence: Divergence| {
+            if std::fs::write(&assembly_path, &result.assembly).is_ok() {
+                divergence.assembly = Some(assembly_path.clone());
+            }
+            divergence
+        };
+
+        let executable = crate::compiler::assemble_and_link(&result.assembly, &self.work_dir.join(&name), Target::Linux64, false)
+            .map_err(|e| with_assembly(diverged(Stage::Assemble, e)))?;
+        let run = run_with_input(&executable, &input).map_err(|e| with_assembly(diverged(Stage::Run, e)))?;
+        match (run.status.success(), &expected_error) {
+            (false, None) => {
+                let detail = format!("the program exited with {}\n{}", run.status, run.stderr);
+                return Err(with_assembly(diverged(Stage::Run, detail)));
+            }
+            (true, Some(expected_error)) => {
+                let detail = format!("the program exited successfully, where the interpreter stopped with '{}'", expected_error);
+                return Err(with_assembly(diverged(Stage::Run, detail)));
+            }
+            _ => {}
+        }
+        let (want_name, want) = match &native_expected {
+            Some(native_expected) => ("expected of compiled code", native_expected),
+            None => ("interpreter", &interpreted),
+        };
+        if run.stdout != *want {
+            return Err(with_assembly(diverged(Stage::Output, mismatch(want_name, want, "compiled", &run.stdout))));
+        }
+        Ok(Checked { expected_file: expected.is_some(), compiled: true, known_divergence: native_expected.is_some() })
+    }
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        // Keep the assembly of divergences, and the directory while there is any
+        for entry in std::fs::read_dir(&self.work_dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "s") {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+        let _ = std::fs::remove_dir(&self.work_dir);
+    }
+}
+
+/// How a compiled program ended
+struct Run {
+    status: std::process::ExitStatus,
+    stdout: String,
+    stderr: String,
+}
+
+fn run_with_input(executable: &Path, input: &str) -> Result<Run, String> {
+    use std::io::Write;
+    let mut child = std::process::Command::new(executable)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", executable.display(), e))?;
+    // A program that exits without reading closes the pipe; its status says what went wrong
+    let _ = child.stdin.take().map(|mut stdin| stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output().map_err(|e| format!("Failed to run {}: {}", executable.display(), e))?;
+    Ok(Run {
+        status: output.status,
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    })
+}
+
+/// Both outputs line by line, from the first line they differ on
+fn mismatch(left_name: &str, left: &str, right_name: &str, right: &str) -> String {
+    let first = left.lines().zip(right.lines()).take_while(|(a, b)| a == b).count();
+    let show = |text: &str| text.lines().skip(first).map(|line| format!("    {}\n", line)).collect::<String>();
+    format!(
+        "output differs from line {}\n  {}:\n{}  {}:\n{}",
+        first + 1, left_name, show(left), right_name, show(right)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_divergence_names_the_stage() {
+        let dir = std::env::temp_dir().join(format!("earthang_differential_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("good.eg"), "print(input(), 6 * 7)\n").unwrap();
+        std::fs::write(dir.join("good.in"), "answer\n").unwrap();
+        std::fs::write(dir.join("good.out"), "answer 42\n").unwrap();
+        std::fs::write(dir.join("stale.eg"), "print(1)\nprint(2)\n").unwrap();
+        std::fs::write(dir.join("stale.out"), "1\n3\n").unwrap();
+        std::fs::write(dir.join("broken.eg"), "print(1 +\n").unwrap();
+        std::fs::write(dir.join("crash.eg"), "print(1 / 0)\n").unwrap();
+        std::fs::write(dir.join("stops.eg"), "print(2)\nprint(1 / 0)\n").unwrap();
+        std::fs::write(dir.join("stops.out"), "2\n").unwrap();
+        std::fs::write(dir.join("stops.error"), "Division by zero at 2:7\n").unwrap();
+        std::fs::write(dir.join("wrong_error.eg"), "print([][0])\n").unwrap();
+        std::fs::write(dir.join("wrong_error.error"), "Division by zero at 1:7\n").unwrap();
+
+        let harness = Harness::with_native(false);
+        let programs = Harness::corpus(&dir).unwrap();
+        let names: Vec<_> = programs.iter().map(|path| path.file_name().unwrap().to_string_lossy().into_owned()).collect();
+        assert_eq!(names, ["broken.eg", "crash.eg", "good.eg", "stale.eg", "stops.eg", "wrong_error.eg"]);
+
+        assert_eq!(harness.check(&programs[0]).unwrap_err().stage, Stage::Parse);
+        assert_eq!(harness.check(&programs[1]).unwrap_err().stage, Stage::Run);
+        assert_eq!(harness.check(&programs[2]).unwrap(), Checked { expected_file: true, compiled: false, known_divergence: false });
+        let stale = harness.check(&programs[3]).unwrap_err();
+        assert_eq!(stale.stage, Stage::Output);
+        assert!(stale.detail.starts_with("output differs from line 2\n  expected:\n    3\n  interpreter:\n    2\n"), "{}", stale.detail);
+        // A program may be expected to stop, with the error it stops with
+        assert!(harness.check(&programs[4]).is_ok());
+        let wrong = harness.check(&programs[5]).unwrap_err();
+        assert!(wrong.stage == Stage::Run && wrong.detail.contains("expected 'Division by zero at 1:7'"), "{}", wrong);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,383 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use crate::symbol_map::{object_symbols, ObjectSymbol, SymbolMap};
+
+pub const SECTOR_SIZE: usize = 512;
+
+/// Where the BIOS loads the boot sector
+pub const BOOT_SECTOR_ADDRESS: u32 = 0x7C00;
+
+/// Bytes of boot sector code that fit in front of the signature
+pub const BOOT_CODE_LIMIT: usize = 510;
+
+/// Last two bytes of a bootable sector
+pub const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+
+/// Default load address of stage 2, directly after the boot sector in memory
+pub const STAGE2_LOAD_ADDRESS: u32 = 0x7E00;
+
+/// Stage 2 must end below this address; conventional memory above it may hold the EBDA
+pub const STAGE2_MEMORY_END: u32 = 0x80000;
+
+/// Where one part of a disk image was placed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageEntry {
+    pub name: String,
+    pub lba: u32,
+    pub sectors: u32,
+    pub size: usize,
+}
+
+/// A raw disk image and the manifest of its parts
+#[derive(Debug, Clone)]
+pub struct DiskImage {
+    pub bytes: Vec<u8>,
+    pub manifest: Vec<ImageEntry>,
+    /// Labels inside the parts, filled in by the pipelines that know them
+    pub symbols: SymbolMap,
+}
+
+impl DiskImage {
+    pub fn entry(&self, name: &str) -> Option<&ImageEntry> {
+        self.manifest.iter().find(|entry| entry.name == name)
+    }
+
+    /// One line per part: name, first sector, sector count and byte size
+    pub fn manifest_text(&self) -> String {
+        self.manifest.iter()
+            .map(|entry| format!("{:<12} lba {:<6} sectors {:<6} bytes {}\n", entry.name, entry.lba, entry.sectors, entry.size))
+            .collect()
+    }
+}
+
+/// Builder laying out a boot sector followed by further parts, each starting on a sector boundary
+#[derive(Debug, Clone)]
+pub struct DiskImageBuilder {
+    boot_sector: Vec<u8>,
+    parts: Vec<(String, Vec<u8>, Option<u32>)>,
+    total_sectors: Option<u32>,
+}
+
+impl DiskImageBuilder {
+    /// `boot_sector` is the code for LBA 0; the signature is added by `build`
+    pub fn new(boot_sector: Vec<u8>) -> Self {
+        Self { boot_sector, parts: Vec::new(), total_sectors: None }
+    }
+
+    /// Place a part at the first free sector after the previous one
+    pub fn with_part(mut self, name: &str, bytes: Vec<u8>) -> Self {
+        self.parts.push((name.to_string(), bytes, None));
+        self
+    }
+
+    /// Place a part at a fixed sector, for loaders that expect it there
+    pub fn with_part_at(mut self, name: &str, bytes: Vec<u8>, lba: u32) -> Self {
+        self.parts.push((name.to_string(), bytes, Some(lba)));
+        self
+    }
+
+    /// Pad the image to a fixed number of sectors, e.g. 2880 for a 1.44 MB floppy
+    pub fn with_total_sectors(mut self, sectors: u32) -> Self {
+        self.total_sectors = Some(sectors);
+        self
+    }
+
+    pub fn build(&self) -> Result<DiskImage, String> {
+        if self.boot_sector.len() > BOOT_CODE_LIMIT {
+            return Err(format!(
+                "Boot sector code is {} bytes; at most {} fit before the boot signature",
+                self.boot_sector.len(), BOOT_CODE_LIMIT
+            ));
+        }
+
+        let mut bytes = self.boot_sector.clone();
+        bytes.resize(BOOT_CODE_LIMIT, 0);
+        bytes.extend_from_slice(&BOOT_SIGNATURE);
+        let mut manifest = vec![ImageEntry { name: "boot".to_string(), lba: 0, sectors: 1, size: self.boot_sector.len() }];
+
+        for (name, part, fixed_lba) in &self.parts {
+            let next_free = (bytes.len() / SECTOR_SIZE) as u32;
+            let lba = fixed_lba.unwrap_or(next_free);
+            if lba < next_free {
+                return Err(format!("Part '{}' at LBA {} overlaps the parts before it, which end at LBA {}", name, lba, next_free));
+            }
+
+            let sectors = part.len().div_ceil(SECTOR_SIZE) as u32;
+            bytes.resize(lba as usize * SECTOR_SIZE, 0);
+            bytes.extend_from_slice(part);
+            bytes.resize((lba + sectors) as usize * SECTOR_SIZE, 0);
+            manifest.push(ImageEntry { name: name.clone(), lba, sectors, size: part.len() });
+        }
+
+        if let Some(total) = self.total_sectors {
+            let used = bytes.len() / SECTOR_SIZE;
+            if used > total as usize {
+                return Err(format!("Image needs {} sectors but is limited to {}", used, total));
+            }
+            bytes.resize(total as usize * SECTOR_SIZE, 0);
+        }
+
+        Ok(DiskImage { bytes, manifest, symbols: SymbolMap::default() })
+    }
+}
+
+/// Boot sector that reads `sectors` sectors from `start_lba` to `load_address` and jumps there
+/// with the boot drive in dl. It uses the INT 13h extensions when the BIOS has them and falls
+/// back to CHS reads with the geometry from INT 13h AH=08h otherwise
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stage1Loader {
+    pub load_address: u32,
+    pub start_lba: u32,
+    pub sectors: u32,
+    /// Print the real-mode progress marker on COM1 before loading, for `test boot`
+    pub serial_markers: bool,
+}
+
+impl Stage1Loader {
+    pub fn new(sectors: u32) -> Self {
+        Self { load_address: STAGE2_LOAD_ADDRESS, start_lba: 1, sectors, serial_markers: false }
+    }
+
+    pub fn with_load_address(mut self, address: u32) -> Self {
+        self.load_address = address;
+        self
+    }
+
+    pub fn with_serial_markers(mut self, enabled: bool) -> Self {
+        self.serial_markers = enabled;
+        self
+    }
+
+    /// Real-mode segment and offset of the load address. Below 64 KiB the segment
+    /// is zero so stage 2 can be linked at its linear address
+    fn far_address(&self) -> Result<(u32, u32), String> {
+        let end = self.load_address as u64 + self.sectors as u64 * SECTOR_SIZE as u64;
+        if self.load_address < STAGE2_LOAD_ADDRESS || end > STAGE2_MEMORY_END as u64 {
+            return Err(format!(
+                "Stage 2 of {} sectors at 0x{:X} does not fit between 0x{:X} and 0x{:X}",
+                self.sectors, self.load_address, STAGE2_LOAD_ADDRESS, STAGE2_MEMORY_END
+            ));
+        }
+        if self.start_lba > u16::MAX as u32 - self.sectors {
+            return Err(format!("Stage 2 must start below LBA {}", u16::MAX as u32 - self.sectors));
+        }
+
+        match self.load_address {
+            address if address <= 0xFFFF => Ok((0, address)),
+            address if address.is_multiple_of(16) => Ok((address >> 4, 0)),
+            address => Err(format!("Stage 2 load address 0x{:X} above 64 KiB must be 16-byte aligned", address)),
+        }
+    }
+
+    /// GNU as source of the boot sector, without the signature. Addresses are written
+    /// relative to `_start`, so the object needs no relocation before objcopy
+    pub fn to_gas(&self) -> Result<String, String> {
+        let (segment, offset) = self.far_address()?;
+        let at = |label: &str| format!("{} - _start + 0x7C00", label);
+
+        let mut asm = String::new();
+        asm.push_str("    .intel_syntax noprefix\n");
+        asm.push_str("    .code16\n");
+        asm.push_str("    .section .text\n");
+        asm.push_str("    .globl _start\n");
+        asm.push_str("_start:\n");
+        asm.push_str("    cli\n");
+        asm.push_str("    xor ax, ax\n");
+        asm.push_str("    mov ds, ax\n");
+        asm.push_str("    mov ss, ax\n");
+        asm.push_str("    mov sp, 0x7C00\n");
+        asm.push_str("    sti\n");
+        asm.push_str(&format!("    mov [{}], dl\n", at("boot_drive")));
+        if self.serial_markers {
+            asm.push_str(&crate::boot_test::serial_init_gas());
+            asm.push_str(&crate::boot_test::serial_marker_gas(crate::boot_test::BootPhase::RealMode));
+        }
+
+        asm.push_str("    # Prefer the INT 13h extensions; CHS reads need the drive geometry first\n");
+        asm.push_str("    mov ah, 0x41\n");
+        asm.push_str("    mov bx, 0x55AA\n");
+        asm.push_str("    int 0x13\n");
+        asm.push_str("    jc chs_geometry\n");
+        asm.push_str("    cmp bx, 0xAA55\n");
+        asm.push_str("    jne chs_geometry\n");
+        asm.push_str("    test cl, 1\n");
+        asm.push_str("    jz chs_geometry\n");
+        asm.push_str(&format!("    mov byte ptr [{}], 1\n", at("use_lba")));
+        asm.push_str("    jmp read_start\n");
+        asm.push_str("chs_geometry:\n");
+        asm.push_str("    mov ah, 0x08\n");
+        asm.push_str(&format!("    mov dl, [{}]\n", at("boot_drive")));
+        asm.push_str("    xor di, di\n");
+        asm.push_str("    mov es, di\n");
+        asm.push_str("    int 0x13\n");
+        asm.push_str("    jc disk_error\n");
+        asm.push_str("    and cx, 0x3F\n");
+        asm.push_str(&format!("    mov [{}], cx\n", at("sectors_per_track")));
+        asm.push_str("    movzx dx, dh\n");
+        asm.push_str("    inc dx\n");
+        asm.push_str(&format!("    mov [{}], dx\n", at("heads")));
+
+        asm.push_str("read_start:\n");
+        asm.push_str(&format!("    mov di, {}\n", self.sectors));
+        asm.push_str("read_next:\n");
+        asm.push_str(&format!("    mov ax, [{}]\n", at("dap_segment")));
+        asm.push_str("    mov es, ax\n");
+        asm.push_str(&format!("    cmp byte ptr [{}], 0\n", at("use_lba")));
+        asm.push_str("    je read_chs\n");
+        asm.push_str(&format!("    mov si, offset {}\n", at("dap")));
+        asm.push_str("    mov ah, 0x42\n");
+        asm.push_str(&format!("    mov dl, [{}]\n", at("boot_drive")));
+        asm.push_str("    int 0x13\n");
+        asm.push_str("    jc disk_error\n");
+        asm.push_str("    jmp read_done\n");
+        asm.push_str("read_chs:\n");
+        asm.push_str(&format!("    mov ax, [{}]\n", at("dap_lba")));
+        asm.push_str("    xor dx, dx\n");
+        asm.push_str(&format!("    div word ptr [{}]\n", at("sectors_per_track")));
+        asm.push_str("    mov cl, dl\n");
+        asm.push_str("    inc cl\n");
+        asm.push_str("    xor dx, dx\n");
+        asm.push_str(&format!("    div word ptr [{}]\n", at("heads")));
+        asm.push_str("    mov ch, al\n");
+        asm.push_str("    shl ah, 6\n");
+        asm.push_str("    or cl, ah\n");
+        asm.push_str("    mov dh, dl\n");
+        asm.push_str(&format!("    mov dl, [{}]\n", at("boot_drive")));
+        asm.push_str(&format!("    mov bx, [{}]\n", at("dap_offset")));
+        asm.push_str("    mov ax, 0x0201\n");
+        asm.push_str("    int 0x13\n");
+        asm.push_str("    jc disk_error\n");
+        asm.push_str("read_done:\n");
+        asm.push_str("    # One sector at a time, so no read crosses a 64 KiB boundary\n");
+        asm.push_str(&format!("    add word ptr [{}], 0x20\n", at("dap_segment")));
+        asm.push_str(&format!("    inc word ptr [{}]\n", at("dap_lba")));
+        asm.push_str("    dec di\n");
+        asm.push_str("    jnz read_next\n");
+        asm.push_str(&format!("    mov dl, [{}]\n", at("boot_drive")));
+        asm.push_str(&format!("    jmp 0x{:04X}:0x{:04X}\n", segment, offset));
+
+        asm.push_str("disk_error:\n");
+        asm.push_str(&format!("    mov si, offset {}\n", at("disk_error_message")));
+        asm.push_str("    xor bx, bx\n");
+        asm.push_str("print_next:\n");
+        asm.push_str("    lodsb\n");
+        asm.push_str("    test al, al\n");
+        asm.push_str("    jz halt\n");
+        asm.push_str("    mov ah, 0x0E\n");
+        asm.push_str("    int 0x10\n");
+        asm.push_str("    jmp print_next\n");
+        asm.push_str("halt:\n");
+        asm.push_str("    cli\n");
+        asm.push_str("    hlt\n");
+        asm.push_str("    jmp halt\n");
+
+        asm.push_str("boot_drive: .byte 0\n");
+        asm.push_str("use_lba: .byte 0\n");
+        asm.push_str("sectors_per_track: .word 0\n");
+        asm.push_str("heads: .word 0\n");
+        asm.push_str("    .balign 4\n");
+        asm.push_str("dap:\n");
+        asm.push_str("    .byte 0x10, 0\n");
+        asm.push_str("    .word 1\n");
+        asm.push_str(&format!("dap_offset: .word 0x{:04X}\n", offset));
+        asm.push_str(&format!("dap_segment: .word 0x{:04X}\n", segment));
+        asm.push_str(&format!("dap_lba: .quad {}\n", self.start_lba));
+        asm.push_str("disk_error_message: .asciz \"Disk read error\"\n");
+        Ok(asm)
+    }
+
+    /// Assemble the boot sector with GNU as and objcopy
+    pub fn assemble(&self, work_dir: &std::path::Path) -> Result<Vec<u8>, String> {
+        self.assemble_with_symbols(work_dir).map(|(code, _)| code)
+    }
+
+    /// `assemble`, also returning the loader's labels at the addresses they run at
+    pub fn assemble_with_symbols(&self, work_dir: &std::path::Path) -> Result<(Vec<u8>, Vec<ObjectSymbol>), String> {
+        let source = work_dir.join("stage1.s");
+        let object = work_dir.join("stage1.o");
+        let binary = work_dir.join("stage1.bin");
+        std::fs::write(&source, self.to_gas()?)
+            .map_err(|e| format!("Failed to write {}: {}", source.display(), e))?;
+
+        crate::compiler::run_tool("as", &["--32".as_ref(), "-o".as_ref(), object.as_os_str(), source.as_os_str()])?;
+        crate::compiler::run_tool("objcopy", &["-O".as_ref(), "binary".as_ref(), "-j".as_ref(), ".text".as_ref(), object.as_os_str(), binary.as_os_str()])?;
+        let code = std::fs::read(&binary)
+            .map_err(|e| format!("Failed to read {}: {}", binary.display(), e))?;
+        let symbols = object_symbols(&object, BOOT_SECTOR_ADDRESS as u64)?;
+
+        for path in [&source, &object, &binary] {
+            let _ = std::fs::remove_file(path);
+        }
+        Ok((code, symbols))
+    }
+}
+
+/// Image whose boot sector loads `stage2` to `load_address` from the sectors right after it
+pub fn two_stage_image(stage2: Vec<u8>, load_address: u32, work_dir: &std::path::Path) -> Result<DiskImage, String> {
+    let sectors = stage2.len().div_ceil(SECTOR_SIZE).max(1) as u32;
+    let stage1 = Stage1Loader::new(sectors).with_load_address(load_address).assemble(work_dir)?;
+    DiskImageBuilder::new(stage1).with_part("stage2", stage2).build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_layout() {
+        let image = DiskImageBuilder::new(vec![0xEB, 0xFE])
+            .with_part("stage2", vec![0x90; 700])
+            .with_part_at("kernel", vec![0xF4; 10], 64)
+            .build()
+            .unwrap();
+
+        assert_eq!(&image.bytes[510..512], &BOOT_SIGNATURE);
+        let stage2 = image.entry("stage2").unwrap();
+        assert_eq!((stage2.lba, stage2.sectors, stage2.size), (1, 2, 700));
+        assert_eq!(image.bytes[SECTOR_SIZE], 0x90);
+        assert_eq!(image.bytes[SECTOR_SIZE + 700], 0);
+        assert_eq!(image.bytes[64 * SECTOR_SIZE], 0xF4);
+        assert_eq!(image.bytes.len(), 65 * SECTOR_SIZE);
+        assert!(image.manifest_text().contains("kernel       lba 64"));
+
+        assert!(DiskImageBuilder::new(vec![0; 511]).build().is_err());
+        assert!(DiskImageBuilder::new(vec![]).with_part("a", vec![1; 1024]).with_part_at("b", vec![1], 2).build().is_err());
+        assert_eq!(DiskImageBuilder::new(vec![]).with_total_sectors(2880).build().unwrap().bytes.len(), 2880 * SECTOR_SIZE);
+    }
+
+    #[test]
+    fn test_stage1_loader() {
+        assert!(Stage1Loader::new(1000).to_gas().is_err());
+        assert!(Stage1Loader::new(4).with_load_address(0x10008).to_gas().is_err());
+        let gas = Stage1Loader::new(4).with_load_address(0x10000).to_gas().unwrap();
+        assert!(gas.contains("jmp 0x1000:0x0000\n") && gas.contains("dap_lba: .quad 1\n"));
+
+        // Only meaningful where binutils are installed
+        let work_dir = std::env::temp_dir().join(format!("earthang_stage1_{}", std::process::id()));
+        std::fs::create_dir_all(&work_dir).unwrap();
+        let result = two_stage_image(vec![0xF4; 3 * SECTOR_SIZE + 1], STAGE2_LOAD_ADDRESS, &work_dir);
+        let with_markers = Stage1Loader::new(64).with_serial_markers(true).assemble(&work_dir);
+        let _ = std::fs::remove_dir(&work_dir);
+        let image = match result {
+            Ok(image) => image,
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        };
+        assert!(with_markers.unwrap().len() <= BOOT_CODE_LIMIT);
+        assert_eq!(image.bytes[0], 0xFA); // cli
+        assert_eq!(&image.bytes[510..512], &BOOT_SIGNATURE);
+        assert_eq!(image.entry("stage2").map(|e| (e.lba, e.sectors)), Some((1, 4)));
+        assert!(image.bytes[..510].windows(3).any(|w| w == [0xBF, 4, 0])); // mov di, 4
+    }
+}
@@ -13,6 +13,7 @@
 */
 use std::collections::HashMap;
 use std::cell::RefCell;
+use crate::parser::{BoolOp, CompareOp, Expr, FStringPart, Op, Position, Program, Span, Statement, UnaryOp};
 
 #[derive(Debug, Clone)]
 pub struct HardwareDSL {
@@ -861,6 +862,503 @@ pub fn compile_with_hardware(source: &str, target: crate::backend::Target) -> Re
     Ok(final_asm)
 }
 
+// Building programs directly, for tools that generate Earthang code. The
+// builder yields the same Program the parser does; spans default to the start
+// of the file unless the caller sets them with `at` or `Expr::with_span`
+
+fn default_span() -> Span {
+    Span::single(Position::start())
+}
+
+/// Values `lit` turns into literal expressions
+pub trait Literal {
+    fn into_expr(self, span: Span) -> Expr;
+}
+
+impl Literal for i64 {
+    fn into_expr(self, span: Span) -> Expr {
+        Expr::Number(self, span)
+    }
+}
+
+impl Literal for i32 {
+    fn into_expr(self, span: Span) -> Expr {
+        Expr::Number(self as i64, span)
+    }
+}
+
+impl Literal for f64 {
+    fn into_expr(self, span: Span) -> Expr {
+        Expr::Float(self, span)
+    }
+}
+
+impl Literal for bool {
+    fn into_expr(self, span: Span) -> Expr {
+        Expr::Boolean(self, span)
+    }
+}
+
+impl Literal for &str {
+    fn into_expr(self, span: Span) -> Expr {
+        Expr::String(self.to_string(), span)
+    }
+}
+
+impl Literal for String {
+    fn into_expr(self, span: Span) -> Expr {
+        Expr::String(self, span)
+    }
+}
+
+pub fn lit<T: Literal>(value: T) -> Expr {
+    value.into_expr(default_span())
+}
+
+pub fn ident(name: &str) -> Expr {
+    Expr::Var(name.to_string(), default_span())
+}
+
+pub fn none() -> Expr {
+    Expr::None(default_span())
+}
+
+pub fn binop(left: Expr, op: Op, right: Expr) -> Expr {
+    Expr::BinOp { left: Box::new(left), op, right: Box::new(right), span: default_span() }
+}
+
+pub fn unary(op: UnaryOp, operand: Expr) -> Expr {
+    Expr::UnaryOp { op, operand: Box::new(operand), span: default_span() }
+}
+
+pub fn bool_op(op: BoolOp, values: impl IntoIterator<Item = Expr>) -> Expr {
+    Expr::BoolOp { op, values: values.into_iter().collect(), span: default_span() }
+}
+
+pub fn compare(left: Expr, op: CompareOp, right: Expr) -> Expr {
+    Expr::Compare { left: Box::new(left), ops: vec![op], comparators: vec![right], span: default_span() }
+}
+
+pub fn call(func: &str, args: impl IntoIterator<Item = Expr>) -> Expr {
+    Expr::Call { func: func.to_string(), args: args.into_iter().collect(), kwargs: HashMap::new(), span: default_span() }
+}
+
+pub fn call_with_kwargs<'a>(func: &str, args: impl IntoIterator<Item = Expr>, kwargs: impl IntoIterator<Item = (&'a str, Expr)>) -> Expr {
+    Expr::Call {
+        func: func.to_string(),
+        args: args.into_iter().collect(),
+        kwargs: kwargs.into_iter().map(|(name, value)| (name.to_string(), value)).collect(),
+        span: default_span(),
+    }
+}
+
+pub fn hardware_call(device: &str, func: &str, args: impl IntoIterator<Item = Expr>) -> Expr {
+    Expr::HardwareCall { device: device.to_string(), func: func.to_string(), args: args.into_iter().collect(), span: default_span() }
+}
+
+pub fn fstring(parts: impl IntoIterator<Item = FStringPart>) -> Expr {
+    Expr::FString { parts: parts.into_iter().collect(), span: default_span() }
+}
+
+pub fn list(elements: impl IntoIterator<Item = Expr>) -> Expr {
+    Expr::List { elements: elements.into_iter().collect(), span: default_span() }
+}
+
+pub fn dict(entries: impl IntoIterator<Item = (Expr, Expr)>) -> Expr {
+    Expr::Dict { entries: entries.into_iter().collect(), span: default_span() }
+}
+
+pub fn index(value: Expr, index: Expr) -> Expr {
+    Expr::Index { value: Box::new(value), index: Box::new(index), span: default_span() }
+}
+
+/// `value[start:stop]`; `None` leaves a bound out
+pub fn slice(value: Expr, start: Option<Expr>, stop: Option<Expr>) -> Expr {
+    Expr::Slice { value: Box::new(value), start: start.map(Box::new), stop: stop.map(Box::new), span: default_span() }
+}
+
+pub fn field(object: Expr, field: &str) -> Expr {
+    Expr::FieldAccess { object: Box::new(object), field: field.to_string(), span: default_span() }
+}
+
+pub fn method_call(receiver: Expr, method: &str, args: impl IntoIterator<Item = Expr>) -> Expr {
+    Expr::MethodCall { receiver: Box::new(receiver), method: method.to_string(), args: args.into_iter().collect(), span: default_span() }
+}
+
+/// Words the lexer reserves, which cannot name anything
+const KEYWORDS: &[&str] = &[
+    "var", "const", "struct", "if", "elif", "else", "while", "for", "in", "return", "def", "and", "or", "not",
+    "pass", "break", "continue", "True", "False", "None", "include", "import", "from",
+    "section", "global", "end", "device", "hw", "gpu", "network", "storage", "sound",
+];
+
+pub fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KEYWORDS.contains(&name)
+}
+
+/// Builds a top-level program or, inside the closures passed to `func`, `if_`
+/// and the loops, a block of statements
+///
+/// ```ignore
+/// let program = ProgramBuilder::new()
+///     .func("main", |f| f.var("x", lit(5)).call("print", [ident("x")]))
+///     .call("main", [])
+///     .build()?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct ProgramBuilder {
+    body: Vec<Statement>,
+    span: Span,
+    errors: Vec<String>,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        Self {
+            body: Vec::new(),
+            span: default_span(),
+            errors: Vec::new(),
+        }
+    }
+    
+    /// Give the statements added after this one `span`
+    pub fn at(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+    
+    pub fn stmt(mut self, statement: Statement) -> Self {
+        self.body.push(statement);
+        self
+    }
+    
+    pub fn var(self, name: &str, value: Expr) -> Self {
+        let span = self.span;
+        self.stmt(Statement::VarDecl { name: name.to_string(), value, type_hint: None, span })
+    }
+    
+    pub fn var_typed(self, name: &str, type_hint: &str, value: Expr) -> Self {
+        let span = self.span;
+        self.stmt(Statement::VarDecl { name: name.to_string(), value, type_hint: Some(type_hint.to_string()), span })
+    }
+    
+    pub fn const_(self, name: &str, value: Expr) -> Self {
+        let span = self.span;
+        self.stmt(Statement::ConstDecl { name: name.to_string(), value, span })
+    }
+    
+    pub fn assign(self, target: &str, value: Expr) -> Self {
+        let span = self.span;
+        self.stmt(Statement::Assign { target: target.to_string(), value, span })
+    }
+    
+    pub fn aug_assign(self, target: &str, op: Op, value: Expr) -> Self {
+        let span = self.span;
+        self.stmt(Statement::AugAssign { target: target.to_string(), op, value, span })
+    }
+    
+    pub fn index_assign(self, target: Expr, index: Expr, value: Expr) -> Self {
+        let span = self.span;
+        self.stmt(Statement::IndexAssign { target: Box::new(target), index: Box::new(index), value, span })
+    }
+    
+    pub fn field_assign(self, target: Expr, field: &str, value: Expr) -> Self {
+        let span = self.span;
+        self.stmt(Statement::FieldAssign { target: Box::new(target), field: field.to_string(), value, span })
+    }
+    
+    pub fn struct_<'a>(self, name: &str, fields: impl IntoIterator<Item = &'a str>) -> Self {
+        let span = self.span;
+        self.stmt(Statement::StructDef { name: name.to_string(), fields: fields.into_iter().map(str::to_string).collect(), span })
+    }
+    
+    pub fn expr(self, expr: Expr) -> Self {
+        let span = self.span;
+        self.stmt(Statement::Expr(expr.with_span(span)))
+    }
+    
+    pub fn call(self, func: &str, args: impl IntoIterator<Item = Expr>) -> Self {
+        self.expr(call(func, args))
+    }
+    
+    pub fn ret(self, value: Option<Expr>) -> Self {
+        let span = self.span;
+        self.stmt(Statement::Return(value, span))
+    }
+    
+    pub fn pass(self) -> Self {
+        self.stmt(Statement::Pass)
+    }
+    
+    pub fn break_(self) -> Self {
+        self.stmt(Statement::Break)
+    }
+    
+    pub fn continue_(self) -> Self {
+        self.stmt(Statement::Continue)
+    }
+    
+    pub fn func(self, name: &str, body: impl FnOnce(Self) -> Self) -> Self {
+        self.func_with_args(name, &[], body)
+    }
+    
+    pub fn func_with_args(mut self, name: &str, args: &[&str], body: impl FnOnce(Self) -> Self) -> Self {
+        let span = self.span;
+        let body = self.block(body);
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let arg_types = vec![None; args.len()];
+        self.stmt(Statement::FunctionDef { name: name.to_string(), args, arg_types, return_type: None, body, span })
+    }
+    
+    pub fn hardware_func(mut self, device: &str, name: &str, args: &[&str], body: impl FnOnce(Self) -> Self) -> Self {
+        let span = self.span;
+        let body = self.block(body);
+        let args = args.iter().map(|arg| arg.to_string()).collect();
+        self.stmt(Statement::HardwareFunctionDef { device: device.to_string(), name: name.to_string(), args, body, span })
+    }
+    
+    pub fn if_(mut self, condition: Expr, then_block: impl FnOnce(Self) -> Self) -> Self {
+        let span = self.span;
+        let then_block = self.block(then_block);
+        self.stmt(Statement::If { condition, then_block, elif_blocks: Vec::new(), else_block: None, span })
+    }
+    
+    /// Add an `elif` to the `if` just added
+    pub fn elif(mut self, condition: Expr, body: impl FnOnce(Self) -> Self) -> Self {
+        let body = self.block(body);
+        match self.body.last_mut() {
+            Some(Statement::If { elif_blocks, else_block: None, .. }) => elif_blocks.push((condition, body)),
+            _ => self.errors.push("elif must follow an if without an else".to_string()),
+        }
+        self
+    }
+    
+    /// Add an `else` to the `if` just added
+    pub fn else_(mut self, body: impl FnOnce(Self) -> Self) -> Self {
+        let body = self.block(body);
+        match self.body.last_mut() {
+            Some(Statement::If { else_block: else_block @ None, .. }) => *else_block = Some(body),
+            _ => self.errors.push("else must follow an if without an else".to_string()),
+        }
+        self
+    }
+    
+    pub fn while_(mut self, condition: Expr, body: impl FnOnce(Self) -> Self) -> Self {
+        let span = self.span;
+        let body = self.block(body);
+        self.stmt(Statement::While { condition, body, orelse: None, span })
+    }
+    
+    /// `for var in range(args):`, the only iteration the language has
+    pub fn for_range(mut self, var: &str, args: impl IntoIterator<Item = Expr>, body: impl FnOnce(Self) -> Self) -> Self {
+        let span = self.span;
+        let body = self.block(body);
+        let iter = call("range", args).with_span(span);
+        self.stmt(Statement::For { var: var.to_string(), iter, body, span })
+    }
+    
+    pub fn include(self, filename: &str) -> Self {
+        let span = self.span;
+        self.stmt(Statement::Include { filename: filename.to_string(), span })
+    }
+    
+    pub fn import(self, module: &str) -> Self {
+        let span = self.span;
+        self.stmt(Statement::Import { module: module.to_string(), items: None, span })
+    }
+    
+    pub fn import_from(self, module: &str, items: &[&str]) -> Self {
+        let span = self.span;
+        let items = Some(items.iter().map(|item| item.to_string()).collect());
+        self.stmt(Statement::Import { module: module.to_string(), items, span })
+    }
+    
+    pub fn device<'a>(self, device: &str, config: impl IntoIterator<Item = (&'a str, Expr)>) -> Self {
+        let span = self.span;
+        let config = config.into_iter().map(|(key, value)| (key.to_string(), value)).collect();
+        self.stmt(Statement::HardwareDecl { device: device.to_string(), config, span })
+    }
+    
+    /// Build a nested block, keeping its errors and span
+    fn block(&mut self, build: impl FnOnce(Self) -> Self) -> Vec<Statement> {
+        let mut block = build(Self::new().at(self.span));
+        self.errors.append(&mut block.errors);
+        block.body
+    }
+    
+    /// The program, once every name in it is a valid identifier
+    pub fn build(self) -> Result<Program, String> {
+        let mut errors = self.errors;
+        for statement in &self.body {
+            check_statement_names(statement, &mut errors);
+        }
+        if !errors.is_empty() {
+            return Err(errors.join("\n"));
+        }
+        Ok(Program { body: self.body, span: default_span(), hardware_devices: HashMap::new() })
+    }
+}
+
+impl Default for ProgramBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn check_name(name: &str, what: &str, errors: &mut Vec<String>) {
+    if !is_identifier(name) {
+        errors.push(format!("'{}' is not a valid {} name", name, what));
+    }
+}
+
+fn check_block_names(body: &[Statement], errors: &mut Vec<String>) {
+    for statement in body {
+        check_statement_names(statement, errors);
+    }
+}
+
+fn check_statement_names(statement: &Statement, errors: &mut Vec<String>) {
+    match statement {
+        Statement::VarDecl { name, value, type_hint, .. } => {
+            check_name(name, "variable", errors);
+            if let Some(type_hint) = type_hint {
+                check_name(type_hint, "type", errors);
+            }
+            check_expr_names(value, errors);
+        }
+        Statement::ConstDecl { name, value, .. } => {
+            check_name(name, "constant", errors);
+            check_expr_names(value, errors);
+        }
+        Statement::Assign { target, value, .. } | Statement::AugAssign { target, value, .. } => {
+            check_name(target, "variable", errors);
+            check_expr_names(value, errors);
+        }
+        Statement::IndexAssign { target, index, value, .. } => {
+            check_expr_names(target, errors);
+            check_expr_names(index, errors);
+            check_expr_names(value, errors);
+        }
+        Statement::FieldAssign { target, field, value, .. } => {
+            check_expr_names(target, errors);
+            check_name(field, "field", errors);
+            check_expr_names(value, errors);
+        }
+        Statement::Expr(expr) | Statement::Return(Some(expr), _) => check_expr_names(expr, errors),
+        Statement::If { condition, then_block, elif_blocks, else_block, .. } => {
+            check_expr_names(condition, errors);
+            check_block_names(then_block, errors);
+            for (condition, body) in elif_blocks {
+                check_expr_names(condition, errors);
+                check_block_names(body, errors);
+            }
+            check_block_names(else_block.as_deref().unwrap_or_default(), errors);
+        }
+        Statement::While { condition, body, orelse, .. } => {
+            check_expr_names(condition, errors);
+            check_block_names(body, errors);
+            check_block_names(orelse.as_deref().unwrap_or_default(), errors);
+        }
+        Statement::For { var, iter, body, .. } => {
+            check_name(var, "loop variable", errors);
+            check_expr_names(iter, errors);
+            check_block_names(body, errors);
+        }
+        Statement::FunctionDef { name, args, body, .. } => {
+            check_name(name, "function", errors);
+            args.iter().for_each(|arg| check_name(arg, "parameter", errors));
+            check_block_names(body, errors);
+        }
+        Statement::HardwareFunctionDef { device, name, args, body, .. } => {
+            check_name(device, "device", errors);
+            check_name(name, "function", errors);
+            args.iter().for_each(|arg| check_name(arg, "parameter", errors));
+            check_block_names(body, errors);
+        }
+        Statement::StructDef { name, fields, .. } => {
+            check_name(name, "struct", errors);
+            fields.iter().for_each(|field| check_name(field, "field", errors));
+        }
+        Statement::Import { module, items, .. } => {
+            check_name(module, "module", errors);
+            items.iter().flatten().for_each(|item| check_name(item, "imported", errors));
+        }
+        Statement::HardwareDecl { device, config, .. } => {
+            check_name(device, "device", errors);
+            for (key, value) in config {
+                check_name(key, "device setting", errors);
+                check_expr_names(value, errors);
+            }
+        }
+        Statement::Return(None, _) | Statement::Include { .. } | Statement::Pass | Statement::Break | Statement::Continue => {}
+    }
+}
+
+fn check_expr_names(expr: &Expr, errors: &mut Vec<String>) {
+    match expr {
+        Expr::Var(name, _) => check_name(name, "variable", errors),
+        Expr::Number(..) | Expr::Float(..) | Expr::Boolean(..) | Expr::String(..) | Expr::None(_) => {}
+        Expr::BinOp { left, right, .. } => {
+            check_expr_names(left, errors);
+            check_expr_names(right, errors);
+        }
+        Expr::UnaryOp { operand, .. } => check_expr_names(operand, errors),
+        Expr::BoolOp { values, .. } | Expr::List { elements: values, .. } => {
+            values.iter().for_each(|value| check_expr_names(value, errors));
+        }
+        Expr::Compare { left, comparators, .. } => {
+            check_expr_names(left, errors);
+            comparators.iter().for_each(|value| check_expr_names(value, errors));
+        }
+        Expr::Call { func, args, kwargs, .. } => {
+            check_name(func, "function", errors);
+            args.iter().for_each(|arg| check_expr_names(arg, errors));
+            for (name, value) in kwargs {
+                check_name(name, "keyword argument", errors);
+                check_expr_names(value, errors);
+            }
+        }
+        Expr::HardwareCall { device, func, args, .. } => {
+            check_name(device, "device", errors);
+            check_name(func, "function", errors);
+            args.iter().for_each(|arg| check_expr_names(arg, errors));
+        }
+        Expr::FString { parts, .. } => {
+            for part in parts {
+                if let FStringPart::Expr(expr) = part {
+                    check_expr_names(expr, errors);
+                }
+            }
+        }
+        Expr::Dict { entries, .. } => {
+            for (key, value) in entries {
+                check_expr_names(key, errors);
+                check_expr_names(value, errors);
+            }
+        }
+        Expr::Index { value, index, .. } => {
+            check_expr_names(value, errors);
+            check_expr_names(index, errors);
+        }
+        Expr::Slice { value, start, stop, .. } => {
+            check_expr_names(value, errors);
+            [start, stop].into_iter().flatten().for_each(|bound| check_expr_names(bound, errors));
+        }
+        Expr::FieldAccess { object, field, .. } => {
+            check_expr_names(object, errors);
+            check_name(field, "field", errors);
+        }
+        Expr::MethodCall { receiver, method, args, .. } => {
+            check_expr_names(receiver, errors);
+            check_name(method, "method", errors);
+            args.iter().for_each(|arg| check_expr_names(arg, errors));
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -899,6 +1397,67 @@ mod tests {
         
         println!("Hardware library generation test passed!");
     }
+    
+    fn without_spans(program: &Program) -> serde_json::Value {
+        let mut body = program.body.clone();
+        for statement in &mut body {
+            crate::lua_frontend::visit_statement_spans(statement, &mut |span| *span = Span::single(Position::start()));
+        }
+        serde_json::to_value(&body).unwrap()
+    }
+    
+    #[test]
+    fn test_program_builder() {
+        let program = ProgramBuilder::new()
+            .import("math")
+            .import_from("string", &["upper"])
+            .include("lib.eg")
+            .var_typed("name", "str", lit("tab\there \"quoted\""))
+            .var("xs", list([lit(1), lit(2.5), none(), dict([(lit("k"), lit(true))])]))
+            .func_with_args("step", &["a", "b"], |f| f
+                .var("c", binop(binop(ident("a"), Op::Add, ident("b")), Op::Mul, unary(UnaryOp::Minus, ident("a"))))
+                .if_(compare(ident("c"), CompareOp::Lt, lit(0)), |b| b.ret(Some(lit(0))))
+                .elif(bool_op(BoolOp::Or, [compare(ident("c"), CompareOp::In, ident("xs")), unary(UnaryOp::Not, lit(false))]), |b| b.pass())
+                .else_(|b| b.aug_assign("c", Op::Sub, lit(1)))
+                .ret(Some(ident("c"))))
+            .for_range("i", [lit(3)], |b| b
+                .index_assign(ident("xs"), ident("i"), call("step", [ident("i"), index(ident("xs"), lit(0))]))
+                .if_(compare(ident("i"), CompareOp::Eq, lit(2)), |b| b.break_()))
+            .while_(lit(false), |b| b.continue_())
+            .assign("name", fstring([FStringPart::Literal("{n} = ".to_string()), FStringPart::Expr(ident("name"))]))
+            .call("print", [ident("name")])
+            .build()
+            .unwrap();
+        
+        let source = program.to_string();
+        assert!(source.contains("def step(a, b): {\n    var c = (a + b) * -a\n"), "{}", source);
+        let parsed = crate::parser::parse_program(&source).unwrap();
+        assert_eq!(without_spans(&parsed), without_spans(&program), "{}", source);
+        
+        let error = ProgramBuilder::new()
+            .func_with_args("2fast", &["end"], |f| f.call("print", [ident("ok")]))
+            .else_(|b| b.pass())
+            .build()
+            .unwrap_err();
+        assert_eq!(error, "else must follow an if without an else\n'2fast' is not a valid function name\n'end' is not a valid parameter name");
+    }
+    
+    /// A built program compiles like the same program parsed from its source
+    #[test]
+    fn test_compile_built_program() {
+        let program = ProgramBuilder::new()
+            .func("greet", |f| f.var("x", lit(5)).call("print", [ident("x")]))
+            .call("greet", [])
+            .build()
+            .unwrap();
+        let config = || crate::compiler::CompilerConfig::default().with_hardware_dsl(false);
+        let built = crate::compiler::EarthangCompiler::new(config()).compile_program(program.clone(), None).unwrap();
+        let parsed = crate::compiler::EarthangCompiler::new(config()).compile_source(&program.to_string(), None).unwrap();
+        // Only the line markers differ, as built statements all claim line 1
+        let code = |assembly: &str| assembly.lines().filter(|line| !line.contains("# @line")).collect::<Vec<_>>().join("\n");
+        assert_eq!(code(&built.assembly), code(&parsed.assembly));
+        assert_eq!(built.stats.functions_compiled, 1);
+    }
 }
 
 // Make the DSL available as a module
@@ -112,7 +112,7 @@ impl NasmEmitter {
                     }
                 }
             }
-            Statement::Return(value) => {
+            Statement::Return(value, _) => {
                 if let Some(expr) = value {
                     self.collect_strings_from_expr(expr);
                 }
@@ -308,7 +308,10 @@ impl NasmEmitter {
     code.push_str("section .data\n");
     
     // String literals
-    for (string, label) in &self.string_literals {
+    // Emit in first-use order so repeated builds produce identical output
+    let mut literals: Vec<_> = self.string_literals.iter().collect();
+    literals.sort_by_key(|(_, label)| self.data_labels.iter().position(|l| l == *label));
+    for (string, label) in literals {
         code.push_str(&format!("{}: db '{}', 10, 0\n", label, string)); // Add newline for Linux
     }
     
@@ -0,0 +1,234 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::collections::{HashMap, HashSet};
+use crate::parser::{Expr, FStringPart, Statement};
+
+// Escape analysis finds the list literals that can live in their function's
+// frame instead of on the heap. A variable qualifies when:
+//
+// - one `var name = [...]` is its only assignment and it is not a
+//   parameter, and the literal holds at least one element, each an
+//   integer, float, boolean, string literal or None, so dropping the list
+//   never has references to release;
+// - it is only read by subscripting it, passed to the builtin len(), or
+//   stored into through a subscript with one of those literals.
+//
+// Anything else, such as returning it, passing it to a function, storing
+// it anywhere or appending to it, which may move the elements to a bigger
+// heap buffer, keeps it on the heap. Code that compiles with
+// `CompilerConfig::optimize` off never asks.
+
+/// Variables of `body`, a function's or the top level's statements, whose
+/// list literal cannot outlive the frame, with its number of elements.
+/// `params` are the function's parameters and `functions` the names the
+/// program defines, which shadow builtins such as len()
+pub fn stack_lists(body: &[Statement], params: &[String], functions: &HashSet<String>) -> HashMap<String, usize> {
+    let mut uses = Uses { functions, candidates: HashMap::new(), escaped: params.iter().cloned().collect() };
+    uses.visit_block(body);
+    uses.candidates.into_iter()
+        .filter_map(|(name, elements)| elements.filter(|_| !uses.escaped.contains(&name)).map(|elements| (name, elements)))
+        .collect()
+}
+
+/// A value a dropped list holds no counted reference to
+fn is_plain_literal(expr: &Expr) -> bool {
+    matches!(expr, Expr::Number(..) | Expr::Float(..) | Expr::Boolean(..) | Expr::String(..) | Expr::None(_))
+}
+
+struct Uses<'a> {
+    functions: &'a HashSet<String>,
+    /// Declared variables, with their literal's length while there is one
+    /// qualifying declaration
+    candidates: HashMap<String, Option<usize>>,
+    escaped: HashSet<String>,
+}
+
+impl Uses<'_> {
+    fn visit_block(&mut self, body: &[Statement]) {
+        body.iter().for_each(|stmt| self.visit_statement(stmt));
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::VarDecl { name, value, .. } => {
+                let qualifies = matches!(value, Expr::List { elements, .. } if !elements.is_empty() && elements.iter().all(is_plain_literal));
+                let elements = match value {
+                    Expr::List { elements, .. } if qualifies => Some(elements.len()),
+                    _ => None,
+                };
+                // A second declaration is a reassignment
+                let first = !self.candidates.contains_key(name);
+                self.candidates.insert(name.clone(), elements.filter(|_| first));
+                self.visit_expr(value);
+            }
+            Statement::Assign { target, value, .. } | Statement::AugAssign { target, value, .. } => {
+                self.escaped.insert(target.clone());
+                self.visit_expr(value);
+            }
+            Statement::IndexAssign { target, index, value, .. } => {
+                match target.as_ref() {
+                    Expr::Var(name, _) if !is_plain_literal(value) => {
+                        self.escaped.insert(name.clone());
+                    }
+                    Expr::Var(..) => {}
+                    target => self.visit_expr(target),
+                }
+                self.visit_expr(index);
+                self.visit_expr(value);
+            }
+            Statement::FieldAssign { target, value, .. } => {
+                self.visit_expr(target);
+                self.visit_expr(value);
+            }
+            Statement::Expr(expr) | Statement::Return(Some(expr), _) => self.visit_expr(expr),
+            Statement::If { condition, then_block, elif_blocks, else_block, .. } => {
+                self.visit_expr(condition);
+                self.visit_block(then_block);
+                for (condition, block) in elif_blocks {
+                    self.visit_expr(condition);
+                    self.visit_block(block);
+                }
+                if let Some(block) = else_block {
+                    self.visit_block(block);
+                }
+            }
+            Statement::While { condition, body, orelse, .. } => {
+                self.visit_expr(condition);
+                self.visit_block(body);
+                if let Some(block) = orelse {
+                    self.visit_block(block);
+                }
+            }
+            Statement::For { var, iter, body, .. } => {
+                self.escaped.insert(var.clone());
+                self.visit_expr(iter);
+                self.visit_block(body);
+            }
+            Statement::HardwareDecl { config, .. } => config.values().for_each(|expr| self.visit_expr(expr)),
+            // Compiled inline, in the frame of the code around them
+            Statement::HardwareFunctionDef { body, .. } => self.visit_block(body),
+            // Function bodies have frames of their own
+            _ => {}
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Var(name, _) => {
+                self.escaped.insert(name.clone());
+            }
+            Expr::Index { value, index, .. } if matches!(value.as_ref(), Expr::Var(..)) => self.visit_expr(index),
+            Expr::Call { func, args, kwargs, .. } if func == "len" && !self.functions.contains(func)
+                && kwargs.is_empty() && matches!(args.as_slice(), [Expr::Var(..)]) => {}
+            Expr::Number(..) | Expr::Float(..) | Expr::Boolean(..) | Expr::String(..) | Expr::None(_) => {}
+            Expr::BinOp { left, right, .. } | Expr::Index { value: left, index: right, .. } => {
+                self.visit_expr(left);
+                self.visit_expr(right);
+            }
+            Expr::UnaryOp { operand, .. } | Expr::FieldAccess { object: operand, .. } => self.visit_expr(operand),
+            Expr::Compare { left, comparators, .. } => {
+                self.visit_expr(left);
+                comparators.iter().for_each(|e| self.visit_expr(e));
+            }
+            Expr::Call { args, kwargs, .. } => {
+                args.iter().for_each(|e| self.visit_expr(e));
+                kwargs.values().for_each(|e| self.visit_expr(e));
+            }
+            Expr::BoolOp { values, .. } | Expr::List { elements: values, .. } | Expr::HardwareCall { args: values, .. } => {
+                values.iter().for_each(|e| self.visit_expr(e));
+            }
+            Expr::FString { parts, .. } => {
+                for part in parts {
+                    if let FStringPart::Expr(e) = part {
+                        self.visit_expr(e);
+                    }
+                }
+            }
+            Expr::Dict { entries, .. } => {
+                for (key, value) in entries {
+                    self.visit_expr(key);
+                    self.visit_expr(value);
+                }
+            }
+            Expr::Slice { value, start, stop, .. } => {
+                self.visit_expr(value);
+                [start, stop].into_iter().flatten().for_each(|bound| self.visit_expr(bound));
+            }
+            Expr::MethodCall { receiver, args, .. } => {
+                self.visit_expr(receiver);
+                args.iter().for_each(|e| self.visit_expr(e));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{CompilerConfig, EarthangCompiler};
+    use crate::parser::parse_program;
+
+    fn stack_lists_of(source: &str) -> Vec<(String, usize)> {
+        let program = parse_program(source).unwrap();
+        let mut found: Vec<(String, usize)> = stack_lists(&program.body, &[], &HashSet::new()).into_iter().collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn test_only_lists_that_stay_in_the_frame_qualify() {
+        let source = "var xs = [1, 2, 3]\nvar ys = [\"a\", None]\nxs[0] = 4\nprint(xs[0] + len(xs), ys[1])\n";
+        assert_eq!(stack_lists_of(source), vec![("xs".to_string(), 3), ("ys".to_string(), 2)]);
+
+        let escaping = [
+            "var xs = [1]\nprint(xs)\n",
+            "var xs = [1]\nappend(xs, 2)\n",
+            "var xs = [1]\nvar ys = xs\n",
+            "var xs = [1]\nxs = [2]\n",
+            "var xs = [1]\nvar xs = [2]\n",
+            "var xs = [1]\nxs[0] = [2]\n",
+            "var xs = [1]\nprint(1 in xs)\n",
+            "var ys = [1]\nvar xs = [ys]\n",
+            "var xs = [1]\nreturn xs\n",
+            "var xs = []\nprint(len(xs))\n",
+            "var n = 1\nvar xs = [n]\n",
+        ];
+        for source in escaping {
+            assert_eq!(stack_lists_of(source).into_iter().find(|(name, _)| name == "xs"), None, "{}", source);
+        }
+
+        // A user function named len may keep the list
+        let program = parse_program("var xs = [1]\nprint(len(xs))\n").unwrap();
+        assert!(stack_lists(&program.body, &[], &HashSet::from(["len".to_string()])).is_empty());
+        assert!(stack_lists(&program.body, &["xs".to_string()], &HashSet::new()).is_empty());
+    }
+
+    #[test]
+    fn test_stack_lists_skip_the_allocator() {
+        let source = "def total(): {\n    var xs = [4, 5, 6]\n    xs[1] = 10\n    return xs[0] + xs[1] + xs[2] + len(xs)\n}\nvar kept = [1, 2]\nappend(kept, 3)\nprint(total(), len(kept))\n";
+        let compile = |optimize: bool| {
+            let config = CompilerConfig { optimize, ..CompilerConfig::default().with_hardware_dsl(false) };
+            EarthangCompiler::new(config).compile_source(source, None).unwrap().assembly
+        };
+        let (optimized, plain) = (compile(true), compile(false));
+        let function = |assembly: &str, label: &str| {
+            let start = assembly.find(&format!("\n{}:", label)).unwrap();
+            assembly[start..].split("ret\n").next().unwrap().to_string()
+        };
+        assert!(!function(&optimized, "fn_total").contains("call list_create_64"));
+        assert!(function(&plain, "fn_total").contains("call list_create_64"));
+        // `kept` is appended to, so it stays on the heap
+        assert!(function(&optimized, "main").contains("call list_create_64"));
+    }
+}
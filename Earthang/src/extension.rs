@@ -11,9 +11,11 @@
     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
     GNU General Public License for more details.
 */
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use crate::backend::{Target, Capability};
-use crate::parser::Expr;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::parser::{CompareOp, Expr, FStringPart, Program, Span, Statement};
 
 /// Trait for earthang language extension modules
 pub trait EarthngModule {
@@ -42,6 +44,107 @@ pub trait EarthngModule {
     fn supports_function(&self, func: &str) -> bool {
         self.functions().contains(&func)
     }
+    
+    /// Names of other modules this module relies on
+    fn dependencies(&self) -> Vec<&str> {
+        Vec::new()
+    }
+    
+    /// Environment features the module's code needs from the backend
+    fn required_capabilities(&self) -> Vec<Capability> {
+        Vec::new()
+    }
+    
+    /// Support routines appended to the output when the module is used
+    fn library_code(&self, _target: &Target) -> Option<String> {
+        None
+    }
+    
+    /// Whether the module works on `target`: by default, whether it has library
+    /// code for it
+    fn supports_target(&self, target: &Target) -> bool {
+        self.library_code(target).is_some()
+    }
+    
+    /// Whether calls to `func` work on `target`; stubs say no so they fail to
+    /// compile instead of linking against a symbol nobody defines
+    fn is_implemented(&self, _func: &str, _target: &Target) -> bool {
+        true
+    }
+    
+    /// Declared parameters and results of the module's functions; calls to a
+    /// function without one are not checked
+    fn signatures(&self) -> Vec<FunctionSignature> {
+        Vec::new()
+    }
+    
+    fn signature(&self, func: &str) -> Option<FunctionSignature> {
+        self.signatures().into_iter().find(|signature| signature.name == func)
+    }
+}
+
+/// Parameters and result of a module function, written like
+/// `list_set_64(list: list, index: int, value: int) -> int`. A `?` after a
+/// parameter name makes it and the ones after it optional
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionSignature {
+    pub name: String,
+    pub parameters: Vec<(String, String)>, // (name, type)
+    /// How many trailing parameters a call may leave out
+    pub optional: usize,
+    pub return_type: String,
+}
+
+impl FunctionSignature {
+    pub fn parse(declaration: &str) -> Result<Self, String> {
+        let malformed = || format!("Malformed signature '{}'", declaration);
+        let (head, return_type) = declaration.split_once("->").ok_or_else(malformed)?;
+        let (name, parameters) = head.trim().strip_suffix(')').and_then(|head| head.split_once('(')).ok_or_else(malformed)?;
+        let mut signature = Self {
+            name: name.trim().to_string(),
+            parameters: Vec::new(),
+            optional: 0,
+            return_type: return_type.trim().to_string(),
+        };
+        for parameter in parameters.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (name, type_name) = parameter.split_once(':').ok_or_else(malformed)?;
+            let (name, optional) = match name.trim().strip_suffix('?') {
+                Some(name) => (name, true),
+                None => (name.trim(), false),
+            };
+            if signature.optional > 0 && !optional {
+                return Err(format!("Required parameter '{}' follows an optional one in '{}'", name, declaration));
+            }
+            signature.optional += optional as usize;
+            signature.parameters.push((name.to_string(), type_name.trim().to_string()));
+        }
+        if [&signature.name, &signature.return_type].iter().any(|part| !is_symbol(part)) {
+            return Err(malformed());
+        }
+        Ok(signature)
+    }
+    
+    /// Whether a call with `count` arguments matches
+    pub fn accepts(&self, count: usize) -> bool {
+        (self.parameters.len() - self.optional..=self.parameters.len()).contains(&count)
+    }
+}
+
+impl fmt::Display for FunctionSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let required = self.parameters.len() - self.optional;
+        let parameters: Vec<String> = self.parameters.iter().enumerate()
+            .map(|(index, (name, type_name))| format!("{}{}: {}", name, if index < required { "" } else { "?" }, type_name))
+            .collect();
+        write!(f, "{}({}) -> {}", self.name, parameters.join(", "), self.return_type)
+    }
+}
+
+/// Signatures from declarations a module ships with
+fn declare(declarations: &[&str]) -> Vec<FunctionSignature> {
+    declarations.iter()
+        .map(|declaration| FunctionSignature::parse(declaration).expect("module signatures are well-formed"))
+        .collect()
 }
 
 /// Trait for emitting assembly code from modules
@@ -98,10 +201,20 @@ impl AssemblyEmitter for BasicAssemblyEmitter {
     }
 }
 
+/// Labels the modules that ship with the compiler define
+pub fn builtin_symbols() -> &'static HashSet<String> {
+    static SYMBOLS: std::sync::OnceLock<HashSet<String>> = std::sync::OnceLock::new();
+    SYMBOLS.get_or_init(|| ExtensionRegistry::with_builtin_modules().defined_symbols())
+}
+
 /// Extension Registry for dynamic module loading
 pub struct ExtensionRegistry {
     modules: Vec<Box<dyn EarthngModule>>,
     loaded_modules: HashMap<String, Box<dyn EarthngModule>>,
+    /// Modules defining each label, in registration order
+    definitions: HashMap<String, Vec<String>>,
+    /// Names each module's code uses without defining them
+    references: HashMap<String, HashSet<String>>,
 }
 
 impl ExtensionRegistry {
@@ -109,12 +222,40 @@ impl ExtensionRegistry {
         Self {
             modules: Vec::new(),
             loaded_modules: HashMap::new(),
+            definitions: HashMap::new(),
+            references: HashMap::new(),
         }
     }
     
+    /// Registry holding every module that ships with the compiler
+    pub fn with_builtin_modules() -> Self {
+        let mut registry = Self::new();
+        registry.register_module(Box::new(MathModule::new()));
+        registry.register_module(Box::new(StringModule::new()));
+        registry.register_module(Box::new(SystemModule::new()));
+        registry.register_module(Box::new(ListModule::new()));
+        registry.register_module(Box::new(DictModule::new()));
+        registry.register_module(Box::new(SerialModule::new()));
+        registry.register_module(Box::new(KeyboardModule::new()));
+        registry.register_module(Box::new(DiskModule::new()));
+        registry.register_module(Box::new(InterruptsModule::new()));
+        registry.register_module(Box::new(OsModule::new()));
+        registry
+    }
+    
     /// Register a new module
     pub fn register_module(&mut self, module: Box<dyn EarthngModule>) {
         let name = module.name().to_string();
+        let code: String = Target::ALL.iter().filter_map(|target| module.library_code(target)).collect();
+        let defined = label_definitions(&code);
+        for symbol in &defined {
+            let providers = self.definitions.entry(symbol.clone()).or_default();
+            if !providers.contains(&name) {
+                providers.push(name.clone());
+            }
+        }
+        let used = referenced_names(&code).into_iter().filter(|used| !defined.contains(used)).collect();
+        self.references.insert(name.clone(), used);
         self.modules.push(module);
         // Load the first instance
         if !self.loaded_modules.contains_key(&name) {
@@ -122,6 +263,17 @@ impl ExtensionRegistry {
         }
     }
     
+    /// Register the module stored in a module library file, returning its name
+    pub fn register_from_library(&mut self, path: &std::path::Path) -> Result<String, String> {
+        let library = crate::module_library::ModuleLibrary::load(path)?;
+        let name = library.name.clone();
+        if self.find_module(&name).is_some() {
+            return Err(format!("{} defines the {} module, which is already registered", path.display(), name));
+        }
+        self.register_module(Box::new(library));
+        Ok(name)
+    }
+    
     /// Find a module that supports a function
     pub fn find_module_for_function(&self, func: &str) -> Option<&dyn EarthngModule> {
         for module in &self.modules {
@@ -136,6 +288,389 @@ impl ExtensionRegistry {
     pub fn has_function(&self, func: &str) -> bool {
         self.find_module_for_function(func).is_some()
     }
+    
+    /// Find a module by name
+    pub fn find_module(&self, name: &str) -> Option<&dyn EarthngModule> {
+        self.modules.iter()
+            .find(|module| module.name() == name)
+            .map(|module| module.as_ref())
+    }
+    
+    /// Every label the registered modules' code defines
+    pub fn defined_symbols(&self) -> HashSet<String> {
+        self.definitions.keys().cloned().collect()
+    }
+    
+    /// The module whose library code defines `symbol`; the first registered one
+    /// when several do
+    pub fn who_defines(&self, symbol: &str) -> Option<&dyn EarthngModule> {
+        self.definitions.get(symbol)
+            .and_then(|providers| providers.first())
+            .and_then(|name| self.find_module(name))
+    }
+    
+    /// Collect the modules a program needs, including their dependencies and
+    /// the providers of symbols their code uses.
+    /// Explicit `import` statements are honored alongside call-site inference;
+    /// `from m import a, b` must name functions `m` defines. A module links
+    /// as a whole, so importing some of its functions links all of them
+    pub fn extract_required_modules(&self, program: &Program) -> Result<Vec<String>, String> {
+        let calls = program_calls(program);
+        
+        let mut pending: Vec<String> = Vec::new();
+        for stmt in &program.body {
+            if let Statement::Import { module, items, span } = stmt {
+                let Some(found) = self.find_module(module) else {
+                    return Err(format!("Unknown module '{}' imported at {}", module, span));
+                };
+                let functions = found.functions();
+                if let Some(item) = items.iter().flatten().find(|item| !functions.contains(&item.as_str())) {
+                    return Err(format!("Module '{}' has no function '{}' imported at {}", module, item, span));
+                }
+                pending.push(module.clone());
+            }
+        }
+        
+        for module in &self.modules {
+            if module.functions().iter().any(|func| calls.contains(*func)) {
+                pending.push(module.name().to_string());
+            }
+        }
+        self.with_dependencies(pending)
+    }
+    
+    /// `modules` and the modules defining what `assembly` calls without
+    /// defining it, with their dependencies. The backend picks some routines
+    /// by type, such as whether a subscript reads a string or a list, which
+    /// the calls written in the program do not show
+    pub fn add_referenced_modules(&self, modules: &[String], assembly: &str) -> Result<Vec<String>, String> {
+        let defined = label_definitions(assembly);
+        let mut pending = modules.to_vec();
+        for symbol in referenced_names(assembly) {
+            if symbol.starts_with('.') || defined.contains(&symbol) {
+                continue;
+            }
+            if let Some(provider) = self.who_defines(&symbol) {
+                pending.push(provider.name().to_string());
+            }
+        }
+        self.with_dependencies(pending)
+    }
+    
+    /// `pending` with every module they depend on or use symbols of, sorted
+    fn with_dependencies(&self, mut pending: Vec<String>) -> Result<Vec<String>, String> {
+        let mut required: Vec<String> = Vec::new();
+        while let Some(name) = pending.pop() {
+            if required.contains(&name) {
+                continue;
+            }
+            if let Some(module) = self.find_module(&name) {
+                pending.extend(module.dependencies().iter().map(|dep| dep.to_string()));
+            }
+            for symbol in self.references.get(&name).into_iter().flatten() {
+                if let Some(provider) = self.who_defines(symbol) {
+                    pending.push(provider.name().to_string());
+                }
+            }
+            required.push(name);
+        }
+        
+        required.sort();
+        self.check_duplicate_symbols(&required)?;
+        Ok(required)
+    }
+    
+    /// Reject module sets where two modules define the same label, which the
+    /// assembler would only report as a redefinition
+    fn check_duplicate_symbols(&self, modules: &[String]) -> Result<(), String> {
+        let mut symbols: Vec<(&String, &Vec<String>)> = self.definitions.iter().collect();
+        symbols.sort();
+        for (symbol, providers) in symbols {
+            let linked: Vec<&String> = providers.iter().filter(|name| modules.contains(name)).collect();
+            if let [first, second, ..] = linked.as_slice() {
+                return Err(format!("Symbol '{}' is defined by both the {} and {} modules", symbol, first, second));
+            }
+        }
+        Ok(())
+    }
+    
+    /// Reject calls to module functions that have no implementation for `target`;
+    /// the program's own functions shadow module ones
+    pub fn check_implemented(&self, program: &Program, target: &Target) -> Result<(), String> {
+        let calls = program_calls(program);
+        let defined = defined_functions(program);
+        for module in &self.modules {
+            for func in module.functions() {
+                if calls.contains(func) && !defined.contains(func) && !module.is_implemented(func, target) {
+                    return Err(format!("{}() from the {} module is not implemented for {}", func, module.name(), target));
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    /// Reject calls to module functions with an argument count their declared
+    /// signature does not allow; the program's own functions shadow module ones
+    pub fn check_signatures(&self, program: &Program) -> Result<(), String> {
+        let defined = defined_functions(program);
+        for (func, count, span) in written_calls(program) {
+            if defined.contains(func.as_str()) {
+                continue;
+            }
+            let Some(signature) = self.find_module_for_function(&func).and_then(|module| module.signature(&func)) else { continue };
+            if !signature.accepts(count) {
+                let (required, total) = (signature.parameters.len() - signature.optional, signature.parameters.len());
+                let expected = if required == total { total.to_string() } else { format!("{} to {}", required, total) };
+                return Err(format!(
+                    "{}() takes {} argument{} but {} were given at {}; it is declared as {}",
+                    func, expected, if total == 1 { "" } else { "s" }, count, span, signature
+                ));
+            }
+        }
+        Ok(())
+    }
+    
+    /// Reject modules in `modules` that have nothing for `target`, naming the
+    /// targets they do support
+    pub fn check_targets(&self, modules: &[String], target: &Target) -> Result<(), String> {
+        for module in modules.iter().filter_map(|name| self.find_module(name)) {
+            if !module.supports_target(target) {
+                let supported: Vec<String> = Target::ALL.iter()
+                    .filter(|other| module.supports_target(other))
+                    .map(|other| other.to_string())
+                    .collect();
+                let supported = if supported.is_empty() { "no target".to_string() } else { supported.join(", ") };
+                return Err(format!("The {} module has no code for {}; it supports {}", module.name(), target, supported));
+            }
+        }
+        Ok(())
+    }
+    
+    /// Every registered module, in registration order
+    pub fn modules(&self) -> impl Iterator<Item = &dyn EarthngModule> {
+        self.modules.iter().map(|module| module.as_ref())
+    }
+    
+    /// Concatenate the library code of the given modules
+    /// Capabilities required by any of `modules`
+    pub fn required_capabilities(&self, modules: &[String]) -> Vec<Capability> {
+        let mut capabilities = Vec::new();
+        for cap in modules.iter().filter_map(|name| self.find_module(name)).flat_map(|module| module.required_capabilities()) {
+            if !capabilities.contains(&cap) {
+                capabilities.push(cap);
+            }
+        }
+        capabilities
+    }
+    
+    pub fn library_code(&self, modules: &[String], target: &Target) -> String {
+        let mut asm = String::new();
+        for name in modules {
+            if let Some(code) = self.find_module(name).and_then(|module| module.library_code(target)) {
+                asm.push_str(&code);
+            }
+        }
+        asm
+    }
+}
+
+/// Labels defined at the start of a line of GNU assembly, numeric local labels aside
+fn label_definitions(asm: &str) -> HashSet<String> {
+    asm.lines()
+        .filter(|line| !line.starts_with(char::is_whitespace))
+        .filter_map(|line| line.split_once(':').map(|(label, _)| label))
+        .filter(|label| is_symbol(label) && !label.chars().all(|c| c.is_ascii_digit()))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Identifiers in the instructions and directives of `asm`, leaving out
+/// comments and string literals
+fn referenced_names(asm: &str) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for line in asm.lines() {
+        let code = line.split(['#', '"']).next().unwrap_or("");
+        let code = match code.split_once(':') {
+            Some((label, rest)) if is_symbol(label) => rest,
+            _ => code,
+        };
+        names.extend(code.split(|c: char| !is_symbol_char(c))
+            .filter(|word| is_symbol(word))
+            .map(str::to_string));
+    }
+    names
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+fn is_symbol(word: &str) -> bool {
+    !word.is_empty() && word.chars().all(is_symbol_char)
+}
+
+/// Names of the functions `program` defines itself, struct constructors included
+fn defined_functions(program: &Program) -> HashSet<&str> {
+    program.body.iter()
+        .filter_map(|stmt| match stmt {
+            Statement::FunctionDef { name, .. } | Statement::StructDef { name, .. } => Some(name.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Every function name called anywhere in `program`, with the module helpers
+/// that subscripts and membership tests lower to
+pub(crate) fn program_calls(program: &Program) -> HashSet<String> {
+    let mut calls = CallCollector::default();
+    for stmt in &program.body {
+        collect_statement_calls(stmt, &mut calls);
+    }
+    calls.names
+}
+
+/// The calls spelled out in `program`: function, argument count and span
+pub(crate) fn written_calls(program: &Program) -> Vec<(String, usize, Span)> {
+    let mut calls = CallCollector::default();
+    for stmt in &program.body {
+        collect_statement_calls(stmt, &mut calls);
+    }
+    calls.written
+}
+
+#[derive(Default)]
+struct CallCollector {
+    names: HashSet<String>,
+    written: Vec<(String, usize, Span)>,
+}
+
+impl CallCollector {
+    fn insert(&mut self, name: String) {
+        self.names.insert(name);
+    }
+}
+
+fn collect_statement_calls(stmt: &Statement, calls: &mut CallCollector) {
+    match stmt {
+        Statement::Expr(expr) => collect_expression_calls(expr, calls),
+        Statement::VarDecl { value, .. }
+        | Statement::Assign { value, .. }
+        | Statement::AugAssign { value, .. } => collect_expression_calls(value, calls),
+        Statement::IndexAssign { target, index, value, .. } => {
+            // Stores through a subscript are lowered to a list or dict module call
+            let setter = if matches!(index.as_ref(), Expr::String(..)) { "dict_set_64" } else { "list_set_64" };
+            calls.insert(setter.to_string());
+            [target.as_ref(), index.as_ref(), value].into_iter().for_each(|e| collect_expression_calls(e, calls));
+        }
+        Statement::Return(Some(expr), _) => collect_expression_calls(expr, calls),
+        Statement::If { condition, then_block, elif_blocks, else_block, .. } => {
+            collect_expression_calls(condition, calls);
+            then_block.iter().for_each(|s| collect_statement_calls(s, calls));
+            for (cond, block) in elif_blocks {
+                collect_expression_calls(cond, calls);
+                block.iter().for_each(|s| collect_statement_calls(s, calls));
+            }
+            if let Some(block) = else_block {
+                block.iter().for_each(|s| collect_statement_calls(s, calls));
+            }
+        }
+        Statement::While { condition, body, orelse, .. } => {
+            collect_expression_calls(condition, calls);
+            body.iter().for_each(|s| collect_statement_calls(s, calls));
+            if let Some(block) = orelse {
+                block.iter().for_each(|s| collect_statement_calls(s, calls));
+            }
+        }
+        Statement::For { iter, body, .. } => {
+            // A step only known at runtime is checked against zero by the system module
+            if let Expr::Call { args, .. } = iter {
+                if args.len() == 3 && crate::backend::literal_integer(&args[2]).is_none() {
+                    calls.insert("runtime_error_64".to_string());
+                }
+            }
+            collect_expression_calls(iter, calls);
+            body.iter().for_each(|s| collect_statement_calls(s, calls));
+        }
+        Statement::FunctionDef { body, .. } | Statement::HardwareFunctionDef { body, .. } => {
+            body.iter().for_each(|s| collect_statement_calls(s, calls));
+        }
+        // Constructors put instances on the heap
+        Statement::StructDef { .. } => calls.insert("heap_alloc_64".to_string()),
+        Statement::FieldAssign { target, value, .. } => {
+            collect_expression_calls(target, calls);
+            collect_expression_calls(value, calls);
+        }
+        Statement::HardwareDecl { config, .. } => {
+            config.values().for_each(|e| collect_expression_calls(e, calls));
+        }
+        _ => {}
+    }
+}
+
+fn collect_expression_calls(expr: &Expr, calls: &mut CallCollector) {
+    match expr {
+        Expr::Call { func, args, kwargs, span } => {
+            calls.insert(func.clone());
+            calls.written.push((func.clone(), args.len(), *span));
+            args.iter().for_each(|e| collect_expression_calls(e, calls));
+            kwargs.values().for_each(|e| collect_expression_calls(e, calls));
+        }
+        Expr::FString { parts, .. } => {
+            for part in parts {
+                if let FStringPart::Expr(e) = part {
+                    collect_expression_calls(e, calls);
+                }
+            }
+        }
+        Expr::HardwareCall { args, .. } => {
+            args.iter().for_each(|e| collect_expression_calls(e, calls));
+        }
+        Expr::BinOp { left, right, .. } => {
+            collect_expression_calls(left, calls);
+            collect_expression_calls(right, calls);
+        }
+        Expr::UnaryOp { operand, .. } => collect_expression_calls(operand, calls),
+        Expr::BoolOp { values, .. } => values.iter().for_each(|e| collect_expression_calls(e, calls)),
+        Expr::Compare { left, ops, comparators, .. } => {
+            if ops.iter().any(|op| matches!(op, CompareOp::In | CompareOp::NotIn)) {
+                calls.insert("dict_find_index_64".to_string());
+            }
+            collect_expression_calls(left, calls);
+            comparators.iter().for_each(|e| collect_expression_calls(e, calls));
+        }
+        Expr::List { elements, .. } => {
+            calls.insert("list_create_64".to_string());
+            elements.iter().for_each(|e| collect_expression_calls(e, calls));
+        }
+        Expr::Dict { entries, .. } => {
+            calls.insert("dict_create_64".to_string());
+            for (key, value) in entries {
+                collect_expression_calls(key, calls);
+                collect_expression_calls(value, calls);
+            }
+        }
+        Expr::FieldAccess { object, .. } => collect_expression_calls(object, calls),
+        Expr::MethodCall { receiver, method, args, .. } => {
+            // Only strings have methods, each lowered to a module routine
+            if let Some((_, routine)) = string_method(method) {
+                calls.insert(routine.to_string());
+            }
+            collect_expression_calls(receiver, calls);
+            args.iter().for_each(|e| collect_expression_calls(e, calls));
+        }
+        Expr::Slice { value, start, stop, .. } => {
+            collect_expression_calls(value, calls);
+            [start, stop].into_iter().flatten().for_each(|bound| collect_expression_calls(bound, calls));
+        }
+        Expr::Index { value, index, .. } => {
+            // A string subscript can only index a dictionary
+            let accessor = if matches!(index.as_ref(), Expr::String(..)) { "dict_get_64" } else { "list_get_64" };
+            calls.insert(accessor.to_string());
+            collect_expression_calls(value, calls);
+            collect_expression_calls(index, calls);
+        }
+        _ => {}
+    }
 }
 
 /// Math module for earthang
@@ -178,6 +713,20 @@ impl EarthngModule for MathModule {
         self.functions.iter().map(|s| s.as_str()).collect()
     }
     
+    fn signatures(&self) -> Vec<FunctionSignature> {
+        declare(&[
+            "sin(x: float) -> float",
+            "cos(x: float) -> float",
+            "tan(x: float) -> float",
+            "sqrt(x: float) -> float",
+            "pow(base: float, exponent: float) -> float",
+            "abs(x: float) -> float",
+            "floor(x: float) -> int",
+            "ceil(x: float) -> int",
+            "round(x: float) -> int",
+        ])
+    }
+    
     fn compile_function(
         &self,
         func: &str,
@@ -243,6 +792,14 @@ impl StringModule {
                 "to_upper".to_string(),
                 "to_lower".to_string(),
                 "trim".to_string(),
+                "str_length_64".to_string(),
+                "str_index_64".to_string(),
+                "str_slice_64".to_string(),
+                "str_find_64".to_string(),
+                "str_replace_64".to_string(),
+                "str_upper_64".to_string(),
+                "str_lower_64".to_string(),
+                "str_strip_64".to_string(),
             ],
         }
     }
@@ -261,6 +818,39 @@ impl EarthngModule for StringModule {
         self.functions.iter().map(|s| s.as_str()).collect()
     }
     
+    fn signatures(&self) -> Vec<FunctionSignature> {
+        declare(&[
+            "length(s: str) -> int",
+            "concat(a: str, b: str) -> str",
+            "substr(s: str, start: int, length: int) -> str",
+            "find(s: str, needle: str) -> int",
+            "replace(s: str, old: str, new: str) -> str",
+            "to_upper(s: str) -> str",
+            "to_lower(s: str) -> str",
+            "trim(s: str) -> str",
+        ])
+    }
+    
+    fn dependencies(&self) -> Vec<&str> {
+        // String routines allocate their results through the system module
+        vec!["system"]
+    }
+    
+    fn required_capabilities(&self) -> Vec<Capability> {
+        vec![Capability::Heap]
+    }
+    
+    fn library_code(&self, target: &Target) -> Option<String> {
+        match target {
+            Target::Linux64 => Some(format!("{}{}", STRING_LIBRARY_X86_64, STRING_INDEX_LIBRARY_LINUX64)),
+            _ => None,
+        }
+    }
+    
+    fn is_implemented(&self, func: &str, target: &Target) -> bool {
+        *target == Target::Linux64 && matches!(func, "replace" | "str_length_64" | "str_index_64" | "str_slice_64" | "str_find_64" | "str_replace_64" | "str_upper_64" | "str_lower_64" | "str_strip_64")
+    }
+    
     fn compile_function(
         &self,
         func: &str,
@@ -295,6 +885,394 @@ impl EarthngModule for StringModule {
     }
 }
 
+/// Routines that only need heap_alloc_64, so --bios-mode payloads link them too
+pub const STRING_LIBRARY_X86_64: &str = "    .section .text
+str_hash_64:
+    # Input: rdi = string; output: rax = djb2 hash of its bytes
+    push rcx
+    push rdi
+    mov rax, 5381
+.str_hash_loop:
+    movzx ecx, BYTE PTR [rdi]
+    test ecx, ecx
+    jz .str_hash_done
+    imul rax, rax, 33
+    add rax, rcx
+    inc rdi
+    jmp .str_hash_loop
+.str_hash_done:
+    pop rdi
+    pop rcx
+    ret
+
+str_equal_64:
+    # Input: rdi, rsi = strings; output: rax = 1 when their contents match
+    push rcx
+    push rdx
+    push rdi
+    push rsi
+.str_equal_loop:
+    movzx ecx, BYTE PTR [rdi]
+    movzx edx, BYTE PTR [rsi]
+    cmp ecx, edx
+    jne .str_equal_differs
+    test ecx, ecx
+    jz .str_equal_same
+    inc rdi
+    inc rsi
+    jmp .str_equal_loop
+.str_equal_same:
+    mov rax, 1
+    jmp .str_equal_done
+.str_equal_differs:
+    xor eax, eax
+.str_equal_done:
+    pop rsi
+    pop rdi
+    pop rdx
+    pop rcx
+    ret
+
+str_length_64:
+    # Input: rdi = string; output: rax = number of bytes before its NUL. len(),
+    # subscripts and slices all count bytes, so a character outside ASCII
+    # takes several positions
+    xor eax, eax
+.str_length_loop:
+    cmp BYTE PTR [rdi + rax], 0
+    je .str_length_done
+    inc rax
+    jmp .str_length_loop
+.str_length_done:
+    ret
+
+str_slice_64:
+    # Input: rdi = string, rsi = start, rdx = stop, each counted from the end
+    # when negative and clamped to the string; output: rax = fresh copy of the
+    # bytes from start up to stop, empty when stop is not after start
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    call str_length_64
+    mov rcx, rax
+    mov rax, rsi
+    call .str_clamp
+    mov rsi, rax
+    mov rax, rdx
+    call .str_clamp
+    sub rax, rsi
+    jg .str_slice_copy
+    xor eax, eax
+.str_slice_copy:
+    push rsi
+    push rax
+    lea rdi, [rax + 1]
+    call heap_alloc_64
+    pop rcx
+    pop rsi
+    add rsi, QWORD PTR [rsp]    # the string
+    mov rdi, rax
+    rep movsb
+    mov BYTE PTR [rdi], 0
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    ret
+
+.str_clamp:
+    # Input: rax = bound, rcx = length; output: rax = the bound counted from the
+    # end when negative, clamped to 0..length
+    test rax, rax
+    jns .str_clamp_upper
+    add rax, rcx
+    jns .str_clamp_upper
+    xor eax, eax
+.str_clamp_upper:
+    cmp rax, rcx
+    jle .str_clamp_done
+    mov rax, rcx
+.str_clamp_done:
+    ret
+
+.str_prefix:
+    # Input: rdi = position in a string, rsi = pattern; output: ZF set when the
+    # pattern starts there
+    push rax
+    push rcx
+    xor ecx, ecx
+.str_prefix_loop:
+    movzx eax, BYTE PTR [rsi + rcx]
+    test eax, eax
+    jz .str_prefix_done
+    cmp al, BYTE PTR [rdi + rcx]
+    jne .str_prefix_done
+    inc rcx
+    jmp .str_prefix_loop
+.str_prefix_done:
+    pop rcx
+    pop rax
+    ret
+
+str_replace_64:
+    # Input: rdi = string, rsi = old, rdx = new; output: rax = fresh copy of the
+    # string with every occurrence of old replaced, scanning left to right
+    push rbx
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    push r8
+    push r9
+    push r10
+    push r12
+    mov r8, rdi
+    mov r9, rsi
+    mov r10, rdx
+    mov rdi, r9
+    call str_length_64
+    mov rbx, rax
+    mov rdi, r10
+    call str_length_64
+    mov r12, rax
+    xor ecx, ecx                # length of the result
+    mov rdi, r8
+    mov rsi, r9
+.str_replace_measure:
+    cmp BYTE PTR [rdi], 0
+    je .str_replace_alloc
+    test rbx, rbx               # an empty old string matches nowhere
+    jz .str_replace_measure_byte
+    call .str_prefix
+    jne .str_replace_measure_byte
+    add rcx, r12
+    add rdi, rbx
+    jmp .str_replace_measure
+.str_replace_measure_byte:
+    inc rcx
+    inc rdi
+    jmp .str_replace_measure
+.str_replace_alloc:
+    lea rdi, [rcx + 1]
+    call heap_alloc_64
+    push rax
+    mov rdx, rax
+    mov rdi, r8
+.str_replace_copy:
+    movzx ecx, BYTE PTR [rdi]
+    test ecx, ecx
+    jz .str_replace_done
+    test rbx, rbx
+    jz .str_replace_copy_byte
+    call .str_prefix
+    jne .str_replace_copy_byte
+    push rsi
+    push rdi
+    mov rsi, r10
+    mov rdi, rdx
+    mov rcx, r12
+    rep movsb
+    mov rdx, rdi
+    pop rdi
+    pop rsi
+    add rdi, rbx
+    jmp .str_replace_copy
+.str_replace_copy_byte:
+    mov BYTE PTR [rdx], cl
+    inc rdx
+    inc rdi
+    jmp .str_replace_copy
+.str_replace_done:
+    mov BYTE PTR [rdx], 0
+    pop rax
+    pop r12
+    pop r10
+    pop r9
+    pop r8
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    pop rbx
+    ret
+
+str_find_64:
+    # Input: rdi = string, rsi = needle; output: rax = byte position of the
+    # needle's first occurrence, -1 when it does not occur
+    push rdi
+.str_find_loop:
+    call .str_prefix
+    je .str_find_found
+    cmp BYTE PTR [rdi], 0
+    je .str_find_missing
+    inc rdi
+    jmp .str_find_loop
+.str_find_found:
+    mov rax, rdi
+    sub rax, QWORD PTR [rsp]
+    pop rdi
+    ret
+.str_find_missing:
+    mov rax, -1
+    pop rdi
+    ret
+
+str_upper_64:
+    # Input: rdi = string; output: rax = fresh copy with a-z in uppercase
+    push rdx
+    mov edx, 0x7A61             # dl = 'a', dh = 'z'
+    jmp .str_case
+str_lower_64:
+    # Input: rdi = string; output: rax = fresh copy with A-Z in lowercase
+    push rdx
+    mov edx, 0x5A41             # dl = 'A', dh = 'Z'
+.str_case:
+    # Copies the string with the case of the letters from dl to dh flipped;
+    # other bytes, those of characters outside ASCII included, stay as they are
+    push rcx
+    push rsi
+    push rdi
+    call str_length_64
+    lea rdi, [rax + 1]
+    call heap_alloc_64
+    mov rsi, QWORD PTR [rsp]    # the string
+    mov rdi, rax
+.str_case_loop:
+    movzx ecx, BYTE PTR [rsi]
+    cmp cl, dl
+    jb .str_case_store
+    cmp cl, dh
+    ja .str_case_store
+    xor cl, 0x20
+.str_case_store:
+    mov BYTE PTR [rdi], cl
+    inc rsi
+    inc rdi
+    test ecx, ecx
+    jnz .str_case_loop
+    pop rdi
+    pop rsi
+    pop rcx
+    pop rdx
+    ret
+
+str_strip_64:
+    # Input: rdi = string; output: rax = fresh copy without the ASCII
+    # whitespace at either end
+    push rcx
+    push rsi
+    push rdi
+    call str_length_64
+    lea rsi, [rdi + rax]        # one past the last byte
+.str_strip_start:
+    cmp rdi, rsi
+    je .str_strip_copy
+    movzx ecx, BYTE PTR [rdi]
+    call .str_space
+    jnc .str_strip_end
+    inc rdi
+    jmp .str_strip_start
+.str_strip_end:
+    movzx ecx, BYTE PTR [rsi - 1]   # rdi is not whitespace, so this stops there
+    call .str_space
+    jnc .str_strip_copy
+    dec rsi
+    jmp .str_strip_end
+.str_strip_copy:
+    push rdx
+    mov rdx, rsi
+    mov rsi, rdi
+    mov rdi, QWORD PTR [rsp + 8]    # the string
+    sub rsi, rdi
+    sub rdx, rdi
+    call str_slice_64
+    pop rdx
+    pop rdi
+    pop rsi
+    pop rcx
+    ret
+
+.str_space:
+    # Input: ecx = byte; output: CF set for a space, tab, line feed, vertical
+    # tab, form feed or carriage return
+    cmp ecx, 32
+    je .str_space_found
+    push rcx
+    sub ecx, 9
+    cmp ecx, 5
+    pop rcx
+    ret
+.str_space_found:
+    stc
+    ret
+";
+
+/// str_index_64 stops the program on a bad index, which needs the system
+/// module's runtime_error_64
+const STRING_INDEX_LIBRARY_LINUX64: &str = "    .section .text
+str_index_64:
+    # Input: rdi = string, rsi = index, negative counting from the end;
+    # output: rax = fresh one-byte string holding the byte there
+    push rcx
+    push rsi
+    push rdi
+    call str_length_64
+    test rsi, rsi
+    jns .str_index_check
+    add rsi, rax
+.str_index_check:
+    cmp rsi, rax
+    jae .str_index_error        # unsigned, so what is still negative is rejected too
+    push rsi
+    mov edi, 2
+    call heap_alloc_64
+    pop rsi
+    mov rdi, QWORD PTR [rsp]
+    movzx ecx, BYTE PTR [rdi + rsi]
+    mov BYTE PTR [rax], cl
+    mov BYTE PTR [rax + 1], 0
+    pop rdi
+    pop rsi
+    pop rcx
+    ret
+.str_index_error:
+    lea rdi, [str_index_error_message]
+    jmp runtime_error_64
+
+    .section .data
+str_index_error_message:
+    .asciz \"string index out of range\"
+";
+
+/// String builtins of the Linux backend; split() builds a list, so its routine
+/// comes with the list module
+pub const STRING_BUILTINS: [&str; 2] = ["replace", "split"];
+
+/// Methods strings have: the number of arguments each takes and the routine
+/// it lowers to, called with the string first
+pub const STRING_METHODS: [(&str, usize, &str); 6] = [
+    ("find", 1, "str_find_64"),
+    ("replace", 2, "str_replace_64"),
+    ("split", 1, "str_split_64"),
+    ("upper", 0, "str_upper_64"),
+    ("lower", 0, "str_lower_64"),
+    ("strip", 0, "str_strip_64"),
+];
+
+/// Argument count and routine of the string method `name`
+pub fn string_method(name: &str) -> Option<(usize, &'static str)> {
+    STRING_METHODS.iter()
+        .find(|(method, _, _)| *method == name)
+        .map(|(_, arity, routine)| (*arity, *routine))
+}
+
+/// The error for calling `method`, which strings do not have
+pub fn unknown_string_method(method: &str, span: Span) -> String {
+    let supported: Vec<&str> = STRING_METHODS.iter().map(|(name, _, _)| *name).collect();
+    format!("Strings have no method '{}'; the supported methods are {} at {}", method, supported.join(", "), span)
+}
+
 /// System module for earthang
 pub struct SystemModule {
     name: String,
@@ -310,9 +1288,14 @@ impl SystemModule {
             functions: vec![
                 "time".to_string(),
                 "sleep".to_string(),
+                "sleep_ms_64".to_string(),
                 "exit".to_string(),
                 "getenv".to_string(),
                 "platform".to_string(),
+                "input".to_string(),
+                "io_read_line_64".to_string(),
+                "runtime_error_64".to_string(),
+                "heap_alloc_64".to_string(),
             ],
         }
     }
@@ -331,6 +1314,21 @@ impl EarthngModule for SystemModule {
         self.functions.iter().map(|s| s.as_str()).collect()
     }
     
+    fn signatures(&self) -> Vec<FunctionSignature> {
+        declare(&[
+            "time() -> int",
+            "sleep(milliseconds: int) -> void",
+            "sleep_ms_64(milliseconds: int) -> int",
+            "exit(code: int) -> void",
+            "getenv(name: str) -> str",
+            "platform() -> str",
+            "input(prompt?: str) -> str",
+            "io_read_line_64() -> str",
+            "runtime_error_64(message: str) -> void",
+            "heap_alloc_64(bytes: int) -> int",
+        ])
+    }
+    
     fn compile_function(
         &self,
         func: &str,
@@ -362,10 +1360,2453 @@ impl EarthngModule for SystemModule {
         Ok(asm)
     }
     
+    fn library_code(&self, target: &Target) -> Option<String> {
+        match target {
+            Target::Linux64 => Some(format!("{}{}", SYSTEM_LIBRARY_LINUX64, REFCOUNT_LIBRARY_X86_64)),
+            _ => None,
+        }
+    }
+    
+    fn supports_target(&self, _target: &Target) -> bool {
+        // The RISC-V and AArch64 runtimes carry sleep and runtime errors themselves
+        true
+    }
+    
+    fn is_implemented(&self, func: &str, target: &Target) -> bool {
+        *target == Target::Linux64 || matches!(func, "sleep" | "runtime_error_64")
+    }
+    
     fn init(&mut self, capabilities: &[Capability]) {
         // System module might check for specific capabilities
         if capabilities.contains(&Capability::Linux) {
             // Linux-specific initialization
         }
     }
-}
\ No newline at end of file
+}
+/// Heap growth and fatal error reporting shared by the other modules
+const SYSTEM_LIBRARY_LINUX64: &str = "    .section .text
+heap_grow_64:
+    # Input: rdi = size in bytes; output: rax = 16-byte aligned memory from the
+    # program break, for heap_alloc_64 to carve blocks from
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    push r11
+    mov rax, QWORD PTR [heap_top]
+    test rax, rax
+    jnz .heap_have_top
+    mov rax, 12                 # syscall: brk(0) reports the current break
+    xor rdi, rdi
+    syscall
+    add rax, 15
+    and rax, -16
+    mov QWORD PTR [heap_base], rax
+    mov QWORD PTR [heap_top], rax
+.heap_have_top:
+    mov rdx, QWORD PTR [rsp + 8]
+    add rdx, 15
+    and rdx, -16
+    lea rdi, [rax + rdx]
+    mov rsi, rax
+    mov rax, 12                 # syscall: brk
+    syscall
+    cmp rax, rdi
+    jb .heap_exhausted
+    mov QWORD PTR [heap_top], rdi
+    mov rax, rsi
+    pop r11
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    ret
+.heap_exhausted:
+    lea rdi, [heap_error_message]
+    jmp runtime_error_64
+
+io_read_line_64:
+    # Output: rax = next line of stdin without its newline, NUL-terminated in a
+    # fresh 256-byte block; longer lines are cut short and the rest skipped
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    push r11
+    push rbx
+    push r12
+    mov rdi, 256
+    call heap_alloc_64
+    mov rbx, rax
+    xor r12, r12
+.io_read_loop:
+    xor eax, eax                # syscall: read one byte
+    xor edi, edi
+    lea rsi, [rbx + r12]
+    mov rdx, 1
+    syscall
+    cmp rax, 1
+    jne .io_read_done           # end of input
+    cmp BYTE PTR [rbx + r12], 10
+    je .io_read_done
+    cmp r12, 255
+    je .io_read_loop
+    inc r12
+    jmp .io_read_loop
+.io_read_done:
+    mov BYTE PTR [rbx + r12], 0
+    mov rax, rbx
+    pop r12
+    pop rbx
+    pop r11
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    ret
+
+sleep_ms_64:
+    # Input: rdi = milliseconds; output: rax = 0, or -errno when nanosleep fails.
+    # The request struct timespec { tv_sec, tv_nsec } is built on the stack, with
+    # the remainder after it so a signal only resumes the rest of the sleep
+    xor eax, eax
+    test rdi, rdi
+    jle .sleep_done
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    push r11
+    mov rax, rdi
+    xor edx, edx
+    mov ecx, 1000
+    div rcx
+    imul rdx, rdx, 1000000
+    sub rsp, 32
+    mov QWORD PTR [rsp], rax        # tv_sec
+    mov QWORD PTR [rsp + 8], rdx    # tv_nsec
+.sleep_again:
+    mov rdi, rsp
+    lea rsi, [rsp + 16]             # time left when interrupted
+    mov rax, 35                     # syscall: nanosleep
+    syscall
+    cmp rax, -4                     # EINTR
+    jne .sleep_slept
+    movdqu xmm0, XMMWORD PTR [rsp + 16]
+    movdqu XMMWORD PTR [rsp], xmm0
+    jmp .sleep_again
+.sleep_slept:
+    add rsp, 32
+    pop r11
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+.sleep_done:
+    ret
+
+runtime_error_64:
+    # Input: rdi = message; reports it on stderr and exits with status 1
+    push rdi
+    mov rax, 1
+    mov rdi, 2
+    lea rsi, [runtime_error_prefix]
+    mov rdx, 15
+    syscall
+    pop rsi
+    xor rdx, rdx
+.runtime_error_length:
+    cmp BYTE PTR [rsi + rdx], 0
+    je .runtime_error_write
+    inc rdx
+    jmp .runtime_error_length
+.runtime_error_write:
+    mov rax, 1
+    mov rdi, 2
+    syscall
+    mov rax, 1
+    mov rdi, 2
+    lea rsi, [runtime_error_newline]
+    mov rdx, 1
+    syscall
+    mov rax, 60                 # syscall: exit
+    mov rdi, 1
+    syscall
+
+    .section .data
+heap_base:
+    .quad 0
+heap_top:
+    .quad 0
+runtime_error_prefix:
+    .ascii \"Runtime error: \"
+runtime_error_newline:
+    .byte 10
+heap_error_message:
+    .asciz \"out of memory\"
+";
+
+/// Size-class allocator with reference counts, on top of an environment's
+/// `heap_grow_64` and its `heap_base`/`heap_top`. Every block starts with a
+/// 16-byte header: the size class, the kind (0 raw, 1 list, 2 dictionary, 3
+/// struct), a struct's field count and the magic 0x45475243 in the first word,
+/// the count in the second. Objects start at 0 and
+/// are released when the last counted reference goes; `rc_enabled` is the
+/// backend's switch for programs compiled without reference counting
+pub const REFCOUNT_LIBRARY_X86_64: &str = "    .section .text
+heap_alloc_64:
+    # Input: rdi = size in bytes; output: rax = 16-byte aligned block, reused
+    # from the free list of its power-of-two size class when there is one
+    push rcx
+    push rdx
+    push rdi
+    lea rdi, [rdi + 15]         # header included, minus one
+    mov ecx, 5                  # 32 bytes at least
+    cmp rdi, 32
+    jb .heap_class_found
+    bsr rcx, rdi
+    inc ecx
+.heap_class_found:
+    lea rdx, [rip + heap_free_lists]
+    mov rax, QWORD PTR [rdx + rcx*8]
+    test rax, rax
+    jz .heap_carve
+    mov rdi, QWORD PTR [rax + 8]
+    mov QWORD PTR [rdx + rcx*8], rdi
+    jmp .heap_header
+.heap_carve:
+    mov edi, 1
+    shl rdi, cl
+    call heap_grow_64
+.heap_header:
+    mov DWORD PTR [rax], ecx    # size class, raw kind
+    mov DWORD PTR [rax + 4], 0x45475243     # magic
+    mov QWORD PTR [rax + 8], 0
+    add rax, 16
+    pop rdi
+    pop rdx
+    pop rcx
+    ret
+
+heap_free_64:
+    # Input: rdi = block from heap_alloc_64; puts it on its size class's free list
+    push rax
+    push rcx
+    push rdx
+    push rsi
+    lea rax, [rdi - 16]
+    movzx ecx, BYTE PTR [rax]
+    mov QWORD PTR [rax], rcx    # no magic: stale pointers are not objects any more
+    lea rdx, [rip + heap_free_lists]
+    mov rsi, QWORD PTR [rdx + rcx*8]
+    mov QWORD PTR [rax + 8], rsi
+    mov QWORD PTR [rdx + rcx*8], rax
+    pop rsi
+    pop rdx
+    pop rcx
+    pop rax
+    ret
+
+heap_realloc_64:
+    # Input: rdi = block from heap_alloc_64, rsi = size in bytes; output: rax =
+    # a block that large with the old contents. It stays put while its size
+    # class has room, otherwise the whole old block is copied and then freed
+    push rcx
+    push rsi
+    push rdi
+    movzx ecx, BYTE PTR [rdi - 16]
+    mov eax, 1
+    shl rax, cl
+    sub rax, 16                 # room the old block has
+    cmp rsi, rax
+    jbe .heap_realloc_fits
+    mov rcx, rax
+    shr rcx, 3
+    mov rdi, rsi
+    call heap_alloc_64
+    mov rsi, QWORD PTR [rsp]
+    mov rdi, rax
+    rep movsq
+    mov rdi, QWORD PTR [rsp]
+    call heap_free_64
+    pop rdi
+    pop rsi
+    pop rcx
+    ret
+.heap_realloc_fits:
+    mov rax, rdi
+    pop rdi
+    pop rsi
+    pop rcx
+    ret
+
+.rc_header:
+    # Input: rdi = value; output: rax = its header when it is a live heap
+    # block, otherwise 0 (integers, literals and freed blocks)
+    lea rax, [rdi - 16]
+    cmp rax, QWORD PTR [rip + heap_base]
+    jb .rc_not_object
+    cmp rax, QWORD PTR [rip + heap_top]
+    jae .rc_not_object
+    test al, 15
+    jnz .rc_not_object
+    cmp DWORD PTR [rax + 4], 0x45475243
+    jne .rc_not_object
+    ret
+.rc_not_object:
+    xor eax, eax
+    ret
+
+__rc_inc:
+    # Input: rdi = value; counts one more reference to it if it is an object
+    push rax
+    call .rc_header
+    test rax, rax
+    jz .rc_inc_done
+    inc QWORD PTR [rax + 8]
+.rc_inc_done:
+    pop rax
+    ret
+
+__rc_dec:
+    # Input: rdi = value; drops a reference and releases the object with the last one
+    cmp BYTE PTR [rip + rc_enabled], 0
+    je .rc_dec_disabled
+    push rax
+    call .rc_header
+    test rax, rax
+    jz .rc_dec_done
+    cmp QWORD PTR [rax + 8], 0  # never counted, still owned by whoever made it
+    je .rc_dec_done
+    dec QWORD PTR [rax + 8]
+    jnz .rc_dec_done
+    call .rc_release
+.rc_dec_done:
+    pop rax
+.rc_dec_disabled:
+    ret
+
+__rc_disown:
+    # Input: rdi = value; drops a reference without releasing the object, so a
+    # function can hand its result back uncounted
+    push rax
+    call .rc_header
+    test rax, rax
+    jz .rc_disown_done
+    cmp QWORD PTR [rax + 8], 0
+    je .rc_disown_done
+    dec QWORD PTR [rax + 8]
+.rc_disown_done:
+    pop rax
+    ret
+
+.rc_release:
+    # Input: rdi = object, rax = its header; drops what a list, dictionary or
+    # struct holds, frees its buffers and then the object itself
+    push rcx
+    push rsi
+    push rdi
+    mov rsi, rdi
+    movzx ecx, BYTE PTR [rax + 1]
+    cmp ecx, 1
+    je .rc_release_list
+    cmp ecx, 2
+    je .rc_release_dict
+    cmp ecx, 3
+    je .rc_release_struct
+    jmp .rc_release_free
+.rc_release_list:
+    mov rcx, QWORD PTR [rsi]
+.rc_release_elements:
+    test rcx, rcx
+    jz .rc_release_list_data
+    dec rcx
+    mov rdi, QWORD PTR [rsi + 16]
+    mov rdi, QWORD PTR [rdi + rcx*8]
+    call __rc_dec
+    jmp .rc_release_elements
+.rc_release_list_data:
+    mov rdi, QWORD PTR [rsi + 16]
+    call heap_free_64
+    jmp .rc_release_free
+.rc_release_dict:
+    mov rcx, QWORD PTR [rsi + 8]
+.rc_release_slots:
+    test rcx, rcx
+    jz .rc_release_tables
+    dec rcx
+    mov rdi, QWORD PTR [rsi + 16]
+    mov rdi, QWORD PTR [rdi + rcx*8]
+    test rdi, rdi
+    jz .rc_release_slots
+    call __rc_dec
+    mov rdi, QWORD PTR [rsi + 24]
+    mov rdi, QWORD PTR [rdi + rcx*8]
+    call __rc_dec
+    jmp .rc_release_slots
+.rc_release_tables:
+    mov rdi, QWORD PTR [rsi + 16]
+    call heap_free_64
+    mov rdi, QWORD PTR [rsi + 24]
+    call heap_free_64
+    jmp .rc_release_free
+.rc_release_struct:
+    movzx ecx, WORD PTR [rax + 2]
+.rc_release_fields:
+    test rcx, rcx
+    jz .rc_release_free
+    dec rcx
+    mov rdi, QWORD PTR [rsi + rcx*8]
+    call __rc_dec
+    jmp .rc_release_fields
+.rc_release_free:
+    mov rdi, rsi
+    call heap_free_64
+    pop rdi
+    pop rsi
+    pop rcx
+    ret
+
+    .section .data
+heap_free_lists:
+    .zero 512                   # one list head per size class
+";
+
+/// Calls handing out the heap objects reference counting tracks, list and
+/// dictionary literals and struct constructors included
+pub const ALLOCATING_BUILTINS: [&str; 5] = ["input", "read", "list_create_64", "dict_create_64", "heap_alloc_64"];
+
+/// File descriptor I/O through Linux syscalls
+pub struct OsModule {
+    name: String,
+    description: String,
+    functions: Vec<String>,
+}
+
+impl OsModule {
+    pub fn new() -> Self {
+        Self {
+            name: "os".to_string(),
+            description: "open/read/write/close on Linux file descriptors".to_string(),
+            functions: vec![
+                "open".to_string(),
+                "read".to_string(),
+                "write".to_string(),
+                "close".to_string(),
+                "sys_open_64".to_string(),
+                "sys_read_64".to_string(),
+                "sys_write_64".to_string(),
+                "sys_close_64".to_string(),
+                "os_read_string_64".to_string(),
+                "os_write_string_64".to_string(),
+            ],
+        }
+    }
+}
+
+impl Default for OsModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EarthngModule for OsModule {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    
+    fn description(&self) -> &str {
+        &self.description
+    }
+    
+    fn functions(&self) -> Vec<&str> {
+        self.functions.iter().map(|s| s.as_str()).collect()
+    }
+    
+    fn signatures(&self) -> Vec<FunctionSignature> {
+        declare(&[
+            "open(path: str, mode: str) -> int",
+            "read(fd: int, count: int) -> str",
+            "write(fd: int, text: str) -> int",
+            "close(fd: int) -> int",
+            "sys_open_64(path: str, flags: int, mode: int) -> int",
+            "sys_read_64(fd: int, buffer: ptr, count: int) -> int",
+            "sys_write_64(fd: int, buffer: ptr, count: int) -> int",
+            "sys_close_64(fd: int) -> int",
+            "os_read_string_64(fd: int, count: int) -> str",
+            "os_write_string_64(fd: int, text: str) -> int",
+        ])
+    }
+    
+    fn dependencies(&self) -> Vec<&str> {
+        // read() returns its bytes in a heap block
+        vec!["system"]
+    }
+    
+    fn required_capabilities(&self) -> Vec<Capability> {
+        vec![Capability::LongMode64, Capability::Linux, Capability::Filesystem]
+    }
+    
+    fn compile_function(
+        &self,
+        func: &str,
+        _args: &[Expr],
+        _target: &Target,
+        emitter: &mut dyn AssemblyEmitter
+    ) -> Result<String, String> {
+        Ok(emitter.emit_call(func, &[]))
+    }
+    
+    fn library_code(&self, target: &Target) -> Option<String> {
+        match target {
+            Target::Linux64 => Some(OS_LIBRARY_LINUX64.to_string()),
+            _ => None,
+        }
+    }
+    
+    fn init(&mut self, _capabilities: &[Capability]) {
+        // Descriptors come from the kernel at runtime
+    }
+}
+
+/// Builtins the Linux backend lowers onto the os module's routines
+pub const OS_BUILTINS: [&str; 4] = ["open", "read", "write", "close"];
+
+/// Permissions of files open() creates, before the umask
+pub const OPEN_CREATE_MODE: i64 = 0o644;
+
+/// open() flags for Python's mode strings: O_WRONLY/O_RDWR combined with
+/// O_CREAT (0o100), O_TRUNC (0o1000) and O_APPEND (0o2000)
+pub fn open_flags(mode: &str) -> Option<i64> {
+    match mode {
+        "r" => Some(0),
+        "r+" => Some(0o2),
+        "w" => Some(0o1 | 0o100 | 0o1000),
+        "w+" => Some(0o2 | 0o100 | 0o1000),
+        "a" => Some(0o1 | 0o100 | 0o2000),
+        "a+" => Some(0o2 | 0o100 | 0o2000),
+        _ => None,
+    }
+}
+
+/// The syscall wrappers take their arguments in the System V registers the
+/// backends call with and return the kernel's result, so errors are -errno
+const OS_LIBRARY_LINUX64: &str = "    .section .text
+sys_open_64:
+    # Input: rdi = path, rsi = flags, rdx = mode; output: rax = fd or -errno
+    mov eax, 2                  # syscall: open
+    jmp .os_syscall
+sys_read_64:
+    # Input: rdi = fd, rsi = buffer, rdx = count; output: rax = bytes read or -errno
+    xor eax, eax                # syscall: read
+    jmp .os_syscall
+sys_write_64:
+    # Input: rdi = fd, rsi = buffer, rdx = count; output: rax = bytes written or -errno
+    mov eax, 1                  # syscall: write
+    jmp .os_syscall
+sys_close_64:
+    # Input: rdi = fd; output: rax = 0 or -errno
+    mov eax, 3                  # syscall: close
+.os_syscall:
+    push rcx
+    push r11
+    syscall
+    pop r11
+    pop rcx
+    ret
+
+os_read_string_64:
+    # Input: rdi = fd, rsi = most bytes to read; output: rax = the bytes read as a
+    # NUL-terminated string in a fresh block, empty at end of file or on error
+    push rbx
+    push r12
+    push r13
+    mov r12, rdi
+    xor r13, r13
+    test rsi, rsi
+    cmovg r13, rsi
+    lea rdi, [r13 + 1]
+    call heap_alloc_64
+    mov rbx, rax
+    mov rdi, r12
+    mov rsi, rbx
+    mov rdx, r13
+    call sys_read_64
+    test rax, rax
+    jns .os_read_terminate
+    xor eax, eax
+.os_read_terminate:
+    mov BYTE PTR [rbx + rax], 0
+    mov rax, rbx
+    pop r13
+    pop r12
+    pop rbx
+    ret
+
+os_write_string_64:
+    # Input: rdi = fd, rsi = NUL-terminated string; output: rax = bytes written or -errno
+    xor edx, edx
+.os_write_length:
+    cmp BYTE PTR [rsi + rdx], 0
+    je sys_write_64
+    inc rdx
+    jmp .os_write_length
+";
+
+/// COM1 output for freestanding code: 16550 UART setup and polled writes
+pub struct SerialModule {
+    name: String,
+    description: String,
+    functions: Vec<String>,
+}
+
+impl SerialModule {
+    pub fn new() -> Self {
+        Self {
+            name: "serial".to_string(),
+            description: "16550 UART output on COM1".to_string(),
+            functions: vec![
+                "serial_init_64".to_string(),
+                "serial_write_char_64".to_string(),
+                "serial_write_string_64".to_string(),
+                "serial_write_64".to_string(),
+            ],
+        }
+    }
+}
+
+impl Default for SerialModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EarthngModule for SerialModule {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    
+    fn description(&self) -> &str {
+        &self.description
+    }
+    
+    fn functions(&self) -> Vec<&str> {
+        self.functions.iter().map(|s| s.as_str()).collect()
+    }
+    
+    fn signatures(&self) -> Vec<FunctionSignature> {
+        declare(&[
+            "serial_init_64() -> void",
+            "serial_write_char_64(byte: int) -> void",
+            "serial_write_string_64(text: str) -> void",
+            "serial_write_64(fd: int, bytes: ptr, count: int) -> void",
+        ])
+    }
+    
+    fn required_capabilities(&self) -> Vec<Capability> {
+        vec![Capability::LongMode64, Capability::PortIO]
+    }
+    
+    fn compile_function(
+        &self,
+        func: &str,
+        _args: &[Expr],
+        _target: &Target,
+        emitter: &mut dyn AssemblyEmitter
+    ) -> Result<String, String> {
+        Ok(emitter.emit_call(func, &[]))
+    }
+    
+    fn library_code(&self, target: &Target) -> Option<String> {
+        match target {
+            // The freestanding --bios-mode payload is built by the x86-64 backend
+            Target::Linux64 => Some(serial_library_x86_64()),
+            _ => None,
+        }
+    }
+    
+    fn init(&mut self, _capabilities: &[Capability]) {
+        // The UART is programmed at runtime by serial_init_64
+    }
+}
+
+/// serial_write_64 takes the buffer and length of a write(2) to stdout in rsi
+/// and rdx, so the print helpers can call it in place of the syscall
+fn serial_library_x86_64() -> String {
+    use crate::boot_test::{serial_init_gas, COM1, COM1_LINE_STATUS};
+
+    let mut asm = String::from("    .section .text\n");
+    asm.push_str("serial_init_64:\n");
+    asm.push_str("    # 115200 baud, 8N1, FIFOs enabled and cleared\n");
+    asm.push_str("    push rax\n");
+    asm.push_str("    push rdx\n");
+    asm.push_str(&serial_init_gas());
+    asm.push_str("    pop rdx\n");
+    asm.push_str("    pop rax\n");
+    asm.push_str("    ret\n\n");
+
+    asm.push_str("serial_write_char_64:\n");
+    asm.push_str("    # Input: dil = byte, sent once the transmit holding register is empty\n");
+    asm.push_str("    push rax\n");
+    asm.push_str("    push rdx\n");
+    asm.push_str(&format!("    mov dx, 0x{:X}\n", COM1_LINE_STATUS));
+    asm.push_str(".serial_wait:\n");
+    asm.push_str("    in al, dx\n");
+    asm.push_str("    test al, 0x20\n");
+    asm.push_str("    jz .serial_wait\n");
+    asm.push_str(&format!("    mov dx, 0x{:X}\n", COM1));
+    asm.push_str("    mov eax, edi\n");
+    asm.push_str("    out dx, al\n");
+    asm.push_str("    pop rdx\n");
+    asm.push_str("    pop rax\n");
+    asm.push_str("    ret\n\n");
+
+    asm.push_str("serial_write_string_64:\n");
+    asm.push_str("    # Input: rdi = NUL-terminated string\n");
+    asm.push_str("    push rdi\n");
+    asm.push_str("    push rsi\n");
+    asm.push_str("    mov rsi, rdi\n");
+    asm.push_str(".serial_string_loop:\n");
+    asm.push_str("    movzx edi, BYTE PTR [rsi]\n");
+    asm.push_str("    test edi, edi\n");
+    asm.push_str("    jz .serial_string_done\n");
+    asm.push_str("    call serial_write_char_64\n");
+    asm.push_str("    inc rsi\n");
+    asm.push_str("    jmp .serial_string_loop\n");
+    asm.push_str(".serial_string_done:\n");
+    asm.push_str("    pop rsi\n");
+    asm.push_str("    pop rdi\n");
+    asm.push_str("    ret\n\n");
+
+    asm.push_str("serial_write_64:\n");
+    asm.push_str("    # Input: rsi = bytes, rdx = count\n");
+    asm.push_str("    push rdi\n");
+    asm.push_str("    push rsi\n");
+    asm.push_str("    push rdx\n");
+    asm.push_str(".serial_write_loop:\n");
+    asm.push_str("    test rdx, rdx\n");
+    asm.push_str("    jz .serial_write_done\n");
+    asm.push_str("    movzx edi, BYTE PTR [rsi]\n");
+    asm.push_str("    call serial_write_char_64\n");
+    asm.push_str("    inc rsi\n");
+    asm.push_str("    dec rdx\n");
+    asm.push_str("    jmp .serial_write_loop\n");
+    asm.push_str(".serial_write_done:\n");
+    asm.push_str("    pop rdx\n");
+    asm.push_str("    pop rsi\n");
+    asm.push_str("    pop rdi\n");
+    asm.push_str("    ret\n\n");
+    asm
+}
+
+/// ATA PIO sector access on the primary bus, master or slave drive
+pub struct DiskModule {
+    name: String,
+    description: String,
+    functions: Vec<String>,
+}
+
+impl DiskModule {
+    pub fn new() -> Self {
+        Self {
+            name: "disk".to_string(),
+            description: "ATA PIO LBA28 sector reads and writes".to_string(),
+            functions: vec![
+                "ata_read_sectors_64".to_string(),
+                "ata_write_sectors_64".to_string(),
+                "disk_read_64".to_string(),
+                "disk_write_64".to_string(),
+            ],
+        }
+    }
+}
+
+impl Default for DiskModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EarthngModule for DiskModule {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    
+    fn description(&self) -> &str {
+        &self.description
+    }
+    
+    fn functions(&self) -> Vec<&str> {
+        self.functions.iter().map(|s| s.as_str()).collect()
+    }
+    
+    fn signatures(&self) -> Vec<FunctionSignature> {
+        declare(&[
+            "ata_read_sectors_64(lba: int, count: int, buffer: ptr, drive: int) -> int",
+            "ata_write_sectors_64(lba: int, count: int, buffer: ptr, drive: int) -> int",
+            "disk_read_64(lba: int, count: int, drive: int) -> ptr",
+            "disk_write_64(lba: int, buffer: ptr, drive: int) -> int",
+        ])
+    }
+    
+    fn dependencies(&self) -> Vec<&str> {
+        // disk_read_64 allocates its buffers with heap_alloc_64
+        vec!["system"]
+    }
+    
+    fn required_capabilities(&self) -> Vec<Capability> {
+        vec![Capability::LongMode64, Capability::PortIO, Capability::DiskIO]
+    }
+    
+    fn compile_function(
+        &self,
+        func: &str,
+        _args: &[Expr],
+        _target: &Target,
+        emitter: &mut dyn AssemblyEmitter
+    ) -> Result<String, String> {
+        Ok(emitter.emit_call(func, &[]))
+    }
+    
+    fn library_code(&self, target: &Target) -> Option<String> {
+        match target {
+            Target::Linux64 => Some(disk_library_x86_64()),
+            _ => None,
+        }
+    }
+    
+    fn init(&mut self, _capabilities: &[Capability]) {
+        // Drives are selected per call
+    }
+}
+
+/// Builtins the BIOS backend lowers onto disk_read_64 / disk_write_64
+pub const DISK_BUILTINS: [&str; 2] = ["disk_read", "disk_write"];
+
+/// Primary ATA bus: data register, then sector count, LBA bytes, drive/head
+/// and status/command at the following ports, alternate status on the control block
+pub const ATA_DATA_PORT: u16 = 0x1F0;
+pub const ATA_STATUS_PORT: u16 = ATA_DATA_PORT + 7;
+const ATA_ALT_STATUS_PORT: u16 = 0x3F6;
+pub const ATA_READ_SECTORS: u8 = 0x20;
+pub const ATA_WRITE_SECTORS: u8 = 0x30;
+const ATA_CACHE_FLUSH: u8 = 0xE7;
+
+/// Status codes of the ATA routines, 0 meaning success
+pub const ATA_DRIVE_ERROR: u8 = 1;
+pub const ATA_TIMEOUT: u8 = 2;
+
+/// Status reads before a drive that stays busy counts as gone
+const ATA_TIMEOUT_POLLS: u32 = 0x10_0000;
+
+/// ata_read_sectors_64 / ata_write_sectors_64(lba in rdi, count in rsi,
+/// buffer in rdx, drive in rcx: 0 master, 1 slave) return a status code.
+/// disk_read_64(lba, count, drive) wraps a read into a fresh heap buffer: the
+/// sector count at [buffer - 8], the data, then a NUL so it can be printed; it
+/// returns 0 when the read fails. disk_write_64(lba, buffer, drive) writes such
+/// a buffer back and returns the status code
+fn disk_library_x86_64() -> String {
+    let port = |offset: u16| format!("    mov dx, 0x{:X}\n", ATA_DATA_PORT + offset);
+    let mut asm = String::from("    .section .text\n");
+
+    asm.push_str("ata_read_sectors_64:\n");
+    asm.push_str("    push rdi\n");
+    asm.push_str("    mov r11, rdx        # the port writes need dx\n");
+    asm.push_str(&format!("    mov al, 0x{:02X}        # READ SECTORS\n", ATA_READ_SECTORS));
+    asm.push_str("    call .ata_command\n");
+    asm.push_str("    test eax, eax\n");
+    asm.push_str("    jnz .ata_read_done\n");
+    asm.push_str("    mov rdi, r11\n");
+    asm.push_str("    mov r8, rsi\n");
+    asm.push_str(".ata_read_sector:\n");
+    asm.push_str("    call .ata_wait_data\n");
+    asm.push_str("    test eax, eax\n");
+    asm.push_str("    jnz .ata_read_done\n");
+    asm.push_str("    mov ecx, 256\n");
+    asm.push_str(&port(0));
+    asm.push_str("    rep insw\n");
+    asm.push_str("    dec r8\n");
+    asm.push_str("    jnz .ata_read_sector\n");
+    asm.push_str(".ata_read_done:\n");
+    asm.push_str("    pop rdi\n");
+    asm.push_str("    ret\n\n");
+
+    asm.push_str("ata_write_sectors_64:\n");
+    asm.push_str("    push rsi\n");
+    asm.push_str("    mov r11, rdx\n");
+    asm.push_str(&format!("    mov al, 0x{:02X}        # WRITE SECTORS\n", ATA_WRITE_SECTORS));
+    asm.push_str("    call .ata_command\n");
+    asm.push_str("    test eax, eax\n");
+    asm.push_str("    jnz .ata_write_done\n");
+    asm.push_str("    mov r8, rsi\n");
+    asm.push_str("    mov rsi, r11\n");
+    asm.push_str(".ata_write_sector:\n");
+    asm.push_str("    call .ata_wait_data\n");
+    asm.push_str("    test eax, eax\n");
+    asm.push_str("    jnz .ata_write_done\n");
+    asm.push_str("    mov ecx, 256\n");
+    asm.push_str(&port(0));
+    asm.push_str("    rep outsw\n");
+    asm.push_str("    dec r8\n");
+    asm.push_str("    jnz .ata_write_sector\n");
+    asm.push_str(&port(7));
+    asm.push_str(&format!("    mov al, 0x{:02X}        # CACHE FLUSH\n", ATA_CACHE_FLUSH));
+    asm.push_str("    out dx, al\n");
+    asm.push_str("    call .ata_wait_idle\n");
+    asm.push_str(".ata_write_done:\n");
+    asm.push_str("    pop rsi\n");
+    asm.push_str("    ret\n\n");
+
+    // Select the drive and LBA, then issue the command in al. eax is the status code
+    asm.push_str(".ata_command:\n");
+    asm.push_str("    movzx r9d, al\n");
+    asm.push_str("    call .ata_wait_idle\n");
+    asm.push_str("    test eax, eax\n");
+    asm.push_str("    jnz .ata_command_done\n");
+    asm.push_str("    mov eax, edi\n");
+    asm.push_str("    shr eax, 24\n");
+    asm.push_str("    and al, 0x0F        # LBA bits 24-27\n");
+    asm.push_str("    or al, 0xE0         # LBA mode, master\n");
+    asm.push_str("    test ecx, ecx\n");
+    asm.push_str("    jz .ata_master\n");
+    asm.push_str("    or al, 0x10         # slave\n");
+    asm.push_str(".ata_master:\n");
+    asm.push_str(&port(6));
+    asm.push_str("    out dx, al\n");
+    asm.push_str(&format!("    mov dx, 0x{:X}\n", ATA_ALT_STATUS_PORT));
+    for _ in 0..4 {
+        asm.push_str("    in al, dx           # 400ns for the drive select to settle\n");
+    }
+    asm.push_str(&port(2));
+    asm.push_str("    mov eax, esi\n");
+    asm.push_str("    out dx, al\n");
+    for (offset, shift) in [(3, 0), (4, 8), (5, 16)] {
+        asm.push_str(&port(offset));
+        asm.push_str("    mov eax, edi\n");
+        if shift > 0 {
+            asm.push_str(&format!("    shr eax, {}\n", shift));
+        }
+        asm.push_str("    out dx, al\n");
+    }
+    asm.push_str(&port(7));
+    asm.push_str("    mov eax, r9d\n");
+    asm.push_str("    out dx, al\n");
+    asm.push_str("    xor eax, eax\n");
+    asm.push_str(".ata_command_done:\n");
+    asm.push_str("    ret\n\n");
+
+    // Wait for BSY to clear
+    asm.push_str(".ata_wait_idle:\n");
+    asm.push_str(&format!("    mov r10d, 0x{:X}\n", ATA_TIMEOUT_POLLS));
+    asm.push_str(&port(7));
+    asm.push_str(".ata_idle_poll:\n");
+    asm.push_str("    in al, dx\n");
+    asm.push_str("    test al, 0x80\n");
+    asm.push_str("    jz .ata_ok\n");
+    asm.push_str("    dec r10d\n");
+    asm.push_str("    jnz .ata_idle_poll\n");
+    asm.push_str(&format!("    mov eax, {}\n", ATA_TIMEOUT));
+    asm.push_str("    ret\n");
+    // Wait for BSY to clear and DRQ to be set, failing on ERR or DF
+    asm.push_str(".ata_wait_data:\n");
+    asm.push_str(&format!("    mov r10d, 0x{:X}\n", ATA_TIMEOUT_POLLS));
+    asm.push_str(&port(7));
+    asm.push_str(".ata_data_poll:\n");
+    asm.push_str("    in al, dx\n");
+    asm.push_str("    test al, 0x80       # BSY\n");
+    asm.push_str("    jnz .ata_data_wait\n");
+    asm.push_str("    test al, 0x21       # ERR, DF\n");
+    asm.push_str("    jnz .ata_failed\n");
+    asm.push_str("    test al, 0x08       # DRQ\n");
+    asm.push_str("    jnz .ata_ok\n");
+    asm.push_str(".ata_data_wait:\n");
+    asm.push_str("    dec r10d\n");
+    asm.push_str("    jnz .ata_data_poll\n");
+    asm.push_str(&format!("    mov eax, {}\n", ATA_TIMEOUT));
+    asm.push_str("    ret\n");
+    asm.push_str(".ata_failed:\n");
+    asm.push_str(&format!("    mov eax, {}\n", ATA_DRIVE_ERROR));
+    asm.push_str("    ret\n");
+    asm.push_str(".ata_ok:\n");
+    asm.push_str("    xor eax, eax\n");
+    asm.push_str("    ret\n\n");
+
+    asm.push_str("disk_read_64:\n");
+    for reg in ["rbx", "r12", "r13", "r14"] {
+        asm.push_str(&format!("    push {}\n", reg));
+    }
+    asm.push_str("    mov r12, rdi\n");
+    asm.push_str("    mov r13, rsi\n");
+    asm.push_str("    mov r14, rdx\n");
+    asm.push_str("    xor ebx, ebx\n");
+    asm.push_str("    lea rax, [rsi - 1]\n");
+    asm.push_str("    cmp rax, 254        # 1 to 255 sectors per call\n");
+    asm.push_str("    ja .disk_read_done\n");
+    asm.push_str("    mov rdi, rsi\n");
+    asm.push_str("    shl rdi, 9\n");
+    asm.push_str("    add rdi, 9          # count before the data, NUL after it\n");
+    asm.push_str("    call heap_alloc_64\n");
+    asm.push_str("    mov [rax], r13\n");
+    asm.push_str("    lea rbx, [rax + 8]\n");
+    asm.push_str("    mov rax, r13\n");
+    asm.push_str("    shl rax, 9\n");
+    asm.push_str("    mov BYTE PTR [rbx + rax], 0\n");
+    asm.push_str("    mov rdi, r12\n");
+    asm.push_str("    mov rsi, r13\n");
+    asm.push_str("    mov rdx, rbx\n");
+    asm.push_str("    mov rcx, r14\n");
+    asm.push_str("    call ata_read_sectors_64\n");
+    asm.push_str("    test eax, eax\n");
+    asm.push_str("    jz .disk_read_done\n");
+    asm.push_str("    xor ebx, ebx\n");
+    asm.push_str(".disk_read_done:\n");
+    asm.push_str("    mov rax, rbx\n");
+    for reg in ["r14", "r13", "r12", "rbx"] {
+        asm.push_str(&format!("    pop {}\n", reg));
+    }
+    asm.push_str("    ret\n\n");
+
+    asm.push_str("disk_write_64:\n");
+    asm.push_str("    mov rcx, rdx\n");
+    asm.push_str("    mov rdx, rsi\n");
+    asm.push_str("    mov rsi, [rdx - 8]  # sectors disk_read_64 allocated\n");
+    asm.push_str("    jmp ata_write_sectors_64\n\n");
+    asm
+}
+
+/// Bytes input() allocates for a line, terminator included
+pub const INPUT_LINE_BYTES: u32 = 256;
+
+/// PS/2 keyboard input for freestanding code, polled through the 8042 controller
+/// or, with interrupts enabled, taken from the scancodes IRQ1 queued
+pub struct KeyboardModule {
+    name: String,
+    description: String,
+    functions: Vec<String>,
+    interrupt_driven: bool,
+}
+
+impl KeyboardModule {
+    pub fn new() -> Self {
+        Self {
+            name: "keyboard".to_string(),
+            description: "PS/2 keyboard input with scancode set 1 translation".to_string(),
+            functions: vec![
+                "kbd_read_char_64".to_string(),
+                "kbd_read_line_64".to_string(),
+                "kbd_translate_64".to_string(),
+            ],
+            interrupt_driven: false,
+        }
+    }
+}
+
+impl Default for KeyboardModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EarthngModule for KeyboardModule {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    
+    fn description(&self) -> &str {
+        &self.description
+    }
+    
+    fn functions(&self) -> Vec<&str> {
+        self.functions.iter().map(|s| s.as_str()).collect()
+    }
+    
+    fn signatures(&self) -> Vec<FunctionSignature> {
+        // kbd_translate_64 takes its scancode in al, so it cannot be called from a program
+        declare(&[
+            "kbd_read_char_64() -> int",
+            "kbd_read_line_64(buffer: ptr, size: int, echo: ptr) -> ptr",
+        ])
+    }
+    
+    fn required_capabilities(&self) -> Vec<Capability> {
+        vec![Capability::LongMode64, Capability::PortIO]
+    }
+    
+    fn compile_function(
+        &self,
+        func: &str,
+        _args: &[Expr],
+        _target: &Target,
+        emitter: &mut dyn AssemblyEmitter
+    ) -> Result<String, String> {
+        Ok(emitter.emit_call(func, &[]))
+    }
+    
+    fn library_code(&self, target: &Target) -> Option<String> {
+        match target {
+            Target::Linux64 => Some(keyboard_library_x86_64(self.interrupt_driven)),
+            _ => None,
+        }
+    }
+    
+    fn init(&mut self, capabilities: &[Capability]) {
+        // The BIOS leaves the controller in scancode set 1 translation
+        self.interrupt_driven = capabilities.contains(&Capability::Interrupts);
+    }
+}
+
+/// 8042 status and data ports; status bit 0 means a byte is waiting
+const PS2_STATUS_PORT: u16 = 0x64;
+const PS2_DATA_PORT: u16 = 0x60;
+
+/// ASCII of the scancode set 1 make codes below 0x3A (up to the space bar),
+/// without and with shift; 0 for keys that produce no character
+const SCANCODES: &[u8; 0x3A] = b"\0\x1B1234567890-=\x08\tqwertyuiop[]\n\0asdfghjkl;'`\0\\zxcvbnm,./\0*\0 ";
+const SCANCODES_SHIFTED: &[u8; 0x3A] = b"\0\x1B!@#$%^&*()_+\x08\tQWERTYUIOP{}\n\0ASDFGHJKL:\"~\0|ZXCVBNM<>?\0*\0 ";
+
+/// kbd_read_line_64 echoes through a routine taking a NUL-terminated string
+/// in rdi, like fb_print or serial_write_string_64, so it works with either console
+fn keyboard_library_x86_64(interrupt_driven: bool) -> String {
+    let mut asm = String::from("    .section .text\n");
+    asm.push_str("kbd_read_char_64:\n");
+    asm.push_str("    # Output: rax = ASCII of the next key pressed, waiting for one\n");
+    asm.push_str(".kbd_poll:\n");
+    if interrupt_driven {
+        asm.push_str("    call kbd_ring_wait_64\n");
+    } else {
+        asm.push_str(&format!("    in al, 0x{:X}\n", PS2_STATUS_PORT));
+        asm.push_str("    test al, 1\n");
+        asm.push_str("    jz .kbd_poll\n");
+        asm.push_str(&format!("    in al, 0x{:X}\n", PS2_DATA_PORT));
+    }
+    asm.push_str("    call kbd_translate_64\n");
+    asm.push_str("    test eax, eax\n");
+    asm.push_str("    jz .kbd_poll\n");
+    asm.push_str("    ret\n\n");
+
+    asm.push_str("kbd_translate_64:\n");
+    asm.push_str("    # Input: al = scancode. Output: rax = its ASCII, 0 for anything but a key press\n");
+    asm.push_str("    push rcx\n");
+    for (code, label, key) in [(0x2A, "down", "left shift"), (0x36, "down", "right shift"), (0xAA, "up", "left shift"), (0xB6, "up", "right shift")] {
+        asm.push_str(&format!("    cmp al, 0x{:02X}        # {} {}\n", code, key, label));
+        asm.push_str(&format!("    je .kbd_shift_{}\n", label));
+    }
+    asm.push_str("    test al, 0x80       # other releases and the 0xE0 prefix\n");
+    asm.push_str("    jnz .kbd_no_key\n");
+    asm.push_str(&format!("    cmp al, 0x{:X}\n", SCANCODES.len()));
+    asm.push_str("    jae .kbd_no_key\n");
+    asm.push_str("    movzx ecx, al\n");
+    asm.push_str("    lea rax, [kbd_scancodes]\n");
+    asm.push_str("    cmp BYTE PTR [kbd_shift], 0\n");
+    asm.push_str("    je .kbd_translate\n");
+    asm.push_str("    lea rax, [kbd_scancodes_shifted]\n");
+    asm.push_str(".kbd_translate:\n");
+    asm.push_str("    movzx eax, BYTE PTR [rax + rcx]\n");
+    asm.push_str("    pop rcx\n");
+    asm.push_str("    ret\n");
+    asm.push_str(".kbd_shift_down:\n");
+    asm.push_str("    mov BYTE PTR [kbd_shift], 1\n");
+    asm.push_str("    jmp .kbd_no_key\n");
+    asm.push_str(".kbd_shift_up:\n");
+    asm.push_str("    mov BYTE PTR [kbd_shift], 0\n");
+    asm.push_str(".kbd_no_key:\n");
+    asm.push_str("    xor eax, eax\n");
+    asm.push_str("    pop rcx\n");
+    asm.push_str("    ret\n\n");
+
+    asm.push_str("kbd_read_line_64:\n");
+    asm.push_str("    # Input: rdi = buffer, rsi = its size, rdx = echo routine or 0\n");
+    asm.push_str("    # Output: rax = buffer holding the line up to Enter, NUL-terminated\n");
+    for reg in ["rbx", "r12", "r13", "r14"] {
+        asm.push_str(&format!("    push {}\n", reg));
+    }
+    asm.push_str("    mov rbx, rdi\n");
+    asm.push_str("    mov r12, rsi\n");
+    asm.push_str("    mov r13, rdx\n");
+    asm.push_str("    xor r14, r14        # length\n");
+    asm.push_str(".kbd_line_loop:\n");
+    asm.push_str("    call kbd_read_char_64\n");
+    asm.push_str("    cmp al, 10\n");
+    asm.push_str("    je .kbd_line_done\n");
+    asm.push_str("    cmp al, 8\n");
+    asm.push_str("    je .kbd_line_backspace\n");
+    asm.push_str("    cmp al, 32          # escape and tab are not stored\n");
+    asm.push_str("    jb .kbd_line_loop\n");
+    asm.push_str("    lea rcx, [r14 + 1]\n");
+    asm.push_str("    cmp rcx, r12        # keep room for the terminator\n");
+    asm.push_str("    jae .kbd_line_loop\n");
+    asm.push_str("    mov BYTE PTR [rbx + r14], al\n");
+    asm.push_str("    inc r14\n");
+    asm.push_str("    mov BYTE PTR [kbd_echo_char], al\n");
+    asm.push_str("    lea rdi, [kbd_echo_char]\n");
+    asm.push_str("    call .kbd_echo\n");
+    asm.push_str("    jmp .kbd_line_loop\n");
+    asm.push_str(".kbd_line_backspace:\n");
+    asm.push_str("    test r14, r14\n");
+    asm.push_str("    jz .kbd_line_loop\n");
+    asm.push_str("    dec r14\n");
+    asm.push_str("    lea rdi, [kbd_erase]\n");
+    asm.push_str("    call .kbd_echo\n");
+    asm.push_str("    jmp .kbd_line_loop\n");
+    asm.push_str(".kbd_line_done:\n");
+    asm.push_str("    mov BYTE PTR [rbx + r14], 0\n");
+    asm.push_str("    lea rdi, [kbd_newline]\n");
+    asm.push_str("    call .kbd_echo\n");
+    asm.push_str("    mov rax, rbx\n");
+    for reg in ["r14", "r13", "r12", "rbx"] {
+        asm.push_str(&format!("    pop {}\n", reg));
+    }
+    asm.push_str("    ret\n");
+    asm.push_str(".kbd_echo:\n");
+    asm.push_str("    test r13, r13\n");
+    asm.push_str("    jz .kbd_echo_done\n");
+    asm.push_str("    call r13\n");
+    asm.push_str(".kbd_echo_done:\n");
+    asm.push_str("    ret\n\n");
+
+    asm.push_str("    .section .data\n");
+    asm.push_str("kbd_shift:\n");
+    asm.push_str("    .byte 0\n");
+    asm.push_str("kbd_echo_char:\n");
+    asm.push_str("    .byte 0, 0\n");
+    asm.push_str("kbd_erase:\n");
+    asm.push_str("    .byte 8, 32, 8, 0   # back, blank, back\n");
+    asm.push_str("kbd_newline:\n");
+    asm.push_str("    .byte 10, 0\n");
+    for (label, table) in [("kbd_scancodes", SCANCODES), ("kbd_scancodes_shifted", SCANCODES_SHIFTED)] {
+        asm.push_str(&format!("{}:\n", label));
+        for row in table.chunks(16) {
+            let bytes: Vec<String> = row.iter().map(|byte| format!("0x{:02X}", byte)).collect();
+            asm.push_str(&format!("    .byte {}\n", bytes.join(", ")));
+        }
+    }
+    asm.push_str("    .section .text\n");
+    asm
+}
+
+/// IDT, PIC remapping and the timer and keyboard IRQs for freestanding code
+pub struct InterruptsModule {
+    name: String,
+    description: String,
+    functions: Vec<String>,
+}
+
+impl InterruptsModule {
+    pub fn new() -> Self {
+        Self {
+            name: "interrupts".to_string(),
+            description: "64-bit IDT with timer ticks and a keyboard scancode queue".to_string(),
+            functions: vec![
+                "idt_init_64".to_string(),
+                "idt_set_gate_64".to_string(),
+                "ticks_64".to_string(),
+                "key_pressed_64".to_string(),
+                "kbd_ring_wait_64".to_string(),
+            ],
+        }
+    }
+}
+
+impl Default for InterruptsModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EarthngModule for InterruptsModule {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    
+    fn description(&self) -> &str {
+        &self.description
+    }
+    
+    fn functions(&self) -> Vec<&str> {
+        self.functions.iter().map(|s| s.as_str()).collect()
+    }
+    
+    fn signatures(&self) -> Vec<FunctionSignature> {
+        declare(&[
+            "idt_init_64() -> void",
+            "idt_set_gate_64(vector: int, handler: ptr) -> void",
+            "ticks_64() -> int",
+            "key_pressed_64() -> int",
+            "kbd_ring_wait_64() -> int",
+        ])
+    }
+    
+    fn dependencies(&self) -> Vec<&str> {
+        // key_pressed_64 translates scancodes with kbd_translate_64
+        vec!["keyboard"]
+    }
+    
+    fn required_capabilities(&self) -> Vec<Capability> {
+        vec![Capability::LongMode64, Capability::PortIO, Capability::Interrupts]
+    }
+    
+    fn compile_function(
+        &self,
+        func: &str,
+        _args: &[Expr],
+        _target: &Target,
+        emitter: &mut dyn AssemblyEmitter
+    ) -> Result<String, String> {
+        Ok(emitter.emit_call(func, &[]))
+    }
+    
+    fn library_code(&self, target: &Target) -> Option<String> {
+        match target {
+            Target::Linux64 => Some(interrupts_library_x86_64()),
+            _ => None,
+        }
+    }
+    
+    fn init(&mut self, _capabilities: &[Capability]) {
+        // idt_init_64 remaps the PICs and enables interrupts at runtime
+    }
+}
+
+/// Builtins the BIOS backend lowers onto ticks_64 / key_pressed_64
+pub const INTERRUPT_BUILTINS: [&str; 2] = ["ticks", "key_pressed"];
+
+/// Vectors the remapped master and slave PICs raise IRQ0-7 and IRQ8-15 on,
+/// clear of the CPU exceptions below 0x20
+pub const PIC_MASTER_OFFSET: u8 = 0x20;
+pub const PIC_SLAVE_OFFSET: u8 = 0x28;
+const PIC_MASTER_COMMAND: u16 = 0x20;
+const PIC_SLAVE_COMMAND: u16 = 0xA0;
+const PIC_EOI: u8 = 0x20;
+
+/// Present, DPL 0, 64-bit interrupt gate (type 0xE), so IF is cleared on entry
+pub const INTERRUPT_GATE: u8 = 0x8E;
+const IDT_ENTRIES: usize = 256;
+const IDT_ENTRY_BYTES: usize = 16;
+
+/// PIT input clock and the rate IRQ0 is programmed to, so ticks() counts milliseconds
+const PIT_FREQUENCY: u32 = 1_193_182;
+pub const TIMER_HZ: u32 = 1000;
+
+/// sleep_ms_64(milliseconds in rdi) for BIOS mode: each millisecond is a PIT
+/// channel 2 countdown in mode 0, polled until its output goes high, so it
+/// needs neither interrupts nor a calibrated TSC
+pub fn pit_sleep_routine() -> String {
+    let mut asm = String::from("\n# ========== PIT SLEEP ==========\n");
+    asm.push_str("sleep_ms_64:\n");
+    asm.push_str("    push rcx\n");
+    asm.push_str("    mov rcx, rdi\n");
+    asm.push_str("    test rcx, rcx\n");
+    asm.push_str("    jle .pit_sleep_done\n");
+    asm.push_str(".pit_sleep_ms:\n");
+    asm.push_str("    in al, 0x61\n");
+    asm.push_str("    and al, 0xFC        # gate channel 2 off, speaker off\n");
+    asm.push_str("    out 0x61, al\n");
+    asm.push_str("    mov al, 0xB0        # channel 2, lobyte/hibyte, interrupt on terminal count\n");
+    asm.push_str("    out 0x43, al\n");
+    asm.push_str(&format!("    mov ax, {}\n", PIT_FREQUENCY / 1000));
+    asm.push_str("    out 0x42, al\n");
+    asm.push_str("    mov al, ah\n");
+    asm.push_str("    out 0x42, al\n");
+    asm.push_str("    in al, 0x61\n");
+    asm.push_str("    or al, 0x01         # gate on: the countdown starts\n");
+    asm.push_str("    out 0x61, al\n");
+    asm.push_str(".pit_sleep_poll:\n");
+    asm.push_str("    in al, 0x61\n");
+    asm.push_str("    test al, 0x20       # channel 2 output\n");
+    asm.push_str("    jz .pit_sleep_poll\n");
+    asm.push_str("    dec rcx\n");
+    asm.push_str("    jnz .pit_sleep_ms\n");
+    asm.push_str(".pit_sleep_done:\n");
+    asm.push_str("    xor eax, eax        # same result as a nanosleep that slept\n");
+    asm.push_str("    pop rcx\n");
+    asm.push_str("    ret\n");
+    asm
+}
+
+/// Scancodes IRQ1 can queue before key presses are dropped; a power of two
+const KBD_RING_BYTES: u32 = 64;
+
+/// The 16-byte IDT descriptor of an interrupt gate to `handler` in the code
+/// segment `selector`: the offset is split into bits 0-15, 16-31 and 32-63
+/// around the selector, IST and type bytes
+pub fn idt_entry(handler: u64, selector: u16) -> [u8; 16] {
+    let mut entry = [0u8; IDT_ENTRY_BYTES];
+    entry[0..2].copy_from_slice(&(handler as u16).to_le_bytes());
+    entry[2..4].copy_from_slice(&selector.to_le_bytes());
+    entry[4] = 0; // IST 0: stay on the current stack
+    entry[5] = INTERRUPT_GATE;
+    entry[6..8].copy_from_slice(&((handler >> 16) as u16).to_le_bytes());
+    entry[8..12].copy_from_slice(&((handler >> 32) as u32).to_le_bytes());
+    entry
+}
+
+/// idt_init_64 remaps the PICs, starts the PIT, points every vector at a handler
+/// and loads the IDT before enabling interrupts. CPU exceptions halt, stray IRQs
+/// are acknowledged and ignored, IRQ0 counts ticks and IRQ1 queues scancodes
+/// for kbd_ring_wait_64 and key_pressed_64
+fn interrupts_library_x86_64() -> String {
+    let out = |port: u16, value: u8, what: &str| {
+        format!("    mov al, 0x{:02X}        # {}\n    out 0x{:02X}, al\n", value, what, port)
+    };
+    let eoi = |asm: &mut String, slave: bool| {
+        asm.push_str(&format!("    mov al, 0x{:02X}\n", PIC_EOI));
+        if slave {
+            asm.push_str(&format!("    out 0x{:02X}, al\n", PIC_SLAVE_COMMAND));
+        }
+        asm.push_str(&format!("    out 0x{:02X}, al\n", PIC_MASTER_COMMAND));
+    };
+    let mut asm = String::from("    .section .text\n");
+
+    asm.push_str("idt_init_64:\n");
+    asm.push_str("    cli\n");
+    asm.push_str(&out(PIC_MASTER_COMMAND, 0x11, "ICW1: initialise, ICW4 follows"));
+    asm.push_str(&format!("    out 0x{:02X}, al\n", PIC_SLAVE_COMMAND));
+    asm.push_str(&out(PIC_MASTER_COMMAND + 1, PIC_MASTER_OFFSET, "ICW2: master vector offset"));
+    asm.push_str(&out(PIC_SLAVE_COMMAND + 1, PIC_SLAVE_OFFSET, "ICW2: slave vector offset"));
+    asm.push_str(&out(PIC_MASTER_COMMAND + 1, 0x04, "ICW3: slave on IRQ2"));
+    asm.push_str(&out(PIC_SLAVE_COMMAND + 1, 0x02, "ICW3: cascade identity"));
+    asm.push_str(&out(PIC_MASTER_COMMAND + 1, 0x01, "ICW4: 8086 mode"));
+    asm.push_str(&format!("    out 0x{:02X}, al\n", PIC_SLAVE_COMMAND + 1));
+    asm.push_str(&out(PIC_MASTER_COMMAND + 1, 0xFC, "unmask the timer and keyboard"));
+    asm.push_str(&out(PIC_SLAVE_COMMAND + 1, 0xFF, "mask the slave's IRQs"));
+    let divisor = PIT_FREQUENCY / TIMER_HZ;
+    asm.push_str(&out(0x43, 0x36, "PIT channel 0, lobyte/hibyte, rate generator"));
+    asm.push_str(&format!("    mov ax, {}\n", divisor));
+    asm.push_str("    out 0x40, al\n");
+    asm.push_str("    mov al, ah\n");
+    asm.push_str("    out 0x40, al\n");
+    asm.push_str("    push rbx\n");
+    asm.push_str("    xor ebx, ebx\n");
+    asm.push_str(".idt_fill:\n");
+    asm.push_str("    lea rsi, [isr_default]\n");
+    asm.push_str(&format!("    cmp ebx, 0x{:02X}\n", PIC_MASTER_OFFSET));
+    asm.push_str("    jae .idt_gate\n");
+    asm.push_str("    lea rsi, [isr_exception]\n");
+    asm.push_str(".idt_gate:\n");
+    asm.push_str("    mov edi, ebx\n");
+    asm.push_str("    call idt_set_gate_64\n");
+    asm.push_str("    inc ebx\n");
+    asm.push_str(&format!("    cmp ebx, {}\n", IDT_ENTRIES));
+    asm.push_str("    jb .idt_fill\n");
+    asm.push_str("    pop rbx\n");
+    for (irq, handler) in [(0, "isr_timer"), (1, "isr_keyboard")] {
+        asm.push_str(&format!("    mov edi, 0x{:02X}\n", PIC_MASTER_OFFSET + irq));
+        asm.push_str(&format!("    lea rsi, [{}]\n", handler));
+        asm.push_str("    call idt_set_gate_64\n");
+    }
+    asm.push_str("    lidt [idtr]\n");
+    asm.push_str("    sti\n");
+    asm.push_str("    ret\n\n");
+
+    // Same layout as idt_entry
+    asm.push_str("idt_set_gate_64:\n");
+    asm.push_str("    # Input: rdi = vector, rsi = handler address\n");
+    asm.push_str("    shl rdi, 4\n");
+    asm.push_str("    lea rax, [idt]\n");
+    asm.push_str("    add rdi, rax\n");
+    asm.push_str("    mov WORD PTR [rdi], si\n");
+    asm.push_str(&format!("    mov WORD PTR [rdi + 2], 0x{:02X}\n", crate::mode_transition::CODE64_SELECTOR));
+    asm.push_str(&format!("    mov WORD PTR [rdi + 4], 0x{:02X}00\n", INTERRUPT_GATE));
+    asm.push_str("    shr rsi, 16\n");
+    asm.push_str("    mov WORD PTR [rdi + 6], si\n");
+    asm.push_str("    shr rsi, 16\n");
+    asm.push_str("    mov DWORD PTR [rdi + 8], esi\n");
+    asm.push_str("    mov DWORD PTR [rdi + 12], 0\n");
+    asm.push_str("    ret\n\n");
+
+    asm.push_str("isr_exception:\n");
+    asm.push_str("    cli                 # nothing to recover to\n");
+    asm.push_str("    hlt\n");
+    asm.push_str("    jmp isr_exception\n\n");
+
+    asm.push_str("isr_default:\n");
+    asm.push_str("    push rax\n");
+    eoi(&mut asm, true);
+    asm.push_str("    pop rax\n");
+    asm.push_str("    iretq\n\n");
+
+    asm.push_str("isr_timer:\n");
+    asm.push_str("    push rax\n");
+    asm.push_str("    inc QWORD PTR [irq_ticks]\n");
+    eoi(&mut asm, false);
+    asm.push_str("    pop rax\n");
+    asm.push_str("    iretq\n\n");
+
+    asm.push_str("isr_keyboard:\n");
+    for reg in ["rax", "rcx", "rdx"] {
+        asm.push_str(&format!("    push {}\n", reg));
+    }
+    asm.push_str(&format!("    in al, 0x{:X}\n", PS2_DATA_PORT));
+    asm.push_str("    mov ecx, [kbd_ring_head]\n");
+    asm.push_str("    lea edx, [ecx + 1]\n");
+    asm.push_str(&format!("    and edx, {}\n", KBD_RING_BYTES - 1));
+    asm.push_str("    cmp edx, [kbd_ring_tail]\n");
+    asm.push_str("    je .kbd_ring_full   # drop the scancode\n");
+    asm.push_str("    mov BYTE PTR [kbd_ring + rcx], al\n");
+    asm.push_str("    mov [kbd_ring_head], edx\n");
+    asm.push_str(".kbd_ring_full:\n");
+    eoi(&mut asm, false);
+    for reg in ["rdx", "rcx", "rax"] {
+        asm.push_str(&format!("    pop {}\n", reg));
+    }
+    asm.push_str("    iretq\n\n");
+
+    asm.push_str("ticks_64:\n");
+    asm.push_str("    # Output: rax = milliseconds since idt_init_64\n");
+    asm.push_str("    mov rax, [irq_ticks]\n");
+    asm.push_str("    ret\n\n");
+
+    asm.push_str("kbd_ring_pop_64:\n");
+    asm.push_str("    # Output: eax = the oldest queued scancode, -1 when there is none\n");
+    asm.push_str("    mov eax, [kbd_ring_tail]\n");
+    asm.push_str("    cmp eax, [kbd_ring_head]\n");
+    asm.push_str("    je .kbd_ring_empty\n");
+    asm.push_str("    push rcx\n");
+    asm.push_str("    movzx ecx, BYTE PTR [kbd_ring + rax]\n");
+    asm.push_str("    inc eax\n");
+    asm.push_str(&format!("    and eax, {}\n", KBD_RING_BYTES - 1));
+    asm.push_str("    mov [kbd_ring_tail], eax\n");
+    asm.push_str("    mov eax, ecx\n");
+    asm.push_str("    pop rcx\n");
+    asm.push_str("    ret\n");
+    asm.push_str(".kbd_ring_empty:\n");
+    asm.push_str("    mov eax, -1\n");
+    asm.push_str("    ret\n\n");
+
+    asm.push_str("kbd_ring_wait_64:\n");
+    asm.push_str("    # Output: al = the next scancode, halting until IRQ1 queues one\n");
+    asm.push_str("    call kbd_ring_pop_64\n");
+    asm.push_str("    test eax, eax\n");
+    asm.push_str("    jns .kbd_ring_ready\n");
+    asm.push_str("    hlt\n");
+    asm.push_str("    jmp kbd_ring_wait_64\n");
+    asm.push_str(".kbd_ring_ready:\n");
+    asm.push_str("    ret\n\n");
+
+    asm.push_str("key_pressed_64:\n");
+    asm.push_str("    # Output: rax = ASCII of a key pressed since the last call, 0 when none\n");
+    asm.push_str("    call kbd_ring_pop_64\n");
+    asm.push_str("    test eax, eax\n");
+    asm.push_str("    js .key_none\n");
+    asm.push_str("    call kbd_translate_64\n");
+    asm.push_str("    test eax, eax\n");
+    asm.push_str("    jz key_pressed_64   # releases and shift\n");
+    asm.push_str("    ret\n");
+    asm.push_str(".key_none:\n");
+    asm.push_str("    xor eax, eax\n");
+    asm.push_str("    ret\n\n");
+
+    asm.push_str("    .section .data\n");
+    asm.push_str("    .balign 16\n");
+    asm.push_str("idt:\n");
+    asm.push_str(&format!("    .zero {}\n", IDT_ENTRIES * IDT_ENTRY_BYTES));
+    asm.push_str("idtr:\n");
+    asm.push_str(&format!("    .word {}\n", IDT_ENTRIES * IDT_ENTRY_BYTES - 1));
+    asm.push_str("    .quad idt\n");
+    asm.push_str("irq_ticks:\n");
+    asm.push_str("    .quad 0\n");
+    asm.push_str("kbd_ring_head:\n");
+    asm.push_str("    .long 0\n");
+    asm.push_str("kbd_ring_tail:\n");
+    asm.push_str("    .long 0\n");
+    asm.push_str("kbd_ring:\n");
+    asm.push_str(&format!("    .zero {}\n", KBD_RING_BYTES));
+    asm.push_str("    .section .text\n");
+    asm
+}
+
+/// List module for earthang
+pub struct ListModule {
+    name: String,
+    description: String,
+    functions: Vec<String>,
+}
+
+impl ListModule {
+    pub fn new() -> Self {
+        Self {
+            name: "list".to_string(),
+            description: "Growable arrays behind list literals and subscripts".to_string(),
+            functions: vec![
+                "len".to_string(),
+                "append".to_string(),
+                "split".to_string(),
+                "str_split_64".to_string(),
+                "list_create_64".to_string(),
+                "list_append_64".to_string(),
+                "list_get_64".to_string(),
+                "list_set_64".to_string(),
+            ],
+        }
+    }
+}
+
+impl Default for ListModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EarthngModule for ListModule {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    
+    fn description(&self) -> &str {
+        &self.description
+    }
+    
+    fn functions(&self) -> Vec<&str> {
+        self.functions.iter().map(|s| s.as_str()).collect()
+    }
+    
+    fn signatures(&self) -> Vec<FunctionSignature> {
+        declare(&[
+            "len(list: list) -> int",
+            "append(list: list, value: int) -> list",
+            "split(text: str, separator: str) -> list",
+            "list_create_64(capacity: int) -> list",
+            "list_append_64(list: list, value: int) -> list",
+            "list_get_64(list: list, index: int) -> int",
+            "list_set_64(list: list, index: int, value: int) -> int",
+        ])
+    }
+    
+    fn dependencies(&self) -> Vec<&str> {
+        // Buffers come from the system module's allocator
+        vec!["system"]
+    }
+    
+    fn required_capabilities(&self) -> Vec<Capability> {
+        vec![Capability::Heap]
+    }
+    
+    fn compile_function(
+        &self,
+        func: &str,
+        _args: &[Expr],
+        _target: &Target,
+        emitter: &mut dyn AssemblyEmitter
+    ) -> Result<String, String> {
+        // The backends lower list syntax themselves and only call into the library
+        Ok(emitter.emit_call(func, &[]))
+    }
+    
+    fn library_code(&self, target: &Target) -> Option<String> {
+        match target {
+            Target::Linux64 => Some(LIST_LIBRARY_LINUX64.to_string()),
+            _ => None,
+        }
+    }
+    
+    fn init(&mut self, _capabilities: &[Capability]) {
+        // List module doesn't require special initialization
+    }
+}
+
+/// A list is a 24-byte header `[length, capacity, data]` pointing at a buffer
+/// of 8-byte elements that doubles whenever it fills up. Each element holds a
+/// counted reference
+const LIST_LIBRARY_LINUX64: &str = "    .section .text
+list_create_64:
+    # Input: rdi = initial capacity; output: rax = empty list
+    push rdi
+    mov rdi, 24
+    call heap_alloc_64
+    mov BYTE PTR [rax - 15], 1  # kind: list
+    pop rdi
+    test rdi, rdi
+    jnz .list_create_sized
+    mov rdi, 4
+.list_create_sized:
+    mov QWORD PTR [rax], 0
+    mov QWORD PTR [rax + 8], rdi
+    push rax
+    shl rdi, 3
+    call heap_alloc_64
+    mov rdi, rax
+    pop rax
+    mov QWORD PTR [rax + 16], rdi
+    ret
+
+list_append_64:
+    # Input: rdi = list, rsi = value; output: rax = list
+    push rcx
+    push rdi
+    mov rdi, rsi
+    call __rc_inc
+    pop rdi
+    mov rcx, QWORD PTR [rdi]
+    cmp rcx, QWORD PTR [rdi + 8]
+    jb .list_append_store
+    call list_grow_64
+.list_append_store:
+    mov rax, QWORD PTR [rdi + 16]
+    mov QWORD PTR [rax + rcx*8], rsi
+    inc rcx
+    mov QWORD PTR [rdi], rcx
+    pop rcx
+    mov rax, rdi
+    ret
+
+list_grow_64:
+    # Input: rdi = list; doubles its capacity, moving the elements if the buffer has to
+    push rsi
+    push rdi
+    mov rax, QWORD PTR [rdi + 8]
+    shl rax, 1
+    mov QWORD PTR [rdi + 8], rax
+    lea rsi, [rax*8]
+    mov rdi, QWORD PTR [rdi + 16]
+    call heap_realloc_64
+    pop rdi
+    mov QWORD PTR [rdi + 16], rax
+    pop rsi
+    ret
+
+list_get_64:
+    # Input: rdi = list, rsi = index; output: rax = element
+    cmp rsi, QWORD PTR [rdi]
+    jae .list_index_error       # unsigned, so negative indices are rejected too
+    mov rax, QWORD PTR [rdi + 16]
+    mov rax, QWORD PTR [rax + rsi*8]
+    ret
+
+list_set_64:
+    # Input: rdi = list, rsi = index, rdx = value; output: rax = value
+    cmp rsi, QWORD PTR [rdi]
+    jae .list_index_error
+    push rdi
+    mov rax, QWORD PTR [rdi + 16]
+    lea rax, [rax + rsi*8]
+    mov rdi, rdx
+    call __rc_inc
+    mov rdi, QWORD PTR [rax]
+    mov QWORD PTR [rax], rdx
+    call __rc_dec               # the element it replaces
+    pop rdi
+    mov rax, rdx
+    ret
+
+.list_index_error:
+    lea rdi, [list_index_error_message]
+    jmp runtime_error_64
+
+list_len_64:
+    # Input: rdi = list; output: rax = number of elements
+    mov rax, QWORD PTR [rdi]
+    ret
+
+str_split_64:
+    # Input: rdi = string, rsi = separator; output: rax = list of fresh copies of
+    # the pieces between separators, empty ones included
+    push rbx
+    push rdi
+    push r8
+    push r9
+    push r12
+    mov r8, rdi
+    mov r9, rsi
+    xor ebx, ebx
+.str_split_measure:
+    cmp BYTE PTR [r9 + rbx], 0
+    je .str_split_measured
+    inc rbx
+    jmp .str_split_measure
+.str_split_measured:
+    test rbx, rbx
+    jz .str_split_empty_separator
+    xor edi, edi
+    call list_create_64
+    mov r12, rax
+    mov rdi, r8
+.str_split_scan:
+    cmp BYTE PTR [rdi], 0
+    je .str_split_last
+    call .str_split_match
+    jne .str_split_next
+    call .str_split_piece
+    add rdi, rbx
+    mov r8, rdi
+    jmp .str_split_scan
+.str_split_next:
+    inc rdi
+    jmp .str_split_scan
+.str_split_last:
+    call .str_split_piece
+    mov rax, r12
+    pop r12
+    pop r9
+    pop r8
+    pop rdi
+    pop rbx
+    ret
+.str_split_empty_separator:
+    lea rdi, [str_split_error_message]
+    jmp runtime_error_64
+
+.str_split_match:
+    # Input: rdi = position in the string, r9 = separator of rbx bytes; output:
+    # ZF set when the separator starts there
+    push rax
+    push rcx
+    xor ecx, ecx
+.str_split_match_loop:
+    cmp rcx, rbx
+    je .str_split_match_done
+    mov al, BYTE PTR [r9 + rcx]
+    cmp al, BYTE PTR [rdi + rcx]
+    jne .str_split_match_done
+    inc rcx
+    jmp .str_split_match_loop
+.str_split_match_done:
+    pop rcx
+    pop rax
+    ret
+
+.str_split_piece:
+    # Input: r8 = start of a piece, rdi = its end, r12 = list; appends a copy
+    push rcx
+    push rsi
+    push rdi
+    mov rcx, rdi
+    sub rcx, r8
+    lea rdi, [rcx + 1]
+    call heap_alloc_64
+    mov rdi, rax
+    mov rsi, r8
+    rep movsb
+    mov BYTE PTR [rdi], 0
+    mov rsi, rax
+    mov rdi, r12
+    call list_append_64
+    pop rdi
+    pop rsi
+    pop rcx
+    ret
+
+    .section .data
+list_index_error_message:
+    .asciz \"list index out of range\"
+str_split_error_message:
+    .asciz \"empty separator\"
+";
+
+/// Dictionary module for earthang
+pub struct DictModule {
+    name: String,
+    description: String,
+    functions: Vec<String>,
+}
+
+impl DictModule {
+    pub fn new() -> Self {
+        Self {
+            name: "dict".to_string(),
+            description: "Hash tables with string keys behind dictionary literals".to_string(),
+            functions: vec![
+                "dict_create_64".to_string(),
+                "dict_set_64".to_string(),
+                "dict_get_64".to_string(),
+                "dict_find_index_64".to_string(),
+            ],
+        }
+    }
+}
+
+impl Default for DictModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EarthngModule for DictModule {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    
+    fn description(&self) -> &str {
+        &self.description
+    }
+    
+    fn functions(&self) -> Vec<&str> {
+        self.functions.iter().map(|s| s.as_str()).collect()
+    }
+    
+    fn signatures(&self) -> Vec<FunctionSignature> {
+        declare(&[
+            "dict_create_64() -> dict",
+            "dict_set_64(dict: dict, key: str, value: int) -> int",
+            "dict_get_64(dict: dict, key: str) -> int",
+            "dict_find_index_64(dict: dict, key: str) -> int",
+        ])
+    }
+    
+    fn dependencies(&self) -> Vec<&str> {
+        // Keys are hashed and compared by the string module, tables come from the system allocator
+        vec!["string", "system"]
+    }
+    
+    fn required_capabilities(&self) -> Vec<Capability> {
+        vec![Capability::Heap]
+    }
+    
+    fn compile_function(
+        &self,
+        func: &str,
+        _args: &[Expr],
+        _target: &Target,
+        emitter: &mut dyn AssemblyEmitter
+    ) -> Result<String, String> {
+        // The backends lower dictionary syntax themselves and only call into the library
+        Ok(emitter.emit_call(func, &[]))
+    }
+    
+    fn library_code(&self, target: &Target) -> Option<String> {
+        match target {
+            Target::Linux64 => Some(DICT_LIBRARY_LINUX64.to_string()),
+            _ => None,
+        }
+    }
+    
+    fn init(&mut self, _capabilities: &[Capability]) {
+        // Dict module doesn't require special initialization
+    }
+}
+
+/// A dictionary is a 32-byte header `[count, capacity, keys, values]`. Keys are
+/// string pointers in an open-addressing table with linear probing; a null key
+/// marks an empty slot. The table doubles before it gets more than 3/4 full.
+/// Keys and values hold counted references
+const DICT_LIBRARY_LINUX64: &str = "    .section .text
+dict_create_64:
+    # Output: rax = empty dictionary with 16 slots
+    push rdi
+    mov rdi, 32
+    call heap_alloc_64
+    mov BYTE PTR [rax - 15], 2  # kind: dictionary
+    mov QWORD PTR [rax], 0
+    mov QWORD PTR [rax + 8], 16
+    mov rdi, rax
+    call dict_alloc_slots_64
+    mov rax, rdi
+    pop rdi
+    ret
+
+dict_alloc_slots_64:
+    # Input: rdi = dictionary; gives it empty key and value arrays of its capacity
+    push rax
+    push rcx
+    push rdi
+    push rsi
+    mov rsi, rdi
+    mov rdi, QWORD PTR [rsi + 8]
+    shl rdi, 3
+    call heap_alloc_64
+    mov QWORD PTR [rsi + 16], rax
+    call heap_alloc_64
+    mov QWORD PTR [rsi + 24], rax
+    mov rdi, QWORD PTR [rsi + 16]
+    mov rcx, QWORD PTR [rsi + 8]
+    xor eax, eax
+    rep stosq
+    pop rsi
+    pop rdi
+    pop rcx
+    pop rax
+    ret
+
+dict_find_slot_64:
+    # Input: rdi = dictionary, rsi = key
+    # Output: rax = slot holding the key, or the empty slot it would go in; rdx = 1 if found
+    push rcx
+    push rdi
+    push r8
+    mov r8, rdi
+    mov rdi, rsi
+    call str_hash_64
+    mov rcx, QWORD PTR [r8 + 8]
+    dec rcx
+    and rax, rcx
+.dict_probe:
+    mov rdx, QWORD PTR [r8 + 16]
+    mov rdi, QWORD PTR [rdx + rax*8]
+    test rdi, rdi
+    jz .dict_probe_empty
+    push rax
+    call str_equal_64
+    mov rdx, rax
+    pop rax
+    test rdx, rdx
+    jnz .dict_probe_found
+    inc rax
+    and rax, rcx
+    jmp .dict_probe
+.dict_probe_empty:
+    xor edx, edx
+    jmp .dict_probe_done
+.dict_probe_found:
+    mov edx, 1
+.dict_probe_done:
+    pop r8
+    pop rdi
+    pop rcx
+    ret
+
+dict_find_index_64:
+    # Input: rdi = dictionary, rsi = key; output: rax = slot index, or -1 when absent
+    push rdx
+    call dict_find_slot_64
+    test rdx, rdx
+    jnz .dict_find_done
+    mov rax, -1
+.dict_find_done:
+    pop rdx
+    ret
+
+dict_get_64:
+    # Input: rdi = dictionary, rsi = key
+    # Output: rax = value; rdx = 0 is the missing-key sentinel
+    call dict_find_slot_64
+    test rdx, rdx
+    jz .dict_get_missing
+    push rcx
+    mov rcx, QWORD PTR [rdi + 24]
+    mov rax, QWORD PTR [rcx + rax*8]
+    pop rcx
+    ret
+.dict_get_missing:
+    xor eax, eax
+    ret
+
+dict_set_64:
+    # Input: rdi = dictionary, rsi = key, rdx = value; output: rax = value
+    push rcx
+    push rdx
+    call dict_find_slot_64
+    test rdx, rdx
+    jnz .dict_set_store
+    mov rcx, QWORD PTR [rdi]
+    inc rcx
+    shl rcx, 2
+    mov rdx, QWORD PTR [rdi + 8]
+    lea rdx, [rdx + rdx*2]
+    cmp rcx, rdx
+    jbe .dict_set_insert
+    call dict_resize_64
+    call dict_find_slot_64
+.dict_set_insert:
+    inc QWORD PTR [rdi]
+    mov rcx, QWORD PTR [rdi + 16]
+    mov QWORD PTR [rcx + rax*8], rsi
+    mov rcx, QWORD PTR [rdi + 24]
+    mov QWORD PTR [rcx + rax*8], 0  # nothing to drop in a fresh slot
+    push rdi
+    mov rdi, rsi
+    call __rc_inc
+    pop rdi
+.dict_set_store:
+    mov rcx, QWORD PTR [rdi + 24]
+    lea rcx, [rcx + rax*8]
+    pop rdx
+    push rdi
+    mov rdi, rdx
+    call __rc_inc
+    mov rdi, QWORD PTR [rcx]
+    mov QWORD PTR [rcx], rdx
+    call __rc_dec               # the value it replaces
+    pop rdi
+    mov rax, rdx
+    pop rcx
+    ret
+
+dict_resize_64:
+    # Input: rdi = dictionary; doubles its capacity and reinserts every entry
+    push rax
+    push rcx
+    push rdx
+    push rsi
+    push r8
+    push r9
+    push r10
+    mov r8, QWORD PTR [rdi + 16]
+    mov r9, QWORD PTR [rdi + 24]
+    mov r10, QWORD PTR [rdi + 8]
+    shl QWORD PTR [rdi + 8], 1
+    call dict_alloc_slots_64
+    xor ecx, ecx
+.dict_resize_loop:
+    cmp rcx, r10
+    je .dict_resize_done
+    mov rsi, QWORD PTR [r8 + rcx*8]
+    test rsi, rsi
+    jz .dict_resize_next
+    call dict_find_slot_64
+    mov rdx, QWORD PTR [rdi + 16]
+    mov QWORD PTR [rdx + rax*8], rsi
+    mov rdx, QWORD PTR [r9 + rcx*8]
+    mov rsi, QWORD PTR [rdi + 24]
+    mov QWORD PTR [rsi + rax*8], rdx
+.dict_resize_next:
+    inc rcx
+    jmp .dict_resize_loop
+.dict_resize_done:
+    push rdi
+    mov rdi, r8
+    call heap_free_64
+    mov rdi, r9
+    call heap_free_64
+    pop rdi
+    pop r10
+    pop r9
+    pop r8
+    pop rsi
+    pop rdx
+    pop rcx
+    pop rax
+    ret
+
+dict_key_error_64:
+    lea rdi, [dict_key_error_message]
+    jmp runtime_error_64
+
+    .section .data
+dict_key_error_message:
+    .asciz \"key not found in dictionary\"
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Position, Span};
+
+    #[test]
+    fn test_extract_required_modules() {
+        let span = Span::single(Position::start());
+        let program = Program {
+            body: vec![Statement::VarDecl {
+                name: "s".to_string(),
+                value: Expr::Call {
+                    func: "concat".to_string(),
+                    args: vec![],
+                    kwargs: HashMap::new(),
+                    span,
+                },
+                type_hint: None,
+                span,
+            }],
+            span,
+            hardware_devices: HashMap::new(),
+        };
+
+        let mut registry = ExtensionRegistry::new();
+        registry.register_module(Box::new(MathModule::new()));
+        registry.register_module(Box::new(StringModule::new()));
+        registry.register_module(Box::new(SystemModule::new()));
+
+        assert_eq!(registry.extract_required_modules(&program).unwrap(), vec!["string", "system"]);
+
+        // List syntax pulls in the list runtime and its allocator
+        registry.register_module(Box::new(ListModule::new()));
+        let program = crate::parser::parse_program("var xs = [1]\nprint(xs[0])\n").unwrap();
+        assert_eq!(registry.extract_required_modules(&program).unwrap(), vec!["list", "system"]);
+
+        // Dictionaries hash their keys with the string module
+        registry.register_module(Box::new(DictModule::new()));
+        let program = crate::parser::parse_program("var d = {\"a\": 1}\nprint(\"a\" in d)\n").unwrap();
+        assert_eq!(registry.extract_required_modules(&program).unwrap(), vec!["dict", "string", "system"]);
+
+        // Subscripting a string calls a routine the program's calls do not name
+        let program = crate::parser::parse_program("var s = \"ab\"\nprint(s[0])\n").unwrap();
+        let required = registry.extract_required_modules(&program).unwrap();
+        assert_eq!(required, vec!["list", "system"]);
+        let assembly = "main:\n    call str_index_64\n    call .local\n";
+        assert_eq!(registry.add_referenced_modules(&required, assembly).unwrap(), vec!["list", "string", "system"]);
+
+        // Methods name their routines; split's builds a list, so the list module has it
+        let program = crate::parser::parse_program("var s = \" a \".strip()\nvar parts = s.split(\",\")\n").unwrap();
+        assert_eq!(registry.extract_required_modules(&program).unwrap(), vec!["list", "string", "system"]);
+    }
+
+    /// Run a BIOS mode payload as a Linux program whose port I/O goes to model
+    /// devices: a keyboard replaying `scancodes`, an ATA master holding `disk`
+    /// (absent when empty) and a UART whose data register writes come back as
+    /// the output. `None` without binutils
+    fn run_on_model_devices(source: &str, scancodes: &[u8], disk: &[u8]) -> Option<String> {
+        use crate::backend::Backend;
+        let program = crate::parser::parse_program(source).unwrap();
+        let asm = crate::backend::Linux64Backend::new()
+            .with_bios_graphics(crate::framebuffer::Framebuffer::vga_mode_13h(), crate::framebuffer::SimdLevel::Sse2)
+            .with_debug_serial(true)
+            .compile_program(&program)
+            .unwrap();
+        assert!(!asm.contains("syscall"), "print() still reaches for stdout");
+
+        let mut harness = asm
+            .replace("in al, 0x64", "call kbd_status_model")
+            .replace("in al, 0x60", "call kbd_data_model")
+            .replace("in al, dx", "call port_in_model")
+            .replace("out dx, al", "call port_out_model")
+            .replace("rep insw", "call ata_insw_model")
+            .replace("rep outsw", "call ata_outsw_model")
+            .replace("    hlt\n", "    call uart_dump\n")
+            .replace(&crate::framebuffer::heap_data(&crate::framebuffer::MemoryLayout::default()), "heap_base:\n    .quad model_heap\nheap_top:\n    .quad model_heap\n");
+        harness.push_str("    .intel_syntax noprefix\n    .section .text\n");
+        harness.push_str("kbd_status_model:\n    mov al, 1           # a scancode is always waiting\n    ret\n");
+        harness.push_str(&format!("kbd_data_model:\n    cmp qword ptr [rip + kbd_model_next], {}\n    jae uart_dump\n", scancodes.len()));
+        harness.push_str("    push rsi\n    lea rsi, [rip + kbd_model_codes]\n    add rsi, [rip + kbd_model_next]\n    mov al, [rsi]\n    inc qword ptr [rip + kbd_model_next]\n    pop rsi\n    ret\n");
+        // ATA status: DRDY, DSC and DRQ with a drive, a floating bus without one
+        let ata_status = if disk.is_empty() { 0xFF } else { 0x58 };
+        harness.push_str(&format!("port_in_model:\n    mov al, 0x20        # transmitter always empty\n    cmp dx, 0x1F7\n    je 1f\n    cmp dx, 0x3F6\n    jne 2f\n1:  mov al, 0x{:02X}\n2:  ret\n", ata_status));
+        // Command writes move the data register to the selected LBA's sector
+        harness.push_str("port_out_model:\n    cmp dx, 0x1F2\n    jb uart_out\n    cmp dx, 0x1F7\n    ja uart_out\n    push rdi\n    push rsi\n    lea rdi, [rip + ata_regs - 0x1F0]\n    movzx esi, dx\n    mov [rdi + rsi], al\n");
+        harness.push_str("    cmp dx, 0x1F7\n    jne 1f\n    movzx esi, byte ptr [rdi + 0x1F5]\n    shl esi, 8\n    mov sil, [rdi + 0x1F4]\n    shl esi, 8\n    mov sil, [rdi + 0x1F3]\n    shl esi, 9\n    mov [rip + ata_position], rsi\n1:  pop rsi\n    pop rdi\n    ret\n");
+        harness.push_str("ata_insw_model:\n    push rsi\n    lea rsi, [rip + model_disk]\n    add rsi, [rip + ata_position]\n    add rcx, rcx\n    add [rip + ata_position], rcx\n    rep movsb\n    pop rsi\n    ret\n");
+        harness.push_str("ata_outsw_model:\n    push rdi\n    lea rdi, [rip + model_disk]\n    add rdi, [rip + ata_position]\n    add rcx, rcx\n    add [rip + ata_position], rcx\n    rep movsb\n    pop rdi\n    ret\n");
+        harness.push_str("uart_out:\n    cmp dx, 0x3FB\n    jne 1f\n    mov [rip + uart_lcr], al\n1:  cmp dx, 0x3F8\n    jne 2f\n    test byte ptr [rip + uart_lcr], 0x80\n    jnz 2f\n");
+        harness.push_str("    push rsi\n    lea rsi, [rip + uart_data]\n    add rsi, [rip + uart_length]\n    mov [rsi], al\n    inc qword ptr [rip + uart_length]\n    pop rsi\n2:  ret\n");
+        harness.push_str("uart_dump:\n    mov eax, 1\n    mov edi, 1\n    lea rsi, [rip + uart_data]\n    mov rdx, [rip + uart_length]\n    syscall\n    mov eax, 60\n    xor edi, edi\n    syscall\n");
+        harness.push_str("    .section .data\nuart_lcr:\n    .byte 0\nuart_length:\n    .quad 0\nuart_data:\n    .zero 256\n    .balign 16\nmodel_heap:\n    .zero 8192\n");
+        harness.push_str("ata_regs:\n    .zero 8\nata_position:\n    .quad 0\nmodel_disk:\n");
+        for byte in disk {
+            harness.push_str(&format!("    .byte 0x{:02X}\n", byte));
+        }
+        harness.push_str("    .zero 2048\n");
+        harness.push_str("kbd_model_next:\n    .quad 0\nkbd_model_codes:\n");
+        for code in scancodes {
+            harness.push_str(&format!("    .byte 0x{:02X}\n", code));
+        }
+
+        let work_dir = std::env::temp_dir().join(format!("earthang_devices_{}_{}_{}", std::process::id(), scancodes.len(), disk.len()));
+        std::fs::create_dir_all(&work_dir).unwrap();
+        let (source, object, exe) = (work_dir.join("devices.s"), work_dir.join("devices.o"), work_dir.join("devices"));
+        std::fs::write(&source, harness).unwrap();
+        let built = crate::compiler::run_tool("as", &["--64".as_ref(), "-o".as_ref(), object.as_os_str(), source.as_os_str()])
+            .and_then(|_| crate::compiler::run_tool("ld", &["-o".as_ref(), exe.as_os_str(), object.as_os_str()]));
+        let output = built.as_ref().ok().map(|_| std::process::Command::new(&exe).output().unwrap());
+        let _ = std::fs::remove_dir_all(&work_dir);
+        match built {
+            Err(e) if e.contains("Failed to run") => None,
+            Err(e) => panic!("{}", e),
+            Ok(_) => Some(String::from_utf8_lossy(&output.unwrap().stdout).into_owned()),
+        }
+    }
+
+    #[test]
+    fn test_serial_print_loopback() {
+        let library = SerialModule::new().library_code(&Target::Linux64).unwrap();
+        // Divisor 1 (115200 baud) behind DLAB, then 8N1 and the FIFOs
+        for setup in ["0x3FB\n    mov al, 0x80", "0x3F8\n    mov al, 0x01", "0x3FB\n    mov al, 0x03", "0x3FA\n    mov al, 0xC7"] {
+            assert!(library.contains(&format!("    mov dx, {}\n    out dx, al\n", setup)), "{}", setup);
+        }
+        let Some(output) = run_on_model_devices("print(\"EG:OK\", 42)\n", &[], &[]) else { return };
+        assert_eq!(output, "EG:OK 42\n");
+    }
+
+    /// Type "Adx", erase the x, finish with "a" and Enter; releases, the extended
+    /// up arrow and the echo of every key are part of the stream
+    #[test]
+    fn test_keyboard_input_loopback() {
+        let library = KeyboardModule::new().library_code(&Target::Linux64).unwrap();
+        assert!(library.contains("    in al, 0x64\n    test al, 1\n") && library.contains("    in al, 0x60\n"));
+        assert_eq!((SCANCODES[0x1E], SCANCODES_SHIFTED[0x1E], SCANCODES[0x39], SCANCODES_SHIFTED[0x02]), (b'a', b'A', b' ', b'!'));
+
+        let scancodes = [0x2A, 0x1E, 0x9E, 0xAA, 0x20, 0xA0, 0x2D, 0xAD, 0x0E, 0x8E, 0xE0, 0x48, 0xE0, 0xC8, 0x1E, 0x9E, 0x1C];
+        let Some(output) = run_on_model_devices("name = input(\"Name? \")\nprint(\"hi\", name)\n", &scancodes, &[]) else { return };
+        assert_eq!(output, "Name? Adx\x08 \x08a\nhi Ada\n");
+    }
+
+    /// Read sector 1 from the model drive, write it to sector 2 and read that back;
+    /// without a drive the bus floats busy and the read times out
+    #[test]
+    fn test_disk_read_write_loopback() {
+        let library = DiskModule::new().library_code(&Target::Linux64).unwrap();
+        for port in ["0x1F0", "0x1F2", "0x1F3", "0x1F6", "0x1F7"] {
+            assert!(library.contains(&format!("    mov dx, {}\n", port)), "{}", port);
+        }
+        assert!(library.contains("    mov al, 0x20        # READ SECTORS\n"));
+        assert!(library.contains("    mov al, 0x30        # WRITE SECTORS\n"));
+
+        let mut disk = vec![0u8; 512];
+        disk.extend_from_slice(b"EG:DISK");
+        let source = "var data: str = disk_read(1, 1)\nprint(data)\nprint(disk_write(2, data))\nvar copy: str = disk_read(2, 1)\nprint(copy)\n";
+        let Some(output) = run_on_model_devices(source, &[], &disk) else { return };
+        assert_eq!(output, "EG:DISK\n0\nEG:DISK\n");
+
+        let Some(output) = run_on_model_devices("print(disk_read(0, 1) == 0)\n", &[], &[]) else { return };
+        assert_eq!(output, "1\n");
+    }
+
+    /// idt_set_gate_64 has to lay a gate out the way idt_entry encodes it
+    #[test]
+    fn test_idt_entry_encoding() {
+        let handler = 0x1122_3344_5566_7788u64;
+        let entry = idt_entry(handler, crate::mode_transition::CODE64_SELECTOR);
+        assert_eq!(entry, [0x88, 0x77, 0x18, 0x00, 0x00, 0x8E, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, 0, 0, 0, 0]);
+        assert_eq!(entry[5] & 0x80, 0x80, "present");
+        assert_eq!(entry[5] & 0x0F, 0xE, "64-bit interrupt gate");
+
+        let library = interrupts_library_x86_64();
+        for icw in ["0x11        # ICW1: initialise, ICW4 follows\n    out 0x20, al\n    out 0xA0, al", "0x20        # ICW2: master vector offset\n    out 0x21, al", "0x28        # ICW2: slave vector offset\n    out 0xA1, al"] {
+            assert!(library.contains(&format!("    mov al, {}\n", icw)), "{}", icw);
+        }
+
+        let mut program = String::from("    .intel_syntax noprefix\n    .section .text\n    .globl _start\n_start:\n");
+        program.push_str(&format!("    mov edi, 3\n    mov rsi, 0x{:X}\n    call idt_set_gate_64\n", handler));
+        program.push_str("    mov eax, 1\n    mov edi, 1\n    lea rsi, [idt + 48]\n    mov edx, 16\n    syscall\n    mov eax, 60\n    xor edi, edi\n    syscall\n");
+        program.push_str(&library);
+        program.push_str(&KeyboardModule::new().library_code(&Target::Linux64).unwrap());
+
+        let work_dir = std::env::temp_dir().join(format!("earthang_idt_{}", std::process::id()));
+        std::fs::create_dir_all(&work_dir).unwrap();
+        let (source, object, exe) = (work_dir.join("idt.s"), work_dir.join("idt.o"), work_dir.join("idt"));
+        std::fs::write(&source, program).unwrap();
+        let built = crate::compiler::run_tool("as", &["--64".as_ref(), "-o".as_ref(), object.as_os_str(), source.as_os_str()])
+            .and_then(|_| crate::compiler::run_tool("ld", &["-o".as_ref(), exe.as_os_str(), object.as_os_str()]));
+        let output = built.as_ref().ok().map(|_| std::process::Command::new(&exe).output().unwrap());
+        let _ = std::fs::remove_dir_all(&work_dir);
+        match built {
+            Err(e) if e.contains("Failed to run") => {}
+            Err(e) => panic!("{}", e),
+            Ok(_) => assert_eq!(output.unwrap().stdout, entry),
+        }
+    }
+
+    /// nanosleep gets a 16-byte timespec in rdi: seconds at [rsp], nanoseconds
+    /// at [rsp + 8], and the remainder right after it
+    #[test]
+    fn test_sleep_timespec_layout() {
+        let library = SystemModule::new().library_code(&Target::Linux64).unwrap();
+        let sleep = &library[library.find("sleep_ms_64:").unwrap()..library.find("runtime_error_64:").unwrap()];
+        for line in ["    sub rsp, 32\n", "    mov QWORD PTR [rsp], rax        # tv_sec\n", "    mov QWORD PTR [rsp + 8], rdx    # tv_nsec\n", "    mov rdi, rsp\n", "    lea rsi, [rsp + 16]", "    mov rax, 35", "    add rsp, 32\n"] {
+            assert!(sleep.contains(line), "{}", line);
+        }
+        assert!(sleep.contains("    mov ecx, 1000\n    div rcx\n    imul rdx, rdx, 1000000\n"));
+
+        // BIOS mode counts milliseconds down on PIT channel 2 instead
+        assert!(pit_sleep_routine().contains("    mov ax, 1193\n    out 0x42, al\n"));
+    }
+
+    #[test]
+    fn test_function_signatures() {
+        let modules: Vec<Box<dyn EarthngModule>> = vec![
+            Box::new(MathModule::new()), Box::new(StringModule::new()), Box::new(SystemModule::new()),
+            Box::new(OsModule::new()), Box::new(SerialModule::new()), Box::new(DiskModule::new()),
+            Box::new(KeyboardModule::new()), Box::new(InterruptsModule::new()), Box::new(ListModule::new()),
+            Box::new(DictModule::new()),
+        ];
+        for module in &modules {
+            for signature in module.signatures() {
+                assert!(module.supports_function(&signature.name), "{} declares {}", module.name(), signature.name);
+                assert_eq!(FunctionSignature::parse(&signature.to_string()).unwrap(), signature);
+            }
+        }
+        let input = SystemModule::new().signature("input").unwrap();
+        assert_eq!(input.to_string(), "input(prompt?: str) -> str");
+        assert!(input.accepts(0) && input.accepts(1) && !input.accepts(2));
+        assert!(FunctionSignature::parse("f(a?: int, b: int) -> int").is_err());
+        assert!(FunctionSignature::parse("f(a int) -> int").is_err());
+
+        let mut registry = ExtensionRegistry::new();
+        registry.register_module(Box::new(SystemModule::new()));
+        registry.register_module(Box::new(DictModule::new()));
+        let program = crate::parser::parse_program("var d = dict_create_64()\ndict_set_64(d, \"k\")\n").unwrap();
+        assert_eq!(
+            registry.check_signatures(&program).unwrap_err(),
+            "dict_set_64() takes 3 arguments but 2 were given at 2:1; it is declared as dict_set_64(dict: dict, key: str, value: int) -> int"
+        );
+        let program = crate::parser::parse_program("print(input(\"a\", \"b\"))\n").unwrap();
+        assert_eq!(
+            registry.check_signatures(&program).unwrap_err(),
+            "input() takes 0 to 1 argument but 2 were given at 1:7; it is declared as input(prompt?: str) -> str"
+        );
+        // The program's own functions are not held to the module's signature
+        let program = crate::parser::parse_program("def dict_set_64(d): return d\nprint(dict_set_64(1))\n").unwrap();
+        assert!(registry.check_signatures(&program).is_ok());
+    }
+
+    /// A module that is nothing but a function list and a blob of assembly
+    struct BlobModule {
+        name: &'static str,
+        functions: Vec<&'static str>,
+        code: &'static str,
+    }
+
+    impl EarthngModule for BlobModule {
+        fn name(&self) -> &str { self.name }
+        fn description(&self) -> &str { "test blob" }
+        fn functions(&self) -> Vec<&str> { self.functions.clone() }
+        fn compile_function(&self, _func: &str, _args: &[Expr], _target: &Target, _emitter: &mut dyn AssemblyEmitter) -> Result<String, String> {
+            Ok(String::new())
+        }
+        fn init(&mut self, _capabilities: &[Capability]) {}
+        fn library_code(&self, target: &Target) -> Option<String> {
+            (*target == Target::Linux64).then(|| self.code.to_string())
+        }
+    }
+
+    #[test]
+    fn test_symbol_index() {
+        let mut registry = ExtensionRegistry::new();
+        registry.register_module(Box::new(SystemModule::new()));
+        registry.register_module(Box::new(BlobModule {
+            name: "utils",
+            functions: vec!["random_int"],
+            code: "random_int_64:\n    mov rax, [rip + random_state]\n    ret\nrandom_state:\n    .quad 1\n",
+        }));
+        registry.register_module(Box::new(BlobModule {
+            name: "random",
+            functions: vec!["random_seed"],
+            code: "random_int_64:\n    mov rax, [rip + random_state]  # xorshift\n    ret\nrandom_state:\n    .quad 1\n",
+        }));
+        registry.register_module(Box::new(BlobModule { name: "hdmi", functions: vec![], code: "hdmi_draw_line_64:\n1:  ret\n" }));
+        registry.register_module(Box::new(BlobModule {
+            name: "vga",
+            functions: vec!["vga_line"],
+            code: "vga_line_64:\n    call hdmi_draw_line_64\n    call heap_alloc_64\n    ret\n.vga_message:\n    .asciz \"random_int_64\"\n",
+        }));
+
+        assert_eq!(registry.who_defines("heap_alloc_64").unwrap().name(), "system");
+        assert_eq!(registry.who_defines("random_state").unwrap().name(), "utils");
+        assert_eq!(registry.who_defines("hdmi_draw_line_64").unwrap().name(), "hdmi");
+        assert!(registry.who_defines("1").is_none() && registry.who_defines("print_string").is_none());
+
+        // The line routine and the allocator come along with vga; the string
+        // literal naming random_int_64 is not a reference
+        let program = crate::parser::parse_program("vga_line()\n").unwrap();
+        assert_eq!(registry.extract_required_modules(&program).unwrap(), vec!["hdmi", "system", "vga"]);
+
+        let program = crate::parser::parse_program("random_int()\n").unwrap();
+        assert_eq!(registry.extract_required_modules(&program).unwrap(), vec!["utils"]);
+        let program = crate::parser::parse_program("random_int()\nrandom_seed()\n").unwrap();
+        assert_eq!(registry.extract_required_modules(&program).unwrap_err(), "Symbol 'random_int_64' is defined by both the utils and random modules");
+    }
+
+    #[test]
+    fn test_module_target_support() {
+        let mut registry = ExtensionRegistry::new();
+        registry.register_module(Box::new(MathModule::new()));
+        registry.register_module(Box::new(SystemModule::new()));
+        registry.register_module(Box::new(ListModule::new()));
+        let modules = |source: &str| registry.extract_required_modules(&crate::parser::parse_program(source).unwrap()).unwrap();
+
+        let lists = modules("var xs = [1]\nprint(xs[0])\n");
+        assert!(registry.check_targets(&lists, &Target::Linux64).is_ok());
+        assert_eq!(registry.check_targets(&lists, &Target::RiscV64).unwrap_err(), "The list module has no code for riscv64; it supports linux64");
+        // The other backends bring their own sleep
+        assert!(registry.check_targets(&modules("sleep(10)\n"), &Target::Aarch64).is_ok());
+        assert_eq!(registry.check_targets(&modules("print(sqrt(4))\n"), &Target::Linux64).unwrap_err(), "The math module has no code for linux64; it supports no target");
+        assert!(registry.find_module("system").unwrap().supports_target(&Target::RiscV64));
+        assert!(!registry.find_module("list").unwrap().supports_target(&Target::Aarch64));
+    }
+
+    #[test]
+    fn test_serial_module_needs_port_io() {
+        let mut registry = ExtensionRegistry::new();
+        registry.register_module(Box::new(SerialModule::new()));
+        let program = crate::parser::parse_program("serial_write_string_64(\"hi\")\n").unwrap();
+        let modules = registry.extract_required_modules(&program).unwrap();
+        assert_eq!(modules, vec!["serial"]);
+        assert!(registry.required_capabilities(&modules).contains(&Capability::PortIO));
+    }
+}
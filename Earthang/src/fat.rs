@@ -0,0 +1,437 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use crate::disk_image::{BOOT_CODE_LIMIT, BOOT_SIGNATURE, SECTOR_SIZE};
+
+/// Offset of the boot code, right after the extended BIOS parameter block
+pub const BOOT_CODE_OFFSET: usize = 0x3E;
+
+/// Serial number written to new volumes; fixed so the same inputs give the same image
+const VOLUME_ID: u32 = 0x4541_5254;
+
+/// 1980-01-01, the earliest date a directory entry can hold
+const DIRECTORY_DATE: u16 = (1 << 5) | 1;
+
+const ATTR_VOLUME_LABEL: u8 = 0x08;
+const ATTR_ARCHIVE: u8 = 0x20;
+
+/// `cli; hlt; jmp $-2` for images that are not meant to boot
+const HALT_CODE: [u8; 4] = [0xFA, 0xF4, 0xEB, 0xFD];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType {
+    Fat12,
+    Fat16,
+}
+
+impl FatType {
+    fn end_of_chain(self) -> u16 {
+        match self {
+            FatType::Fat12 => 0xFFF,
+            FatType::Fat16 => 0xFFFF,
+        }
+    }
+
+    fn label(self) -> &'static [u8; 8] {
+        match self {
+            FatType::Fat12 => b"FAT12   ",
+            FatType::Fat16 => b"FAT16   ",
+        }
+    }
+}
+
+/// BIOS parameter block and the extended fields FAT12/16 keep behind it
+#[derive(Debug, Clone, PartialEq)]
+pub struct BiosParameterBlock {
+    pub bytes_per_sector: u16,
+    pub sectors_per_cluster: u8,
+    pub reserved_sectors: u16,
+    pub fat_count: u8,
+    pub root_entries: u16,
+    pub total_sectors: u32,
+    pub media: u8,
+    pub sectors_per_fat: u16,
+    pub sectors_per_track: u16,
+    pub heads: u16,
+    pub drive_number: u8,
+    pub volume_id: u32,
+    pub volume_label: [u8; 11],
+}
+
+impl BiosParameterBlock {
+    /// 1.44 MB 3.5" floppy
+    pub fn floppy_1440() -> Self {
+        Self {
+            bytes_per_sector: SECTOR_SIZE as u16,
+            sectors_per_cluster: 1,
+            reserved_sectors: 1,
+            fat_count: 2,
+            root_entries: 224,
+            total_sectors: 2880,
+            media: 0xF0,
+            sectors_per_fat: 9,
+            sectors_per_track: 18,
+            heads: 2,
+            drive_number: 0x00,
+            volume_id: VOLUME_ID,
+            volume_label: *b"EARTHANG   ",
+        }
+    }
+
+    /// FAT16 volume of `total_sectors`, with the smallest clusters that keep the count in range
+    pub fn fat16(total_sectors: u32) -> Result<Self, String> {
+        let mut bpb = Self {
+            root_entries: 512,
+            total_sectors,
+            media: 0xF8,
+            sectors_per_track: 63,
+            heads: 16,
+            drive_number: 0x80,
+            ..Self::floppy_1440()
+        };
+
+        for shift in 0..=6 {
+            bpb.sectors_per_cluster = 1 << shift;
+            bpb.sectors_per_fat = 1;
+            // The FAT grows with the cluster count it describes; settle on a size that covers it
+            loop {
+                let needed = ((bpb.cluster_count() + 2) * 2).div_ceil(SECTOR_SIZE as u32) as u16;
+                if needed <= bpb.sectors_per_fat {
+                    break;
+                }
+                bpb.sectors_per_fat = needed;
+            }
+            if bpb.fat_type() == Ok(FatType::Fat16) {
+                return Ok(bpb);
+            }
+        }
+        Err(format!("{} sectors cannot hold a FAT16 volume; it needs between 4085 and 65524 clusters", total_sectors))
+    }
+
+    fn root_dir_sectors(&self) -> u32 {
+        (self.root_entries as u32 * 32).div_ceil(self.bytes_per_sector as u32)
+    }
+
+    fn first_root_dir_sector(&self) -> u32 {
+        self.reserved_sectors as u32 + self.fat_count as u32 * self.sectors_per_fat as u32
+    }
+
+    fn first_data_sector(&self) -> u32 {
+        self.first_root_dir_sector() + self.root_dir_sectors()
+    }
+
+    pub fn cluster_count(&self) -> u32 {
+        self.total_sectors.saturating_sub(self.first_data_sector()) / self.sectors_per_cluster as u32
+    }
+
+    fn cluster_bytes(&self) -> usize {
+        self.sectors_per_cluster as usize * self.bytes_per_sector as usize
+    }
+
+    /// The FAT type is decided by the cluster count alone, as every FAT driver does
+    pub fn fat_type(&self) -> Result<FatType, String> {
+        match self.cluster_count() {
+            0..4085 => Ok(FatType::Fat12),
+            4085..65525 => Ok(FatType::Fat16),
+            count => Err(format!("{} clusters is too many for FAT12/16", count)),
+        }
+    }
+
+    /// Write the fields from offset 0x0B up to the boot code
+    fn write(&self, sector: &mut [u8]) -> Result<(), String> {
+        let fat_type = self.fat_type()?;
+        let (total_16, total_32) = match u16::try_from(self.total_sectors) {
+            Ok(total) => (total, 0),
+            Err(_) => (0, self.total_sectors),
+        };
+
+        sector[0x0B..0x0D].copy_from_slice(&self.bytes_per_sector.to_le_bytes());
+        sector[0x0D] = self.sectors_per_cluster;
+        sector[0x0E..0x10].copy_from_slice(&self.reserved_sectors.to_le_bytes());
+        sector[0x10] = self.fat_count;
+        sector[0x11..0x13].copy_from_slice(&self.root_entries.to_le_bytes());
+        sector[0x13..0x15].copy_from_slice(&total_16.to_le_bytes());
+        sector[0x15] = self.media;
+        sector[0x16..0x18].copy_from_slice(&self.sectors_per_fat.to_le_bytes());
+        sector[0x18..0x1A].copy_from_slice(&self.sectors_per_track.to_le_bytes());
+        sector[0x1A..0x1C].copy_from_slice(&self.heads.to_le_bytes());
+        sector[0x1C..0x20].copy_from_slice(&0u32.to_le_bytes()); // hidden sectors
+        sector[0x20..0x24].copy_from_slice(&total_32.to_le_bytes());
+        sector[0x24] = self.drive_number;
+        sector[0x26] = 0x29; // extended boot signature: the next three fields are valid
+        sector[0x27..0x2B].copy_from_slice(&self.volume_id.to_le_bytes());
+        sector[0x2B..0x36].copy_from_slice(&self.volume_label);
+        sector[0x36..0x3E].copy_from_slice(fat_type.label());
+        Ok(())
+    }
+
+    /// Read the parameter block back from a boot sector
+    pub fn parse(sector: &[u8]) -> Result<Self, String> {
+        if sector.len() < SECTOR_SIZE || sector[510..512] != BOOT_SIGNATURE {
+            return Err("Not a boot sector: missing 0x55AA signature".to_string());
+        }
+        let u16_at = |at: usize| u16::from_le_bytes([sector[at], sector[at + 1]]);
+        let u32_at = |at: usize| u32::from_le_bytes(sector[at..at + 4].try_into().unwrap());
+
+        let total_sectors = match u16_at(0x13) {
+            0 => u32_at(0x20),
+            total => total as u32,
+        };
+        let bpb = Self {
+            bytes_per_sector: u16_at(0x0B),
+            sectors_per_cluster: sector[0x0D],
+            reserved_sectors: u16_at(0x0E),
+            fat_count: sector[0x10],
+            root_entries: u16_at(0x11),
+            total_sectors,
+            media: sector[0x15],
+            sectors_per_fat: u16_at(0x16),
+            sectors_per_track: u16_at(0x18),
+            heads: u16_at(0x1A),
+            drive_number: sector[0x24],
+            volume_id: u32_at(0x27),
+            volume_label: sector[0x2B..0x36].try_into().unwrap(),
+        };
+        if bpb.bytes_per_sector as usize != SECTOR_SIZE || bpb.sectors_per_cluster == 0 {
+            return Err("Unsupported BIOS parameter block".to_string());
+        }
+        Ok(bpb)
+    }
+}
+
+/// Builder for a FAT12/16 volume holding files in its root directory
+#[derive(Debug, Clone)]
+pub struct FatImageBuilder {
+    bpb: BiosParameterBlock,
+    boot_code: Vec<u8>,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl FatImageBuilder {
+    pub fn new(bpb: BiosParameterBlock) -> Self {
+        Self { bpb, boot_code: HALT_CODE.to_vec(), files: Vec::new() }
+    }
+
+    /// Code placed after the parameter block; it runs at 0x7C3E
+    pub fn with_boot_code(mut self, code: Vec<u8>) -> Self {
+        self.boot_code = code;
+        self
+    }
+
+    /// Add a file to the root directory under its 8.3 name
+    pub fn with_file(mut self, name: &str, data: Vec<u8>) -> Self {
+        self.files.push((name.to_string(), data));
+        self
+    }
+
+    pub fn build(&self) -> Result<Vec<u8>, String> {
+        let bpb = &self.bpb;
+        let fat_type = bpb.fat_type()?;
+        let mut image = vec![0u8; bpb.total_sectors as usize * SECTOR_SIZE];
+
+        // Boot sector: a short jump over the parameter block, then the code
+        if self.boot_code.len() > BOOT_CODE_LIMIT - BOOT_CODE_OFFSET {
+            return Err(format!(
+                "Boot code is {} bytes; at most {} fit behind the BIOS parameter block",
+                self.boot_code.len(), BOOT_CODE_LIMIT - BOOT_CODE_OFFSET
+            ));
+        }
+        image[0..3].copy_from_slice(&[0xEB, (BOOT_CODE_OFFSET - 2) as u8, 0x90]);
+        image[3..11].copy_from_slice(b"EARTHANG");
+        bpb.write(&mut image[..SECTOR_SIZE])?;
+        image[BOOT_CODE_OFFSET..BOOT_CODE_OFFSET + self.boot_code.len()].copy_from_slice(&self.boot_code);
+        image[510..512].copy_from_slice(&BOOT_SIGNATURE);
+
+        if self.files.len() + 1 > bpb.root_entries as usize {
+            return Err(format!("The root directory holds at most {} files", bpb.root_entries - 1));
+        }
+
+        let mut fat = vec![0u16; bpb.cluster_count() as usize + 2];
+        fat[0] = 0xFF00 | bpb.media as u16;
+        fat[1] = 0xFFFF;
+        let mut directory = vec![directory_entry(&bpb.volume_label, ATTR_VOLUME_LABEL, 0, 0)];
+        let mut next_cluster = 2usize;
+
+        for (name, data) in &self.files {
+            let short_name = short_name(name)?;
+            if directory.iter().any(|entry| entry[..11] == short_name) {
+                return Err(format!("File '{}' is added twice", name));
+            }
+
+            let clusters = data.len().div_ceil(bpb.cluster_bytes());
+            if next_cluster + clusters > fat.len() {
+                return Err(format!("File '{}' does not fit in the remaining space of the image", name));
+            }
+
+            let first = if clusters == 0 { 0 } else { next_cluster };
+            for (i, chunk) in data.chunks(bpb.cluster_bytes()).enumerate() {
+                let cluster = next_cluster + i;
+                fat[cluster] = if i + 1 == clusters { 0xFFFF } else { cluster as u16 + 1 };
+                let at = (bpb.first_data_sector() as usize + (cluster - 2) * bpb.sectors_per_cluster as usize) * SECTOR_SIZE;
+                image[at..at + chunk.len()].copy_from_slice(chunk);
+            }
+            next_cluster += clusters;
+            directory.push(directory_entry(&short_name, ATTR_ARCHIVE, first as u16, data.len() as u32));
+        }
+
+        let encoded = encode_fat(&fat, fat_type, bpb.sectors_per_fat as usize * SECTOR_SIZE);
+        for copy in 0..bpb.fat_count as usize {
+            let at = (bpb.reserved_sectors as usize + copy * bpb.sectors_per_fat as usize) * SECTOR_SIZE;
+            image[at..at + encoded.len()].copy_from_slice(&encoded);
+        }
+
+        let root = bpb.first_root_dir_sector() as usize * SECTOR_SIZE;
+        for (i, entry) in directory.iter().enumerate() {
+            image[root + i * 32..root + (i + 1) * 32].copy_from_slice(entry);
+        }
+
+        Ok(image)
+    }
+}
+
+/// Pack FAT entries, truncating the 0xFFFF markers to the width of the FAT type
+fn encode_fat(entries: &[u16], fat_type: FatType, size: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; size];
+    for (n, &entry) in entries.iter().enumerate() {
+        let value = entry & fat_type.end_of_chain();
+        match fat_type {
+            FatType::Fat16 => bytes[n * 2..n * 2 + 2].copy_from_slice(&value.to_le_bytes()),
+            // Two 12-bit entries share three bytes
+            FatType::Fat12 if n.is_multiple_of(2) => {
+                let at = n * 3 / 2;
+                bytes[at] = value as u8;
+                bytes[at + 1] = (bytes[at + 1] & 0xF0) | (value >> 8) as u8;
+            }
+            FatType::Fat12 => {
+                let at = n * 3 / 2;
+                bytes[at] = (bytes[at] & 0x0F) | ((value & 0x0F) << 4) as u8;
+                bytes[at + 1] = (value >> 4) as u8;
+            }
+        }
+    }
+    bytes
+}
+
+fn directory_entry(name: &[u8; 11], attributes: u8, first_cluster: u16, size: u32) -> [u8; 32] {
+    let mut entry = [0u8; 32];
+    entry[..11].copy_from_slice(name);
+    entry[11] = attributes;
+    entry[16..18].copy_from_slice(&DIRECTORY_DATE.to_le_bytes()); // created
+    entry[18..20].copy_from_slice(&DIRECTORY_DATE.to_le_bytes()); // accessed
+    entry[24..26].copy_from_slice(&DIRECTORY_DATE.to_le_bytes()); // modified
+    entry[26..28].copy_from_slice(&first_cluster.to_le_bytes());
+    entry[28..32].copy_from_slice(&size.to_le_bytes());
+    entry
+}
+
+/// Space-padded, upper-case 8.3 form of `name`
+pub fn short_name(name: &str) -> Result<[u8; 11], String> {
+    let (stem, extension) = name.rsplit_once('.').unwrap_or((name, ""));
+    let valid = |part: &str, max: usize| {
+        !part.is_empty() && part.len() <= max
+            && part.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'()-@^_`{}~".contains(c))
+    };
+    if !valid(stem, 8) || !(extension.is_empty() || valid(extension, 3)) {
+        return Err(format!("'{}' is not a valid 8.3 file name", name));
+    }
+
+    let mut short = [b' '; 11];
+    short[..stem.len()].copy_from_slice(stem.to_ascii_uppercase().as_bytes());
+    short[8..8 + extension.len()].copy_from_slice(extension.to_ascii_uppercase().as_bytes());
+    Ok(short)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Follow a file's cluster chain using only the structures on the image
+    fn read_file(image: &[u8], name: &str) -> Option<Vec<u8>> {
+        let bpb = BiosParameterBlock::parse(image).unwrap();
+        let fat_type = bpb.fat_type().unwrap();
+        let fat = &image[bpb.reserved_sectors as usize * SECTOR_SIZE..];
+        let entry_at = |n: usize| match fat_type {
+            FatType::Fat16 => u16::from_le_bytes([fat[n * 2], fat[n * 2 + 1]]),
+            FatType::Fat12 => {
+                let pair = u16::from_le_bytes([fat[n * 3 / 2], fat[n * 3 / 2 + 1]]);
+                if n.is_multiple_of(2) { pair & 0xFFF } else { pair >> 4 }
+            }
+        };
+
+        let root = bpb.first_root_dir_sector() as usize * SECTOR_SIZE;
+        let entry = image[root..root + bpb.root_entries as usize * 32].chunks(32)
+            .find(|entry| entry[..11] == short_name(name).unwrap())?;
+        let size = u32::from_le_bytes(entry[28..32].try_into().unwrap()) as usize;
+        let mut cluster = u16::from_le_bytes([entry[26], entry[27]]) as usize;
+
+        let mut data = Vec::new();
+        while data.len() < size {
+            let at = (bpb.first_data_sector() as usize + (cluster - 2) * bpb.sectors_per_cluster as usize) * SECTOR_SIZE;
+            data.extend_from_slice(&image[at..at + bpb.cluster_bytes()]);
+            cluster = entry_at(cluster) as usize;
+        }
+        assert_eq!(cluster as u16, fat_type.end_of_chain());
+        data.truncate(size);
+        Some(data)
+    }
+
+    #[test]
+    fn test_fat12_floppy() {
+        let kernel: Vec<u8> = (0..1300).map(|i| i as u8).collect();
+        let image = FatImageBuilder::new(BiosParameterBlock::floppy_1440())
+            .with_boot_code(vec![0xCD, 0x18])
+            .with_file("kernel.bin", kernel.clone())
+            .with_file("empty", Vec::new())
+            .with_file("logo.bmp", vec![7; 513])
+            .build()
+            .unwrap();
+
+        assert_eq!(image.len(), 1_474_560);
+        assert_eq!(&image[0..3], &[0xEB, 0x3C, 0x90]);
+        assert_eq!(&image[0x3E..0x40], &[0xCD, 0x18]);
+        let bpb = BiosParameterBlock::parse(&image).unwrap();
+        assert_eq!(bpb, BiosParameterBlock::floppy_1440());
+        assert_eq!(bpb.fat_type(), Ok(FatType::Fat12));
+        assert_eq!(&image[0x36..0x3E], b"FAT12   ");
+
+        // Media descriptor entries, then kernel.bin in clusters 2-4 and logo.bmp in 5-6
+        assert_eq!(&image[512..521], &[0xF0, 0xFF, 0xFF, 0x03, 0x40, 0x00, 0xFF, 0x6F, 0x00]);
+        assert_eq!(image[512..512 + 9 * 512], image[512 + 9 * 512..512 + 18 * 512]);
+
+        assert_eq!(read_file(&image, "KERNEL.BIN"), Some(kernel));
+        assert_eq!(read_file(&image, "logo.bmp"), Some(vec![7; 513]));
+        let root = 19 * SECTOR_SIZE;
+        assert_eq!(&image[root..root + 12], b"EARTHANG   \x08");
+        assert_eq!(&image[root + 64..root + 75], b"EMPTY      ");
+        assert_eq!(&image[root + 90..root + 92], &[0, 0]);
+    }
+
+    #[test]
+    fn test_fat16_and_errors() {
+        let bpb = BiosParameterBlock::fat16(32768).unwrap();
+        assert_eq!(bpb.fat_type(), Ok(FatType::Fat16));
+        let data = vec![0xAB; 5000];
+        let image = FatImageBuilder::new(bpb.clone()).with_file("data.bin", data.clone()).build().unwrap();
+        assert_eq!(BiosParameterBlock::parse(&image).unwrap(), bpb);
+        assert_eq!(read_file(&image, "DATA.BIN"), Some(data));
+        assert_eq!(&image[0x36..0x3E], b"FAT16   ");
+
+        assert!(BiosParameterBlock::fat16(2880).is_err());
+        assert!(short_name("toolongname.bin").is_err());
+        assert!(short_name("a.b.c").is_err());
+        let floppy = FatImageBuilder::new(BiosParameterBlock::floppy_1440());
+        assert!(floppy.clone().with_file("a", vec![]).with_file("A", vec![]).build().is_err());
+        assert!(floppy.clone().with_file("big", vec![0; 1_474_560]).build().is_err());
+        assert!(floppy.with_boot_code(vec![0; 449]).build().is_err());
+    }
+}
@@ -0,0 +1,1431 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::path::Path;
+use crate::backend::{literal_integer, Backend, Linux64Backend};
+use crate::compiler::CompilerConfig;
+use crate::disk_image::{DiskImage, BOOT_SECTOR_ADDRESS, SECTOR_SIZE, STAGE2_LOAD_ADDRESS, STAGE2_MEMORY_END};
+use crate::font::{FIRST_GLYPH, FONT_8X16, GLYPH_COUNT, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::image::{EmbeddedImage, Pixel, PixelFormat, PALETTE_CUBE_START};
+use crate::lua_frontend::{Expr, Program, Span, Statement};
+use crate::mode_transition::{BootLayout, ModeTransitionEmitter};
+use crate::simd;
+use crate::symbol_map::{object_symbols, ObjectSymbol};
+
+/// Builtins drawing into the framebuffer of a `--bios-mode` program
+pub const FRAMEBUFFER_BUILTINS: [&str; 9] = ["fb_init", "fb_fill", "fb_rect", "fb_line", "fb_present", "fb_text", "fb_print", "fb_set_palette", "image"];
+
+/// Builtins naming a color by its components; they fold to the value the
+/// framebuffer's pixel format stores
+pub const COLOR_BUILTINS: [&str; 2] = ["rgb", "rgba"];
+
+/// Builtins drawing text; the font is only linked into programs calling them
+pub const TEXT_BUILTINS: [&str; 2] = ["fb_text", "fb_print"];
+
+/// VGA mode 13h: 320x200 with one palette index per pixel
+pub const VGA_MODE_13H: u8 = 0x13;
+pub const VGA_FRAMEBUFFER: u64 = 0xA0000;
+pub const VGA_WIDTH: i64 = 320;
+pub const VGA_HEIGHT: i64 = 200;
+
+/// Linker symbol marking where the payload's data follows its code
+const PAYLOAD_DATA_SYMBOL: &str = "payload_data";
+
+/// Back buffer of programs calling fb_present without choosing one: above the
+/// loader, inside the first GiB the transition identity-maps
+pub const DEFAULT_BACK_BUFFER: u64 = 0x200000;
+const IDENTITY_MAPPED_END: u64 = 0x4000_0000;
+
+/// Default start of the memory heap_alloc_64 hands out in BIOS mode, up to the
+/// end of the identity map
+pub const BIOS_HEAP: u64 = 0x100_0000;
+
+/// The heap must start above the loader, the BIOS and video memory
+const HEAP_LOWEST: u64 = 0x10_0000;
+
+/// VGA input status register; bit 3 is set during vertical retrace
+const VGA_STATUS_PORT: u16 = 0x3DA;
+/// VGA DAC write index; red, green and blue of each entry then go to the next port
+const VGA_DAC_WRITE_PORT: u16 = 0x3C8;
+
+/// Widest vector stores fb_fill may use; the CPU booting the image must support them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimdLevel {
+    Sse2,
+    Avx2,
+    Avx512,
+}
+
+/// Framebuffer description the runtime routines clip and address against.
+/// The base is where drawing goes: the back buffer when there is one
+pub const FB_INFO_BASE: u32 = 0;
+pub const FB_INFO_WIDTH: u32 = 8;
+pub const FB_INFO_HEIGHT: u32 = 12;
+pub const FB_INFO_PITCH: u32 = 16;
+pub const FB_INFO_BYTES_PER_PIXEL: u32 = 20;
+pub const FB_INFO_FRONT: u32 = 24;
+
+/// Geometry and buffers of the linear framebuffer a `--bios-mode` program draws
+/// into. The drawing primitives store one byte per pixel; `fb_present` copies
+/// `bytes_per_pixel` bytes per pixel, so wider formats present correctly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    /// Bytes from the start of one row to the next, at least `row_bytes`
+    pub pitch: u32,
+    pub bytes_per_pixel: u32,
+    /// The buffer on screen
+    pub front: u64,
+    /// Off-screen buffer all drawing targets; fb_present copies it to `front`
+    pub back: Option<u64>,
+    /// Make fb_present wait for the VGA vertical retrace first
+    pub vsync: bool,
+}
+
+impl Framebuffer {
+    pub fn vga_mode_13h() -> Self {
+        Self {
+            width: VGA_WIDTH as u32,
+            height: VGA_HEIGHT as u32,
+            pitch: VGA_WIDTH as u32,
+            bytes_per_pixel: 1,
+            front: VGA_FRAMEBUFFER,
+            back: None,
+            vsync: false,
+        }
+    }
+
+    pub fn with_back_buffer(mut self, address: u64) -> Self {
+        self.back = Some(address);
+        self
+    }
+
+    pub fn with_vsync(mut self, enabled: bool) -> Self {
+        self.vsync = enabled;
+        self
+    }
+
+    /// Where the drawing primitives write
+    pub fn draw_base(&self) -> u64 {
+        self.back.unwrap_or(self.front)
+    }
+
+    /// Visible bytes of one row, without the padding up to `pitch`
+    pub fn row_bytes(&self) -> u32 {
+        self.width * self.bytes_per_pixel
+    }
+
+    fn size(&self) -> u64 {
+        self.pitch as u64 * self.height as u64
+    }
+
+    fn check(&self) -> Result<(), String> {
+        let Some(back) = self.back else { return Ok(()) };
+        if back + self.size() > IDENTITY_MAPPED_END {
+            return Err(format!("Back buffer at 0x{:X} must end inside the identity-mapped first GiB", back));
+        }
+        if back < self.front + self.size() && self.front < back + self.size() {
+            return Err(format!("Back buffer at 0x{:X} overlaps the framebuffer at 0x{:X}", back, self.front));
+        }
+        Ok(())
+    }
+}
+
+/// Where a `--bios-mode` image puts its code, stack and heap. The defaults are
+/// the addresses images have always used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryLayout {
+    /// Where stage 1 loads the transition code and the payload after it
+    pub load_address: u32,
+    /// Stack pointer of the transition and the payload, growing down
+    pub stack_top: u32,
+    /// First byte heap_alloc_64 hands out
+    pub heap_base: u64,
+    /// Bytes the heap may grow to; without a size it ends with the identity map
+    pub heap_size: Option<u64>,
+}
+
+impl Default for MemoryLayout {
+    fn default() -> Self {
+        Self { load_address: STAGE2_LOAD_ADDRESS, stack_top: BOOT_SECTOR_ADDRESS, heap_base: BIOS_HEAP, heap_size: None }
+    }
+}
+
+impl MemoryLayout {
+    pub fn with_load_address(mut self, address: u32) -> Self {
+        self.load_address = address;
+        self
+    }
+
+    pub fn with_stack_top(mut self, address: u32) -> Self {
+        self.stack_top = address;
+        self
+    }
+
+    pub fn with_heap_base(mut self, address: u64) -> Self {
+        self.heap_base = address;
+        self
+    }
+
+    pub fn with_heap_size(mut self, bytes: u64) -> Self {
+        self.heap_size = Some(bytes);
+        self
+    }
+
+    /// First byte past the heap, where heap_alloc_64 halts
+    pub fn heap_end(&self) -> u64 {
+        self.heap_size.map_or(IDENTITY_MAPPED_END, |size| self.heap_base.saturating_add(size))
+    }
+
+    /// The heap has to fit the identity map and stay clear of the back buffer.
+    /// The load address and stack are checked by the mode transition
+    fn check(&self, framebuffer: &Framebuffer) -> Result<(), String> {
+        if self.heap_base < HEAP_LOWEST || !self.heap_base.is_multiple_of(16) {
+            return Err(format!("Heap at 0x{:X} must be 16-byte aligned and start at 0x{:X} or above", self.heap_base, HEAP_LOWEST));
+        }
+        if self.heap_size == Some(0) || self.heap_end() > IDENTITY_MAPPED_END {
+            return Err(format!("Heap at 0x{:X} must end inside the identity-mapped first GiB", self.heap_base));
+        }
+        if let Some(back) = framebuffer.back {
+            if back < self.heap_end() && self.heap_base < back + framebuffer.size() {
+                return Err(format!("Back buffer at 0x{:X} overlaps the heap at 0x{:X}", back, self.heap_base));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Inline code for a framebuffer call whose arguments are all literals, or `None`
+/// when the call has to go through the runtime routine of the same name. fb_init
+/// stays a compile-time check: the loader sets the mode before leaving real mode
+pub fn inline_call(func: &str, args: &[Expr], framebuffer: &Framebuffer, simd: SimdLevel, span: Span) -> Result<Option<String>, String> {
+    let expected = match func {
+        "fb_present" => 0,
+        "fb_init" => 2,
+        "fb_fill" | "fb_print" => 1,
+        "fb_set_palette" => 4,
+        "fb_rect" | "fb_line" | "fb_text" => 5,
+        _ => unreachable!("not a framebuffer builtin"),
+    };
+    if args.len() != expected {
+        return Err(format!("{}() takes {} arguments but {} were given at {}", func, expected, args.len(), span));
+    }
+    let values: Option<Vec<i64>> = args.iter().map(|arg| literal_argument(arg, framebuffer)).collect();
+
+    match (func, values) {
+        ("fb_text" | "fb_print", _) => Ok(None),
+        ("fb_init", Some(values)) if values == [VGA_WIDTH, VGA_HEIGHT] => {
+            Ok(Some(format!("    # fb_init({}, {}): mode 0x{:02X} is set by the loader\n", VGA_WIDTH, VGA_HEIGHT, VGA_MODE_13H)))
+        }
+        ("fb_init", _) => Err(format!("fb_init only supports {}x{} at {}", VGA_WIDTH, VGA_HEIGHT, span)),
+        ("fb_fill", Some(values)) => fill(values[0], framebuffer, simd, span).map(Some),
+        ("fb_rect", Some(values)) => rect(&values, framebuffer, span).map(Some),
+        ("fb_line", Some(values)) => line(&values, framebuffer, span),
+        ("fb_set_palette", Some(values)) => set_palette(&values, span).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// The framebuffer value of an rgb(r, g, b) or rgba(r, g, b, a) call, whose
+/// components must be literals from 0 to 255
+pub fn color_value(func: &str, args: &[Expr], framebuffer: &Framebuffer, span: Span) -> Result<i64, String> {
+    let expected = if func == "rgba" { 4 } else { 3 };
+    if args.len() != expected {
+        return Err(format!("{}() takes {} arguments but {} were given at {}", func, expected, args.len(), span));
+    }
+    let mut components = [0xFF; 4];
+    for (component, arg) in components.iter_mut().zip(args) {
+        *component = literal_integer(arg).and_then(|value| u8::try_from(value).ok())
+            .ok_or_else(|| format!("{}() components must be integer literals from 0 to 255 at {}", func, span))?;
+    }
+    let [r, g, b, a] = components;
+    let format = PixelFormat::for_bytes_per_pixel(framebuffer.bytes_per_pixel)?;
+    Ok(Pixel::new(r, g, b, a).to_format(format) as i64)
+}
+
+/// An integer literal, or a color call `color_value` can fold
+fn literal_argument(expr: &Expr, framebuffer: &Framebuffer) -> Option<i64> {
+    match expr {
+        Expr::Call { func, args, span, .. } if COLOR_BUILTINS.contains(&func.as_str()) => color_value(func, args, framebuffer, *span).ok(),
+        _ => literal_integer(expr),
+    }
+}
+
+/// The callable drawing routines, System V style: fb_fill(color in edi),
+/// fb_rect(x in edi, y in esi, w in edx, h in ecx, color in r8d),
+/// fb_line(x0 in edi, y0 in esi, x1 in edx, y1 in ecx, color in r8d) and
+/// fb_set_palette(index in edi, r in esi, g in edx, b in ecx), plus fb_present(). Coordinates are signed and clipped against `fb_info`; only
+/// caller-saved registers are clobbered
+pub fn runtime_routines(framebuffer: &Framebuffer, simd: SimdLevel) -> String {
+    let info = |field: u32| format!("[rip + fb_info + {}]", field);
+    let (broadcast, width, store) = broadcast(simd);
+
+    let mut asm = String::from("\n# ========== FRAMEBUFFER ROUTINES ==========\n");
+    asm.push_str("fb_fill:\n");
+    asm.push_str("    movzx eax, dil\n");
+    asm.push_str("    imul eax, eax, 0x01010101\n");
+    asm.push_str(&broadcast);
+    asm.push_str(&format!("    mov rdi, {}\n", info(FB_INFO_BASE)));
+    asm.push_str(&format!("    mov ecx, {}\n", info(FB_INFO_PITCH)));
+    asm.push_str(&format!("    imul ecx, {}\n", info(FB_INFO_HEIGHT)));
+    asm.push_str(&format!("    shr ecx, {}        # whole {}-byte stores\n", width.trailing_zeros(), width));
+    asm.push_str(&store_loop(simd, width, &store));
+    asm.push_str("    ret\n\n");
+
+    asm.push_str("fb_rect:\n");
+    asm.push_str("    add rdx, rdi        # right edge\n");
+    asm.push_str("    add rcx, rsi        # bottom edge\n");
+    asm.push_str("    xor eax, eax\n");
+    asm.push_str("    cmp rdi, rax\n");
+    asm.push_str("    cmovl rdi, rax\n");
+    asm.push_str("    cmp rsi, rax\n");
+    asm.push_str("    cmovl rsi, rax\n");
+    asm.push_str(&format!("    mov eax, {}\n", info(FB_INFO_WIDTH)));
+    asm.push_str("    cmp rdx, rax\n");
+    asm.push_str("    cmovg rdx, rax\n");
+    asm.push_str(&format!("    mov eax, {}\n", info(FB_INFO_HEIGHT)));
+    asm.push_str("    cmp rcx, rax\n");
+    asm.push_str("    cmovg rcx, rax\n");
+    asm.push_str("    sub rdx, rdi        # clipped width\n");
+    asm.push_str("    jle 2f\n");
+    asm.push_str("    sub rcx, rsi        # clipped height\n");
+    asm.push_str("    jle 2f\n");
+    asm.push_str(&format!("    mov r9d, {}\n", info(FB_INFO_PITCH)));
+    asm.push_str("    imul rsi, r9\n");
+    asm.push_str("    add rdi, rsi\n");
+    asm.push_str(&format!("    add rdi, {}        # base + y * pitch + x\n", info(FB_INFO_BASE)));
+    asm.push_str("    mov eax, r8d\n");
+    asm.push_str("    mov r8, rcx\n");
+    asm.push_str("1:  mov r10, rdi\n");
+    asm.push_str("    mov rcx, rdx\n");
+    asm.push_str("    rep stosb\n");
+    asm.push_str("    lea rdi, [r10 + r9]\n");
+    asm.push_str("    dec r8\n");
+    asm.push_str("    jnz 1b\n");
+    asm.push_str("2:  ret\n\n");
+
+    // Bresenham as in line_pixels; pixels off the screen are stepped over, not drawn
+    asm.push_str("fb_line:\n");
+    asm.push_str("    push rbx\n");
+    asm.push_str("    push r12\n");
+    asm.push_str("    push r13\n");
+    asm.push_str("    push r14\n");
+    asm.push_str("    mov r9, rdx\n");
+    asm.push_str("    sub r9, rdi\n");
+    asm.push_str("    mov r10, 1          # x step\n");
+    asm.push_str("    jge 1f\n");
+    asm.push_str("    neg r9\n");
+    asm.push_str("    neg r10\n");
+    asm.push_str("1:  mov r11, rcx\n");
+    asm.push_str("    sub r11, rsi\n");
+    asm.push_str("    mov r12, 1          # y step\n");
+    asm.push_str("    jge 2f\n");
+    asm.push_str("    neg r11\n");
+    asm.push_str("    neg r12\n");
+    asm.push_str("2:  neg r11             # dy = -|y1 - y0|\n");
+    asm.push_str("    lea r13, [r9 + r11] # error\n");
+    asm.push_str(&format!("    mov ebx, {}\n", info(FB_INFO_WIDTH)));
+    asm.push_str(&format!("    mov r14d, {}\n", info(FB_INFO_HEIGHT)));
+    asm.push_str("3:  cmp rdi, rbx        # unsigned, so negative coordinates fail too\n");
+    asm.push_str("    jae 4f\n");
+    asm.push_str("    cmp rsi, r14\n");
+    asm.push_str("    jae 4f\n");
+    asm.push_str(&format!("    mov eax, {}\n", info(FB_INFO_PITCH)));
+    asm.push_str("    imul rax, rsi\n");
+    asm.push_str("    add rax, rdi\n");
+    asm.push_str(&format!("    add rax, {}\n", info(FB_INFO_BASE)));
+    asm.push_str("    mov byte ptr [rax], r8b\n");
+    asm.push_str("4:  cmp rdi, rdx\n");
+    asm.push_str("    jne 5f\n");
+    asm.push_str("    cmp rsi, rcx\n");
+    asm.push_str("    je 7f\n");
+    asm.push_str("5:  lea rax, [r13 + r13]\n");
+    asm.push_str("    cmp rax, r11\n");
+    asm.push_str("    jl 6f\n");
+    asm.push_str("    add r13, r11\n");
+    asm.push_str("    add rdi, r10\n");
+    asm.push_str("6:  cmp rax, r9\n");
+    asm.push_str("    jg 3b\n");
+    asm.push_str("    add r13, r9\n");
+    asm.push_str("    add rsi, r12\n");
+    asm.push_str("    jmp 3b\n");
+    asm.push_str("7:  pop r14\n");
+    asm.push_str("    pop r13\n");
+    asm.push_str("    pop r12\n");
+    asm.push_str("    pop rbx\n");
+    asm.push_str("    ret\n\n");
+
+    // The DAC takes 6 bits per channel
+    asm.push_str("fb_set_palette:\n");
+    asm.push_str("    mov r8d, edx\n");
+    asm.push_str(&format!("    mov dx, 0x{:X}\n", VGA_DAC_WRITE_PORT));
+    asm.push_str("    mov eax, edi\n");
+    asm.push_str("    out dx, al\n");
+    asm.push_str("    inc dx\n");
+    for reg in ["esi", "r8d", "ecx"] {
+        asm.push_str(&format!("    mov eax, {}\n", reg));
+        asm.push_str("    shr al, 2\n");
+        asm.push_str("    out dx, al\n");
+    }
+    asm.push_str("    ret\n\n");
+    asm.push_str(&present(framebuffer, simd));
+    asm
+}
+
+/// fb_present: copy the visible bytes of every back buffer row to the front
+/// buffer, leaving the padding up to the pitch alone. Rows go through the
+/// vector registers when both buffers and the pitch keep every row aligned
+fn present(framebuffer: &Framebuffer, simd: SimdLevel) -> String {
+    let info = |field: u32| format!("[rip + fb_info + {}]", field);
+    let mut asm = String::from("fb_present:\n");
+    let Some(back) = framebuffer.back else {
+        asm.push_str("    ret                 # single buffered: drawing is already visible\n");
+        return asm;
+    };
+
+    if framebuffer.vsync {
+        // Let a retrace in progress finish, then wait for the next one to start
+        asm.push_str(&format!("    mov dx, 0x{:X}\n", VGA_STATUS_PORT));
+        asm.push_str("1:  in al, dx\n");
+        asm.push_str("    test al, 8\n");
+        asm.push_str("    jnz 1b\n");
+        asm.push_str("2:  in al, dx\n");
+        asm.push_str("    test al, 8\n");
+        asm.push_str("    jz 2b\n");
+    }
+    asm.push_str(&format!("    mov rsi, {}\n", info(FB_INFO_BASE)));
+    asm.push_str(&format!("    mov rdi, {}\n", info(FB_INFO_FRONT)));
+    asm.push_str(&format!("    mov r10d, {}\n", info(FB_INFO_WIDTH)));
+    asm.push_str(&format!("    imul r10d, {}        # bytes per row\n", info(FB_INFO_BYTES_PER_PIXEL)));
+    asm.push_str(&format!("    mov r9d, {}\n", info(FB_INFO_PITCH)));
+    asm.push_str(&format!("    mov r8d, {}\n", info(FB_INFO_HEIGHT)));
+    asm.push_str("    test r8d, r8d\n");
+    asm.push_str("    jz 5f\n");
+    asm.push_str("3:  mov r11, rsi\n");
+    asm.push_str("    mov rdx, rdi\n");
+
+    let (load, width, store) = copy_vector(simd);
+    let aligned = [back, framebuffer.front, framebuffer.pitch as u64].iter().all(|value| value % width as u64 == 0);
+    if aligned {
+        asm.push_str("    mov ecx, r10d\n");
+        asm.push_str(&format!("    shr ecx, {}\n", width.trailing_zeros()));
+        asm.push_str("    jz 4f\n");
+        asm.push_str("6:\n");
+        asm.push_str(&load);
+        asm.push_str(&store);
+        asm.push_str(&format!("    add rsi, {}\n", width));
+        asm.push_str(&format!("    add rdi, {}\n", width));
+        asm.push_str("    dec ecx\n");
+        asm.push_str("    jnz 6b\n");
+        asm.push_str("4:  mov ecx, r10d\n");
+        asm.push_str(&format!("    and ecx, {}        # bytes after the last whole vector\n", width - 1));
+    } else {
+        asm.push_str("    mov ecx, r10d\n");
+    }
+    asm.push_str("    rep movsb\n");
+    asm.push_str("    lea rsi, [r11 + r9]\n");
+    asm.push_str("    lea rdi, [rdx + r9]\n");
+    asm.push_str("    dec r8d\n");
+    asm.push_str("    jnz 3b\n");
+    if aligned && simd == SimdLevel::Avx2 {
+        asm.push_str(&simd::gas_bytes(&simd::vzeroupper(), "vzeroupper"));
+    }
+    asm.push_str("5:  ret\n");
+    asm
+}
+
+/// fb_text(x in edi, y in esi, text in rdx, fg in ecx, bg in r8d) draws a string
+/// with its top left corner at a pixel position. fb_print(text in rdi) draws at
+/// a text cursor that starts in the top left corner, moves to the next line on
+/// '\n' or at the right edge, back one column on '\b', and scrolls the screen
+/// up one text row when it passes the bottom. Both go through fb_glyph, which clips pixel by pixel
+pub fn text_routines() -> String {
+    let info = |field: u32| format!("[rip + fb_info + {}]", field);
+    let mut asm = String::from("\n# ========== FRAMEBUFFER TEXT ==========\n");
+
+    // fb_glyph(c in edi, x in rsi, y in rdx, fg in ecx, bg in r8d)
+    asm.push_str("fb_glyph:\n");
+    for reg in ["rbx", "r12", "r13", "r14", "r15"] {
+        asm.push_str(&format!("    push {}\n", reg));
+    }
+    asm.push_str(&format!("    mov r12d, {}\n", info(FB_INFO_WIDTH)));
+    asm.push_str(&format!("    mov r13d, {}\n", info(FB_INFO_HEIGHT)));
+    asm.push_str(&format!("    mov r14d, {}\n", info(FB_INFO_PITCH)));
+    asm.push_str(&format!("    sub edi, {}\n", FIRST_GLYPH));
+    asm.push_str(&format!("    cmp edi, {}\n", GLYPH_COUNT - 1));
+    asm.push_str("    jbe 1f\n");
+    asm.push_str(&format!("    mov edi, {}        # '?'\n", b'?' - FIRST_GLYPH));
+    asm.push_str(&format!("1:  shl edi, {}\n", GLYPH_HEIGHT.trailing_zeros()));
+    asm.push_str("    lea r9, [rip + fb_font]\n");
+    asm.push_str("    add r9, rdi         # glyph rows\n");
+    asm.push_str(&format!("    mov r10d, {}\n", GLYPH_HEIGHT));
+    asm.push_str("2:  movzx eax, byte ptr [r9]\n");
+    asm.push_str("    mov r11, rsi\n");
+    asm.push_str(&format!("    mov edi, {}\n", GLYPH_WIDTH));
+    asm.push_str("3:  mov ebx, r8d\n");
+    asm.push_str("    test al, 0x80\n");
+    asm.push_str("    cmovnz ebx, ecx\n");
+    asm.push_str("    cmp r11, r12        # unsigned, so negative coordinates fail too\n");
+    asm.push_str("    jae 4f\n");
+    asm.push_str("    cmp rdx, r13\n");
+    asm.push_str("    jae 4f\n");
+    asm.push_str("    mov r15, rdx\n");
+    asm.push_str("    imul r15, r14\n");
+    asm.push_str("    add r15, r11\n");
+    asm.push_str(&format!("    add r15, {}\n", info(FB_INFO_BASE)));
+    asm.push_str("    mov byte ptr [r15], bl\n");
+    asm.push_str("4:  shl al, 1\n");
+    asm.push_str("    inc r11\n");
+    asm.push_str("    dec edi\n");
+    asm.push_str("    jnz 3b\n");
+    asm.push_str("    inc r9\n");
+    asm.push_str("    inc rdx\n");
+    asm.push_str("    dec r10d\n");
+    asm.push_str("    jnz 2b\n");
+    for reg in ["r15", "r14", "r13", "r12", "rbx"] {
+        asm.push_str(&format!("    pop {}\n", reg));
+    }
+    asm.push_str("    ret\n\n");
+
+    asm.push_str("fb_text:\n");
+    for reg in ["rbx", "r12", "r13", "r14", "r15"] {
+        asm.push_str(&format!("    push {}\n", reg));
+    }
+    asm.push_str("    mov rbx, rdx\n");
+    asm.push_str("    mov r12, rdi\n");
+    asm.push_str("    mov r13, rsi\n");
+    asm.push_str("    mov r14, rcx\n");
+    asm.push_str("    mov r15, r8\n");
+    asm.push_str("1:  movzx edi, byte ptr [rbx]\n");
+    asm.push_str("    test edi, edi\n");
+    asm.push_str("    jz 2f\n");
+    asm.push_str("    mov rsi, r12\n");
+    asm.push_str("    mov rdx, r13\n");
+    asm.push_str("    mov rcx, r14\n");
+    asm.push_str("    mov r8, r15\n");
+    asm.push_str("    call fb_glyph\n");
+    asm.push_str(&format!("    add r12, {}\n", GLYPH_WIDTH));
+    asm.push_str("    inc rbx\n");
+    asm.push_str("    jmp 1b\n");
+    asm.push_str("2:\n");
+    for reg in ["r15", "r14", "r13", "r12", "rbx"] {
+        asm.push_str(&format!("    pop {}\n", reg));
+    }
+    asm.push_str("    ret\n\n");
+
+    asm.push_str("fb_print:\n");
+    asm.push_str("    push rbx\n");
+    asm.push_str("    mov rbx, rdi\n");
+    asm.push_str("1:  movzx edi, byte ptr [rbx]\n");
+    asm.push_str("    test edi, edi\n");
+    asm.push_str("    jz 5f\n");
+    asm.push_str("    inc rbx\n");
+    asm.push_str("    cmp edi, 10\n");
+    asm.push_str("    je 3f\n");
+    asm.push_str("    cmp edi, 8\n");
+    asm.push_str("    je 6f\n");
+    asm.push_str("    mov rsi, [rip + fb_cursor]\n");
+    asm.push_str(&format!("    shl rsi, {}\n", GLYPH_WIDTH.trailing_zeros()));
+    asm.push_str("    mov rdx, [rip + fb_cursor + 8]\n");
+    asm.push_str(&format!("    shl rdx, {}\n", GLYPH_HEIGHT.trailing_zeros()));
+    asm.push_str("    movzx ecx, byte ptr [rip + fb_text_colors]\n");
+    asm.push_str("    movzx r8d, byte ptr [rip + fb_text_colors + 1]\n");
+    asm.push_str("    call fb_glyph\n");
+    asm.push_str("    mov rax, [rip + fb_cursor]\n");
+    asm.push_str("    inc rax\n");
+    asm.push_str("    mov [rip + fb_cursor], rax\n");
+    asm.push_str(&format!("    mov ecx, {}\n", info(FB_INFO_WIDTH)));
+    asm.push_str(&format!("    shr ecx, {}        # columns\n", GLYPH_WIDTH.trailing_zeros()));
+    asm.push_str("    cmp rax, rcx\n");
+    asm.push_str("    jb 1b\n");
+    asm.push_str("3:  mov qword ptr [rip + fb_cursor], 0\n");
+    asm.push_str("    mov rax, [rip + fb_cursor + 8]\n");
+    asm.push_str("    inc rax\n");
+    asm.push_str(&format!("    mov ecx, {}\n", info(FB_INFO_HEIGHT)));
+    asm.push_str(&format!("    shr ecx, {}        # rows\n", GLYPH_HEIGHT.trailing_zeros()));
+    asm.push_str("    cmp rax, rcx\n");
+    asm.push_str("    jb 4f\n");
+    asm.push_str("    call fb_scroll\n");
+    asm.push_str(&format!("    mov eax, {}\n", info(FB_INFO_HEIGHT)));
+    asm.push_str(&format!("    shr eax, {}\n", GLYPH_HEIGHT.trailing_zeros()));
+    asm.push_str("    dec eax             # stay on the last row\n");
+    asm.push_str("4:  mov [rip + fb_cursor + 8], rax\n");
+    asm.push_str("    jmp 1b\n");
+    asm.push_str("5:  pop rbx\n");
+    asm.push_str("    ret\n");
+    asm.push_str("6:  mov rax, [rip + fb_cursor]\n");
+    asm.push_str("    test rax, rax       # not past the start of the row\n");
+    asm.push_str("    jz 1b\n");
+    asm.push_str("    dec rax\n");
+    asm.push_str("    mov [rip + fb_cursor], rax\n");
+    asm.push_str("    jmp 1b\n\n");
+
+    // Move every text row but the first up by one, then clear the last
+    asm.push_str("fb_scroll:\n");
+    asm.push_str(&format!("    mov rdi, {}\n", info(FB_INFO_BASE)));
+    asm.push_str(&format!("    mov eax, {}\n", info(FB_INFO_PITCH)));
+    asm.push_str(&format!("    shl eax, {}        # bytes per text row\n", GLYPH_HEIGHT.trailing_zeros()));
+    asm.push_str("    lea rsi, [rdi + rax]\n");
+    asm.push_str(&format!("    mov ecx, {}\n", info(FB_INFO_HEIGHT)));
+    asm.push_str(&format!("    shr ecx, {}\n", GLYPH_HEIGHT.trailing_zeros()));
+    asm.push_str("    dec ecx\n");
+    asm.push_str("    imul ecx, eax\n");
+    asm.push_str("    rep movsb\n");
+    asm.push_str("    mov ecx, eax\n");
+    asm.push_str("    movzx eax, byte ptr [rip + fb_text_colors + 1]\n");
+    asm.push_str("    rep stosb\n");
+    asm.push_str("    ret\n");
+    asm
+}
+
+/// heap_alloc_64(size in rdi) for BIOS mode: a bump allocator from the heap
+/// base that never frees, halting when it reaches the end of `memory`'s heap.
+/// With `refcounting` the bump allocator becomes the heap_grow_64 under the
+/// shared size-class one
+pub fn heap_routine(refcounting: bool, memory: &MemoryLayout) -> String {
+    let mut asm = String::from("\n# ========== BIOS HEAP ==========\n");
+    if refcounting {
+        asm.push_str("heap_grow_64:\n");
+        asm.push_str("    push rdi\n");
+        asm.push_str("    mov rax, [rip + heap_top]\n");
+        asm.push_str("    add rdi, rax\n");
+    } else {
+        asm.push_str("heap_alloc_64:\n");
+        asm.push_str("    mov rax, [rip + heap_top]\n");
+        asm.push_str("    lea rdi, [rax + rdi + 7]\n");
+        asm.push_str("    and rdi, -8\n");
+    }
+    asm.push_str(&format!("    cmp rdi, 0x{:X}\n", memory.heap_end()));
+    asm.push_str("    ja .halt\n");
+    asm.push_str("    mov [rip + heap_top], rdi\n");
+    if refcounting {
+        asm.push_str("    pop rdi\n");
+        asm.push_str("    ret\n");
+        asm.push_str(crate::extension::REFCOUNT_LIBRARY_X86_64);
+        asm.push_str("    .section .text\n");
+    } else {
+        asm.push_str("    ret\n");
+    }
+    asm
+}
+
+/// Bounds of the memory `heap_routine` has handed out so far
+pub fn heap_data(memory: &MemoryLayout) -> String {
+    format!("heap_base:\n    .quad 0x{:X}\nheap_top:\n    .quad 0x{:X}\n", memory.heap_base, memory.heap_base)
+}
+
+/// Cursor, fb_print colors and font of `text_routines`
+pub fn text_data() -> String {
+    let mut data = String::from("fb_cursor:\n");
+    data.push_str("    .quad 0, 0          # column, row\n");
+    data.push_str("fb_text_colors:\n");
+    data.push_str("    .byte 15, 0         # foreground, background\n");
+    data.push_str("fb_font:\n");
+    for glyph in FONT_8X16.chunks(GLYPH_HEIGHT as usize) {
+        let rows: Vec<String> = glyph.iter().map(|row| format!("0x{:02X}", row)).collect();
+        data.push_str(&format!("    .byte {}\n", rows.join(", ")));
+    }
+    data.push('\n');
+    data
+}
+
+/// Index into `images` of the image `image(name, x, y)` draws. The name has to be
+/// a string literal naming a file passed with --embed-image
+pub fn image_index(args: &[Expr], images: &[EmbeddedImage], span: Span) -> Result<usize, String> {
+    if args.len() != 3 {
+        return Err(format!("image() takes 3 arguments but {} were given at {}", args.len(), span));
+    }
+    let Expr::String(name, _) = &args[0] else {
+        return Err(format!("image() needs the file name as a string literal at {}", args[0].span()));
+    };
+    let file_name = Path::new(name).file_name().map(|name| name.to_string_lossy());
+    images.iter().position(|image| Some(image.name.as_str()) == file_name.as_deref())
+        .ok_or_else(|| format!("Image '{}' is not embedded; pass it with --embed-image at {}", name, span))
+}
+
+/// fb_blit(pixels in rdi, x in rsi, y in rdx, width in ecx, height in r8d) copies
+/// an image whose rows are `width` pixels apart, clipped against `fb_info`
+pub fn blit_routine() -> String {
+    let info = |field: u32| format!("[rip + fb_info + {}]", field);
+    let mut asm = String::from("\n# ========== FRAMEBUFFER IMAGES ==========\n");
+    asm.push_str("fb_blit:\n");
+    for reg in ["rbx", "r12", "r13"] {
+        asm.push_str(&format!("    push {}\n", reg));
+    }
+    asm.push_str(&format!("    mov ebx, {}        # bytes per pixel\n", info(FB_INFO_BYTES_PER_PIXEL)));
+    asm.push_str("    mov r9, rcx\n");
+    asm.push_str("    imul r9, rbx        # source stride\n");
+    asm.push_str("    lea r10, [rsi + rcx] # right edge\n");
+    asm.push_str("    lea r11, [rdx + r8]  # bottom edge\n");
+    asm.push_str("    test rsi, rsi\n");
+    asm.push_str("    jge 1f\n");
+    asm.push_str("    mov rax, rsi\n");
+    asm.push_str("    imul rax, rbx\n");
+    asm.push_str("    sub rdi, rax        # skip the columns left of the screen\n");
+    asm.push_str("    xor esi, esi\n");
+    asm.push_str("1:  test rdx, rdx\n");
+    asm.push_str("    jge 2f\n");
+    asm.push_str("    mov rax, rdx\n");
+    asm.push_str("    imul rax, r9\n");
+    asm.push_str("    sub rdi, rax        # and the rows above it\n");
+    asm.push_str("    xor edx, edx\n");
+    asm.push_str(&format!("2:  mov eax, {}\n", info(FB_INFO_WIDTH)));
+    asm.push_str("    cmp r10, rax\n");
+    asm.push_str("    cmovg r10, rax\n");
+    asm.push_str(&format!("    mov eax, {}\n", info(FB_INFO_HEIGHT)));
+    asm.push_str("    cmp r11, rax\n");
+    asm.push_str("    cmovg r11, rax\n");
+    asm.push_str("    sub r10, rsi\n");
+    asm.push_str("    jle 4f\n");
+    asm.push_str("    imul r10, rbx       # bytes per visible row\n");
+    asm.push_str("    sub r11, rdx\n");
+    asm.push_str("    jle 4f\n");
+    asm.push_str(&format!("    mov r8d, {}\n", info(FB_INFO_PITCH)));
+    asm.push_str("    mov rax, rdx\n");
+    asm.push_str("    imul rax, r8\n");
+    asm.push_str("    imul rsi, rbx\n");
+    asm.push_str("    add rax, rsi\n");
+    asm.push_str(&format!("    add rax, {}\n", info(FB_INFO_BASE)));
+    asm.push_str("    mov r12, rdi\n");
+    asm.push_str("    mov r13, rax\n");
+    asm.push_str("3:  mov rsi, r12\n");
+    asm.push_str("    mov rdi, r13\n");
+    asm.push_str("    mov rcx, r10\n");
+    asm.push_str("    rep movsb\n");
+    asm.push_str("    add r12, r9\n");
+    asm.push_str("    add r13, r8\n");
+    asm.push_str("    dec r11\n");
+    asm.push_str("    jnz 3b\n");
+    asm.push_str("4:\n");
+    for reg in ["r13", "r12", "rbx"] {
+        asm.push_str(&format!("    pop {}\n", reg));
+    }
+    asm.push_str("    ret\n");
+    asm
+}
+
+/// Load the color cube palette-mode images are converted to into the VGA DAC.
+/// Runs before main, so only programs embedding images change the palette
+pub fn palette_routine() -> String {
+    let mut asm = String::from("fb_load_palette:\n");
+    asm.push_str(&format!("    mov dx, 0x{:X}\n", VGA_DAC_WRITE_PORT));
+    asm.push_str(&format!("    mov al, {}\n", PALETTE_CUBE_START));
+    asm.push_str("    out dx, al\n");
+    asm.push_str("    inc dx\n");
+    asm.push_str("    lea rsi, [rip + fb_palette]\n");
+    asm.push_str(&format!("    mov ecx, {}\n", crate::image::palette_cube_dac().len()));
+    asm.push_str("    rep outsb\n");
+    asm.push_str("    ret\n");
+    asm
+}
+
+/// Pixel data of every embedded image, labelled `fb_image_<index>`, and the
+/// palette palette-mode images need
+pub fn image_data(images: &[EmbeddedImage], framebuffer: &Framebuffer) -> String {
+    let mut data = String::new();
+    if framebuffer.bytes_per_pixel == 1 {
+        let entries: Vec<String> = crate::image::palette_cube_dac().iter().map(|v| v.to_string()).collect();
+        data.push_str("fb_palette:\n");
+        data.push_str(&format!("    .byte {}\n", entries.join(", ")));
+    }
+    for (index, image) in images.iter().enumerate() {
+        data.push_str(&format!("fb_image_{}:  # {} ({}x{})\n", index, image.name, image.width, image.height));
+        for chunk in image.pixels.chunks(16) {
+            let bytes: Vec<String> = chunk.iter().map(|b| format!("0x{:02X}", b)).collect();
+            data.push_str(&format!("    .byte {}\n", bytes.join(", ")));
+        }
+    }
+    data.push('\n');
+    data
+}
+
+/// Data directives of `fb_info` describing `framebuffer`
+pub fn fb_info_data(framebuffer: &Framebuffer) -> String {
+    let mut data = String::from("fb_info:\n");
+    data.push_str(&format!("    .quad 0x{:X}        # base\n", framebuffer.draw_base()));
+    data.push_str(&format!("    .long {}        # width\n", framebuffer.width));
+    data.push_str(&format!("    .long {}        # height\n", framebuffer.height));
+    data.push_str(&format!("    .long {}        # pitch\n", framebuffer.pitch));
+    data.push_str(&format!("    .long {}        # bytes per pixel\n", framebuffer.bytes_per_pixel));
+    data.push_str(&format!("    .quad 0x{:X}        # front\n\n", framebuffer.front));
+    data
+}
+
+/// How a `--bios-mode` image draws and prints. Where it loads and keeps its
+/// stack and heap is `CompilerConfig::memory`
+#[derive(Debug, Clone)]
+pub struct BiosOptions {
+    pub framebuffer: Framebuffer,
+    /// Vector stores for fb_fill and fb_present
+    pub simd: SimdLevel,
+    /// Linked into the payload for image() to draw
+    pub images: Vec<EmbeddedImage>,
+    /// Send print() to COM1
+    pub debug_serial: bool,
+}
+
+impl Default for BiosOptions {
+    fn default() -> Self {
+        Self { framebuffer: Framebuffer::vga_mode_13h(), simd: SimdLevel::Sse2, images: Vec::new(), debug_serial: false }
+    }
+}
+
+impl BiosOptions {
+    pub fn with_framebuffer(mut self, framebuffer: Framebuffer) -> Self {
+        self.framebuffer = framebuffer;
+        self
+    }
+
+    pub fn with_simd(mut self, simd: SimdLevel) -> Self {
+        self.simd = simd;
+        self
+    }
+
+    pub fn with_images(mut self, images: Vec<EmbeddedImage>) -> Self {
+        self.images = images;
+        self
+    }
+
+    pub fn with_debug_serial(mut self, enabled: bool) -> Self {
+        self.debug_serial = enabled;
+        self
+    }
+}
+
+/// Disk image booting through the mode transition into `program`, compiled as a
+/// freestanding 64-bit payload linked where the transition jumps. Programs that
+/// call fb_present get the default back buffer unless the framebuffer has one.
+/// `config` gives the memory layout and whether heap objects are freed
+pub fn bios_image(program: &Program, config: &CompilerConfig, options: &BiosOptions, work_dir: &Path) -> Result<DiskImage, String> {
+    let (memory, images) = (config.memory, &options.images);
+    let calls_init = program.body.iter().any(|stmt| matches!(stmt, Statement::Expr(Expr::Call { func, .. }) if func == "fb_init"));
+    if !calls_init {
+        return Err("BIOS mode programs must call fb_init(320, 200)".to_string());
+    }
+    let mut framebuffer = options.framebuffer;
+    if framebuffer.back.is_none() && crate::extension::program_calls(program).contains("fb_present") {
+        framebuffer.back = Some(DEFAULT_BACK_BUFFER);
+    }
+    framebuffer.check()?;
+    memory.check(&framebuffer)?;
+    let format = PixelFormat::for_bytes_per_pixel(framebuffer.bytes_per_pixel)?;
+    if let Some(image) = images.iter().find(|image| image.pixels.len() != image.width as usize * image.height as usize * format.bytes_per_pixel()) {
+        return Err(format!("Image '{}' was not converted to {:?} pixels", image.name, format));
+    }
+    let image_bytes: usize = images.iter().map(|image| image.pixels.len()).sum();
+    let budget = STAGE2_MEMORY_END.saturating_sub(memory.load_address) as usize;
+    if image_bytes > budget {
+        return Err(image_budget_error(images, budget));
+    }
+
+    let emitter = ModeTransitionEmitter::new(BootLayout::TwoStage)
+        .with_video_mode(VGA_MODE_13H)
+        .with_stage2_address(memory.load_address)
+        .with_stack_top(memory.stack_top);
+    let address = emitter.payload_address(work_dir)?;
+    let asm = Linux64Backend::new()
+        .with_bios_graphics(framebuffer, options.simd)
+        .with_memory_layout(memory)
+        .with_embedded_images(images.to_vec())
+        .with_debug_serial(options.debug_serial)
+        .with_refcounting(config.refcounting)
+        .compile_program(program)?;
+    let (payload, symbols, data_address) = link_payload(&asm, address, work_dir)?;
+    let budget = (STAGE2_MEMORY_END - address) as usize;
+    if !images.is_empty() && payload.len() > budget {
+        return Err(image_budget_error(images, budget));
+    }
+
+    let end = address as u64 + payload.len() as u64;
+    let bootloader = emitter.with_payload(payload).create_bootloader(work_dir)?;
+    if bootloader.payload_address != address {
+        return Err(format!("Payload linked at 0x{:X} but placed at 0x{:X}", address, bootloader.payload_address));
+    }
+    let mut image = bootloader.image;
+    let stage2 = image.entry("stage2").map_or(0, |entry| entry.lba as u64 * SECTOR_SIZE as u64);
+    let offset = stage2 + (address - memory.load_address) as u64;
+    image.symbols.add_region("payload", address as u64, data_address - address as u64, offset, &symbols);
+    image.symbols.add_region("data", data_address, end - data_address, offset + data_address - address as u64, &symbols);
+    Ok(image)
+}
+
+/// Parse `source`, expand its includes from `config`'s search paths, check
+/// its types, fold its constant expressions and build the image of `bios_image`
+pub fn compile_bios_image(source: &str, config: &CompilerConfig, options: &BiosOptions, work_dir: &Path) -> Result<DiskImage, String> {
+    use crate::compiler::OptimizationPass;
+
+    let program = crate::lua_frontend::parse_program(source).map_err(|errors| {
+        let messages: Vec<String> = errors.iter().map(|e| e.format_error(source)).collect();
+        format!("Parse errors:\n{}", messages.join("\n"))
+    })?;
+    let mut includes = crate::lua_frontend::IncludeProcessor::new();
+    for path in &config.search_paths {
+        includes.add_search_path(path);
+    }
+    let mut program = includes.process_includes(&program, None)
+        .map_err(|e| format!("Include processing error: {}", e))?;
+    crate::compiler::ConstantFoldingPass.optimize(&mut program)?;
+    crate::typecheck::check_types(&mut program, config.strict_types)?;
+    bios_image(&program, config, options, work_dir)
+}
+
+fn image_budget_error(images: &[EmbeddedImage], budget: usize) -> String {
+    let sizes: Vec<String> = images.iter().map(|image| format!("'{}' {} bytes", image.name, image.pixels.len())).collect();
+    format!("Embedded images ({}) do not fit the {} bytes the payload has below 0x{:X}", sizes.join(", "), budget, STAGE2_MEMORY_END)
+}
+
+/// Assemble backend output and link it into a flat binary running at `address`.
+/// Also returns the payload's labels and the address its data starts at
+fn link_payload(asm: &str, address: u32, work_dir: &Path) -> Result<(Vec<u8>, Vec<ObjectSymbol>, u64), String> {
+    let source = work_dir.join("payload.s");
+    let object = work_dir.join("payload.o");
+    let script = work_dir.join("payload.ld");
+    let linked = work_dir.join("payload.elf");
+    let binary = work_dir.join("payload.bin");
+    let linker_script = format!(
+        "SECTIONS {{\n    . = 0x{:X};\n    .text : {{ *(.text*) }}\n    .data : {{ {} = .; *(.data*) *(.rodata*) }}\n    /DISCARD/ : {{ *(.note*) *(.comment) }}\n}}\n",
+        address, PAYLOAD_DATA_SYMBOL
+    );
+    for (path, contents) in [(&source, asm), (&script, linker_script.as_str())] {
+        std::fs::write(path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+
+    crate::compiler::run_tool("as", &["--64".as_ref(), "-o".as_ref(), object.as_os_str(), source.as_os_str()])?;
+    crate::compiler::run_tool("ld", &["-T".as_ref(), script.as_os_str(), "-o".as_ref(), linked.as_os_str(), object.as_os_str()])?;
+    crate::compiler::run_tool("objcopy", &["-O".as_ref(), "binary".as_ref(), linked.as_os_str(), binary.as_os_str()])?;
+    let code = std::fs::read(&binary)
+        .map_err(|e| format!("Failed to read {}: {}", binary.display(), e))?;
+    let mut symbols = object_symbols(&linked, 0)?;
+
+    for path in [&source, &object, &script, &linked, &binary] {
+        let _ = std::fs::remove_file(path);
+    }
+    let data_address = symbols.iter().position(|symbol| symbol.name == PAYLOAD_DATA_SYMBOL)
+        .map_or(address as u64 + code.len() as u64, |index| symbols.remove(index).address);
+    Ok((code, symbols, data_address))
+}
+
+fn palette_index(color: i64, span: Span) -> Result<u8, String> {
+    u8::try_from(color).map_err(|_| format!("Color {} is not a palette index from 0 to 255 at {}", color, span))
+}
+
+fn pixel_address(framebuffer: &Framebuffer, x: i64, y: i64) -> u64 {
+    framebuffer.draw_base() + (y * framebuffer.pitch as i64 + x) as u64
+}
+
+/// Broadcast the color into a vector register and store it over the whole screen.
+/// The vector instructions are emitted as bytes so any assembler can take them
+fn fill(color: i64, framebuffer: &Framebuffer, simd: SimdLevel, span: Span) -> Result<String, String> {
+    let color = palette_index(color, span)? as u32;
+    let (broadcast, width, store) = broadcast(simd);
+    let mut asm = format!("    # fb_fill({}) with {:?} stores\n", color, simd);
+    asm.push_str(&format!("    mov eax, 0x{:08X}\n", color * 0x0101_0101));
+    asm.push_str(&broadcast);
+    asm.push_str(&format!("    mov edi, 0x{:X}\n", framebuffer.draw_base()));
+    asm.push_str(&format!("    mov ecx, {}\n", framebuffer.size() / width as u64));
+    asm.push_str(&store_loop(simd, width, &store));
+    Ok(asm)
+}
+
+/// Code spreading the color pattern in eax over a vector register, the register's
+/// width and the aligned store of it to [rdi]
+fn broadcast(simd: SimdLevel) -> (String, u32, String) {
+    match simd {
+        SimdLevel::Sse2 => (
+            simd::gas_bytes(&simd::movd_xmm_r32(0, simd::RAX), "movd xmm0, eax")
+                + &simd::gas_bytes(&simd::pshufd_xmm_xmm_imm8(0, 0, 0), "pshufd xmm0, xmm0, 0"),
+            16,
+            simd::gas_bytes(&simd::movdqa_mem_xmm(simd::RDI, 0), "movdqa [rdi], xmm0"),
+        ),
+        SimdLevel::Avx2 => (
+            simd::gas_bytes(&simd::movd_xmm_r32(0, simd::RAX), "movd xmm0, eax")
+                + &simd::gas_bytes(&simd::vpbroadcastd_ymm_xmm(0, 0), "vpbroadcastd ymm0, xmm0"),
+            32,
+            simd::gas_bytes(&simd::vmovdqa_mem_ymm(simd::RDI, 0), "vmovdqa [rdi], ymm0"),
+        ),
+        SimdLevel::Avx512 => (
+            simd::gas_bytes(&simd::vpbroadcastd_zmm_r32(0, simd::RAX), "vpbroadcastd zmm0, eax"),
+            64,
+            simd::gas_bytes(&simd::vmovdqa64_mem_zmm(simd::RDI, 0), "vmovdqa64 [rdi], zmm0"),
+        ),
+    }
+}
+
+/// Aligned load of [rsi] into the widest vector register and its store to [rdi]
+fn copy_vector(simd: SimdLevel) -> (String, u32, String) {
+    match simd {
+        SimdLevel::Sse2 => (
+            simd::gas_bytes(&simd::movdqa_xmm_mem(0, simd::RSI), "movdqa xmm0, [rsi]"),
+            16,
+            simd::gas_bytes(&simd::movdqa_mem_xmm(simd::RDI, 0), "movdqa [rdi], xmm0"),
+        ),
+        SimdLevel::Avx2 => (
+            simd::gas_bytes(&simd::vmovdqa_ymm_mem(0, simd::RSI), "vmovdqa ymm0, [rsi]"),
+            32,
+            simd::gas_bytes(&simd::vmovdqa_mem_ymm(simd::RDI, 0), "vmovdqa [rdi], ymm0"),
+        ),
+        SimdLevel::Avx512 => (
+            simd::gas_bytes(&simd::vmovdqa64_zmm_mem(0, simd::RSI), "vmovdqa64 zmm0, [rsi]"),
+            64,
+            simd::gas_bytes(&simd::vmovdqa64_mem_zmm(simd::RDI, 0), "vmovdqa64 [rdi], zmm0"),
+        ),
+    }
+}
+
+/// Repeat `store` ecx times, advancing rdi by `width` bytes
+fn store_loop(simd: SimdLevel, width: u32, store: &str) -> String {
+    let mut asm = String::from("1:\n");
+    asm.push_str(store);
+    asm.push_str(&format!("    add rdi, {}\n", width));
+    asm.push_str("    dec ecx\n");
+    asm.push_str("    jnz 1b\n");
+    if simd == SimdLevel::Avx2 {
+        asm.push_str(&simd::gas_bytes(&simd::vzeroupper(), "vzeroupper"));
+    }
+    asm
+}
+
+/// Program one DAC entry from 8-bit components
+fn set_palette(values: &[i64], span: Span) -> Result<String, String> {
+    let [index, r, g, b] = values[..] else { unreachable!() };
+    let index = palette_index(index, span)?;
+    let mut asm = format!("    # fb_set_palette({}, {}, {}, {})\n", index, r, g, b);
+    asm.push_str(&format!("    mov dx, 0x{:X}\n", VGA_DAC_WRITE_PORT));
+    asm.push_str(&format!("    mov al, {}\n", index));
+    asm.push_str("    out dx, al\n");
+    asm.push_str("    inc dx\n");
+    for component in [r, g, b] {
+        let component = u8::try_from(component)
+            .map_err(|_| format!("Palette component {} is not from 0 to 255 at {}", component, span))?;
+        asm.push_str(&format!("    mov al, {}\n", component >> 2));
+        asm.push_str("    out dx, al\n");
+    }
+    Ok(asm)
+}
+
+/// The rectangle is clipped here, the same way fb_rect clips at runtime
+fn rect(values: &[i64], framebuffer: &Framebuffer, span: Span) -> Result<String, String> {
+    let [x, y, w, h, color] = values[..] else { unreachable!() };
+    let color = palette_index(color, span)?;
+    let mut asm = format!("    # fb_rect({}, {}, {}, {}, {})\n", x, y, w, h, color);
+    let (left, top) = (x.max(0), y.max(0));
+    let (right, bottom) = (x.saturating_add(w).min(framebuffer.width as i64), y.saturating_add(h).min(framebuffer.height as i64));
+    if right <= left || bottom <= top {
+        asm.push_str("    # off screen\n");
+        return Ok(asm);
+    }
+
+    asm.push_str(&format!("    mov edi, 0x{:X}\n", pixel_address(framebuffer, left, top)));
+    asm.push_str(&format!("    mov edx, {}\n", bottom - top));
+    asm.push_str(&format!("    mov al, {}\n", color));
+    asm.push_str("1:  mov rsi, rdi\n");
+    asm.push_str(&format!("    mov ecx, {}\n", right - left));
+    asm.push_str("    rep stosb\n");
+    asm.push_str(&format!("    lea rdi, [rsi + {}]\n", framebuffer.pitch));
+    asm.push_str("    dec edx\n");
+    asm.push_str("    jnz 1b\n");
+    Ok(asm)
+}
+
+/// Lines between on-screen endpoints are rasterized at compile time, leaving one
+/// store per pixel; any other line is left to fb_line
+fn line(values: &[i64], framebuffer: &Framebuffer, span: Span) -> Result<Option<String>, String> {
+    let [x0, y0, x1, y1, color] = values[..] else { unreachable!() };
+    let color = palette_index(color, span)?;
+    let on_screen = |x: i64, y: i64| (0..framebuffer.width as i64).contains(&x) && (0..framebuffer.height as i64).contains(&y);
+    if !on_screen(x0, y0) || !on_screen(x1, y1) {
+        return Ok(None);
+    }
+
+    let mut asm = format!("    # fb_line({}, {}, {}, {}, {})\n", x0, y0, x1, y1, color);
+    for (x, y) in line_pixels(x0, y0, x1, y1) {
+        asm.push_str(&format!("    mov byte ptr [0x{:X}], {}\n", pixel_address(framebuffer, x, y), color));
+    }
+    Ok(Some(asm))
+}
+
+/// Bresenham's algorithm over all octants, both endpoints included
+fn line_pixels(x0: i64, y0: i64, x1: i64, y1: i64) -> Vec<(i64, i64)> {
+    let (dx, sx) = ((x1 - x0).abs(), if x0 < x1 { 1 } else { -1 });
+    let (dy, sy) = (-(y1 - y0).abs(), if y0 < y1 { 1 } else { -1 });
+    let (mut x, mut y, mut error) = (x0, y0, dx + dy);
+    let mut pixels = Vec::new();
+    loop {
+        pixels.push((x, y));
+        if x == x1 && y == y1 {
+            return pixels;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += sx;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += sy;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_program;
+
+    #[test]
+    fn test_memory_layout() {
+        // The defaults are the addresses images always used
+        let default = MemoryLayout::default();
+        assert!(heap_routine(false, &default).contains("    cmp rdi, 0x40000000\n"));
+        assert_eq!(heap_data(&default), "heap_base:\n    .quad 0x1000000\nheap_top:\n    .quad 0x1000000\n");
+        let small = default.with_heap_base(0x200_0000).with_heap_size(0x10_0000);
+        assert!(heap_routine(true, &small).contains("    cmp rdi, 0x2100000\n"));
+
+        let framebuffer = Framebuffer::vga_mode_13h().with_back_buffer(DEFAULT_BACK_BUFFER);
+        assert_eq!(small.check(&framebuffer), Ok(()));
+        assert!(default.with_heap_base(0x8_0000).check(&framebuffer).unwrap_err().contains("start at 0x100000 or above"));
+        assert!(default.with_heap_size(0x4000_0000).check(&framebuffer).unwrap_err().contains("identity-mapped"));
+        assert!(default.with_heap_base(0x10_0000).check(&framebuffer).unwrap_err().contains("overlaps the heap at 0x100000"));
+        assert_eq!(default.with_heap_base(0x10_0000).with_heap_size(0x10_0000).check(&framebuffer), Ok(()));
+
+        // Only meaningful where binutils are installed
+        let moved = MemoryLayout::default().with_load_address(0x8000).with_stack_top(0x7000).with_heap_base(0x200_0000).with_heap_size(0x10_0000);
+        let work_dir = work_dir("fb_layout");
+        let image = compile_bios_image("fb_init(320, 200)\nvar name = input()\nfb_fill(1)\n", &CompilerConfig::default().with_memory_layout(moved), &BiosOptions::default(), &work_dir);
+        let _ = std::fs::remove_dir_all(&work_dir);
+        let image = match image {
+            Ok(image) => image,
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        };
+        let symbol = |name: &str| image.symbols.symbols.iter().find(|symbol| symbol.name == name).unwrap();
+        assert_eq!(symbol("transition").address, 0x8000);
+        assert!(symbol("payload").address > 0x8000);
+        let heap_base = symbol("heap_base").offset as usize;
+        assert_eq!(image.bytes[heap_base..heap_base + 8], 0x200_0000u64.to_le_bytes());
+    }
+
+    fn work_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("earthang_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Inline code of every call in the example, followed by a halt loop
+    fn assemble_example(program: &Program, simd: SimdLevel, work_dir: &Path) -> Result<Vec<u8>, String> {
+        let mut asm = String::from("    .intel_syntax noprefix\n    .code64\n_start:\n");
+        for stmt in &program.body {
+            let Statement::Expr(Expr::Call { func, args, span, .. }) = stmt else { unreachable!() };
+            asm.push_str(&inline_call(func, args, &Framebuffer::vga_mode_13h(), simd, *span)?.unwrap());
+        }
+        asm.push_str("halt:\n    hlt\n    jmp halt\n");
+
+        let (source, object, binary) = (work_dir.join("example.s"), work_dir.join("example.o"), work_dir.join("example.bin"));
+        std::fs::write(&source, asm).unwrap();
+        crate::compiler::run_tool("as", &["--64".as_ref(), "-o".as_ref(), object.as_os_str(), source.as_os_str()])?;
+        crate::compiler::run_tool("objcopy", &["-O".as_ref(), "binary".as_ref(), object.as_os_str(), binary.as_os_str()])?;
+        Ok(std::fs::read(&binary).unwrap())
+    }
+
+    #[test]
+    fn test_rect_example_golden_bytes() {
+        let program = parse_program(include_str!("../examples/fb_rect.eg")).unwrap();
+        assert_eq!(line_pixels(0, 0, 3, 1), vec![(0, 0), (1, 0), (2, 1), (3, 1)]);
+
+        let call = |source: &str| {
+            let program = parse_program(source).unwrap();
+            let Statement::Expr(Expr::Call { func, args, span, .. }) = &program.body[0] else { unreachable!() };
+            inline_call(func, args, &Framebuffer::vga_mode_13h(), SimdLevel::Sse2, *span)
+        };
+        for (source, error) in [
+            ("fb_init(640, 480)\n", "only supports 320x200"),
+            ("fb_fill(1, 2)\n", "takes 1 arguments but 2"),
+            ("fb_rect(0, 0, 1, 1, 256)\n", "not a palette index"),
+            ("fb_set_palette(1, 0, 300, 0)\n", "Palette component 300"),
+        ] {
+            assert!(call(source).unwrap_err().contains(error), "{}", source);
+        }
+        // Literal rectangles are clipped like runtime ones, off-screen lines go to fb_line
+        assert!(call("fb_rect(300, -5, 40, 10, 4)\n").unwrap().unwrap().contains("mov edx, 5\n"));
+        assert!(call("fb_rect(400, 0, 40, 10, 4)\n").unwrap().unwrap().contains("off screen"));
+        // Colors fold to the nearest EGA entry in mode 13h, so red fills with index 4
+        assert!(call("fb_fill(rgb(255, 0, 0))\n").unwrap().unwrap().contains("fb_fill(4)"));
+        assert!(call("fb_rect(0, 0, 1, 1, rgba(255, 255, 255, 0))\n").unwrap().unwrap().contains("mov al, 15\n"));
+        assert_eq!(call("fb_fill(rgb(c, 0, 0))\n").unwrap(), None);
+        assert_eq!(
+            call("fb_set_palette(4, 255, 128, 0)\n").unwrap().unwrap(),
+            ["    # fb_set_palette(4, 255, 128, 0)\n    mov dx, 0x3C8\n    mov al, 4\n    out dx, al\n    inc dx\n",
+             "    mov al, 63\n    out dx, al\n    mov al, 32\n    out dx, al\n    mov al, 0\n    out dx, al\n"].concat()
+        );
+        let compile = |source: &str| Linux64Backend::new().with_bios_graphics(Framebuffer::vga_mode_13h(), SimdLevel::Sse2).compile_program(&parse_program(source).unwrap());
+        assert!(compile("c = rgb(0, 0, 170)\nfb_fill(c)\n").unwrap().contains("mov rax, 1        # rgb()"));
+        assert!(compile("fb_fill(rgb(c, 0, 0))\n").unwrap_err().contains("must be integer literals"));
+        assert!(Linux64Backend::new().compile_program(&parse_program("c = rgb(1, 2, 3)\n").unwrap()).unwrap_err().contains("needs --bios-mode"));
+        assert_eq!(call("fb_line(-1, 0, 3, 1, 15)\n").unwrap(), None);
+        assert_eq!(call("fb_fill(c)\n").unwrap(), None);
+        let image = |source: &str, framebuffer: Framebuffer| {
+            bios_image(&parse_program(source).unwrap(), &CompilerConfig::default(), &BiosOptions::default().with_framebuffer(framebuffer), Path::new("."))
+        };
+        assert!(image("fb_fill(1)\n", Framebuffer::vga_mode_13h()).unwrap_err().contains("must call fb_init"));
+        let overlapping = Framebuffer::vga_mode_13h().with_back_buffer(VGA_FRAMEBUFFER + 0x100);
+        assert!(image("fb_init(320, 200)\n", overlapping).unwrap_err().contains("overlaps"));
+        let into_heap = Framebuffer::vga_mode_13h().with_back_buffer(BIOS_HEAP - 0x100);
+        assert!(image("fb_init(320, 200)\n", into_heap).unwrap_err().contains("overlaps the heap"));
+
+        // Only meaningful where binutils are installed
+        let work_dir = work_dir("fb");
+        let result = assemble_example(&program, SimdLevel::Sse2, &work_dir);
+        let wide: Vec<_> = [SimdLevel::Avx2, SimdLevel::Avx512].iter()
+            .map(|&simd| assemble_example(&program, simd, &work_dir))
+            .collect();
+        let _ = std::fs::remove_dir_all(&work_dir);
+        let code = match result {
+            Ok(code) => code,
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        };
+        let golden: &[u8] = &[
+            // fb_fill(1) with SSE2 stores
+            0xB8, 0x01, 0x01, 0x01, 0x01, 0x66, 0x0F, 0x6E, 0xC0, 0x66, 0x0F, 0x70, 0xC0, 0x00,
+            0xBF, 0x00, 0x00, 0x0A, 0x00, 0xB9, 0xA0, 0x0F, 0x00, 0x00,
+            0x66, 0x0F, 0x7F, 0x07, 0x48, 0x83, 0xC7, 0x10, 0xFF, 0xC9, 0x75, 0xF4,
+            // fb_rect(100, 50, 120, 80, 4)
+            0xBF, 0xE4, 0x3E, 0x0A, 0x00, 0xBA, 0x50, 0x00, 0x00, 0x00, 0xB0, 0x04,
+            0x48, 0x89, 0xFE, 0xB9, 0x78, 0x00, 0x00, 0x00, 0xF3, 0xAA,
+            0x48, 0x8D, 0xBE, 0x40, 0x01, 0x00, 0x00, 0xFF, 0xCA, 0x75, 0xEB,
+            // fb_line(0, 0, 3, 1, 15)
+            0xC6, 0x04, 0x25, 0x00, 0x00, 0x0A, 0x00, 0x0F,
+            0xC6, 0x04, 0x25, 0x01, 0x00, 0x0A, 0x00, 0x0F,
+            0xC6, 0x04, 0x25, 0x42, 0x01, 0x0A, 0x00, 0x0F,
+            0xC6, 0x04, 0x25, 0x43, 0x01, 0x0A, 0x00, 0x0F,
+            // halt loop
+            0xF4, 0xEB, 0xFD,
+        ];
+        assert_eq!(code, golden);
+
+        // Wider stores halve the loop count each step and keep the rest of the program
+        for (code, (store, count)) in wide.into_iter().zip([(simd::vmovdqa_mem_ymm(simd::RDI, 0), 2000u32), (simd::vmovdqa64_mem_zmm(simd::RDI, 0), 1000)]) {
+            let code = code.unwrap();
+            let mut loop_head = vec![0xB9];
+            loop_head.extend(count.to_le_bytes());
+            loop_head.extend(&store);
+            assert!(code.windows(loop_head.len()).any(|w| w == loop_head.as_slice()));
+            assert!(code.ends_with(&golden[36..]));
+        }
+    }
+
+    /// Assemble `asm` as a Linux program and run it, or `None` without binutils
+    fn run_harness(name: &str, asm: &str) -> Option<std::process::Output> {
+        let work_dir = work_dir(name);
+        let (source, object, exe) = (work_dir.join("harness.s"), work_dir.join("harness.o"), work_dir.join("harness"));
+        std::fs::write(&source, asm).unwrap();
+        let built = crate::compiler::run_tool("as", &["--64".as_ref(), "-o".as_ref(), object.as_os_str(), source.as_os_str()])
+            .and_then(|_| crate::compiler::run_tool("ld", &["-o".as_ref(), exe.as_os_str(), object.as_os_str()]));
+        let output = built.as_ref().ok().map(|_| std::process::Command::new(&exe).output().unwrap());
+        let _ = std::fs::remove_dir_all(&work_dir);
+        match built {
+            Err(e) if e.contains("Failed to run") => None,
+            Err(e) => panic!("{}", e),
+            Ok(_) => output,
+        }
+    }
+
+    /// Run the routines with fb_info pointed at a buffer between two guard rows;
+    /// the exit status names the first check that failed
+    #[test]
+    fn test_runtime_routines_clip() {
+        let framebuffer = Framebuffer::vga_mode_13h();
+        let on_screen = |&(x, y): &(i64, i64)| (0..VGA_WIDTH).contains(&x) && (0..VGA_HEIGHT).contains(&y);
+        let line_count = line_pixels(310, -3, 330, 7).iter().filter(|p| on_screen(p)).count();
+        let count = |color: u8, expected: usize, status: u8| format!(
+            "    lea rsi, [rip + screen]\n    mov ecx, 64000\n    xor edx, edx\n\
+             1:  cmp byte ptr [rsi], {}\n    jne 2f\n    inc edx\n2:  inc rsi\n    dec ecx\n    jnz 1b\n\
+                 mov edi, {}\n    cmp edx, {}\n    jne fail\n",
+            color, status, expected
+        );
+
+        let mut asm = String::from("    .intel_syntax noprefix\n    .globl _start\n_start:\n");
+        asm.push_str("    lea rax, [rip + screen]\n    mov [rip + fb_info], rax\n");
+        // 15x10 visible out of 20x20 at (-5, 190), then a line leaving through the top right
+        asm.push_str("    mov rdi, -5\n    mov rsi, 190\n    mov rdx, 20\n    mov rcx, 20\n    mov r8, 7\n    call fb_rect\n");
+        asm.push_str("    mov rdi, 310\n    mov rsi, -3\n    mov rdx, 330\n    mov rcx, 7\n    mov r8, 9\n    call fb_line\n");
+        asm.push_str(&count(7, 150, 1));
+        asm.push_str(&count(9, line_count, 2));
+        asm.push_str("    mov rdi, 3\n    call fb_fill\n");
+        asm.push_str(&count(3, 64000, 3));
+        asm.push_str("    lea rsi, [rip + screen - 320]\n    mov ecx, 320\n    mov edi, 4\n");
+        asm.push_str("1:  cmp byte ptr [rsi], 0\n    jne fail\n    cmp byte ptr [rsi + 64320], 0\n    jne fail\n    inc rsi\n    dec ecx\n    jnz 1b\n");
+        asm.push_str("    xor edi, edi\nfail:\n    mov eax, 60\n    syscall\n");
+        asm.push_str(&runtime_routines(&framebuffer, SimdLevel::Sse2));
+        asm.push_str("    .section .data\n");
+        asm.push_str(&fb_info_data(&framebuffer));
+        asm.push_str("    .balign 16\n    .zero 320\nscreen:\n    .zero 64000\n    .zero 320\n");
+        let Some(output) = run_harness("fb_runtime", &asm) else { return };
+        assert_eq!(output.status.code(), Some(0));
+
+        // The whole pipeline also links programs whose calls take runtime values
+        let work_dir = work_dir("fb_bars");
+        let image = compile_bios_image(include_str!("../examples/fb_bars.eg"), &CompilerConfig::default(), &BiosOptions::default().with_framebuffer(framebuffer), &work_dir);
+        let _ = std::fs::remove_dir_all(&work_dir);
+        let image = image.unwrap();
+        assert!(image.entry("stage2").is_some());
+
+        // Its symbol map points at the bytes each label names
+        let symbols = &image.symbols;
+        let gdt = symbols.symbols.iter().find(|symbol| symbol.name == "gdt").unwrap();
+        assert_eq!(gdt.phase, "transition");
+        let code64 = gdt.offset as usize + 0x18;
+        assert_eq!(image.bytes[code64..code64 + 8], 0x00AF9A000000FFFFu64.to_le_bytes());
+        let (phase, _) = symbols.resolve(gdt.address - 1).unwrap();
+        assert_eq!(phase.name, "no_long_mode");
+        let main = symbols.symbols.iter().find(|symbol| symbol.name == "main").unwrap();
+        assert_eq!((main.phase.as_str(), symbols.resolve(main.address + 2).unwrap().0), ("payload", main));
+        let long_mode = symbols.symbols.iter().find(|symbol| symbol.name == "long_mode").unwrap();
+        assert_eq!(symbols.resolve(long_mode.address).unwrap().0.phase, "long_mode");
+        let data = symbols.symbols.iter().find(|symbol| symbol.kind == crate::symbol_map::SymbolKind::Region && symbol.name == "data").unwrap();
+        assert_eq!(data.offset + data.size, image.entry("stage2").map(|entry| (SECTOR_SIZE + entry.size) as u64).unwrap());
+    }
+
+    /// fb_present over 32-bit pixels with row padding: only width * height * 4
+    /// bytes reach the front buffer, through vector stores plus a tail when the
+    /// pitch keeps rows aligned and byte by byte when it does not
+    #[test]
+    fn test_present_copies_visible_rows() {
+        for pitch in [48, 44] {
+            let framebuffer = Framebuffer { width: 10, height: 3, pitch, bytes_per_pixel: 4, front: 0x100000, back: Some(0x200000), vsync: false };
+            let size = pitch * 3;
+            let mut asm = String::from("    .intel_syntax noprefix\n    .globl _start\n_start:\n");
+            asm.push_str("    lea rax, [rip + back]\n    mov [rip + fb_info], rax\n");
+            asm.push_str(&format!("    lea rax, [rip + front]\n    mov [rip + fb_info + {}], rax\n", FB_INFO_FRONT));
+            asm.push_str("    call fb_present\n");
+            // Count the copied bytes, and require the padding after each row to stay clear
+            asm.push_str(&format!("    lea rsi, [rip + front]\n    xor ecx, ecx\n    xor edx, edx\n1:  cmp byte ptr [rsi + rcx], 0xAB\n    jne 2f\n    inc edx\n2:  inc ecx\n    cmp ecx, {}\n    jne 1b\n", size + 64));
+            asm.push_str(&format!("    mov edi, 1\n    cmp edx, {}\n    jne fail\n", framebuffer.row_bytes() * framebuffer.height));
+            for row in 0..3 {
+                asm.push_str(&format!("    mov edi, 2\n    cmp dword ptr [rsi + {}], 0\n    jne fail\n", row * pitch + framebuffer.row_bytes()));
+            }
+            asm.push_str("    xor edi, edi\nfail:\n    mov eax, 60\n    syscall\n");
+            asm.push_str(&present(&framebuffer, SimdLevel::Sse2));
+            assert_eq!(present(&framebuffer, SimdLevel::Sse2).contains("movdqa"), pitch % 16 == 0);
+            asm.push_str("    .section .data\n");
+            asm.push_str(&fb_info_data(&framebuffer));
+            asm.push_str(&format!("    .balign 64\nback:\n    .fill {}, 1, 0xAB\n    .balign 64\nfront:\n    .zero {}\n", size, size + 64));
+            let Some(output) = run_harness("fb_present", &asm) else { return };
+            assert_eq!(output.status.code(), Some(0), "pitch {}", pitch);
+        }
+    }
+
+    /// Draw with fb_text and fb_print, dump the screen to stdout and compare it
+    /// with a model of the text console
+    #[test]
+    fn test_text_wraps_and_scrolls() {
+        let framebuffer = Framebuffer::vga_mode_13h();
+        let (width, height) = (VGA_WIDTH as usize, VGA_HEIGHT as usize);
+        let mut expected = vec![0u8; width * height];
+        let draw = |c: u8, x: i64, y: i64, fg: u8, bg: u8, screen: &mut Vec<u8>| {
+            for (dy, row) in crate::font::glyph(c).iter().enumerate() {
+                for dx in 0..8 {
+                    let (px, py) = (x + dx, y + dy as i64);
+                    if (0..VGA_WIDTH).contains(&px) && (0..VGA_HEIGHT).contains(&py) {
+                        screen[py as usize * width + px as usize] = if row & (0x80 >> dx) != 0 { fg } else { bg };
+                    }
+                }
+            }
+        };
+        for (i, c) in "Hi!".bytes().enumerate() {
+            draw(c, -3 + 8 * i as i64, 5, 14, 1, &mut expected);
+        }
+        let printed = format!("\x08ax\x08b\n{}\n{}", "0123456789".repeat(5), "row\n".repeat(11));
+        let (rows, text_row) = (height / 16, width * 16);
+        let (mut column, mut row) = (0usize, 0);
+        for c in printed.bytes() {
+            if c == 8 {
+                column = column.saturating_sub(1);
+                continue;
+            }
+            if c != b'\n' {
+                draw(c, column as i64 * 8, row as i64 * 16, 15, 0, &mut expected);
+                column += 1;
+            }
+            if c == b'\n' || column == width / 8 {
+                column = 0;
+                row += 1;
+                if row == rows {
+                    expected.copy_within(text_row..rows * text_row, 0);
+                    expected[(rows - 1) * text_row..rows * text_row].fill(0);
+                    row = rows - 1;
+                }
+            }
+        }
+
+        let mut asm = String::from("    .intel_syntax noprefix\n    .globl _start\n_start:\n");
+        asm.push_str("    lea rax, [rip + screen]\n    mov [rip + fb_info], rax\n");
+        asm.push_str("    mov rdi, -3\n    mov rsi, 5\n    lea rdx, [rip + hi]\n    mov rcx, 14\n    mov r8, 1\n    call fb_text\n");
+        asm.push_str("    lea rdi, [rip + printed]\n    call fb_print\n");
+        asm.push_str(&format!("    mov eax, 1\n    mov edi, 1\n    lea rsi, [rip + screen]\n    mov edx, {}\n    syscall\n", width * height));
+        asm.push_str("    mov eax, 60\n    xor edi, edi\n    syscall\n");
+        asm.push_str(&runtime_routines(&framebuffer, SimdLevel::Sse2));
+        asm.push_str(&text_routines());
+        asm.push_str("    .section .data\n");
+        asm.push_str(&fb_info_data(&framebuffer));
+        asm.push_str(&text_data());
+        let bytes: Vec<String> = printed.bytes().chain([0]).map(|byte| byte.to_string()).collect();
+        asm.push_str(&format!("hi:\n    .asciz \"Hi!\"\nprinted:\n    .byte {}\nscreen:\n    .zero {}\n", bytes.join(", "), width * height));
+
+        // The font only ends up in programs that draw text
+        let program = |source: &str| Linux64Backend::new().with_bios_graphics(framebuffer, SimdLevel::Sse2).compile_program(&parse_program(source).unwrap()).unwrap();
+        assert!(!program("fb_init(320, 200)\nfb_fill(1)\n").contains("fb_font"));
+        assert!(program("fb_init(320, 200)\nfb_print(\"hi\")\n").contains("fb_font:"));
+
+        let Some(output) = run_harness("fb_text", &asm) else { return };
+        assert_eq!(output.status.code(), Some(0));
+        assert!(output.stdout == expected, "screen differs from the console model");
+    }
+
+    /// Blit a 6x4 image hanging over the bottom left corner, then check the size budget
+    #[test]
+    fn test_image_blit_clips() {
+        let framebuffer = Framebuffer::vga_mode_13h();
+        let image = EmbeddedImage { name: "tile.bmp".into(), width: 6, height: 4, pixels: (1..=24).collect() };
+        let mut expected = vec![0u8; (VGA_WIDTH * VGA_HEIGHT) as usize];
+        for (y, x) in (0..4).flat_map(|y| (0..6).map(move |x| (y, x))) {
+            let (px, py) = (x - 2, y + 198);
+            if px >= 0 && py < VGA_HEIGHT {
+                expected[(py * VGA_WIDTH + px) as usize] = image.pixels[(y * 6 + x) as usize];
+            }
+        }
+
+        let mut asm = String::from("    .intel_syntax noprefix\n    .globl _start\n_start:\n");
+        asm.push_str("    lea rax, [rip + screen]\n    mov [rip + fb_info], rax\n");
+        asm.push_str("    lea rdi, [rip + fb_image_0]\n    mov rsi, -2\n    mov rdx, 198\n    mov ecx, 6\n    mov r8d, 4\n    call fb_blit\n");
+        asm.push_str(&format!("    mov eax, 1\n    mov edi, 1\n    lea rsi, [rip + screen]\n    mov edx, {}\n    syscall\n", expected.len()));
+        asm.push_str("    mov eax, 60\n    xor edi, edi\n    syscall\n");
+        asm.push_str(&blit_routine());
+        asm.push_str("    .section .data\n");
+        asm.push_str(&fb_info_data(&framebuffer));
+        asm.push_str(&image_data(std::slice::from_ref(&image), &framebuffer));
+        asm.push_str(&format!("screen:\n    .zero {}\n", expected.len()));
+
+        let huge = EmbeddedImage { name: "huge.bmp".into(), width: 1000, height: 500, pixels: vec![0; 500_000] };
+        let err = bios_image(&parse_program("fb_init(320, 200)\n").unwrap(), &CompilerConfig::default(), &BiosOptions::default().with_framebuffer(framebuffer).with_images(vec![image.clone(), huge]), Path::new(".")).unwrap_err();
+        assert!(err.contains("'huge.bmp' 500000 bytes") && err.contains("below 0x80000"), "{}", err);
+        let missing = Linux64Backend::new().with_bios_graphics(framebuffer, SimdLevel::Sse2)
+            .compile_program(&parse_program("fb_init(320, 200)\nimage(\"logo.bmp\", 0, 0)\n").unwrap()).unwrap_err();
+        assert!(missing.contains("--embed-image"), "{}", missing);
+
+        let Some(output) = run_harness("fb_blit", &asm) else { return };
+        assert_eq!(output.status.code(), Some(0));
+        assert!(output.stdout == expected, "screen differs from the clipped image");
+    }
+}
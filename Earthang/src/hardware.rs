@@ -0,0 +1,164 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use crate::backend::Capability;
+
+/// Raw CPUID access, abstracted so detection can be tested without the hardware
+pub trait CpuidSource {
+    /// Returns (eax, ebx, ecx, edx) for `leaf`/`subleaf`
+    fn cpuid(&self, leaf: u32, subleaf: u32) -> (u32, u32, u32, u32);
+
+    /// Extended control register 0, only read when the OS enabled XSAVE
+    fn xcr0(&self) -> u64;
+}
+
+/// The CPU this process is running on
+pub struct HostCpu;
+
+#[cfg(target_arch = "x86_64")]
+impl CpuidSource for HostCpu {
+    fn cpuid(&self, leaf: u32, subleaf: u32) -> (u32, u32, u32, u32) {
+        let result = std::arch::x86_64::__cpuid_count(leaf, subleaf);
+        (result.eax, result.ebx, result.ecx, result.edx)
+    }
+
+    fn xcr0(&self) -> u64 {
+        // SAFETY: only called after CPUID reported OSXSAVE
+        unsafe { std::arch::x86_64::_xgetbv(0) }
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+impl CpuidSource for HostCpu {
+    fn cpuid(&self, _leaf: u32, _subleaf: u32) -> (u32, u32, u32, u32) {
+        (0, 0, 0, 0)
+    }
+
+    fn xcr0(&self) -> u64 {
+        0
+    }
+}
+
+/// CPU extensions of the host machine
+pub fn detect_capabilities() -> Vec<Capability> {
+    detect_with(&HostCpu)
+}
+
+pub fn detect_with(cpu: &dyn CpuidSource) -> Vec<Capability> {
+    let mut capabilities = Vec::new();
+
+    let (max_leaf, _, _, _) = cpu.cpuid(0, 0);
+    if max_leaf < 1 {
+        return capabilities;
+    }
+
+    let (_, _, ecx, edx) = cpu.cpuid(1, 0);
+    let bit = |reg: u32, n: u32| reg & (1 << n) != 0;
+
+    if bit(edx, 25) { capabilities.push(Capability::SSE); }
+    if bit(edx, 26) { capabilities.push(Capability::SSE2); }
+    if bit(ecx, 0) { capabilities.push(Capability::_SSE3); }
+    if bit(ecx, 19) { capabilities.push(Capability::_SSE4); }
+
+    // AVX state must also be enabled by the OS, otherwise the instructions fault
+    let xcr0 = if bit(ecx, 27) { cpu.xcr0() } else { 0 };
+    let os_avx = xcr0 & 0x6 == 0x6;
+    let os_avx512 = os_avx && xcr0 & 0xE0 == 0xE0;
+
+    if os_avx && bit(ecx, 28) {
+        capabilities.push(Capability::AVX);
+    }
+
+    if max_leaf >= 7 && os_avx {
+        let (_, ebx, _, _) = cpu.cpuid(7, 0);
+        if bit(ebx, 5) { capabilities.push(Capability::_AVX2); }
+        if os_avx512 && bit(ebx, 16) { capabilities.push(Capability::AVX512); }
+    }
+
+    capabilities
+}
+
+/// Display name of a CPU extension
+pub fn feature_name(capability: &Capability) -> &'static str {
+    match capability {
+        Capability::SSE => "SSE",
+        Capability::SSE2 => "SSE2",
+        Capability::_SSE3 => "SSE3",
+        Capability::_SSE4 => "SSE4.1",
+        Capability::AVX => "AVX",
+        Capability::_AVX2 => "AVX2",
+        Capability::AVX512 => "AVX-512F",
+        _ => "unknown",
+    }
+}
+
+/// Every extension `detect_capabilities` can report, in ascending order
+pub const KNOWN_FEATURES: [Capability; 7] = [
+    Capability::SSE,
+    Capability::SSE2,
+    Capability::_SSE3,
+    Capability::_SSE4,
+    Capability::AVX,
+    Capability::_AVX2,
+    Capability::AVX512,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{BackendModule, BackendRegistry};
+
+    struct MockCpu {
+        leaf1: (u32, u32),  // (ecx, edx)
+        leaf7_ebx: u32,
+        xcr0: u64,
+    }
+
+    impl CpuidSource for MockCpu {
+        fn cpuid(&self, leaf: u32, _subleaf: u32) -> (u32, u32, u32, u32) {
+            match leaf {
+                0 => (7, 0, 0, 0),
+                1 => (0, 0, self.leaf1.0, self.leaf1.1),
+                7 => (0, self.leaf7_ebx, 0, 0),
+                _ => (0, 0, 0, 0),
+            }
+        }
+
+        fn xcr0(&self) -> u64 {
+            self.xcr0
+        }
+    }
+
+    const SSE_EDX: u32 = (1 << 25) | (1 << 26);
+    const AVX_ECX: u32 = (1 << 27) | (1 << 28);
+
+    #[test]
+    fn test_detect_features() {
+        let sse_only = MockCpu { leaf1: (0, SSE_EDX), leaf7_ebx: 0, xcr0: 0 };
+        assert_eq!(detect_with(&sse_only), vec![Capability::SSE, Capability::SSE2]);
+
+        // AVX reported by the CPU but not enabled by the OS
+        let no_os_support = MockCpu { leaf1: (AVX_ECX, SSE_EDX), leaf7_ebx: 1 << 5, xcr0: 0x1 };
+        assert!(!detect_with(&no_os_support).contains(&Capability::AVX));
+
+        let avx512 = MockCpu { leaf1: (AVX_ECX, SSE_EDX), leaf7_ebx: (1 << 5) | (1 << 16), xcr0: 0xE7 };
+        let detected = detect_with(&avx512);
+        assert!(detected.contains(&Capability::_AVX2));
+        assert!(detected.contains(&Capability::AVX512));
+
+        // The hosted backend needs no extensions, so it is selected on any CPU
+        let module = BackendModule { functions: Vec::new(), globals: Vec::new(), required_capabilities: vec![Capability::Linux] };
+        let registry = BackendRegistry::default_registry();
+        assert_eq!(registry.find_best_backend(&module, Some(&detect_with(&sse_only))).map(|b| b.name()), Some("linux64"));
+    }
+}
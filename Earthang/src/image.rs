@@ -0,0 +1,350 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::path::Path;
+
+/// First palette entry of the 6x6x6 color cube images are converted to in
+/// palette modes; entries 0-15 keep their default EGA colors
+pub const PALETTE_CUBE_START: u8 = 16;
+const CUBE_LEVELS: u32 = 6;
+
+/// The 16 colors every mode 13h palette starts with, as 0xRRGGBB
+pub const EGA_PALETTE: [u32; 16] = [
+    0x000000, 0x0000AA, 0x00AA00, 0x00AAAA, 0xAA0000, 0xAA00AA, 0xAA5500, 0xAAAAAA,
+    0x555555, 0x5555FF, 0x55FF55, 0x55FFFF, 0xFF5555, 0xFF55FF, 0xFFFF55, 0xFFFFFF,
+];
+
+/// 4x4 Bayer matrix, thresholds 0-15
+const BAYER_4X4: [[i32; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// How pixels are laid out in the framebuffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// One palette index per pixel
+    Indexed8,
+    /// 5 bits red, 6 green, 5 blue in a little-endian u16
+    Rgb565,
+    /// Blue, green, red bytes
+    Rgb888,
+    /// Little-endian 0xAARRGGBB, so blue comes first in memory
+    Argb8888,
+}
+
+impl PixelFormat {
+    pub fn for_bytes_per_pixel(bytes: u32) -> Result<Self, String> {
+        match bytes {
+            1 => Ok(PixelFormat::Indexed8),
+            2 => Ok(PixelFormat::Rgb565),
+            3 => Ok(PixelFormat::Rgb888),
+            4 => Ok(PixelFormat::Argb8888),
+            _ => Err(format!("Images cannot be converted to {}-byte pixels", bytes)),
+        }
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Indexed8 => 1,
+            PixelFormat::Rgb565 => 2,
+            PixelFormat::Rgb888 => 3,
+            PixelFormat::Argb8888 => 4,
+        }
+    }
+
+    /// Distance between neighbouring red, green and blue levels images are
+    /// converted to; palette formats use the color cube
+    fn channel_steps(&self) -> [i32; 3] {
+        match self {
+            PixelFormat::Indexed8 => [51; 3],
+            PixelFormat::Rgb565 => [8, 4, 8],
+            PixelFormat::Rgb888 | PixelFormat::Argb8888 => [1; 3],
+        }
+    }
+}
+
+/// An 8-bit-per-channel color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pixel {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Pixel {
+    pub fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// From 0xAARRGGBB
+    pub fn from_u32(argb: u32) -> Self {
+        let [b, g, r, a] = argb.to_le_bytes();
+        Self { r, g, b, a }
+    }
+
+    /// As 0xAARRGGBB
+    pub fn to_u32(&self) -> u32 {
+        u32::from_le_bytes([self.b, self.g, self.r, self.a])
+    }
+
+    /// The value a framebuffer in `format` stores for this color. Palette
+    /// formats get the nearest of the 16 EGA colors, which keep their place
+    /// even when embedded images load the color cube
+    pub fn to_format(&self, format: PixelFormat) -> u32 {
+        match format {
+            PixelFormat::Indexed8 => {
+                let distance = |rgb: u32| {
+                    let other = Pixel::from_u32(rgb);
+                    [(self.r, other.r), (self.g, other.g), (self.b, other.b)].iter()
+                        .map(|&(a, b)| (a as i32 - b as i32).pow(2))
+                        .sum::<i32>()
+                };
+                (0..EGA_PALETTE.len()).min_by_key(|&i| distance(EGA_PALETTE[i])).unwrap() as u32
+            }
+            PixelFormat::Rgb565 => (self.r as u32 >> 3) << 11 | (self.g as u32 >> 2) << 5 | self.b as u32 >> 3,
+            PixelFormat::Rgb888 => self.to_u32() & 0xFF_FFFF,
+            PixelFormat::Argb8888 => self.to_u32(),
+        }
+    }
+
+    /// The color of a framebuffer value in `format`. Palette indices read the
+    /// EGA colors and the color cube; entries past the cube are black
+    pub fn from_format(format: PixelFormat, value: u32) -> Self {
+        match format {
+            PixelFormat::Indexed8 => {
+                let index = value as usize;
+                let cube = PALETTE_CUBE_START as usize..PALETTE_CUBE_START as usize + CUBE_LEVELS.pow(3) as usize;
+                if index < EGA_PALETTE.len() {
+                    Self::from_u32(0xFF00_0000 | EGA_PALETTE[index])
+                } else if cube.contains(&index) {
+                    let dac = palette_cube_dac();
+                    let level = |channel: usize| {
+                        let value = dac[(index - cube.start) * 3 + channel];
+                        value << 2 | value >> 4
+                    };
+                    Self::new(level(0), level(1), level(2), 0xFF)
+                } else {
+                    Self::new(0, 0, 0, 0xFF)
+                }
+            }
+            PixelFormat::Rgb565 => {
+                let widen = |bits: u32, width: u32| (bits << (8 - width) | bits >> (2 * width - 8)) as u8;
+                Self::new(widen(value >> 11 & 0x1F, 5), widen(value >> 5 & 0x3F, 6), widen(value & 0x1F, 5), 0xFF)
+            }
+            PixelFormat::Rgb888 => Self::from_u32(0xFF00_0000 | value),
+            PixelFormat::Argb8888 => Self::from_u32(value),
+        }
+    }
+}
+
+/// How image colors between the levels of a pixel format are rounded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dither {
+    /// Each pixel rounds to the nearest level
+    None,
+    /// A 4x4 Bayer threshold nudges each pixel up to half a level, trading
+    /// banding in gradients for a fine pattern
+    Ordered,
+}
+
+impl Dither {
+    /// `pixel` at (x, y), adjusted before it is converted to `format`
+    pub fn apply(&self, pixel: Pixel, x: u32, y: u32, format: PixelFormat) -> Pixel {
+        if *self == Dither::None {
+            return pixel;
+        }
+        let threshold = BAYER_4X4[y as usize % 4][x as usize % 4];
+        let [r, g, b] = format.channel_steps();
+        let nudge = |value: u8, step: i32| (value as i32 + (2 * threshold - 15) * step / 32).clamp(0, 255) as u8;
+        Pixel::new(nudge(pixel.r, r), nudge(pixel.g, g), nudge(pixel.b, b), pixel.a)
+    }
+}
+
+/// Decoded image, top row first, one 0xAARRGGBB value per pixel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bitmap {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u32>,
+}
+
+/// Pixel data converted for the framebuffer, ready to be blitted
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddedImage {
+    /// File name `image(...)` refers to the image by
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl EmbeddedImage {
+    pub fn load(path: &Path, format: PixelFormat, dither: Dither) -> Result<Self, String> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("Failed to read image '{}': {}", path.display(), e))?;
+        let name = path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned());
+        let bitmap = decode_bmp(&name, &bytes)?;
+        Ok(Self::from_bitmap(name, &bitmap, format, dither))
+    }
+
+    pub fn from_bitmap(name: String, bitmap: &Bitmap, format: PixelFormat, dither: Dither) -> Self {
+        let pixels = bitmap.pixels.iter().enumerate().flat_map(|(i, &argb)| {
+            let (x, y) = (i as u32 % bitmap.width, i as u32 / bitmap.width);
+            let pixel = dither.apply(Pixel::from_u32(argb), x, y, format);
+            match format {
+                PixelFormat::Indexed8 => vec![palette_cube_index(pixel.to_u32())],
+                _ => pixel.to_format(format).to_le_bytes()[..format.bytes_per_pixel()].to_vec(),
+            }
+        }).collect();
+        Self { name, width: bitmap.width, height: bitmap.height, pixels }
+    }
+}
+
+/// Decode an uncompressed 24- or 32-bit BMP. Errors name the file
+pub fn decode_bmp(name: &str, bytes: &[u8]) -> Result<Bitmap, String> {
+    if bytes.starts_with(&PNG_SIGNATURE) {
+        return Err(format!("Image '{}' is a PNG; only uncompressed 24- and 32-bit BMP files can be embedded", name));
+    }
+    let u16_at = |offset: usize| bytes.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+    let u32_at = |offset: usize| bytes.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    let truncated = || format!("Image '{}' is truncated", name);
+
+    if !bytes.starts_with(b"BM") {
+        return Err(format!("Image '{}' is not a BMP file", name));
+    }
+    let data_offset = u32_at(10).ok_or_else(truncated)? as usize;
+    let width = u32_at(18).ok_or_else(truncated)? as i32;
+    let height = u32_at(22).ok_or_else(truncated)? as i32;
+    let bits = u16_at(28).ok_or_else(truncated)?;
+    let compression = u32_at(30).ok_or_else(truncated)?;
+    if compression != 0 {
+        return Err(format!("Image '{}' is compressed; only uncompressed BMP files can be embedded", name));
+    }
+    if bits != 24 && bits != 32 {
+        return Err(format!("Image '{}' has {} bits per pixel; only 24 and 32 are supported", name, bits));
+    }
+    if width <= 0 || height == 0 {
+        return Err(format!("Image '{}' has no pixels", name));
+    }
+
+    // Rows are stored bottom-up unless the height is negative, each padded to 4 bytes
+    let (width, rows) = (width as usize, height.unsigned_abs() as usize);
+    let pixel_bytes = bits as usize / 8;
+    let stride = (width * pixel_bytes).div_ceil(4) * 4;
+    let mut pixels = Vec::with_capacity(width * rows);
+    for row in 0..rows {
+        let stored = if height > 0 { rows - 1 - row } else { row };
+        let start = data_offset + stored * stride;
+        let line = bytes.get(start..start + width * pixel_bytes).ok_or_else(truncated)?;
+        for pixel in line.chunks(pixel_bytes) {
+            // Uncompressed 32-bit BMPs leave the fourth byte undefined, so pixels are opaque
+            pixels.push(0xFF00_0000 | (pixel[2] as u32) << 16 | (pixel[1] as u32) << 8 | pixel[0] as u32);
+        }
+    }
+    Ok(Bitmap { width: width as u32, height: rows as u32, pixels })
+}
+
+/// Nearest entry of the color cube for an 0xAARRGGBB pixel
+pub fn palette_cube_index(argb: u32) -> u8 {
+    let level = |shift: u32| ((argb >> shift & 0xFF) * (CUBE_LEVELS - 1) + 127) / 255;
+    PALETTE_CUBE_START + (level(16) * CUBE_LEVELS * CUBE_LEVELS + level(8) * CUBE_LEVELS + level(0)) as u8
+}
+
+/// Red, green and blue of every cube entry as the 6-bit values the VGA DAC takes
+pub fn palette_cube_dac() -> Vec<u8> {
+    let level = |value: u32| (value * 63 / (CUBE_LEVELS - 1)) as u8;
+    (0..CUBE_LEVELS.pow(3))
+        .flat_map(|i| [level(i / (CUBE_LEVELS * CUBE_LEVELS)), level(i / CUBE_LEVELS % CUBE_LEVELS), level(i % CUBE_LEVELS)])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_checker_bmp() {
+        let bytes = include_bytes!("../examples/checker.bmp");
+        let bitmap = decode_bmp("checker.bmp", bytes).unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (6, 4));
+        let (red, blue) = (0xFFFF_0000, 0xFF00_80FF);
+        for y in 0..4 {
+            for x in 0..6 {
+                let expected = if (x / 2 + y / 2) % 2 == 0 { red } else { blue };
+                assert_eq!(bitmap.pixels[(y * 6 + x) as usize], expected, "pixel ({}, {})", x, y);
+            }
+        }
+
+        let argb = EmbeddedImage::from_bitmap("checker.bmp".into(), &bitmap, PixelFormat::Argb8888, Dither::None);
+        assert_eq!(argb.pixels[..8], [0x00, 0x00, 0xFF, 0xFF, 0x00, 0x00, 0xFF, 0xFF]);
+        assert_eq!(argb.pixels[8..12], [0xFF, 0x80, 0x00, 0xFF]);
+        let indexed = EmbeddedImage::from_bitmap("checker.bmp".into(), &bitmap, PixelFormat::Indexed8, Dither::None);
+        assert_eq!(indexed.pixels[..3], [PALETTE_CUBE_START + 5 * 36, PALETTE_CUBE_START + 5 * 36, PALETTE_CUBE_START + 3 * 6 + 5]);
+    }
+
+    #[test]
+    fn test_decode_rejects_unsupported() {
+        let png = decode_bmp("logo.png", &PNG_SIGNATURE).unwrap_err();
+        assert!(png.contains("'logo.png' is a PNG"), "{}", png);
+
+        let mut bytes = include_bytes!("../examples/checker.bmp").to_vec();
+        bytes[28] = 16;
+        let err = decode_bmp("checker.bmp", &bytes).unwrap_err();
+        assert!(err.contains("has 16 bits per pixel"), "{}", err);
+
+        bytes[28] = 24;
+        bytes.truncate(100);
+        assert_eq!(decode_bmp("checker.bmp", &bytes).unwrap_err(), "Image 'checker.bmp' is truncated");
+    }
+
+    #[test]
+    fn test_pixel_format_conversions() {
+        let red = Pixel::new(255, 0, 0, 255);
+        assert_eq!(red.to_format(PixelFormat::Indexed8), 4);
+        assert_eq!(Pixel::new(250, 250, 240, 255).to_format(PixelFormat::Indexed8), 15);
+        assert_eq!(Pixel::new(90, 90, 255, 255).to_format(PixelFormat::Indexed8), 9);
+        assert_eq!(Pixel::from_format(PixelFormat::Indexed8, 4), Pixel::new(0xAA, 0, 0, 255));
+        assert_eq!(Pixel::from_format(PixelFormat::Indexed8, PALETTE_CUBE_START as u32 + 5 * 36), red);
+        assert_eq!(Pixel::from_format(PixelFormat::Indexed8, 255), Pixel::new(0, 0, 0, 255));
+
+        assert_eq!(red.to_format(PixelFormat::Rgb565), 0xF800);
+        assert_eq!(Pixel::new(0, 255, 0, 255).to_format(PixelFormat::Rgb565), 0x07E0);
+        assert_eq!(Pixel::new(0x12, 0x34, 0x56, 255).to_format(PixelFormat::Rgb565), 0x11AA);
+        assert_eq!(Pixel::from_format(PixelFormat::Rgb565, 0xFFFF), Pixel::new(255, 255, 255, 255));
+        assert_eq!(Pixel::from_format(PixelFormat::Rgb565, 0x11AA), Pixel::new(0x10, 0x34, 0x52, 255));
+
+        let color = Pixel::new(0x12, 0x34, 0x56, 0x80);
+        assert_eq!(color.to_format(PixelFormat::Rgb888), 0x123456);
+        assert_eq!(color.to_format(PixelFormat::Argb8888), 0x8012_3456);
+        assert_eq!(Pixel::from_format(PixelFormat::Argb8888, 0x8012_3456), color);
+        assert_eq!(Pixel::from_format(PixelFormat::Rgb888, 0x123456), Pixel::new(0x12, 0x34, 0x56, 255));
+    }
+
+    #[test]
+    fn test_ordered_dither_mixes_levels() {
+        // Between two cube levels, a flat color dithers to a mix of both
+        let gray = Bitmap { width: 4, height: 4, pixels: vec![0xFF40_4040; 16] };
+        let flat = EmbeddedImage::from_bitmap("gray".into(), &gray, PixelFormat::Indexed8, Dither::None);
+        assert!(flat.pixels.iter().all(|&p| p == flat.pixels[0]));
+        let dithered = EmbeddedImage::from_bitmap("gray".into(), &gray, PixelFormat::Indexed8, Dither::Ordered);
+        let mut levels = dithered.pixels.clone();
+        levels.sort();
+        levels.dedup();
+        assert_eq!(levels, [palette_cube_index(0xFF33_3333), palette_cube_index(0xFF66_6666)]);
+
+        // Formats that show every level are left alone
+        let pixel = Pixel::new(0x40, 0x41, 0x42, 255);
+        assert_eq!(Dither::Ordered.apply(pixel, 1, 2, PixelFormat::Argb8888), pixel);
+    }
+}
@@ -0,0 +1,881 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+use crate::parser::{BoolOp, CompareOp, Expr, FStringPart, Op, Program, Span, Statement, UnaryOp};
+
+// Runs a `Program` by walking its AST, so programs can be tried without an
+// assembler or linker. It follows what the Linux64 backend's code does:
+//
+// - Integers are 64-bit and wrap on overflow.
+// - `/` and `%` truncate toward zero, so a remainder has the sign of the
+//   dividend.
+// - Negative indices count from the end of a string, but are out of range
+//   for a list.
+// - Lists and dicts are shared references, so `append` through one name is
+//   seen through every other.
+// - Functions see their parameters and their own variables but no globals,
+//   and can be called before the line defining them.
+//
+// Where compiled code has no meaningful answer, or chose a cheaper one, the
+// interpreter behaves like Python instead. `test_known_divergences` pins each case:
+//
+// - print writes booleans as True and False; compiled code writes 1 and 0.
+// - `and` and `or` give back the deciding operand; compiled code gives 1 or 0.
+// - Strings, lists, dicts and structs print their contents. Compiled code
+//   prints a list, a struct or a string from a function whose return type
+//   the type checker cannot tell as an address, and `len` only measures
+//   lists and what it knows to be strings.
+// - `len`, subscripts and slices count a string's bytes, as compiled code
+//   does, but a piece that splits a character holds U+FFFD instead of the
+//   bytes.
+// - `upper`, `lower` and `strip` know all of Unicode; compiled code only
+//   changes ASCII letters and strips ASCII whitespace. `replace` with an
+//   empty old string puts the new one around every character, where
+//   compiled code leaves the string as it is.
+// - Runtime errors such as division by zero or an index out of range name
+//   the position they happened at.
+// - A float may cross any call. The compiler rejects one passed to a
+//   parameter, or returned by a function, not declared float.
+// - Dict keys may be any value; the compiler takes only strings.
+//
+// Includes must be expanded before running. Imports, hardware access, asm() and
+// the OS, disk and framebuffer builtins need the compiler and are reported as errors.
+
+/// Calls nested deeper than this stop the program instead of overflowing the host stack
+const MAX_CALL_DEPTH: usize = 1000;
+
+/// Stack of the thread programs run on, enough for `MAX_CALL_DEPTH` calls in a debug build
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    List(Rc<RefCell<Vec<Value>>>),
+    /// Entries in insertion order
+    Dict(Rc<RefCell<Vec<(Value, Value)>>>),
+    /// An instance of a declared struct, field values in declaration order
+    Struct(Rc<StructType>, Rc<RefCell<Vec<Value>>>),
+    None,
+    Bool(bool),
+}
+
+#[derive(Debug)]
+pub struct StructType {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+impl Value {
+    pub fn list(values: Vec<Value>) -> Self {
+        Value::List(Rc::new(RefCell::new(values)))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::Str(_) => "str",
+            Value::List(_) => "list",
+            Value::Dict(_) => "dict",
+            Value::Struct(..) => "struct",
+            Value::None => "NoneType",
+            Value::Bool(_) => "bool",
+        }
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Int(n) => *n != 0,
+            Value::Float(f) => *f != 0.0,
+            Value::Str(s) => !s.is_empty(),
+            Value::List(items) => !items.borrow().is_empty(),
+            Value::Dict(entries) => !entries.borrow().is_empty(),
+            Value::Struct(..) => true,
+            Value::None => false,
+            Value::Bool(b) => *b,
+        }
+    }
+
+    fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::None, Value::None) => true,
+            (Value::List(a), Value::List(b)) => {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| x.equals(y))
+            }
+            (Value::Dict(a), Value::Dict(b)) => {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len() && a.iter().all(|(key, value)| b.iter().any(|(k, v)| k.equals(key) && v.equals(value)))
+            }
+            // Compiled code compares the addresses
+            (Value::Struct(_, a), Value::Struct(_, b)) => Rc::ptr_eq(a, b),
+            _ => match (self.as_number(), other.as_number()) {
+                (Some(Number::Int(a)), Some(Number::Int(b))) => a == b,
+                (Some(a), Some(b)) => a.as_f64() == b.as_f64(),
+                _ => false,
+            },
+        }
+    }
+
+    fn as_number(&self) -> Option<Number> {
+        match self {
+            Value::Int(n) => Some(Number::Int(*n)),
+            Value::Bool(b) => Some(Number::Int(*b as i64)),
+            Value::Float(f) => Some(Number::Float(*f)),
+            _ => None,
+        }
+    }
+
+    /// How the value looks inside a list or dict
+    fn repr(&self) -> String {
+        match self {
+            Value::Str(s) => format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'")),
+            other => other.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Float(x) => write!(f, "{}", format_float(*x)),
+            Value::Str(s) => write!(f, "{}", s),
+            Value::List(items) => {
+                let items: Vec<String> = items.borrow().iter().map(Value::repr).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Value::Dict(entries) => {
+                let entries: Vec<String> = entries.borrow().iter().map(|(k, v)| format!("{}: {}", k.repr(), v.repr())).collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+            Value::Struct(kind, values) => {
+                let fields: Vec<String> = kind.fields.iter().zip(values.borrow().iter()).map(|(name, v)| format!("{}={}", name, v.repr())).collect();
+                write!(f, "{}({})", kind.name, fields.join(", "))
+            }
+            Value::None => write!(f, "None"),
+            Value::Bool(b) => write!(f, "{}", if *b { "True" } else { "False" }),
+        }
+    }
+}
+
+/// Floats as the runtime's print_float writes them: six decimals at most,
+/// trailing zeros dropped but one kept after the point
+fn format_float(x: f64) -> String {
+    if !x.is_finite() {
+        return x.to_string();
+    }
+    let text = format!("{:.6}", x);
+    let trimmed = text.trim_end_matches('0');
+    if trimmed.ends_with('.') { format!("{}0", trimmed) } else { trimmed.to_string() }
+}
+
+#[derive(Clone, Copy)]
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn as_f64(self) -> f64 {
+        match self {
+            Number::Int(n) => n as f64,
+            Number::Float(f) => f,
+        }
+    }
+}
+
+/// How a statement finished
+enum Flow {
+    Next,
+    Break,
+    Continue,
+    Return(Value),
+}
+
+struct Function {
+    args: Vec<String>,
+    body: Vec<Statement>,
+}
+
+pub struct Interpreter<'io> {
+    /// Variables of the top level and of each active call, innermost last
+    frames: Vec<HashMap<String, Value>>,
+    functions: HashMap<String, Rc<Function>>,
+    structs: HashMap<String, Rc<StructType>>,
+    input: Box<dyn BufRead + 'io>,
+    output: Box<dyn Write + 'io>,
+}
+
+impl<'io> Interpreter<'io> {
+    pub fn new(input: impl BufRead + 'io, output: impl Write + 'io) -> Self {
+        Self {
+            frames: vec![HashMap::new()],
+            functions: HashMap::new(),
+            structs: HashMap::new(),
+            input: Box::new(input),
+            output: Box::new(output),
+        }
+    }
+
+    pub fn run(&mut self, program: &Program) -> Result<(), String> {
+        let mut program = program.clone();
+        crate::compiler::resolve_constants(&mut program)?;
+        crate::typecheck::check_types(&mut program, false)?;
+        self.define_functions(&program.body);
+        for (name, fields) in crate::backend::struct_declarations(&program)? {
+            self.structs.insert(name.clone(), Rc::new(StructType { name, fields }));
+        }
+        let result = self.execute_block(&program.body).map(|_| ());
+        self.output.flush().map_err(|e| format!("Failed to write output: {}", e))?;
+        result
+    }
+
+    /// Value of a top-level variable after `run`
+    pub fn global(&self, name: &str) -> Option<&Value> {
+        self.frames.first()?.get(name)
+    }
+
+    fn define_functions(&mut self, body: &[Statement]) {
+        for stmt in body {
+            if let Statement::FunctionDef { name, args, body, .. } = stmt {
+                self.functions.insert(name.clone(), Rc::new(Function { args: args.clone(), body: body.clone() }));
+            }
+        }
+    }
+
+    fn execute_block(&mut self, body: &[Statement]) -> Result<Flow, String> {
+        for stmt in body {
+            match self.execute(stmt)? {
+                Flow::Next => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn execute(&mut self, stmt: &Statement) -> Result<Flow, String> {
+        match stmt {
+            Statement::VarDecl { name, value, .. } | Statement::Assign { target: name, value, .. } => {
+                let value = self.evaluate(value)?;
+                self.scope().insert(name.clone(), value);
+            }
+            Statement::AugAssign { target, op, value, span } => {
+                let current = self.variable(target, *span)?;
+                let value = self.evaluate(value)?;
+                let result = binary(op, current, value, *span)?;
+                self.scope().insert(target.clone(), result);
+            }
+            Statement::IndexAssign { target, index, value, span } => {
+                let container = self.evaluate(target)?;
+                let index = self.evaluate(index)?;
+                let value = self.evaluate(value)?;
+                match container {
+                    Value::List(items) => {
+                        let mut items = items.borrow_mut();
+                        let slot = list_index(&index, items.len(), false, *span)?;
+                        items[slot] = value;
+                    }
+                    Value::Dict(entries) => {
+                        let mut entries = entries.borrow_mut();
+                        match entries.iter_mut().find(|(key, _)| key.equals(&index)) {
+                            Some(entry) => entry.1 = value,
+                            None => entries.push((index, value)),
+                        }
+                    }
+                    other => return Err(format!("Cannot assign to an element of a {} at {}", other.type_name(), span)),
+                }
+            }
+            Statement::FieldAssign { target, field, value, span } => {
+                let object = self.evaluate(target)?;
+                let value = self.evaluate(value)?;
+                let (index, values) = struct_field(&object, field, *span)?;
+                values.borrow_mut()[index] = value;
+            }
+            Statement::Expr(expr) => {
+                self.evaluate(expr)?;
+            }
+            Statement::Return(value, _) => {
+                let value = match value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Value::None,
+                };
+                return Ok(Flow::Return(value));
+            }
+            Statement::If { condition, then_block, elif_blocks, else_block, .. } => {
+                if self.evaluate(condition)?.is_truthy() {
+                    return self.execute_block(then_block);
+                }
+                for (condition, block) in elif_blocks {
+                    if self.evaluate(condition)?.is_truthy() {
+                        return self.execute_block(block);
+                    }
+                }
+                if let Some(block) = else_block {
+                    return self.execute_block(block);
+                }
+            }
+            Statement::While { condition, body, orelse, .. } => {
+                while self.evaluate(condition)?.is_truthy() {
+                    match self.execute_block(body)? {
+                        Flow::Break => return Ok(Flow::Next),
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Next | Flow::Continue => {}
+                    }
+                }
+                if let Some(block) = orelse {
+                    return self.execute_block(block);
+                }
+            }
+            Statement::For { var, iter, body, span } => {
+                for item in self.iteration(iter, *span)? {
+                    self.scope().insert(var.clone(), item);
+                    match self.execute_block(body)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Next | Flow::Continue => {}
+                    }
+                }
+            }
+            // Defined before the program started
+            Statement::FunctionDef { .. } | Statement::ConstDecl { .. } | Statement::Pass => {}
+            Statement::StructDef { name, span, .. } => {
+                if self.frames.len() > 1 || !self.structs.contains_key(name) {
+                    return Err(format!("Structs must be declared at the top level at {}", span));
+                }
+            }
+            Statement::Break => return Ok(Flow::Break),
+            Statement::Continue => return Ok(Flow::Continue),
+            Statement::HardwareFunctionDef { span, .. } | Statement::HardwareDecl { span, .. } => {
+                return Err(format!("Hardware access needs the compiler; run with --backend native at {}", span));
+            }
+            Statement::Include { filename, span } => {
+                return Err(format!("'{}' was not included before running at {}", filename, span));
+            }
+            Statement::Import { module, span, .. } => {
+                return Err(format!("Module '{}' is implemented in assembly and needs the compiler; run with --backend native at {}", module, span));
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    /// The values a for loop binds in turn
+    fn iteration(&mut self, iter: &Expr, span: Span) -> Result<Vec<Value>, String> {
+        if let Expr::Call { func, args, .. } = iter {
+            if func == "range" && !self.functions.contains_key(func) {
+                return self.range(args, span).map(|values| values.into_iter().map(Value::Int).collect());
+            }
+        }
+        match self.evaluate(iter)? {
+            Value::List(items) => Ok(items.borrow().clone()),
+            Value::Dict(entries) => Ok(entries.borrow().iter().map(|(key, _)| key.clone()).collect()),
+            Value::Str(s) => Ok(s.chars().map(|c| Value::Str(c.to_string())).collect()),
+            other => Err(format!("Cannot iterate over a {} at {}", other.type_name(), span)),
+        }
+    }
+
+    fn range(&mut self, args: &[Expr], span: Span) -> Result<Vec<i64>, String> {
+        let mut bounds = Vec::with_capacity(args.len());
+        for arg in args {
+            match self.evaluate(arg)? {
+                Value::Int(n) => bounds.push(n),
+                _ => return Err(format!("range() arguments must be integers at {}", span)),
+            }
+        }
+        let (start, stop, step) = match bounds[..] {
+            [stop] => (0, stop, 1),
+            [start, stop] => (start, stop, 1),
+            [start, stop, step] => (start, stop, step),
+            _ => return Err(format!("range() takes 1 to 3 arguments at {}", span)),
+        };
+        if step == 0 {
+            return Err(format!("range() step must not be zero at {}", span));
+        }
+        let mut values = Vec::new();
+        let mut n = start;
+        while (step > 0 && n < stop) || (step < 0 && n > stop) {
+            values.push(n);
+            n = n.wrapping_add(step);
+        }
+        Ok(values)
+    }
+
+    fn scope(&mut self) -> &mut HashMap<String, Value> {
+        self.frames.last_mut().expect("the top-level frame is never popped")
+    }
+
+    fn variable(&self, name: &str, span: Span) -> Result<Value, String> {
+        self.frames.last().and_then(|frame| frame.get(name)).cloned()
+            .ok_or_else(|| format!("Undefined variable '{}' at {}", name, span))
+    }
+
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value, String> {
+        Ok(match expr {
+            Expr::Number(n, _) => Value::Int(*n),
+            Expr::Float(f, _) => Value::Float(*f),
+            Expr::Boolean(b, _) => Value::Bool(*b),
+            Expr::String(s, _) => Value::Str(s.clone()),
+            Expr::None(_) => Value::None,
+            Expr::Var(name, span) => self.variable(name, *span)?,
+            Expr::BinOp { left, op, right, span } => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                binary(op, left, right, *span)?
+            }
+            Expr::UnaryOp { op, operand, span } => {
+                let value = self.evaluate(operand)?;
+                match (op, value.as_number()) {
+                    (UnaryOp::Not, _) => Value::Bool(!value.is_truthy()),
+                    (UnaryOp::Plus, Some(Number::Int(n))) => Value::Int(n),
+                    (UnaryOp::Minus, Some(Number::Int(n))) => Value::Int(n.wrapping_neg()),
+                    (UnaryOp::Invert, Some(Number::Int(n))) => Value::Int(!n),
+                    (UnaryOp::Plus, Some(Number::Float(f))) => Value::Float(f),
+                    (UnaryOp::Minus, Some(Number::Float(f))) => Value::Float(-f),
+                    _ => return Err(format!("Bad operand type {} for unary {:?} at {}", value.type_name(), op, span)),
+                }
+            }
+            Expr::BoolOp { op, values, .. } => {
+                let mut result = Value::Bool(matches!(op, BoolOp::And));
+                for value in values {
+                    result = self.evaluate(value)?;
+                    if result.is_truthy() != matches!(op, BoolOp::And) {
+                        break;
+                    }
+                }
+                result
+            }
+            Expr::Compare { left, ops, comparators, span } => {
+                let mut left = self.evaluate(left)?;
+                for (op, right) in ops.iter().zip(comparators) {
+                    let right = self.evaluate(right)?;
+                    if !compare(op, &left, &right, *span)? {
+                        return Ok(Value::Bool(false));
+                    }
+                    left = right;
+                }
+                Value::Bool(true)
+            }
+            Expr::Call { func, args, kwargs, span } if func == "print" => {
+                let end = match crate::backend::print_end(kwargs, *span)? {
+                    Some(end) => match self.evaluate(end)? {
+                        Value::Str(s) => s,
+                        _ => return Err(format!("print() end must be a string at {}", span)),
+                    },
+                    None => "\n".to_string(),
+                };
+                let text: Vec<String> = args.iter().map(|arg| self.evaluate(arg).map(|value| value.to_string())).collect::<Result<_, _>>()?;
+                write!(self.output, "{}{}", text.join(" "), end).map_err(|e| format!("Failed to write output: {}", e))?;
+                Value::None
+            }
+            Expr::Call { func, kwargs, span, .. } if !kwargs.is_empty() => {
+                crate::backend::reject_kwargs(func, kwargs, *span)?;
+                Value::None
+            }
+            Expr::Call { func, args, span, .. } => self.call(func, args, *span)?,
+            Expr::FString { parts, .. } => {
+                let mut text = String::new();
+                for part in parts {
+                    match part {
+                        FStringPart::Literal(s) => text.push_str(s),
+                        FStringPart::Expr(e) => text.push_str(&self.evaluate(e)?.to_string()),
+                    }
+                }
+                Value::Str(text)
+            }
+            Expr::HardwareCall { device, span, .. } => {
+                return Err(format!("Device '{}' needs the compiler; run with --backend native at {}", device, span));
+            }
+            Expr::List { elements, .. } => {
+                let values = elements.iter().map(|e| self.evaluate(e)).collect::<Result<Vec<_>, _>>()?;
+                Value::list(values)
+            }
+            Expr::Dict { entries, .. } => {
+                let mut values: Vec<(Value, Value)> = Vec::with_capacity(entries.len());
+                for (key, value) in entries {
+                    let (key, value) = (self.evaluate(key)?, self.evaluate(value)?);
+                    match values.iter_mut().find(|(existing, _)| existing.equals(&key)) {
+                        Some(entry) => entry.1 = value,
+                        None => values.push((key, value)),
+                    }
+                }
+                Value::Dict(Rc::new(RefCell::new(values)))
+            }
+            Expr::Index { value, index, span } => {
+                let container = self.evaluate(value)?;
+                let index = self.evaluate(index)?;
+                match container {
+                    Value::List(items) => {
+                        let items = items.borrow();
+                        items[list_index(&index, items.len(), false, *span)?].clone()
+                    }
+                    Value::Str(s) => {
+                        let position = list_index(&index, s.len(), true, *span)?;
+                        Value::Str(String::from_utf8_lossy(&s.as_bytes()[position..=position]).into_owned())
+                    }
+                    Value::Dict(entries) => entries.borrow().iter().find(|(key, _)| key.equals(&index)).map(|(_, v)| v.clone())
+                        .ok_or_else(|| format!("Key {} is not in the dict at {}", index.repr(), span))?,
+                    other => return Err(format!("A {} cannot be indexed at {}", other.type_name(), span)),
+                }
+            }
+            Expr::Slice { value, start, stop, span } => {
+                let s = match self.evaluate(value)? {
+                    Value::Str(s) => s,
+                    other => return Err(format!("Only strings can be sliced, not a {} at {}", other.type_name(), span)),
+                };
+                let start = self.slice_bound(start, s.len(), 0, *span)?;
+                let stop = self.slice_bound(stop, s.len(), s.len(), *span)?;
+                Value::Str(String::from_utf8_lossy(&s.as_bytes()[start..stop.max(start)]).into_owned())
+            }
+            Expr::FieldAccess { object, field, span } => {
+                let object = self.evaluate(object)?;
+                let (index, values) = struct_field(&object, field, *span)?;
+                let value = values.borrow()[index].clone();
+                value
+            }
+            Expr::MethodCall { receiver, method, args, span } => {
+                let receiver = self.evaluate(receiver)?;
+                let args = args.iter().map(|arg| self.evaluate(arg)).collect::<Result<Vec<_>, _>>()?;
+                string_method(&receiver, method, &args, *span)?
+            }
+        })
+    }
+
+    /// A slice bound counted from the end when negative and clamped to `0..=len`
+    fn slice_bound(&mut self, bound: &Option<Box<Expr>>, len: usize, default: usize, span: Span) -> Result<usize, String> {
+        let Some(bound) = bound else { return Ok(default) };
+        let value = self.evaluate(bound)?;
+        let Some(Number::Int(n)) = value.as_number() else {
+            return Err(format!("Slice bounds must be integers, not {} at {}", value.type_name(), span));
+        };
+        let position = if n < 0 { n.saturating_add(len as i64) } else { n };
+        Ok(position.clamp(0, len as i64) as usize)
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr], span: Span) -> Result<Value, String> {
+        let values = args.iter().map(|arg| self.evaluate(arg)).collect::<Result<Vec<_>, _>>()?;
+        if let Some(function) = self.functions.get(name).cloned() {
+            if values.len() != function.args.len() {
+                return Err(format!("Function '{}' takes {} arguments but {} were given at {}", name, function.args.len(), values.len(), span));
+            }
+            if self.frames.len() > MAX_CALL_DEPTH {
+                return Err(format!("Calls nested deeper than {} at {}", MAX_CALL_DEPTH, span));
+            }
+            self.frames.push(function.args.iter().cloned().zip(values).collect());
+            let result = self.execute_block(&function.body);
+            self.frames.pop();
+            return match result? {
+                Flow::Return(value) => Ok(value),
+                _ => Ok(Value::None),
+            };
+        }
+        if let Some(kind) = self.structs.get(name).cloned() {
+            if values.len() != kind.fields.len() {
+                let plural = if kind.fields.len() == 1 { "" } else { "s" };
+                return Err(format!("{}() takes {} argument{} but {} were given at {}", name, kind.fields.len(), plural, values.len(), span));
+            }
+            return Ok(Value::Struct(kind, Rc::new(RefCell::new(values))));
+        }
+        self.builtin(name, values, span)
+    }
+
+    fn builtin(&mut self, name: &str, args: Vec<Value>, span: Span) -> Result<Value, String> {
+        let arity = |count: usize| if args.len() == count {
+            Ok(())
+        } else {
+            Err(format!("{}() takes {} argument{} but {} were given at {}", name, count, if count == 1 { "" } else { "s" }, args.len(), span))
+        };
+        match name {
+            "len" => {
+                arity(1)?;
+                match &args[0] {
+                    // Bytes, which is what subscripts and slices count
+                    Value::Str(s) => Ok(Value::Int(s.len() as i64)),
+                    Value::List(items) => Ok(Value::Int(items.borrow().len() as i64)),
+                    Value::Dict(entries) => Ok(Value::Int(entries.borrow().len() as i64)),
+                    other => Err(format!("A {} has no length at {}", other.type_name(), span)),
+                }
+            }
+            "str" => {
+                arity(1)?;
+                Ok(Value::Str(args[0].to_string()))
+            }
+            "type" => {
+                arity(1)?;
+                Ok(Value::Str(args[0].type_name().to_string()))
+            }
+            "input" => {
+                if let Some(prompt) = args.first() {
+                    write!(self.output, "{}", prompt).and_then(|_| self.output.flush()).map_err(|e| format!("Failed to write output: {}", e))?;
+                }
+                let mut line = String::new();
+                self.input.read_line(&mut line).map_err(|e| format!("Failed to read input: {}", e))?;
+                Ok(Value::Str(line.trim_end_matches(['\n', '\r']).to_string()))
+            }
+            "append" => {
+                arity(2)?;
+                match &args[0] {
+                    Value::List(items) => {
+                        items.borrow_mut().push(args[1].clone());
+                        Ok(Value::None)
+                    }
+                    other => Err(format!("Cannot append to a {} at {}", other.type_name(), span)),
+                }
+            }
+            "range" => {
+                let values = args.iter().map(|arg| match arg {
+                    Value::Int(n) => Ok(Expr::Number(*n, span)),
+                    _ => Err(format!("range() arguments must be integers at {}", span)),
+                }).collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::list(self.range(&values, span)?.into_iter().map(Value::Int).collect()))
+            }
+            "sleep" => {
+                arity(1)?;
+                let Value::Int(ms) = args[0] else { return Err(format!("sleep() takes milliseconds as an integer at {}", span)) };
+                std::thread::sleep(std::time::Duration::from_millis(ms.max(0) as u64));
+                Ok(Value::None)
+            }
+            "asm" => Err(format!("asm() runs machine code and needs the compiler; run with --backend native at {}", span)),
+            _ => Err(format!("Function '{}' is not defined at {}", name, span)),
+        }
+    }
+}
+
+/// Where `field` is in the struct `object`, and the struct's field values
+fn struct_field<'a>(object: &'a Value, field: &str, span: Span) -> Result<(usize, &'a RefCell<Vec<Value>>), String> {
+    match object {
+        Value::Struct(kind, values) => kind.fields.iter().position(|name| name == field)
+            .map(|index| (index, values.as_ref()))
+            .ok_or_else(|| format!("Struct '{}' has no field '{}' at {}", kind.name, field, span)),
+        other => Err(format!("A {} has no field '{}' at {}", other.type_name(), field, span)),
+    }
+}
+
+/// `receiver.method(args)`, where only strings have methods
+fn string_method(receiver: &Value, method: &str, args: &[Value], span: Span) -> Result<Value, String> {
+    let Value::Str(s) = receiver else {
+        return Err(format!("A {} has no method '{}' at {}", receiver.type_name(), method, span));
+    };
+    let (arity, _) = crate::extension::string_method(method)
+        .ok_or_else(|| crate::extension::unknown_string_method(method, span))?;
+    if args.len() != arity {
+        return Err(format!("str.{}() takes {} argument{} but {} were given at {}", method, arity, if arity == 1 { "" } else { "s" }, args.len(), span));
+    }
+    let args = args.iter().map(|arg| match arg {
+        Value::Str(text) => Ok(text.as_str()),
+        other => Err(format!("str.{}() takes strings, not {} at {}", method, other.type_name(), span)),
+    }).collect::<Result<Vec<_>, _>>()?;
+    Ok(match (method, args.as_slice()) {
+        // A byte position, like subscripts
+        ("find", [needle]) => Value::Int(s.find(needle).map_or(-1, |position| position as i64)),
+        ("replace", [old, new]) => Value::Str(s.replace(old, new)),
+        ("split", [""]) => return Err(format!("Empty separator for str.split() at {}", span)),
+        ("split", [separator]) => Value::list(s.split(separator).map(|piece| Value::Str(piece.to_string())).collect()),
+        ("upper", []) => Value::Str(s.to_uppercase()),
+        ("lower", []) => Value::Str(s.to_lowercase()),
+        _ => Value::Str(s.trim().to_string()),
+    })
+}
+
+/// Position among `len` items that `index` names. Negative indices count
+/// from the end when `from_end` is set, as compiled code does for strings;
+/// list_get_64 and list_set_64 treat them as out of range
+fn list_index(index: &Value, len: usize, from_end: bool, span: Span) -> Result<usize, String> {
+    let Some(Number::Int(n)) = index.as_number() else {
+        return Err(format!("Indices must be integers, not {} at {}", index.type_name(), span));
+    };
+    let position = if n < 0 && from_end { n + len as i64 } else { n };
+    if position < 0 || position >= len as i64 {
+        return Err(format!("Index {} is out of range for {} items at {}", n, len, span));
+    }
+    Ok(position as usize)
+}
+
+fn binary(op: &Op, left: Value, right: Value, span: Span) -> Result<Value, String> {
+    match (op, &left, &right) {
+        (Op::Add, Value::Str(a), Value::Str(b)) => return Ok(Value::Str(format!("{}{}", a, b))),
+        (Op::Mul, Value::Str(s), Value::Int(n)) | (Op::Mul, Value::Int(n), Value::Str(s)) => return Ok(Value::Str(s.repeat((*n).max(0) as usize))),
+        (Op::Add, Value::List(a), Value::List(b)) => {
+            let mut items = a.borrow().clone();
+            items.extend(b.borrow().iter().cloned());
+            return Ok(Value::list(items));
+        }
+        _ => {}
+    }
+    let (Some(a), Some(b)) = (left.as_number(), right.as_number()) else {
+        return Err(format!("Unsupported operand types for {:?}: {} and {} at {}", op, left.type_name(), right.type_name(), span));
+    };
+    let division_by_zero = || format!("Division by zero at {}", span);
+    Ok(match (a, b) {
+        (Number::Int(a), Number::Int(b)) => Value::Int(match op {
+            Op::Add => a.wrapping_add(b),
+            Op::Sub => a.wrapping_sub(b),
+            Op::Mul => a.wrapping_mul(b),
+            Op::Div => a.checked_rem(b).map(|_| a.wrapping_div(b)).ok_or_else(division_by_zero)?,
+            Op::FloorDiv => {
+                let quotient = a.checked_rem(b).map(|_| a.wrapping_div(b)).ok_or_else(division_by_zero)?;
+                if (a % b != 0) && ((a < 0) != (b < 0)) { quotient - 1 } else { quotient }
+            }
+            Op::Mod => a.checked_rem(b).ok_or_else(division_by_zero)?,
+            Op::Pow if b < 0 => return Ok(Value::Float((a as f64).powf(b as f64))),
+            Op::Pow => a.wrapping_pow(b.min(u32::MAX as i64) as u32),
+            Op::BitAnd => a & b,
+            Op::BitOr => a | b,
+            Op::BitXor => a ^ b,
+        }),
+        (a, b) => {
+            let (a, b) = (a.as_f64(), b.as_f64());
+            Value::Float(match op {
+                Op::Add => a + b,
+                Op::Sub => a - b,
+                Op::Mul => a * b,
+                Op::Div | Op::FloorDiv | Op::Mod if b == 0.0 => return Err(division_by_zero()),
+                Op::Div => a / b,
+                Op::FloorDiv => (a / b).floor(),
+                Op::Mod => a - b * (a / b).floor(),
+                Op::Pow => a.powf(b),
+                Op::BitAnd | Op::BitOr | Op::BitXor => {
+                    return Err(format!("Bitwise {:?} needs integers at {}", op, span));
+                }
+            })
+        }
+    })
+}
+
+fn compare(op: &CompareOp, left: &Value, right: &Value, span: Span) -> Result<bool, String> {
+    let ordering = || match (left, right) {
+        (Value::Str(a), Value::Str(b)) => Ok(a.cmp(b)),
+        _ => match (left.as_number(), right.as_number()) {
+            (Some(a), Some(b)) => a.as_f64().partial_cmp(&b.as_f64()).ok_or_else(|| format!("Cannot order NaN at {}", span)),
+            _ => Err(format!("Cannot order {} and {} at {}", left.type_name(), right.type_name(), span)),
+        },
+    };
+    let contains = || match right {
+        Value::List(items) => Ok(items.borrow().iter().any(|item| item.equals(left))),
+        Value::Dict(entries) => Ok(entries.borrow().iter().any(|(key, _)| key.equals(left))),
+        Value::Str(s) => match left {
+            Value::Str(part) => Ok(s.contains(part.as_str())),
+            _ => Err(format!("'in <str>' needs a string on the left at {}", span)),
+        },
+        other => Err(format!("A {} cannot be searched with 'in' at {}", other.type_name(), span)),
+    };
+    Ok(match op {
+        CompareOp::Eq | CompareOp::Is => left.equals(right),
+        CompareOp::Ne | CompareOp::IsNot => !left.equals(right),
+        CompareOp::Lt => ordering()?.is_lt(),
+        CompareOp::Le => ordering()?.is_le(),
+        CompareOp::Gt => ordering()?.is_gt(),
+        CompareOp::Ge => ordering()?.is_ge(),
+        CompareOp::In => contains()?,
+        CompareOp::NotIn => !contains()?,
+    })
+}
+
+/// Run `program` on the process's stdin and stdout
+pub fn run_program(program: &Program) -> Result<(), String> {
+    with_large_stack(|| Interpreter::new(std::io::stdin().lock(), std::io::stdout().lock()).run(program))
+}
+
+/// Run `f` on a thread whose stack fits the deepest calls the interpreter allows
+pub fn with_large_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, f)
+            .expect("failed to start the interpreter thread")
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Target;
+
+    fn interpret(source: &str, input: &str) -> Result<String, String> {
+        let program = crate::parser::parse_program(source).map_err(|errors| format!("{:?}", errors))?;
+        with_large_stack(|| {
+            let mut output = Vec::new();
+            Interpreter::new(input.as_bytes(), &mut output).run(&program)?;
+            Ok(String::from_utf8(output).unwrap())
+        })
+    }
+
+    fn compile_and_run(source: &str, input: &str, name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("earthang_interp_{}_{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        let executable = crate::compiler::compile_to_executable(source, &dir.join("program"), Target::Linux64).unwrap();
+        let mut child = std::process::Command::new(&executable)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        // Programs that never read may exit before the input is written
+        let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+        let output = child.wait_with_output().unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn test_interpreter_matches_compiled_code() {
+        let programs = [
+            ("arithmetic", "print(7 / 2, 17 % 5, 3 * 4 - 1, 5 & 3, 5 | 3, 5 ^ 3, ~5, -5)\nprint(1.5 + 2.25, 3.0, 1.0 / 3.0, 7 / 2.0, 3 * 2.5)\n"),
+            ("overflow", "var big = 9223372036854775807\nprint(big + 1)\nprint(big * 2)\n"),
+            ("control", "var i = 0\nwhile i < 5:\n    i += 1\n    if i == 2: continue\n    if i == 4: break\n    print(i)\nend\nfor j in range(10, 0, -3):\n    print(j)\nend\n"),
+            ("functions", "def fact(n): {\n    if n < 2: return 1\n    return n * fact(n - 1)\n}\ndef fib(n): {\n    if n < 2: return n\n    return fib(n - 1) + fib(n - 2)\n}\nprint(fact(10), fib(15))\n"),
+            ("collections", "var xs = [1, 2]\nvar ys = xs\nappend(ys, 5)\nxs[0] = 9\nprint(xs[0], xs[2], len(xs))\nvar d = {\"a\": 1, \"b\": 2}\nd[\"b\"] = 7\nprint(d[\"a\"] + d[\"b\"])\n"),
+            ("strings", "var name = input()\nvar s = \"abc\"\nprint(s, name, f\"x={1 + 2} s={s}\")\nprint(s == \"abc\", not 0)\n"),
+        ];
+        for (name, source) in programs {
+            let interpreted = interpret(source, "earth\n").unwrap();
+            let compiled = compile_and_run(source, "earth\n", name);
+            // Booleans are the one documented difference these programs touch
+            assert_eq!(interpreted.replace("True", "1").replace("False", "0"), compiled, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_known_divergences() {
+        let pinned = [
+            ("print(1 < 2, 2 < 1)\n", "True False\n", "1 0\n"),
+            ("print(1 == 1 and 2)\nprint(0 or 3)\n", "2\n3\n", "1\n1\n"),
+            ("print(\"ab\".replace(\"\", \"-\"), \"\u{e9}t\u{e9}\".upper())\n", "-a-b- \u{c9}T\u{c9}\n", "ab \u{e9}T\u{e9}\n"),
+        ];
+        for (index, (source, interpreted, compiled)) in pinned.into_iter().enumerate() {
+            assert_eq!(interpret(source, "").unwrap(), interpreted, "{}", source);
+            assert_eq!(compile_and_run(source, "", &format!("divergence{}", index)), compiled, "{}", source);
+        }
+
+        // Compiled code has no meaningful output for these, so only the interpreter's is pinned
+        assert_eq!(interpret("def f(x): return x\nprint(f(\"s\"), \"a\" + \"b\")\nprint([1, \"x\"], {\"k\": None})\n", "").unwrap(), "s ab\n[1, 'x'] {'k': None}\n");
+        assert_eq!(interpret("var s = \"h\u{e9}llo\"\nprint(len(s), s[1], s[:2])\n", "").unwrap(), "6 \u{fffd} h\u{fffd}\n");
+        assert_eq!(interpret("struct P: x, name\nprint(P(1, \"a\"))\n", "").unwrap(), "P(x=1, name='a')\n");
+        assert_eq!(interpret("print(-7 / 2, -7 % 2)\n", "").unwrap(), "-3 -1\n");
+        assert_eq!(interpret("def half(v): return v / 2.0\nprint(half(3.0))\n", "").unwrap(), "1.5\n");
+        assert_eq!(interpret("var xs = [1]\nprint(xs[3])\n", "").unwrap_err(), "Index 3 is out of range for 1 items at 2:7");
+        assert_eq!(interpret("var xs = [1, 2]\nprint(xs[-1])\n", "").unwrap_err(), "Index -1 is out of range for 2 items at 2:7");
+        assert_eq!(interpret("var xs = [1, 2]\nxs[-2] = 3\n", "").unwrap_err(), "Index -2 is out of range for 2 items at 2:1");
+        assert_eq!(interpret("var s = \"ab\"\nprint(s[-1])\n", "").unwrap(), "b\n");
+        assert_eq!(interpret("print(1 / 0)\n", "").unwrap_err(), "Division by zero at 1:7");
+        assert!(interpret("def down(n): return down(n + 1)\nprint(down(0))\n", "").unwrap_err().starts_with("Calls nested deeper than"));
+        assert_eq!(interpret("var x = 1\ndef f(): return x\nprint(f())\n", "").unwrap_err(), "Undefined variable 'x' at 2:10");
+    }
+}
@@ -0,0 +1,251 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::path::Path;
+
+/// ISO9660 logical block size
+pub const ISO_SECTOR_SIZE: usize = 2048;
+
+/// Sector of the primary volume descriptor; the ones before it are the system area
+pub const PRIMARY_DESCRIPTOR_SECTOR: usize = 16;
+pub const BOOT_RECORD_SECTOR: usize = 17;
+pub const TERMINATOR_SECTOR: usize = 18;
+const L_PATH_TABLE_SECTOR: usize = 19;
+const M_PATH_TABLE_SECTOR: usize = 20;
+const ROOT_DIRECTORY_SECTOR: usize = 21;
+pub const BOOT_CATALOG_SECTOR: usize = 22;
+const FIRST_FILE_SECTOR: usize = 23;
+
+/// No-emulation boot images are loaded to 0x7C00 and must end below 0x80000
+const MAX_BOOT_IMAGE: usize = 0x80000 - 0x7C00;
+
+/// Recording date of every directory record, fixed so the same inputs give the same image
+const RECORDING_DATE: [u8; 7] = [126, 1, 1, 0, 0, 0, 0]; // 2026-01-01 00:00 UTC
+
+/// Bootable ISO9660 image whose El Torito default entry loads `boot_code` to 0x7C00
+/// without emulation. The boot image and `kernel` are also visible as BOOT.IMG and KERNEL.BIN
+pub fn bootable_iso(boot_code: &[u8], kernel: &[u8]) -> Result<Vec<u8>, String> {
+    if boot_code.is_empty() || boot_code.len() > MAX_BOOT_IMAGE {
+        return Err(format!("Boot image is {} bytes; it must be between 1 and {} bytes", boot_code.len(), MAX_BOOT_IMAGE));
+    }
+
+    let sectors_for = |bytes: usize| bytes.div_ceil(ISO_SECTOR_SIZE).max(1);
+    let boot_image_sector = FIRST_FILE_SECTOR;
+    let kernel_sector = boot_image_sector + sectors_for(boot_code.len());
+    let total_sectors = kernel_sector + sectors_for(kernel.len());
+
+    let mut image = vec![0u8; total_sectors * ISO_SECTOR_SIZE];
+    let mut write = |sector: usize, bytes: &[u8]| {
+        let at = sector * ISO_SECTOR_SIZE;
+        image[at..at + bytes.len()].copy_from_slice(bytes);
+    };
+
+    let files = [
+        ("BOOT.CAT;1", BOOT_CATALOG_SECTOR, ISO_SECTOR_SIZE),
+        ("BOOT.IMG;1", boot_image_sector, boot_code.len()),
+        ("KERNEL.BIN;1", kernel_sector, kernel.len()),
+    ];
+    let mut root = Vec::new();
+    root.extend(directory_record(&[0], ROOT_DIRECTORY_SECTOR, ISO_SECTOR_SIZE, true));
+    root.extend(directory_record(&[1], ROOT_DIRECTORY_SECTOR, ISO_SECTOR_SIZE, true));
+    for (name, sector, size) in files {
+        root.extend(directory_record(name.as_bytes(), sector, size, false));
+    }
+
+    write(PRIMARY_DESCRIPTOR_SECTOR, &primary_volume_descriptor(total_sectors));
+    write(BOOT_RECORD_SECTOR, &boot_record_descriptor());
+    write(TERMINATOR_SECTOR, &descriptor_header(255));
+    write(L_PATH_TABLE_SECTOR, &path_table(u32::to_le_bytes, u16::to_le_bytes));
+    write(M_PATH_TABLE_SECTOR, &path_table(u32::to_be_bytes, u16::to_be_bytes));
+    write(ROOT_DIRECTORY_SECTOR, &root);
+    write(BOOT_CATALOG_SECTOR, &boot_catalog(boot_image_sector, boot_code.len())?);
+    write(boot_image_sector, boot_code);
+    write(kernel_sector, kernel);
+    Ok(image)
+}
+
+/// Write the image of `bootable_iso` to `path`
+pub fn write_bootable_iso(boot_code: &[u8], kernel: &[u8], path: &Path) -> Result<(), String> {
+    let image = bootable_iso(boot_code, kernel)?;
+    std::fs::write(path, image).map_err(|e| format!("Failed to write ISO image {}: {}", path.display(), e))
+}
+
+/// ISO9660 stores most numbers twice, little-endian then big-endian
+fn both_endian_u32(value: u32) -> [u8; 8] {
+    let mut bytes = [0u8; 8];
+    bytes[..4].copy_from_slice(&value.to_le_bytes());
+    bytes[4..].copy_from_slice(&value.to_be_bytes());
+    bytes
+}
+
+fn both_endian_u16(value: u16) -> [u8; 4] {
+    let mut bytes = [0u8; 4];
+    bytes[..2].copy_from_slice(&value.to_le_bytes());
+    bytes[2..].copy_from_slice(&value.to_be_bytes());
+    bytes
+}
+
+fn descriptor_header(descriptor_type: u8) -> Vec<u8> {
+    let mut sector = vec![0u8; ISO_SECTOR_SIZE];
+    sector[0] = descriptor_type;
+    sector[1..6].copy_from_slice(b"CD001");
+    sector[6] = 1;
+    sector
+}
+
+fn primary_volume_descriptor(total_sectors: usize) -> Vec<u8> {
+    let mut pvd = descriptor_header(1);
+    let mut text = |at: usize, len: usize, value: &str| {
+        pvd[at..at + len].fill(b' ');
+        pvd[at..at + value.len()].copy_from_slice(value.as_bytes());
+    };
+    text(8, 32, "");                  // system identifier
+    text(40, 32, "EARTHANG");         // volume identifier
+    text(190, 128, "");               // volume set identifier
+    text(318, 128, "");               // publisher
+    text(446, 128, "");               // data preparer
+    text(574, 128, "EARTHANG");       // application
+    text(702, 37 * 3, "");            // copyright, abstract and bibliographic files
+
+    pvd[80..88].copy_from_slice(&both_endian_u32(total_sectors as u32));
+    pvd[120..124].copy_from_slice(&both_endian_u16(1)); // volume set size
+    pvd[124..128].copy_from_slice(&both_endian_u16(1)); // volume sequence number
+    pvd[128..132].copy_from_slice(&both_endian_u16(ISO_SECTOR_SIZE as u16));
+    pvd[132..140].copy_from_slice(&both_endian_u32(path_table(u32::to_le_bytes, u16::to_le_bytes).len() as u32));
+    pvd[140..144].copy_from_slice(&(L_PATH_TABLE_SECTOR as u32).to_le_bytes());
+    pvd[148..152].copy_from_slice(&(M_PATH_TABLE_SECTOR as u32).to_be_bytes());
+    pvd[156..190].copy_from_slice(&directory_record(&[0], ROOT_DIRECTORY_SECTOR, ISO_SECTOR_SIZE, true));
+
+    // Creation, modification, expiration and effective dates are all "not specified"
+    for at in [813, 830, 847, 864] {
+        pvd[at..at + 16].fill(b'0');
+    }
+    pvd[881] = 1; // file structure version
+    pvd
+}
+
+fn boot_record_descriptor() -> Vec<u8> {
+    let mut record = descriptor_header(0);
+    let system = b"EL TORITO SPECIFICATION";
+    record[7..7 + system.len()].copy_from_slice(system);
+    record[0x47..0x4B].copy_from_slice(&(BOOT_CATALOG_SECTOR as u32).to_le_bytes());
+    record
+}
+
+/// Validation entry followed by a bootable, no-emulation default entry
+fn boot_catalog(boot_image_sector: usize, boot_image_size: usize) -> Result<Vec<u8>, String> {
+    let mut catalog = vec![0u8; ISO_SECTOR_SIZE];
+    catalog[0] = 1; // header ID
+    catalog[1] = 0; // platform: 80x86
+    catalog[4..12].copy_from_slice(b"EARTHANG");
+    catalog[30] = 0x55;
+    catalog[31] = 0xAA;
+    // The 16 words of the validation entry must sum to zero
+    let sum = catalog[..32].chunks(2).fold(0u16, |sum, word| sum.wrapping_add(u16::from_le_bytes([word[0], word[1]])));
+    catalog[28..30].copy_from_slice(&0u16.wrapping_sub(sum).to_le_bytes());
+
+    // The sector count is in 512-byte units, however large the medium's sectors are
+    let load_sectors = u16::try_from(boot_image_size.div_ceil(512))
+        .map_err(|_| format!("Boot image of {} bytes is too large for El Torito", boot_image_size))?;
+    catalog[32] = 0x88; // bootable
+    catalog[33] = 0; // no emulation
+    catalog[34..36].copy_from_slice(&0u16.to_le_bytes()); // load segment 0 means 0x7C0
+    catalog[38..40].copy_from_slice(&load_sectors.to_le_bytes());
+    catalog[40..44].copy_from_slice(&(boot_image_sector as u32).to_le_bytes());
+    Ok(catalog)
+}
+
+fn directory_record(name: &[u8], sector: usize, size: usize, is_directory: bool) -> Vec<u8> {
+    // Records have even length, so an even-length name is followed by a pad byte
+    let length = 33 + name.len() + (name.len() + 1) % 2;
+    let mut record = vec![0u8; length];
+    record[0] = length as u8;
+    record[2..10].copy_from_slice(&both_endian_u32(sector as u32));
+    record[10..18].copy_from_slice(&both_endian_u32(size as u32));
+    record[18..25].copy_from_slice(&RECORDING_DATE);
+    record[25] = if is_directory { 0x02 } else { 0x00 };
+    record[28..32].copy_from_slice(&both_endian_u16(1));
+    record[32] = name.len() as u8;
+    record[33..33 + name.len()].copy_from_slice(name);
+    record
+}
+
+/// The path table has the root as its only directory; L and M tables differ only in byte order
+fn path_table(u32_bytes: fn(u32) -> [u8; 4], u16_bytes: fn(u16) -> [u8; 2]) -> Vec<u8> {
+    let mut table = vec![1, 0];
+    table.extend(u32_bytes(ROOT_DIRECTORY_SECTOR as u32));
+    table.extend(u16_bytes(1)); // parent directory number
+    table.extend([0, 0]); // root name and pad
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sector(image: &[u8], n: usize) -> &[u8] {
+        &image[n * ISO_SECTOR_SIZE..(n + 1) * ISO_SECTOR_SIZE]
+    }
+
+    fn le32(bytes: &[u8]) -> usize {
+        u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize
+    }
+
+    #[test]
+    fn test_el_torito_layout() {
+        let boot: Vec<u8> = (0..3000).map(|i| (i % 251) as u8).collect();
+        let kernel = vec![0xF4; 5000];
+        let image = bootable_iso(&boot, &kernel).unwrap();
+
+        // Primary volume descriptor, with the size in both byte orders
+        let pvd = sector(&image, PRIMARY_DESCRIPTOR_SECTOR);
+        assert_eq!(&pvd[..7], b"\x01CD001\x01");
+        assert_eq!(le32(&pvd[80..]) * ISO_SECTOR_SIZE, image.len());
+        assert_eq!(&pvd[84..88], &(image.len() as u32 / 2048).to_be_bytes());
+        assert_eq!(&pvd[128..132], &[0x00, 0x08, 0x08, 0x00]);
+        assert_eq!(&pvd[40..48], b"EARTHANG");
+
+        let record = sector(&image, BOOT_RECORD_SECTOR);
+        assert_eq!(&record[..7], b"\x00CD001\x01");
+        assert_eq!(&record[7..31], b"EL TORITO SPECIFICATION\0");
+        assert_eq!(&sector(&image, TERMINATOR_SECTOR)[..7], b"\xFFCD001\x01");
+
+        // Boot catalog: checksummed validation entry, then the default entry
+        let catalog = sector(&image, le32(&record[0x47..]));
+        let sum = catalog[..32].chunks(2).fold(0u16, |sum, w| sum.wrapping_add(u16::from_le_bytes([w[0], w[1]])));
+        assert_eq!(sum, 0);
+        assert_eq!(&catalog[30..32], &[0x55, 0xAA]);
+        assert_eq!(&catalog[32..34], &[0x88, 0x00]);
+        let load_sectors = u16::from_le_bytes([catalog[38], catalog[39]]) as usize;
+        assert_eq!(load_sectors, 6);
+        let boot_at = le32(&catalog[40..]) * ISO_SECTOR_SIZE;
+        assert_eq!(&image[boot_at..boot_at + boot.len()], &boot[..]);
+
+        // KERNEL.BIN is reachable through the root directory record of the PVD
+        let root = sector(&image, le32(&pvd[156 + 2..]));
+        let mut at = 0;
+        let mut kernel_extent = None;
+        while root[at] != 0 {
+            let name = &root[at + 33..at + 33 + root[at + 32] as usize];
+            if name == b"KERNEL.BIN;1" {
+                kernel_extent = Some((le32(&root[at + 2..]), le32(&root[at + 10..])));
+            }
+            at += root[at] as usize;
+        }
+        let (extent, size) = kernel_extent.unwrap();
+        assert_eq!(&image[extent * ISO_SECTOR_SIZE..extent * ISO_SECTOR_SIZE + size], &kernel[..]);
+
+        assert!(bootable_iso(&[], &kernel).is_err());
+        assert!(bootable_iso(&vec![0; MAX_BOOT_IMAGE + 1], &kernel).is_err());
+    }
+}
@@ -11,25 +11,52 @@
     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
     GNU General Public License for more details.
 */
+pub mod analysis;
 pub mod backend;
+pub mod bench;
+pub mod boot_test;
 pub mod compiler;
+pub mod differential;
 pub mod disk_cache;
+pub mod disk_image;
 pub mod dsl;
 pub mod emitter;
+pub mod escape;
 pub mod extension;
+pub mod fat;
+pub mod font;
+pub mod framebuffer;
+pub mod hardware;
+pub mod image;
+pub mod interp;
+pub mod iso;
+pub mod linker;
+pub mod logging;
 pub mod lua_frontend;
+pub mod lsp;
 pub mod lua_pool;
+pub mod messages;
+pub mod mode_transition;
+pub mod module_library;
+pub mod regalloc;
+pub mod repl;
+pub mod simd;
+pub mod size;
+pub mod symbol_map;
+pub mod typecheck;
+pub mod watch;
 pub mod cli;
 
 pub use backend::{Backend, BackendRegistry, Target, Capability};
-pub use compiler::{EarthangCompiler, CompilerConfig, compile, compile_with_hardware};
+pub use compiler::{EarthangCompiler, CompilerConfig, CompileError, compile, compile_with_hardware};
 pub use lua_frontend::{parse_program, LuaFrontend};
-pub use extension::{EarthngModule, AssemblyEmitter, BasicAssemblyEmitter, ExtensionRegistry, MathModule, StringModule, SystemModule};  // NEW
+pub use module_library::ModuleLibrary;
+pub use extension::{EarthngModule, AssemblyEmitter, BasicAssemblyEmitter, DictModule, DiskModule, ExtensionRegistry, InterruptsModule, KeyboardModule, ListModule, MathModule, OsModule, SerialModule, StringModule, SystemModule};  // NEW
 
 pub mod parser {
     pub use crate::lua_frontend::{
         Program, Statement, Expr, Position, Span, Op,
-        parse_program, ParseError,
-        CompareOp, BoolOp, UnaryOp
+        parse_program, parse_bytes, ParseError,
+        CompareOp, BoolOp, UnaryOp, FStringPart
     };
 }
\ No newline at end of file
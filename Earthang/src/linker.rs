@@ -0,0 +1,186 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::extension::ExtensionRegistry;
+use crate::parser::{Position, Program, Span, Statement};
+
+/// Functions the backends compile themselves rather than calling a definition
+const INLINE_BUILTINS: [&str; 12] = [
+    "print", "asm", "len", "append", "input", "sleep", "range",
+    "write_register", "read_register", "dma_transfer", "port_in", "port_out",
+];
+
+/// Several source files joined into the one program the backends compile
+#[derive(Debug, Clone)]
+pub struct LinkedProgram {
+    pub program: Program,
+    /// File whose top-level code runs
+    pub entry: PathBuf,
+    /// Every function with the file defining it
+    pub definitions: Vec<(String, PathBuf)>,
+}
+
+impl LinkedProgram {
+    /// File defining `function`
+    pub fn defined_in(&self, function: &str) -> Option<&Path> {
+        self.definitions.iter().find(|(name, _)| name == function).map(|(_, file)| file.as_path())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkError {
+    NoFiles,
+    DuplicateFunction { name: String, first: PathBuf, first_at: Position, second: PathBuf, second_at: Position },
+    UnresolvedFunction { name: String, file: PathBuf, span: Span },
+    /// Statements outside functions in a file other than the entry
+    TopLevelCode { file: PathBuf, span: Span, entry: PathBuf },
+}
+
+impl std::fmt::Display for LinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkError::NoFiles => write!(f, "No files to link"),
+            LinkError::DuplicateFunction { name, first, first_at, second, second_at } => write!(
+                f, "Function '{}' is defined in both {} at {} and {} at {}",
+                name, first.display(), first_at, second.display(), second_at
+            ),
+            LinkError::UnresolvedFunction { name, file, span } => write!(
+                f, "Function '{}' called in {} at {} is not defined in any linked file or module",
+                name, file.display(), span
+            ),
+            LinkError::TopLevelCode { file, span, entry } => write!(
+                f, "{} has code outside functions at {}; only {}, the first file, runs top-level code",
+                file.display(), span, entry.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+/// Link `files` against the modules that ship with the compiler
+pub fn link_programs(files: Vec<(PathBuf, Program)>) -> Result<LinkedProgram, LinkError> {
+    link_with_registry(files, &ExtensionRegistry::with_builtin_modules())
+}
+
+/// Join `files` into one program. The first file is the entry: its statements
+/// stay in order and its top-level code becomes the program's. The others may
+/// only define functions, structs and constants and import modules. Every call must reach a function
+/// of some file, a builtin or a function of a module in `registry`
+pub fn link_with_registry(files: Vec<(PathBuf, Program)>, registry: &ExtensionRegistry) -> Result<LinkedProgram, LinkError> {
+    let entry = files.first().map(|(path, _)| path.clone()).ok_or(LinkError::NoFiles)?;
+
+    let mut defined: HashMap<String, (PathBuf, Span)> = HashMap::new();
+    let mut definitions = Vec::new();
+    for (path, program) in &files {
+        for stmt in &program.body {
+            let (name, span) = match stmt {
+                Statement::FunctionDef { name, span, .. }
+                | Statement::HardwareFunctionDef { name, span, .. }
+                | Statement::StructDef { name, span, .. } => (name, *span),
+                _ => continue,
+            };
+            match defined.get(name) {
+                Some((first, first_span)) if first != path => {
+                    return Err(LinkError::DuplicateFunction {
+                        name: name.clone(), first: first.clone(), first_at: first_span.start, second: path.clone(), second_at: span.start,
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    defined.insert(name.clone(), (path.clone(), span));
+                    definitions.push((name.clone(), path.clone()));
+                }
+            }
+        }
+    }
+
+    for (path, program) in &files {
+        for (name, _, span) in crate::extension::written_calls(program) {
+            if !defined.contains_key(&name) && !is_builtin(&name) && !registry.has_function(&name) {
+                return Err(LinkError::UnresolvedFunction { name, file: path.clone(), span });
+            }
+        }
+    }
+
+    let mut files = files.into_iter();
+    let (_, mut program) = files.next().expect("the entry was found above");
+    for (path, other) in files {
+        for stmt in other.body {
+            match stmt {
+                Statement::FunctionDef { .. }
+                | Statement::HardwareFunctionDef { .. }
+                | Statement::StructDef { .. }
+                | Statement::ConstDecl { .. }
+                | Statement::Include { .. } => program.body.push(stmt),
+                Statement::Import { ref module, .. } => {
+                    let imported = program.body.iter().any(|existing| matches!(existing, Statement::Import { module: other, .. } if other == module));
+                    if !imported {
+                        program.body.push(stmt);
+                    }
+                }
+                Statement::Pass => {}
+                other => return Err(LinkError::TopLevelCode { file: path, span: other.span(), entry }),
+            }
+        }
+    }
+
+    Ok(LinkedProgram { program, entry, definitions })
+}
+
+fn is_builtin(name: &str) -> bool {
+    name.starts_with("hw_")
+        || INLINE_BUILTINS.contains(&name)
+        || crate::extension::OS_BUILTINS.contains(&name)
+        || crate::extension::STRING_BUILTINS.contains(&name)
+        || crate::extension::INTERRUPT_BUILTINS.contains(&name)
+        || crate::extension::DISK_BUILTINS.contains(&name)
+        || crate::framebuffer::FRAMEBUFFER_BUILTINS.contains(&name)
+        || crate::framebuffer::COLOR_BUILTINS.contains(&name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_program;
+
+    fn file(name: &str, source: &str) -> (PathBuf, Program) {
+        (PathBuf::from(name), parse_program(source).unwrap())
+    }
+
+    #[test]
+    fn test_link_programs() {
+        let linked = link_programs(vec![
+            file("main.eg", "import math\nprint(twice(abs(-4)))\n"),
+            file("util.eg", "import math\ndef twice(n): return add(n, n)\n"),
+            file("add.eg", "def add(a, b): return a + b\n"),
+        ]).unwrap();
+        assert_eq!(linked.entry, PathBuf::from("main.eg"));
+        assert_eq!(linked.defined_in("add"), Some(Path::new("add.eg")));
+        assert_eq!(linked.program.body.len(), 4);
+        assert!(matches!(&linked.program.body[1], Statement::Expr(_)));
+
+        let error = link_programs(vec![
+            file("a.eg", "def f(): return 1\nprint(f())\n"),
+            file("b.eg", "\ndef f(): return 2\n"),
+        ]).unwrap_err();
+        assert_eq!(error.to_string(), "Function 'f' is defined in both a.eg at 1:1 and b.eg at 2:1");
+        let error = link_programs(vec![file("a.eg", "print(missing(1))\n"), file("b.eg", "def f(): return 2\n")]).unwrap_err();
+        assert!(matches!(error, LinkError::UnresolvedFunction { ref name, .. } if name == "missing"), "{}", error);
+        let error = link_programs(vec![file("a.eg", "print(1)\n"), file("b.eg", "def f(): return 2\nprint(f())\n")]).unwrap_err();
+        assert_eq!(error.to_string(), "b.eg has code outside functions at 2:1; only a.eg, the first file, runs top-level code");
+        assert_eq!(link_programs(Vec::new()).unwrap_err(), LinkError::NoFiles);
+    }
+}
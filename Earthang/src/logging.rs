@@ -0,0 +1,211 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use serde::Serialize;
+
+// What the compiler is doing and how long it takes, for finding out which
+// phase makes a compile slow or its output odd. The compiler reports each
+// phase it runs: parse, analysis, modules, backend, codegen, size, assemble
+// and link, with the time it took. `-v` prints those, `-vv` adds detail
+// such as every function's code generation, and `--log-json` writes each
+// event as a line of JSON instead. Events go to stderr, so they never mix
+// with assembly written to stdout. Tests capture them instead.
+//
+// A Logger is cheap to clone and shared by the threads compiling functions.
+// The default one is off and costs a branch per event.
+
+/// How much a Logger reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Off,
+    /// A summary of each phase, `-v`
+    Phases,
+    /// Also work within phases, such as each function compiled, `-vv`
+    Detail,
+}
+
+impl Level {
+    /// The level `-v` given `count` times asks for
+    pub fn from_verbosity(count: u8) -> Self {
+        match count {
+            0 => Level::Off,
+            1 => Level::Phases,
+            _ => Level::Detail,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Event {
+    pub phase: String,
+    pub message: String,
+    /// Set on detail events, which only `-vv` shows
+    pub detail: bool,
+    /// Microseconds since the logger was created
+    pub at_us: u64,
+    /// Microseconds the phase took, on the event that ends one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elapsed_us: Option<u64>,
+}
+
+impl Event {
+    fn render(&self) -> String {
+        let mut line = format!("[{:>10.3}ms] {}: {}", self.at_us as f64 / 1000.0, self.phase, self.message);
+        if let Some(elapsed) = self.elapsed_us {
+            line.push_str(&format!(" ({:.3}ms)", elapsed as f64 / 1000.0));
+        }
+        line
+    }
+}
+
+enum Sink {
+    Stderr { json: bool },
+    Captured(Vec<Event>),
+}
+
+struct Inner {
+    level: Level,
+    start: Instant,
+    sink: Mutex<Sink>,
+}
+
+#[derive(Clone, Default)]
+pub struct Logger {
+    inner: Option<Arc<Inner>>,
+}
+
+impl std::fmt::Debug for Logger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Logger({:?})", self.level())
+    }
+}
+
+impl Logger {
+    /// Report events up to `level` on stderr, as text or as JSON lines
+    pub fn stderr(level: Level, json: bool) -> Self {
+        Self::with_sink(level, Sink::Stderr { json })
+    }
+
+    /// Keep events up to `level` for `events` to return
+    pub fn capture(level: Level) -> Self {
+        Self::with_sink(level, Sink::Captured(Vec::new()))
+    }
+
+    fn with_sink(level: Level, sink: Sink) -> Self {
+        if level == Level::Off {
+            return Self::default();
+        }
+        Self { inner: Some(Arc::new(Inner { level, start: Instant::now(), sink: Mutex::new(sink) })) }
+    }
+
+    pub fn level(&self) -> Level {
+        self.inner.as_ref().map_or(Level::Off, |inner| inner.level)
+    }
+
+    pub fn enabled(&self, level: Level) -> bool {
+        level != Level::Off && self.level() >= level
+    }
+
+    /// A phase summary
+    pub fn info(&self, phase: &str, message: impl Into<String>) {
+        self.emit(Level::Phases, phase, message.into(), None);
+    }
+
+    /// Work within a phase
+    pub fn detail(&self, phase: &str, message: impl Into<String>) {
+        self.emit(Level::Detail, phase, message.into(), None);
+    }
+
+    /// Start timing `phase`, reported as a summary when the returned span ends
+    pub fn phase(&self, phase: &str) -> PhaseSpan {
+        PhaseSpan { logger: self.clone(), level: Level::Phases, phase: phase.to_string(), start: Instant::now() }
+    }
+
+    /// Start timing work within `phase`, reported as detail
+    pub fn detail_phase(&self, phase: &str) -> PhaseSpan {
+        PhaseSpan { logger: self.clone(), level: Level::Detail, phase: phase.to_string(), start: Instant::now() }
+    }
+
+    /// Events a `capture` logger has kept, in order
+    pub fn events(&self) -> Vec<Event> {
+        let Some(inner) = &self.inner else { return Vec::new() };
+        match &*inner.sink.lock().unwrap() {
+            Sink::Captured(events) => events.clone(),
+            Sink::Stderr { .. } => Vec::new(),
+        }
+    }
+
+    fn emit(&self, level: Level, phase: &str, message: String, elapsed_us: Option<u64>) {
+        let Some(inner) = self.inner.as_ref().filter(|inner| inner.level >= level) else { return };
+        let event = Event {
+            phase: phase.to_string(),
+            message,
+            detail: level == Level::Detail,
+            at_us: inner.start.elapsed().as_micros() as u64,
+            elapsed_us,
+        };
+        match &mut *inner.sink.lock().unwrap() {
+            Sink::Stderr { json: true } => eprintln!("{}", serde_json::to_string(&event).unwrap_or_default()),
+            Sink::Stderr { json: false } => eprintln!("{}", event.render()),
+            Sink::Captured(events) => events.push(event),
+        }
+    }
+}
+
+/// A phase being timed; `end` reports it
+pub struct PhaseSpan {
+    logger: Logger,
+    level: Level,
+    phase: String,
+    start: Instant,
+}
+
+impl PhaseSpan {
+    pub fn end(self, message: impl Into<String>) {
+        let elapsed = self.start.elapsed().as_micros() as u64;
+        self.logger.emit(self.level, &self.phase, message.into(), Some(elapsed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{CompilerConfig, EarthangCompiler};
+
+    #[test]
+    fn test_compile_reports_phases_in_order() {
+        let logger = Logger::capture(Level::Detail);
+        let config = CompilerConfig::default().with_hardware_dsl(false).with_logger(logger.clone());
+        let source = "def square(n): return n * n\ndef cube(n): return n * square(n)\nprint(cube(3), 1.5 * 2.0)\n";
+        EarthangCompiler::new(config).compile_source(source, None).unwrap();
+
+        let events = logger.events();
+        let phases: Vec<&str> = events.iter().filter(|e| !e.detail).map(|e| e.phase.as_str()).collect();
+        assert_eq!(phases, ["parse", "analysis", "modules", "backend", "backend", "backend", "codegen", "size"]);
+
+        // The program uses floats, which the riscv64 backend lacks
+        let rejected = events.iter().find(|e| e.message.starts_with("riscv64 rejected")).unwrap();
+        assert!(rejected.message.contains("Float"), "{}", rejected.message);
+        let functions: Vec<&str> = events.iter().filter(|e| e.detail && e.phase == "codegen").map(|e| e.message.as_str()).collect();
+        assert!(["square", "cube"].iter().all(|name| functions.iter().any(|message| message.starts_with(&format!("function {}", name)))), "{:?}", functions);
+
+        // -v leaves the detail out
+        let logger = Logger::capture(Level::Phases);
+        let config = CompilerConfig::default().with_hardware_dsl(false).with_logger(logger.clone());
+        EarthangCompiler::new(config).compile_source(source, None).unwrap();
+        assert!(!logger.events().is_empty() && logger.events().iter().all(|e| !e.detail));
+        assert!(Logger::default().events().is_empty() && !Logger::capture(Level::Off).enabled(Level::Phases));
+    }
+}
@@ -0,0 +1,319 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::io::{BufRead, Write};
+use serde_json::{json, Value};
+use crate::analysis::Severity;
+use crate::lua_frontend::LuaFrontend;
+use crate::parser::{ParseError, Position, Span, Statement};
+
+// A language server speaking JSON-RPC over stdio. It keeps every open document
+// in a `ParserSession`, so typing inside one statement only reparses that
+// statement, and answers with:
+//
+//   initialize                      capabilities: incremental sync and document symbols
+//   textDocument/didOpen, didChange publishDiagnostics with parse errors, or the
+//                                   analysis warnings once the document parses
+//   textDocument/didClose           publishDiagnostics clearing the document's diagnostics
+//   textDocument/documentSymbol     functions, structs and top-level variables
+//   shutdown, exit
+//
+// LSP lines and characters count from 0 and characters in UTF-16 units; spans
+// count both from 1, columns in characters.
+
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// `SymbolKind` values from the specification
+const SYMBOL_FIELD: u64 = 8;
+const SYMBOL_FUNCTION: u64 = 12;
+const SYMBOL_VARIABLE: u64 = 13;
+const SYMBOL_STRUCT: u64 = 23;
+
+pub struct LanguageServer {
+    frontend: LuaFrontend,
+    shutting_down: bool,
+    exited: bool,
+}
+
+impl Default for LanguageServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LanguageServer {
+    pub fn new() -> Self {
+        Self { frontend: LuaFrontend::new(), shutting_down: false, exited: false }
+    }
+
+    /// Whether the client sent `exit`
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Handle one request or notification, returning the messages to send back
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let id = message.get("id").cloned();
+        let result = match method {
+            _ if self.shutting_down && method != "exit" => Err((INVALID_REQUEST, "The server is shutting down".to_string())),
+            "initialize" => Ok(json!({
+                "capabilities": { "textDocumentSync": 2, "documentSymbolProvider": true },
+                "serverInfo": { "name": "earthang", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => {
+                self.shutting_down = true;
+                Ok(Value::Null)
+            }
+            "exit" => {
+                self.exited = true;
+                return Vec::new();
+            }
+            "textDocument/didOpen" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                let result = self.frontend.open_document(uri, text);
+                return vec![self.publish_diagnostics(uri, result.err())];
+            }
+            "textDocument/didChange" => return self.did_change(params),
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                self.frontend.close_document(uri);
+                return vec![notification("textDocument/publishDiagnostics", json!({ "uri": uri, "diagnostics": [] }))];
+            }
+            "textDocument/documentSymbol" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                self.document_symbols(uri).ok_or_else(|| (INVALID_PARAMS, format!("Document '{}' is not open", uri)))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method '{}'", method))),
+        };
+
+        // Notifications get no answer, not even an error
+        let Some(id) = id else { return Vec::new() };
+        vec![match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+        }]
+    }
+
+    fn did_change(&mut self, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let mut result = Ok(());
+        for change in params["contentChanges"].as_array().into_iter().flatten() {
+            let text = change["text"].as_str().unwrap_or_default();
+            result = match self.frontend.document_text(uri) {
+                Some(current) if !change["range"].is_null() => {
+                    let start = byte_offset(&current, &change["range"]["start"]);
+                    let end = byte_offset(&current, &change["range"]["end"]);
+                    self.frontend.edit_document(uri, start..end.max(start), text).map(|_| ())
+                }
+                _ => self.frontend.open_document(uri, text),
+            };
+        }
+        vec![self.publish_diagnostics(uri, result.err())]
+    }
+
+    fn publish_diagnostics(&self, uri: &str, errors: Option<Vec<ParseError>>) -> Value {
+        let text = self.frontend.document_text(uri).unwrap_or_default();
+        let diagnostics: Vec<Value> = match errors {
+            Some(errors) => errors.iter().map(|error| {
+                let span = error.span().unwrap_or_else(|| Span::single(Position::new(1, 1, 0)));
+                json!({ "range": range(&text, span), "severity": 1, "source": "earthang", "message": error.message() })
+            }).collect(),
+            None => self.frontend.document_program(uri).map(|program| crate::analysis::analyze(&program, &text)).unwrap_or_default()
+                .iter()
+                .map(|diagnostic| json!({
+                    "range": range(&text, diagnostic.span),
+                    "severity": if diagnostic.severity == Severity::Error { 1 } else { 2 },
+                    "code": diagnostic.code,
+                    "source": "earthang",
+                    "message": diagnostic.message,
+                }))
+                .collect(),
+        };
+        notification("textDocument/publishDiagnostics", json!({ "uri": uri, "diagnostics": diagnostics }))
+    }
+
+    /// Functions and structs, with their parameters and fields inside, and the
+    /// first binding of each top-level variable
+    fn document_symbols(&self, uri: &str) -> Option<Value> {
+        let text = self.frontend.document_text(uri)?;
+        let Some(program) = self.frontend.document_program(uri) else { return Some(json!([])) };
+        let mut variables: Vec<&str> = Vec::new();
+        let mut symbols = Vec::new();
+        for stmt in &program.body {
+            match stmt {
+                Statement::FunctionDef { name, args, span, .. } | Statement::HardwareFunctionDef { name, args, span, .. } => {
+                    let parameters: Vec<Value> = args.iter().map(|arg| symbol(arg, SYMBOL_VARIABLE, &text, *span, Vec::new())).collect();
+                    symbols.push(symbol(name, SYMBOL_FUNCTION, &text, *span, parameters));
+                }
+                Statement::StructDef { name, fields, span } => {
+                    let fields: Vec<Value> = fields.iter().map(|field| symbol(field, SYMBOL_FIELD, &text, *span, Vec::new())).collect();
+                    symbols.push(symbol(name, SYMBOL_STRUCT, &text, *span, fields));
+                }
+                Statement::VarDecl { name, span, .. } | Statement::Assign { target: name, span, .. } if !variables.contains(&name.as_str()) => {
+                    variables.push(name);
+                    symbols.push(symbol(name, SYMBOL_VARIABLE, &text, *span, Vec::new()));
+                }
+                _ => {}
+            }
+        }
+        Some(Value::Array(symbols))
+    }
+}
+
+/// Answer messages from `reader` on `writer` until the client exits or closes the stream
+pub fn serve(mut reader: impl BufRead, mut writer: impl Write) -> Result<(), String> {
+    let mut server = LanguageServer::new();
+    while let Some(message) = read_message(&mut reader)? {
+        for reply in server.handle(&message) {
+            write_message(&mut writer, &reply)?;
+        }
+        if server.exited() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// One `Content-Length` framed message, or None at the end of the stream
+pub fn read_message(reader: &mut impl BufRead) -> Result<Option<Value>, String> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).map_err(|e| format!("Failed to read a message header: {}", e))? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = Some(value.trim().parse::<usize>().map_err(|_| format!("Invalid Content-Length '{}'", value.trim()))?);
+        }
+    }
+    let length = length.ok_or("Message without a Content-Length header")?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|e| format!("Failed to read a message body: {}", e))?;
+    serde_json::from_slice(&body).map(Some).map_err(|e| format!("Message is not JSON: {}", e))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> Result<(), String> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+        .and_then(|_| writer.flush())
+        .map_err(|e| format!("Failed to write a message: {}", e))
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+fn symbol(name: &str, kind: u64, text: &str, span: Span, children: Vec<Value>) -> Value {
+    let range = range(text, span);
+    json!({ "name": name, "kind": kind, "range": range, "selectionRange": range, "children": children })
+}
+
+/// LSP range covering `span`, whose end column is the last character it covers
+fn range(text: &str, span: Span) -> Value {
+    let end = Position::new(span.end.line, span.end.column + 1, span.end.offset);
+    json!({ "start": lsp_position(text, span.start), "end": lsp_position(text, end) })
+}
+
+fn lsp_position(text: &str, position: Position) -> Value {
+    let line = position.line.saturating_sub(1);
+    let character: usize = text.lines().nth(line).unwrap_or_default()
+        .chars().take(position.column.saturating_sub(1)).map(char::len_utf16).sum();
+    json!({ "line": line, "character": character })
+}
+
+/// Byte offset of an LSP position in `text`, clamped to the end of its line
+fn byte_offset(text: &str, position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap_or(0) as usize;
+    let character = position["character"].as_u64().unwrap_or(0) as usize;
+    let line_start: usize = text.split_inclusive('\n').take(line).map(str::len).sum();
+    let line_text = text[line_start..].split('\n').next().unwrap_or_default();
+    let mut units = 0;
+    for (index, c) in line_text.char_indices() {
+        if units >= character {
+            return line_start + index;
+        }
+        units += c.len_utf16();
+    }
+    line_start + line_text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    #[test]
+    fn test_language_server_session() {
+        let uri = "file:///shapes.eg";
+        let fixture = "size = 3\ndef area(n): return n * n\nprint(area(size))\n";
+        let mut server = LanguageServer::new();
+        let replies = server.handle(&request(1, "initialize", json!({})));
+        assert_eq!(replies[0]["result"]["capabilities"]["documentSymbolProvider"], true);
+
+        let replies = server.handle(&notification("textDocument/didOpen", json!({ "textDocument": { "uri": uri, "text": fixture } })));
+        assert_eq!(replies[0]["method"], "textDocument/publishDiagnostics");
+        assert_eq!(replies[0]["params"]["diagnostics"], json!([]));
+
+        let symbols = server.handle(&request(2, "textDocument/documentSymbol", json!({ "textDocument": { "uri": uri } })));
+        let symbols = symbols[0]["result"].as_array().unwrap();
+        let names: Vec<(&str, u64)> = symbols.iter().map(|s| (s["name"].as_str().unwrap(), s["kind"].as_u64().unwrap())).collect();
+        assert_eq!(names, vec![("size", SYMBOL_VARIABLE), ("area", SYMBOL_FUNCTION)]);
+        assert_eq!(symbols[1]["range"]["start"], json!({ "line": 1, "character": 0 }));
+        assert_eq!(symbols[1]["children"][0]["name"], "n");
+
+        // Break the call on line 3, then add a variable nothing reads
+        let edit = |line: u64, start: u64, end: u64, text: &str| notification("textDocument/didChange", json!({
+            "textDocument": { "uri": uri },
+            "contentChanges": [{ "range": { "start": { "line": line, "character": start }, "end": { "line": line, "character": end } }, "text": text }],
+        }));
+        let replies = server.handle(&edit(2, 15, 15, " +"));
+        let diagnostics = replies[0]["params"]["diagnostics"].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+        assert_eq!((diagnostics[0]["severity"].as_u64(), diagnostics[0]["range"]["start"]["line"].as_u64()), (Some(1), Some(2)));
+
+        server.handle(&edit(2, 15, 17, ""));
+        let replies = server.handle(&edit(2, 17, 17, "\nspare = 1"));
+        let diagnostics = replies[0]["params"]["diagnostics"].as_array().unwrap();
+        assert_eq!((diagnostics[0]["code"].as_str(), diagnostics[0]["severity"].as_u64()), (Some("unused"), Some(2)));
+        assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 3, "character": 0 }));
+
+        let unknown = server.handle(&request(3, "textDocument/hover", json!({})));
+        assert_eq!(unknown[0]["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_serve_frames_messages() {
+        let mut input = Vec::new();
+        for message in [request(1, "initialize", json!({})), request(2, "shutdown", Value::Null), notification("exit", Value::Null)] {
+            write_message(&mut input, &message).unwrap();
+        }
+        let mut output = Vec::new();
+        serve(std::io::Cursor::new(input), &mut output).unwrap();
+
+        let mut reader = std::io::Cursor::new(output);
+        assert_eq!(read_message(&mut reader).unwrap().unwrap()["id"], 1);
+        assert_eq!(read_message(&mut reader).unwrap().unwrap(), json!({ "jsonrpc": "2.0", "id": 2, "result": null }));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+}
@@ -90,6 +90,13 @@ pub enum ParseError {
         help: Option<String>,
         context: Option<String>,
     },
+    /// Parsing stopped at `found` where one of `expected` was required
+    UnexpectedToken {
+        message: String,
+        span: Span,
+        found: String,
+        expected: Vec<String>,
+    },
     IncludeError {
         filename: String,
         message: String,
@@ -116,6 +123,33 @@ impl ParseError {
         }
     }
     
+    /// Rebuild an error raised by the Lua parser. Located errors use
+    /// "SYNTAX:<line>:<col>:<message>[\t<found>\t<expected>|...]"; anything else stays a LuaError
+    pub fn from_lua_message(message: &str) -> Self {
+        if let Some(start) = message.find("SYNTAX:") {
+            let located = message[start + 7..].lines().next().unwrap_or("");
+            let mut fields = located.splitn(3, ':');
+            if let (Some(line), Some(col), Some(rest)) = (fields.next(), fields.next(), fields.next()) {
+                if let (Ok(line), Ok(col)) = (line.parse(), col.parse()) {
+                    let span = Span::single(Position::new(line, col, 0));
+                    let mut parts = rest.split('\t');
+                    let text = parts.next().unwrap_or("");
+                    return match (parts.next(), parts.next()) {
+                        (Some(found), Some(expected)) => ParseError::UnexpectedToken {
+                            message: text.to_string(),
+                            span,
+                            found: found.to_string(),
+                            expected: expected.split('|').map(str::to_string).collect(),
+                        },
+                        _ => ParseError::syntax_error(text, span),
+                    };
+                }
+            }
+        }
+        
+        ParseError::LuaError(message.to_string())
+    }
+    
     pub fn include_error(filename: impl Into<String>, message: impl Into<String>, span: Span) -> Self {
         ParseError::IncludeError {
             filename: filename.into(),
@@ -140,9 +174,20 @@ impl ParseError {
         self
     }
     
+    /// What went wrong, without the position `Display` puts in front
+    pub fn message(&self) -> String {
+        match self {
+            ParseError::LuaError(message) => message.clone(),
+            ParseError::SyntaxError { message, .. } | ParseError::UnexpectedToken { message, .. } => message.clone(),
+            ParseError::IncludeError { filename, message, .. } => format!("cannot include '{}': {}", filename, message),
+            ParseError::HardwareError { message, .. } => message.clone(),
+        }
+    }
+    
     pub fn span(&self) -> Option<Span> {
         match self {
             ParseError::SyntaxError { span, .. } => Some(*span),
+            ParseError::UnexpectedToken { span, .. } => Some(*span),
             ParseError::IncludeError { span, .. } => Some(*span),
             ParseError::HardwareError { span, .. } => Some(*span),
             _ => None,
@@ -157,6 +202,9 @@ impl ParseError {
             ParseError::SyntaxError { message, span, help, context } => {
                 self.format_detailed("syntax error", message, span, help.as_deref(), context.as_deref(), source)
             }
+            ParseError::UnexpectedToken { message, span, .. } => {
+                self.format_detailed("syntax error", message, span, None, None, source)
+            }
             ParseError::IncludeError { filename, message, span } => {
                 let mut output = format!("include error at {}: {}: {}\n", span, filename, message);
                 if let Some(context) = self.extract_source_context(source, span) {
@@ -177,6 +225,40 @@ impl ParseError {
         }
     }
     
+    /// rustc-style report: the message, `file:line:col`, the source line and a caret
+    /// underline beneath the offending token
+    pub fn render(&self, file: &str, source: &str) -> String {
+        match self {
+            ParseError::LuaError(message) => render_diagnostic("error", file, source, message, None, &[]),
+            ParseError::SyntaxError { message, span, help, context } => {
+                let mut notes = Vec::new();
+                if let Some(context) = context {
+                    notes.push(format!("note: {}", context));
+                }
+                if let Some(help) = help {
+                    notes.push(format!("help: {}", help));
+                }
+                render_diagnostic("error", file, source, message, Some(*span), &notes)
+            }
+            ParseError::UnexpectedToken { message, span, found, expected } => {
+                // The caret underlines the whole offending token
+                let mut span = *span;
+                if found != "EOF" {
+                    span.end.column += found.chars().count().saturating_sub(1);
+                }
+                let notes: Vec<String> = (expected.len() > 1).then(|| format!("expected one of {}", expected.join(", "))).into_iter().collect();
+                render_diagnostic("error", file, source, message, Some(span), &notes)
+            }
+            ParseError::IncludeError { filename, message, span } => {
+                render_diagnostic("error", file, source, &format!("cannot include '{}': {}", filename, message), Some(*span), &[])
+            }
+            ParseError::HardwareError { message, span, device } => {
+                let notes: Vec<String> = device.iter().map(|d| format!("device: {}", d)).collect();
+                render_diagnostic("error", file, source, message, Some(*span), &notes)
+            }
+        }
+    }
+    
     fn format_detailed(&self, error_type: &str, message: &str, span: &Span, help: Option<&str>, context: Option<&str>, source: &str) -> String {
         use colored::*;
         
@@ -249,7 +331,7 @@ impl std::fmt::Display for ParseError {
             ParseError::LuaError(message) => {
                 write!(f, "Lua parsing error: {}", message)
             }
-            ParseError::SyntaxError { message, span, .. } => {
+            ParseError::SyntaxError { message, span, .. } | ParseError::UnexpectedToken { message, span, .. } => {
                 write!(f, "syntax error at {}: {}", span, message)
             }
             ParseError::IncludeError { filename, message, span } => {
@@ -270,8 +352,49 @@ impl std::error::Error for ParseError {}
 
 impl From<LuaError> for ParseError {
     fn from(err: LuaError) -> Self {
-        ParseError::LuaError(err.to_string())
+        ParseError::from_lua_message(&err.to_string())
+    }
+}
+
+/// Format one diagnostic at `level` ("error", "warning") the way rustc does:
+///
+/// ```text
+/// error: expected ':', found 'x'
+///  --> demo.eg:3:7
+///   |
+/// 3 | while x > 1 x
+///   |             ^
+///   = help: ...
+/// ```
+///
+/// The snippet is left out when `span` is missing or outside `source`.
+pub fn render_diagnostic(level: &str, file: &str, source: &str, message: &str, span: Option<Span>, notes: &[String]) -> String {
+    let line = span.and_then(|span| {
+        source.lines().nth(span.start.line.checked_sub(1)?).map(|text| (span, text))
+    });
+    
+    let mut output = format!("{}: {}\n", level, message);
+    let Some((span, text)) = line else {
+        output.push_str(&format!(" --> {}\n", file));
+        for note in notes {
+            output.push_str(&format!("  = {}\n", note));
+        }
+        return output;
+    };
+    
+    let number = span.start.line.to_string();
+    let gutter = " ".repeat(number.len());
+    let start = span.start.column.max(1);
+    let end = if span.end.line == span.start.line { span.end.column.max(start) } else { text.chars().count().max(start) };
+    
+    output.push_str(&format!("{}--> {}:{}:{}\n", gutter, file, span.start.line, start));
+    output.push_str(&format!("{} |\n", gutter));
+    output.push_str(&format!("{} | {}\n", number, text));
+    output.push_str(&format!("{} | {}{}\n", gutter, " ".repeat(start - 1), "^".repeat(end - start + 1)));
+    for note in notes {
+        output.push_str(&format!("{} = {}\n", gutter, note));
     }
+    output
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -295,6 +418,12 @@ pub enum UnaryOp {
     Not, Plus, Minus, Invert,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum FStringPart {
+    Literal(String),
+    Expr(Expr),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Expr {
     Number(i64, Span),
@@ -308,7 +437,16 @@ pub enum Expr {
     BoolOp { op: BoolOp, values: Vec<Expr>, span: Span },
     Compare { left: Box<Expr>, ops: Vec<CompareOp>, comparators: Vec<Expr>, span: Span },
     Call { func: String, args: Vec<Expr>, kwargs: HashMap<String, Expr>, span: Span },
+    FString { parts: Vec<FStringPart>, span: Span },
     HardwareCall { device: String, func: String, args: Vec<Expr>, span: Span },
+    List { elements: Vec<Expr>, span: Span },
+    Dict { entries: Vec<(Expr, Expr)>, span: Span },
+    Index { value: Box<Expr>, index: Box<Expr>, span: Span },
+    /// `value[start:stop]`, either bound left out
+    Slice { value: Box<Expr>, start: Option<Box<Expr>>, stop: Option<Box<Expr>>, span: Span },
+    FieldAccess { object: Box<Expr>, field: String, span: Span },
+    /// `receiver.method(args)`
+    MethodCall { receiver: Box<Expr>, method: String, args: Vec<Expr>, span: Span },
 }
 
 impl Expr {
@@ -325,7 +463,66 @@ impl Expr {
             Expr::BoolOp { span, .. } => *span,
             Expr::Compare { span, .. } => *span,
             Expr::Call { span, .. } => *span,
+            Expr::FString { span, .. } => *span,
             Expr::HardwareCall { span, .. } => *span,
+            Expr::List { span, .. } => *span,
+            Expr::Dict { span, .. } => *span,
+            Expr::Index { span, .. } => *span,
+            Expr::Slice { span, .. } => *span,
+            Expr::FieldAccess { span, .. } => *span,
+            Expr::MethodCall { span, .. } => *span,
+        }
+    }
+    
+    /// The same expression with its own span, not those inside it, replaced
+    pub fn with_span(mut self, new_span: Span) -> Self {
+        match &mut self {
+            Expr::Number(_, span)
+            | Expr::Float(_, span)
+            | Expr::Boolean(_, span)
+            | Expr::String(_, span)
+            | Expr::Var(_, span)
+            | Expr::None(span)
+            | Expr::BinOp { span, .. }
+            | Expr::UnaryOp { span, .. }
+            | Expr::BoolOp { span, .. }
+            | Expr::Compare { span, .. }
+            | Expr::Call { span, .. }
+            | Expr::FString { span, .. }
+            | Expr::HardwareCall { span, .. }
+            | Expr::List { span, .. }
+            | Expr::Dict { span, .. }
+            | Expr::Index { span, .. }
+            | Expr::Slice { span, .. }
+            | Expr::FieldAccess { span, .. }
+            | Expr::MethodCall { span, .. } => *span = new_span,
+        }
+        self
+    }
+    
+    /// What this kind of expression is called, in the plural, for errors
+    /// naming a construct a backend cannot compile
+    pub fn construct(&self) -> &'static str {
+        match self {
+            Expr::Number(..) => "integers",
+            Expr::Float(..) => "floats",
+            Expr::Boolean(..) => "booleans",
+            Expr::String(..) => "strings",
+            Expr::Var(..) => "variables",
+            Expr::None(..) => "None values",
+            Expr::BinOp { .. } => "binary operations",
+            Expr::UnaryOp { .. } => "unary operations",
+            Expr::BoolOp { .. } => "and/or expressions",
+            Expr::Compare { .. } => "comparisons",
+            Expr::Call { .. } => "function calls",
+            Expr::FString { .. } => "f-strings",
+            Expr::HardwareCall { .. } => "hardware calls",
+            Expr::List { .. } => "lists",
+            Expr::Dict { .. } => "dicts",
+            Expr::Index { .. } => "subscripts",
+            Expr::Slice { .. } => "slices",
+            Expr::FieldAccess { .. } => "field accesses",
+            Expr::MethodCall { .. } => "method calls",
         }
     }
 }
@@ -333,13 +530,29 @@ impl Expr {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Statement {
     VarDecl { name: String, value: Expr, type_hint: Option<String>, span: Span },
+    /// `const NAME = value`, replaced by its value wherever NAME is read
+    ConstDecl { name: String, value: Expr, span: Span },
     Assign { target: String, value: Expr, span: Span },
     AugAssign { target: String, op: Op, value: Expr, span: Span },
+    IndexAssign { target: Box<Expr>, index: Box<Expr>, value: Expr, span: Span },
+    FieldAssign { target: Box<Expr>, field: String, value: Expr, span: Span },
     Expr(Expr),
-    Return(Option<Expr>),
+    Return(Option<Expr>, Span),
     If { condition: Expr, then_block: Vec<Statement>, elif_blocks: Vec<(Expr, Vec<Statement>)>, else_block: Option<Vec<Statement>>, span: Span },
     While { condition: Expr, body: Vec<Statement>, orelse: Option<Vec<Statement>>, span: Span },
-    FunctionDef { name: String, args: Vec<String>, body: Vec<Statement>, span: Span },
+    For { var: String, iter: Expr, body: Vec<Statement>, span: Span },
+    /// `def name(a: int, b) -> str: ...`; `arg_types` has an entry, maybe
+    /// `None`, for every argument
+    FunctionDef {
+        name: String,
+        args: Vec<String>,
+        arg_types: Vec<Option<String>>,
+        return_type: Option<String>,
+        body: Vec<Statement>,
+        span: Span,
+    },
+    /// `struct Name: field, ...`, whose instances `Name(...)` builds from positional arguments
+    StructDef { name: String, fields: Vec<String>, span: Span },
     HardwareFunctionDef { 
         device: String, 
         name: String, 
@@ -351,6 +564,7 @@ pub enum Statement {
     Break,
     Continue,
     Include { filename: String, span: Span },
+    Import { module: String, items: Option<Vec<String>>, span: Span },
     HardwareDecl { device: String, config: HashMap<String, Expr>, span: Span },
 }
 
@@ -358,21 +572,54 @@ impl Statement {
     pub fn span(&self) -> Span {
         match self {
             Statement::VarDecl { span, .. } => *span,
+            Statement::ConstDecl { span, .. } => *span,
             Statement::Assign { span, .. } => *span,
             Statement::AugAssign { span, .. } => *span,
+            Statement::IndexAssign { span, .. } => *span,
+            Statement::FieldAssign { span, .. } => *span,
             Statement::Expr(expr) => expr.span(),
-            Statement::Return(expr) => expr.as_ref().map_or(Span::single(Position::new(0, 0, 0)), |e| e.span()),
+            Statement::Return(_, span) => *span,
             Statement::If { span, .. } => *span,
             Statement::While { span, .. } => *span,
+            Statement::For { span, .. } => *span,
             Statement::FunctionDef { span, .. } => *span,
+            Statement::StructDef { span, .. } => *span,
             Statement::HardwareFunctionDef { span, .. } => *span,
             Statement::Pass => Span::single(Position::new(0, 0, 0)),
             Statement::Break => Span::single(Position::new(0, 0, 0)),
             Statement::Continue => Span::single(Position::new(0, 0, 0)),
             Statement::Include { span, .. } => *span,
+            Statement::Import { span, .. } => *span,
             Statement::HardwareDecl { span, .. } => *span,
         }
     }
+    
+    /// What this kind of statement is called, in the plural, for errors
+    /// naming a construct a backend cannot compile
+    pub fn construct(&self) -> &'static str {
+        match self {
+            Statement::VarDecl { .. } => "variable declarations",
+            Statement::ConstDecl { .. } => "constants",
+            Statement::Assign { .. } => "assignments",
+            Statement::AugAssign { .. } => "augmented assignments",
+            Statement::IndexAssign { .. } => "subscript assignments",
+            Statement::FieldAssign { .. } => "field assignments",
+            Statement::Expr(expr) => expr.construct(),
+            Statement::Return(..) => "return statements",
+            Statement::If { .. } => "if statements",
+            Statement::While { .. } => "while loops",
+            Statement::For { .. } => "for loops",
+            Statement::FunctionDef { .. } => "function definitions",
+            Statement::StructDef { .. } => "structs",
+            Statement::HardwareFunctionDef { .. } => "hardware functions",
+            Statement::Pass => "pass statements",
+            Statement::Break => "break statements",
+            Statement::Continue => "continue statements",
+            Statement::Include { .. } => "includes",
+            Statement::Import { .. } => "imports",
+            Statement::HardwareDecl { .. } => "hardware declarations",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -393,9 +640,50 @@ impl LuaFrontend {
         }
     }
     
-    pub fn parse_program(&self, source: &str) -> Result<Program, ParseError> {
+    /// Start tracking `document`, replacing any session it already had
+    pub fn open_document(&self, document: &str, text: &str) -> Result<(), Vec<ParseError>> {
+        let (session, result) = ParserSession::open(self, text);
+        self.lua_pool.store_session(document, session);
+        result
+    }
+    
+    /// Apply an edit to an open document, returning the top-level statements it changed
+    pub fn edit_document(&self, document: &str, range: std::ops::Range<usize>, replacement: &str) -> Result<Vec<usize>, Vec<ParseError>> {
+        let mut session = self.lua_pool.take_session(document)
+            .ok_or_else(|| vec![ParseError::lua_error(format!("Document '{}' is not open", document))])?;
+        let result = session.edit(self, range, replacement);
+        self.lua_pool.store_session(document, session);
+        result
+    }
+    
+    pub fn document_program(&self, document: &str) -> Option<Program> {
+        let session = self.lua_pool.take_session(document)?;
+        let program = session.program().cloned();
+        self.lua_pool.store_session(document, session);
+        program
+    }
+    
+    pub fn document_text(&self, document: &str) -> Option<String> {
+        let session = self.lua_pool.take_session(document)?;
+        let text = session.text().to_string();
+        self.lua_pool.store_session(document, session);
+        Some(text)
+    }
+    
+    pub fn close_document(&self, document: &str) -> bool {
+        self.lua_pool.take_session(document).is_some()
+    }
+    
+    /// Parse `source`, reporting every statement that failed to parse
+    pub fn parse_program(&self, source: &str) -> Result<Program, Vec<ParseError>> {
         let lua = self.lua_pool.get_instance()
-            .ok_or_else(|| ParseError::lua_error("Failed to get Lua instance from pool"))?;
+            .ok_or_else(|| vec![ParseError::lua_error("Failed to get Lua instance from pool")])?;
+        let result = self.parse_with(&lua, source);
+        self.lua_pool.return_instance(lua);
+        result
+    }
+    
+    fn parse_with(&self, lua: &Lua, source: &str) -> Result<Program, Vec<ParseError>> {
         
         let parser_script = r#"
 local parser = {}
@@ -409,10 +697,14 @@ local TokenType = {
     PUNCTUATION = 6,
     COMMENT = 7,
     EOF = 8,
+    FSTRING = 9,
+    FLOAT = 10,
 }
 
 local keywords = {
     ["var"] = true,
+    ["const"] = true,
+    ["struct"] = true,
     ["if"] = true,
     ["elif"] = true,
     ["else"] = true,
@@ -431,6 +723,8 @@ local keywords = {
     ["False"] = true,
     ["None"] = true,
     ["include"] = true,
+    ["import"] = true,
+    ["from"] = true,
     ["section"] = true,
     ["global"] = true,
     ["end"] = true,
@@ -464,6 +758,7 @@ local operators = {
     ["|"] = "BIT_OR",
     ["^"] = "BIT_XOR",
     ["~"] = "BIT_NOT",
+    ["->"] = "ARROW",
 }
 
 function parser.lex(source)
@@ -542,7 +837,7 @@ function parser.lex(source)
             end
             
             if #hex_str == 0 then
-                error("Invalid hex literal at line " .. start_line .. ", col " .. start_col)
+                error(string.format("SYNTAX:%d:%d:hex literal needs a digit after '0x'", start_line, start_col), 0)
             end
             
             local num = tonumber(hex_str, 16)
@@ -569,27 +864,42 @@ function parser.lex(source)
             end
             
             if is_float then
-                add_token(TokenType.NUMBER, tonumber(num_str), start_line, start_col, #num_str)
+                add_token(TokenType.FLOAT, tonumber(num_str), start_line, start_col, #num_str)
             else
                 add_token(TokenType.NUMBER, tonumber(num_str), start_line, start_col, #num_str)
             end
         
-        elseif c == '"' or c == "'" then
-            local quote = c
+        elseif c == '"' or c == "'" or (c == 'f' and source:sub(pos + 1, pos + 1):match('["\']')) then
+            local is_fstring = c == 'f'
+            if is_fstring then
+                pos = pos + 1
+                col = col + 1
+            end
+            local quote = source:sub(pos, pos)
             pos = pos + 1
             col = col + 1
             local str = ''
             local escape = false
+            local closed = false
             
             while pos <= #source do
                 local ch = source:sub(pos, pos)
-                if escape then
+                if ch == '\n' then
+                    -- Like Python, a string cannot run past the end of its line
+                    break
+                elseif escape then
                     if ch == 'n' then str = str .. '\n'
                     elseif ch == 't' then str = str .. '\t'
                     elseif ch == 'r' then str = str .. '\r'
                     elseif ch == '\\' then str = str .. '\\'
                     elseif ch == '"' then str = str .. '"'
                     elseif ch == "'" then str = str .. "'"
+                    elseif ch == '0' then str = str .. '\0'
+                    elseif ch == 'x' and source:sub(pos + 1, pos + 2):match('^%x%x$') then
+                        -- \xNN names a code point, stored as UTF-8 like Python
+                        str = str .. utf8.char(tonumber(source:sub(pos + 1, pos + 2), 16))
+                        pos = pos + 2
+                        col = col + 2
                     else str = str .. ch end
                     escape = false
                 elseif ch == '\\' then
@@ -597,6 +907,7 @@ function parser.lex(source)
                 elseif ch == quote then
                     pos = pos + 1
                     col = col + 1
+                    closed = true
                     break
                 else
                     str = str .. ch
@@ -604,8 +915,15 @@ function parser.lex(source)
                 pos = pos + 1
                 col = col + 1
             end
+            if not closed then
+                error(string.format("SYNTAX:%d:%d:unterminated string literal", start_line, start_col), 0)
+            end
             
-            add_token(TokenType.STRING, str, start_line, start_col, #str + 2)
+            if is_fstring then
+                add_token(TokenType.FSTRING, str, start_line, start_col, #str + 3)
+            else
+                add_token(TokenType.STRING, str, start_line, start_col, #str + 2)
+            end
         
         elseif c:match('[%a_]') then
             local ident = ''
@@ -660,6 +978,37 @@ function parser.lex(source)
     return tokens
 end
 
+local token_type_names = {
+    [TokenType.KEYWORD] = "keyword",
+    [TokenType.IDENTIFIER] = "identifier",
+    [TokenType.NUMBER] = "number",
+    [TokenType.STRING] = "string",
+    [TokenType.OPERATOR] = "operator",
+    [TokenType.PUNCTUATION] = "punctuation",
+    [TokenType.COMMENT] = "comment",
+    [TokenType.EOF] = "end of file",
+    [TokenType.FSTRING] = "f-string",
+    [TokenType.FLOAT] = "number",
+}
+
+-- How a token is named in "expected ..., found ..." messages
+local function describe_token(token)
+    if token.type == TokenType.EOF then
+        return "end of file"
+    end
+    return "'" .. token.value .. "'"
+end
+
+-- Errors are raised as "SYNTAX:<line>:<col>:<message>", optionally followed by
+-- "\t<found>\t<expected>|<expected>..." so the Rust side can rebuild a located ParseError
+local function syntax_error(token, message, expected)
+    local located = string.format("SYNTAX:%d:%d:%s", token.line, token.col, message)
+    if expected then
+        located = located .. "\t" .. token.value .. "\t" .. table.concat(expected, "|")
+    end
+    error(located, 0)
+end
+
 function parser.parse(tokens)
     local pos = 1
     
@@ -677,7 +1026,8 @@ function parser.parse(tokens)
             pos = pos + 1
             return token
         else
-            error("Expected " .. (value or type) .. ", got " .. token.value)
+            local wanted = value and ("'" .. value .. "'") or token_type_names[type]
+            syntax_error(token, "expected " .. wanted .. ", found " .. describe_token(token), {wanted})
         end
     end
     
@@ -699,6 +1049,167 @@ function parser.parse(tokens)
     local parse_unary
     local parse_primary
     
+    local function at_punctuation(value)
+        return current().type == TokenType.PUNCTUATION and current().value == value
+    end
+    
+    -- The arguments of a call after its `(`, up to and including the `)`
+    local function parse_arguments()
+        local args = {}
+        -- `name=value` arguments, which like Python's come after the positional ones.
+        -- The name may be a keyword, so print's `end=` can be passed
+        local kwargs = {}
+        local has_kwargs = false
+        
+        if not match(TokenType.PUNCTUATION, ")") then
+            repeat
+                local arg = current()
+                local after = peek()
+                if (arg.type == TokenType.IDENTIFIER or arg.type == TokenType.KEYWORD) and after and
+                   after.type == TokenType.OPERATOR and after.value == "=" then
+                    consume(arg.type)
+                    consume(TokenType.OPERATOR, "=")
+                    if kwargs[arg.value] ~= nil then
+                        syntax_error(arg, "keyword argument '" .. arg.value .. "' is repeated")
+                    end
+                    kwargs[arg.value] = parse_expression()
+                    has_kwargs = true
+                elseif has_kwargs then
+                    syntax_error(arg, "positional argument follows keyword argument")
+                else
+                    -- Arguments remember where they start so errors can point at them
+                    local value = parse_expression()
+                    value.line = arg.line
+                    value.col = arg.col
+                    table.insert(args, value)
+                end
+            until not match(TokenType.PUNCTUATION, ",")
+            consume(TokenType.PUNCTUATION, ")")
+        end
+        return args, kwargs
+    end
+    
+    -- Any number of `[index]`, `[start:stop]`, `.field` and `.method(args)`
+    -- suffixes after a primary expression
+    local function parse_subscripts(expr)
+        while true do
+            if match(TokenType.PUNCTUATION, "[") then
+                local index = nil
+                if not at_punctuation(":") then
+                    index = parse_expression()
+                end
+                if index == nil or at_punctuation(":") then
+                    consume(TokenType.PUNCTUATION, ":")
+                    local stop = nil
+                    if not at_punctuation("]") then
+                        stop = parse_expression()
+                    end
+                    consume(TokenType.PUNCTUATION, "]")
+                    expr = {
+                        type = "Slice",
+                        value = expr,
+                        start = index,
+                        stop = stop
+                    }
+                else
+                    consume(TokenType.PUNCTUATION, "]")
+                    expr = {
+                        type = "Index",
+                        value = expr,
+                        index = index
+                    }
+                end
+            elseif match(TokenType.PUNCTUATION, ".") then
+                local name = consume(TokenType.IDENTIFIER)
+                if match(TokenType.PUNCTUATION, "(") then
+                    local args, kwargs = parse_arguments()
+                    if next(kwargs) ~= nil then
+                        syntax_error(name, "method '" .. name.value .. "' takes no keyword arguments")
+                    end
+                    expr = {
+                        type = "MethodCall",
+                        receiver = expr,
+                        method = name.value,
+                        args = args
+                    }
+                else
+                    expr = {
+                        type = "FieldAccess",
+                        object = expr,
+                        field = name.value
+                    }
+                end
+            else
+                return expr
+            end
+        end
+    end
+    
+    -- Errors carrying a position are reported as SyntaxError spans by the Rust side
+    local function fstring_error(token, offset, message)
+        error(string.format("SYNTAX:%d:%d:%s", token.line, token.col + 2 + offset, message), 0)
+    end
+    
+    local function parse_fstring(token)
+        local text = token.value
+        local parts = {}
+        local literal = ''
+        local i = 1
+        
+        while i <= #text do
+            local ch = text:sub(i, i)
+            local next_ch = text:sub(i + 1, i + 1)
+            
+            if ch == '{' and next_ch == '{' then
+                literal = literal .. '{'
+                i = i + 2
+            elseif ch == '}' and next_ch == '}' then
+                literal = literal .. '}'
+                i = i + 2
+            elseif ch == '{' then
+                local close = text:find('}', i + 1, true)
+                if not close then
+                    fstring_error(token, i - 1, "unterminated '{' in f-string")
+                end
+                
+                -- Parse the embedded expression with this parser on its own token stream
+                local saved_tokens, saved_pos = tokens, pos
+                tokens = parser.lex(text:sub(i + 1, close - 1))
+                pos = 1
+                if current().type == TokenType.EOF then
+                    fstring_error(token, i - 1, "expected an expression inside '{}' in f-string")
+                end
+                local expr = parse_expression()
+                local complete = current().type == TokenType.EOF
+                tokens, pos = saved_tokens, saved_pos
+                if not complete then
+                    fstring_error(token, i - 1, "expected an expression inside '{}' in f-string")
+                end
+                
+                if #literal > 0 then
+                    table.insert(parts, {kind = "text", value = literal})
+                    literal = ''
+                end
+                table.insert(parts, {kind = "expr", expr = expr})
+                i = close + 1
+            elseif ch == '}' then
+                fstring_error(token, i - 1, "single '}' is not allowed in f-string")
+            else
+                literal = literal .. ch
+                i = i + 1
+            end
+        end
+        
+        if #literal > 0 then
+            table.insert(parts, {kind = "text", value = literal})
+        end
+        
+        return {
+            type = "FString",
+            parts = parts
+        }
+    end
+    
     parse_primary = function()
         local token = current()
         
@@ -709,37 +1220,70 @@ function parser.parse(tokens)
                 value = token.value
             }
         
+        elseif token.type == TokenType.FLOAT then
+            consume(TokenType.FLOAT)
+            return {
+                type = "Float",
+                value = token.value
+            }
+        
         elseif token.type == TokenType.STRING then
             consume(TokenType.STRING)
-            return {
+            return parse_subscripts({
                 type = "String",
                 value = token.value
-            }
+            })
+        
+        elseif token.type == TokenType.FSTRING then
+            consume(TokenType.FSTRING)
+            return parse_subscripts(parse_fstring(token))
         
         elseif token.type == TokenType.IDENTIFIER then
             consume(TokenType.IDENTIFIER)
             
             if match(TokenType.PUNCTUATION, "(") then
-                local args = {}
-                
-                if not match(TokenType.PUNCTUATION, ")") then
-                    repeat
-                        table.insert(args, parse_expression())
-                    until not match(TokenType.PUNCTUATION, ",")
-                    consume(TokenType.PUNCTUATION, ")")
-                end
-                
-                return {
+                local args, kwargs = parse_arguments()
+                return parse_subscripts({
                     type = "Call",
                     func = token.value,
-                    args = args
-                }
+                    args = args,
+                    kwargs = kwargs
+                })
             else
-                return {
+                return parse_subscripts({
                     type = "Var",
                     name = token.value
-                }
+                })
+            end
+        
+        elseif match(TokenType.PUNCTUATION, "[") then
+            local elements = {}
+            if not match(TokenType.PUNCTUATION, "]") then
+                repeat
+                    table.insert(elements, parse_expression())
+                until not match(TokenType.PUNCTUATION, ",")
+                consume(TokenType.PUNCTUATION, "]")
+            end
+            return parse_subscripts({
+                type = "List",
+                elements = elements
+            })
+        
+        elseif match(TokenType.PUNCTUATION, "{") then
+            local keys, values = {}, {}
+            if not match(TokenType.PUNCTUATION, "}") then
+                repeat
+                    table.insert(keys, parse_expression())
+                    consume(TokenType.PUNCTUATION, ":")
+                    table.insert(values, parse_expression())
+                until not match(TokenType.PUNCTUATION, ",")
+                consume(TokenType.PUNCTUATION, "}")
             end
+            return parse_subscripts({
+                type = "Dict",
+                keys = keys,
+                values = values
+            })
         
         elseif token.type == TokenType.KEYWORD then
             if token.value == "True" then
@@ -752,14 +1296,15 @@ function parser.parse(tokens)
                 consume(TokenType.KEYWORD)
                 return {type = "None"}
             end
+            syntax_error(token, "expected an expression, found " .. describe_token(token), {"expression"})
         
         elseif match(TokenType.PUNCTUATION, "(") then
             local expr = parse_expression()
             consume(TokenType.PUNCTUATION, ")")
-            return expr
+            return parse_subscripts(expr)
         
         else
-            error("Unexpected token: " .. token.value)
+            syntax_error(token, "expected an expression, found " .. describe_token(token), {"expression"})
         end
     end
     
@@ -905,6 +1450,25 @@ function parser.parse(tokens)
             end
         end
         
+        -- Membership tests: `key in d` and `key not in d`
+        local membership = nil
+        if match(TokenType.KEYWORD, "in") then
+            membership = "in"
+        elseif current().type == TokenType.KEYWORD and current().value == "not" and
+               peek() and peek().type == TokenType.KEYWORD and peek().value == "in" then
+            consume(TokenType.KEYWORD, "not")
+            consume(TokenType.KEYWORD, "in")
+            membership = "not in"
+        end
+        if membership then
+            return {
+                type = "Compare",
+                left = left,
+                ops = {membership},
+                comparators = {parse_addition()}
+            }
+        end
+        
         return left
     end
     
@@ -944,16 +1508,33 @@ function parser.parse(tokens)
         return parse_logical_or()
     end
     
+    -- Statements remember where they start so later errors can point at them
     function parse_statement()
         local token = current()
+        local stmt = parse_statement_kind()
+        if stmt.line == nil then
+            stmt.line = token.line
+            stmt.col = token.col
+        end
+        return stmt
+    end
+    
+    function parse_statement_kind()
+        local token = current()
         
         if token.type == TokenType.KEYWORD then
             if token.value == "var" then
                 return parse_var_decl()
+            elseif token.value == "const" then
+                return parse_const_decl()
+            elseif token.value == "struct" then
+                return parse_struct_def()
             elseif token.value == "if" then
                 return parse_if_statement()
             elseif token.value == "while" then
                 return parse_while_statement()
+            elseif token.value == "for" then
+                return parse_for_statement()
             elseif token.value == "def" then
                 return parse_function_def()
             elseif token.value == "device" then
@@ -971,6 +1552,10 @@ function parser.parse(tokens)
                 return {type = "Continue"}
             elseif token.value == "include" then
                 return parse_include_statement()
+            elseif token.value == "import" then
+                return parse_import_statement()
+            elseif token.value == "from" then
+                return parse_from_import_statement()
             elseif token.value == "section" then
                 return parse_section_statement()
             elseif token.value == "global" then
@@ -992,6 +1577,39 @@ function parser.parse(tokens)
         end
         
         local expr = parse_expression()
+        
+        -- `xs[i] = v` and `xs[i] op= v` store through the subscript
+        if expr.type == "Index" and current().type == TokenType.OPERATOR and
+           (current().value == "=" or current().value:find("^[%+%-%*/%%&|%^]+=$")) then
+            local op = consume(TokenType.OPERATOR).value
+            local value = parse_expression()
+            if op ~= "=" then
+                value = {type = "BinOp", op = op:sub(1, -2), left = expr, right = value}
+            end
+            return {
+                type = "IndexAssign",
+                target = expr.value,
+                index = expr.index,
+                value = value
+            }
+        end
+        
+        -- `p.x = v` and `p.x op= v` store into the field
+        if expr.type == "FieldAccess" and current().type == TokenType.OPERATOR and
+           (current().value == "=" or current().value:find("^[%+%-%*/%%&|%^]+=$")) then
+            local op = consume(TokenType.OPERATOR).value
+            local value = parse_expression()
+            if op ~= "=" then
+                value = {type = "BinOp", op = op:sub(1, -2), left = expr, right = value}
+            end
+            return {
+                type = "FieldAssign",
+                target = expr.object,
+                field = expr.field,
+                value = value
+            }
+        end
+        
         if match(TokenType.PUNCTUATION, ";") then
             -- Optional semicolon
         end
@@ -1004,7 +1622,7 @@ function parser.parse(tokens)
         
         local type_hint = nil
         if match(TokenType.PUNCTUATION, ":") then
-            type_hint = consume(TokenType.IDENTIFIER).value
+            type_hint = parse_type_name()
         end
         
         consume(TokenType.OPERATOR, "=")
@@ -1018,6 +1636,36 @@ function parser.parse(tokens)
         }
     end
     
+    function parse_const_decl()
+        consume(TokenType.KEYWORD, "const")
+        local name = consume(TokenType.IDENTIFIER).value
+        consume(TokenType.OPERATOR, "=")
+        local value = parse_expression()
+        
+        return {
+            type = "ConstDecl",
+            name = name,
+            value = value
+        }
+    end
+    
+    -- `struct Point: x, y`
+    function parse_struct_def()
+        consume(TokenType.KEYWORD, "struct")
+        local name = consume(TokenType.IDENTIFIER).value
+        consume(TokenType.PUNCTUATION, ":")
+        local fields = {}
+        repeat
+            table.insert(fields, consume(TokenType.IDENTIFIER).value)
+        until not match(TokenType.PUNCTUATION, ",")
+        
+        return {
+            type = "StructDef",
+            name = name,
+            fields = fields
+        }
+    end
+    
     function parse_device_decl()
         consume(TokenType.KEYWORD, "device")
         local device_name = consume(TokenType.IDENTIFIER).value
@@ -1171,6 +1819,15 @@ function parser.parse(tokens)
     local condition = parse_expression()
     consume(TokenType.PUNCTUATION, ":")
     
+    return {
+        type = "While",
+        condition = condition,
+        body = parse_loop_body("while")
+    }
+end
+    
+    -- Loop bodies are either braced or run until a matching "end"
+    function parse_loop_body(loop_name)
     local body = {}
     
     -- Skip optional newline after colon
@@ -1185,7 +1842,7 @@ function parser.parse(tokens)
         end
     else
         -- Parse statements until we hit the "end" keyword
-        -- This handles multi-statement while loops
+        -- This handles multi-statement loops
         while true do
             local token = current()
             
@@ -1197,7 +1854,7 @@ function parser.parse(tokens)
             
             -- If hit EOF, that's an error
             if token.type == TokenType.EOF then
-                error("Unexpected end of file in while loop at line " .. token.line .. ", col " .. token.col)
+                error("Unexpected end of file in " .. loop_name .. " loop at line " .. token.line .. ", col " .. token.col)
             end
             
             -- Parse the statement and add it to the body
@@ -1210,7 +1867,7 @@ function parser.parse(tokens)
                 consume(TokenType.KEYWORD, "end")
                 break
             elseif next_token.type == TokenType.EOF then
-                error("Missing 'end' for while loop")
+                error("Missing 'end' for " .. loop_name .. " loop")
             end
             
             -- Skip optional semicolon
@@ -1220,26 +1877,61 @@ function parser.parse(tokens)
         end
     end
     
+    return body
+end
+    
+    function parse_for_statement()
+    local token = consume(TokenType.KEYWORD, "for")
+    local var = consume(TokenType.IDENTIFIER).value
+    consume(TokenType.KEYWORD, "in")
+    local iter = parse_expression()
+    
+    -- Only range() can be iterated for now
+    if iter.type ~= "Call" or iter.func ~= "range" or #iter.args < 1 or #iter.args > 3 then
+        error(string.format("SYNTAX:%d:%d:%s", token.line, token.col,
+            "for loops expect range(stop), range(start, stop) or range(start, stop, step)"), 0)
+    end
+    consume(TokenType.PUNCTUATION, ":")
+    
     return {
-        type = "While",
-        condition = condition,
-        body = body
+        type = "For",
+        var = var,
+        iter = iter,
+        body = parse_loop_body("for")
     }
 end
     
+    -- A type annotation: a type or struct name, or None
+    function parse_type_name()
+        if match(TokenType.KEYWORD, "None") then
+            return "None"
+        end
+        return consume(TokenType.IDENTIFIER).value
+    end
+    
     function parse_function_def()
-        consume(TokenType.KEYWORD, "def")
+        local token = consume(TokenType.KEYWORD, "def")
         local name = consume(TokenType.IDENTIFIER).value
         consume(TokenType.PUNCTUATION, "(")
         
+        -- arg_types[i] stays nil for an argument without an annotation
         local args = {}
+        local arg_types = {}
         if not match(TokenType.PUNCTUATION, ")") then
             repeat
                 table.insert(args, consume(TokenType.IDENTIFIER).value)
+                if match(TokenType.PUNCTUATION, ":") then
+                    arg_types[#args] = parse_type_name()
+                end
             until not match(TokenType.PUNCTUATION, ",")
             consume(TokenType.PUNCTUATION, ")")
         end
         
+        local return_type = nil
+        if match(TokenType.OPERATOR, "->") then
+            return_type = parse_type_name()
+        end
+        
         consume(TokenType.PUNCTUATION, ":")
         
         local body = {}
@@ -1255,21 +1947,29 @@ end
             type = "FunctionDef",
             name = name,
             args = args,
-            body = body
+            arg_types = arg_types,
+            return_type = return_type,
+            body = body,
+            line = token.line,
+            col = token.col
         }
     end
     
     function parse_return_statement()
-        consume(TokenType.KEYWORD, "return")
+        local token = consume(TokenType.KEYWORD, "return")
         local expr = nil
-        if current().type ~= TokenType.EOF and 
-           current().type ~= TokenType.PUNCTUATION and 
-           current().type ~= TokenType.KEYWORD then
+        -- A value starts on the return's line; brackets and literal keywords can open it
+        local next = current()
+        if next.type ~= TokenType.EOF and next.line == token.line and
+           (next.type ~= TokenType.PUNCTUATION or next.value == "(" or next.value == "[" or next.value == "{") and
+           (next.type ~= TokenType.KEYWORD or next.value == "True" or next.value == "False" or next.value == "None" or next.value == "not") then
             expr = parse_expression()
         end
         return {
             type = "Return",
-            expr = expr
+            expr = expr,
+            line = token.line,
+            col = token.col
         }
     end
     
@@ -1282,6 +1982,34 @@ end
         }
     end
     
+    function parse_import_statement()
+        local token = consume(TokenType.KEYWORD, "import")
+        local module = consume(TokenType.IDENTIFIER).value
+        return {
+            type = "Import",
+            module = module,
+            line = token.line,
+            col = token.col
+        }
+    end
+    
+    function parse_from_import_statement()
+        local token = consume(TokenType.KEYWORD, "from")
+        local module = consume(TokenType.IDENTIFIER).value
+        consume(TokenType.KEYWORD, "import")
+        local items = {}
+        repeat
+            table.insert(items, consume(TokenType.IDENTIFIER).value)
+        until not match(TokenType.PUNCTUATION, ",")
+        return {
+            type = "Import",
+            module = module,
+            items = items,
+            line = token.line,
+            col = token.col
+        }
+    end
+    
     function parse_section_statement()
         consume(TokenType.KEYWORD, "section")
         local name_token = consume(TokenType.STRING)
@@ -1305,25 +2033,45 @@ end
         pos = pos + 1
     end
     
+    -- A failed statement is recorded and parsing resumes at the next line that
+    -- is not indented deeper than it, so one run reports every broken statement
     local statements = {}
+    local errors = {}
     while current().type ~= TokenType.EOF do
         if current().type == TokenType.COMMENT then
             pos = pos + 1
         else
-            table.insert(statements, parse_statement())
+            local start = current()
+            local ok, result = pcall(parse_statement)
+            if ok then
+                table.insert(statements, result)
+            else
+                table.insert(errors, tostring(result))
+                local failed_line = current().line
+                while current().type ~= TokenType.EOF and
+                      (current().line <= failed_line or current().col > start.col) do
+                    pos = pos + 1
+                end
+                -- The "end" closing a broken loop belongs to it, not to a new statement
+                if current().type == TokenType.KEYWORD and current().value == "end" then
+                    pos = pos + 1
+                end
+            end
         end
     end
     
     return {
         type = "Program",
-        body = statements
+        body = statements,
+        errors = errors
     }
 end
 
 return parser
 "#;
         
-        let parser_module: Table = lua.load(parser_script).eval()?;
+        let single = |e: LuaError| vec![ParseError::from(e)];
+        let parser_module: Table = lua.load(parser_script).eval().map_err(single)?;
         
         let ast: Table = lua.scope(|_scope| {
             let parse_func: mlua::Function = parser_module.get("parse")?;
@@ -1332,13 +2080,15 @@ return parser
             let tokens: Table = lex_func.call(lua.create_string(source)?)?;
             let ast: Table = parse_func.call(tokens)?;
             Ok(ast)
-        })?;
+        }).map_err(single)?;
         
-        let program = self.convert_lua_ast_to_rust(&lua, &ast)?;
-        
-        self.lua_pool.return_instance(lua);
+        // Statements the parser recovered from are reported together
+        let errors: Vec<String> = ast.get("errors").map_err(single)?;
+        if !errors.is_empty() {
+            return Err(errors.iter().map(|e| ParseError::from_lua_message(e)).collect());
+        }
         
-        Ok(program)
+        self.convert_lua_ast_to_rust(lua, &ast).map_err(|e| vec![e])
     }
     
     fn convert_lua_ast_to_rust(&self, lua: &Lua, lua_ast: &Table) -> Result<Program, ParseError> {
@@ -1352,10 +2102,33 @@ return parser
                     let value: i64 = expr_table.get("value").map_err(|e| ParseError::lua_error(e.to_string()))?;
                     Ok(Expr::Number(value, span))
                 }
+                "Float" => {
+                    let value: f64 = expr_table.get("value").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    Ok(Expr::Float(value, span))
+                }
                 "String" => {
                     let value: String = expr_table.get("value").map_err(|e| ParseError::lua_error(e.to_string()))?;
                     Ok(Expr::String(value, span))
                 }
+                "FString" => {
+                    let parts_table: Table = expr_table.get("parts").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let parts_len: i64 = parts_table.len().map_err(|e: LuaError| ParseError::lua_error(e.to_string()))?;
+                    
+                    let mut parts = Vec::new();
+                    for i in 1..=parts_len {
+                        let part_table: Table = parts_table.get(i).map_err(|e| ParseError::lua_error(e.to_string()))?;
+                        let kind: String = part_table.get("kind").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                        if kind == "text" {
+                            let value: String = part_table.get("value").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                            parts.push(FStringPart::Literal(value));
+                        } else {
+                            let inner_table: Table = part_table.get("expr").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                            parts.push(FStringPart::Expr(convert_expr(lua, &inner_table, span)?));
+                        }
+                    }
+                    
+                    Ok(Expr::FString { parts, span })
+                }
                 "Boolean" => {
                     let value: bool = expr_table.get("value").map_err(|e| ParseError::lua_error(e.to_string()))?;
                     Ok(Expr::Boolean(value, span))
@@ -1412,6 +2185,85 @@ return parser
                         span,
                     })
                 }
+                "List" => {
+                    let elements_table: Table = expr_table.get("elements").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let elements_len: i64 = elements_table.len().map_err(|e: LuaError| ParseError::lua_error(e.to_string()))?;
+                    
+                    let mut elements = Vec::new();
+                    for i in 1..=elements_len {
+                        let element_table: Table = elements_table.get(i).map_err(|e| ParseError::lua_error(e.to_string()))?;
+                        elements.push(convert_expr(lua, &element_table, span)?);
+                    }
+                    
+                    Ok(Expr::List { elements, span })
+                }
+                "Dict" => {
+                    let keys_table: Table = expr_table.get("keys").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let values_table: Table = expr_table.get("values").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let entries_len: i64 = keys_table.len().map_err(|e: LuaError| ParseError::lua_error(e.to_string()))?;
+                    
+                    let mut entries = Vec::new();
+                    for i in 1..=entries_len {
+                        let key_table: Table = keys_table.get(i).map_err(|e| ParseError::lua_error(e.to_string()))?;
+                        let value_table: Table = values_table.get(i).map_err(|e| ParseError::lua_error(e.to_string()))?;
+                        entries.push((convert_expr(lua, &key_table, span)?, convert_expr(lua, &value_table, span)?));
+                    }
+                    
+                    Ok(Expr::Dict { entries, span })
+                }
+                "Index" => {
+                    let value_table: Table = expr_table.get("value").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let index_table: Table = expr_table.get("index").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    
+                    Ok(Expr::Index {
+                        value: Box::new(convert_expr(lua, &value_table, span)?),
+                        index: Box::new(convert_expr(lua, &index_table, span)?),
+                        span,
+                    })
+                }
+                "Slice" => {
+                    let value_table: Table = expr_table.get("value").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let bound = |name: &str| -> Result<Option<Box<Expr>>, ParseError> {
+                        let table: Option<Table> = expr_table.get(name).map_err(|e| ParseError::lua_error(e.to_string()))?;
+                        table.map(|table| convert_expr(lua, &table, span).map(Box::new)).transpose()
+                    };
+                    
+                    Ok(Expr::Slice {
+                        value: Box::new(convert_expr(lua, &value_table, span)?),
+                        start: bound("start")?,
+                        stop: bound("stop")?,
+                        span,
+                    })
+                }
+                "FieldAccess" => {
+                    let object_table: Table = expr_table.get("object").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let field: String = expr_table.get("field").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    
+                    Ok(Expr::FieldAccess {
+                        object: Box::new(convert_expr(lua, &object_table, span)?),
+                        field,
+                        span,
+                    })
+                }
+                "MethodCall" => {
+                    let receiver_table: Table = expr_table.get("receiver").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let method: String = expr_table.get("method").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let args_table: Table = expr_table.get("args").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let args_len: i64 = args_table.len().map_err(|e: LuaError| ParseError::lua_error(e.to_string()))?;
+                    
+                    let mut args = Vec::new();
+                    for i in 1..=args_len {
+                        let arg_table: Table = args_table.get(i).map_err(|e| ParseError::lua_error(e.to_string()))?;
+                        args.push(convert_argument(lua, &arg_table, span)?);
+                    }
+                    
+                    Ok(Expr::MethodCall {
+                        receiver: Box::new(convert_expr(lua, &receiver_table, span)?),
+                        method,
+                        args,
+                        span,
+                    })
+                }
                 "BoolOp" => {
                     let op_str: String = expr_table.get("op").map_err(|e| ParseError::lua_error(e.to_string()))?;
                     let op = match op_str.as_str() {
@@ -1448,6 +2300,8 @@ return parser
                             "<=" => CompareOp::Le,
                             ">" => CompareOp::Gt,
                             ">=" => CompareOp::Ge,
+                            "in" => CompareOp::In,
+                            "not in" => CompareOp::NotIn,
                             _ => return Err(ParseError::syntax_error(format!("Unknown comparison operator: {}", op_str), span)),
                         };
                         ops.push(op);
@@ -1499,13 +2353,22 @@ return parser
                         let mut args = Vec::new();
                         for i in 1..=args_len {
                             let arg_table: Table = args_table.get(i).map_err(|e| ParseError::lua_error(e.to_string()))?;
-                            args.push(convert_expr(lua, &arg_table, span)?);
+                            args.push(convert_argument(lua, &arg_table, span)?);
+                        }
+                        
+                        let mut kwargs = HashMap::new();
+                        let kwargs_table: Option<Table> = expr_table.get("kwargs").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                        if let Some(kwargs_table) = kwargs_table {
+                            for pair in kwargs_table.pairs::<String, Table>() {
+                                let (name, value_table) = pair.map_err(|e| ParseError::lua_error(e.to_string()))?;
+                                kwargs.insert(name, convert_expr(lua, &value_table, span)?);
+                            }
                         }
                         
                         Ok(Expr::Call {
                             func,
                             args,
-                            kwargs: HashMap::new(),
+                            kwargs,
                             span,
                         })
                     }
@@ -1514,9 +2377,23 @@ return parser
             }
         }
         
+        // A call argument knows where it starts; what is inside it keeps the statement's position
+        fn convert_argument(lua: &Lua, arg_table: &Table, span: Span) -> Result<Expr, ParseError> {
+            let arg = convert_expr(lua, arg_table, span)?;
+            Ok(match (arg_table.get::<usize>("line"), arg_table.get::<usize>("col")) {
+                (Ok(line), Ok(column)) => arg.with_span(Span::single(Position::new(line, column, 0))),
+                _ => arg,
+            })
+        }
+        
         fn convert_stmt(lua: &Lua, stmt_table: &Table, span: Span) -> Result<Statement, ParseError> {
             let stmt_type: String = stmt_table.get("type").map_err(|e| ParseError::lua_error(e.to_string()))?;
             
+            // Statements carry the position of their first token, which their expressions share
+            let line: usize = stmt_table.get("line").unwrap_or(span.start.line);
+            let column: usize = stmt_table.get("col").unwrap_or(span.start.column);
+            let span = Span::single(Position::new(line, column, 0));
+            
             match stmt_type.as_str() {
                 "VarDecl" => {
                     let name: String = stmt_table.get("name").map_err(|e| ParseError::lua_error(e.to_string()))?;
@@ -1532,6 +2409,12 @@ return parser
                         span,
                     })
                 }
+                "ConstDecl" => {
+                    let name: String = stmt_table.get("name").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let value_table: Table = stmt_table.get("value").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let value = convert_expr(lua, &value_table, span)?;
+                    Ok(Statement::ConstDecl { name, value, span })
+                }
                 "DeviceDecl" => {
                     let device: String = stmt_table.get("device").map_err(|e| ParseError::lua_error(e.to_string()))?;
                     
@@ -1588,6 +2471,35 @@ return parser
                         span,
                     })
                 }
+                "IndexAssign" => {
+                    let target_table: Table = stmt_table.get("target").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let index_table: Table = stmt_table.get("index").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let value_table: Table = stmt_table.get("value").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    
+                    Ok(Statement::IndexAssign {
+                        target: Box::new(convert_expr(lua, &target_table, span)?),
+                        index: Box::new(convert_expr(lua, &index_table, span)?),
+                        value: convert_expr(lua, &value_table, span)?,
+                        span,
+                    })
+                }
+                "FieldAssign" => {
+                    let target_table: Table = stmt_table.get("target").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let field: String = stmt_table.get("field").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let value_table: Table = stmt_table.get("value").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    
+                    Ok(Statement::FieldAssign {
+                        target: Box::new(convert_expr(lua, &target_table, span)?),
+                        field,
+                        value: convert_expr(lua, &value_table, span)?,
+                        span,
+                    })
+                }
+                "StructDef" => {
+                    let name: String = stmt_table.get("name").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let fields: Vec<String> = stmt_table.get("fields").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    Ok(Statement::StructDef { name, fields, span })
+                }
                 "Expr" => {
                     let expr_table: Table = stmt_table.get("expr").map_err(|e| ParseError::lua_error(e.to_string()))?;
                     let expr = convert_expr(lua, &expr_table, span)?;
@@ -1599,7 +2511,7 @@ return parser
                     } else {
                         None
                     };
-                    Ok(Statement::Return(expr))
+                    Ok(Statement::Return(expr, span))
                 }
                 "If" => {
                     let condition_table: Table = stmt_table.get("condition").map_err(|e| ParseError::lua_error(e.to_string()))?;
@@ -1680,6 +2592,22 @@ return parser
                         span,
                     })
                 }
+                "For" => {
+                    let var: String = stmt_table.get("var").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let iter_table: Table = stmt_table.get("iter").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let iter = convert_expr(lua, &iter_table, span)?;
+                    
+                    let body_table: Table = stmt_table.get("body").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let body_len: i64 = body_table.len().map_err(|e: LuaError| ParseError::lua_error(e.to_string()))?;
+                    
+                    let mut body = Vec::new();
+                    for i in 1..=body_len {
+                        let stmt_table: Table = body_table.get(i).map_err(|e| ParseError::lua_error(e.to_string()))?;
+                        body.push(convert_stmt(lua, &stmt_table, span)?);
+                    }
+                    
+                    Ok(Statement::For { var, iter, body, span })
+                }
                 "FunctionDef" => {
                     let name: String = stmt_table.get("name").map_err(|e| ParseError::lua_error(e.to_string()))?;
                     
@@ -1691,6 +2619,9 @@ return parser
                         let arg: String = args_table.get(i).map_err(|e| ParseError::lua_error(e.to_string()))?;
                         args.push(arg);
                     }
+                    let types_table: Table = stmt_table.get("arg_types").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let arg_types = (1..=args_len).map(|i| types_table.get(i).ok()).collect();
+                    let return_type: Option<String> = stmt_table.get("return_type").ok();
                     
                     let body_table: Table = stmt_table.get("body").map_err(|e| ParseError::lua_error(e.to_string()))?;
                     let body_len: i64 = body_table.len().map_err(|e: LuaError| ParseError::lua_error(e.to_string()))?;
@@ -1701,11 +2632,16 @@ return parser
                         body.push(convert_stmt(lua, &stmt_table, span)?);
                     }
                     
+                    let line: usize = stmt_table.get("line").unwrap_or(span.start.line);
+                    let column: usize = stmt_table.get("col").unwrap_or(span.start.column);
+                    
                     Ok(Statement::FunctionDef {
                         name,
                         args,
+                        arg_types,
+                        return_type,
                         body,
-                        span,
+                        span: Span::single(Position::new(line, column, 0)),
                     })
                 }
                 "HardwareFunctionDef" => {
@@ -1748,6 +2684,33 @@ return parser
                         span,
                     })
                 }
+                "Import" => {
+                    let module: String = stmt_table.get("module").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    
+                    let items_table: Option<Table> = stmt_table.get("items").map_err(|e| ParseError::lua_error(e.to_string()))?;
+                    let items = match items_table {
+                        Some(items_table) => {
+                            let items_len: i64 = items_table.len().map_err(|e: LuaError| ParseError::lua_error(e.to_string()))?;
+                            let mut items = Vec::new();
+                            for i in 1..=items_len {
+                                let item: String = items_table.get(i).map_err(|e| ParseError::lua_error(e.to_string()))?;
+                                items.push(item);
+                            }
+                            Some(items)
+                        }
+                        None => None,
+                    };
+                    
+                    // Keep the import line so unknown modules can be reported precisely
+                    let line: usize = stmt_table.get("line").unwrap_or(span.start.line);
+                    let column: usize = stmt_table.get("col").unwrap_or(span.start.column);
+                    
+                    Ok(Statement::Import {
+                        module,
+                        items,
+                        span: Span::single(Position::new(line, column, 0)),
+                    })
+                }
                 "Section" => {
                     // Convert section statement to a pass statement for now
                     // This will be handled by the backend
@@ -1913,10 +2876,11 @@ impl IncludeProcessor {
                             Err(err) => return Err(err),
                         }
                     }
-                    Err(err) => {
+                    Err(errors) => {
+                        let reasons: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
                         return Err(ParseError::include_error(
                             filename,
-                            format!("Failed to parse included file: {}", err),
+                            format!("Failed to parse included file: {}", reasons.join("; ")),
                             *span
                         ));
                     }
@@ -1932,11 +2896,640 @@ impl IncludeProcessor {
     }
 }
 
+/// A document kept parsed across edits, for editors that reparse on every
+/// keystroke. Top-level statements own the lines from their first one up to the
+/// next statement; an edit within one statement's lines reparses just those and
+/// moves the statements after it. Anything else, or a fragment that does not
+/// come back as exactly one statement, reparses the whole document
+pub struct ParserSession {
+    text: String,
+    program: Option<Program>,
+    /// First line of each top-level statement, when they allow incremental edits
+    lines: Option<Vec<usize>>,
+    full_parses: usize,
+}
+
+impl ParserSession {
+    pub fn open(frontend: &LuaFrontend, text: &str) -> (Self, Result<(), Vec<ParseError>>) {
+        let mut session = Self {
+            text: text.to_string(),
+            program: None,
+            lines: None,
+            full_parses: 0,
+        };
+        let result = session.reparse(frontend).map(|_| ());
+        (session, result)
+    }
+    
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    
+    /// The program as of the last edit that parsed
+    pub fn program(&self) -> Option<&Program> {
+        self.program.as_ref()
+    }
+    
+    /// How many times the whole document was parsed
+    pub fn full_parses(&self) -> usize {
+        self.full_parses
+    }
+    
+    /// Replace the bytes in `range` with `replacement`, returning the indices of
+    /// the top-level statements that changed
+    pub fn edit(&mut self, frontend: &LuaFrontend, range: std::ops::Range<usize>, replacement: &str) -> Result<Vec<usize>, Vec<ParseError>> {
+        if range.start > range.end || !self.text.is_char_boundary(range.start) || !self.text.is_char_boundary(range.end) {
+            return Err(vec![ParseError::lua_error(format!("Edit {:?} is outside the document", range))]);
+        }
+        let line_of = |offset: usize| self.text[..offset].matches('\n').count() + 1;
+        let (first_line, last_line) = (line_of(range.start), line_of(range.end));
+        let delta = replacement.matches('\n').count() as isize - self.text[range.clone()].matches('\n').count() as isize;
+        self.text.replace_range(range, replacement);
+        
+        if let Some(index) = self.statement_owning(first_line, last_line) {
+            if let Some(changed) = self.reparse_statement(frontend, index, delta) {
+                return Ok(changed);
+            }
+        }
+        self.reparse(frontend)
+    }
+    
+    /// The statement whose lines hold both `first_line` and `last_line`
+    fn statement_owning(&self, first_line: usize, last_line: usize) -> Option<usize> {
+        let lines = self.lines.as_ref()?;
+        let index = lines.iter().rposition(|line| *line <= first_line)?;
+        match lines.get(index + 1) {
+            Some(next) if last_line >= *next => None,
+            _ => Some(index),
+        }
+    }
+    
+    fn reparse_statement(&mut self, frontend: &LuaFrontend, index: usize, delta: isize) -> Option<Vec<usize>> {
+        let lines = self.lines.as_mut()?;
+        let program = self.program.as_mut()?;
+        let first = lines[index];
+        let next = lines.get(index + 1).map(|line| line.checked_add_signed(delta).unwrap_or(0));
+        let fragment: String = self.text.split_inclusive('\n')
+            .skip(first - 1)
+            .take(next.map_or(usize::MAX, |next| next.saturating_sub(first)))
+            .collect();
+        let mut parsed = frontend.parse_program(&fragment).ok()?;
+        let [statement] = parsed.body.as_mut_slice() else { return None };
+        let line = statement_line(statement)?;
+        shift_statement_lines(statement, first as isize - 1);
+        
+        program.body[index] = statement.clone();
+        lines[index] = first + line - 1;
+        if delta != 0 {
+            for (line, statement) in lines.iter_mut().zip(program.body.iter_mut()).skip(index + 1) {
+                *line = line.checked_add_signed(delta)?;
+                shift_statement_lines(statement, delta);
+            }
+        }
+        Some(vec![index])
+    }
+    
+    fn reparse(&mut self, frontend: &LuaFrontend) -> Result<Vec<usize>, Vec<ParseError>> {
+        self.full_parses += 1;
+        self.lines = None;
+        let program = match frontend.parse_program(&self.text) {
+            Ok(program) => program,
+            Err(errors) => {
+                self.program = None;
+                return Err(errors);
+            }
+        };
+        
+        let lines: Option<Vec<usize>> = program.body.iter().map(statement_line).collect();
+        self.lines = lines.filter(|lines| lines.windows(2).all(|pair| pair[0] < pair[1]));
+        let previous = self.program.as_ref().map_or(&[][..], |old| old.body.as_slice());
+        let changed = (0..program.body.len())
+            .filter(|index| previous.get(*index).is_none_or(|old| !same_statement(old, &program.body[*index])))
+            .collect();
+        self.program = Some(program);
+        Ok(changed)
+    }
+}
+
+/// Line a top-level statement starts on; `pass`, `break` and `continue` carry none
+fn statement_line(statement: &Statement) -> Option<usize> {
+    Some(statement.span().start.line).filter(|line| *line > 0)
+}
+
+fn same_statement(a: &Statement, b: &Statement) -> bool {
+    // Through JSON, whose maps are ordered, since keyword arguments are a HashMap
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+/// Move every span in `statement` down by `delta` lines. The parser records
+/// lines and columns only, so offsets stay as they are
+fn shift_statement_lines(statement: &mut Statement, delta: isize) {
+    let mut shift = |span: &mut Span| {
+        span.start.line = span.start.line.saturating_add_signed(delta);
+        span.end.line = span.end.line.saturating_add_signed(delta);
+    };
+    visit_statement_spans(statement, &mut shift);
+}
+
+pub(crate) fn visit_statement_spans(statement: &mut Statement, visit: &mut dyn FnMut(&mut Span)) {
+    let block = |statements: &mut Vec<Statement>, visit: &mut dyn FnMut(&mut Span)| {
+        statements.iter_mut().for_each(|statement| visit_statement_spans(statement, visit));
+    };
+    match statement {
+        Statement::VarDecl { value, span, .. }
+        | Statement::ConstDecl { value, span, .. }
+        | Statement::Assign { value, span, .. }
+        | Statement::AugAssign { value, span, .. } => {
+            visit_expression_spans(value, visit);
+            visit(span);
+        }
+        Statement::IndexAssign { target, index, value, span } => {
+            visit_expression_spans(target, visit);
+            visit_expression_spans(index, visit);
+            visit_expression_spans(value, visit);
+            visit(span);
+        }
+        Statement::FieldAssign { target, value, span, .. } => {
+            visit_expression_spans(target, visit);
+            visit_expression_spans(value, visit);
+            visit(span);
+        }
+        Statement::Expr(expr) => visit_expression_spans(expr, visit),
+        Statement::Return(value, span) => {
+            if let Some(value) = value {
+                visit_expression_spans(value, visit);
+            }
+            visit(span);
+        }
+        Statement::If { condition, then_block, elif_blocks, else_block, span } => {
+            visit_expression_spans(condition, visit);
+            block(then_block, visit);
+            for (condition, body) in elif_blocks {
+                visit_expression_spans(condition, visit);
+                block(body, visit);
+            }
+            if let Some(body) = else_block {
+                block(body, visit);
+            }
+            visit(span);
+        }
+        Statement::While { condition, body, orelse, span } => {
+            visit_expression_spans(condition, visit);
+            block(body, visit);
+            if let Some(body) = orelse {
+                block(body, visit);
+            }
+            visit(span);
+        }
+        Statement::For { iter, body, span, .. } => {
+            visit_expression_spans(iter, visit);
+            block(body, visit);
+            visit(span);
+        }
+        Statement::FunctionDef { body, span, .. } | Statement::HardwareFunctionDef { body, span, .. } => {
+            block(body, visit);
+            visit(span);
+        }
+        Statement::HardwareDecl { config, span, .. } => {
+            config.values_mut().for_each(|value| visit_expression_spans(value, visit));
+            visit(span);
+        }
+        Statement::StructDef { span, .. } | Statement::Include { span, .. } | Statement::Import { span, .. } => visit(span),
+        Statement::Pass | Statement::Break | Statement::Continue => {}
+    }
+}
+
+pub(crate) fn visit_expression_spans(expr: &mut Expr, visit: &mut dyn FnMut(&mut Span)) {
+    match expr {
+        Expr::Number(_, span)
+        | Expr::Float(_, span)
+        | Expr::Boolean(_, span)
+        | Expr::String(_, span)
+        | Expr::Var(_, span)
+        | Expr::None(span) => visit(span),
+        Expr::BinOp { left, right, span, .. } => {
+            visit_expression_spans(left, visit);
+            visit_expression_spans(right, visit);
+            visit(span);
+        }
+        Expr::UnaryOp { operand, span, .. } => {
+            visit_expression_spans(operand, visit);
+            visit(span);
+        }
+        Expr::Call { args, kwargs, span, .. } => {
+            args.iter_mut().for_each(|arg| visit_expression_spans(arg, visit));
+            kwargs.values_mut().for_each(|value| visit_expression_spans(value, visit));
+            visit(span);
+        }
+        Expr::BoolOp { values: items, span, .. }
+        | Expr::HardwareCall { args: items, span, .. }
+        | Expr::List { elements: items, span } => {
+            items.iter_mut().for_each(|item| visit_expression_spans(item, visit));
+            visit(span);
+        }
+        Expr::Compare { left, comparators, span, .. } => {
+            visit_expression_spans(left, visit);
+            comparators.iter_mut().for_each(|item| visit_expression_spans(item, visit));
+            visit(span);
+        }
+        Expr::FString { parts, span } => {
+            for part in parts {
+                if let FStringPart::Expr(expr) = part {
+                    visit_expression_spans(expr, visit);
+                }
+            }
+            visit(span);
+        }
+        Expr::Dict { entries, span } => {
+            for (key, value) in entries {
+                visit_expression_spans(key, visit);
+                visit_expression_spans(value, visit);
+            }
+            visit(span);
+        }
+        Expr::Index { value, index, span } => {
+            visit_expression_spans(value, visit);
+            visit_expression_spans(index, visit);
+            visit(span);
+        }
+        Expr::Slice { value, start, stop, span } => {
+            visit_expression_spans(value, visit);
+            for bound in [start, stop].into_iter().flatten() {
+                visit_expression_spans(bound, visit);
+            }
+            visit(span);
+        }
+        Expr::FieldAccess { object, span, .. } => {
+            visit_expression_spans(object, visit);
+            visit(span);
+        }
+        Expr::MethodCall { receiver, args, span, .. } => {
+            visit_expression_spans(receiver, visit);
+            args.iter_mut().for_each(|arg| visit_expression_spans(arg, visit));
+            visit(span);
+        }
+    }
+}
+
+// The formatter prints programs back to canonical source: four-space indents,
+// braced `if` and `def` bodies, loops closed with `end`, and single spaces
+// around operators. Compound operands are parenthesised, so parsing the text
+// gives back the same tree. Comments are not in the tree; `format_source`
+// puts them back before the statement that followed them
+
+fn quote(text: &str, prefix: &str) -> String {
+    let mut quoted = format!("{}\"", prefix);
+    for c in text.chars() {
+        match c {
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            '\0' => quoted.push_str("\\0"),
+            '\\' | '"' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            // Other control characters, such as ESC, would land raw in the file
+            c if c.is_ascii_control() => quoted.push_str(&format!("\\x{:02x}", c as u32)),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn join(exprs: &[Expr]) -> String {
+    exprs.iter().map(|expr| expr.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+/// How tightly `expr` binds, following the parser: `or`, `and`, comparisons,
+/// `+ -`, then `* / %` with the bitwise operators, unary operators and atoms
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::BoolOp { op: BoolOp::Or, .. } => 1,
+        Expr::BoolOp { op: BoolOp::And, .. } => 2,
+        Expr::Compare { .. } => 3,
+        Expr::BinOp { op: Op::Add | Op::Sub, .. } => 4,
+        Expr::BinOp { .. } => 5,
+        Expr::UnaryOp { .. } => 6,
+        _ => 7,
+    }
+}
+
+/// `expr` as an operand that must bind at least as tightly as `min`
+fn operand(expr: &Expr, min: u8) -> String {
+    if precedence(expr) < min {
+        format!("({})", expr)
+    } else {
+        expr.to_string()
+    }
+}
+
+pub(crate) fn op_text(op: &Op) -> &'static str {
+    match op {
+        Op::Add => "+",
+        Op::Sub => "-",
+        Op::Mul => "*",
+        Op::Div => "/",
+        Op::Mod => "%",
+        Op::Pow => "**",
+        Op::FloorDiv => "//",
+        Op::BitAnd => "&",
+        Op::BitOr => "|",
+        Op::BitXor => "^",
+    }
+}
+
+pub(crate) fn compare_text(op: &CompareOp) -> &'static str {
+    match op {
+        CompareOp::Eq => "==",
+        CompareOp::Ne => "!=",
+        CompareOp::Lt => "<",
+        CompareOp::Le => "<=",
+        CompareOp::Gt => ">",
+        CompareOp::Ge => ">=",
+        CompareOp::In => "in",
+        CompareOp::NotIn => "not in",
+        CompareOp::Is => "is",
+        CompareOp::IsNot => "is not",
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Number(value, _) => write!(f, "{}", value),
+            Expr::Float(value, _) => write!(f, "{:?}", value),
+            Expr::Boolean(value, _) => write!(f, "{}", if *value { "True" } else { "False" }),
+            Expr::String(text, _) => write!(f, "{}", quote(text, "")),
+            Expr::Var(name, _) => write!(f, "{}", name),
+            Expr::None(_) => write!(f, "None"),
+            Expr::BinOp { left, op, right, .. } => {
+                // Left-associative, so only the right operand needs parentheses at the same level
+                let level = precedence(self);
+                write!(f, "{} {} {}", operand(left, level), op_text(op), operand(right, level + 1))
+            }
+            Expr::UnaryOp { op, operand: value, .. } => {
+                let op = match op {
+                    UnaryOp::Not => "not ",
+                    UnaryOp::Plus => "+",
+                    UnaryOp::Minus => "-",
+                    UnaryOp::Invert => "~",
+                };
+                let value = operand(value, 6);
+                if op == "-" && value.starts_with('-') {
+                    // `--` would start a comment
+                    write!(f, "-({})", value)
+                } else {
+                    write!(f, "{}{}", op, value)
+                }
+            }
+            Expr::BoolOp { op, values, .. } => {
+                let op = if *op == BoolOp::And { " and " } else { " or " };
+                let level = precedence(self);
+                let values: Vec<String> = values.iter().enumerate()
+                    .map(|(at, value)| operand(value, if at == 0 { level } else { level + 1 }))
+                    .collect();
+                write!(f, "{}", values.join(op))
+            }
+            Expr::Compare { left, ops, comparators, .. } => {
+                write!(f, "{}", operand(left, 4))?;
+                for (op, right) in ops.iter().zip(comparators) {
+                    write!(f, " {} {}", compare_text(op), operand(right, 4))?;
+                }
+                Ok(())
+            }
+            Expr::Call { func, args, kwargs, .. } => {
+                let mut kwargs: Vec<_> = kwargs.iter().collect();
+                kwargs.sort_by(|a, b| a.0.cmp(b.0));
+                let mut all: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                all.extend(kwargs.into_iter().map(|(name, value)| format!("{}={}", name, value)));
+                write!(f, "{}({})", func, all.join(", "))
+            }
+            Expr::HardwareCall { device, func, args, .. } => write!(f, "{}.{}({})", device, func, join(args)),
+            Expr::FString { parts, .. } => {
+                let mut text = String::new();
+                for part in parts {
+                    match part {
+                        FStringPart::Literal(literal) => text.push_str(&literal.replace('{', "{{").replace('}', "}}")),
+                        FStringPart::Expr(expr) => text.push_str(&format!("{{{}}}", expr)),
+                    }
+                }
+                write!(f, "{}", quote(&text, "f"))
+            }
+            Expr::List { elements, .. } => write!(f, "[{}]", join(elements)),
+            Expr::Dict { entries, .. } => {
+                let entries: Vec<String> = entries.iter().map(|(key, value)| format!("{}: {}", key, value)).collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+            Expr::Index { value, index, .. } => write!(f, "{}[{}]", operand(value, 7), index),
+            Expr::Slice { value, start, stop, .. } => {
+                let bound = |bound: &Option<Box<Expr>>| bound.as_ref().map_or(String::new(), |bound| bound.to_string());
+                write!(f, "{}[{}:{}]", operand(value, 7), bound(start), bound(stop))
+            }
+            Expr::FieldAccess { object, field, .. } => write!(f, "{}.{}", operand(object, 7), field),
+            Expr::MethodCall { receiver, method, args, .. } => write!(f, "{}.{}({})", operand(receiver, 7), method, join(args)),
+        }
+    }
+}
+
+/// Comments found in source, by line, for `SourceWriter` to put back
+struct Comment {
+    line: usize,
+    text: String,
+    /// Whether code precedes it on its line
+    trailing: bool,
+}
+
+/// Every `#`, `//` and `--` comment in `source`, as the lexer finds them
+fn collect_comments(source: &str) -> Vec<Comment> {
+    let mut comments = Vec::new();
+    for (number, text) in source.lines().enumerate() {
+        let mut quote = None;
+        let mut escape = false;
+        for (at, c) in text.char_indices() {
+            match quote {
+                Some(_) if escape => escape = false,
+                Some(_) if c == '\\' => escape = true,
+                Some(open) if c == open => quote = None,
+                Some(_) => {}
+                None if c == '"' || c == '\'' => quote = Some(c),
+                None if c == '#' || text[at..].starts_with("//") || text[at..].starts_with("--") => {
+                    comments.push(Comment {
+                        line: number + 1,
+                        text: text[at..].trim_end().to_string(),
+                        trailing: !text[..at].trim().is_empty(),
+                    });
+                    break;
+                }
+                None => {}
+            }
+        }
+    }
+    comments
+}
+
+struct SourceWriter {
+    out: String,
+    /// Comments not yet written, in source order
+    comments: std::collections::VecDeque<Comment>,
+}
+
+impl SourceWriter {
+    fn new(comments: Vec<Comment>) -> Self {
+        Self { out: String::new(), comments: comments.into() }
+    }
+    
+    /// Write one line of output for source line `line`, preceded by the comments
+    /// above it and followed by a comment that shared its line. Line 0 is unknown
+    fn line(&mut self, indent: usize, line: usize, text: &str) {
+        let pad = "    ".repeat(indent);
+        while line > 0 && self.comments.front().is_some_and(|comment| comment.line < line) {
+            let comment = self.comments.pop_front().unwrap();
+            self.out.push_str(&format!("{}{}\n", pad, comment.text));
+        }
+        self.out.push_str(&pad);
+        self.out.push_str(text);
+        if let Some(comment) = self.comments.front().filter(|comment| line > 0 && comment.line == line && comment.trailing) {
+            self.out.push_str(&format!("  {}", comment.text));
+            self.comments.pop_front();
+        }
+        self.out.push('\n');
+    }
+    
+    fn block(&mut self, body: &[Statement], indent: usize) {
+        for statement in body {
+            self.statement(statement, indent + 1);
+        }
+    }
+    
+    /// A braced body opened on the line before
+    fn braced(&mut self, body: &[Statement], indent: usize) {
+        self.block(body, indent);
+        self.line(indent, 0, "}");
+    }
+    
+    fn statement(&mut self, statement: &Statement, indent: usize) {
+        let line = statement.span().start.line;
+        match statement {
+            Statement::VarDecl { name, value, type_hint: Some(type_hint), .. } => self.line(indent, line, &format!("var {}: {} = {}", name, type_hint, value)),
+            Statement::VarDecl { name, value, type_hint: None, .. } => self.line(indent, line, &format!("var {} = {}", name, value)),
+            Statement::ConstDecl { name, value, .. } => self.line(indent, line, &format!("const {} = {}", name, value)),
+            Statement::Assign { target, value, .. } => self.line(indent, line, &format!("{} = {}", target, value)),
+            Statement::AugAssign { target, op, value, .. } => self.line(indent, line, &format!("{} {}= {}", target, op_text(op), value)),
+            Statement::IndexAssign { target, index, value, .. } => self.line(indent, line, &format!("{}[{}] = {}", operand(target, 7), index, value)),
+            Statement::FieldAssign { target, field, value, .. } => self.line(indent, line, &format!("{}.{} = {}", operand(target, 7), field, value)),
+            Statement::StructDef { name, fields, .. } => self.line(indent, line, &format!("struct {}: {}", name, fields.join(", "))),
+            Statement::Expr(expr) => self.line(indent, line, &expr.to_string()),
+            Statement::Return(Some(value), _) => self.line(indent, line, &format!("return {}", value)),
+            Statement::Return(None, _) => self.line(indent, line, "return"),
+            Statement::If { condition, then_block, elif_blocks, else_block, .. } => {
+                self.line(indent, line, &format!("if {}: {{", condition));
+                self.braced(then_block, indent);
+                for (condition, body) in elif_blocks {
+                    self.line(indent, 0, &format!("elif {}: {{", condition));
+                    self.braced(body, indent);
+                }
+                if let Some(body) = else_block {
+                    self.line(indent, 0, "else: {");
+                    self.braced(body, indent);
+                }
+            }
+            Statement::While { condition, body, .. } => {
+                self.line(indent, line, &format!("while {}:", condition));
+                self.block(body, indent);
+                self.line(indent, 0, "end");
+            }
+            Statement::For { var, iter, body, .. } => {
+                self.line(indent, line, &format!("for {} in {}:", var, iter));
+                self.block(body, indent);
+                self.line(indent, 0, "end");
+            }
+            Statement::FunctionDef { name, args, arg_types, return_type, body, .. } => {
+                let args: Vec<String> = args.iter().zip(arg_types).map(|(arg, hint)| match hint {
+                    Some(hint) => format!("{}: {}", arg, hint),
+                    None => arg.clone(),
+                }).collect();
+                let returns = return_type.as_ref().map_or_else(String::new, |hint| format!(" -> {}", hint));
+                self.line(indent, line, &format!("def {}({}){}: {{", name, args.join(", "), returns));
+                self.braced(body, indent);
+            }
+            Statement::HardwareFunctionDef { device, name, args, body, .. } => {
+                self.line(indent, line, &format!("@{}.def {}({}): {{", device, name, args.join(", ")));
+                self.braced(body, indent);
+            }
+            Statement::Pass => self.line(indent, line, "pass"),
+            Statement::Break => self.line(indent, line, "break"),
+            Statement::Continue => self.line(indent, line, "continue"),
+            Statement::Include { filename, .. } => self.line(indent, line, &format!("include {}", quote(filename, ""))),
+            Statement::Import { module, items: None, .. } => self.line(indent, line, &format!("import {}", module)),
+            Statement::Import { module, items: Some(items), .. } => self.line(indent, line, &format!("from {} import {}", module, items.join(", "))),
+            Statement::HardwareDecl { device, config, .. } => {
+                let mut config: Vec<_> = config.iter().collect();
+                config.sort_by(|a, b| a.0.cmp(b.0));
+                let config: Vec<String> = config.into_iter().map(|(key, value)| format!("{}: {}", key, value)).collect();
+                self.line(indent, line, &format!("device {} = {{{}}}", device, config.join(", ")));
+            }
+        }
+    }
+    
+    /// The output, with comments after the last statement at the end
+    fn finish(mut self) -> String {
+        for comment in self.comments.drain(..) {
+            self.out.push_str(&comment.text);
+            self.out.push('\n');
+        }
+        self.out
+    }
+}
+
+/// `program` as canonical source
+pub fn format_program(program: &Program) -> String {
+    let mut writer = SourceWriter::new(Vec::new());
+    program.body.iter().for_each(|statement| writer.statement(statement, 0));
+    writer.finish()
+}
+
+/// Reformat `source`, keeping its comments
+pub fn format_source(source: &str) -> Result<String, Vec<ParseError>> {
+    let program = parse_program(source)?;
+    let mut writer = SourceWriter::new(collect_comments(source));
+    program.body.iter().for_each(|statement| writer.statement(statement, 0));
+    Ok(writer.finish())
+}
+
+impl std::fmt::Display for Statement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut writer = SourceWriter::new(Vec::new());
+        writer.statement(self, 0);
+        write!(f, "{}", writer.finish())
+    }
+}
+
+impl std::fmt::Display for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", format_program(self))
+    }
+}
+
 pub fn parse_program(source: &str) -> Result<Program, Vec<ParseError>> {
-    let frontend = LuaFrontend::new();
-    match frontend.parse_program(source) {
-        Ok(program) => Ok(program),
-        Err(err) => Err(vec![err]),
+    LuaFrontend::new().parse_program(source)
+}
+
+/// Parse source that may not be text, as fuzzers and editors hand over.
+/// Bytes that are not UTF-8 are a syntax error at the first invalid one
+pub fn parse_bytes(bytes: &[u8]) -> Result<Program, Vec<ParseError>> {
+    match std::str::from_utf8(bytes) {
+        Ok(source) => parse_program(source),
+        Err(error) => {
+            let valid = &bytes[..error.valid_up_to()];
+            let line = valid.iter().filter(|&&b| b == b'\n').count() + 1;
+            let line_start = valid.iter().rposition(|&b| b == b'\n').map_or(0, |newline| newline + 1);
+            // Everything before the invalid byte is UTF-8, so columns count characters
+            let column = String::from_utf8_lossy(&valid[line_start..]).chars().count() + 1;
+            let span = Span::single(Position::new(line, column, error.valid_up_to()));
+            Err(vec![ParseError::syntax_error("source is not valid UTF-8", span)])
+        }
     }
 }
 
@@ -1985,4 +3578,192 @@ pub fn format_parse_errors(errors: &[ParseError], source: &str) -> String {
     }
     
     output
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn as_json(program: &Program) -> serde_json::Value {
+        serde_json::to_value(&program.body).unwrap()
+    }
+
+    /// Edit the open document and `text` alike, checking the session still
+    /// agrees with a parse from scratch
+    fn edit(frontend: &LuaFrontend, text: &mut String, range: std::ops::Range<usize>, replacement: &str) -> Vec<usize> {
+        let changed = frontend.edit_document("main.eg", range.clone(), replacement).unwrap();
+        text.replace_range(range, replacement);
+        let expected = frontend.parse_program(text).unwrap();
+        assert_eq!(as_json(&frontend.document_program("main.eg").unwrap()), as_json(&expected), "after editing to {:?}", text);
+        changed
+    }
+
+    #[test]
+    fn test_session_edits_match_full_parse() {
+        let frontend = LuaFrontend::new();
+        let mut text = "var x = 1\ndef add(a, b): {\n    var c = a + b\n    return c\n}\nprint(add(x, 2))\n".to_string();
+        frontend.open_document("main.eg", &text).unwrap();
+
+        // Edits inside one statement reparse just that statement
+        let body = text.find("a + b").unwrap();
+        assert_eq!(edit(&frontend, &mut text, body + 4..body + 5, "x"), vec![1]);
+        assert_eq!(edit(&frontend, &mut text, body + 4..body + 5, "b * 2\n    c = c + 1"), vec![1]);
+        assert_eq!(edit(&frontend, &mut text, 8..9, "7"), vec![0]);
+        let call = text.find("x, 2").unwrap();
+        assert_eq!(edit(&frontend, &mut text, call + 3..call + 4, "3"), vec![2]);
+        let session = frontend.lua_pool.take_session("main.eg").unwrap();
+        assert_eq!(session.full_parses(), 1);
+        frontend.lua_pool.store_session("main.eg", session);
+
+        // A fragment that parses to two statements falls back to the whole document
+        assert_eq!(edit(&frontend, &mut text, 0..0, "var y = 2\n"), vec![0, 1, 2, 3]);
+        let end = text.len();
+        assert_eq!(edit(&frontend, &mut text, end..end, "print(y)\n"), vec![4]);
+        assert!(frontend.edit_document("main.eg", 0..end + 100, "").is_err());
+        assert!(frontend.close_document("main.eg"));
+        assert!(frontend.edit_document("main.eg", 0..0, "").is_err());
+    }
+
+    fn without_spans(program: &Program) -> serde_json::Value {
+        let mut body = program.body.clone();
+        for statement in &mut body {
+            visit_statement_spans(statement, &mut |span| *span = Span::single(Position::start()));
+        }
+        serde_json::to_value(&body).unwrap()
+    }
+
+    /// Formatting never changes what a program means: every example and a
+    /// program using each construct parse to the same tree before and after,
+    /// and formatting again changes nothing
+    #[test]
+    fn test_format_round_trips() {
+        let constructs = "import math\nfrom string import upper, lower\ninclude \"lib.eg\"\n\
+            var s:str='a\\tb \\\"q\\\" \\x41'   # trailing\nvar xs = [1, 2.5, None, True, {\"k\": -3}]\n\
+            // leading\ndef f(a,b): {\n  if a<b and not (a == 0 or b != 1): return a * 2\n  elif a in xs: pass\n  else: { a -= 1\n b = ~a % 3 & 4 | 5 ^ 6 }\n  return\n}\n\
+            @vga.def draw(x): { xs[x] = x * (x - 1) / 2 }\n\
+            for i in range(1, 10, 2):\n  if i >= 5: break\n  continue\nend\nwhile s not in xs: { s += f\"{{{s}}} {xs[0]}\" }\n\
+            struct P:x ,y\nxs[0].x += P(1, 2).y.z[0]\n\
+            print(f(1, xs[f(2, 3)][0]), -(1 + 2) <= +4, s[1 :], \"ab\"[:-1][0], s . upper().split(\",\" )[0])\n-- the end\n";
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        let mut sources = vec![("constructs".to_string(), constructs.to_string())];
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|extension| extension == "eg") {
+                sources.push((path.display().to_string(), std::fs::read_to_string(&path).unwrap()));
+            }
+        }
+        assert!(sources.len() > 1);
+
+        for (name, source) in sources {
+            let formatted = format_source(&source).unwrap();
+            let reparsed = parse_program(&formatted).unwrap_or_else(|e| panic!("{}: {:?}\n{}", name, e, formatted));
+            assert_eq!(without_spans(&reparsed), without_spans(&parse_program(&source).unwrap()), "{}:\n{}", name, formatted);
+            assert_eq!(format_source(&formatted).unwrap(), formatted, "{}", name);
+            assert_eq!(format_program(&reparsed), format_program(&parse_program(&source).unwrap()), "{}", name);
+        }
+
+        let formatted = format_source(constructs).unwrap();
+        assert!(formatted.starts_with("import math\nfrom string import upper, lower\ninclude \"lib.eg\"\nvar s: str = \"a\\tb \\\"q\\\" A\"  # trailing\n"), "{}", formatted);
+        assert!(formatted.contains("// leading\ndef f(a, b): {\n    if a < b and not (a == 0 or b != 1): {\n        return a * 2\n    }\n"), "{}", formatted);
+        assert!(formatted.contains("for i in range(1, 10, 2):\n    if i >= 5: {\n        break\n    }\n    continue\nend\n"), "{}", formatted);
+        assert!(formatted.contains("\nstruct P: x, y\nxs[0].x = xs[0].x + P(1, 2).y.z[0]\n"), "{}", formatted);
+        assert!(formatted.contains(", s[1:], \"ab\"[:-1][0], s.upper().split(\",\")[0])\n"), "{}", formatted);
+        assert!(formatted.ends_with("-- the end\n"), "{}", formatted);
+    }
+
+    #[test]
+    fn test_format_escapes_control_characters() {
+        let source = "var a = \"\\x1b[31mred\\x1b[0m\"\nvar b = \"\\x7f\\x01\\x1f\"\nprint(f\"\\x1b{a}\\x7f\", b)\n";
+        let formatted = format_source(source).unwrap();
+        assert!(formatted.bytes().all(|byte| byte == b'\n' || !byte.is_ascii_control()), "{:?}", formatted);
+        assert!(formatted.contains("\"\\x1b[31mred\\x1b[0m\"") && formatted.contains("\"\\x7f\\x01\\x1f\""), "{}", formatted);
+        assert_eq!(without_spans(&parse_program(&formatted).unwrap()), without_spans(&parse_program(source).unwrap()));
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+    }
+
+
+    /// Pieces random sources are made of, weighted toward the ones that
+    /// open or close something
+    const TOKENS: &[&str] = &[
+        "var", "const", "struct", "def", "if", "elif", "else", "while", "for", "in", "end", "return", "import", "from", "include",
+        "pass", "break", "and", "not", "True", "None", "@vga", ".", "x", "print", "range",
+        "0", "42", "99999999999999999999", "1.5", "0x", "\"s\"", "\"", "'", "f\"", "f\"{", "f\"{x}\"", "}\"", "{", "}",
+        "(", ")", "[", "]", ":", ",", "=", "+=", "-", "->", "*", "/", "**", "<", "==", "~", "&", "#", "--", "\\",
+        " ", "\t", "\n", "\r", "\0", "\u{e9}", "\u{1F600}",
+    ];
+
+    fn check_never_panics(source: &[u8]) {
+        let text = String::from_utf8_lossy(source);
+        match parse_bytes(source) {
+            Ok(mut program) => {
+                let _ = format_source(&text);
+                let _ = crate::analysis::analyze(&program, &text);
+                let _ = crate::typecheck::check_types(&mut program, true);
+            }
+            Err(errors) => {
+                assert!(!errors.is_empty());
+                errors.iter().for_each(|error| { error.render("fuzz.eg", &text); });
+            }
+        }
+    }
+
+    proptest::proptest! {
+        /// Whatever the input, parsing and reporting the result return rather than panic
+        #[test]
+        fn test_parser_never_panics(
+            tokens in proptest::collection::vec(proptest::sample::select(TOKENS), 0..40),
+            bytes in proptest::collection::vec(proptest::prelude::any::<u8>(), 0..64),
+        ) {
+            check_never_panics(tokens.concat().as_bytes());
+            check_never_panics(&bytes);
+        }
+    }
+
+    #[test]
+    fn test_malformed_literals_are_located() {
+        let located = |source: &[u8]| match parse_bytes(source).unwrap_err().as_slice() {
+            [error] => (error.message(), error.span().map(|span| (span.start.line, span.start.column))),
+            errors => panic!("{:?}", errors),
+        };
+        assert_eq!(located(b"var s = \"abc\nprint(1)\n"), ("unterminated string literal".to_string(), Some((1, 9))));
+        assert_eq!(located(b"x = 1\nprint(f'{x}\")\n").1, Some((2, 7)));
+        assert_eq!(located(b"x = 0x\n").1, Some((1, 5)));
+        assert_eq!(located(b"x = 1\ns = \"\xc3\xa9\xff\"\n"), ("source is not valid UTF-8".to_string(), Some((2, 7))));
+    }
+
+    #[test]
+    fn test_keyword_arguments() {
+        let program = parse_program("print(1, x == 2, end=\"\")\n").unwrap();
+        let Statement::Expr(Expr::Call { args, kwargs, .. }) = &program.body[0] else { panic!("{:?}", program.body) };
+        assert_eq!(args.len(), 2);
+        assert!(matches!(kwargs.get("end"), Some(Expr::String(s, _)) if s.is_empty()), "{:?}", kwargs);
+        
+        let located = |source: &str| match parse_program(source).unwrap_err().as_slice() {
+            [error] => (error.message(), error.span().map(|span| (span.start.line, span.start.column))),
+            errors => panic!("{:?}", errors),
+        };
+        assert_eq!(located("print(end=\"\", 1)\n"), ("positional argument follows keyword argument".to_string(), Some((1, 15))));
+        assert_eq!(located("print(1, end=\"\", end=\"\")\n"), ("keyword argument 'end' is repeated".to_string(), Some((1, 18))));
+    }
+
+    #[test]
+    fn test_import_statements() {
+        let program = parse_program("x = 1\nimport math\nfrom string import upper, lower\n").unwrap();
+        let imports: Vec<_> = program.body.iter().filter_map(|stmt| match stmt {
+            Statement::Import { module, items, span } => Some((module.as_str(), items.clone(), span.start.line)),
+            _ => None,
+        }).collect();
+        assert_eq!(imports, [("math", None, 2), ("string", Some(vec!["upper".to_string(), "lower".to_string()]), 3)]);
+
+        // import and from are reserved, and a from needs its names
+        for source in ["var from = 1\n", "def import(x): return x\n", "from math import\n", "from math sqrt\n"] {
+            assert!(parse_program(source).is_err(), "{}", source);
+        }
+
+        let compile = |source: &str| {
+            let config = crate::compiler::CompilerConfig::default().with_hardware_dsl(false);
+            crate::compiler::EarthangCompiler::new(config).compile_source(source, None)
+        };
+        assert_eq!(compile("print(1)\nimport nope\n").unwrap_err(), "Unknown module 'nope' imported at 2:1");
+        assert_eq!(compile("from math import sqrt, cube\n").unwrap_err(), "Module 'math' has no function 'cube' imported at 1:1");
+    }
+}
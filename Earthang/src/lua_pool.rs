@@ -12,13 +12,16 @@
     GNU General Public License for more details.
 */
 use mlua::Lua;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, Mutex};
+use crate::lua_frontend::ParserSession;
 
 #[derive(Clone)]
 pub struct LuaPool {
     pool: Arc<Mutex<VecDeque<Lua>>>,
     max_size: usize,
+    /// Open documents by name, kept parsed between edits
+    sessions: Arc<Mutex<HashMap<String, ParserSession>>>,
 }
 
 impl LuaPool {
@@ -33,6 +36,7 @@ impl LuaPool {
         LuaPool {
             pool: Arc::new(Mutex::new(pool)),
             max_size,
+            sessions: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
@@ -61,6 +65,19 @@ impl LuaPool {
     pub fn max_size(&self) -> usize {
         self.max_size
     }
+
+    /// Check a document's session out; put it back with `store_session`
+    pub fn take_session(&self, document: &str) -> Option<ParserSession> {
+        self.sessions.lock().unwrap().remove(document)
+    }
+
+    pub fn store_session(&self, document: &str, session: ParserSession) {
+        self.sessions.lock().unwrap().insert(document.to_string(), session);
+    }
+
+    pub fn has_session(&self, document: &str) -> bool {
+        self.sessions.lock().unwrap().contains_key(document)
+    }
 }
 
 impl Default for LuaPool {
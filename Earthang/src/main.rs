@@ -14,8 +14,5 @@
 use earthang::cli;
 
 fn main() {
-    if let Err(e) = cli::run() {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
-    }
+    std::process::exit(cli::run());
 }
\ No newline at end of file
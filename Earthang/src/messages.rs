@@ -0,0 +1,181 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::path::PathBuf;
+use serde::Serialize;
+use crate::analysis::{Diagnostic, Severity};
+use crate::compiler::CompileError;
+use crate::parser::{ParseError, Span};
+
+// `compile --message-format json` writes one of these per line on stdout, for
+// editors and other tools. Every diagnostic looks like
+//
+//   {"type":"diagnostic","severity":"warning","message":"variable 'x' is never read",
+//    "file":"main.eg","span":{"start_line":1,"start_column":1,"end_line":1,"end_column":1},"code":"unused"}
+//
+// and the last line is always a summary:
+//
+//   {"type":"summary","success":true,"message":null,"artifacts":[{"path":"main.elf","size":9120}]}
+//
+// Lines and columns count from 1. `file` and `span` are null when unknown.
+
+/// One line of `--message-format json` output
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JsonMessage {
+    Diagnostic(JsonDiagnostic),
+    Summary {
+        success: bool,
+        /// Why the build failed, when it did for a reason no diagnostic covers
+        message: Option<String>,
+        artifacts: Vec<Artifact>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JsonDiagnostic {
+    /// "error" or "warning"
+    pub severity: &'static str,
+    pub message: String,
+    pub file: Option<String>,
+    pub span: Option<JsonSpan>,
+    /// Stable name of the kind of problem; for warnings the name `# noqa` takes
+    pub code: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct JsonSpan {
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl From<Span> for JsonSpan {
+    fn from(span: Span) -> Self {
+        Self { start_line: span.start.line, start_column: span.start.column, end_line: span.end.line, end_column: span.end.column }
+    }
+}
+
+/// A file the build wrote
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Artifact {
+    pub path: String,
+    pub size: u64,
+}
+
+impl JsonMessage {
+    pub fn from_parse_error(error: &ParseError, file: &str) -> Self {
+        let code = match error {
+            ParseError::LuaError(_) => "lua",
+            ParseError::SyntaxError { .. } => "syntax",
+            ParseError::UnexpectedToken { .. } => "unexpected-token",
+            ParseError::IncludeError { .. } => "include",
+            ParseError::HardwareError { .. } => "hardware",
+        };
+        // Like the rendered report, cover the whole offending token
+        let span = error.span().map(|mut span| {
+            if let ParseError::UnexpectedToken { found, .. } = error {
+                if found != "EOF" {
+                    span.end.column += found.chars().count().saturating_sub(1);
+                }
+            }
+            span
+        });
+        Self::error(code, error.message(), Some(file), span)
+    }
+
+    pub fn from_compile_error(error: &CompileError, file: &str) -> Self {
+        Self::error("compile", error.message.clone(), Some(file), error.span)
+    }
+
+    /// `file` is None for a linked program, whose spans do not say which file they are in
+    pub fn from_diagnostic(diagnostic: &Diagnostic, file: Option<&str>) -> Self {
+        JsonMessage::Diagnostic(JsonDiagnostic {
+            severity: match diagnostic.severity {
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            },
+            message: diagnostic.message.clone(),
+            file: file.map(str::to_string),
+            span: Some(diagnostic.span.into()),
+            code: diagnostic.code,
+        })
+    }
+
+    pub fn error(code: &'static str, message: String, file: Option<&str>, span: Option<Span>) -> Self {
+        JsonMessage::Diagnostic(JsonDiagnostic {
+            severity: "error",
+            message,
+            file: file.map(str::to_string),
+            span: span.map(JsonSpan::from),
+            code,
+        })
+    }
+
+    /// Summary of a build that wrote `artifacts`; files that cannot be read are left out
+    pub fn success(artifacts: &[PathBuf]) -> Self {
+        let artifacts = artifacts.iter()
+            .filter_map(|path| Some(Artifact { path: path.display().to_string(), size: std::fs::metadata(path).ok()?.len() }))
+            .collect();
+        JsonMessage::Summary { success: true, message: None, artifacts }
+    }
+
+    pub fn failure(message: impl Into<String>) -> Self {
+        JsonMessage::Summary { success: false, message: Some(message.into()), artifacts: Vec::new() }
+    }
+
+    pub fn to_line(&self) -> String {
+        serde_json::to_string(self).expect("messages only hold strings and numbers")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{CompilerConfig, EarthangCompiler};
+
+    #[test]
+    fn test_json_messages_for_warning_and_error() {
+        let source = "unused = 1\nprint(missing)\n";
+        let program = crate::parser::parse_program(source).unwrap();
+        let mut lines: Vec<String> = crate::analysis::analyze(&program, source).iter()
+            .map(|diagnostic| JsonMessage::from_diagnostic(diagnostic, Some("main.eg")).to_line())
+            .collect();
+        let error = EarthangCompiler::new(CompilerConfig::default()).compile_source(source, None).unwrap_err();
+        lines.push(JsonMessage::from_compile_error(&CompileError::from_message(error), "main.eg").to_line());
+        let errors = crate::parser::parse_program("x = (1 +\n").unwrap_err();
+        lines.extend(errors.iter().map(|error| JsonMessage::from_parse_error(error, "bad.eg").to_line()));
+        lines.push(JsonMessage::failure("compilation failed").to_line());
+
+        let values: Vec<serde_json::Value> = lines.iter().map(|line| serde_json::from_str(line).unwrap()).collect();
+        let (summary, diagnostics) = values.split_last().unwrap();
+        assert!(diagnostics.len() >= 3, "{:?}", lines);
+        for value in diagnostics {
+            assert_eq!(value["type"], "diagnostic");
+            assert!(matches!(value["severity"].as_str(), Some("warning" | "error")), "{}", value);
+            assert!(value["message"].is_string() && value["file"].is_string() && value["code"].is_string(), "{}", value);
+            for field in ["start_line", "start_column", "end_line", "end_column"] {
+                assert!(value["span"][field].as_u64().is_some_and(|n| n > 0), "{}", value);
+            }
+        }
+        assert_eq!((diagnostics[0]["severity"].as_str(), diagnostics[0]["code"].as_str()), (Some("warning"), Some("unused")));
+        assert_eq!(diagnostics[0]["span"]["start_line"], 1);
+        assert_eq!((diagnostics[1]["severity"].as_str(), diagnostics[1]["code"].as_str()), (Some("error"), Some("compile")));
+        assert_eq!(diagnostics[1]["span"]["start_line"], 2);
+        assert!(diagnostics[1]["message"].as_str().unwrap().contains("missing"), "{}", diagnostics[1]);
+        assert_eq!(diagnostics[2]["file"], "bad.eg");
+        assert_eq!((summary["type"].as_str(), summary["success"].as_bool()), (Some("summary"), Some(false)));
+        assert!(summary["artifacts"].as_array().unwrap().is_empty());
+    }
+}
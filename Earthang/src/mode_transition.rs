@@ -0,0 +1,691 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::path::Path;
+use crate::boot_test::{serial_init_gas, serial_marker_gas, BootPhase};
+use crate::disk_image::{DiskImage, DiskImageBuilder, Stage1Loader, BOOT_CODE_LIMIT, BOOT_SECTOR_ADDRESS, SECTOR_SIZE, STAGE2_LOAD_ADDRESS};
+use crate::symbol_map::{object_symbols, ObjectSymbol};
+
+/// Identity-mapped page tables: PML4, PDPT and a page directory of 2 MiB pages
+const PML4_ADDRESS: u32 = 0x1000;
+const PDPT_ADDRESS: u32 = 0x2000;
+const PAGE_DIRECTORY_ADDRESS: u32 = 0x3000;
+/// First byte above the page tables, the lowest a stack may start growing down from
+const PAGE_TABLES_END: u32 = PAGE_DIRECTORY_ADDRESS + 0x1000;
+
+/// GDT selectors
+const CODE32_SELECTOR: u16 = 0x08;
+const DATA_SELECTOR: u16 = 0x10;
+pub const CODE64_SELECTOR: u16 = 0x18;
+
+/// One step of the climb from the BIOS to 64-bit code, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionPhase {
+    RealModeSetup,
+    CpuidProbe,
+    EnableA20,
+    ProtectedMode,
+    LongMode,
+    SimdEnable,
+    Avx,
+    Avx512,
+}
+
+impl TransitionPhase {
+    pub const ALL: [TransitionPhase; 8] = [
+        TransitionPhase::RealModeSetup, TransitionPhase::CpuidProbe, TransitionPhase::EnableA20,
+        TransitionPhase::ProtectedMode, TransitionPhase::LongMode, TransitionPhase::SimdEnable,
+        TransitionPhase::Avx, TransitionPhase::Avx512,
+    ];
+
+    /// Optional phases in the order they are given up when the code does not fit
+    pub const DROP_ORDER: [TransitionPhase; 3] = [TransitionPhase::Avx512, TransitionPhase::Avx, TransitionPhase::CpuidProbe];
+
+    pub fn is_optional(&self) -> bool {
+        Self::DROP_ORDER.contains(self)
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            TransitionPhase::RealModeSetup => "real_mode_setup",
+            TransitionPhase::CpuidProbe => "cpuid_probe",
+            TransitionPhase::EnableA20 => "enable_a20",
+            TransitionPhase::ProtectedMode => "protected_mode",
+            TransitionPhase::LongMode => "long_mode",
+            TransitionPhase::SimdEnable => "simd_enable",
+            TransitionPhase::Avx => "avx",
+            TransitionPhase::Avx512 => "avx512",
+        }
+    }
+}
+
+impl std::fmt::Display for TransitionPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// Phases with the number of bytes each one assembled to
+pub type PhaseCosts = Vec<(TransitionPhase, usize)>;
+
+/// Code that fit, its phase costs, the phases dropped for it and its labels
+type FittedCode = (Vec<u8>, PhaseCosts, Vec<TransitionPhase>, Vec<ObjectSymbol>);
+
+/// Optional parts of the transition code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransitionFeatures {
+    /// Halt cleanly on CPUs without CPUID or long mode instead of faulting
+    pub cpuid_probe: bool,
+    /// Enable XSAVE and AVX state when the CPU has them
+    pub avx: bool,
+    /// Also enable the AVX-512 state components when the CPU has AVX-512F
+    pub avx512: bool,
+    /// Print the `test boot` markers on COM1; never dropped to make room
+    pub serial_markers: bool,
+}
+
+impl Default for TransitionFeatures {
+    fn default() -> Self {
+        Self { cpuid_probe: true, avx: true, avx512: true, serial_markers: false }
+    }
+}
+
+/// Where the transition code lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootLayout {
+    /// Everything in the 510 bytes of the boot sector; no payload can be loaded
+    BootSector,
+    /// Stage 2 at 0x7E00 unless moved, loaded together with the payload by a `Stage1Loader`
+    TwoStage,
+}
+
+/// Bootable image produced by `ModeTransitionEmitter::create_bootloader`
+#[derive(Debug, Clone)]
+pub struct Bootloader {
+    pub image: DiskImage,
+    /// Included phases
+    pub phases: PhaseCosts,
+    /// Optional phases left out to make the code fit
+    pub dropped: Vec<TransitionPhase>,
+    /// Size of the transition code including the GDT
+    pub code_size: usize,
+    /// Linear address the payload runs at in 64-bit mode
+    pub payload_address: u32,
+}
+
+impl Bootloader {
+    pub fn describe(&self) -> String {
+        let mut text = format!("Transition code: {} bytes\n", self.code_size);
+        for (phase, bytes) in &self.phases {
+            text.push_str(&format!("  {:<16} {:>4} bytes\n", phase.to_string(), bytes));
+        }
+        if !self.dropped.is_empty() {
+            let dropped: Vec<String> = self.dropped.iter().map(|phase| phase.to_string()).collect();
+            text.push_str(&format!("  dropped to fit: {}\n", dropped.join(", ")));
+        }
+        text
+    }
+}
+
+/// Emits the real mode -> protected mode -> long mode ladder, dropping optional
+/// phases until the assembled code fits its layout
+#[derive(Debug, Clone)]
+pub struct ModeTransitionEmitter {
+    pub layout: BootLayout,
+    pub features: TransitionFeatures,
+    pub size_limit: Option<usize>,
+    pub payload: Vec<u8>,
+    /// Legacy BIOS video mode set through INT 10h before leaving real mode
+    pub video_mode: Option<u8>,
+    /// Where stage 2 is loaded in the two-stage layout; the BIOS always loads the boot sector to 0x7C00
+    pub stage2_address: u32,
+    /// Stack pointer set in every mode, growing down from below the code
+    pub stack_top: u32,
+}
+
+impl ModeTransitionEmitter {
+    pub fn new(layout: BootLayout) -> Self {
+        Self {
+            layout,
+            features: TransitionFeatures::default(),
+            size_limit: None,
+            payload: Vec::new(),
+            video_mode: None,
+            stage2_address: STAGE2_LOAD_ADDRESS,
+            stack_top: BOOT_SECTOR_ADDRESS,
+        }
+    }
+
+    pub fn with_features(mut self, features: TransitionFeatures) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Cap on the transition code; the boot sector layout never allows more than 510 bytes
+    pub fn with_size_limit(mut self, bytes: usize) -> Self {
+        self.size_limit = Some(bytes);
+        self
+    }
+
+    /// 64-bit flat binary jumped to once the transition is done, linked at `Bootloader::payload_address`
+    pub fn with_payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    pub fn with_video_mode(mut self, mode: u8) -> Self {
+        self.video_mode = Some(mode);
+        self
+    }
+
+    /// Load stage 2 elsewhere, for example above another loader that chainloads this one
+    pub fn with_stage2_address(mut self, address: u32) -> Self {
+        self.stage2_address = address;
+        self
+    }
+
+    pub fn with_stack_top(mut self, address: u32) -> Self {
+        self.stack_top = address;
+        self
+    }
+
+    fn load_address(&self) -> u32 {
+        match self.layout {
+            BootLayout::BootSector => BOOT_SECTOR_ADDRESS,
+            BootLayout::TwoStage => self.stage2_address,
+        }
+    }
+
+    /// The stack has to be reachable from real mode and grow down into free memory
+    fn check_memory(&self) -> Result<(), String> {
+        if self.layout == BootLayout::TwoStage && self.stage2_address < STAGE2_LOAD_ADDRESS {
+            return Err(format!("Stage 2 at 0x{:X} would overwrite the boot sector; it must load at 0x{:X} or above", self.stage2_address, STAGE2_LOAD_ADDRESS));
+        }
+        if !self.stack_top.is_multiple_of(16) {
+            return Err(format!("Stack top 0x{:X} must be 16-byte aligned", self.stack_top));
+        }
+        if self.stack_top <= PAGE_TABLES_END || self.stack_top > self.load_address() {
+            return Err(format!(
+                "Stack top 0x{:X} must lie between the page tables ending at 0x{:X} and the code at 0x{:X}",
+                self.stack_top, PAGE_TABLES_END, self.load_address()
+            ));
+        }
+        Ok(())
+    }
+
+    fn limit(&self) -> usize {
+        match self.layout {
+            BootLayout::BootSector => self.size_limit.unwrap_or(BOOT_CODE_LIMIT).min(BOOT_CODE_LIMIT),
+            BootLayout::TwoStage => self.size_limit.unwrap_or(usize::MAX),
+        }
+    }
+
+    /// Phases the feature mask asks for
+    pub fn selected_phases(&self) -> Vec<TransitionPhase> {
+        TransitionPhase::ALL.iter().copied().filter(|phase| match phase {
+            TransitionPhase::CpuidProbe => self.features.cpuid_probe,
+            TransitionPhase::Avx => self.features.avx,
+            TransitionPhase::Avx512 => self.features.avx && self.features.avx512,
+            _ => true,
+        }).collect()
+    }
+
+    /// GNU as source of the transition code for `phases`. Every address is written
+    /// relative to `_start`, so the GDTR and far jumps need no patching after assembly.
+    /// Each phase is bracketed by labels whose distance lands in `.phase_sizes`
+    pub fn to_gas(&self, phases: &[TransitionPhase]) -> String {
+        let load = self.load_address();
+        let at = |label: &str| format!("{} - _start + 0x{:X}", label, load);
+
+        let mut asm = String::new();
+        asm.push_str("    .intel_syntax noprefix\n");
+        asm.push_str("    .section .text\n");
+        asm.push_str("    .globl _start\n");
+        asm.push_str("    .code16\n");
+        asm.push_str("_start:\n");
+        for phase in phases {
+            asm.push_str(&format!("phase_{}_start:\n", phase.label()));
+            asm.push_str(&self.phase_gas(*phase, &at));
+            asm.push_str(&format!("phase_{}_end:\n", phase.label()));
+        }
+
+        asm.push_str("    # Transition done\n");
+        if self.payload.is_empty() {
+            asm.push_str("done:\n");
+            asm.push_str("    hlt\n");
+            asm.push_str("    jmp done\n");
+        } else {
+            asm.push_str(&format!("    mov rax, offset {}\n", at("payload")));
+            asm.push_str("    jmp rax\n");
+        }
+        if phases.contains(&TransitionPhase::CpuidProbe) {
+            asm.push_str("    .code16\n");
+            asm.push_str("no_long_mode:\n");
+            asm.push_str("    hlt\n");
+            asm.push_str("    jmp no_long_mode\n");
+        }
+
+        asm.push_str("    .balign 8\n");
+        asm.push_str("gdt:\n");
+        asm.push_str("    .quad 0\n");
+        asm.push_str("    .quad 0x00CF9A000000FFFF  # 0x08: 32-bit code\n");
+        asm.push_str("    .quad 0x00CF92000000FFFF  # 0x10: data\n");
+        asm.push_str("    .quad 0x00AF9A000000FFFF  # 0x18: 64-bit code\n");
+        asm.push_str("gdt_end:\n");
+        asm.push_str("gdtr:\n");
+        asm.push_str("    .word gdt_end - gdt - 1\n");
+        asm.push_str(&format!("    .long {}\n", at("gdt")));
+        asm.push_str("    .balign 16\n");
+        asm.push_str("payload:\n");
+
+        asm.push_str("    .section .phase_sizes, \"a\"\n");
+        for phase in phases {
+            asm.push_str(&format!("    .long phase_{0}_end - phase_{0}_start\n", phase.label()));
+        }
+        asm
+    }
+
+    fn phase_gas(&self, phase: TransitionPhase, at: &dyn Fn(&str) -> String) -> String {
+        let marker = |phase: BootPhase| match self.features.serial_markers {
+            true => serial_marker_gas(phase),
+            false => String::new(),
+        };
+
+        let mut asm = String::new();
+        match phase {
+            TransitionPhase::RealModeSetup => {
+                asm.push_str("    cli\n");
+                asm.push_str("    xor ax, ax\n");
+                asm.push_str("    mov ds, ax\n");
+                asm.push_str("    mov es, ax\n");
+                asm.push_str("    mov ss, ax\n");
+                asm.push_str(&format!("    mov sp, 0x{:X}\n", self.stack_top));
+                asm.push_str("    cld\n");
+                if let Some(mode) = self.video_mode {
+                    asm.push_str("    sti\n");
+                    asm.push_str(&format!("    mov ax, 0x{:04X}  # set video mode\n", mode));
+                    asm.push_str("    int 0x10\n");
+                    asm.push_str("    cli\n");
+                }
+                if self.features.serial_markers {
+                    asm.push_str(&serial_init_gas());
+                }
+                asm.push_str(&marker(BootPhase::RealMode));
+            }
+            TransitionPhase::CpuidProbe => {
+                asm.push_str("    # CPUID exists when EFLAGS.ID can be toggled\n");
+                asm.push_str("    pushfd\n");
+                asm.push_str("    pop eax\n");
+                asm.push_str("    mov ecx, eax\n");
+                asm.push_str("    xor eax, 0x200000\n");
+                asm.push_str("    push eax\n");
+                asm.push_str("    popfd\n");
+                asm.push_str("    pushfd\n");
+                asm.push_str("    pop eax\n");
+                asm.push_str("    push ecx\n");
+                asm.push_str("    popfd\n");
+                asm.push_str("    cmp eax, ecx\n");
+                asm.push_str("    je no_long_mode\n");
+                asm.push_str("    mov eax, 0x80000000\n");
+                asm.push_str("    cpuid\n");
+                asm.push_str("    cmp eax, 0x80000001\n");
+                asm.push_str("    jb no_long_mode\n");
+                asm.push_str("    mov eax, 0x80000001\n");
+                asm.push_str("    cpuid\n");
+                asm.push_str("    test edx, 0x20000000  # LM\n");
+                asm.push_str("    jz no_long_mode\n");
+            }
+            TransitionPhase::EnableA20 => {
+                asm.push_str("    # Fast A20 through the system control port\n");
+                asm.push_str("    in al, 0x92\n");
+                asm.push_str("    or al, 2\n");
+                asm.push_str("    and al, 0xFE\n");
+                asm.push_str("    out 0x92, al\n");
+            }
+            TransitionPhase::ProtectedMode => {
+                asm.push_str(&format!("    lgdt [{}]\n", at("gdtr")));
+                asm.push_str("    mov eax, cr0\n");
+                asm.push_str("    or eax, 1\n");
+                asm.push_str("    mov cr0, eax\n");
+                asm.push_str(&format!("    jmp 0x{:02X}:{}\n", CODE32_SELECTOR, at("protected_entry")));
+                asm.push_str("    .code32\n");
+                asm.push_str("protected_entry:\n");
+                asm.push_str(&format!("    mov ax, 0x{:02X}\n", DATA_SELECTOR));
+                for segment in ["ds", "es", "fs", "gs", "ss"] {
+                    asm.push_str(&format!("    mov {}, ax\n", segment));
+                }
+                asm.push_str(&format!("    mov esp, 0x{:X}\n", self.stack_top));
+                asm.push_str(&marker(BootPhase::ProtectedMode));
+            }
+            TransitionPhase::LongMode => {
+                asm.push_str("    # Identity-map the first GiB with 2 MiB pages\n");
+                asm.push_str(&format!("    mov edi, 0x{:X}\n", PML4_ADDRESS));
+                asm.push_str("    xor eax, eax\n");
+                asm.push_str(&format!("    mov ecx, 0x{:X}\n", (PAGE_DIRECTORY_ADDRESS + 0x1000 - PML4_ADDRESS) / 4));
+                asm.push_str("    rep stosd\n");
+                asm.push_str(&format!("    mov dword ptr [0x{:X}], 0x{:X}\n", PML4_ADDRESS, PDPT_ADDRESS | 3));
+                asm.push_str(&format!("    mov dword ptr [0x{:X}], 0x{:X}\n", PDPT_ADDRESS, PAGE_DIRECTORY_ADDRESS | 3));
+                asm.push_str(&format!("    mov edi, 0x{:X}\n", PAGE_DIRECTORY_ADDRESS));
+                asm.push_str("    mov eax, 0x83  # present, writable, 2 MiB\n");
+                asm.push_str("    mov ecx, 512\n");
+                asm.push_str("1:  mov [edi], eax\n");
+                asm.push_str("    add eax, 0x200000\n");
+                asm.push_str("    add edi, 8\n");
+                asm.push_str("    loop 1b\n");
+                asm.push_str("    mov eax, cr4\n");
+                asm.push_str("    or eax, 0x20  # PAE\n");
+                asm.push_str("    mov cr4, eax\n");
+                asm.push_str(&format!("    mov eax, 0x{:X}\n", PML4_ADDRESS));
+                asm.push_str("    mov cr3, eax\n");
+                asm.push_str("    mov ecx, 0xC0000080  # EFER\n");
+                asm.push_str("    rdmsr\n");
+                asm.push_str("    or eax, 0x100  # LME\n");
+                asm.push_str("    wrmsr\n");
+                asm.push_str("    mov eax, cr0\n");
+                asm.push_str("    or eax, 0x80000000  # PG\n");
+                asm.push_str("    mov cr0, eax\n");
+                asm.push_str(&format!("    jmp 0x{:02X}:{}\n", CODE64_SELECTOR, at("long_entry")));
+                asm.push_str("    .code64\n");
+                asm.push_str("long_entry:\n");
+                asm.push_str(&format!("    mov ax, 0x{:02X}\n", DATA_SELECTOR));
+                for segment in ["ds", "es", "fs", "gs", "ss"] {
+                    asm.push_str(&format!("    mov {}, ax\n", segment));
+                }
+                asm.push_str(&format!("    mov rsp, 0x{:X}\n", self.stack_top));
+                asm.push_str(&marker(BootPhase::LongMode));
+            }
+            TransitionPhase::SimdEnable => {
+                asm.push_str("    # SSE is architectural in long mode: clear CR0.EM, set CR0.MP, CR4.OSFXSR and OSXMMEXCPT\n");
+                asm.push_str("    mov rax, cr0\n");
+                asm.push_str("    and ax, 0xFFFB\n");
+                asm.push_str("    or ax, 2\n");
+                asm.push_str("    mov cr0, rax\n");
+                asm.push_str("    mov rax, cr4\n");
+                asm.push_str("    or ax, 0x600\n");
+                asm.push_str("    mov cr4, rax\n");
+                asm.push_str(&marker(BootPhase::SimdEnabled));
+            }
+            TransitionPhase::Avx => {
+                asm.push_str("    # XCR0 gets x87, SSE and AVX state when CPUID.1 reports XSAVE and AVX\n");
+                asm.push_str("    mov eax, 1\n");
+                asm.push_str("    cpuid\n");
+                asm.push_str("    and ecx, 0x14000000\n");
+                asm.push_str("    cmp ecx, 0x14000000\n");
+                asm.push_str("    jne avx_done\n");
+                asm.push_str("    mov rax, cr4\n");
+                asm.push_str("    or eax, 0x40000  # OSXSAVE\n");
+                asm.push_str("    mov cr4, rax\n");
+                asm.push_str("    xor ecx, ecx\n");
+                asm.push_str("    xgetbv\n");
+                asm.push_str("    or eax, 7\n");
+                asm.push_str("    xsetbv\n");
+                asm.push_str("avx_done:\n");
+            }
+            TransitionPhase::Avx512 => {
+                asm.push_str("    # Opmask and ZMM state when CPUID.7 reports AVX-512F and AVX is already on\n");
+                asm.push_str("    mov rax, cr4\n");
+                asm.push_str("    test eax, 0x40000\n");
+                asm.push_str("    jz avx512_done\n");
+                asm.push_str("    mov eax, 7\n");
+                asm.push_str("    xor ecx, ecx\n");
+                asm.push_str("    cpuid\n");
+                asm.push_str("    test ebx, 0x10000  # AVX512F\n");
+                asm.push_str("    jz avx512_done\n");
+                asm.push_str("    xor ecx, ecx\n");
+                asm.push_str("    xgetbv\n");
+                asm.push_str("    or eax, 0xE0\n");
+                asm.push_str("    xsetbv\n");
+                asm.push_str("avx512_done:\n");
+            }
+        }
+        asm
+    }
+
+    /// Assemble the code for `phases`, returning it with the byte cost of every phase
+    pub fn assemble(&self, phases: &[TransitionPhase], work_dir: &Path) -> Result<(Vec<u8>, PhaseCosts), String> {
+        self.assemble_source(&self.to_gas(phases), phases, work_dir)
+    }
+
+    /// Assemble `to_gas` output for `phases`, possibly edited, and check that the
+    /// LGDT operand and the real-mode far jump still fit their 16-bit fields
+    pub fn assemble_source(&self, gas: &str, phases: &[TransitionPhase], work_dir: &Path) -> Result<(Vec<u8>, PhaseCosts), String> {
+        self.assemble_with_symbols(gas, phases, work_dir).map(|(code, costs, _)| (code, costs))
+    }
+
+    fn assemble_with_symbols(&self, gas: &str, phases: &[TransitionPhase], work_dir: &Path) -> Result<(Vec<u8>, PhaseCosts, Vec<ObjectSymbol>), String> {
+        let source = work_dir.join("transition.s");
+        let object = work_dir.join("transition.o");
+        let binary = work_dir.join("transition.bin");
+        let sizes = work_dir.join("transition.sizes");
+        std::fs::write(&source, gas)
+            .map_err(|e| format!("Failed to write {}: {}", source.display(), e))?;
+
+        crate::compiler::run_tool("as", &["--64".as_ref(), "-o".as_ref(), object.as_os_str(), source.as_os_str()])?;
+        for (section, output) in [(".text", &binary), (".phase_sizes", &sizes)] {
+            crate::compiler::run_tool("objcopy", &["-O".as_ref(), "binary".as_ref(), "-j".as_ref(), section.as_ref(), object.as_os_str(), output.as_os_str()])?;
+        }
+        let read = |path: &Path| std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e));
+        let code = read(&binary)?;
+        let costs = read(&sizes)?.chunks(4)
+            .zip(phases)
+            .map(|(bytes, phase)| (*phase, u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize))
+            .collect();
+        let symbols = object_symbols(&object, self.load_address() as u64)?;
+
+        for path in [&source, &object, &binary, &sizes] {
+            let _ = std::fs::remove_file(path);
+        }
+        if self.load_address() as usize + code.len() > 0x10000 {
+            return Err(format!(
+                "Transition code of {} bytes at 0x{:X} crosses 64 KiB; its real-mode pointers are 16 bits wide",
+                code.len(), self.load_address()
+            ));
+        }
+        Ok((code, costs, symbols))
+    }
+
+    /// Assemble the ladder, leaving out optional phases in `DROP_ORDER` until it fits the layout
+    pub fn create_bootloader(&self, work_dir: &Path) -> Result<Bootloader, String> {
+        if self.layout == BootLayout::BootSector && !self.payload.is_empty() {
+            return Err("A payload needs the two-stage layout; the boot sector layout loads nothing".to_string());
+        }
+
+        let (code, costs, dropped, symbols) = self.fit(work_dir)?;
+        let load_address = self.load_address();
+        let payload_address = load_address + code.len() as u32;
+        let mut image = match self.layout {
+            BootLayout::BootSector => DiskImageBuilder::new(code.clone()).build()?,
+            BootLayout::TwoStage => {
+                let mut stage2 = code.clone();
+                stage2.extend(&self.payload);
+                let sectors = stage2.len().div_ceil(SECTOR_SIZE) as u32;
+                let (stage1, stage1_symbols) = Stage1Loader::new(sectors).with_load_address(load_address).assemble_with_symbols(work_dir)?;
+                let mut image = DiskImageBuilder::new(stage1).with_part("stage2", stage2).build()?;
+                let boot_size = image.manifest[0].size as u64;
+                image.symbols.add_region("stage1", BOOT_SECTOR_ADDRESS as u64, boot_size, 0, &stage1_symbols);
+                image
+            }
+        };
+        self.map_transition(&mut image, &code, &costs, &symbols);
+
+        Ok(Bootloader { image, phases: costs, dropped, code_size: code.len(), payload_address })
+    }
+
+    /// Add the transition code and each of its phases to the image's symbol map
+    fn map_transition(&self, image: &mut DiskImage, code: &[u8], costs: &PhaseCosts, symbols: &[ObjectSymbol]) {
+        let part = match self.layout {
+            BootLayout::BootSector => "boot",
+            BootLayout::TwoStage => "stage2",
+        };
+        let offset = image.entry(part).map_or(0, |entry| entry.lba as u64 * SECTOR_SIZE as u64);
+        let load = self.load_address() as u64;
+        // The phase markers split the labels at phase boundaries, then make way for the phases
+        image.symbols.add_region("transition", load, code.len() as u64, offset, symbols);
+        image.symbols.symbols.retain(|symbol| !symbol.name.starts_with("phase_"));
+        for (phase, bytes) in costs {
+            let start = format!("phase_{}_start", phase.label());
+            if let Some(symbol) = symbols.iter().find(|symbol| symbol.name == start) {
+                image.symbols.add_phase(phase.label(), symbol.address, *bytes as u64, offset + symbol.address - load);
+            }
+        }
+    }
+
+    /// Where `create_bootloader` will place a payload, so it can be linked there first.
+    /// The transition code depends on there being a payload, not on its bytes
+    pub fn payload_address(&self, work_dir: &Path) -> Result<u32, String> {
+        let mut probe = self.clone();
+        if probe.payload.is_empty() {
+            probe.payload = vec![0xF4];
+        }
+        let (code, _, _, _) = probe.fit(work_dir)?;
+        Ok(self.load_address() + code.len() as u32)
+    }
+
+    fn fit(&self, work_dir: &Path) -> Result<FittedCode, String> {
+        self.check_memory()?;
+        let mut phases = self.selected_phases();
+        let mut dropped = Vec::new();
+        loop {
+            let (code, costs, symbols) = self.assemble_with_symbols(&self.to_gas(&phases), &phases, work_dir)?;
+            if code.len() <= self.limit() {
+                return Ok((code, costs, dropped, symbols));
+            }
+            match TransitionPhase::DROP_ORDER.iter().find(|phase| phases.contains(phase)) {
+                Some(phase) => {
+                    phases.retain(|p| p != phase);
+                    dropped.push(*phase);
+                }
+                None => return Err(format!(
+                    "The mode transition needs {} bytes without optional phases but only {} fit{}",
+                    code.len(), self.limit(),
+                    if self.features.serial_markers { "; serial markers need the two-stage layout" } else { "" }
+                )),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_aware_transition() {
+        let emitter = ModeTransitionEmitter::new(BootLayout::BootSector);
+        let gas = emitter.to_gas(&emitter.selected_phases());
+        assert!(gas.contains("lgdt [gdtr - _start + 0x7C00]"));
+        assert!(gas.contains("    .long gdt - _start + 0x7C00\n"));
+        let no_avx = TransitionFeatures { avx: false, ..TransitionFeatures::default() };
+        assert!(!ModeTransitionEmitter::new(BootLayout::BootSector).with_features(no_avx).selected_phases().contains(&TransitionPhase::Avx512));
+
+        // Only meaningful where binutils are installed
+        let work_dir = std::env::temp_dir().join(format!("earthang_transition_{}", std::process::id()));
+        std::fs::create_dir_all(&work_dir).unwrap();
+        let full = emitter.create_bootloader(&work_dir);
+        let tight = emitter.clone().with_size_limit(340).create_bootloader(&work_dir);
+        let markers = TransitionFeatures { serial_markers: true, ..TransitionFeatures::default() };
+        let too_big = emitter.clone().with_features(markers).create_bootloader(&work_dir);
+        let _ = std::fs::remove_dir(&work_dir);
+        let full = match full {
+            Ok(bootloader) => bootloader,
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        };
+
+        assert!(full.dropped.is_empty());
+        assert_eq!(full.phases.len(), TransitionPhase::ALL.len());
+        assert!(full.phases.iter().map(|(_, bytes)| bytes).sum::<usize>() < full.code_size);
+        assert_eq!(&full.image.bytes[510..512], &crate::disk_image::BOOT_SIGNATURE);
+
+        let tight = tight.unwrap();
+        assert!(tight.code_size <= 340);
+        assert_eq!(tight.dropped, vec![TransitionPhase::Avx512, TransitionPhase::Avx]);
+        assert!(tight.describe().contains("dropped to fit: avx512, avx"));
+        assert!(too_big.unwrap_err().contains("two-stage layout"));
+    }
+
+    /// LGDT operand, GDTR base and both far jumps of assembled code loaded at `load`
+    fn check_pointers(code: &[u8], load: usize) {
+        let word = |at: usize| u16::from_le_bytes([code[at], code[at + 1]]) as usize;
+        let dword = |at: usize| u32::from_le_bytes(code[at..at + 4].try_into().unwrap()) as usize;
+        let find_all = |pattern: &[u8]| -> Vec<usize> {
+            code.windows(pattern.len()).enumerate().filter(|(_, w)| *w == pattern).map(|(at, _)| at).collect()
+        };
+
+        let gdt = find_all(&0x00CF9A000000FFFFu64.to_le_bytes())[0] - 8;
+        let gdtr = gdt + 32;
+        assert_eq!(word(gdtr), 31);
+        assert_eq!(dword(gdtr + 2), load + gdt);
+
+        let lgdt = find_all(&[0x0F, 0x01, 0x16])[0];
+        assert_eq!(word(lgdt + 3), load + gdtr);
+
+        // `mov cr0, eax` then a far jump to the entry label right behind it, first 16 then 32-bit
+        let jumps = find_all(&[0x0F, 0x22, 0xC0, 0xEA]);
+        let (jump16, jump32) = (jumps[0] + 3, jumps[1] + 3);
+        assert_eq!((word(jump16 + 1), word(jump16 + 3)), (load + jump16 + 5, CODE32_SELECTOR as usize));
+        assert_eq!((dword(jump32 + 1), word(jump32 + 5)), (load + jump32 + 7, CODE64_SELECTOR as usize));
+    }
+
+    #[test]
+    fn test_pointers_survive_phase_growth() {
+        let emitter = ModeTransitionEmitter::new(BootLayout::BootSector);
+        let phases = emitter.selected_phases();
+        let gas = emitter.to_gas(&phases);
+        let grown = gas.replace("phase_enable_a20_start:\n", &format!("phase_enable_a20_start:\n{}", "    nop\n".repeat(8)));
+
+        let work_dir = std::env::temp_dir().join(format!("earthang_pointers_{}", std::process::id()));
+        std::fs::create_dir_all(&work_dir).unwrap();
+        let original = emitter.assemble_source(&gas, &phases, &work_dir);
+        let padded = emitter.assemble_source(&grown, &phases, &work_dir);
+        let _ = std::fs::remove_dir(&work_dir);
+        let (original, original_costs) = match original {
+            Ok(assembled) => assembled,
+            Err(e) if e.contains("Failed to run") => return,
+            Err(e) => panic!("{}", e),
+        };
+        let (padded, padded_costs) = padded.unwrap();
+
+        let a20 = |costs: &PhaseCosts| costs.iter().find(|(phase, _)| *phase == TransitionPhase::EnableA20).unwrap().1;
+        assert_eq!(a20(&padded_costs), a20(&original_costs) + 8);
+        let gdt_at = |code: &[u8]| code.windows(8).position(|w| w == 0x00CF9A000000FFFFu64.to_le_bytes());
+        assert_eq!(gdt_at(&padded), gdt_at(&original).map(|at| at + 8));
+        check_pointers(&original, 0x7C00);
+        check_pointers(&padded, 0x7C00);
+    }
+
+    #[test]
+    fn test_moved_stage2_and_stack() {
+        let default = ModeTransitionEmitter::new(BootLayout::TwoStage);
+        let gas = default.to_gas(&default.selected_phases());
+        assert!(gas.contains("lgdt [gdtr - _start + 0x7E00]") && gas.contains("    mov rsp, 0x7C00\n"));
+
+        let moved = ModeTransitionEmitter::new(BootLayout::TwoStage).with_stage2_address(0x8000).with_stack_top(0x7000);
+        let gas = moved.to_gas(&moved.selected_phases());
+        assert!(gas.contains("lgdt [gdtr - _start + 0x8000]"));
+        assert!(gas.contains("    jmp 0x08:protected_entry - _start + 0x8000\n"));
+        assert!(gas.contains("    jmp 0x18:long_entry - _start + 0x8000\n"));
+        assert!(gas.contains("    .long gdt - _start + 0x8000\n"));
+        for register in ["sp", "esp", "rsp"] {
+            assert!(gas.contains(&format!("    mov {}, 0x7000\n", register)), "{}", gas);
+        }
+        assert!(!gas.contains("0x7E00") && !gas.contains("0x7C00"));
+        assert_eq!(moved.check_memory(), Ok(()));
+
+        let error = |emitter: ModeTransitionEmitter| emitter.check_memory().unwrap_err();
+        assert!(error(moved.clone().with_stage2_address(0x7C00)).contains("would overwrite the boot sector"));
+        assert!(error(moved.clone().with_stack_top(0x7008)).contains("16-byte aligned"));
+        assert!(error(moved.clone().with_stack_top(0x8010)).contains("between the page tables"));
+        assert!(error(moved.with_stack_top(0x4000)).contains("between the page tables"));
+    }
+}
@@ -0,0 +1,241 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::backend::{Capability, Target};
+use crate::extension::{AssemblyEmitter, EarthngModule, FunctionSignature};
+use crate::parser::Expr;
+
+// A module library file carries an extension module's metadata and library
+// code, so modules can be shared without their Rust source. Integers are
+// little-endian:
+//
+//   offset  size  field
+//   0       4     magic "EGML"
+//   4       2     format version, LIBRARY_FORMAT_VERSION
+//   6       2     reserved, zero
+//   8       4     payload length in bytes
+//   12      4     FNV-1a hash of the payload
+//   16      ...   payload: the ModuleLibrary as JSON
+
+/// First four bytes of every module library file
+pub const LIBRARY_MAGIC: [u8; 4] = *b"EGML";
+
+/// Layout version written by this compiler; others are refused
+pub const LIBRARY_FORMAT_VERSION: u16 = 1;
+
+/// File extension `import` looks for in the search paths
+pub const LIBRARY_EXTENSION: &str = "egm";
+
+const HEADER_SIZE: usize = 16;
+
+/// An extension module stored as data: what it provides and the assembly behind it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModuleLibrary {
+    pub name: String,
+    pub description: String,
+    /// Version of the compiler that wrote the library
+    pub compiler_version: String,
+    pub functions: Vec<String>,
+    #[serde(default)]
+    pub signatures: Vec<FunctionSignature>,
+    pub dependencies: Vec<String>,
+    pub capabilities: Vec<Capability>,
+    /// Library code for each target the module supports
+    pub code: Vec<(Target, String)>,
+}
+
+impl ModuleLibrary {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            compiler_version: env!("CARGO_PKG_VERSION").to_string(),
+            functions: Vec::new(),
+            signatures: Vec::new(),
+            dependencies: Vec::new(),
+            capabilities: Vec::new(),
+            code: Vec::new(),
+        }
+    }
+
+    pub fn with_function(mut self, func: &str) -> Self {
+        self.functions.push(func.to_string());
+        self
+    }
+
+    /// Declare a function's parameters and result, providing it if it is not yet
+    pub fn with_signature(mut self, signature: FunctionSignature) -> Self {
+        if !self.functions.contains(&signature.name) {
+            self.functions.push(signature.name.clone());
+        }
+        self.signatures.push(signature);
+        self
+    }
+
+    pub fn with_dependency(mut self, module: &str) -> Self {
+        self.dependencies.push(module.to_string());
+        self
+    }
+
+    pub fn with_capability(mut self, capability: Capability) -> Self {
+        self.capabilities.push(capability);
+        self
+    }
+
+    pub fn with_code(mut self, target: Target, code: &str) -> Self {
+        self.code.push((target, code.to_string()));
+        self
+    }
+
+    /// Snapshot of `module` as it is currently initialised
+    pub fn from_module(module: &dyn EarthngModule) -> Self {
+        let mut library = Self::new(module.name(), module.description());
+        library.functions = module.functions().iter().map(|func| func.to_string()).collect();
+        library.signatures = module.signatures();
+        library.dependencies = module.dependencies().iter().map(|dep| dep.to_string()).collect();
+        library.capabilities = module.required_capabilities();
+        library.code = Target::ALL.iter()
+            .filter_map(|target| module.library_code(target).map(|code| (*target, code)))
+            .collect();
+        library
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let payload = serde_json::to_vec(self).expect("module libraries serialize to JSON");
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + payload.len());
+        bytes.extend_from_slice(&LIBRARY_MAGIC);
+        bytes.extend_from_slice(&LIBRARY_FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&fnv1a(&payload).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < HEADER_SIZE || bytes[0..4] != LIBRARY_MAGIC {
+            return Err("Not a module library".to_string());
+        }
+        let field = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != LIBRARY_FORMAT_VERSION {
+            return Err(format!("Module library format version {} is not supported (expected {})", version, LIBRARY_FORMAT_VERSION));
+        }
+        let (length, checksum) = (field(8) as usize, field(12));
+        let payload = &bytes[HEADER_SIZE..];
+        if payload.len() != length {
+            return Err(format!("Module library is truncated: {} of {} payload bytes", payload.len(), length));
+        }
+        if fnv1a(payload) != checksum {
+            return Err("Module library checksum mismatch".to_string());
+        }
+        serde_json::from_slice(payload).map_err(|e| format!("Invalid module library payload: {}", e))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_bytes())
+            .map_err(|e| format!("Failed to write module library {}: {}", path.display(), e))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("Failed to read module library {}: {}", path.display(), e))?;
+        Self::from_bytes(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// 32-bit FNV-1a, enough to notice a damaged file
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811C9DC5, |hash, byte| (hash ^ *byte as u32).wrapping_mul(0x01000193))
+}
+
+impl EarthngModule for ModuleLibrary {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn functions(&self) -> Vec<&str> {
+        self.functions.iter().map(|s| s.as_str()).collect()
+    }
+
+    fn signatures(&self) -> Vec<FunctionSignature> {
+        self.signatures.clone()
+    }
+
+    fn dependencies(&self) -> Vec<&str> {
+        self.dependencies.iter().map(|s| s.as_str()).collect()
+    }
+
+    fn required_capabilities(&self) -> Vec<Capability> {
+        self.capabilities.clone()
+    }
+
+    fn compile_function(
+        &self,
+        func: &str,
+        _args: &[Expr],
+        _target: &Target,
+        emitter: &mut dyn AssemblyEmitter
+    ) -> Result<String, String> {
+        Ok(emitter.emit_call(func, &[]))
+    }
+
+    fn library_code(&self, target: &Target) -> Option<String> {
+        self.code.iter().find(|(code_target, _)| code_target == target).map(|(_, code)| code.clone())
+    }
+
+    fn init(&mut self, _capabilities: &[Capability]) {
+        // The code was generated when the library was written
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extension::SerialModule;
+
+    #[test]
+    fn test_library_round_trip() {
+        let library = ModuleLibrary::from_module(&SerialModule::new());
+        assert_eq!(library.capabilities, vec![Capability::LongMode64, Capability::PortIO]);
+        assert_eq!(library.code.len(), 1);
+        assert_eq!(library.signatures.len(), library.functions.len());
+
+        let path = std::env::temp_dir().join(format!("earthang_serial_{}.{}", std::process::id(), LIBRARY_EXTENSION));
+        library.save(&path).unwrap();
+        let loaded = ModuleLibrary::load(&path);
+        let _ = std::fs::remove_file(&path);
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded, library);
+        assert_eq!(loaded.signature("serial_write_64").unwrap().to_string(), "serial_write_64(fd: int, bytes: ptr, count: int) -> void");
+        assert_eq!(loaded.library_code(&Target::Linux64), SerialModule::new().library_code(&Target::Linux64));
+        assert!(loaded.library_code(&Target::RiscV64).is_none());
+
+        let bytes = library.to_bytes();
+        assert_eq!(&bytes[0..8], b"EGML\x01\x00\x00\x00");
+        let error = ModuleLibrary::from_bytes(&bytes[..bytes.len() - 10]).unwrap_err();
+        assert_eq!(error, format!("Module library is truncated: {} of {} payload bytes", bytes.len() - 26, bytes.len() - 16));
+        let mut damaged = bytes.clone();
+        damaged[40] ^= 0x20;
+        assert_eq!(ModuleLibrary::from_bytes(&damaged).unwrap_err(), "Module library checksum mismatch");
+        assert_eq!(ModuleLibrary::from_bytes(b"MZ").unwrap_err(), "Not a module library");
+    }
+}
@@ -0,0 +1,187 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use crate::parser::{Expr, Op, UnaryOp};
+
+// Register allocation for integer expression trees on x86-64. The plain
+// lowering keeps every intermediate value in rax and pushes it around the
+// other operand; here each subtree is labelled with the number of registers
+// it needs (Sethi-Ullman numbering) and the hungrier side is evaluated
+// first, so `(a + b) * (c + d)` becomes
+//
+//     mov rax, QWORD PTR [rbp - 8]
+//     add rax, QWORD PTR [rbp - 16]
+//     mov rcx, QWORD PTR [rbp - 24]
+//     add rcx, QWORD PTR [rbp - 32]
+//     imul rax, rcx
+//
+// Variables and small constants on the right of an operator are used in
+// place. A subtree that needs more registers than are left is spilled to
+// the stack. Trees with division, calls or anything else that clobbers
+// registers are left to the plain lowering; their subtrees still qualify.
+
+/// Scratch registers in the order they are handed out. rdi and rsi stay
+/// free, since they carry arguments around the calls an expression sits in
+pub const REGISTERS: [&str; 7] = ["rax", "rcx", "rdx", "r8", "r9", "r10", "r11"];
+
+enum Node {
+    /// A memory or immediate operand; `direct` when an instruction can take
+    /// it as its second operand
+    Leaf { operand: String, direct: bool },
+    Unary(&'static str, Box<Node>),
+    Binary(&'static str, Box<Node>, Box<Node>),
+}
+
+/// Code leaving the value of `expr` in `registers[0]`, using only
+/// `registers` and the stack, or None when the tree is not made of integer
+/// arithmetic on operands `operand` can name. `operand` gives the memory
+/// operand of a variable
+pub fn compile_expression(expr: &Expr, registers: &[&str], operand: &dyn Fn(&str) -> Option<String>) -> Option<String> {
+    let node = lower(expr, operand)?;
+    // A single leaf or negation gains nothing over the plain lowering
+    if !matches!(node, Node::Binary(..)) || registers.is_empty() {
+        return None;
+    }
+    let mut code = String::from("    # Binary operation in registers\n");
+    emit(&node, registers, &mut code);
+    Some(code)
+}
+
+fn lower(expr: &Expr, operand: &dyn Fn(&str) -> Option<String>) -> Option<Node> {
+    match expr {
+        Expr::Number(n, _) => Some(Node::Leaf { operand: n.to_string(), direct: i32::try_from(*n).is_ok() }),
+        Expr::Boolean(b, _) => Some(Node::Leaf { operand: (*b as i32).to_string(), direct: true }),
+        Expr::Var(name, _) => operand(name).map(|operand| Node::Leaf { operand, direct: true }),
+        Expr::UnaryOp { op: UnaryOp::Plus, operand: inner, .. } => lower(inner, operand),
+        Expr::UnaryOp { op: UnaryOp::Minus, operand: inner, .. } => Some(Node::Unary("neg", Box::new(lower(inner, operand)?))),
+        Expr::UnaryOp { op: UnaryOp::Invert, operand: inner, .. } => Some(Node::Unary("not", Box::new(lower(inner, operand)?))),
+        Expr::BinOp { left, op, right, .. } => {
+            let instruction = match op {
+                Op::Add => "add",
+                Op::Sub => "sub",
+                Op::Mul => "imul",
+                Op::BitAnd => "and",
+                Op::BitOr => "or",
+                Op::BitXor => "xor",
+                _ => return None,
+            };
+            Some(Node::Binary(instruction, Box::new(lower(left, operand)?), Box::new(lower(right, operand)?)))
+        }
+        _ => None,
+    }
+}
+
+/// Registers `node` needs to be evaluated without spilling
+fn need(node: &Node) -> usize {
+    match node {
+        Node::Leaf { .. } => 1,
+        Node::Unary(_, inner) => need(inner),
+        Node::Binary(_, left, right) if matches!(right.as_ref(), Node::Leaf { direct: true, .. }) => need(left),
+        Node::Binary(_, left, right) => {
+            let (left, right) = (need(left), need(right));
+            if left == right { left + 1 } else { left.max(right) }
+        }
+    }
+}
+
+fn emit(node: &Node, registers: &[&str], code: &mut String) {
+    let target = registers[0];
+    match node {
+        Node::Leaf { operand, .. } => code.push_str(&format!("    mov {}, {}\n", target, operand)),
+        Node::Unary(instruction, inner) => {
+            emit(inner, registers, code);
+            code.push_str(&format!("    {} {}\n", instruction, target));
+        }
+        Node::Binary(instruction, left, right) => {
+            if let Node::Leaf { operand, direct: true } = right.as_ref() {
+                emit(left, registers, code);
+                code.push_str(&format!("    {} {}, {}\n", instruction, target, operand));
+            } else if registers.len() == 1 {
+                // Out of registers: the right side waits on the stack
+                emit(right, registers, code);
+                code.push_str(&format!("    push {}\n", target));
+                emit(left, registers, code);
+                code.push_str(&format!("    {} {}, QWORD PTR [rsp]\n", instruction, target));
+                code.push_str("    add rsp, 8\n");
+            } else if need(left) >= need(right) {
+                emit(left, registers, code);
+                emit(right, &registers[1..], code);
+                code.push_str(&format!("    {} {}, {}\n", instruction, target, registers[1]));
+            } else {
+                // The right side goes first, into the second register
+                let mut swapped = vec![registers[1], registers[0]];
+                swapped.extend_from_slice(&registers[2..]);
+                emit(right, &swapped, code);
+                let mut rest = vec![registers[0]];
+                rest.extend_from_slice(&registers[2..]);
+                emit(left, &rest, code);
+                code.push_str(&format!("    {} {}, {}\n", instruction, target, registers[1]));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Backend, Linux64Backend};
+    use crate::parser::parse_program;
+
+    fn expression(source: &str) -> Expr {
+        match parse_program(&format!("print({})\n", source)).unwrap().body.remove(0) {
+            crate::parser::Statement::Expr(Expr::Call { mut args, .. }) => args.remove(0),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    fn slot(name: &str) -> Option<String> {
+        let index = "abcdefgh".find(name)?;
+        Some(format!("QWORD PTR [rbp - {}]", 8 * (index + 1)))
+    }
+
+    #[test]
+    fn test_expressions_stay_in_registers() {
+        let code = compile_expression(&expression("(a + b) * (c + d)"), &REGISTERS, &slot).unwrap();
+        assert_eq!(code, "    # Binary operation in registers\n    mov rax, QWORD PTR [rbp - 8]\n    add rax, QWORD PTR [rbp - 16]\n    mov rcx, QWORD PTR [rbp - 24]\n    add rcx, QWORD PTR [rbp - 32]\n    imul rax, rcx\n");
+
+        // The deeper right side is evaluated first, and only as many
+        // registers as needed are touched
+        let code = compile_expression(&expression("a - (b * (c - d))"), &REGISTERS, &slot).unwrap();
+        assert!(code.contains("    sub rax, rcx\n") && !code.contains("rdx") && !code.contains("push"), "{}", code);
+
+        // Two registers are too few for three pending values
+        let wide = expression("((a + b) - (c + d)) * ((e + f) - (g + h))");
+        let code = compile_expression(&wide, &REGISTERS[..2], &slot).unwrap();
+        assert_eq!(code.matches("push rcx").count(), 1, "{}", code);
+        assert!(!compile_expression(&wide, &REGISTERS, &slot).unwrap().contains("push"));
+
+        for plain in ["a / b + c", "a + len(b)", "a + z", "-a", "a"] {
+            assert!(compile_expression(&expression(plain), &REGISTERS, &slot).is_none(), "{}", plain);
+        }
+    }
+
+    #[test]
+    fn test_register_allocation_shrinks_arithmetic() {
+        let source = "def f(a: int, b: int, c: int, d: int): {\n    return (a + b) * (c + d) - (a ^ 3) * -b\n}\nprint(f(1, 2, 3, 4))\n";
+        let program = parse_program(source).unwrap();
+        let optimized = Linux64Backend::new().with_register_allocation(true).compile_program(&program).unwrap();
+        let plain = Linux64Backend::new().compile_program(&program).unwrap();
+        let body = |assembly: &str| {
+            let start = assembly.find("\nfn_f:").unwrap();
+            assembly[start..].split("ret\n").next().unwrap().lines().filter(|line| !line.trim_start().starts_with('#')).count()
+        };
+        let function = &optimized[optimized.find("\nfn_f:").unwrap()..];
+        assert!(!function.split("ret\n").next().unwrap().contains("pop rax"));
+        assert!(body(&optimized) + 10 < body(&plain), "{} vs {}", body(&optimized), body(&plain));
+    }
+}
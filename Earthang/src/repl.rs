@@ -0,0 +1,193 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use colored::*;
+use crate::backend::Target;
+use crate::compiler::{CompilerConfig, EarthangCompiler};
+use crate::parser::Program;
+
+// Compiled code keeps no state between runs, so the REPL keeps the statements
+// instead. Every entry is appended to the accumulated program, which is
+// compiled for Linux64, linked and run from the start. Variables and functions
+// therefore carry over, and so does everything else the earlier entries do:
+// their input() calls and sleeps happen again on each run. Output is assumed
+// to be repeatable, so only what the program prints after the previous run's
+// output is shown. An entry that fails to parse, compile or run is dropped.
+
+/// Name parse errors are reported against
+const REPL_FILE: &str = "<repl>";
+
+pub struct Repl {
+    program: Program,
+    /// Everything the accumulated program printed on its last run
+    output: String,
+    last_assembly: Option<String>,
+    work_dir: PathBuf,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Self {
+            program: empty_program(),
+            output: String::new(),
+            last_assembly: None,
+            work_dir: std::env::temp_dir().join(format!("earthang_repl_{}", std::process::id())),
+        }
+    }
+
+    /// Run `snippet` after everything entered before, returning what it printed
+    pub fn evaluate(&mut self, snippet: &str) -> Result<String, String> {
+        let entry = crate::parser::parse_program(snippet).map_err(|errors| {
+            errors.iter().map(|error| error.render(REPL_FILE, snippet)).collect::<Vec<_>>().join("\n")
+        })?;
+        let mut program = self.program.clone();
+        program.body.extend(entry.body);
+
+        let config = CompilerConfig::default().with_target(Target::Linux64).with_hardware_dsl(false);
+        let result = EarthangCompiler::new(config).compile_program(program.clone(), None)?;
+        self.last_assembly = Some(result.assembly.clone());
+
+        std::fs::create_dir_all(&self.work_dir)
+            .map_err(|e| format!("Failed to create '{}': {}", self.work_dir.display(), e))?;
+        let executable = crate::compiler::assemble_and_link(&result.assembly, &self.work_dir.join("repl"), Target::Linux64, false)?;
+        let run = std::process::Command::new(&executable).output()
+            .map_err(|e| format!("Failed to run {}: {}", executable.display(), e))?;
+        let output = String::from_utf8_lossy(&run.stdout).into_owned();
+        if !run.status.success() {
+            return Err(format!("The program exited with {}\n{}{}", run.status, output, String::from_utf8_lossy(&run.stderr)));
+        }
+
+        let added = output.strip_prefix(self.output.as_str()).unwrap_or(&output).to_string();
+        self.program = program;
+        self.output = output;
+        Ok(added)
+    }
+
+    /// Forget every entry
+    pub fn reset(&mut self) {
+        self.program = empty_program();
+        self.output.clear();
+        self.last_assembly = None;
+    }
+
+    /// Assembly of the accumulated program as of the last entry that compiled
+    pub fn last_assembly(&self) -> Option<&str> {
+        self.last_assembly.as_deref()
+    }
+}
+
+impl Drop for Repl {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.work_dir);
+    }
+}
+
+fn empty_program() -> Program {
+    crate::parser::parse_program("").expect("an empty program parses")
+}
+
+/// Why snippets cannot be run here, if the assembler or linker is missing
+pub fn missing_toolchain() -> Option<String> {
+    let missing = crate::compiler::missing_linux_toolchain();
+    (!missing.is_empty()).then(|| format!(
+        "The REPL assembles and links every entry with GNU {}, which could not be run; install binutils or put the tools in ./bin",
+        missing.join(" and ")
+    ))
+}
+
+/// Read entries from `input` until `:quit` or the end of the input. A line
+/// ending in ':' or '{' opens a block that continues up to an empty line
+pub fn run(repl: &mut Repl, mut input: impl BufRead, mut output: impl Write) -> Result<(), String> {
+    let write_error = |e: std::io::Error| format!("Failed to write to the terminal: {}", e);
+    loop {
+        write!(output, "{} ", ">>>".cyan()).and_then(|_| output.flush()).map_err(write_error)?;
+        let Some(line) = read_line(&mut input)? else { break };
+        let mut snippet = match line.trim() {
+            "" => continue,
+            ":quit" | ":q" => break,
+            ":reset" => {
+                repl.reset();
+                writeln!(output, "{}", "Cleared every entry".dimmed()).map_err(write_error)?;
+                continue;
+            }
+            ":asm" => {
+                let text = repl.last_assembly().unwrap_or("No entry has compiled yet\n");
+                write!(output, "{}", text).map_err(write_error)?;
+                continue;
+            }
+            command if command.starts_with(':') => {
+                writeln!(output, "{}", format!("Unknown command {}; try :quit, :reset or :asm", command).yellow()).map_err(write_error)?;
+                continue;
+            }
+            _ => format!("{}\n", line),
+        };
+        if line.trim_end().ends_with(':') || line.trim_end().ends_with('{') {
+            loop {
+                write!(output, "{} ", "...".cyan()).and_then(|_| output.flush()).map_err(write_error)?;
+                match read_line(&mut input)? {
+                    Some(line) if !line.trim().is_empty() => snippet.push_str(&format!("{}\n", line)),
+                    _ => break,
+                }
+            }
+        }
+
+        match repl.evaluate(&snippet) {
+            Ok(printed) => write!(output, "{}", printed).map_err(write_error)?,
+            Err(e) => writeln!(output, "{}", e.red()).map_err(write_error)?,
+        }
+    }
+    Ok(())
+}
+
+fn read_line(input: &mut impl BufRead) -> Result<Option<String>, String> {
+    let mut line = String::new();
+    match input.read_line(&mut line) {
+        Ok(0) => Ok(None),
+        Ok(_) => Ok(Some(line.trim_end_matches(['\n', '\r']).to_string())),
+        Err(e) => Err(format!("Failed to read input: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repl_keeps_state_between_entries() {
+        let input = "var x = 20\nprint(x + 1)\nprint(x +\ndef twice(n): {\n    return n * 2\n}\n\nprint(twice(x))\n:frobnicate\n:reset\nprint(x)\nprint(3)\n:asm\n:quit\nprint(4)\n";
+        let mut repl = Repl::new();
+        let mut output = Vec::new();
+        run(&mut repl, std::io::Cursor::new(input), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        let shown: Vec<&str> = output.split(">>> ").map(str::trim_end).collect();
+        assert_eq!(shown[2], "21", "{}", output);
+        assert!(shown[3].contains("error:"), "{}", output);
+        assert_eq!(shown[4], "... ... ...", "{}", output);
+        // The earlier print is not repeated
+        assert_eq!(shown[5], "40", "{}", output);
+        assert!(shown[6].starts_with("Unknown command :frobnicate"), "{}", output);
+        assert!(shown[8].contains("Undefined variable 'x'"), "{}", output);
+        assert_eq!(shown[9], "3");
+        assert!(shown[10].contains("_start"), "{}", output);
+        assert_eq!(shown.len(), 12);
+    }
+}
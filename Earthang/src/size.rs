@@ -0,0 +1,233 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::collections::BTreeMap;
+use crate::backend::Target;
+use crate::parser::Statement;
+
+/// Comment a backend places before the code of each statement in a program or
+/// function body, so bytes can be traced back to source lines. Statements
+/// without a position get no marker and count towards the one before them
+pub(crate) fn statement_marker(comment: &str, stmt: &Statement) -> String {
+    match stmt.span().start.line {
+        0 => String::new(),
+        line => format!("    {} @line {}\n", comment, line),
+    }
+}
+
+/// Comment closing the last statement of a body; what follows is frame and runtime code
+pub(crate) fn body_end_marker(comment: &str) -> String {
+    format!("    {} @end\n", comment)
+}
+
+/// `assembly` with the source line of each statement marker quoted under it,
+/// for reading generated code next to the program it came from
+pub fn annotate_assembly(assembly: &str, source: &str) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut annotated = String::with_capacity(assembly.len());
+    for raw in assembly.lines() {
+        annotated.push_str(raw);
+        annotated.push('\n');
+        let text = raw.trim();
+        let Some((comment, marker)) = text.strip_prefix('#').map(|rest| ("#", rest))
+            .or_else(|| text.strip_prefix("//").map(|rest| ("//", rest))) else { continue };
+        let line = marker.trim().strip_prefix("@line ").and_then(|number| number.trim().parse::<usize>().ok());
+        if let Some(code) = line.and_then(|line| lines.get(line.wrapping_sub(1))) {
+            annotated.push_str(&format!("    {} {} | {}\n", comment, line.unwrap(), code.trim()));
+        }
+    }
+    annotated
+}
+
+/// Estimated size of the generated code and data, broken down by where it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct SizeReport {
+    pub total: usize,
+    /// Code bytes per source line holding a top-level or function-body statement
+    pub statements: BTreeMap<usize, usize>,
+    /// Bytes per string literal, including its terminator
+    pub strings: Vec<(String, usize)>,
+    /// Startup code, function frames, the runtime library and other data
+    pub runtime: usize,
+}
+
+impl SizeReport {
+    /// Walk the assembly of `target`, estimating every instruction and counting data directives exactly
+    pub fn measure(assembly: &str, target: &Target) -> Self {
+        let mut report = SizeReport { total: 0, statements: BTreeMap::new(), strings: Vec::new(), runtime: 0 };
+        let mut line = None;
+        let mut literal = None;
+
+        for raw in assembly.lines() {
+            let text = raw.trim();
+            if let Some(comment) = text.strip_prefix('#').or_else(|| text.strip_prefix("//")) {
+                match comment.trim().strip_prefix("@line ") {
+                    Some(number) => line = number.trim().parse().ok(),
+                    None if comment.trim() == "@end" => line = None,
+                    None => {}
+                }
+                continue;
+            }
+
+            // String pool entries are a label commented with the literal, then its bytes
+            let first = text.split_whitespace().next().unwrap_or("");
+            if let Some(label) = first.strip_suffix(':') {
+                let comment = text[first.len()..].trim().trim_start_matches('#').trim_start_matches("//").trim();
+                if label.starts_with("str_") && comment.starts_with('"') {
+                    literal = Some(comment.to_string());
+                }
+                continue;
+            }
+
+            let bytes = match data_bytes(text) {
+                Some(bytes) => {
+                    if let Some(name) = literal.take() {
+                        report.strings.push((name, bytes));
+                        report.total += bytes;
+                        continue;
+                    }
+                    bytes
+                }
+                None => instruction_bytes(text, target),
+            };
+
+            report.total += bytes;
+            match line {
+                Some(line) => *report.statements.entry(line).or_default() += bytes,
+                None => report.runtime += bytes,
+            }
+        }
+        report
+    }
+
+    /// Breakdown listing the `max_items` biggest statements and strings; `source` supplies line text
+    pub fn render(&self, source: &str, max_items: usize) -> String {
+        let mut output = format!("Estimated size: {} bytes\n", self.total);
+
+        let mut statements: Vec<_> = self.statements.iter().collect();
+        statements.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        if !statements.is_empty() {
+            output.push_str("  Statements:\n");
+            for (line, bytes) in statements.iter().take(max_items) {
+                let text = source.lines().nth(**line - 1).unwrap_or("").trim();
+                output.push_str(&format!("    line {:<5} {:>6} bytes  {}\n", line, bytes, text));
+            }
+        }
+
+        let mut strings: Vec<_> = self.strings.iter().collect();
+        strings.sort_by_key(|(_, bytes)| std::cmp::Reverse(*bytes));
+        if !strings.is_empty() {
+            output.push_str("  String data:\n");
+            for (literal, bytes) in strings.iter().take(max_items) {
+                output.push_str(&format!("    {:>17} bytes  {}\n", bytes, literal));
+            }
+        }
+
+        output.push_str(&format!("  Startup, frames and runtime: {} bytes\n", self.runtime));
+        output
+    }
+}
+
+/// Exact size of a data directive, `None` for anything else
+fn data_bytes(text: &str) -> Option<usize> {
+    let (directive, operands) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let count = || operands.split(',').filter(|o| !o.trim().is_empty()).count();
+    let quoted = || {
+        // Every escape sequence stands for one byte
+        let inner = operands.trim().trim_matches('"');
+        inner.chars().count() - inner.matches('\\').count()
+    };
+
+    match directive {
+        ".byte" => Some(count()),
+        ".word" | ".short" | ".hword" | ".2byte" => Some(2 * count()),
+        ".long" | ".int" | ".4byte" => Some(4 * count()),
+        ".quad" | ".8byte" | ".xword" | ".dword" => Some(8 * count()),
+        ".zero" | ".skip" | ".space" => operands.split(',').next()?.trim().parse().ok(),
+        ".ascii" => Some(quoted()),
+        ".asciz" | ".string" => Some(quoted() + 1),
+        _ => None,
+    }
+}
+
+/// Encoded size of one instruction, 0 for labels, directives and blank lines.
+/// x86-64 lengths are typical encodings of each form rather than exact ones
+fn instruction_bytes(text: &str, target: &Target) -> usize {
+    let code = text.split('#').next().unwrap_or("").trim();
+    if code.is_empty() || code.starts_with('.') || code.ends_with(':') || code.starts_with(';') {
+        return 0;
+    }
+
+    let (mnemonic, operands) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+    let immediate = operands.rsplit(',').next()
+        .map(|o| o.trim().trim_start_matches('#'))
+        .and_then(|o| o.parse::<i64>().ok().or_else(|| i64::from_str_radix(o.strip_prefix("0x")?, 16).ok()));
+
+    match target {
+        Target::Linux64 => match mnemonic {
+            "ret" | "leave" | "cqo" | "nop" => 1,
+            // r8-r15 need a REX prefix
+            "push" | "pop" if !operands.contains('[') => 1 + operands.trim().trim_start_matches('r').starts_with(char::is_numeric) as usize,
+            "syscall" => 2,
+            "call" | "jmp" => 5,
+            m if m.starts_with('j') => 6,
+            "mov" if immediate.is_some_and(|n| i32::try_from(n).is_err()) => 10,
+            "mov" if immediate.is_some() => 7,
+            _ if operands.contains("rip") => 7,
+            _ if operands.contains('[') => 4,
+            _ => 3,
+        },
+        Target::RiscV64 => match mnemonic {
+            "li" if immediate.is_some_and(|n| (-2048..2048).contains(&n)) => 4,
+            "li" if immediate.is_some_and(|n| i32::try_from(n).is_ok()) => 8,
+            "li" => 24,
+            "la" | "call" | "tail" => 8,
+            _ => 4,
+        },
+        Target::Aarch64 => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_attribution() {
+        let assembly = "main:\n    push rbp\n    # @line 2\n    mov rax, 14\n    call print_int\n    # @end\n    ret\n    .section .data\nstr_0001:  # \"hi\"\n    .byte 0x68, 0x69, 0x00\n";
+        let report = SizeReport::measure(assembly, &Target::Linux64);
+        assert_eq!(report.statements.get(&2), Some(&12));
+        assert_eq!(report.strings, vec![("\"hi\"".to_string(), 3)]);
+        assert_eq!(report.runtime, 2);
+        assert_eq!(report.total, 17);
+        assert!(report.render("var x = 1\nprint(14)\n", 5).contains("line 2         12 bytes  print(14)"));
+    }
+
+    /// Every backend marks each top-level statement once, so annotating quotes each line once
+    #[test]
+    fn test_annotated_assembly() {
+        let source = "var total = 0\nvar step = 2\nfor i in range(4):\n    total = total + i * step\nend\nprint(total)\nstep += 1\nprint(\"done\")\n";
+        for target in [Target::Linux64, Target::RiscV64, Target::Aarch64] {
+            let config = crate::compiler::CompilerConfig::default().with_hardware_dsl(false).with_target(target);
+            let assembly = crate::compiler::EarthangCompiler::new(config).compile_source(source, None).unwrap().assembly;
+            let comment = if target == Target::Aarch64 { "//" } else { "#" };
+            let annotated = annotate_assembly(&assembly, source);
+            // Top-level statements are the lines that are neither indented nor closing a block
+            for (index, text) in source.lines().enumerate().filter(|(_, text)| !text.starts_with([' ', '}']) && *text != "end") {
+                let quoted = format!("    {} {} | {}\n", comment, index + 1, text.trim());
+                assert_eq!(annotated.matches(&quoted).count(), 1, "{:?} on {:?}:\n{}", quoted, target, annotated);
+            }
+            assert_eq!(annotated.lines().filter(|line| !line.contains(" | ")).collect::<Vec<_>>(), assembly.lines().collect::<Vec<_>>());
+        }
+    }
+}
@@ -0,0 +1,242 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::path::Path;
+
+// A symbol map is tab-separated text, one symbol per line after the header:
+//
+//   address  offset  size  kind  phase  name
+//
+// `address` is where the byte runs in memory and `offset` where it is in the
+// image file, both in hex; `size` is decimal. Lines starting with '#' are comments.
+
+/// First line of every map written by `to_tsv`
+pub const MAP_HEADER: &str = "# earthang symbol map v1";
+
+/// What a map entry covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// A whole part of the image: the boot sector, the transition code, the payload or its data
+    Region,
+    /// One `TransitionPhase` of the mode transition
+    Phase,
+    Code,
+    String,
+    Data,
+}
+
+impl SymbolKind {
+    const ALL: [SymbolKind; 5] = [SymbolKind::Region, SymbolKind::Phase, SymbolKind::Code, SymbolKind::String, SymbolKind::Data];
+
+    fn label(&self) -> &'static str {
+        match self {
+            SymbolKind::Region => "region",
+            SymbolKind::Phase => "phase",
+            SymbolKind::Code => "code",
+            SymbolKind::String => "string",
+            SymbolKind::Data => "data",
+        }
+    }
+}
+
+impl std::fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// A label read from an assembled object, at the address it runs at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectSymbol {
+    pub name: String,
+    pub address: u64,
+    /// Defined in a data section rather than in code
+    pub data: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapSymbol {
+    pub address: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub kind: SymbolKind,
+    /// Boot stage or transition phase the bytes belong to
+    pub phase: String,
+    pub name: String,
+}
+
+impl MapSymbol {
+    fn contains(&self, address: u64) -> bool {
+        address >= self.address && address < self.address + self.size.max(1)
+    }
+}
+
+/// Labels, phases and regions of a flat image with where they sit in memory and in the file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolMap {
+    pub symbols: Vec<MapSymbol>,
+}
+
+impl SymbolMap {
+    /// Add a region of `size` bytes running at `address` and stored at `offset`,
+    /// with `symbols` inside it. Each symbol extends to the next one or the region's end
+    pub fn add_region(&mut self, name: &str, address: u64, size: u64, offset: u64, symbols: &[ObjectSymbol]) {
+        self.symbols.push(MapSymbol { address, offset, size, kind: SymbolKind::Region, phase: name.to_string(), name: name.to_string() });
+
+        let end = address + size;
+        let mut inside: Vec<&ObjectSymbol> = symbols.iter().filter(|symbol| symbol.address >= address && symbol.address < end).collect();
+        inside.sort_by_key(|symbol| symbol.address);
+        for symbol in &inside {
+            let next = inside.iter().map(|other| other.address).find(|other| *other > symbol.address).unwrap_or(end);
+            let kind = match (symbol.data, symbol.name.starts_with("str_")) {
+                (false, _) => SymbolKind::Code,
+                (true, true) => SymbolKind::String,
+                (true, false) => SymbolKind::Data,
+            };
+            self.symbols.push(MapSymbol {
+                address: symbol.address,
+                offset: offset + symbol.address - address,
+                size: next - symbol.address,
+                kind,
+                phase: name.to_string(),
+                name: symbol.name.clone(),
+            });
+        }
+    }
+
+    /// Add a transition phase, moving the symbols it contains into it
+    pub fn add_phase(&mut self, name: &str, address: u64, size: u64, offset: u64) {
+        for symbol in &mut self.symbols {
+            if symbol.kind != SymbolKind::Region && symbol.address >= address && symbol.address < address + size {
+                symbol.phase = name.to_string();
+            }
+        }
+        self.symbols.push(MapSymbol { address, offset, size, kind: SymbolKind::Phase, phase: name.to_string(), name: name.to_string() });
+    }
+
+    /// The innermost entry covering `address` with the distance into it: a label,
+    /// else the phase, else the region
+    pub fn resolve(&self, address: u64) -> Option<(&MapSymbol, u64)> {
+        [SymbolKind::Code, SymbolKind::String, SymbolKind::Data, SymbolKind::Phase, SymbolKind::Region].iter()
+            .find_map(|kind| self.symbols.iter()
+                .filter(|symbol| symbol.kind == *kind && symbol.contains(address))
+                .max_by_key(|symbol| symbol.address))
+            .map(|symbol| (symbol, address - symbol.address))
+    }
+
+    pub fn to_tsv(&self) -> String {
+        let mut symbols: Vec<&MapSymbol> = self.symbols.iter().collect();
+        symbols.sort_by_key(|symbol| (symbol.address, symbol.kind != SymbolKind::Region, symbol.kind != SymbolKind::Phase));
+        let mut text = format!("{}\n# address\toffset\tsize\tkind\tphase\tname\n", MAP_HEADER);
+        for symbol in symbols {
+            text.push_str(&format!(
+                "0x{:08X}\t0x{:08X}\t{}\t{}\t{}\t{}\n",
+                symbol.address, symbol.offset, symbol.size, symbol.kind, symbol.phase, symbol.name
+            ));
+        }
+        text
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let hex = |field: &str, line: usize| u64::from_str_radix(field.trim_start_matches("0x"), 16)
+            .map_err(|_| format!("'{}' is not a hex number at line {}", field, line));
+        let mut symbols = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let number = index + 1;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [address, offset, size, kind, phase, name] = fields[..] else {
+                return Err(format!("Expected 6 tab-separated fields but found {} at line {}", fields.len(), number));
+            };
+            symbols.push(MapSymbol {
+                address: hex(address, number)?,
+                offset: hex(offset, number)?,
+                size: size.parse().map_err(|_| format!("'{}' is not a size at line {}", size, number))?,
+                kind: SymbolKind::ALL.iter().copied().find(|known| known.label() == kind)
+                    .ok_or_else(|| format!("Unknown symbol kind '{}' at line {}", kind, number))?,
+                phase: phase.to_string(),
+                name: name.to_string(),
+            });
+        }
+        Ok(Self { symbols })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_tsv())
+            .map_err(|e| format!("Failed to write symbol map {}: {}", path.display(), e))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read symbol map {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// Defined labels of an object or linked file, from `nm`, moved by `base`
+pub(crate) fn object_symbols(object: &Path, base: u64) -> Result<Vec<ObjectSymbol>, String> {
+    let listing = crate::compiler::tool_output("nm", &["--defined-only".as_ref(), object.as_os_str()])?;
+    Ok(listing.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let address = u64::from_str_radix(fields.next()?, 16).ok()?;
+            let kind = fields.next()?.chars().next()?;
+            let name = fields.next()?;
+            let data = match kind.to_ascii_lowercase() {
+                't' => false,
+                'd' | 'r' | 'b' => true,
+                _ => return None,
+            };
+            Some(ObjectSymbol { name: name.to_string(), address: base + address, data })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_resolves_addresses() {
+        let symbol = |name: &str, address: u64, data: bool| ObjectSymbol { name: name.to_string(), address, data };
+        let mut map = SymbolMap::default();
+        map.add_region("stage1", 0x7C00, 0x80, 0, &[symbol("_start", 0x7C00, false), symbol("read_sectors", 0x7C40, false)]);
+        map.add_region("transition", 0x7E00, 0x100, 0x200, &[symbol("gdt", 0x7EC0, false), symbol("far_jump", 0x7E30, false)]);
+        map.add_phase("long_mode", 0x7E20, 0x40, 0x220);
+        map.add_region("data", 0x9000, 0x20, 0x1400, &[symbol("str_0", 0x9000, true), symbol("fb_info", 0x9010, true)]);
+
+        let (found, distance) = map.resolve(0x7C63).unwrap();
+        assert_eq!((found.name.as_str(), distance, found.size, found.offset), ("read_sectors", 0x23, 0x40, 0x40));
+        let (found, distance) = map.resolve(0x7E21).unwrap();
+        assert_eq!((found.kind, found.name.as_str(), distance), (SymbolKind::Phase, "long_mode", 1));
+        let (found, _) = map.resolve(0x7E31).unwrap();
+        assert_eq!((found.name.as_str(), found.phase.as_str()), ("far_jump", "long_mode"));
+        assert_eq!(map.resolve(0x7E10).unwrap().0.kind, SymbolKind::Region);
+        assert_eq!(map.resolve(0x9004).unwrap().0.kind, SymbolKind::String);
+        assert_eq!(map.resolve(0x9012).unwrap().0.kind, SymbolKind::Data);
+        assert!(map.resolve(0x8000).is_none());
+
+        let text = map.to_tsv();
+        assert!(text.starts_with(MAP_HEADER));
+        assert!(text.contains("0x00007C40\t0x00000040\t64\tcode\tstage1\tread_sectors\n"), "{}", text);
+        let parsed = SymbolMap::parse(&text).unwrap();
+        assert_eq!(parsed.symbols.len(), map.symbols.len());
+        assert_eq!(parsed.resolve(0x7C63).unwrap().0.name, "read_sectors");
+        assert_eq!(SymbolMap::parse("0x10\t0x0\t4\tcode\tstage1\n").unwrap_err(), "Expected 6 tab-separated fields but found 5 at line 1");
+        assert_eq!(SymbolMap::parse("0x10\t0x0\t4\tfunc\tstage1\tx\n").unwrap_err(), "Unknown symbol kind 'func' at line 1");
+    }
+}
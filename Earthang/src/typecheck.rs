@@ -0,0 +1,717 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::collections::{HashMap, HashSet};
+use crate::lua_frontend::{compare_text, op_text};
+use crate::parser::{BoolOp, CompareOp, Expr, FStringPart, Op, Program, Span, Statement, UnaryOp};
+
+// Checks types before code generation. An expression's type is worked out
+// from literals, annotations, builtins and what functions return; where it
+// cannot be, the expression has no type and nothing about it is checked, so
+// code without annotations compiles as before (gradual typing).
+//
+// - A variable annotated with `var x: T` holds a T wherever it is assigned
+//   in its function. One without an annotation has the type of everything
+//   assigned to it while that is always the same type.
+// - Arguments are checked against annotated parameters and returned values
+//   against `-> T`. A function without `-> T` returns the type all its
+//   returns share, None included when it can fall off its end.
+// - Operators must accept the operand types, following the interpreter,
+//   except that strings and numbers are not compared with `==`: compiled
+//   code would compare the string's address.
+//
+// The return types found are written into the functions, where the Linux64
+// backend reads them, with the parameter annotations, to tell strings,
+// dicts and structs from integers. `strict` requires every function to
+// annotate its parameters and its return type.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    Int,
+    Float,
+    Str,
+    Bool,
+    List,
+    Dict,
+    None,
+    Function,
+    Struct(String),
+}
+
+impl Type {
+    fn is_number(&self) -> bool {
+        matches!(self, Type::Int | Type::Float | Type::Bool)
+    }
+
+    /// Whether a variable of this type may hold `value`: booleans count as
+    /// integers, and both as floats
+    pub fn accepts(&self, value: &Type) -> bool {
+        self == value || matches!((self, value), (Type::Int, Type::Bool) | (Type::Float, Type::Int | Type::Bool))
+    }
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Type::Int => "int",
+            Type::Float => "float",
+            Type::Str => "str",
+            Type::Bool => "bool",
+            Type::List => "list",
+            Type::Dict => "dict",
+            Type::None => "None",
+            Type::Function => "function",
+            Type::Struct(name) => name,
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What builtins return, where that does not depend on the arguments
+fn builtin_type(func: &str) -> Option<Type> {
+    match func {
+        "len" => Some(Type::Int),
+        "str" | "type" | "input" | "read" | "replace" => Some(Type::Str),
+        "range" => Some(Type::List),
+        "print" | "append" => Some(Type::None),
+        _ => None,
+    }
+}
+
+/// The result of `left op right`, `Err` when the operator rejects the types
+fn binary_type(op: &Op, left: &Type, right: &Type) -> Result<Option<Type>, ()> {
+    match (op, left, right) {
+        (Op::Add, Type::Str, Type::Str) | (Op::Mul, Type::Str, Type::Int) | (Op::Mul, Type::Int, Type::Str) => {
+            Ok(Some(Type::Str))
+        }
+        (Op::Add, Type::List, Type::List) => Ok(Some(Type::List)),
+        _ if !left.is_number() || !right.is_number() => Err(()),
+        (Op::BitAnd | Op::BitOr | Op::BitXor, _, _) if *left == Type::Float || *right == Type::Float => Err(()),
+        _ if *left == Type::Float || *right == Type::Float => Ok(Some(Type::Float)),
+        // A negative exponent gives a float
+        (Op::Pow, _, _) => Ok(None),
+        _ => Ok(Some(Type::Int)),
+    }
+}
+
+/// Join `found` into what is known of a variable or result so far: one type
+/// while every value has it, and no type once two differ
+fn join(known: &mut HashMap<String, Option<Type>>, name: &str, found: Option<Type>) -> bool {
+    match known.get(name) {
+        Some(None) => false,
+        Some(Some(current)) if Some(current) == found.as_ref() => false,
+        Some(Some(_)) => {
+            known.insert(name.to_string(), None);
+            true
+        }
+        None => {
+            known.insert(name.to_string(), found);
+            true
+        }
+    }
+}
+
+/// Whether `expr` calls a function `pending` holds for
+fn calls(expr: &Expr, pending: &impl Fn(&str) -> bool) -> bool {
+    match expr {
+        Expr::Call { func, args, .. } => pending(func) || args.iter().any(|arg| calls(arg, pending)),
+        Expr::BinOp { left, right, .. } | Expr::Index { value: left, index: right, .. } => calls(left, pending) || calls(right, pending),
+        Expr::UnaryOp { operand, .. } | Expr::FieldAccess { object: operand, .. } => calls(operand, pending),
+        Expr::Slice { value, start, stop, .. } => {
+            calls(value, pending) || [start, stop].into_iter().flatten().any(|bound| calls(bound, pending))
+        }
+        Expr::BoolOp { values, .. } | Expr::List { elements: values, .. } => values.iter().any(|value| calls(value, pending)),
+        Expr::MethodCall { receiver, args, .. } => calls(receiver, pending) || args.iter().any(|arg| calls(arg, pending)),
+        _ => false,
+    }
+}
+
+/// Whether running `body` always reaches a `return`
+fn always_returns(body: &[Statement]) -> bool {
+    match body.last() {
+        Some(Statement::Return(..)) => true,
+        Some(Statement::If { then_block, elif_blocks, else_block: Some(else_block), .. }) => {
+            always_returns(then_block) && elif_blocks.iter().all(|(_, block)| always_returns(block)) && always_returns(else_block)
+        }
+        _ => false,
+    }
+}
+
+/// Parameters and result of a user function, `None` where not known
+#[derive(Debug, Clone)]
+struct Signature {
+    args: Vec<Option<Type>>,
+    returns: Option<Type>,
+    /// Whether `returns` is an annotation the returns are checked against
+    declared: bool,
+    span: Span,
+}
+
+/// Variables of one function body or of the top level
+#[derive(Default)]
+struct Scope {
+    /// Annotated variables and parameters with where they were annotated
+    declared: HashMap<String, (Type, Span)>,
+    inferred: HashMap<String, Option<Type>>,
+    /// Return annotation of the function the scope is the body of
+    returns: Option<(String, Type)>,
+}
+
+impl Scope {
+    fn variable(&self, name: &str) -> Option<Option<Type>> {
+        match self.declared.get(name) {
+            Some((declared, _)) => Some(Some(declared.clone())),
+            None => self.inferred.get(name).cloned(),
+        }
+    }
+}
+
+struct Checker {
+    structs: HashSet<String>,
+    functions: HashMap<String, Signature>,
+}
+
+/// Check `program`'s types and record the return types found on its functions
+pub fn check_types(program: &mut Program, strict: bool) -> Result<(), String> {
+    let structs = program.body.iter()
+        .filter_map(|stmt| match stmt {
+            Statement::StructDef { name, .. } => Some(name.clone()),
+            _ => None,
+        })
+        .collect();
+    let mut checker = Checker { structs, functions: HashMap::new() };
+
+    // Annotations first, so calls can be checked against functions defined below them
+    for stmt in &program.body {
+        let Statement::FunctionDef { name, args, arg_types, return_type, span, .. } = stmt else { continue };
+        if strict {
+            if let Some((arg, _)) = args.iter().zip(arg_types).find(|(_, hint)| hint.is_none()) {
+                return Err(format!("Parameter '{}' of '{}' needs a type annotation with --strict-types at {}", arg, name, span));
+            }
+            if return_type.is_none() {
+                return Err(format!("Function '{}' needs a return type annotation with --strict-types at {}", name, span));
+            }
+        }
+        let args = arg_types.iter()
+            .map(|hint| hint.as_deref().map(|hint| checker.annotation(hint, *span)).transpose())
+            .collect::<Result<_, _>>()?;
+        let returns = return_type.as_deref().map(|hint| checker.annotation(hint, *span)).transpose()?;
+        let declared = returns.is_some();
+        checker.functions.insert(name.clone(), Signature { args, returns, declared, span: *span });
+    }
+
+    // Then what the others return, until that stops changing. A value calling
+    // a function nothing is known about yet is left for a later round, so
+    // recursive functions get a type from their other returns
+    let mut returns: HashMap<String, Option<Type>> = HashMap::new();
+    loop {
+        let mut changed = false;
+        for stmt in &program.body {
+            let Statement::FunctionDef { name, args, body, return_type: None, .. } = stmt else { continue };
+            let scope = checker.scope(name, args, body)?;
+            let pending = |func: &str| checker.functions.get(func).is_some_and(|signature| !signature.declared) && !returns.contains_key(func);
+            let found: Vec<_> = checker.return_types(body, &scope).into_iter()
+                .filter(|(value, found)| found.is_some() || !value.is_some_and(|value| calls(value, &pending)))
+                .map(|(_, found)| found)
+                .collect();
+            for found in found {
+                changed |= join(&mut returns, name, found);
+            }
+            if !always_returns(body) {
+                changed |= join(&mut returns, name, Some(Type::None));
+            }
+            if let Some(signature) = checker.functions.get_mut(name) {
+                signature.returns = returns.get(name).cloned().flatten();
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let top_level = checker.scope_of(&[], None, &program.body)?;
+    checker.check_block(&program.body, &top_level)?;
+    for stmt in &mut program.body {
+        if let Statement::FunctionDef { name, return_type: return_type @ None, .. } = stmt {
+            *return_type = checker.functions[name.as_str()].returns.as_ref().map(Type::to_string);
+        }
+    }
+    Ok(())
+}
+
+impl Checker {
+    /// The type an annotation names
+    fn annotation(&self, name: &str, span: Span) -> Result<Type, String> {
+        Ok(match name {
+            "int" => Type::Int,
+            "float" => Type::Float,
+            "str" | "string" => Type::Str,
+            "bool" => Type::Bool,
+            "list" => Type::List,
+            "dict" => Type::Dict,
+            "None" => Type::None,
+            "function" => Type::Function,
+            _ if self.structs.contains(name) => Type::Struct(name.to_string()),
+            _ => return Err(format!("Unknown type '{}' at {}", name, span)),
+        })
+    }
+
+    /// The scope of user function `name`'s body
+    fn scope(&self, name: &str, args: &[String], body: &[Statement]) -> Result<Scope, String> {
+        self.scope_of(args, Some((name, &self.functions[name])), body)
+    }
+
+    /// The scope of `body`, the body of the function `signature` names if any
+    fn scope_of(&self, args: &[String], signature: Option<(&str, &Signature)>, body: &[Statement]) -> Result<Scope, String> {
+        let mut scope = Scope::default();
+        for (index, arg) in args.iter().enumerate() {
+            match signature.and_then(|(_, signature)| Some((signature.args[index].clone()?, signature.span))) {
+                Some(declared) => {
+                    scope.declared.insert(arg.clone(), declared);
+                }
+                None => {
+                    scope.inferred.insert(arg.clone(), None);
+                }
+            }
+        }
+        if let Some((name, signature)) = signature.filter(|(_, signature)| signature.declared) {
+            scope.returns = signature.returns.clone().map(|returns| (name.to_string(), returns));
+        }
+        self.declare_block(body, &mut scope)?;
+        // Every assignment again until no variable's type changes
+        while self.infer_block(body, &mut scope) {}
+        Ok(scope)
+    }
+
+    fn declare_block(&self, body: &[Statement], scope: &mut Scope) -> Result<(), String> {
+        for stmt in body {
+            match stmt {
+                Statement::VarDecl { name, type_hint: Some(hint), span, .. } => {
+                    let declared = self.annotation(hint, *span)?;
+                    match scope.declared.get(name) {
+                        Some((first, at)) if *first != declared => {
+                            return Err(format!("'{}' was declared {} at {} and is redeclared {} at {}", name, first, at, declared, span));
+                        }
+                        Some(_) => {}
+                        None => {
+                            scope.declared.insert(name.clone(), (declared, *span));
+                        }
+                    }
+                }
+                _ => self.for_each_block(stmt, |block| self.declare_block(block, scope))?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply `f` to the blocks nested in `stmt` that belong to the same scope
+    fn for_each_block<'a>(&self, stmt: &'a Statement, mut f: impl FnMut(&'a [Statement]) -> Result<(), String>) -> Result<(), String> {
+        match stmt {
+            Statement::If { then_block, elif_blocks, else_block, .. } => {
+                f(then_block)?;
+                for (_, block) in elif_blocks {
+                    f(block)?;
+                }
+                else_block.as_deref().map_or(Ok(()), f)
+            }
+            Statement::While { body, orelse, .. } => {
+                f(body)?;
+                orelse.as_deref().map_or(Ok(()), f)
+            }
+            Statement::For { body, .. } => f(body),
+            _ => Ok(()),
+        }
+    }
+
+    /// Join the type of every assignment in `body` into its variable; whether any changed
+    fn infer_block(&self, body: &[Statement], scope: &mut Scope) -> bool {
+        let mut changed = false;
+        for stmt in body {
+            let (name, found) = match stmt {
+                Statement::VarDecl { name, value, .. } | Statement::Assign { target: name, value, .. } => (name, self.type_of(value, scope)),
+                Statement::AugAssign { target, op, value, .. } => {
+                    let found = match (scope.variable(target).flatten(), self.type_of(value, scope)) {
+                        (Some(left), Some(right)) => binary_type(op, &left, &right).unwrap_or(None),
+                        _ => None,
+                    };
+                    (target, found)
+                }
+                // The parser only takes `for var in range(...)`
+                Statement::For { var, .. } => (var, Some(Type::Int)),
+                _ => {
+                    let _ = self.for_each_block(stmt, |block| {
+                        changed |= self.infer_block(block, scope);
+                        Ok(())
+                    });
+                    continue;
+                }
+            };
+            if !scope.declared.contains_key(name) {
+                changed |= join(&mut scope.inferred, name, found);
+            }
+            if let Statement::For { body, .. } = stmt {
+                changed |= self.infer_block(body, scope);
+            }
+        }
+        changed
+    }
+
+    /// The values `body` returns with their types, `None` for those not known
+    fn return_types<'a>(&self, body: &'a [Statement], scope: &Scope) -> Vec<(Option<&'a Expr>, Option<Type>)> {
+        let mut found = Vec::new();
+        for stmt in body {
+            match stmt {
+                Statement::Return(Some(value), _) => found.push((Some(value), self.type_of(value, scope))),
+                Statement::Return(None, _) => found.push((None, Some(Type::None))),
+                _ => {
+                    let _ = self.for_each_block(stmt, |block| {
+                        found.extend(self.return_types(block, scope));
+                        Ok(())
+                    });
+                }
+            }
+        }
+        found
+    }
+
+    /// The type of `expr`, `None` when it is not known or the expression is rejected
+    fn type_of(&self, expr: &Expr, scope: &Scope) -> Option<Type> {
+        self.check_expr(expr, scope).unwrap_or(None)
+    }
+
+    fn check_block(&self, body: &[Statement], scope: &Scope) -> Result<(), String> {
+        for stmt in body {
+            self.check_statement(stmt, scope)?;
+        }
+        Ok(())
+    }
+
+    fn check_assignment(&self, name: &str, found: Option<Type>, scope: &Scope, span: Span) -> Result<(), String> {
+        match (scope.declared.get(name), found) {
+            (Some((declared, _)), Some(found)) if !declared.accepts(&found) => {
+                Err(format!("'{}' is declared {} but assigned {} at {}", name, declared, found, span))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_statement(&self, stmt: &Statement, scope: &Scope) -> Result<(), String> {
+        match stmt {
+            Statement::VarDecl { name, value, span, .. } | Statement::Assign { target: name, value, span } => {
+                let found = self.check_expr(value, scope)?;
+                self.check_assignment(name, found, scope, *span)
+            }
+            Statement::AugAssign { target, op, value, span } => {
+                let right = self.check_expr(value, scope)?;
+                let found = match (scope.variable(target).flatten(), right) {
+                    (Some(left), Some(right)) => binary_type(op, &left, &right).map_err(|_| {
+                        format!("Unsupported operand types for {}: {} and {} at {}", op_text(op), left, right, span)
+                    })?,
+                    _ => None,
+                };
+                self.check_assignment(target, found, scope, *span)
+            }
+            Statement::IndexAssign { target, index, value, span } => {
+                self.check_indexable(target, scope, *span)?;
+                self.check_expr(index, scope)?;
+                self.check_expr(value, scope).map(|_| ())
+            }
+            Statement::FieldAssign { target, field, value, span } => {
+                self.check_field(target, field, scope, *span)?;
+                self.check_expr(value, scope).map(|_| ())
+            }
+            Statement::Expr(expr) => self.check_expr(expr, scope).map(|_| ()),
+            Statement::Return(value, span) => {
+                let found = match value {
+                    Some(value) => self.check_expr(value, scope)?,
+                    None => Some(Type::None),
+                };
+                match (&scope.returns, found) {
+                    (Some((name, declared)), Some(found)) if !declared.accepts(&found) => {
+                        Err(format!("'{}' is declared to return {} but returns {} at {}", name, declared, found, span))
+                    }
+                    _ => Ok(()),
+                }
+            }
+            Statement::If { condition, elif_blocks, .. } => {
+                self.check_expr(condition, scope)?;
+                for (condition, _) in elif_blocks {
+                    self.check_expr(condition, scope)?;
+                }
+                self.for_each_block(stmt, |block| self.check_block(block, scope))
+            }
+            Statement::While { condition, .. } => {
+                self.check_expr(condition, scope)?;
+                self.for_each_block(stmt, |block| self.check_block(block, scope))
+            }
+            Statement::For { iter, .. } => {
+                self.check_expr(iter, scope)?;
+                self.for_each_block(stmt, |block| self.check_block(block, scope))
+            }
+            Statement::FunctionDef { name, args, body, .. } if self.functions.contains_key(name) => {
+                self.check_block(body, &self.scope(name, args, body)?)
+            }
+            // Nested functions and device functions, which nothing is known about
+            Statement::FunctionDef { args, body, .. } | Statement::HardwareFunctionDef { args, body, .. } => {
+                self.check_block(body, &self.scope_of(args, None, body)?)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_indexable(&self, value: &Expr, scope: &Scope, span: Span) -> Result<Option<Type>, String> {
+        match self.check_expr(value, scope)? {
+            Some(found) if !matches!(found, Type::Str | Type::List | Type::Dict) => {
+                Err(format!("Cannot index a value of type {} at {}", found, span))
+            }
+            found => Ok(found),
+        }
+    }
+
+    fn check_field(&self, object: &Expr, field: &str, scope: &Scope, span: Span) -> Result<(), String> {
+        match self.check_expr(object, scope)? {
+            Some(found) if !matches!(found, Type::Struct(_)) => {
+                Err(format!("A value of type {} has no field '{}' at {}", found, field, span))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check `expr` and give its type, `None` when that is not known
+    fn check_expr(&self, expr: &Expr, scope: &Scope) -> Result<Option<Type>, String> {
+        Ok(match expr {
+            Expr::Number(..) => Some(Type::Int),
+            Expr::Float(..) => Some(Type::Float),
+            Expr::Boolean(..) => Some(Type::Bool),
+            Expr::String(..) => Some(Type::Str),
+            Expr::None(_) => Some(Type::None),
+            Expr::Var(name, _) => match scope.variable(name) {
+                Some(found) => found,
+                None if self.functions.contains_key(name) => Some(Type::Function),
+                None => None,
+            },
+            Expr::BinOp { left, op, right, span } => {
+                match (self.check_expr(left, scope)?, self.check_expr(right, scope)?) {
+                    (Some(left), Some(right)) => binary_type(op, &left, &right).map_err(|_| {
+                        format!("Unsupported operand types for {}: {} and {} at {}", op_text(op), left, right, span)
+                    })?,
+                    _ => None,
+                }
+            }
+            Expr::UnaryOp { op, operand, span } => {
+                let found = self.check_expr(operand, scope)?;
+                match (op, found) {
+                    (UnaryOp::Not, _) => Some(Type::Bool),
+                    (_, None) => None,
+                    (UnaryOp::Plus | UnaryOp::Minus, Some(Type::Float)) => Some(Type::Float),
+                    (_, Some(Type::Int | Type::Bool)) => Some(Type::Int),
+                    (_, Some(found)) => {
+                        let op = match op {
+                            UnaryOp::Plus => "+",
+                            UnaryOp::Minus => "-",
+                            _ => "~",
+                        };
+                        return Err(format!("Bad operand type {} for unary {} at {}", found, op, span));
+                    }
+                }
+            }
+            // Compiled code gives 1 or 0 where the interpreter gives an operand back
+            Expr::BoolOp { op: BoolOp::And | BoolOp::Or, values, .. } => {
+                let mut all_bool = true;
+                for value in values {
+                    all_bool &= self.check_expr(value, scope)? == Some(Type::Bool);
+                }
+                all_bool.then_some(Type::Bool)
+            }
+            Expr::Compare { left, ops, comparators, span } => {
+                let mut left = self.check_expr(left, scope)?;
+                for (op, right) in ops.iter().zip(comparators) {
+                    let right = self.check_expr(right, scope)?;
+                    if let (Some(a), Some(b)) = (&left, &right) {
+                        let valid = match op {
+                            CompareOp::Lt | CompareOp::Le | CompareOp::Gt | CompareOp::Ge => {
+                                (a.is_number() && b.is_number()) || (*a == Type::Str && *b == Type::Str)
+                            }
+                            CompareOp::In | CompareOp::NotIn => matches!(b, Type::List | Type::Dict) || (*a == Type::Str && *b == Type::Str),
+                            // What compiled code compares a string with is a pointer
+                            CompareOp::Eq | CompareOp::Ne | CompareOp::Is | CompareOp::IsNot => {
+                                !((*a == Type::Str && b.is_number()) || (a.is_number() && *b == Type::Str))
+                            }
+                        };
+                        if !valid {
+                            return Err(format!("Cannot compare {} and {} with {} at {}", a, b, compare_text(op), span));
+                        }
+                    }
+                    left = right;
+                }
+                Some(Type::Bool)
+            }
+            Expr::Call { func, args, kwargs, .. } => {
+                let mut found = Vec::with_capacity(args.len());
+                for arg in args {
+                    found.push(self.check_expr(arg, scope)?);
+                }
+                for value in kwargs.values() {
+                    self.check_expr(value, scope)?;
+                }
+                match self.functions.get(func) {
+                    Some(signature) => {
+                        for (index, ((declared, found), arg)) in signature.args.iter().zip(&found).zip(args).enumerate() {
+                            if let (Some(declared), Some(found)) = (declared, found) {
+                                if !declared.accepts(found) {
+                                    return Err(format!("Argument {} of '{}' is declared {} but passed {} at {}", index + 1, func, declared, found, arg.span()));
+                                }
+                            }
+                        }
+                        signature.returns.clone()
+                    }
+                    None if self.structs.contains(func) => Some(Type::Struct(func.clone())),
+                    None => builtin_type(func),
+                }
+            }
+            Expr::HardwareCall { args, .. } | Expr::List { elements: args, .. } => {
+                for arg in args {
+                    self.check_expr(arg, scope)?;
+                }
+                matches!(expr, Expr::List { .. }).then_some(Type::List)
+            }
+            Expr::FString { parts, .. } => {
+                for part in parts {
+                    if let FStringPart::Expr(expr) = part {
+                        self.check_expr(expr, scope)?;
+                    }
+                }
+                Some(Type::Str)
+            }
+            Expr::Dict { entries, .. } => {
+                for (key, value) in entries {
+                    self.check_expr(key, scope)?;
+                    self.check_expr(value, scope)?;
+                }
+                Some(Type::Dict)
+            }
+            Expr::Index { value, index, span } => {
+                let container = self.check_indexable(value, scope, *span)?;
+                self.check_expr(index, scope)?;
+                container.filter(|found| *found == Type::Str)
+            }
+            Expr::Slice { value, start, stop, span } => {
+                if let Some(found) = self.check_expr(value, scope)?.filter(|found| *found != Type::Str) {
+                    return Err(format!("Only strings can be sliced, not a value of type {} at {}", found, span));
+                }
+                for bound in [start, stop].into_iter().flatten() {
+                    if let Some(found) = self.check_expr(bound, scope)?.filter(|found| !Type::Int.accepts(found)) {
+                        return Err(format!("Slice bounds must be int, not {} at {}", found, span));
+                    }
+                }
+                Some(Type::Str)
+            }
+            Expr::FieldAccess { object, field, span } => {
+                self.check_field(object, field, scope, *span)?;
+                None
+            }
+            // Only strings have methods
+            Expr::MethodCall { receiver, method, args, span } => {
+                if let Some(found) = self.check_expr(receiver, scope)?.filter(|found| *found != Type::Str) {
+                    return Err(format!("A value of type {} has no method '{}' at {}", found, method, span));
+                }
+                let (arity, _) = crate::extension::string_method(method)
+                    .ok_or_else(|| crate::extension::unknown_string_method(method, *span))?;
+                if args.len() != arity {
+                    return Err(format!("str.{}() takes {} argument{} but {} were given at {}", method, arity, if arity == 1 { "" } else { "s" }, args.len(), span));
+                }
+                for arg in args {
+                    if let Some(found) = self.check_expr(arg, scope)?.filter(|found| *found != Type::Str) {
+                        return Err(format!("str.{}() takes str arguments, not {} at {}", method, found, arg.span()));
+                    }
+                }
+                Some(match method.as_str() {
+                    "find" => Type::Int,
+                    "split" => Type::List,
+                    _ => Type::Str,
+                })
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_program;
+
+    fn check(source: &str, strict: bool) -> Result<Program, String> {
+        let mut program = parse_program(source).unwrap();
+        check_types(&mut program, strict).map(|_| program)
+    }
+
+    fn return_types(program: &Program) -> Vec<Option<String>> {
+        program.body.iter()
+            .filter_map(|stmt| match stmt {
+                Statement::FunctionDef { return_type, .. } => Some(return_type.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_mismatches_are_reported() {
+        let errors = [
+            ("var x: int = \"a\"\n", "'x' is declared int but assigned str at 1:1"),
+            ("var x: str = \"a\"\nx = 2\n", "'x' is declared str but assigned int at 2:1"),
+            ("var x: int = 1\nvar x: str = \"a\"\n", "'x' was declared int at 1:1 and is redeclared str at 2:1"),
+            ("def f(n: int): return n\nf(\"a\")\n", "Argument 1 of 'f' is declared int but passed str at 2:3"),
+            ("def f(a: int, b: str): return a\nprint(f(1, 2 + 3))\n", "Argument 2 of 'f' is declared str but passed int at 2:12"),
+            ("def f() -> int: return \"a\"\n", "'f' is declared to return int but returns str at 1:17"),
+            ("def f() -> str: return\n", "'f' is declared to return str but returns None at 1:17"),
+            ("print(\"a\" * \"b\")\n", "Unsupported operand types for *: str and str at 1:7"),
+            ("var s = \"a\"\nprint(s - 1)\n", "Unsupported operand types for -: str and int at 2:7"),
+            ("print(1.5 & 1)\n", "Unsupported operand types for &: float and int at 1:7"),
+            ("print(-\"a\")\n", "Bad operand type str for unary - at 1:7"),
+            ("print(\"a\" < 1)\n", "Cannot compare str and int with < at 1:7"),
+            ("def f() -> str: return \"a\"\nprint(f() == 1)\n", "Cannot compare str and int with == at 2:7"),
+            ("var n = 1\nprint(n[0])\n", "Cannot index a value of type int at 2:7"),
+            ("var n = 1\nprint(n.upper())\n", "A value of type int has no method 'upper' at 2:7"),
+            ("print(\"a\".title())\n", "Strings have no method 'title'; the supported methods are find, replace, split, upper, lower, strip at 1:7"),
+            ("print(\"a\".replace(\"a\"))\n", "str.replace() takes 2 arguments but 1 were given at 1:7"),
+            ("print(\"a\".find(1))\n", "str.find() takes str arguments, not int at 1:16"),
+            ("var x: vector = 1\n", "Unknown type 'vector' at 1:1"),
+        ];
+        for (source, expected) in errors {
+            assert_eq!(check(source, false).unwrap_err(), expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_unannotated_code_is_inferred_gradually() {
+        // Unknown types are never errors: a parameter, or a variable that held two types
+        let program = check("def f(x): return x + 1\nvar y = 1\ny = \"a\"\nprint(y * y, f(\"a\"))\n", false).unwrap();
+        assert_eq!(return_types(&program), [None]);
+
+        let source = "struct P: x\ndef name(): return \"n\" + str(1)\ndef make(): return P(1)\ndef maybe(n):\n    if n: return 1\n\
+                      def count(n):\n    if n > 0: return count(n - 1)\n    else: return 0\ndef later(): return first()\ndef first() -> list: return []\n";
+        let program = check(source, false).unwrap();
+        let expected = [Some("str"), Some("P"), None, Some("int"), Some("list"), Some("list")];
+        assert_eq!(return_types(&program), expected.map(|hint| hint.map(String::from)));
+        // 1 + None is what the interpreter rejects, and only `maybe` can return None
+        assert!(check(&format!("{}print(name() * 2, maybe(1) + 1)\n", source), false).is_ok());
+        assert!(check(&format!("{}print(name() - 2)\n", source), false).is_err());
+    }
+
+    #[test]
+    fn test_strict_types_requires_signatures() {
+        assert_eq!(check("def f(a: int, b): return a\n", true).unwrap_err(), "Parameter 'b' of 'f' needs a type annotation with --strict-types at 1:1");
+        assert_eq!(check("def f(a: int): return a\n", true).unwrap_err(), "Function 'f' needs a return type annotation with --strict-types at 1:1");
+        assert!(check("def f(a: int, b: float) -> float: return a * b\nvar x = f(1, 2)\n", true).is_ok());
+    }
+}
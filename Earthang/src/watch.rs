@@ -0,0 +1,201 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::time::{Duration, Instant, SystemTime};
+use colored::*;
+use crate::parser::Statement;
+
+/// Modification time and length of each watched file; None while a file is missing
+type Snapshot = Vec<Option<(SystemTime, u64)>>;
+
+/// Rebuilds whenever one of the files the last build read changes, by polling
+/// their modification times so no platform notification API is needed.
+/// Ctrl+C reaches the whole foreground process group, so the watcher and the
+/// post-build command stop together
+#[derive(Debug, Clone)]
+pub struct WatchCommand {
+    pub interval: Duration,
+    /// How long the files must stay unchanged before a rebuild starts
+    pub debounce: Duration,
+    /// Shell command started after each successful build, stopped before the next one
+    pub on_success: Option<String>,
+}
+
+impl Default for WatchCommand {
+    fn default() -> Self {
+        Self { interval: Duration::from_millis(250), debounce: Duration::from_millis(150), on_success: None }
+    }
+}
+
+impl WatchCommand {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    pub fn with_on_success(mut self, command: Option<String>) -> Self {
+        self.on_success = command;
+        self
+    }
+
+    /// Build, then wait for a change and build again, until the process is
+    /// interrupted. `build` returns its result with the files it read
+    pub fn run(&self, mut build: impl FnMut() -> (Result<(), String>, Vec<PathBuf>)) -> Result<(), String> {
+        let mut running: Option<Child> = None;
+        loop {
+            stop(&mut running);
+            let start = Instant::now();
+            let (result, files) = build();
+            let elapsed = format!("({:.2}s)", start.elapsed().as_secs_f64()).dimmed();
+            match result {
+                Ok(()) => {
+                    println!("{} Build succeeded {}", "✓".green(), elapsed);
+                    if let Some(command) = &self.on_success {
+                        match shell(command).spawn() {
+                            Ok(child) => running = Some(child),
+                            Err(e) => eprintln!("{} Failed to run '{}': {}", "!".yellow(), command, e),
+                        }
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    println!("{} Build failed {}", "✗".red(), elapsed);
+                }
+            }
+
+            let names: Vec<String> = files.iter().map(|file| file.display().to_string()).collect();
+            println!("{} {}", "Watching".cyan(), names.join(", ").dimmed());
+            let changed = self.wait_for_change(&files);
+            println!("\n{} {} changed, rebuilding", ">".blue(), changed.display());
+        }
+    }
+
+    /// Block until one of `files` changes and then stays unchanged for the
+    /// debounce period, returning the first file seen changing
+    pub fn wait_for_change(&self, files: &[PathBuf]) -> PathBuf {
+        let before = snapshot(files);
+        let changed = loop {
+            std::thread::sleep(self.interval);
+            let now = snapshot(files);
+            if let Some(index) = (0..files.len()).find(|&index| now[index] != before[index]) {
+                break index;
+            }
+        };
+
+        let mut last = snapshot(files);
+        loop {
+            std::thread::sleep(self.debounce);
+            let now = snapshot(files);
+            if now == last {
+                return files[changed].clone();
+            }
+            last = now;
+        }
+    }
+}
+
+fn snapshot(files: &[PathBuf]) -> Snapshot {
+    files.iter()
+        .map(|file| std::fs::metadata(file).ok().and_then(|meta| Some((meta.modified().ok()?, meta.len()))))
+        .collect()
+}
+
+fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(windows) { Command::new("cmd") } else { Command::new("sh") };
+    shell.arg(if cfg!(windows) { "/C" } else { "-c" }).arg(command);
+    shell
+}
+
+fn stop(running: &mut Option<Child>) {
+    if let Some(mut child) = running.take() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// `files` with the files they include and the module libraries they import,
+/// found the way the compiler finds them. Files that do not parse contribute
+/// only themselves
+pub fn source_dependencies(files: &[PathBuf], search_paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = Vec::new();
+    let mut pending: Vec<PathBuf> = files.iter().rev().cloned().collect();
+    while let Some(file) = pending.pop() {
+        if found.contains(&file) {
+            continue;
+        }
+        let base_dir = file.parent().map(Path::to_path_buf).unwrap_or_default();
+        let program = std::fs::read_to_string(&file).ok().and_then(|source| crate::parser::parse_program(&source).ok());
+        found.push(file);
+        let Some(program) = program else { continue };
+
+        let locate = |name: &str| std::iter::once(&base_dir).chain(search_paths).map(|dir| dir.join(name)).find(|path| path.is_file());
+        for stmt in &program.body {
+            match stmt {
+                Statement::Include { filename, .. } => pending.extend(locate(filename)),
+                Statement::Import { module, .. } => {
+                    let library = format!("{}.{}", module, crate::module_library::LIBRARY_EXTENSION);
+                    found.extend(locate(&library).filter(|path| !found.contains(path)));
+                }
+                _ => {}
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watch_sees_dependencies_change() {
+        let dir = std::env::temp_dir().join(format!("earthang_watch_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let main = dir.join("main.eg");
+        std::fs::write(&main, "include \"shapes.eg\"\nimport answer\nprint(area(2))\n").unwrap();
+        std::fs::write(dir.join("shapes.eg"), "include \"missing.eg\"\ndef area(n): return n * n\n").unwrap();
+        std::fs::write(dir.join("answer.egm"), "").unwrap();
+
+        let files = source_dependencies(std::slice::from_ref(&main), &[]);
+        assert_eq!(files, vec![main.clone(), dir.join("answer.egm"), dir.join("shapes.eg")]);
+
+        // Three quick writes end up as one change, reported once they stop
+        let watch = WatchCommand::new().with_interval(Duration::from_millis(5)).with_debounce(Duration::from_millis(200));
+        let writer = {
+            let shapes = dir.join("shapes.eg");
+            std::thread::spawn(move || {
+                for body in ["n", "n * n * n", "n + n"] {
+                    std::thread::sleep(Duration::from_millis(10));
+                    std::fs::write(&shapes, format!("def area(n): return {}\n", body)).unwrap();
+                }
+            })
+        };
+        let changed = watch.wait_for_change(&files);
+        let finished = writer.is_finished();
+        writer.join().unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(changed, dir.join("shapes.eg"));
+        assert!(finished);
+    }
+}
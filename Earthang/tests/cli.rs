@@ -0,0 +1,45 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// Runs the earthang binary the way scripts do, for behaviour that depends
+// on the process, such as what it makes of a piped stdin.
+
+#[test]
+fn piped_program_compiles_to_the_output_given() {
+    let dir = std::env::temp_dir().join(format!("earthang_cli_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = dir.join("piped.s");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_earthang"))
+        .args(["-q", "compile", "-", "-o"])
+        .arg(&output)
+        .current_dir(&dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"print(6 * 7)\n").unwrap();
+    let finished = child.wait_with_output().unwrap();
+    let assembly = std::fs::read_to_string(&output);
+    let stray = dir.join("stdin.elf").exists();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let stderr = String::from_utf8_lossy(&finished.stderr);
+    assert!(finished.status.success(), "{}", stderr);
+    assert!(!stderr.contains("Ctrl+D") && !String::from_utf8_lossy(&finished.stdout).contains("Ctrl+D"), "{}", stderr);
+    assert!(assembly.unwrap().contains("main:"));
+    assert!(!stray);
+}
@@ -0,0 +1,49 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::path::Path;
+use earthang::differential::Harness;
+
+// Every program in examples/programs runs through the interpreter and, when
+// GNU as and ld are installed, through the compiler too. Each one has a
+// NAME.out, so without the toolchain the interpreter is still checked.
+
+/// Programs whose compiled output knowingly differs from the interpreter's,
+/// recorded in NAME.native.out. Fixing either side changes this list
+const KNOWN_DIVERGENCES: [&str; 0] = [];
+
+#[test]
+fn examples_agree_across_backends() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples").join("programs");
+    let harness = Harness::new();
+    let programs = Harness::corpus(&dir).unwrap();
+    assert!(!programs.is_empty(), "no programs in {}", dir.display());
+
+    let mut divergences = Vec::new();
+    for program in &programs {
+        let name = program.file_stem().unwrap().to_string_lossy();
+        let listed = KNOWN_DIVERGENCES.contains(&name.as_ref());
+        assert_eq!(program.with_extension("native.out").exists(), listed, "{} is listed in KNOWN_DIVERGENCES only if it has a .native.out", name);
+        match harness.check(program) {
+            Ok(checked) => {
+                assert!(checked.expected_file, "{} has no expected output file", program.display());
+                assert!(!checked.compiled || checked.known_divergence == listed, "{}", name);
+            }
+            Err(divergence) => divergences.push(divergence.to_string()),
+        }
+    }
+    if !harness.native() {
+        eprintln!("GNU as or ld is missing; only checked the interpreter against the expected output files");
+    }
+    assert!(divergences.is_empty(), "{}", divergences.join("\n\n"));
+}
@@ -0,0 +1,185 @@
+/*
+    Copyright (C) 2026 Emanuel
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+*/
+use std::path::{Path, PathBuf};
+use earthang::backend::{Backend, Linux64Backend, Target};
+use earthang::compiler::{CompilerConfig, ConstantFoldingPass, EarthangCompiler, OptimizationPass};
+use earthang::framebuffer::{Framebuffer, SimdLevel};
+use earthang::mode_transition::{BootLayout, ModeTransitionEmitter, TransitionFeatures};
+
+// Every program in tests/snapshots/fixtures is compiled by every backend and
+// the result compared with tests/snapshots/<backend>/<program>.s. A backend
+// that rejects a program has its error recorded in the file instead, so what
+// each backend supports is pinned too. The transition code the BIOS images
+// boot through is pinned in tests/snapshots/boot.
+//
+// After an intended change, regenerate the files with
+//
+//   UPDATE_SNAPSHOTS=1 cargo test --test snapshots
+//
+// and review the diff like any other.
+
+/// Lines of unchanged context around each change
+const CONTEXT: usize = 3;
+
+#[derive(Clone, Copy)]
+enum Snapshot {
+    /// A hosted executable for a target
+    Hosted(Target),
+    /// The 64-bit payload of a `--bios-mode --debug-serial` disk image
+    Bios(SimdLevel),
+}
+
+const BACKENDS: [(&str, Snapshot); 6] = [
+    ("linux64", Snapshot::Hosted(Target::Linux64)),
+    ("riscv64", Snapshot::Hosted(Target::RiscV64)),
+    ("aarch64", Snapshot::Hosted(Target::Aarch64)),
+    ("bios-sse2", Snapshot::Bios(SimdLevel::Sse2)),
+    ("bios-avx2", Snapshot::Bios(SimdLevel::Avx2)),
+    ("bios-avx512", Snapshot::Bios(SimdLevel::Avx512)),
+];
+
+fn snapshot_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("snapshots")
+}
+
+fn compile(snapshot: Snapshot, fixture: &Path) -> String {
+    let source = std::fs::read_to_string(fixture).unwrap();
+    let result = match snapshot {
+        Snapshot::Hosted(target) => {
+            let program = earthang::parser::parse_program(&source).unwrap();
+            let config = CompilerConfig::default().with_target(target).with_hardware_dsl(false);
+            EarthangCompiler::new(config).compile_program(program, Some(fixture)).map(|result| result.assembly)
+        }
+        // What compile_bios_image runs before linking, for every fixture whether or not it draws
+        Snapshot::Bios(simd) => {
+            let program = earthang::parser::parse_program(&source).unwrap();
+            let mut includes = earthang::lua_frontend::IncludeProcessor::new();
+            includes.add_search_path(fixture.parent().unwrap());
+            let mut program = includes.process_includes(&program, None).unwrap();
+            ConstantFoldingPass.optimize(&mut program)
+                .and_then(|_| {
+                    Linux64Backend::new()
+                        .with_bios_graphics(Framebuffer::vga_mode_13h(), simd)
+                        .with_debug_serial(true)
+                        .compile_program(&program)
+                })
+        }
+    };
+    result.unwrap_or_else(|error| format!("# error: {}\n", error))
+}
+
+/// Compare `actual` with the file at `path`, or write it there when updating
+fn check(path: &Path, actual: &str, mismatches: &mut Vec<String>) {
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some_and(|value| value != "0") {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, actual).unwrap();
+        return;
+    }
+    let relative = path.strip_prefix(env!("CARGO_MANIFEST_DIR")).unwrap_or(path).display().to_string();
+    match std::fs::read_to_string(path) {
+        Ok(expected) if expected == actual => {}
+        Ok(expected) => mismatches.push(unified_diff(&relative, &expected, actual)),
+        Err(_) => mismatches.push(format!("{} is missing", relative)),
+    }
+}
+
+#[test]
+fn backend_output_matches_snapshots() {
+    let fixtures_dir = snapshot_dir().join("fixtures");
+    let mut fixtures: Vec<PathBuf> = std::fs::read_dir(&fixtures_dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "eg"))
+        .collect();
+    fixtures.sort();
+    assert!(!fixtures.is_empty());
+
+    let mut mismatches = Vec::new();
+    for (name, snapshot) in BACKENDS {
+        for fixture in &fixtures {
+            let expected = snapshot_dir().join(name).join(fixture.file_name().unwrap()).with_extension("s");
+            check(&expected, &compile(snapshot, fixture), &mut mismatches);
+        }
+    }
+    assert!(mismatches.is_empty(), "{} snapshots changed; rerun with UPDATE_SNAPSHOTS=1 if that is intended\n\n{}", mismatches.len(), mismatches.join("\n"));
+}
+
+#[test]
+fn boot_code_matches_snapshots() {
+    let no_avx = TransitionFeatures { avx: false, avx512: false, ..TransitionFeatures::default() };
+    let emitters = [
+        ("boot_sector", ModeTransitionEmitter::new(BootLayout::BootSector).with_features(no_avx)),
+        ("two_stage", ModeTransitionEmitter::new(BootLayout::TwoStage).with_video_mode(0x13)),
+        ("two_stage_no_avx", ModeTransitionEmitter::new(BootLayout::TwoStage).with_features(no_avx)),
+    ];
+
+    let mut mismatches = Vec::new();
+    for (name, emitter) in emitters {
+        let gas = emitter.to_gas(&emitter.selected_phases());
+        check(&snapshot_dir().join("boot").join(format!("{}.s", name)), &gas, &mut mismatches);
+    }
+    assert!(mismatches.is_empty(), "{} snapshots changed; rerun with UPDATE_SNAPSHOTS=1 if that is intended\n\n{}", mismatches.len(), mismatches.join("\n"));
+}
+
+/// `diff -u` of two texts, lines compared through their longest common subsequence
+fn unified_diff(name: &str, expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] { common[i + 1][j + 1] + 1 } else { common[i + 1][j].max(common[i][j + 1]) };
+        }
+    }
+    // (marker, old line index, new line index) for every line of either text
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push((' ', i, j));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            edits.push(('-', i, j));
+            i += 1;
+        } else {
+            edits.push(('+', i, j));
+            j += 1;
+        }
+    }
+
+    let mut text = format!("--- {} (snapshot)\n+++ {} (compiled)\n", name, name);
+    let changed: Vec<usize> = edits.iter().enumerate().filter(|(_, edit)| edit.0 != ' ').map(|(index, _)| index).collect();
+    let mut start = 0;
+    while start < changed.len() {
+        // Changes closer than twice the context share a hunk
+        let mut end = start;
+        while end + 1 < changed.len() && changed[end + 1] - changed[end] <= 2 * CONTEXT {
+            end += 1;
+        }
+        let from = changed[start].saturating_sub(CONTEXT);
+        let to = (changed[end] + CONTEXT + 1).min(edits.len());
+        let hunk = &edits[from..to];
+        let old_count = hunk.iter().filter(|edit| edit.0 != '+').count();
+        let new_count = hunk.iter().filter(|edit| edit.0 != '-').count();
+        text.push_str(&format!("@@ -{},{} +{},{} @@\n", hunk[0].1 + 1, old_count, hunk[0].2 + 1, new_count));
+        for &(marker, i, j) in hunk {
+            let line = if marker == '+' { new[j] } else { old[i] };
+            text.push_str(&format!("{}{}\n", marker, line));
+        }
+        start = end + 1;
+    }
+    text
+}
@@ -0,0 +1,325 @@
+    .text
+    .globl _start
+
+_start:
+    bl main
+    mov x8, #93         // exit with main's result
+    svc #0
+
+main:
+    sub sp, sp, #80
+    stp x29, x30, [sp]
+    mov x29, sp
+
+    // @line 2
+    // Import: system
+    // @line 3
+    // Variable declaration: a
+    movz x0, #17
+    str x0, [x29, #16]
+    // @line 4
+    // Variable declaration: b
+    movz x0, #5
+    str x0, [x29, #24]
+    // @line 5
+    // Variable declaration: on
+    mov x0, #1
+    str x0, [x29, #32]
+    // @line 6
+    // Assignment to a
+    ldr x0, [x29, #16]  // a
+    str x0, [sp, #-16]!
+    movz x0, #1
+    ldr x1, [sp], #16
+    add x0, x1, x0
+    str x0, [x29, #16]
+    // @line 7
+    // Augmented assignment to a
+    ldr x0, [x29, #16]  // a
+    str x0, [sp, #-16]!
+    ldr x0, [x29, #24]  // b
+    ldr x1, [sp], #16
+    add x0, x1, x0
+    str x0, [x29, #16]
+    // @line 8
+    // Augmented assignment to a
+    ldr x0, [x29, #16]  // a
+    str x0, [sp, #-16]!
+    movz x0, #2
+    ldr x1, [sp], #16
+    sub x0, x1, x0
+    str x0, [x29, #16]
+    // @line 9
+    // Augmented assignment to a
+    ldr x0, [x29, #16]  // a
+    str x0, [sp, #-16]!
+    movz x0, #3
+    ldr x1, [sp], #16
+    mul x0, x1, x0
+    str x0, [x29, #16]
+    // @line 10
+    ldr x0, [x29, #16]  // a
+    str x0, [sp, #-16]!
+    ldr x0, [x29, #24]  // b
+    ldr x1, [sp], #16
+    add x0, x1, x0
+    bl print_decimal
+    adrp x0, str_000000000002b5c5
+    add x0, x0, :lo12:str_000000000002b5c5
+    bl print_string
+    ldr x0, [x29, #16]  // a
+    str x0, [sp, #-16]!
+    ldr x0, [x29, #24]  // b
+    ldr x1, [sp], #16
+    sub x0, x1, x0
+    bl print_decimal
+    adrp x0, str_000000000002b5c5
+    add x0, x0, :lo12:str_000000000002b5c5
+    bl print_string
+    ldr x0, [x29, #16]  // a
+    str x0, [sp, #-16]!
+    ldr x0, [x29, #24]  // b
+    ldr x1, [sp], #16
+    mul x0, x1, x0
+    bl print_decimal
+    adrp x0, str_000000000002b5c5
+    add x0, x0, :lo12:str_000000000002b5c5
+    bl print_string
+    ldr x0, [x29, #16]  // a
+    str x0, [sp, #-16]!
+    ldr x0, [x29, #24]  // b
+    ldr x1, [sp], #16
+    sdiv x0, x1, x0
+    bl print_decimal
+    adrp x0, str_000000000002b5c5
+    add x0, x0, :lo12:str_000000000002b5c5
+    bl print_string
+    ldr x0, [x29, #16]  // a
+    str x0, [sp, #-16]!
+    ldr x0, [x29, #24]  // b
+    ldr x1, [sp], #16
+    sdiv x2, x1, x0
+    msub x0, x2, x0, x1
+    bl print_decimal
+    bl print_newline
+    // @line 11
+    ldr x0, [x29, #16]  // a
+    str x0, [sp, #-16]!
+    ldr x0, [x29, #24]  // b
+    ldr x1, [sp], #16
+    and x0, x1, x0
+    bl print_decimal
+    adrp x0, str_000000000002b5c5
+    add x0, x0, :lo12:str_000000000002b5c5
+    bl print_string
+    ldr x0, [x29, #16]  // a
+    str x0, [sp, #-16]!
+    ldr x0, [x29, #24]  // b
+    ldr x1, [sp], #16
+    orr x0, x1, x0
+    bl print_decimal
+    adrp x0, str_000000000002b5c5
+    add x0, x0, :lo12:str_000000000002b5c5
+    bl print_string
+    ldr x0, [x29, #16]  // a
+    str x0, [sp, #-16]!
+    ldr x0, [x29, #24]  // b
+    ldr x1, [sp], #16
+    eor x0, x1, x0
+    bl print_decimal
+    adrp x0, str_000000000002b5c5
+    add x0, x0, :lo12:str_000000000002b5c5
+    bl print_string
+    ldr x0, [x29, #16]  // a
+    neg x0, x0
+    bl print_decimal
+    adrp x0, str_000000000002b5c5
+    add x0, x0, :lo12:str_000000000002b5c5
+    bl print_string
+    ldr x0, [x29, #24]  // b
+    bl print_decimal
+    adrp x0, str_000000000002b5c5
+    add x0, x0, :lo12:str_000000000002b5c5
+    bl print_string
+    ldr x0, [x29, #32]  // on
+    cmp x0, #0
+    cset x0, eq
+    bl print_decimal
+    bl print_newline
+    // @line 12
+    ldr x0, [x29, #32]  // on
+    cbz x0, .Lbool_short_1
+    ldr x0, [x29, #24]  // b
+    cbz x0, .Lbool_short_1
+    mov x0, #1
+    b .Lbool_end_1
+.Lbool_short_1:
+    mov x0, #0
+.Lbool_end_1:
+    bl print_decimal
+    adrp x0, str_000000000002b5c5
+    add x0, x0, :lo12:str_000000000002b5c5
+    bl print_string
+    ldr x0, [x29, #32]  // on
+    cbnz x0, .Lbool_short_2
+    ldr x0, [x29, #16]  // a
+    cbnz x0, .Lbool_short_2
+    mov x0, #0
+    b .Lbool_end_2
+.Lbool_short_2:
+    mov x0, #1
+.Lbool_end_2:
+    bl print_decimal
+    adrp x0, str_000000000002b5c5
+    add x0, x0, :lo12:str_000000000002b5c5
+    bl print_string
+    adrp x0, str_000000017c95cc2b  // "done"
+    add x0, x0, :lo12:str_000000017c95cc2b
+    bl print_string
+    bl print_newline
+    // @line 13
+    // For loop over range() into i
+    movz x0, #2
+    str x0, [x29, #40]
+    movz x0, #8
+    str x0, [x29, #48]
+.Lfor_start_3:
+    ldr x9, [x29, #40]
+    ldr x10, [x29, #48]
+    cmp x9, x10
+    b.ge .Lfor_end_3
+    str x9, [x29, #64]
+    ldr x0, [x29, #64]  // i
+    bl print_decimal
+    bl print_newline
+    movz x0, #3
+    ldr x9, [x29, #40]
+    add x9, x9, x0
+    str x9, [x29, #40]
+    b .Lfor_start_3
+.Lfor_end_3:
+    // @line 17
+    movz x0, #1
+    bl sleep_ms
+    // @end
+
+    mov x0, #0
+.Lmain_epilogue:
+    mov sp, x29
+    ldp x29, x30, [sp]
+    add sp, sp, #80
+    ret
+
+print_string:
+    mov x1, x0
+    mov x2, #0
+1:
+    ldrb w9, [x1, x2]
+    cbz w9, 2f
+    add x2, x2, #1
+    b 1b
+2:
+    mov x0, #1
+    mov x8, #64
+    svc #0
+    ret
+
+print_decimal:
+    sub sp, sp, #32
+    mov x9, x0
+    add x10, sp, #32
+    mov x11, #0
+    cmp x9, #0
+    b.ge 1f
+    mov x11, #1
+    neg x9, x9
+1:
+    mov x12, #10
+2:
+    udiv x13, x9, x12
+    msub x14, x13, x12, x9
+    add x14, x14, #48
+    strb w14, [x10, #-1]!
+    mov x9, x13
+    cbnz x9, 2b
+    cbz x11, 3f
+    mov x14, #45
+    strb w14, [x10, #-1]!
+3:
+    mov x0, #1
+    mov x1, x10
+    add x2, sp, #32
+    sub x2, x2, x10
+    mov x8, #64
+    svc #0
+    add sp, sp, #32
+    ret
+
+print_newline:
+    mov x0, #1
+    adrp x1, newline
+    add x1, x1, :lo12:newline
+    mov x2, #1
+    mov x8, #64
+    svc #0
+    ret
+
+runtime_error:
+    mov x12, x0
+    mov x0, #2
+    adrp x1, runtime_error_prefix
+    add x1, x1, :lo12:runtime_error_prefix
+    mov x2, #15
+    mov x8, #64
+    svc #0
+    mov x1, x12
+    mov x2, #0
+1:
+    ldrb w9, [x1, x2]
+    cbz w9, 2f
+    add x2, x2, #1
+    b 1b
+2:
+    mov x0, #2
+    svc #0
+    mov x0, #2
+    adrp x1, newline
+    add x1, x1, :lo12:newline
+    mov x2, #1
+    svc #0
+    mov x0, #1
+    mov x8, #93
+    svc #0
+
+sleep_ms:
+    cmp x0, #0
+    b.gt 1f
+    mov x0, #0
+    ret
+1:
+    sub sp, sp, #16
+    mov x9, #1000
+    sdiv x10, x0, x9
+    msub x11, x10, x9, x0
+    mov x9, #0x4240
+    movk x9, #0xF, lsl #16
+    mul x11, x11, x9
+    stp x10, x11, [sp]
+    mov x0, sp
+    mov x1, #0
+    mov x8, #101
+    svc #0
+    add sp, sp, #16
+    ret
+
+    .section .rodata
+newline:
+    .byte 10, 0
+runtime_error_prefix:
+    .ascii "Runtime error: "
+
+// String literals
+str_000000000002b5c5:  // " "
+    .byte 0x20, 0x00
+str_000000017c95cc2b:  // "done"
+    .byte 0x64, 0x6f, 0x6e, 0x65, 0x00
@@ -0,0 +1,253 @@
+    .text
+    .globl _start
+
+_start:
+    bl main
+    mov x8, #93         // exit with main's result
+    svc #0
+
+main:
+    sub sp, sp, #16
+    stp x29, x30, [sp]
+    mov x29, sp
+
+    // @line 11
+    movz x0, #2
+    str x0, [sp, #-16]!
+    movz x0, #3
+    str x0, [sp, #-16]!
+    ldr x1, [sp], #16
+    ldr x0, [sp], #16
+    bl fn_add
+    bl print_decimal
+    adrp x0, str_000000000002b5c5
+    add x0, x0, :lo12:str_000000000002b5c5
+    bl print_string
+    movz x0, #21
+    str x0, [sp, #-16]!
+    ldr x0, [sp], #16
+    bl fn_twice
+    bl print_decimal
+    adrp x0, str_000000000002b5c5
+    add x0, x0, :lo12:str_000000000002b5c5
+    bl print_string
+    movz x0, #9
+    str x0, [sp, #-16]!
+    ldr x0, [sp], #16
+    bl fn_again
+    bl print_decimal
+    bl print_newline
+    // @line 12
+    bl fn_nothing
+    // @end
+
+    mov x0, #0
+.Lmain_epilogue:
+    mov sp, x29
+    ldp x29, x30, [sp]
+    add sp, sp, #16
+    ret
+
+fn_add:
+    sub sp, sp, #32
+    stp x29, x30, [sp]
+    mov x29, sp
+    str x0, [x29, #16]
+    str x1, [x29, #24]
+
+    // @line 2
+    ldr x0, [x29, #16]  // x
+    str x0, [sp, #-16]!
+    ldr x0, [x29, #24]  // y
+    ldr x1, [sp], #16
+    add x0, x1, x0
+    b .Lfn_add_epilogue
+    // @end
+
+    mov x0, #0
+.Lfn_add_epilogue:
+    mov sp, x29
+    ldp x29, x30, [sp]
+    add sp, sp, #32
+    ret
+
+fn_twice:
+    sub sp, sp, #32
+    stp x29, x30, [sp]
+    mov x29, sp
+    str x0, [x29, #16]
+
+    // @line 4
+    // Variable declaration: doubled
+    ldr x0, [x29, #16]  // n
+    str x0, [sp, #-16]!
+    ldr x0, [x29, #16]  // n
+    str x0, [sp, #-16]!
+    ldr x1, [sp], #16
+    ldr x0, [sp], #16
+    bl fn_add
+    str x0, [x29, #24]
+    // @line 5
+    ldr x0, [x29, #24]  // doubled
+    b .Lfn_twice_epilogue
+    // @end
+
+    mov x0, #0
+.Lfn_twice_epilogue:
+    mov sp, x29
+    ldp x29, x30, [sp]
+    add sp, sp, #32
+    ret
+
+fn_again:
+    sub sp, sp, #32
+    stp x29, x30, [sp]
+    mov x29, sp
+    str x0, [x29, #16]
+
+    // @line 7
+    ldr x0, [x29, #16]  // n
+    str x0, [sp, #-16]!
+    movz x0, #1
+    str x0, [sp, #-16]!
+    ldr x1, [sp], #16
+    ldr x0, [sp], #16
+    bl fn_add
+    b .Lfn_again_epilogue
+    // @end
+
+    mov x0, #0
+.Lfn_again_epilogue:
+    mov sp, x29
+    ldp x29, x30, [sp]
+    add sp, sp, #32
+    ret
+
+fn_nothing:
+    sub sp, sp, #16
+    stp x29, x30, [sp]
+    mov x29, sp
+
+    // @line 9
+    mov x0, #0
+    b .Lfn_nothing_epilogue
+    // @end
+
+    mov x0, #0
+.Lfn_nothing_epilogue:
+    mov sp, x29
+    ldp x29, x30, [sp]
+    add sp, sp, #16
+    ret
+
+print_string:
+    mov x1, x0
+    mov x2, #0
+1:
+    ldrb w9, [x1, x2]
+    cbz w9, 2f
+    add x2, x2, #1
+    b 1b
+2:
+    mov x0, #1
+    mov x8, #64
+    svc #0
+    ret
+
+print_decimal:
+    sub sp, sp, #32
+    mov x9, x0
+    add x10, sp, #32
+    mov x11, #0
+    cmp x9, #0
+    b.ge 1f
+    mov x11, #1
+    neg x9, x9
+1:
+    mov x12, #10
+2:
+    udiv x13, x9, x12
+    msub x14, x13, x12, x9
+    add x14, x14, #48
+    strb w14, [x10, #-1]!
+    mov x9, x13
+    cbnz x9, 2b
+    cbz x11, 3f
+    mov x14, #45
+    strb w14, [x10, #-1]!
+3:
+    mov x0, #1
+    mov x1, x10
+    add x2, sp, #32
+    sub x2, x2, x10
+    mov x8, #64
+    svc #0
+    add sp, sp, #32
+    ret
+
+print_newline:
+    mov x0, #1
+    adrp x1, newline
+    add x1, x1, :lo12:newline
+    mov x2, #1
+    mov x8, #64
+    svc #0
+    ret
+
+runtime_error:
+    mov x12, x0
+    mov x0, #2
+    adrp x1, runtime_error_prefix
+    add x1, x1, :lo12:runtime_error_prefix
+    mov x2, #15
+    mov x8, #64
+    svc #0
+    mov x1, x12
+    mov x2, #0
+1:
+    ldrb w9, [x1, x2]
+    cbz w9, 2f
+    add x2, x2, #1
+    b 1b
+2:
+    mov x0, #2
+    svc #0
+    mov x0, #2
+    adrp x1, newline
+    add x1, x1, :lo12:newline
+    mov x2, #1
+    svc #0
+    mov x0, #1
+    mov x8, #93
+    svc #0
+
+sleep_ms:
+    cmp x0, #0
+    b.gt 1f
+    mov x0, #0
+    ret
+1:
+    sub sp, sp, #16
+    mov x9, #1000
+    sdiv x10, x0, x9
+    msub x11, x10, x9, x0
+    mov x9, #0x4240
+    movk x9, #0xF, lsl #16
+    mul x11, x11, x9
+    stp x10, x11, [sp]
+    mov x0, sp
+    mov x1, #0
+    mov x8, #101
+    svc #0
+    add sp, sp, #16
+    ret
+
+    .section .rodata
+newline:
+    .byte 10, 0
+runtime_error_prefix:
+    .ascii "Runtime error: "
+
+// String literals
+str_000000000002b5c5:  // " "
+    .byte 0x20, 0x00
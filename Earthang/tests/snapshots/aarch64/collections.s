@@ -0,0 +1 @@
+# error: The dict module has no code for aarch64; it supports linux64
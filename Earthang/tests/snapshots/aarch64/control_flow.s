@@ -0,0 +1 @@
+# error: while loops are not supported by the aarch64 backend at 7:1
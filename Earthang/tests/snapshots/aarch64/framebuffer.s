@@ -0,0 +1 @@
+# error: Undefined function 'fb_init' at 2:1
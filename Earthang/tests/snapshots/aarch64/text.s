@@ -0,0 +1 @@
+# error: input() from the system module is not implemented for aarch64
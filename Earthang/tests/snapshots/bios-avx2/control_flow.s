@@ -0,0 +1,720 @@
+    .intel_syntax noprefix
+    .section .text
+    .globl _start
+
+_start:
+    mov rbp, rsp
+    and rsp, -16        # 16-byte align stack
+    
+    call serial_init_64
+    call main
+    
+.halt:
+    hlt
+    jmp .halt
+
+main:
+    push rbp
+    mov rbp, rsp
+    sub rsp, 16        # Allocate 16 bytes for locals
+    # Variables span from [rbp - 8] to [rbp - 8]
+
+    # @line 2
+    # Function definition: fact
+    # @line 6
+    # Variable declaration: n
+    # Number: 0
+    mov rax, 0
+    mov QWORD PTR [rbp - 8], rax
+    # @line 7
+    # While loop
+while_start_0:
+    # Variable: n at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    push rax
+    # Number: 10
+    mov rax, 10
+    mov rbx, rax
+    pop rax
+    cmp rax, rbx
+    jge while_end_0
+    # While body
+    # Augmented assignment to n
+    # Binary operation
+    # Variable: n at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    push rax
+    # Number: 1
+    mov rax, 1
+    mov rbx, rax
+    pop rax
+    add rax, rbx
+    mov QWORD PTR [rbp - 8], rax
+    # If condition
+    # Variable: n at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    push rax
+    # Number: 2
+    mov rax, 2
+    mov rbx, rax
+    pop rax
+    cmp rax, rbx
+    jne if_end_1
+    # Then block
+    jmp while_start_0        # continue
+if_end_1:
+    # If condition
+    # Variable: n at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    push rax
+    # Number: 8
+    mov rax, 8
+    mov rbx, rax
+    pop rax
+    cmp rax, rbx
+    jl if_end_2
+    # Then block
+    jmp while_end_0        # break
+if_end_2:
+    # If condition
+    # Binary operation
+    # Variable: n at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    push rax
+    # Number: 3
+    mov rax, 3
+    mov rbx, rax
+    pop rax
+    cqo
+    idiv rbx
+    mov rax, rdx
+    push rax
+    # Number: 0
+    mov rax, 0
+    mov rbx, rax
+    pop rax
+    cmp rax, rbx
+    jne if_else_3
+    # Then block
+    # String: "fizz"
+    lea rdi, [str_000000017c96cd08]
+    call print_string
+    call print_newline
+    jmp if_end_3
+if_else_3:
+    # Elif condition
+    # Variable: n at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    push rax
+    # Number: 5
+    mov rax, 5
+    mov rbx, rax
+    pop rax
+    cmp rax, rbx
+    jg if_elif_3_1
+    # Variable: n at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    push rax
+    # Number: 4
+    mov rax, 4
+    mov rbx, rax
+    pop rax
+    cmp rax, rbx
+    je if_elif_3_1
+    # Elif body
+    # String: "small"
+    lea rdi, [str_00000031105d725e]
+    call print_string
+    call print_newline
+    jmp if_end_3
+if_elif_3_1:
+    # Else block
+    # Comparison operation
+    # Variable: n at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    push rax
+    # Number: 6
+    mov rax, 6
+    mov rbx, rax
+    pop rax
+    cmp rax, rbx
+    setg al
+    movzx rax, al
+    call print_decimal
+    call print_newline
+if_end_3:
+    jmp while_start_0
+while_end_0:
+    # @line 19
+    # Function call: fact
+    # Number: 5
+    mov rax, 5
+    push rax
+    pop rdi
+    call fn_fact
+    call print_decimal
+    call print_newline
+    # @end
+    xor rax, rax
+
+.main_epilogue:
+    mov rsp, rbp
+    pop rbp
+    ret
+
+fn_fact:
+    push rbp
+    mov rbp, rsp
+    sub rsp, 16        # Allocate 16 bytes for locals
+    mov QWORD PTR [rbp - 8], rdi
+    # @line 3
+    # If condition
+    # Variable: n at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    push rax
+    # Number: 2
+    mov rax, 2
+    mov rbx, rax
+    pop rax
+    cmp rax, rbx
+    jge if_end_f0_0
+    # Then block
+    # Return statement
+    # Number: 1
+    mov rax, 1
+    jmp .fn_fact_epilogue
+if_end_f0_0:
+    # @line 4
+    # Return statement
+    # Binary operation
+    # Variable: n at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    push rax
+    # Function call: fact
+    sub rsp, 8
+    # Binary operation
+    # Variable: n at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    push rax
+    # Number: 1
+    mov rax, 1
+    mov rbx, rax
+    pop rax
+    sub rax, rbx
+    push rax
+    pop rdi
+    call fn_fact
+    add rsp, 8
+    mov rbx, rax
+    pop rax
+    imul rax, rbx
+    jmp .fn_fact_epilogue
+    # @end
+    xor rax, rax
+.fn_fact_epilogue:
+    mov rsp, rbp
+    pop rbp
+    ret
+
+print_string:
+    push rax
+    push rdi
+    push rsi
+    push rdx
+    #
+    mov rsi, rdi
+    xor rdx, rdx
+.count_loop:
+    cmp BYTE PTR [rsi + rdx], 0
+    je .count_done
+    inc rdx
+    jmp .count_loop
+.count_done:
+    #
+    mov rax, 1
+    mov rdi, 1
+    call serial_write_64
+    #
+    pop rdx
+    pop rsi
+    pop rdi
+    pop rax
+    ret
+
+print_decimal:
+    # Input: rax = integer
+    push rbp
+    mov rbp, rsp
+    sub rsp, 32
+    #
+    push rbx
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    #
+    mov QWORD PTR [rbp - 8], rax
+    #
+    lea rdi, [rsp + 31]
+    mov BYTE PTR [rdi], 0
+    #
+    mov rax, QWORD PTR [rbp - 8]
+    test rax, rax
+    jns .positive
+    neg rax
+    #
+.positive:
+    mov rbx, 10
+    #
+.convert_loop:
+    xor rdx, rdx
+    div rbx
+    add dl, '0'
+    dec rdi
+    mov BYTE PTR [rdi], dl
+    test rax, rax
+    jnz .convert_loop
+    #
+    mov rax, QWORD PTR [rbp - 8]
+    test rax, rax
+    jns .print_it
+    dec rdi
+    mov BYTE PTR [rdi], '-'
+    #
+.print_it:
+    lea rsi, [rsp + 31]
+    sub rsi, rdi
+    #
+    mov rax, 1
+    mov rdx, rsi
+    mov rsi, rdi
+    mov rdi, 1
+    call serial_write_64
+    #
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    pop rbx
+    #
+    mov rsp, rbp
+    pop rbp
+    ret
+
+str_cmp:
+    # Input: rdi, rsi = strings; output: rax = difference at first mismatch
+    push rcx
+    push rdi
+    push rsi
+.str_cmp_loop:
+    movzx eax, BYTE PTR [rdi]
+    movzx ecx, BYTE PTR [rsi]
+    cmp eax, ecx
+    jne .str_cmp_done
+    test eax, eax
+    jz .str_cmp_done
+    inc rdi
+    inc rsi
+    jmp .str_cmp_loop
+.str_cmp_done:
+    sub rax, rcx
+    pop rsi
+    pop rdi
+    pop rcx
+    ret
+
+print_float:
+    # Input: rax = IEEE double, printed with up to six decimals
+    push rbp
+    mov rbp, rsp
+    sub rsp, 704
+    push rcx
+    push rdx
+    push rdi
+    push rsi
+    push r8
+    #
+    mov rcx, 0x7ff0000000000000
+    mov rdx, rax
+    btr rdx, 63
+    cmp rdx, rcx
+    jbe .float_signed
+    mov DWORD PTR [rbp - 32], 0x4e614e    # "NaN"
+    jmp .float_word
+    #
+.float_signed:
+    btr rax, 63
+    jnc .float_positive
+    mov WORD PTR [rbp - 32], 0x2d    # "-"
+    lea rdi, [rbp - 32]
+    call print_string
+.float_positive:
+    mov rcx, 0x7ff0000000000000     # print_string clobbers rcx
+    cmp rax, rcx
+    jne .float_number
+    mov DWORD PTR [rbp - 32], 0x666e69    # "inf"
+.float_word:
+    lea rdi, [rbp - 32]
+    call print_string
+    jmp .float_done
+    #
+.float_number:
+    mov rcx, rax
+    shr rcx, 52                # biased exponent
+    cmp ecx, 1076
+    jae .float_integral
+    # Below 2^53 the integer part fits a register and taking it away leaves the exact fraction
+    movq xmm0, rax
+    cvttsd2si rax, xmm0
+    cvtsi2sd xmm1, rax
+    subsd xmm0, xmm1
+    mov rcx, 1000000
+    cvtsi2sd xmm1, rcx
+    mulsd xmm0, xmm1
+    cvtsd2si rdx, xmm0         # round to the nearest millionth
+    cmp rdx, rcx
+    jb .float_split
+    inc rax                    # the fraction rounded up to a whole one
+    xor edx, edx
+.float_split:
+    mov QWORD PTR [rbp - 8], rdx
+    call print_decimal
+    mov WORD PTR [rbp - 32], 0x2e    # "."
+    lea rdi, [rbp - 32]
+    call print_string
+    #
+    # Six fraction digits go to [rbp - 24 .. rbp - 19]
+    mov rax, QWORD PTR [rbp - 8]
+    lea rdi, [rbp - 18]
+    mov BYTE PTR [rdi], 0
+    mov rcx, 10
+.float_digit_loop:
+    xor rdx, rdx
+    div rcx
+    add dl, '0'
+    dec rdi
+    mov BYTE PTR [rdi], dl
+    lea rdx, [rbp - 24]
+    cmp rdi, rdx
+    jne .float_digit_loop
+    #
+    # Drop trailing zeros but keep at least one digit
+    lea rdi, [rbp - 19]
+.float_trim_loop:
+    cmp rdi, rdx
+    je .float_print
+    cmp BYTE PTR [rdi], '0'
+    jne .float_print
+    mov BYTE PTR [rdi], 0
+    dec rdi
+    jmp .float_trim_loop
+.float_print:
+    mov rdi, rdx
+    call print_string
+    #
+    jmp .float_done
+    #
+.float_integral:
+    # From 2^53 up a double is the whole number mantissa * 2^(exponent - 1075).
+    # Its decimal digits, least significant first from [rbp - 352], start as
+    # the mantissa's and are doubled once per power of two
+    sub ecx, 1075
+    mov rdx, 0xfffffffffffff
+    and rax, rdx
+    bts rax, 52
+    lea rdi, [rbp - 352]
+    xor esi, esi
+    mov r8, 10
+.float_integral_digits:
+    xor edx, edx
+    div r8
+    mov BYTE PTR [rdi + rsi], dl
+    inc rsi
+    test rax, rax
+    jnz .float_integral_digits
+.float_double:
+    xor edx, edx               # carry
+    xor r8d, r8d
+.float_double_digit:
+    movzx eax, BYTE PTR [rdi + r8]
+    add eax, eax
+    add eax, edx
+    xor edx, edx
+    cmp eax, 10
+    jb .float_double_store
+    sub eax, 10
+    inc edx
+.float_double_store:
+    mov BYTE PTR [rdi + r8], al
+    inc r8
+    cmp r8, rsi
+    jb .float_double_digit
+    test edx, edx
+    jz .float_doubled
+    mov BYTE PTR [rdi + rsi], 1
+    inc rsi
+.float_doubled:
+    dec ecx
+    jnz .float_double
+    # Most significant digit first into [rbp - 688], then ".0"
+    lea rdx, [rbp - 688]
+.float_integral_text:
+    dec rsi
+    movzx eax, BYTE PTR [rdi + rsi]
+    add al, '0'
+    mov BYTE PTR [rdx], al
+    inc rdx
+    test rsi, rsi
+    jnz .float_integral_text
+    mov DWORD PTR [rdx], 0x302e      # ".0"
+    lea rdi, [rbp - 688]
+    call print_string
+    #
+.float_done:
+    pop r8
+    pop rsi
+    pop rdi
+    pop rdx
+    pop rcx
+    mov rsp, rbp
+    pop rbp
+    ret
+
+print_newline:
+    push rax
+    push rdi
+    push rsi
+    push rdx
+    #
+    mov rax, 1
+    mov rdi, 1
+    lea rsi, [newline]
+    mov rdx, 1
+    call serial_write_64
+    #
+    pop rdx
+    pop rsi
+    pop rdi
+    pop rax
+    ret
+
+# ========== FRAMEBUFFER ROUTINES ==========
+fb_fill:
+    movzx eax, dil
+    imul eax, eax, 0x01010101
+    .byte 0x66, 0x0F, 0x6E, 0xC0  # movd xmm0, eax
+    .byte 0xC4, 0xE2, 0x7D, 0x58, 0xC0  # vpbroadcastd ymm0, xmm0
+    mov rdi, [rip + fb_info + 0]
+    mov ecx, [rip + fb_info + 16]
+    imul ecx, [rip + fb_info + 12]
+    shr ecx, 5        # whole 32-byte stores
+1:
+    .byte 0xC5, 0xFD, 0x7F, 0x07  # vmovdqa [rdi], ymm0
+    add rdi, 32
+    dec ecx
+    jnz 1b
+    .byte 0xC5, 0xF8, 0x77  # vzeroupper
+    ret
+
+fb_rect:
+    add rdx, rdi        # right edge
+    add rcx, rsi        # bottom edge
+    xor eax, eax
+    cmp rdi, rax
+    cmovl rdi, rax
+    cmp rsi, rax
+    cmovl rsi, rax
+    mov eax, [rip + fb_info + 8]
+    cmp rdx, rax
+    cmovg rdx, rax
+    mov eax, [rip + fb_info + 12]
+    cmp rcx, rax
+    cmovg rcx, rax
+    sub rdx, rdi        # clipped width
+    jle 2f
+    sub rcx, rsi        # clipped height
+    jle 2f
+    mov r9d, [rip + fb_info + 16]
+    imul rsi, r9
+    add rdi, rsi
+    add rdi, [rip + fb_info + 0]        # base + y * pitch + x
+    mov eax, r8d
+    mov r8, rcx
+1:  mov r10, rdi
+    mov rcx, rdx
+    rep stosb
+    lea rdi, [r10 + r9]
+    dec r8
+    jnz 1b
+2:  ret
+
+fb_line:
+    push rbx
+    push r12
+    push r13
+    push r14
+    mov r9, rdx
+    sub r9, rdi
+    mov r10, 1          # x step
+    jge 1f
+    neg r9
+    neg r10
+1:  mov r11, rcx
+    sub r11, rsi
+    mov r12, 1          # y step
+    jge 2f
+    neg r11
+    neg r12
+2:  neg r11             # dy = -|y1 - y0|
+    lea r13, [r9 + r11] # error
+    mov ebx, [rip + fb_info + 8]
+    mov r14d, [rip + fb_info + 12]
+3:  cmp rdi, rbx        # unsigned, so negative coordinates fail too
+    jae 4f
+    cmp rsi, r14
+    jae 4f
+    mov eax, [rip + fb_info + 16]
+    imul rax, rsi
+    add rax, rdi
+    add rax, [rip + fb_info + 0]
+    mov byte ptr [rax], r8b
+4:  cmp rdi, rdx
+    jne 5f
+    cmp rsi, rcx
+    je 7f
+5:  lea rax, [r13 + r13]
+    cmp rax, r11
+    jl 6f
+    add r13, r11
+    add rdi, r10
+6:  cmp rax, r9
+    jg 3b
+    add r13, r9
+    add rsi, r12
+    jmp 3b
+7:  pop r14
+    pop r13
+    pop r12
+    pop rbx
+    ret
+
+fb_set_palette:
+    mov r8d, edx
+    mov dx, 0x3C8
+    mov eax, edi
+    out dx, al
+    inc dx
+    mov eax, esi
+    shr al, 2
+    out dx, al
+    mov eax, r8d
+    shr al, 2
+    out dx, al
+    mov eax, ecx
+    shr al, 2
+    out dx, al
+    ret
+
+fb_present:
+    ret                 # single buffered: drawing is already visible
+    .section .text
+serial_init_64:
+    # 115200 baud, 8N1, FIFOs enabled and cleared
+    push rax
+    push rdx
+    mov dx, 0x3F9
+    mov al, 0x00
+    out dx, al
+    mov dx, 0x3FB
+    mov al, 0x80
+    out dx, al
+    mov dx, 0x3F8
+    mov al, 0x01
+    out dx, al
+    mov dx, 0x3F9
+    mov al, 0x00
+    out dx, al
+    mov dx, 0x3FB
+    mov al, 0x03
+    out dx, al
+    mov dx, 0x3FA
+    mov al, 0xC7
+    out dx, al
+    pop rdx
+    pop rax
+    ret
+
+serial_write_char_64:
+    # Input: dil = byte, sent once the transmit holding register is empty
+    push rax
+    push rdx
+    mov dx, 0x3FD
+.serial_wait:
+    in al, dx
+    test al, 0x20
+    jz .serial_wait
+    mov dx, 0x3F8
+    mov eax, edi
+    out dx, al
+    pop rdx
+    pop rax
+    ret
+
+serial_write_string_64:
+    # Input: rdi = NUL-terminated string
+    push rdi
+    push rsi
+    mov rsi, rdi
+.serial_string_loop:
+    movzx edi, BYTE PTR [rsi]
+    test edi, edi
+    jz .serial_string_done
+    call serial_write_char_64
+    inc rsi
+    jmp .serial_string_loop
+.serial_string_done:
+    pop rsi
+    pop rdi
+    ret
+
+serial_write_64:
+    # Input: rsi = bytes, rdx = count
+    push rdi
+    push rsi
+    push rdx
+.serial_write_loop:
+    test rdx, rdx
+    jz .serial_write_done
+    movzx edi, BYTE PTR [rsi]
+    call serial_write_char_64
+    inc rsi
+    dec rdx
+    jmp .serial_write_loop
+.serial_write_done:
+    pop rdx
+    pop rsi
+    pop rdi
+    ret
+
+    .section .data
+newline:
+    .byte 10, 0
+
+rc_enabled:
+    .byte 0
+
+fb_info:
+    .quad 0xA0000        # base
+    .long 320        # width
+    .long 200        # height
+    .long 320        # pitch
+    .long 1        # bytes per pixel
+    .quad 0xA0000        # front
+
+# String literals
+str_000000017c96cd08:  # "fizz"
+    .byte 0x66, 0x69, 0x7a, 0x7a, 0x00
+str_00000031105d725e:  # "small"
+    .byte 0x73, 0x6d, 0x61, 0x6c, 0x6c, 0x00
+
+    .att_syntax
@@ -0,0 +1,1201 @@
+    .intel_syntax noprefix
+    .section .text
+    .globl _start
+
+_start:
+    mov rbp, rsp
+    and rsp, -16        # 16-byte align stack
+    
+    call serial_init_64
+    call main
+    
+.halt:
+    hlt
+    jmp .halt
+
+main:
+    push rbp
+    mov rbp, rsp
+    sub rsp, 32        # Allocate 32 bytes for locals
+    # Variables span from [rbp - 8] to [rbp - 32]
+
+    # @line 2
+    # fb_init(320, 200): mode 0x13 is set by the loader
+    # @line 3
+    # fb_fill(1) with Avx2 stores
+    mov eax, 0x01010101
+    .byte 0x66, 0x0F, 0x6E, 0xC0  # movd xmm0, eax
+    .byte 0xC4, 0xE2, 0x7D, 0x58, 0xC0  # vpbroadcastd ymm0, xmm0
+    mov edi, 0xA0000
+    mov ecx, 2000
+1:
+    .byte 0xC5, 0xFD, 0x7F, 0x07  # vmovdqa [rdi], ymm0
+    add rdi, 32
+    dec ecx
+    jnz 1b
+    .byte 0xC5, 0xF8, 0x77  # vzeroupper
+    # @line 4
+    # For loop over range() into i
+    # Number: 0
+    mov rax, 0
+    mov QWORD PTR [rbp - 16], rax
+    # Number: 4
+    mov rax, 4
+    mov QWORD PTR [rbp - 24], rax
+for_start_0:
+    mov rax, QWORD PTR [rbp - 16]
+    cmp rax, QWORD PTR [rbp - 24]
+    jge for_end_0
+    mov QWORD PTR [rbp - 8], rax
+    # For body
+    # Function call: fb_rect
+    # Binary operation
+    # Variable: i at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    push rax
+    # Number: 2
+    mov rax, 2
+    mov rbx, rax
+    pop rax
+    add rax, rbx
+    push rax
+    # Number: 30
+    mov rax, 30
+    push rax
+    # Number: 30
+    mov rax, 30
+    push rax
+    # Number: 20
+    mov rax, 20
+    push rax
+    # Binary operation
+    # Number: 10
+    mov rax, 10
+    push rax
+    # Binary operation
+    # Variable: i at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    push rax
+    # Number: 40
+    mov rax, 40
+    mov rbx, rax
+    pop rax
+    imul rax, rbx
+    mov rbx, rax
+    pop rax
+    add rax, rbx
+    push rax
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    pop r8
+    call fb_rect
+for_next_0:
+    mov rax, 1
+    add QWORD PTR [rbp - 16], rax
+    jmp for_start_0
+for_end_0:
+    # @line 7
+    # fb_line(0, 199, 319, 0, 15)
+    mov byte ptr [0xAF8C0], 15
+    mov byte ptr [0xAF781], 15
+    mov byte ptr [0xAF782], 15
+    mov byte ptr [0xAF643], 15
+    mov byte ptr [0xAF644], 15
+    mov byte ptr [0xAF505], 15
+    mov byte ptr [0xAF3C6], 15
+    mov byte ptr [0xAF3C7], 15
+    mov byte ptr [0xAF288], 15
+    mov byte ptr [0xAF149], 15
+    mov byte ptr [0xAF14A], 15
+    mov byte ptr [0xAF00B], 15
+    mov byte ptr [0xAF00C], 15
+    mov byte ptr [0xAEECD], 15
+    mov byte ptr [0xAED8E], 15
+    mov byte ptr [0xAED8F], 15
+    mov byte ptr [0xAEC50], 15
+    mov byte ptr [0xAEB11], 15
+    mov byte ptr [0xAEB12], 15
+    mov byte ptr [0xAE9D3], 15
+    mov byte ptr [0xAE9D4], 15
+    mov byte ptr [0xAE895], 15
+    mov byte ptr [0xAE756], 15
+    mov byte ptr [0xAE757], 15
+    mov byte ptr [0xAE618], 15
+    mov byte ptr [0xAE4D9], 15
+    mov byte ptr [0xAE4DA], 15
+    mov byte ptr [0xAE39B], 15
+    mov byte ptr [0xAE39C], 15
+    mov byte ptr [0xAE25D], 15
+    mov byte ptr [0xAE11E], 15
+    mov byte ptr [0xAE11F], 15
+    mov byte ptr [0xADFE0], 15
+    mov byte ptr [0xADEA1], 15
+    mov byte ptr [0xADEA2], 15
+    mov byte ptr [0xADD63], 15
+    mov byte ptr [0xADD64], 15
+    mov byte ptr [0xADC25], 15
+    mov byte ptr [0xADAE6], 15
+    mov byte ptr [0xADAE7], 15
+    mov byte ptr [0xAD9A8], 15
+    mov byte ptr [0xAD869], 15
+    mov byte ptr [0xAD86A], 15
+    mov byte ptr [0xAD72B], 15
+    mov byte ptr [0xAD72C], 15
+    mov byte ptr [0xAD5ED], 15
+    mov byte ptr [0xAD4AE], 15
+    mov byte ptr [0xAD4AF], 15
+    mov byte ptr [0xAD370], 15
+    mov byte ptr [0xAD231], 15
+    mov byte ptr [0xAD232], 15
+    mov byte ptr [0xAD0F3], 15
+    mov byte ptr [0xAD0F4], 15
+    mov byte ptr [0xACFB5], 15
+    mov byte ptr [0xACE76], 15
+    mov byte ptr [0xACE77], 15
+    mov byte ptr [0xACD38], 15
+    mov byte ptr [0xACBF9], 15
+    mov byte ptr [0xACBFA], 15
+    mov byte ptr [0xACABB], 15
+    mov byte ptr [0xACABC], 15
+    mov byte ptr [0xAC97D], 15
+    mov byte ptr [0xAC83E], 15
+    mov byte ptr [0xAC83F], 15
+    mov byte ptr [0xAC700], 15
+    mov byte ptr [0xAC5C1], 15
+    mov byte ptr [0xAC5C2], 15
+    mov byte ptr [0xAC483], 15
+    mov byte ptr [0xAC484], 15
+    mov byte ptr [0xAC345], 15
+    mov byte ptr [0xAC206], 15
+    mov byte ptr [0xAC207], 15
+    mov byte ptr [0xAC0C8], 15
+    mov byte ptr [0xABF89], 15
+    mov byte ptr [0xABF8A], 15
+    mov byte ptr [0xABE4B], 15
+    mov byte ptr [0xABE4C], 15
+    mov byte ptr [0xABD0D], 15
+    mov byte ptr [0xABBCE], 15
+    mov byte ptr [0xABBCF], 15
+    mov byte ptr [0xABA90], 15
+    mov byte ptr [0xAB951], 15
+    mov byte ptr [0xAB952], 15
+    mov byte ptr [0xAB813], 15
+    mov byte ptr [0xAB814], 15
+    mov byte ptr [0xAB6D5], 15
+    mov byte ptr [0xAB596], 15
+    mov byte ptr [0xAB597], 15
+    mov byte ptr [0xAB458], 15
+    mov byte ptr [0xAB319], 15
+    mov byte ptr [0xAB31A], 15
+    mov byte ptr [0xAB1DB], 15
+    mov byte ptr [0xAB1DC], 15
+    mov byte ptr [0xAB09D], 15
+    mov byte ptr [0xAAF5E], 15
+    mov byte ptr [0xAAF5F], 15
+    mov byte ptr [0xAAE20], 15
+    mov byte ptr [0xAACE1], 15
+    mov byte ptr [0xAACE2], 15
+    mov byte ptr [0xAABA3], 15
+    mov byte ptr [0xAABA4], 15
+    mov byte ptr [0xAAA65], 15
+    mov byte ptr [0xAA926], 15
+    mov byte ptr [0xAA927], 15
+    mov byte ptr [0xAA7E8], 15
+    mov byte ptr [0xAA6A9], 15
+    mov byte ptr [0xAA6AA], 15
+    mov byte ptr [0xAA56B], 15
+    mov byte ptr [0xAA56C], 15
+    mov byte ptr [0xAA42D], 15
+    mov byte ptr [0xAA2EE], 15
+    mov byte ptr [0xAA2EF], 15
+    mov byte ptr [0xAA1B0], 15
+    mov byte ptr [0xAA1B1], 15
+    mov byte ptr [0xAA072], 15
+    mov byte ptr [0xA9F33], 15
+    mov byte ptr [0xA9F34], 15
+    mov byte ptr [0xA9DF5], 15
+    mov byte ptr [0xA9CB6], 15
+    mov byte ptr [0xA9CB7], 15
+    mov byte ptr [0xA9B78], 15
+    mov byte ptr [0xA9B79], 15
+    mov byte ptr [0xA9A3A], 15
+    mov byte ptr [0xA98FB], 15
+    mov byte ptr [0xA98FC], 15
+    mov byte ptr [0xA97BD], 15
+    mov byte ptr [0xA967E], 15
+    mov byte ptr [0xA967F], 15
+    mov byte ptr [0xA9540], 15
+    mov byte ptr [0xA9541], 15
+    mov byte ptr [0xA9402], 15
+    mov byte ptr [0xA92C3], 15
+    mov byte ptr [0xA92C4], 15
+    mov byte ptr [0xA9185], 15
+    mov byte ptr [0xA9046], 15
+    mov byte ptr [0xA9047], 15
+    mov byte ptr [0xA8F08], 15
+    mov byte ptr [0xA8F09], 15
+    mov byte ptr [0xA8DCA], 15
+    mov byte ptr [0xA8C8B], 15
+    mov byte ptr [0xA8C8C], 15
+    mov byte ptr [0xA8B4D], 15
+    mov byte ptr [0xA8A0E], 15
+    mov byte ptr [0xA8A0F], 15
+    mov byte ptr [0xA88D0], 15
+    mov byte ptr [0xA88D1], 15
+    mov byte ptr [0xA8792], 15
+    mov byte ptr [0xA8653], 15
+    mov byte ptr [0xA8654], 15
+    mov byte ptr [0xA8515], 15
+    mov byte ptr [0xA83D6], 15
+    mov byte ptr [0xA83D7], 15
+    mov byte ptr [0xA8298], 15
+    mov byte ptr [0xA8299], 15
+    mov byte ptr [0xA815A], 15
+    mov byte ptr [0xA801B], 15
+    mov byte ptr [0xA801C], 15
+    mov byte ptr [0xA7EDD], 15
+    mov byte ptr [0xA7D9E], 15
+    mov byte ptr [0xA7D9F], 15
+    mov byte ptr [0xA7C60], 15
+    mov byte ptr [0xA7C61], 15
+    mov byte ptr [0xA7B22], 15
+    mov byte ptr [0xA79E3], 15
+    mov byte ptr [0xA79E4], 15
+    mov byte ptr [0xA78A5], 15
+    mov byte ptr [0xA7766], 15
+    mov byte ptr [0xA7767], 15
+    mov byte ptr [0xA7628], 15
+    mov byte ptr [0xA7629], 15
+    mov byte ptr [0xA74EA], 15
+    mov byte ptr [0xA73AB], 15
+    mov byte ptr [0xA73AC], 15
+    mov byte ptr [0xA726D], 15
+    mov byte ptr [0xA712E], 15
+    mov byte ptr [0xA712F], 15
+    mov byte ptr [0xA6FF0], 15
+    mov byte ptr [0xA6FF1], 15
+    mov byte ptr [0xA6EB2], 15
+    mov byte ptr [0xA6D73], 15
+    mov byte ptr [0xA6D74], 15
+    mov byte ptr [0xA6C35], 15
+    mov byte ptr [0xA6AF6], 15
+    mov byte ptr [0xA6AF7], 15
+    mov byte ptr [0xA69B8], 15
+    mov byte ptr [0xA69B9], 15
+    mov byte ptr [0xA687A], 15
+    mov byte ptr [0xA673B], 15
+    mov byte ptr [0xA673C], 15
+    mov byte ptr [0xA65FD], 15
+    mov byte ptr [0xA64BE], 15
+    mov byte ptr [0xA64BF], 15
+    mov byte ptr [0xA6380], 15
+    mov byte ptr [0xA6381], 15
+    mov byte ptr [0xA6242], 15
+    mov byte ptr [0xA6103], 15
+    mov byte ptr [0xA6104], 15
+    mov byte ptr [0xA5FC5], 15
+    mov byte ptr [0xA5E86], 15
+    mov byte ptr [0xA5E87], 15
+    mov byte ptr [0xA5D48], 15
+    mov byte ptr [0xA5D49], 15
+    mov byte ptr [0xA5C0A], 15
+    mov byte ptr [0xA5ACB], 15
+    mov byte ptr [0xA5ACC], 15
+    mov byte ptr [0xA598D], 15
+    mov byte ptr [0xA584E], 15
+    mov byte ptr [0xA584F], 15
+    mov byte ptr [0xA5710], 15
+    mov byte ptr [0xA5711], 15
+    mov byte ptr [0xA55D2], 15
+    mov byte ptr [0xA5493], 15
+    mov byte ptr [0xA5494], 15
+    mov byte ptr [0xA5355], 15
+    mov byte ptr [0xA5356], 15
+    mov byte ptr [0xA5217], 15
+    mov byte ptr [0xA50D8], 15
+    mov byte ptr [0xA50D9], 15
+    mov byte ptr [0xA4F9A], 15
+    mov byte ptr [0xA4E5B], 15
+    mov byte ptr [0xA4E5C], 15
+    mov byte ptr [0xA4D1D], 15
+    mov byte ptr [0xA4D1E], 15
+    mov byte ptr [0xA4BDF], 15
+    mov byte ptr [0xA4AA0], 15
+    mov byte ptr [0xA4AA1], 15
+    mov byte ptr [0xA4962], 15
+    mov byte ptr [0xA4823], 15
+    mov byte ptr [0xA4824], 15
+    mov byte ptr [0xA46E5], 15
+    mov byte ptr [0xA46E6], 15
+    mov byte ptr [0xA45A7], 15
+    mov byte ptr [0xA4468], 15
+    mov byte ptr [0xA4469], 15
+    mov byte ptr [0xA432A], 15
+    mov byte ptr [0xA41EB], 15
+    mov byte ptr [0xA41EC], 15
+    mov byte ptr [0xA40AD], 15
+    mov byte ptr [0xA40AE], 15
+    mov byte ptr [0xA3F6F], 15
+    mov byte ptr [0xA3E30], 15
+    mov byte ptr [0xA3E31], 15
+    mov byte ptr [0xA3CF2], 15
+    mov byte ptr [0xA3BB3], 15
+    mov byte ptr [0xA3BB4], 15
+    mov byte ptr [0xA3A75], 15
+    mov byte ptr [0xA3A76], 15
+    mov byte ptr [0xA3937], 15
+    mov byte ptr [0xA37F8], 15
+    mov byte ptr [0xA37F9], 15
+    mov byte ptr [0xA36BA], 15
+    mov byte ptr [0xA357B], 15
+    mov byte ptr [0xA357C], 15
+    mov byte ptr [0xA343D], 15
+    mov byte ptr [0xA343E], 15
+    mov byte ptr [0xA32FF], 15
+    mov byte ptr [0xA31C0], 15
+    mov byte ptr [0xA31C1], 15
+    mov byte ptr [0xA3082], 15
+    mov byte ptr [0xA2F43], 15
+    mov byte ptr [0xA2F44], 15
+    mov byte ptr [0xA2E05], 15
+    mov byte ptr [0xA2E06], 15
+    mov byte ptr [0xA2CC7], 15
+    mov byte ptr [0xA2B88], 15
+    mov byte ptr [0xA2B89], 15
+    mov byte ptr [0xA2A4A], 15
+    mov byte ptr [0xA290B], 15
+    mov byte ptr [0xA290C], 15
+    mov byte ptr [0xA27CD], 15
+    mov byte ptr [0xA27CE], 15
+    mov byte ptr [0xA268F], 15
+    mov byte ptr [0xA2550], 15
+    mov byte ptr [0xA2551], 15
+    mov byte ptr [0xA2412], 15
+    mov byte ptr [0xA22D3], 15
+    mov byte ptr [0xA22D4], 15
+    mov byte ptr [0xA2195], 15
+    mov byte ptr [0xA2196], 15
+    mov byte ptr [0xA2057], 15
+    mov byte ptr [0xA1F18], 15
+    mov byte ptr [0xA1F19], 15
+    mov byte ptr [0xA1DDA], 15
+    mov byte ptr [0xA1C9B], 15
+    mov byte ptr [0xA1C9C], 15
+    mov byte ptr [0xA1B5D], 15
+    mov byte ptr [0xA1B5E], 15
+    mov byte ptr [0xA1A1F], 15
+    mov byte ptr [0xA18E0], 15
+    mov byte ptr [0xA18E1], 15
+    mov byte ptr [0xA17A2], 15
+    mov byte ptr [0xA1663], 15
+    mov byte ptr [0xA1664], 15
+    mov byte ptr [0xA1525], 15
+    mov byte ptr [0xA1526], 15
+    mov byte ptr [0xA13E7], 15
+    mov byte ptr [0xA12A8], 15
+    mov byte ptr [0xA12A9], 15
+    mov byte ptr [0xA116A], 15
+    mov byte ptr [0xA102B], 15
+    mov byte ptr [0xA102C], 15
+    mov byte ptr [0xA0EED], 15
+    mov byte ptr [0xA0EEE], 15
+    mov byte ptr [0xA0DAF], 15
+    mov byte ptr [0xA0C70], 15
+    mov byte ptr [0xA0C71], 15
+    mov byte ptr [0xA0B32], 15
+    mov byte ptr [0xA09F3], 15
+    mov byte ptr [0xA09F4], 15
+    mov byte ptr [0xA08B5], 15
+    mov byte ptr [0xA08B6], 15
+    mov byte ptr [0xA0777], 15
+    mov byte ptr [0xA0638], 15
+    mov byte ptr [0xA0639], 15
+    mov byte ptr [0xA04FA], 15
+    mov byte ptr [0xA03BB], 15
+    mov byte ptr [0xA03BC], 15
+    mov byte ptr [0xA027D], 15
+    mov byte ptr [0xA027E], 15
+    mov byte ptr [0xA013F], 15
+    # @line 8
+    # Function call: fb_text
+    # Number: 1
+    mov rax, 1
+    push rax
+    # Number: 15
+    mov rax, 15
+    push rax
+    # String: "Earthang"
+    lea rax, [str_001ae5be669c948f]
+    push rax
+    # Number: 100
+    mov rax, 100
+    push rax
+    # Number: 100
+    mov rax, 100
+    push rax
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    pop r8
+    call fb_text
+    # @line 9
+    # Function call: fb_print
+    # String: "ready\n"
+    lea rax, [str_000006531925b124]
+    push rax
+    pop rdi
+    call fb_print
+    # @end
+    xor rax, rax
+
+.main_epilogue:
+    mov rsp, rbp
+    pop rbp
+    ret
+
+print_string:
+    push rax
+    push rdi
+    push rsi
+    push rdx
+    #
+    mov rsi, rdi
+    xor rdx, rdx
+.count_loop:
+    cmp BYTE PTR [rsi + rdx], 0
+    je .count_done
+    inc rdx
+    jmp .count_loop
+.count_done:
+    #
+    mov rax, 1
+    mov rdi, 1
+    call serial_write_64
+    #
+    pop rdx
+    pop rsi
+    pop rdi
+    pop rax
+    ret
+
+print_decimal:
+    # Input: rax = integer
+    push rbp
+    mov rbp, rsp
+    sub rsp, 32
+    #
+    push rbx
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    #
+    mov QWORD PTR [rbp - 8], rax
+    #
+    lea rdi, [rsp + 31]
+    mov BYTE PTR [rdi], 0
+    #
+    mov rax, QWORD PTR [rbp - 8]
+    test rax, rax
+    jns .positive
+    neg rax
+    #
+.positive:
+    mov rbx, 10
+    #
+.convert_loop:
+    xor rdx, rdx
+    div rbx
+    add dl, '0'
+    dec rdi
+    mov BYTE PTR [rdi], dl
+    test rax, rax
+    jnz .convert_loop
+    #
+    mov rax, QWORD PTR [rbp - 8]
+    test rax, rax
+    jns .print_it
+    dec rdi
+    mov BYTE PTR [rdi], '-'
+    #
+.print_it:
+    lea rsi, [rsp + 31]
+    sub rsi, rdi
+    #
+    mov rax, 1
+    mov rdx, rsi
+    mov rsi, rdi
+    mov rdi, 1
+    call serial_write_64
+    #
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    pop rbx
+    #
+    mov rsp, rbp
+    pop rbp
+    ret
+
+str_cmp:
+    # Input: rdi, rsi = strings; output: rax = difference at first mismatch
+    push rcx
+    push rdi
+    push rsi
+.str_cmp_loop:
+    movzx eax, BYTE PTR [rdi]
+    movzx ecx, BYTE PTR [rsi]
+    cmp eax, ecx
+    jne .str_cmp_done
+    test eax, eax
+    jz .str_cmp_done
+    inc rdi
+    inc rsi
+    jmp .str_cmp_loop
+.str_cmp_done:
+    sub rax, rcx
+    pop rsi
+    pop rdi
+    pop rcx
+    ret
+
+print_float:
+    # Input: rax = IEEE double, printed with up to six decimals
+    push rbp
+    mov rbp, rsp
+    sub rsp, 704
+    push rcx
+    push rdx
+    push rdi
+    push rsi
+    push r8
+    #
+    mov rcx, 0x7ff0000000000000
+    mov rdx, rax
+    btr rdx, 63
+    cmp rdx, rcx
+    jbe .float_signed
+    mov DWORD PTR [rbp - 32], 0x4e614e    # "NaN"
+    jmp .float_word
+    #
+.float_signed:
+    btr rax, 63
+    jnc .float_positive
+    mov WORD PTR [rbp - 32], 0x2d    # "-"
+    lea rdi, [rbp - 32]
+    call print_string
+.float_positive:
+    mov rcx, 0x7ff0000000000000     # print_string clobbers rcx
+    cmp rax, rcx
+    jne .float_number
+    mov DWORD PTR [rbp - 32], 0x666e69    # "inf"
+.float_word:
+    lea rdi, [rbp - 32]
+    call print_string
+    jmp .float_done
+    #
+.float_number:
+    mov rcx, rax
+    shr rcx, 52                # biased exponent
+    cmp ecx, 1076
+    jae .float_integral
+    # Below 2^53 the integer part fits a register and taking it away leaves the exact fraction
+    movq xmm0, rax
+    cvttsd2si rax, xmm0
+    cvtsi2sd xmm1, rax
+    subsd xmm0, xmm1
+    mov rcx, 1000000
+    cvtsi2sd xmm1, rcx
+    mulsd xmm0, xmm1
+    cvtsd2si rdx, xmm0         # round to the nearest millionth
+    cmp rdx, rcx
+    jb .float_split
+    inc rax                    # the fraction rounded up to a whole one
+    xor edx, edx
+.float_split:
+    mov QWORD PTR [rbp - 8], rdx
+    call print_decimal
+    mov WORD PTR [rbp - 32], 0x2e    # "."
+    lea rdi, [rbp - 32]
+    call print_string
+    #
+    # Six fraction digits go to [rbp - 24 .. rbp - 19]
+    mov rax, QWORD PTR [rbp - 8]
+    lea rdi, [rbp - 18]
+    mov BYTE PTR [rdi], 0
+    mov rcx, 10
+.float_digit_loop:
+    xor rdx, rdx
+    div rcx
+    add dl, '0'
+    dec rdi
+    mov BYTE PTR [rdi], dl
+    lea rdx, [rbp - 24]
+    cmp rdi, rdx
+    jne .float_digit_loop
+    #
+    # Drop trailing zeros but keep at least one digit
+    lea rdi, [rbp - 19]
+.float_trim_loop:
+    cmp rdi, rdx
+    je .float_print
+    cmp BYTE PTR [rdi], '0'
+    jne .float_print
+    mov BYTE PTR [rdi], 0
+    dec rdi
+    jmp .float_trim_loop
+.float_print:
+    mov rdi, rdx
+    call print_string
+    #
+    jmp .float_done
+    #
+.float_integral:
+    # From 2^53 up a double is the whole number mantissa * 2^(exponent - 1075).
+    # Its decimal digits, least significant first from [rbp - 352], start as
+    # the mantissa's and are doubled once per power of two
+    sub ecx, 1075
+    mov rdx, 0xfffffffffffff
+    and rax, rdx
+    bts rax, 52
+    lea rdi, [rbp - 352]
+    xor esi, esi
+    mov r8, 10
+.float_integral_digits:
+    xor edx, edx
+    div r8
+    mov BYTE PTR [rdi + rsi], dl
+    inc rsi
+    test rax, rax
+    jnz .float_integral_digits
+.float_double:
+    xor edx, edx               # carry
+    xor r8d, r8d
+.float_double_digit:
+    movzx eax, BYTE PTR [rdi + r8]
+    add eax, eax
+    add eax, edx
+    xor edx, edx
+    cmp eax, 10
+    jb .float_double_store
+    sub eax, 10
+    inc edx
+.float_double_store:
+    mov BYTE PTR [rdi + r8], al
+    inc r8
+    cmp r8, rsi
+    jb .float_double_digit
+    test edx, edx
+    jz .float_doubled
+    mov BYTE PTR [rdi + rsi], 1
+    inc rsi
+.float_doubled:
+    dec ecx
+    jnz .float_double
+    # Most significant digit first into [rbp - 688], then ".0"
+    lea rdx, [rbp - 688]
+.float_integral_text:
+    dec rsi
+    movzx eax, BYTE PTR [rdi + rsi]
+    add al, '0'
+    mov BYTE PTR [rdx], al
+    inc rdx
+    test rsi, rsi
+    jnz .float_integral_text
+    mov DWORD PTR [rdx], 0x302e      # ".0"
+    lea rdi, [rbp - 688]
+    call print_string
+    #
+.float_done:
+    pop r8
+    pop rsi
+    pop rdi
+    pop rdx
+    pop rcx
+    mov rsp, rbp
+    pop rbp
+    ret
+
+print_newline:
+    push rax
+    push rdi
+    push rsi
+    push rdx
+    #
+    mov rax, 1
+    mov rdi, 1
+    lea rsi, [newline]
+    mov rdx, 1
+    call serial_write_64
+    #
+    pop rdx
+    pop rsi
+    pop rdi
+    pop rax
+    ret
+
+# ========== FRAMEBUFFER ROUTINES ==========
+fb_fill:
+    movzx eax, dil
+    imul eax, eax, 0x01010101
+    .byte 0x66, 0x0F, 0x6E, 0xC0  # movd xmm0, eax
+    .byte 0xC4, 0xE2, 0x7D, 0x58, 0xC0  # vpbroadcastd ymm0, xmm0
+    mov rdi, [rip + fb_info + 0]
+    mov ecx, [rip + fb_info + 16]
+    imul ecx, [rip + fb_info + 12]
+    shr ecx, 5        # whole 32-byte stores
+1:
+    .byte 0xC5, 0xFD, 0x7F, 0x07  # vmovdqa [rdi], ymm0
+    add rdi, 32
+    dec ecx
+    jnz 1b
+    .byte 0xC5, 0xF8, 0x77  # vzeroupper
+    ret
+
+fb_rect:
+    add rdx, rdi        # right edge
+    add rcx, rsi        # bottom edge
+    xor eax, eax
+    cmp rdi, rax
+    cmovl rdi, rax
+    cmp rsi, rax
+    cmovl rsi, rax
+    mov eax, [rip + fb_info + 8]
+    cmp rdx, rax
+    cmovg rdx, rax
+    mov eax, [rip + fb_info + 12]
+    cmp rcx, rax
+    cmovg rcx, rax
+    sub rdx, rdi        # clipped width
+    jle 2f
+    sub rcx, rsi        # clipped height
+    jle 2f
+    mov r9d, [rip + fb_info + 16]
+    imul rsi, r9
+    add rdi, rsi
+    add rdi, [rip + fb_info + 0]        # base + y * pitch + x
+    mov eax, r8d
+    mov r8, rcx
+1:  mov r10, rdi
+    mov rcx, rdx
+    rep stosb
+    lea rdi, [r10 + r9]
+    dec r8
+    jnz 1b
+2:  ret
+
+fb_line:
+    push rbx
+    push r12
+    push r13
+    push r14
+    mov r9, rdx
+    sub r9, rdi
+    mov r10, 1          # x step
+    jge 1f
+    neg r9
+    neg r10
+1:  mov r11, rcx
+    sub r11, rsi
+    mov r12, 1          # y step
+    jge 2f
+    neg r11
+    neg r12
+2:  neg r11             # dy = -|y1 - y0|
+    lea r13, [r9 + r11] # error
+    mov ebx, [rip + fb_info + 8]
+    mov r14d, [rip + fb_info + 12]
+3:  cmp rdi, rbx        # unsigned, so negative coordinates fail too
+    jae 4f
+    cmp rsi, r14
+    jae 4f
+    mov eax, [rip + fb_info + 16]
+    imul rax, rsi
+    add rax, rdi
+    add rax, [rip + fb_info + 0]
+    mov byte ptr [rax], r8b
+4:  cmp rdi, rdx
+    jne 5f
+    cmp rsi, rcx
+    je 7f
+5:  lea rax, [r13 + r13]
+    cmp rax, r11
+    jl 6f
+    add r13, r11
+    add rdi, r10
+6:  cmp rax, r9
+    jg 3b
+    add r13, r9
+    add rsi, r12
+    jmp 3b
+7:  pop r14
+    pop r13
+    pop r12
+    pop rbx
+    ret
+
+fb_set_palette:
+    mov r8d, edx
+    mov dx, 0x3C8
+    mov eax, edi
+    out dx, al
+    inc dx
+    mov eax, esi
+    shr al, 2
+    out dx, al
+    mov eax, r8d
+    shr al, 2
+    out dx, al
+    mov eax, ecx
+    shr al, 2
+    out dx, al
+    ret
+
+fb_present:
+    ret                 # single buffered: drawing is already visible
+
+# ========== FRAMEBUFFER TEXT ==========
+fb_glyph:
+    push rbx
+    push r12
+    push r13
+    push r14
+    push r15
+    mov r12d, [rip + fb_info + 8]
+    mov r13d, [rip + fb_info + 12]
+    mov r14d, [rip + fb_info + 16]
+    sub edi, 32
+    cmp edi, 94
+    jbe 1f
+    mov edi, 31        # '?'
+1:  shl edi, 4
+    lea r9, [rip + fb_font]
+    add r9, rdi         # glyph rows
+    mov r10d, 16
+2:  movzx eax, byte ptr [r9]
+    mov r11, rsi
+    mov edi, 8
+3:  mov ebx, r8d
+    test al, 0x80
+    cmovnz ebx, ecx
+    cmp r11, r12        # unsigned, so negative coordinates fail too
+    jae 4f
+    cmp rdx, r13
+    jae 4f
+    mov r15, rdx
+    imul r15, r14
+    add r15, r11
+    add r15, [rip + fb_info + 0]
+    mov byte ptr [r15], bl
+4:  shl al, 1
+    inc r11
+    dec edi
+    jnz 3b
+    inc r9
+    inc rdx
+    dec r10d
+    jnz 2b
+    pop r15
+    pop r14
+    pop r13
+    pop r12
+    pop rbx
+    ret
+
+fb_text:
+    push rbx
+    push r12
+    push r13
+    push r14
+    push r15
+    mov rbx, rdx
+    mov r12, rdi
+    mov r13, rsi
+    mov r14, rcx
+    mov r15, r8
+1:  movzx edi, byte ptr [rbx]
+    test edi, edi
+    jz 2f
+    mov rsi, r12
+    mov rdx, r13
+    mov rcx, r14
+    mov r8, r15
+    call fb_glyph
+    add r12, 8
+    inc rbx
+    jmp 1b
+2:
+    pop r15
+    pop r14
+    pop r13
+    pop r12
+    pop rbx
+    ret
+
+fb_print:
+    push rbx
+    mov rbx, rdi
+1:  movzx edi, byte ptr [rbx]
+    test edi, edi
+    jz 5f
+    inc rbx
+    cmp edi, 10
+    je 3f
+    cmp edi, 8
+    je 6f
+    mov rsi, [rip + fb_cursor]
+    shl rsi, 3
+    mov rdx, [rip + fb_cursor + 8]
+    shl rdx, 4
+    movzx ecx, byte ptr [rip + fb_text_colors]
+    movzx r8d, byte ptr [rip + fb_text_colors + 1]
+    call fb_glyph
+    mov rax, [rip + fb_cursor]
+    inc rax
+    mov [rip + fb_cursor], rax
+    mov ecx, [rip + fb_info + 8]
+    shr ecx, 3        # columns
+    cmp rax, rcx
+    jb 1b
+3:  mov qword ptr [rip + fb_cursor], 0
+    mov rax, [rip + fb_cursor + 8]
+    inc rax
+    mov ecx, [rip + fb_info + 12]
+    shr ecx, 4        # rows
+    cmp rax, rcx
+    jb 4f
+    call fb_scroll
+    mov eax, [rip + fb_info + 12]
+    shr eax, 4
+    dec eax             # stay on the last row
+4:  mov [rip + fb_cursor + 8], rax
+    jmp 1b
+5:  pop rbx
+    ret
+6:  mov rax, [rip + fb_cursor]
+    test rax, rax       # not past the start of the row
+    jz 1b
+    dec rax
+    mov [rip + fb_cursor], rax
+    jmp 1b
+
+fb_scroll:
+    mov rdi, [rip + fb_info + 0]
+    mov eax, [rip + fb_info + 16]
+    shl eax, 4        # bytes per text row
+    lea rsi, [rdi + rax]
+    mov ecx, [rip + fb_info + 12]
+    shr ecx, 4
+    dec ecx
+    imul ecx, eax
+    rep movsb
+    mov ecx, eax
+    movzx eax, byte ptr [rip + fb_text_colors + 1]
+    rep stosb
+    ret
+    .section .text
+serial_init_64:
+    # 115200 baud, 8N1, FIFOs enabled and cleared
+    push rax
+    push rdx
+    mov dx, 0x3F9
+    mov al, 0x00
+    out dx, al
+    mov dx, 0x3FB
+    mov al, 0x80
+    out dx, al
+    mov dx, 0x3F8
+    mov al, 0x01
+    out dx, al
+    mov dx, 0x3F9
+    mov al, 0x00
+    out dx, al
+    mov dx, 0x3FB
+    mov al, 0x03
+    out dx, al
+    mov dx, 0x3FA
+    mov al, 0xC7
+    out dx, al
+    pop rdx
+    pop rax
+    ret
+
+serial_write_char_64:
+    # Input: dil = byte, sent once the transmit holding register is empty
+    push rax
+    push rdx
+    mov dx, 0x3FD
+.serial_wait:
+    in al, dx
+    test al, 0x20
+    jz .serial_wait
+    mov dx, 0x3F8
+    mov eax, edi
+    out dx, al
+    pop rdx
+    pop rax
+    ret
+
+serial_write_string_64:
+    # Input: rdi = NUL-terminated string
+    push rdi
+    push rsi
+    mov rsi, rdi
+.serial_string_loop:
+    movzx edi, BYTE PTR [rsi]
+    test edi, edi
+    jz .serial_string_done
+    call serial_write_char_64
+    inc rsi
+    jmp .serial_string_loop
+.serial_string_done:
+    pop rsi
+    pop rdi
+    ret
+
+serial_write_64:
+    # Input: rsi = bytes, rdx = count
+    push rdi
+    push rsi
+    push rdx
+.serial_write_loop:
+    test rdx, rdx
+    jz .serial_write_done
+    movzx edi, BYTE PTR [rsi]
+    call serial_write_char_64
+    inc rsi
+    dec rdx
+    jmp .serial_write_loop
+.serial_write_done:
+    pop rdx
+    pop rsi
+    pop rdi
+    ret
+
+    .section .data
+newline:
+    .byte 10, 0
+
+rc_enabled:
+    .byte 0
+
+fb_info:
+    .quad 0xA0000        # base
+    .long 320        # width
+    .long 200        # height
+    .long 320        # pitch
+    .long 1        # bytes per pixel
+    .quad 0xA0000        # front
+
+fb_cursor:
+    .quad 0, 0          # column, row
+fb_text_colors:
+    .byte 15, 0         # foreground, background
+fb_font:
+    .byte 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+    .byte 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00, 0x00, 0x10, 0x10, 0x00, 0x00
+    .byte 0x28, 0x28, 0x28, 0x28, 0x28, 0x28, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+    .byte 0x28, 0x28, 0x28, 0x28, 0x7C, 0x7C, 0x28, 0x28, 0x7C, 0x7C, 0x28, 0x28, 0x28, 0x28, 0x00, 0x00
+    .byte 0x10, 0x10, 0x3C, 0x3C, 0x50, 0x50, 0x38, 0x38, 0x14, 0x14, 0x78, 0x78, 0x10, 0x10, 0x00, 0x00
+    .byte 0x60, 0x60, 0x64, 0x64, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x4C, 0x4C, 0x0C, 0x0C, 0x00, 0x00
+    .byte 0x30, 0x30, 0x48, 0x48, 0x50, 0x50, 0x20, 0x20, 0x54, 0x54, 0x48, 0x48, 0x34, 0x34, 0x00, 0x00
+    .byte 0x10, 0x10, 0x10, 0x10, 0x20, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+    .byte 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x10, 0x10, 0x08, 0x08, 0x00, 0x00
+    .byte 0x20, 0x20, 0x10, 0x10, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x00, 0x00
+    .byte 0x00, 0x00, 0x10, 0x10, 0x54, 0x54, 0x38, 0x38, 0x54, 0x54, 0x10, 0x10, 0x00, 0x00, 0x00, 0x00
+    .byte 0x00, 0x00, 0x10, 0x10, 0x10, 0x10, 0x7C, 0x7C, 0x10, 0x10, 0x10, 0x10, 0x00, 0x00, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x30, 0x30, 0x10, 0x10, 0x20, 0x20, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7C, 0x7C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00
+    .byte 0x00, 0x00, 0x04, 0x04, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x40, 0x40, 0x00, 0x00, 0x00, 0x00
+    .byte 0x38, 0x38, 0x44, 0x44, 0x4C, 0x4C, 0x54, 0x54, 0x64, 0x64, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00
+    .byte 0x10, 0x10, 0x30, 0x30, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x38, 0x38, 0x00, 0x00
+    .byte 0x38, 0x38, 0x44, 0x44, 0x04, 0x04, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x7C, 0x7C, 0x00, 0x00
+    .byte 0x7C, 0x7C, 0x08, 0x08, 0x10, 0x10, 0x08, 0x08, 0x04, 0x04, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00
+    .byte 0x08, 0x08, 0x18, 0x18, 0x28, 0x28, 0x48, 0x48, 0x7C, 0x7C, 0x08, 0x08, 0x08, 0x08, 0x00, 0x00
+    .byte 0x7C, 0x7C, 0x40, 0x40, 0x78, 0x78, 0x04, 0x04, 0x04, 0x04, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00
+    .byte 0x18, 0x18, 0x20, 0x20, 0x40, 0x40, 0x78, 0x78, 0x44, 0x44, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00
+    .byte 0x7C, 0x7C, 0x04, 0x04, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00, 0x00
+    .byte 0x38, 0x38, 0x44, 0x44, 0x44, 0x44, 0x38, 0x38, 0x44, 0x44, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00
+    .byte 0x38, 0x38, 0x44, 0x44, 0x44, 0x44, 0x3C, 0x3C, 0x04, 0x04, 0x08, 0x08, 0x30, 0x30, 0x00, 0x00
+    .byte 0x00, 0x00, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00, 0x00, 0x00
+    .byte 0x00, 0x00, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00, 0x30, 0x30, 0x10, 0x10, 0x20, 0x20, 0x00, 0x00
+    .byte 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x40, 0x40, 0x20, 0x20, 0x10, 0x10, 0x08, 0x08, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x7C, 0x7C, 0x00, 0x00, 0x7C, 0x7C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+    .byte 0x20, 0x20, 0x10, 0x10, 0x08, 0x08, 0x04, 0x04, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x00, 0x00
+    .byte 0x38, 0x38, 0x44, 0x44, 0x04, 0x04, 0x08, 0x08, 0x10, 0x10, 0x00, 0x00, 0x10, 0x10, 0x00, 0x00
+    .byte 0x38, 0x38, 0x44, 0x44, 0x04, 0x04, 0x34, 0x34, 0x54, 0x54, 0x54, 0x54, 0x38, 0x38, 0x00, 0x00
+    .byte 0x38, 0x38, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x7C, 0x7C, 0x44, 0x44, 0x44, 0x44, 0x00, 0x00
+    .byte 0x78, 0x78, 0x44, 0x44, 0x44, 0x44, 0x78, 0x78, 0x44, 0x44, 0x44, 0x44, 0x78, 0x78, 0x00, 0x00
+    .byte 0x38, 0x38, 0x44, 0x44, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00
+    .byte 0x70, 0x70, 0x48, 0x48, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x48, 0x48, 0x70, 0x70, 0x00, 0x00
+    .byte 0x7C, 0x7C, 0x40, 0x40, 0x40, 0x40, 0x78, 0x78, 0x40, 0x40, 0x40, 0x40, 0x7C, 0x7C, 0x00, 0x00
+    .byte 0x7C, 0x7C, 0x40, 0x40, 0x40, 0x40, 0x78, 0x78, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x00, 0x00
+    .byte 0x38, 0x38, 0x44, 0x44, 0x40, 0x40, 0x5C, 0x5C, 0x44, 0x44, 0x44, 0x44, 0x3C, 0x3C, 0x00, 0x00
+    .byte 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x7C, 0x7C, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x00, 0x00
+    .byte 0x38, 0x38, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x38, 0x38, 0x00, 0x00
+    .byte 0x1C, 0x1C, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x48, 0x48, 0x30, 0x30, 0x00, 0x00
+    .byte 0x44, 0x44, 0x48, 0x48, 0x50, 0x50, 0x60, 0x60, 0x50, 0x50, 0x48, 0x48, 0x44, 0x44, 0x00, 0x00
+    .byte 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x7C, 0x7C, 0x00, 0x00
+    .byte 0x44, 0x44, 0x6C, 0x6C, 0x54, 0x54, 0x54, 0x54, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x00, 0x00
+    .byte 0x44, 0x44, 0x44, 0x44, 0x64, 0x64, 0x54, 0x54, 0x4C, 0x4C, 0x44, 0x44, 0x44, 0x44, 0x00, 0x00
+    .byte 0x38, 0x38, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00
+    .byte 0x78, 0x78, 0x44, 0x44, 0x44, 0x44, 0x78, 0x78, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x00, 0x00
+    .byte 0x38, 0x38, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x54, 0x54, 0x48, 0x48, 0x34, 0x34, 0x00, 0x00
+    .byte 0x78, 0x78, 0x44, 0x44, 0x44, 0x44, 0x78, 0x78, 0x50, 0x50, 0x48, 0x48, 0x44, 0x44, 0x00, 0x00
+    .byte 0x3C, 0x3C, 0x40, 0x40, 0x40, 0x40, 0x38, 0x38, 0x04, 0x04, 0x04, 0x04, 0x78, 0x78, 0x00, 0x00
+    .byte 0x7C, 0x7C, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00, 0x00
+    .byte 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00
+    .byte 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x28, 0x28, 0x10, 0x10, 0x00, 0x00
+    .byte 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x54, 0x54, 0x54, 0x54, 0x54, 0x54, 0x28, 0x28, 0x00, 0x00
+    .byte 0x44, 0x44, 0x44, 0x44, 0x28, 0x28, 0x10, 0x10, 0x28, 0x28, 0x44, 0x44, 0x44, 0x44, 0x00, 0x00
+    .byte 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x28, 0x28, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00, 0x00
+    .byte 0x7C, 0x7C, 0x04, 0x04, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x40, 0x40, 0x7C, 0x7C, 0x00, 0x00
+    .byte 0x38, 0x38, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x38, 0x38, 0x00, 0x00
+    .byte 0x00, 0x00, 0x40, 0x40, 0x20, 0x20, 0x10, 0x10, 0x08, 0x08, 0x04, 0x04, 0x00, 0x00, 0x00, 0x00
+    .byte 0x38, 0x38, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x38, 0x38, 0x00, 0x00
+    .byte 0x10, 0x10, 0x28, 0x28, 0x44, 0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7C, 0x7C, 0x00, 0x00
+    .byte 0x20, 0x20, 0x10, 0x10, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x38, 0x38, 0x04, 0x04, 0x3C, 0x3C, 0x44, 0x44, 0x3C, 0x3C, 0x00, 0x00
+    .byte 0x40, 0x40, 0x40, 0x40, 0x58, 0x58, 0x64, 0x64, 0x44, 0x44, 0x44, 0x44, 0x78, 0x78, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x38, 0x38, 0x40, 0x40, 0x40, 0x40, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00
+    .byte 0x04, 0x04, 0x04, 0x04, 0x34, 0x34, 0x4C, 0x4C, 0x44, 0x44, 0x44, 0x44, 0x3C, 0x3C, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x38, 0x38, 0x44, 0x44, 0x7C, 0x7C, 0x40, 0x40, 0x38, 0x38, 0x00, 0x00
+    .byte 0x18, 0x18, 0x24, 0x24, 0x20, 0x20, 0x70, 0x70, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x3C, 0x3C, 0x44, 0x44, 0x44, 0x44, 0x3C, 0x3C, 0x04, 0x04, 0x38, 0x38
+    .byte 0x40, 0x40, 0x40, 0x40, 0x58, 0x58, 0x64, 0x64, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x00, 0x00
+    .byte 0x10, 0x10, 0x00, 0x00, 0x30, 0x30, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x38, 0x38, 0x00, 0x00
+    .byte 0x08, 0x08, 0x00, 0x00, 0x18, 0x18, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x48, 0x48, 0x30, 0x30
+    .byte 0x40, 0x40, 0x40, 0x40, 0x48, 0x48, 0x50, 0x50, 0x60, 0x60, 0x50, 0x50, 0x48, 0x48, 0x00, 0x00
+    .byte 0x30, 0x30, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x38, 0x38, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x68, 0x68, 0x54, 0x54, 0x54, 0x54, 0x44, 0x44, 0x44, 0x44, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x58, 0x58, 0x64, 0x64, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x38, 0x38, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x78, 0x78, 0x44, 0x44, 0x44, 0x44, 0x78, 0x78, 0x40, 0x40, 0x40, 0x40
+    .byte 0x00, 0x00, 0x00, 0x00, 0x3C, 0x3C, 0x44, 0x44, 0x44, 0x44, 0x3C, 0x3C, 0x04, 0x04, 0x04, 0x04
+    .byte 0x00, 0x00, 0x00, 0x00, 0x58, 0x58, 0x64, 0x64, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x3C, 0x3C, 0x40, 0x40, 0x38, 0x38, 0x04, 0x04, 0x78, 0x78, 0x00, 0x00
+    .byte 0x20, 0x20, 0x20, 0x20, 0x70, 0x70, 0x20, 0x20, 0x20, 0x20, 0x24, 0x24, 0x18, 0x18, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x4C, 0x4C, 0x34, 0x34, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x28, 0x28, 0x10, 0x10, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x44, 0x44, 0x44, 0x44, 0x54, 0x54, 0x54, 0x54, 0x28, 0x28, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x44, 0x44, 0x28, 0x28, 0x10, 0x10, 0x28, 0x28, 0x44, 0x44, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x3C, 0x3C, 0x04, 0x04, 0x38, 0x38
+    .byte 0x00, 0x00, 0x00, 0x00, 0x7C, 0x7C, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x7C, 0x7C, 0x00, 0x00
+    .byte 0x08, 0x08, 0x10, 0x10, 0x10, 0x10, 0x20, 0x20, 0x10, 0x10, 0x10, 0x10, 0x08, 0x08, 0x00, 0x00
+    .byte 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00, 0x00
+    .byte 0x20, 0x20, 0x10, 0x10, 0x10, 0x10, 0x08, 0x08, 0x10, 0x10, 0x10, 0x10, 0x20, 0x20, 0x00, 0x00
+    .byte 0x00, 0x00, 0x00, 0x00, 0x20, 0x20, 0x54, 0x54, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+
+# String literals
+str_001ae5be669c948f:  # "Earthang"
+    .byte 0x45, 0x61, 0x72, 0x74, 0x68, 0x61, 0x6e, 0x67, 0x00
+str_000006531925b124:  # "ready\n"
+    .byte 0x72, 0x65, 0x61, 0x64, 0x79, 0x0a, 0x00
+
+    .att_syntax
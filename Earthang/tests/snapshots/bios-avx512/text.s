@@ -0,0 +1,964 @@
+    .intel_syntax noprefix
+    .section .text
+    .globl _start
+
+_start:
+    mov rbp, rsp
+    and rsp, -16        # 16-byte align stack
+    
+    call serial_init_64
+    call main
+    
+.halt:
+    hlt
+    jmp .halt
+
+main:
+    push rbp
+    mov rbp, rsp
+    sub rsp, 16        # Allocate 16 bytes for locals
+    # Variables span from [rbp - 8] to [rbp - 16]
+    mov QWORD PTR [rbp - 8], 0
+    mov QWORD PTR [rbp - 16], 0
+
+    # @line 2
+    # Variable declaration: name
+    # input()
+    mov edi, 256
+    call heap_alloc_64
+    mov rdi, rax
+    mov esi, 256
+    lea rdx, [serial_write_string_64]
+    call kbd_read_line_64
+    mov rdi, rax
+    call __rc_inc
+    mov rdi, QWORD PTR [rbp - 8]
+    call __rc_dec
+    mov QWORD PTR [rbp - 8], rax
+    # @line 3
+    # Variable declaration: ratio
+    # Floating point operation
+    # Float: 1.5
+    mov rax, 0x3ff8000000000000
+    push rax
+    # Float: 2.0
+    mov rax, 0x4000000000000000
+    movq xmm1, rax
+    pop rax
+    movq xmm0, rax
+    mulsd xmm0, xmm1
+    movq rax, xmm0
+    mov QWORD PTR [rbp - 16], rax
+    # @line 4
+    # f-string
+    lea rdi, [str_0000065301d843d9]
+    call print_string
+    # Variable: name at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    mov rdi, rax
+    call print_string
+    lea rdi, [str_001ae4b2c7544270]
+    call print_string
+    # Variable: ratio at [rbp - 16]
+    mov rax, QWORD PTR [rbp - 16]
+    call print_float
+    call print_newline
+    # @line 5
+    # Comparison operation
+    # Variable: name at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    push rax
+    # String: "earth"
+    lea rax, [str_000000310f59d019]
+    mov rsi, rax
+    pop rdi
+    call str_cmp
+    cmp rax, 0
+    sete al
+    movzx rax, al
+    call print_decimal
+    lea rdi, [str_000000000002b5c5]
+    call print_string
+    # Floating point comparison
+    # Float: 3.25
+    mov rax, 0x400a000000000000
+    push rax
+    # Variable: ratio at [rbp - 16]
+    mov rax, QWORD PTR [rbp - 16]
+    movq xmm1, rax
+    pop rax
+    movq xmm0, rax
+    comisd xmm0, xmm1
+    setb al
+    movzx rax, al
+    call print_decimal
+    lea rdi, [str_000000000002b5c5]
+    call print_string
+    # Number: -8
+    mov rax, -8
+    call print_decimal
+    call print_newline
+    # @end
+    xor rax, rax
+
+.main_epilogue:
+    mov rsp, rbp
+    pop rbp
+    ret
+
+print_string:
+    push rax
+    push rdi
+    push rsi
+    push rdx
+    #
+    mov rsi, rdi
+    xor rdx, rdx
+.count_loop:
+    cmp BYTE PTR [rsi + rdx], 0
+    je .count_done
+    inc rdx
+    jmp .count_loop
+.count_done:
+    #
+    mov rax, 1
+    mov rdi, 1
+    call serial_write_64
+    #
+    pop rdx
+    pop rsi
+    pop rdi
+    pop rax
+    ret
+
+print_decimal:
+    # Input: rax = integer
+    push rbp
+    mov rbp, rsp
+    sub rsp, 32
+    #
+    push rbx
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    #
+    mov QWORD PTR [rbp - 8], rax
+    #
+    lea rdi, [rsp + 31]
+    mov BYTE PTR [rdi], 0
+    #
+    mov rax, QWORD PTR [rbp - 8]
+    test rax, rax
+    jns .positive
+    neg rax
+    #
+.positive:
+    mov rbx, 10
+    #
+.convert_loop:
+    xor rdx, rdx
+    div rbx
+    add dl, '0'
+    dec rdi
+    mov BYTE PTR [rdi], dl
+    test rax, rax
+    jnz .convert_loop
+    #
+    mov rax, QWORD PTR [rbp - 8]
+    test rax, rax
+    jns .print_it
+    dec rdi
+    mov BYTE PTR [rdi], '-'
+    #
+.print_it:
+    lea rsi, [rsp + 31]
+    sub rsi, rdi
+    #
+    mov rax, 1
+    mov rdx, rsi
+    mov rsi, rdi
+    mov rdi, 1
+    call serial_write_64
+    #
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    pop rbx
+    #
+    mov rsp, rbp
+    pop rbp
+    ret
+
+str_cmp:
+    # Input: rdi, rsi = strings; output: rax = difference at first mismatch
+    push rcx
+    push rdi
+    push rsi
+.str_cmp_loop:
+    movzx eax, BYTE PTR [rdi]
+    movzx ecx, BYTE PTR [rsi]
+    cmp eax, ecx
+    jne .str_cmp_done
+    test eax, eax
+    jz .str_cmp_done
+    inc rdi
+    inc rsi
+    jmp .str_cmp_loop
+.str_cmp_done:
+    sub rax, rcx
+    pop rsi
+    pop rdi
+    pop rcx
+    ret
+
+print_float:
+    # Input: rax = IEEE double, printed with up to six decimals
+    push rbp
+    mov rbp, rsp
+    sub rsp, 704
+    push rcx
+    push rdx
+    push rdi
+    push rsi
+    push r8
+    #
+    mov rcx, 0x7ff0000000000000
+    mov rdx, rax
+    btr rdx, 63
+    cmp rdx, rcx
+    jbe .float_signed
+    mov DWORD PTR [rbp - 32], 0x4e614e    # "NaN"
+    jmp .float_word
+    #
+.float_signed:
+    btr rax, 63
+    jnc .float_positive
+    mov WORD PTR [rbp - 32], 0x2d    # "-"
+    lea rdi, [rbp - 32]
+    call print_string
+.float_positive:
+    mov rcx, 0x7ff0000000000000     # print_string clobbers rcx
+    cmp rax, rcx
+    jne .float_number
+    mov DWORD PTR [rbp - 32], 0x666e69    # "inf"
+.float_word:
+    lea rdi, [rbp - 32]
+    call print_string
+    jmp .float_done
+    #
+.float_number:
+    mov rcx, rax
+    shr rcx, 52                # biased exponent
+    cmp ecx, 1076
+    jae .float_integral
+    # Below 2^53 the integer part fits a register and taking it away leaves the exact fraction
+    movq xmm0, rax
+    cvttsd2si rax, xmm0
+    cvtsi2sd xmm1, rax
+    subsd xmm0, xmm1
+    mov rcx, 1000000
+    cvtsi2sd xmm1, rcx
+    mulsd xmm0, xmm1
+    cvtsd2si rdx, xmm0         # round to the nearest millionth
+    cmp rdx, rcx
+    jb .float_split
+    inc rax                    # the fraction rounded up to a whole one
+    xor edx, edx
+.float_split:
+    mov QWORD PTR [rbp - 8], rdx
+    call print_decimal
+    mov WORD PTR [rbp - 32], 0x2e    # "."
+    lea rdi, [rbp - 32]
+    call print_string
+    #
+    # Six fraction digits go to [rbp - 24 .. rbp - 19]
+    mov rax, QWORD PTR [rbp - 8]
+    lea rdi, [rbp - 18]
+    mov BYTE PTR [rdi], 0
+    mov rcx, 10
+.float_digit_loop:
+    xor rdx, rdx
+    div rcx
+    add dl, '0'
+    dec rdi
+    mov BYTE PTR [rdi], dl
+    lea rdx, [rbp - 24]
+    cmp rdi, rdx
+    jne .float_digit_loop
+    #
+    # Drop trailing zeros but keep at least one digit
+    lea rdi, [rbp - 19]
+.float_trim_loop:
+    cmp rdi, rdx
+    je .float_print
+    cmp BYTE PTR [rdi], '0'
+    jne .float_print
+    mov BYTE PTR [rdi], 0
+    dec rdi
+    jmp .float_trim_loop
+.float_print:
+    mov rdi, rdx
+    call print_string
+    #
+    jmp .float_done
+    #
+.float_integral:
+    # From 2^53 up a double is the whole number mantissa * 2^(exponent - 1075).
+    # Its decimal digits, least significant first from [rbp - 352], start as
+    # the mantissa's and are doubled once per power of two
+    sub ecx, 1075
+    mov rdx, 0xfffffffffffff
+    and rax, rdx
+    bts rax, 52
+    lea rdi, [rbp - 352]
+    xor esi, esi
+    mov r8, 10
+.float_integral_digits:
+    xor edx, edx
+    div r8
+    mov BYTE PTR [rdi + rsi], dl
+    inc rsi
+    test rax, rax
+    jnz .float_integral_digits
+.float_double:
+    xor edx, edx               # carry
+    xor r8d, r8d
+.float_double_digit:
+    movzx eax, BYTE PTR [rdi + r8]
+    add eax, eax
+    add eax, edx
+    xor edx, edx
+    cmp eax, 10
+    jb .float_double_store
+    sub eax, 10
+    inc edx
+.float_double_store:
+    mov BYTE PTR [rdi + r8], al
+    inc r8
+    cmp r8, rsi
+    jb .float_double_digit
+    test edx, edx
+    jz .float_doubled
+    mov BYTE PTR [rdi + rsi], 1
+    inc rsi
+.float_doubled:
+    dec ecx
+    jnz .float_double
+    # Most significant digit first into [rbp - 688], then ".0"
+    lea rdx, [rbp - 688]
+.float_integral_text:
+    dec rsi
+    movzx eax, BYTE PTR [rdi + rsi]
+    add al, '0'
+    mov BYTE PTR [rdx], al
+    inc rdx
+    test rsi, rsi
+    jnz .float_integral_text
+    mov DWORD PTR [rdx], 0x302e      # ".0"
+    lea rdi, [rbp - 688]
+    call print_string
+    #
+.float_done:
+    pop r8
+    pop rsi
+    pop rdi
+    pop rdx
+    pop rcx
+    mov rsp, rbp
+    pop rbp
+    ret
+
+print_newline:
+    push rax
+    push rdi
+    push rsi
+    push rdx
+    #
+    mov rax, 1
+    mov rdi, 1
+    lea rsi, [newline]
+    mov rdx, 1
+    call serial_write_64
+    #
+    pop rdx
+    pop rsi
+    pop rdi
+    pop rax
+    ret
+
+# ========== FRAMEBUFFER ROUTINES ==========
+fb_fill:
+    movzx eax, dil
+    imul eax, eax, 0x01010101
+    .byte 0x62, 0xF2, 0x7D, 0x48, 0x7C, 0xC0  # vpbroadcastd zmm0, eax
+    mov rdi, [rip + fb_info + 0]
+    mov ecx, [rip + fb_info + 16]
+    imul ecx, [rip + fb_info + 12]
+    shr ecx, 6        # whole 64-byte stores
+1:
+    .byte 0x62, 0xF1, 0xFD, 0x48, 0x7F, 0x07  # vmovdqa64 [rdi], zmm0
+    add rdi, 64
+    dec ecx
+    jnz 1b
+    ret
+
+fb_rect:
+    add rdx, rdi        # right edge
+    add rcx, rsi        # bottom edge
+    xor eax, eax
+    cmp rdi, rax
+    cmovl rdi, rax
+    cmp rsi, rax
+    cmovl rsi, rax
+    mov eax, [rip + fb_info + 8]
+    cmp rdx, rax
+    cmovg rdx, rax
+    mov eax, [rip + fb_info + 12]
+    cmp rcx, rax
+    cmovg rcx, rax
+    sub rdx, rdi        # clipped width
+    jle 2f
+    sub rcx, rsi        # clipped height
+    jle 2f
+    mov r9d, [rip + fb_info + 16]
+    imul rsi, r9
+    add rdi, rsi
+    add rdi, [rip + fb_info + 0]        # base + y * pitch + x
+    mov eax, r8d
+    mov r8, rcx
+1:  mov r10, rdi
+    mov rcx, rdx
+    rep stosb
+    lea rdi, [r10 + r9]
+    dec r8
+    jnz 1b
+2:  ret
+
+fb_line:
+    push rbx
+    push r12
+    push r13
+    push r14
+    mov r9, rdx
+    sub r9, rdi
+    mov r10, 1          # x step
+    jge 1f
+    neg r9
+    neg r10
+1:  mov r11, rcx
+    sub r11, rsi
+    mov r12, 1          # y step
+    jge 2f
+    neg r11
+    neg r12
+2:  neg r11             # dy = -|y1 - y0|
+    lea r13, [r9 + r11] # error
+    mov ebx, [rip + fb_info + 8]
+    mov r14d, [rip + fb_info + 12]
+3:  cmp rdi, rbx        # unsigned, so negative coordinates fail too
+    jae 4f
+    cmp rsi, r14
+    jae 4f
+    mov eax, [rip + fb_info + 16]
+    imul rax, rsi
+    add rax, rdi
+    add rax, [rip + fb_info + 0]
+    mov byte ptr [rax], r8b
+4:  cmp rdi, rdx
+    jne 5f
+    cmp rsi, rcx
+    je 7f
+5:  lea rax, [r13 + r13]
+    cmp rax, r11
+    jl 6f
+    add r13, r11
+    add rdi, r10
+6:  cmp rax, r9
+    jg 3b
+    add r13, r9
+    add rsi, r12
+    jmp 3b
+7:  pop r14
+    pop r13
+    pop r12
+    pop rbx
+    ret
+
+fb_set_palette:
+    mov r8d, edx
+    mov dx, 0x3C8
+    mov eax, edi
+    out dx, al
+    inc dx
+    mov eax, esi
+    shr al, 2
+    out dx, al
+    mov eax, r8d
+    shr al, 2
+    out dx, al
+    mov eax, ecx
+    shr al, 2
+    out dx, al
+    ret
+
+fb_present:
+    ret                 # single buffered: drawing is already visible
+
+# ========== BIOS HEAP ==========
+heap_grow_64:
+    push rdi
+    mov rax, [rip + heap_top]
+    add rdi, rax
+    cmp rdi, 0x40000000
+    ja .halt
+    mov [rip + heap_top], rdi
+    pop rdi
+    ret
+    .section .text
+heap_alloc_64:
+    # Input: rdi = size in bytes; output: rax = 16-byte aligned block, reused
+    # from the free list of its power-of-two size class when there is one
+    push rcx
+    push rdx
+    push rdi
+    lea rdi, [rdi + 15]         # header included, minus one
+    mov ecx, 5                  # 32 bytes at least
+    cmp rdi, 32
+    jb .heap_class_found
+    bsr rcx, rdi
+    inc ecx
+.heap_class_found:
+    lea rdx, [rip + heap_free_lists]
+    mov rax, QWORD PTR [rdx + rcx*8]
+    test rax, rax
+    jz .heap_carve
+    mov rdi, QWORD PTR [rax + 8]
+    mov QWORD PTR [rdx + rcx*8], rdi
+    jmp .heap_header
+.heap_carve:
+    mov edi, 1
+    shl rdi, cl
+    call heap_grow_64
+.heap_header:
+    mov DWORD PTR [rax], ecx    # size class, raw kind
+    mov DWORD PTR [rax + 4], 0x45475243     # magic
+    mov QWORD PTR [rax + 8], 0
+    add rax, 16
+    pop rdi
+    pop rdx
+    pop rcx
+    ret
+
+heap_free_64:
+    # Input: rdi = block from heap_alloc_64; puts it on its size class's free list
+    push rax
+    push rcx
+    push rdx
+    push rsi
+    lea rax, [rdi - 16]
+    movzx ecx, BYTE PTR [rax]
+    mov QWORD PTR [rax], rcx    # no magic: stale pointers are not objects any more
+    lea rdx, [rip + heap_free_lists]
+    mov rsi, QWORD PTR [rdx + rcx*8]
+    mov QWORD PTR [rax + 8], rsi
+    mov QWORD PTR [rdx + rcx*8], rax
+    pop rsi
+    pop rdx
+    pop rcx
+    pop rax
+    ret
+
+heap_realloc_64:
+    # Input: rdi = block from heap_alloc_64, rsi = size in bytes; output: rax =
+    # a block that large with the old contents. It stays put while its size
+    # class has room, otherwise the whole old block is copied and then freed
+    push rcx
+    push rsi
+    push rdi
+    movzx ecx, BYTE PTR [rdi - 16]
+    mov eax, 1
+    shl rax, cl
+    sub rax, 16                 # room the old block has
+    cmp rsi, rax
+    jbe .heap_realloc_fits
+    mov rcx, rax
+    shr rcx, 3
+    mov rdi, rsi
+    call heap_alloc_64
+    mov rsi, QWORD PTR [rsp]
+    mov rdi, rax
+    rep movsq
+    mov rdi, QWORD PTR [rsp]
+    call heap_free_64
+    pop rdi
+    pop rsi
+    pop rcx
+    ret
+.heap_realloc_fits:
+    mov rax, rdi
+    pop rdi
+    pop rsi
+    pop rcx
+    ret
+
+.rc_header:
+    # Input: rdi = value; output: rax = its header when it is a live heap
+    # block, otherwise 0 (integers, literals and freed blocks)
+    lea rax, [rdi - 16]
+    cmp rax, QWORD PTR [rip + heap_base]
+    jb .rc_not_object
+    cmp rax, QWORD PTR [rip + heap_top]
+    jae .rc_not_object
+    test al, 15
+    jnz .rc_not_object
+    cmp DWORD PTR [rax + 4], 0x45475243
+    jne .rc_not_object
+    ret
+.rc_not_object:
+    xor eax, eax
+    ret
+
+__rc_inc:
+    # Input: rdi = value; counts one more reference to it if it is an object
+    push rax
+    call .rc_header
+    test rax, rax
+    jz .rc_inc_done
+    inc QWORD PTR [rax + 8]
+.rc_inc_done:
+    pop rax
+    ret
+
+__rc_dec:
+    # Input: rdi = value; drops a reference and releases the object with the last one
+    cmp BYTE PTR [rip + rc_enabled], 0
+    je .rc_dec_disabled
+    push rax
+    call .rc_header
+    test rax, rax
+    jz .rc_dec_done
+    cmp QWORD PTR [rax + 8], 0  # never counted, still owned by whoever made it
+    je .rc_dec_done
+    dec QWORD PTR [rax + 8]
+    jnz .rc_dec_done
+    call .rc_release
+.rc_dec_done:
+    pop rax
+.rc_dec_disabled:
+    ret
+
+__rc_disown:
+    # Input: rdi = value; drops a reference without releasing the object, so a
+    # function can hand its result back uncounted
+    push rax
+    call .rc_header
+    test rax, rax
+    jz .rc_disown_done
+    cmp QWORD PTR [rax + 8], 0
+    je .rc_disown_done
+    dec QWORD PTR [rax + 8]
+.rc_disown_done:
+    pop rax
+    ret
+
+.rc_release:
+    # Input: rdi = object, rax = its header; drops what a list, dictionary or
+    # struct holds, frees its buffers and then the object itself
+    push rcx
+    push rsi
+    push rdi
+    mov rsi, rdi
+    movzx ecx, BYTE PTR [rax + 1]
+    cmp ecx, 1
+    je .rc_release_list
+    cmp ecx, 2
+    je .rc_release_dict
+    cmp ecx, 3
+    je .rc_release_struct
+    jmp .rc_release_free
+.rc_release_list:
+    mov rcx, QWORD PTR [rsi]
+.rc_release_elements:
+    test rcx, rcx
+    jz .rc_release_list_data
+    dec rcx
+    mov rdi, QWORD PTR [rsi + 16]
+    mov rdi, QWORD PTR [rdi + rcx*8]
+    call __rc_dec
+    jmp .rc_release_elements
+.rc_release_list_data:
+    mov rdi, QWORD PTR [rsi + 16]
+    call heap_free_64
+    jmp .rc_release_free
+.rc_release_dict:
+    mov rcx, QWORD PTR [rsi + 8]
+.rc_release_slots:
+    test rcx, rcx
+    jz .rc_release_tables
+    dec rcx
+    mov rdi, QWORD PTR [rsi + 16]
+    mov rdi, QWORD PTR [rdi + rcx*8]
+    test rdi, rdi
+    jz .rc_release_slots
+    call __rc_dec
+    mov rdi, QWORD PTR [rsi + 24]
+    mov rdi, QWORD PTR [rdi + rcx*8]
+    call __rc_dec
+    jmp .rc_release_slots
+.rc_release_tables:
+    mov rdi, QWORD PTR [rsi + 16]
+    call heap_free_64
+    mov rdi, QWORD PTR [rsi + 24]
+    call heap_free_64
+    jmp .rc_release_free
+.rc_release_struct:
+    movzx ecx, WORD PTR [rax + 2]
+.rc_release_fields:
+    test rcx, rcx
+    jz .rc_release_free
+    dec rcx
+    mov rdi, QWORD PTR [rsi + rcx*8]
+    call __rc_dec
+    jmp .rc_release_fields
+.rc_release_free:
+    mov rdi, rsi
+    call heap_free_64
+    pop rdi
+    pop rsi
+    pop rcx
+    ret
+
+    .section .data
+heap_free_lists:
+    .zero 512                   # one list head per size class
+    .section .text
+    .section .text
+serial_init_64:
+    # 115200 baud, 8N1, FIFOs enabled and cleared
+    push rax
+    push rdx
+    mov dx, 0x3F9
+    mov al, 0x00
+    out dx, al
+    mov dx, 0x3FB
+    mov al, 0x80
+    out dx, al
+    mov dx, 0x3F8
+    mov al, 0x01
+    out dx, al
+    mov dx, 0x3F9
+    mov al, 0x00
+    out dx, al
+    mov dx, 0x3FB
+    mov al, 0x03
+    out dx, al
+    mov dx, 0x3FA
+    mov al, 0xC7
+    out dx, al
+    pop rdx
+    pop rax
+    ret
+
+serial_write_char_64:
+    # Input: dil = byte, sent once the transmit holding register is empty
+    push rax
+    push rdx
+    mov dx, 0x3FD
+.serial_wait:
+    in al, dx
+    test al, 0x20
+    jz .serial_wait
+    mov dx, 0x3F8
+    mov eax, edi
+    out dx, al
+    pop rdx
+    pop rax
+    ret
+
+serial_write_string_64:
+    # Input: rdi = NUL-terminated string
+    push rdi
+    push rsi
+    mov rsi, rdi
+.serial_string_loop:
+    movzx edi, BYTE PTR [rsi]
+    test edi, edi
+    jz .serial_string_done
+    call serial_write_char_64
+    inc rsi
+    jmp .serial_string_loop
+.serial_string_done:
+    pop rsi
+    pop rdi
+    ret
+
+serial_write_64:
+    # Input: rsi = bytes, rdx = count
+    push rdi
+    push rsi
+    push rdx
+.serial_write_loop:
+    test rdx, rdx
+    jz .serial_write_done
+    movzx edi, BYTE PTR [rsi]
+    call serial_write_char_64
+    inc rsi
+    dec rdx
+    jmp .serial_write_loop
+.serial_write_done:
+    pop rdx
+    pop rsi
+    pop rdi
+    ret
+
+    .section .text
+kbd_read_char_64:
+    # Output: rax = ASCII of the next key pressed, waiting for one
+.kbd_poll:
+    in al, 0x64
+    test al, 1
+    jz .kbd_poll
+    in al, 0x60
+    call kbd_translate_64
+    test eax, eax
+    jz .kbd_poll
+    ret
+
+kbd_translate_64:
+    # Input: al = scancode. Output: rax = its ASCII, 0 for anything but a key press
+    push rcx
+    cmp al, 0x2A        # left shift down
+    je .kbd_shift_down
+    cmp al, 0x36        # right shift down
+    je .kbd_shift_down
+    cmp al, 0xAA        # left shift up
+    je .kbd_shift_up
+    cmp al, 0xB6        # right shift up
+    je .kbd_shift_up
+    test al, 0x80       # other releases and the 0xE0 prefix
+    jnz .kbd_no_key
+    cmp al, 0x3A
+    jae .kbd_no_key
+    movzx ecx, al
+    lea rax, [kbd_scancodes]
+    cmp BYTE PTR [kbd_shift], 0
+    je .kbd_translate
+    lea rax, [kbd_scancodes_shifted]
+.kbd_translate:
+    movzx eax, BYTE PTR [rax + rcx]
+    pop rcx
+    ret
+.kbd_shift_down:
+    mov BYTE PTR [kbd_shift], 1
+    jmp .kbd_no_key
+.kbd_shift_up:
+    mov BYTE PTR [kbd_shift], 0
+.kbd_no_key:
+    xor eax, eax
+    pop rcx
+    ret
+
+kbd_read_line_64:
+    # Input: rdi = buffer, rsi = its size, rdx = echo routine or 0
+    # Output: rax = buffer holding the line up to Enter, NUL-terminated
+    push rbx
+    push r12
+    push r13
+    push r14
+    mov rbx, rdi
+    mov r12, rsi
+    mov r13, rdx
+    xor r14, r14        # length
+.kbd_line_loop:
+    call kbd_read_char_64
+    cmp al, 10
+    je .kbd_line_done
+    cmp al, 8
+    je .kbd_line_backspace
+    cmp al, 32          # escape and tab are not stored
+    jb .kbd_line_loop
+    lea rcx, [r14 + 1]
+    cmp rcx, r12        # keep room for the terminator
+    jae .kbd_line_loop
+    mov BYTE PTR [rbx + r14], al
+    inc r14
+    mov BYTE PTR [kbd_echo_char], al
+    lea rdi, [kbd_echo_char]
+    call .kbd_echo
+    jmp .kbd_line_loop
+.kbd_line_backspace:
+    test r14, r14
+    jz .kbd_line_loop
+    dec r14
+    lea rdi, [kbd_erase]
+    call .kbd_echo
+    jmp .kbd_line_loop
+.kbd_line_done:
+    mov BYTE PTR [rbx + r14], 0
+    lea rdi, [kbd_newline]
+    call .kbd_echo
+    mov rax, rbx
+    pop r14
+    pop r13
+    pop r12
+    pop rbx
+    ret
+.kbd_echo:
+    test r13, r13
+    jz .kbd_echo_done
+    call r13
+.kbd_echo_done:
+    ret
+
+    .section .data
+kbd_shift:
+    .byte 0
+kbd_echo_char:
+    .byte 0, 0
+kbd_erase:
+    .byte 8, 32, 8, 0   # back, blank, back
+kbd_newline:
+    .byte 10, 0
+kbd_scancodes:
+    .byte 0x00, 0x1B, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x30, 0x2D, 0x3D, 0x08, 0x09
+    .byte 0x71, 0x77, 0x65, 0x72, 0x74, 0x79, 0x75, 0x69, 0x6F, 0x70, 0x5B, 0x5D, 0x0A, 0x00, 0x61, 0x73
+    .byte 0x64, 0x66, 0x67, 0x68, 0x6A, 0x6B, 0x6C, 0x3B, 0x27, 0x60, 0x00, 0x5C, 0x7A, 0x78, 0x63, 0x76
+    .byte 0x62, 0x6E, 0x6D, 0x2C, 0x2E, 0x2F, 0x00, 0x2A, 0x00, 0x20
+kbd_scancodes_shifted:
+    .byte 0x00, 0x1B, 0x21, 0x40, 0x23, 0x24, 0x25, 0x5E, 0x26, 0x2A, 0x28, 0x29, 0x5F, 0x2B, 0x08, 0x09
+    .byte 0x51, 0x57, 0x45, 0x52, 0x54, 0x59, 0x55, 0x49, 0x4F, 0x50, 0x7B, 0x7D, 0x0A, 0x00, 0x41, 0x53
+    .byte 0x44, 0x46, 0x47, 0x48, 0x4A, 0x4B, 0x4C, 0x3A, 0x22, 0x7E, 0x00, 0x7C, 0x5A, 0x58, 0x43, 0x56
+    .byte 0x42, 0x4E, 0x4D, 0x3C, 0x3E, 0x3F, 0x00, 0x2A, 0x00, 0x20
+    .section .text
+    .section .data
+newline:
+    .byte 10, 0
+
+rc_enabled:
+    .byte 1
+
+fb_info:
+    .quad 0xA0000        # base
+    .long 320        # width
+    .long 200        # height
+    .long 320        # pitch
+    .long 1        # bytes per pixel
+    .quad 0xA0000        # front
+
+heap_base:
+    .quad 0x1000000
+heap_top:
+    .quad 0x1000000
+# String literals
+str_0000065301d843d9:  # "hello "
+    .byte 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x20, 0x00
+str_001ae4b2c7544270:  # ", ratio "
+    .byte 0x2c, 0x20, 0x72, 0x61, 0x74, 0x69, 0x6f, 0x20, 0x00
+str_000000310f59d019:  # "earth"
+    .byte 0x65, 0x61, 0x72, 0x74, 0x68, 0x00
+str_000000000002b5c5:  # " "
+    .byte 0x20, 0x00
+
+    .att_syntax
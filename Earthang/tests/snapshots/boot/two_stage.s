@@ -0,0 +1,173 @@
+    .intel_syntax noprefix
+    .section .text
+    .globl _start
+    .code16
+_start:
+phase_real_mode_setup_start:
+    cli
+    xor ax, ax
+    mov ds, ax
+    mov es, ax
+    mov ss, ax
+    mov sp, 0x7C00
+    cld
+    sti
+    mov ax, 0x0013  # set video mode
+    int 0x10
+    cli
+phase_real_mode_setup_end:
+phase_cpuid_probe_start:
+    # CPUID exists when EFLAGS.ID can be toggled
+    pushfd
+    pop eax
+    mov ecx, eax
+    xor eax, 0x200000
+    push eax
+    popfd
+    pushfd
+    pop eax
+    push ecx
+    popfd
+    cmp eax, ecx
+    je no_long_mode
+    mov eax, 0x80000000
+    cpuid
+    cmp eax, 0x80000001
+    jb no_long_mode
+    mov eax, 0x80000001
+    cpuid
+    test edx, 0x20000000  # LM
+    jz no_long_mode
+phase_cpuid_probe_end:
+phase_enable_a20_start:
+    # Fast A20 through the system control port
+    in al, 0x92
+    or al, 2
+    and al, 0xFE
+    out 0x92, al
+phase_enable_a20_end:
+phase_protected_mode_start:
+    lgdt [gdtr - _start + 0x7E00]
+    mov eax, cr0
+    or eax, 1
+    mov cr0, eax
+    jmp 0x08:protected_entry - _start + 0x7E00
+    .code32
+protected_entry:
+    mov ax, 0x10
+    mov ds, ax
+    mov es, ax
+    mov fs, ax
+    mov gs, ax
+    mov ss, ax
+    mov esp, 0x7C00
+phase_protected_mode_end:
+phase_long_mode_start:
+    # Identity-map the first GiB with 2 MiB pages
+    mov edi, 0x1000
+    xor eax, eax
+    mov ecx, 0xC00
+    rep stosd
+    mov dword ptr [0x1000], 0x2003
+    mov dword ptr [0x2000], 0x3003
+    mov edi, 0x3000
+    mov eax, 0x83  # present, writable, 2 MiB
+    mov ecx, 512
+1:  mov [edi], eax
+    add eax, 0x200000
+    add edi, 8
+    loop 1b
+    mov eax, cr4
+    or eax, 0x20  # PAE
+    mov cr4, eax
+    mov eax, 0x1000
+    mov cr3, eax
+    mov ecx, 0xC0000080  # EFER
+    rdmsr
+    or eax, 0x100  # LME
+    wrmsr
+    mov eax, cr0
+    or eax, 0x80000000  # PG
+    mov cr0, eax
+    jmp 0x18:long_entry - _start + 0x7E00
+    .code64
+long_entry:
+    mov ax, 0x10
+    mov ds, ax
+    mov es, ax
+    mov fs, ax
+    mov gs, ax
+    mov ss, ax
+    mov rsp, 0x7C00
+phase_long_mode_end:
+phase_simd_enable_start:
+    # SSE is architectural in long mode: clear CR0.EM, set CR0.MP, CR4.OSFXSR and OSXMMEXCPT
+    mov rax, cr0
+    and ax, 0xFFFB
+    or ax, 2
+    mov cr0, rax
+    mov rax, cr4
+    or ax, 0x600
+    mov cr4, rax
+phase_simd_enable_end:
+phase_avx_start:
+    # XCR0 gets x87, SSE and AVX state when CPUID.1 reports XSAVE and AVX
+    mov eax, 1
+    cpuid
+    and ecx, 0x14000000
+    cmp ecx, 0x14000000
+    jne avx_done
+    mov rax, cr4
+    or eax, 0x40000  # OSXSAVE
+    mov cr4, rax
+    xor ecx, ecx
+    xgetbv
+    or eax, 7
+    xsetbv
+avx_done:
+phase_avx_end:
+phase_avx512_start:
+    # Opmask and ZMM state when CPUID.7 reports AVX-512F and AVX is already on
+    mov rax, cr4
+    test eax, 0x40000
+    jz avx512_done
+    mov eax, 7
+    xor ecx, ecx
+    cpuid
+    test ebx, 0x10000  # AVX512F
+    jz avx512_done
+    xor ecx, ecx
+    xgetbv
+    or eax, 0xE0
+    xsetbv
+avx512_done:
+phase_avx512_end:
+    # Transition done
+done:
+    hlt
+    jmp done
+    .code16
+no_long_mode:
+    hlt
+    jmp no_long_mode
+    .balign 8
+gdt:
+    .quad 0
+    .quad 0x00CF9A000000FFFF  # 0x08: 32-bit code
+    .quad 0x00CF92000000FFFF  # 0x10: data
+    .quad 0x00AF9A000000FFFF  # 0x18: 64-bit code
+gdt_end:
+gdtr:
+    .word gdt_end - gdt - 1
+    .long gdt - _start + 0x7E00
+    .balign 16
+payload:
+    .section .phase_sizes, "a"
+    .long phase_real_mode_setup_end - phase_real_mode_setup_start
+    .long phase_cpuid_probe_end - phase_cpuid_probe_start
+    .long phase_enable_a20_end - phase_enable_a20_start
+    .long phase_protected_mode_end - phase_protected_mode_start
+    .long phase_long_mode_end - phase_long_mode_start
+    .long phase_simd_enable_end - phase_simd_enable_start
+    .long phase_avx_end - phase_avx_start
+    .long phase_avx512_end - phase_avx512_start
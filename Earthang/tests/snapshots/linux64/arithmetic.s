@@ -0,0 +1,894 @@
+    .intel_syntax noprefix
+    .section .text
+    .globl _start
+
+_start:
+    mov rbp, rsp
+    and rsp, -16        # 16-byte align stack
+    
+    call main
+    
+    mov rdi, rax        # exit code
+    mov rax, 60         # syscall: exit
+    syscall
+
+main:
+    push rbp
+    mov rbp, rsp
+    sub rsp, 64        # Allocate 64 bytes for locals
+    # Variables span from [rbp - 8] to [rbp - 56]
+
+    # @line 2
+    # Import: system
+    # @line 3
+    # Variable declaration: a
+    # Number: 17
+    mov rax, 17
+    mov QWORD PTR [rbp - 8], rax
+    # @line 4
+    # Variable declaration: b
+    # Number: 5
+    mov rax, 5
+    mov QWORD PTR [rbp - 16], rax
+    # @line 5
+    # Variable declaration: on
+    # Boolean: true
+    mov rax, 1
+    mov QWORD PTR [rbp - 24], rax
+    # @line 6
+    # Assignment to a
+    # Binary operation in registers
+    mov rax, QWORD PTR [rbp - 8]
+    add rax, 1
+    mov QWORD PTR [rbp - 8], rax
+    # @line 7
+    # Augmented assignment to a
+    # Binary operation in registers
+    mov rax, QWORD PTR [rbp - 8]
+    add rax, QWORD PTR [rbp - 16]
+    mov QWORD PTR [rbp - 8], rax
+    # @line 8
+    # Augmented assignment to a
+    # Binary operation in registers
+    mov rax, QWORD PTR [rbp - 8]
+    sub rax, 2
+    mov QWORD PTR [rbp - 8], rax
+    # @line 9
+    # Augmented assignment to a
+    # Binary operation in registers
+    mov rax, QWORD PTR [rbp - 8]
+    imul rax, 3
+    mov QWORD PTR [rbp - 8], rax
+    # @line 10
+    # Binary operation in registers
+    mov rax, QWORD PTR [rbp - 8]
+    add rax, QWORD PTR [rbp - 16]
+    call print_decimal
+    lea rdi, [str_000000000002b5c5]
+    call print_string
+    # Binary operation in registers
+    mov rax, QWORD PTR [rbp - 8]
+    sub rax, QWORD PTR [rbp - 16]
+    call print_decimal
+    lea rdi, [str_000000000002b5c5]
+    call print_string
+    # Binary operation in registers
+    mov rax, QWORD PTR [rbp - 8]
+    imul rax, QWORD PTR [rbp - 16]
+    call print_decimal
+    lea rdi, [str_000000000002b5c5]
+    call print_string
+    # Binary operation
+    # Variable: a at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    push rax
+    # Variable: b at [rbp - 16]
+    mov rax, QWORD PTR [rbp - 16]
+    mov rbx, rax
+    pop rax
+    cqo
+    idiv rbx
+    call print_decimal
+    lea rdi, [str_000000000002b5c5]
+    call print_string
+    # Binary operation
+    # Variable: a at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    push rax
+    # Variable: b at [rbp - 16]
+    mov rax, QWORD PTR [rbp - 16]
+    mov rbx, rax
+    pop rax
+    cqo
+    idiv rbx
+    mov rax, rdx
+    call print_decimal
+    call print_newline
+    # @line 11
+    # Binary operation in registers
+    mov rax, QWORD PTR [rbp - 8]
+    and rax, QWORD PTR [rbp - 16]
+    call print_decimal
+    lea rdi, [str_000000000002b5c5]
+    call print_string
+    # Binary operation in registers
+    mov rax, QWORD PTR [rbp - 8]
+    or rax, QWORD PTR [rbp - 16]
+    call print_decimal
+    lea rdi, [str_000000000002b5c5]
+    call print_string
+    # Binary operation in registers
+    mov rax, QWORD PTR [rbp - 8]
+    xor rax, QWORD PTR [rbp - 16]
+    call print_decimal
+    lea rdi, [str_000000000002b5c5]
+    call print_string
+    # Variable: a at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    neg rax
+    call print_decimal
+    lea rdi, [str_000000000002b5c5]
+    call print_string
+    # Variable: b at [rbp - 16]
+    mov rax, QWORD PTR [rbp - 16]
+    call print_decimal
+    lea rdi, [str_000000000002b5c5]
+    call print_string
+    # Variable: on at [rbp - 24]
+    mov rax, QWORD PTR [rbp - 24]
+    test rax, rax
+    sete al
+    movzx rax, al
+    call print_decimal
+    call print_newline
+    # @line 12
+    # Short-circuit And
+    # Variable: on at [rbp - 24]
+    mov rax, QWORD PTR [rbp - 24]
+    test rax, rax
+    jz bool_short_0
+    # Variable: b at [rbp - 16]
+    mov rax, QWORD PTR [rbp - 16]
+    test rax, rax
+    jz bool_short_0
+    mov rax, 1
+    jmp bool_end_0
+bool_short_0:
+    mov rax, 0
+bool_end_0:
+    call print_decimal
+    lea rdi, [str_000000000002b5c5]
+    call print_string
+    # Short-circuit Or
+    # Variable: on at [rbp - 24]
+    mov rax, QWORD PTR [rbp - 24]
+    test rax, rax
+    jnz bool_short_1
+    # Variable: a at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    test rax, rax
+    jnz bool_short_1
+    mov rax, 0
+    jmp bool_end_1
+bool_short_1:
+    mov rax, 1
+bool_end_1:
+    call print_decimal
+    lea rdi, [str_000000000002b5c5]
+    call print_string
+    # String: "done"
+    lea rdi, [str_000000017c95cc2b]
+    call print_string
+    call print_newline
+    # @line 13
+    # For loop over range() into i
+    # Number: 2
+    mov rax, 2
+    mov QWORD PTR [rbp - 40], rax
+    # Number: 8
+    mov rax, 8
+    mov QWORD PTR [rbp - 48], rax
+for_start_2:
+    mov rax, QWORD PTR [rbp - 40]
+    cmp rax, QWORD PTR [rbp - 48]
+    jge for_end_2
+    mov QWORD PTR [rbp - 32], rax
+    # For body
+    # Variable: i at [rbp - 32]
+    mov rax, QWORD PTR [rbp - 32]
+    call print_decimal
+    call print_newline
+for_next_2:
+    mov rax, 3
+    add QWORD PTR [rbp - 40], rax
+    jmp for_start_2
+for_end_2:
+    # pass
+    # @line 17
+    # Number: 1
+    mov rax, 1
+    mov rdi, rax
+    call sleep_ms_64
+    # @end
+    xor rax, rax
+
+.main_epilogue:
+    mov rsp, rbp
+    pop rbp
+    ret
+
+print_string:
+    push rax
+    push rdi
+    push rsi
+    push rdx
+    #
+    mov rsi, rdi
+    xor rdx, rdx
+.count_loop:
+    cmp BYTE PTR [rsi + rdx], 0
+    je .count_done
+    inc rdx
+    jmp .count_loop
+.count_done:
+    #
+    mov rax, 1
+    mov rdi, 1
+    syscall
+    #
+    pop rdx
+    pop rsi
+    pop rdi
+    pop rax
+    ret
+
+print_decimal:
+    # Input: rax = integer
+    push rbp
+    mov rbp, rsp
+    sub rsp, 32
+    #
+    push rbx
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    #
+    mov QWORD PTR [rbp - 8], rax
+    #
+    lea rdi, [rsp + 31]
+    mov BYTE PTR [rdi], 0
+    #
+    mov rax, QWORD PTR [rbp - 8]
+    test rax, rax
+    jns .positive
+    neg rax
+    #
+.positive:
+    mov rbx, 10
+    #
+.convert_loop:
+    xor rdx, rdx
+    div rbx
+    add dl, '0'
+    dec rdi
+    mov BYTE PTR [rdi], dl
+    test rax, rax
+    jnz .convert_loop
+    #
+    mov rax, QWORD PTR [rbp - 8]
+    test rax, rax
+    jns .print_it
+    dec rdi
+    mov BYTE PTR [rdi], '-'
+    #
+.print_it:
+    lea rsi, [rsp + 31]
+    sub rsi, rdi
+    #
+    mov rax, 1
+    mov rdx, rsi
+    mov rsi, rdi
+    mov rdi, 1
+    syscall
+    #
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    pop rbx
+    #
+    mov rsp, rbp
+    pop rbp
+    ret
+
+str_cmp:
+    # Input: rdi, rsi = strings; output: rax = difference at first mismatch
+    push rcx
+    push rdi
+    push rsi
+.str_cmp_loop:
+    movzx eax, BYTE PTR [rdi]
+    movzx ecx, BYTE PTR [rsi]
+    cmp eax, ecx
+    jne .str_cmp_done
+    test eax, eax
+    jz .str_cmp_done
+    inc rdi
+    inc rsi
+    jmp .str_cmp_loop
+.str_cmp_done:
+    sub rax, rcx
+    pop rsi
+    pop rdi
+    pop rcx
+    ret
+
+print_float:
+    # Input: rax = IEEE double, printed with up to six decimals
+    push rbp
+    mov rbp, rsp
+    sub rsp, 704
+    push rcx
+    push rdx
+    push rdi
+    push rsi
+    push r8
+    #
+    mov rcx, 0x7ff0000000000000
+    mov rdx, rax
+    btr rdx, 63
+    cmp rdx, rcx
+    jbe .float_signed
+    mov DWORD PTR [rbp - 32], 0x4e614e    # "NaN"
+    jmp .float_word
+    #
+.float_signed:
+    btr rax, 63
+    jnc .float_positive
+    mov WORD PTR [rbp - 32], 0x2d    # "-"
+    lea rdi, [rbp - 32]
+    call print_string
+.float_positive:
+    mov rcx, 0x7ff0000000000000     # print_string clobbers rcx
+    cmp rax, rcx
+    jne .float_number
+    mov DWORD PTR [rbp - 32], 0x666e69    # "inf"
+.float_word:
+    lea rdi, [rbp - 32]
+    call print_string
+    jmp .float_done
+    #
+.float_number:
+    mov rcx, rax
+    shr rcx, 52                # biased exponent
+    cmp ecx, 1076
+    jae .float_integral
+    # Below 2^53 the integer part fits a register and taking it away leaves the exact fraction
+    movq xmm0, rax
+    cvttsd2si rax, xmm0
+    cvtsi2sd xmm1, rax
+    subsd xmm0, xmm1
+    mov rcx, 1000000
+    cvtsi2sd xmm1, rcx
+    mulsd xmm0, xmm1
+    cvtsd2si rdx, xmm0         # round to the nearest millionth
+    cmp rdx, rcx
+    jb .float_split
+    inc rax                    # the fraction rounded up to a whole one
+    xor edx, edx
+.float_split:
+    mov QWORD PTR [rbp - 8], rdx
+    call print_decimal
+    mov WORD PTR [rbp - 32], 0x2e    # "."
+    lea rdi, [rbp - 32]
+    call print_string
+    #
+    # Six fraction digits go to [rbp - 24 .. rbp - 19]
+    mov rax, QWORD PTR [rbp - 8]
+    lea rdi, [rbp - 18]
+    mov BYTE PTR [rdi], 0
+    mov rcx, 10
+.float_digit_loop:
+    xor rdx, rdx
+    div rcx
+    add dl, '0'
+    dec rdi
+    mov BYTE PTR [rdi], dl
+    lea rdx, [rbp - 24]
+    cmp rdi, rdx
+    jne .float_digit_loop
+    #
+    # Drop trailing zeros but keep at least one digit
+    lea rdi, [rbp - 19]
+.float_trim_loop:
+    cmp rdi, rdx
+    je .float_print
+    cmp BYTE PTR [rdi], '0'
+    jne .float_print
+    mov BYTE PTR [rdi], 0
+    dec rdi
+    jmp .float_trim_loop
+.float_print:
+    mov rdi, rdx
+    call print_string
+    #
+    jmp .float_done
+    #
+.float_integral:
+    # From 2^53 up a double is the whole number mantissa * 2^(exponent - 1075).
+    # Its decimal digits, least significant first from [rbp - 352], start as
+    # the mantissa's and are doubled once per power of two
+    sub ecx, 1075
+    mov rdx, 0xfffffffffffff
+    and rax, rdx
+    bts rax, 52
+    lea rdi, [rbp - 352]
+    xor esi, esi
+    mov r8, 10
+.float_integral_digits:
+    xor edx, edx
+    div r8
+    mov BYTE PTR [rdi + rsi], dl
+    inc rsi
+    test rax, rax
+    jnz .float_integral_digits
+.float_double:
+    xor edx, edx               # carry
+    xor r8d, r8d
+.float_double_digit:
+    movzx eax, BYTE PTR [rdi + r8]
+    add eax, eax
+    add eax, edx
+    xor edx, edx
+    cmp eax, 10
+    jb .float_double_store
+    sub eax, 10
+    inc edx
+.float_double_store:
+    mov BYTE PTR [rdi + r8], al
+    inc r8
+    cmp r8, rsi
+    jb .float_double_digit
+    test edx, edx
+    jz .float_doubled
+    mov BYTE PTR [rdi + rsi], 1
+    inc rsi
+.float_doubled:
+    dec ecx
+    jnz .float_double
+    # Most significant digit first into [rbp - 688], then ".0"
+    lea rdx, [rbp - 688]
+.float_integral_text:
+    dec rsi
+    movzx eax, BYTE PTR [rdi + rsi]
+    add al, '0'
+    mov BYTE PTR [rdx], al
+    inc rdx
+    test rsi, rsi
+    jnz .float_integral_text
+    mov DWORD PTR [rdx], 0x302e      # ".0"
+    lea rdi, [rbp - 688]
+    call print_string
+    #
+.float_done:
+    pop r8
+    pop rsi
+    pop rdi
+    pop rdx
+    pop rcx
+    mov rsp, rbp
+    pop rbp
+    ret
+
+print_newline:
+    push rax
+    push rdi
+    push rsi
+    push rdx
+    #
+    mov rax, 1
+    mov rdi, 1
+    lea rsi, [newline]
+    mov rdx, 1
+    syscall
+    #
+    pop rdx
+    pop rsi
+    pop rdi
+    pop rax
+    ret
+    .section .data
+newline:
+    .byte 10, 0
+
+rc_enabled:
+    .byte 0
+
+# String literals
+str_000000000002b5c5:  # " "
+    .byte 0x20, 0x00
+str_000000017c95cc2b:  # "done"
+    .byte 0x64, 0x6f, 0x6e, 0x65, 0x00
+
+    .att_syntax
+
+.intel_syntax noprefix
+    .section .text
+heap_grow_64:
+    # Input: rdi = size in bytes; output: rax = 16-byte aligned memory from the
+    # program break, for heap_alloc_64 to carve blocks from
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    push r11
+    mov rax, QWORD PTR [heap_top]
+    test rax, rax
+    jnz .heap_have_top
+    mov rax, 12                 # syscall: brk(0) reports the current break
+    xor rdi, rdi
+    syscall
+    add rax, 15
+    and rax, -16
+    mov QWORD PTR [heap_base], rax
+    mov QWORD PTR [heap_top], rax
+.heap_have_top:
+    mov rdx, QWORD PTR [rsp + 8]
+    add rdx, 15
+    and rdx, -16
+    lea rdi, [rax + rdx]
+    mov rsi, rax
+    mov rax, 12                 # syscall: brk
+    syscall
+    cmp rax, rdi
+    jb .heap_exhausted
+    mov QWORD PTR [heap_top], rdi
+    mov rax, rsi
+    pop r11
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    ret
+.heap_exhausted:
+    lea rdi, [heap_error_message]
+    jmp runtime_error_64
+
+io_read_line_64:
+    # Output: rax = next line of stdin without its newline, NUL-terminated in a
+    # fresh 256-byte block; longer lines are cut short and the rest skipped
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    push r11
+    push rbx
+    push r12
+    mov rdi, 256
+    call heap_alloc_64
+    mov rbx, rax
+    xor r12, r12
+.io_read_loop:
+    xor eax, eax                # syscall: read one byte
+    xor edi, edi
+    lea rsi, [rbx + r12]
+    mov rdx, 1
+    syscall
+    cmp rax, 1
+    jne .io_read_done           # end of input
+    cmp BYTE PTR [rbx + r12], 10
+    je .io_read_done
+    cmp r12, 255
+    je .io_read_loop
+    inc r12
+    jmp .io_read_loop
+.io_read_done:
+    mov BYTE PTR [rbx + r12], 0
+    mov rax, rbx
+    pop r12
+    pop rbx
+    pop r11
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    ret
+
+sleep_ms_64:
+    # Input: rdi = milliseconds; output: rax = 0, or -errno when nanosleep fails.
+    # The request struct timespec { tv_sec, tv_nsec } is built on the stack, with
+    # the remainder after it so a signal only resumes the rest of the sleep
+    xor eax, eax
+    test rdi, rdi
+    jle .sleep_done
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    push r11
+    mov rax, rdi
+    xor edx, edx
+    mov ecx, 1000
+    div rcx
+    imul rdx, rdx, 1000000
+    sub rsp, 32
+    mov QWORD PTR [rsp], rax        # tv_sec
+    mov QWORD PTR [rsp + 8], rdx    # tv_nsec
+.sleep_again:
+    mov rdi, rsp
+    lea rsi, [rsp + 16]             # time left when interrupted
+    mov rax, 35                     # syscall: nanosleep
+    syscall
+    cmp rax, -4                     # EINTR
+    jne .sleep_slept
+    movdqu xmm0, XMMWORD PTR [rsp + 16]
+    movdqu XMMWORD PTR [rsp], xmm0
+    jmp .sleep_again
+.sleep_slept:
+    add rsp, 32
+    pop r11
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+.sleep_done:
+    ret
+
+runtime_error_64:
+    # Input: rdi = message; reports it on stderr and exits with status 1
+    push rdi
+    mov rax, 1
+    mov rdi, 2
+    lea rsi, [runtime_error_prefix]
+    mov rdx, 15
+    syscall
+    pop rsi
+    xor rdx, rdx
+.runtime_error_length:
+    cmp BYTE PTR [rsi + rdx], 0
+    je .runtime_error_write
+    inc rdx
+    jmp .runtime_error_length
+.runtime_error_write:
+    mov rax, 1
+    mov rdi, 2
+    syscall
+    mov rax, 1
+    mov rdi, 2
+    lea rsi, [runtime_error_newline]
+    mov rdx, 1
+    syscall
+    mov rax, 60                 # syscall: exit
+    mov rdi, 1
+    syscall
+
+    .section .data
+heap_base:
+    .quad 0
+heap_top:
+    .quad 0
+runtime_error_prefix:
+    .ascii "Runtime error: "
+runtime_error_newline:
+    .byte 10
+heap_error_message:
+    .asciz "out of memory"
+    .section .text
+heap_alloc_64:
+    # Input: rdi = size in bytes; output: rax = 16-byte aligned block, reused
+    # from the free list of its power-of-two size class when there is one
+    push rcx
+    push rdx
+    push rdi
+    lea rdi, [rdi + 15]         # header included, minus one
+    mov ecx, 5                  # 32 bytes at least
+    cmp rdi, 32
+    jb .heap_class_found
+    bsr rcx, rdi
+    inc ecx
+.heap_class_found:
+    lea rdx, [rip + heap_free_lists]
+    mov rax, QWORD PTR [rdx + rcx*8]
+    test rax, rax
+    jz .heap_carve
+    mov rdi, QWORD PTR [rax + 8]
+    mov QWORD PTR [rdx + rcx*8], rdi
+    jmp .heap_header
+.heap_carve:
+    mov edi, 1
+    shl rdi, cl
+    call heap_grow_64
+.heap_header:
+    mov DWORD PTR [rax], ecx    # size class, raw kind
+    mov DWORD PTR [rax + 4], 0x45475243     # magic
+    mov QWORD PTR [rax + 8], 0
+    add rax, 16
+    pop rdi
+    pop rdx
+    pop rcx
+    ret
+
+heap_free_64:
+    # Input: rdi = block from heap_alloc_64; puts it on its size class's free list
+    push rax
+    push rcx
+    push rdx
+    push rsi
+    lea rax, [rdi - 16]
+    movzx ecx, BYTE PTR [rax]
+    mov QWORD PTR [rax], rcx    # no magic: stale pointers are not objects any more
+    lea rdx, [rip + heap_free_lists]
+    mov rsi, QWORD PTR [rdx + rcx*8]
+    mov QWORD PTR [rax + 8], rsi
+    mov QWORD PTR [rdx + rcx*8], rax
+    pop rsi
+    pop rdx
+    pop rcx
+    pop rax
+    ret
+
+heap_realloc_64:
+    # Input: rdi = block from heap_alloc_64, rsi = size in bytes; output: rax =
+    # a block that large with the old contents. It stays put while its size
+    # class has room, otherwise the whole old block is copied and then freed
+    push rcx
+    push rsi
+    push rdi
+    movzx ecx, BYTE PTR [rdi - 16]
+    mov eax, 1
+    shl rax, cl
+    sub rax, 16                 # room the old block has
+    cmp rsi, rax
+    jbe .heap_realloc_fits
+    mov rcx, rax
+    shr rcx, 3
+    mov rdi, rsi
+    call heap_alloc_64
+    mov rsi, QWORD PTR [rsp]
+    mov rdi, rax
+    rep movsq
+    mov rdi, QWORD PTR [rsp]
+    call heap_free_64
+    pop rdi
+    pop rsi
+    pop rcx
+    ret
+.heap_realloc_fits:
+    mov rax, rdi
+    pop rdi
+    pop rsi
+    pop rcx
+    ret
+
+.rc_header:
+    # Input: rdi = value; output: rax = its header when it is a live heap
+    # block, otherwise 0 (integers, literals and freed blocks)
+    lea rax, [rdi - 16]
+    cmp rax, QWORD PTR [rip + heap_base]
+    jb .rc_not_object
+    cmp rax, QWORD PTR [rip + heap_top]
+    jae .rc_not_object
+    test al, 15
+    jnz .rc_not_object
+    cmp DWORD PTR [rax + 4], 0x45475243
+    jne .rc_not_object
+    ret
+.rc_not_object:
+    xor eax, eax
+    ret
+
+__rc_inc:
+    # Input: rdi = value; counts one more reference to it if it is an object
+    push rax
+    call .rc_header
+    test rax, rax
+    jz .rc_inc_done
+    inc QWORD PTR [rax + 8]
+.rc_inc_done:
+    pop rax
+    ret
+
+__rc_dec:
+    # Input: rdi = value; drops a reference and releases the object with the last one
+    cmp BYTE PTR [rip + rc_enabled], 0
+    je .rc_dec_disabled
+    push rax
+    call .rc_header
+    test rax, rax
+    jz .rc_dec_done
+    cmp QWORD PTR [rax + 8], 0  # never counted, still owned by whoever made it
+    je .rc_dec_done
+    dec QWORD PTR [rax + 8]
+    jnz .rc_dec_done
+    call .rc_release
+.rc_dec_done:
+    pop rax
+.rc_dec_disabled:
+    ret
+
+__rc_disown:
+    # Input: rdi = value; drops a reference without releasing the object, so a
+    # function can hand its result back uncounted
+    push rax
+    call .rc_header
+    test rax, rax
+    jz .rc_disown_done
+    cmp QWORD PTR [rax + 8], 0
+    je .rc_disown_done
+    dec QWORD PTR [rax + 8]
+.rc_disown_done:
+    pop rax
+    ret
+
+.rc_release:
+    # Input: rdi = object, rax = its header; drops what a list, dictionary or
+    # struct holds, frees its buffers and then the object itself
+    push rcx
+    push rsi
+    push rdi
+    mov rsi, rdi
+    movzx ecx, BYTE PTR [rax + 1]
+    cmp ecx, 1
+    je .rc_release_list
+    cmp ecx, 2
+    je .rc_release_dict
+    cmp ecx, 3
+    je .rc_release_struct
+    jmp .rc_release_free
+.rc_release_list:
+    mov rcx, QWORD PTR [rsi]
+.rc_release_elements:
+    test rcx, rcx
+    jz .rc_release_list_data
+    dec rcx
+    mov rdi, QWORD PTR [rsi + 16]
+    mov rdi, QWORD PTR [rdi + rcx*8]
+    call __rc_dec
+    jmp .rc_release_elements
+.rc_release_list_data:
+    mov rdi, QWORD PTR [rsi + 16]
+    call heap_free_64
+    jmp .rc_release_free
+.rc_release_dict:
+    mov rcx, QWORD PTR [rsi + 8]
+.rc_release_slots:
+    test rcx, rcx
+    jz .rc_release_tables
+    dec rcx
+    mov rdi, QWORD PTR [rsi + 16]
+    mov rdi, QWORD PTR [rdi + rcx*8]
+    test rdi, rdi
+    jz .rc_release_slots
+    call __rc_dec
+    mov rdi, QWORD PTR [rsi + 24]
+    mov rdi, QWORD PTR [rdi + rcx*8]
+    call __rc_dec
+    jmp .rc_release_slots
+.rc_release_tables:
+    mov rdi, QWORD PTR [rsi + 16]
+    call heap_free_64
+    mov rdi, QWORD PTR [rsi + 24]
+    call heap_free_64
+    jmp .rc_release_free
+.rc_release_struct:
+    movzx ecx, WORD PTR [rax + 2]
+.rc_release_fields:
+    test rcx, rcx
+    jz .rc_release_free
+    dec rcx
+    mov rdi, QWORD PTR [rsi + rcx*8]
+    call __rc_dec
+    jmp .rc_release_fields
+.rc_release_free:
+    mov rdi, rsi
+    call heap_free_64
+    pop rdi
+    pop rsi
+    pop rcx
+    ret
+
+    .section .data
+heap_free_lists:
+    .zero 512                   # one list head per size class
+.att_syntax
@@ -0,0 +1,1608 @@
+    .intel_syntax noprefix
+    .section .text
+    .globl _start
+
+_start:
+    mov rbp, rsp
+    and rsp, -16        # 16-byte align stack
+    
+    call main
+    
+    mov rdi, rax        # exit code
+    mov rax, 60         # syscall: exit
+    syscall
+
+main:
+    push rbp
+    mov rbp, rsp
+    sub rsp, 16        # Allocate 16 bytes for locals
+    # Variables span from [rbp - 8] to [rbp - 16]
+    mov QWORD PTR [rbp - 8], 0
+    mov QWORD PTR [rbp - 16], 0
+
+    # @line 2
+    # Variable declaration: xs
+    # List literal with 3 elements
+    mov rdi, 3
+    call list_create_64
+    push rax
+    # Number: 1
+    mov rax, 1
+    mov rsi, rax
+    mov rdi, QWORD PTR [rsp]
+    call list_append_64
+    # Number: 2
+    mov rax, 2
+    mov rsi, rax
+    mov rdi, QWORD PTR [rsp]
+    call list_append_64
+    # Number: 3
+    mov rax, 3
+    mov rsi, rax
+    mov rdi, QWORD PTR [rsp]
+    call list_append_64
+    pop rax
+    mov rdi, rax
+    call __rc_inc
+    mov rdi, QWORD PTR [rbp - 8]
+    call __rc_dec
+    mov QWORD PTR [rbp - 8], rax
+    # @line 3
+    # Number: 4
+    mov rax, 4
+    push rax
+    # Variable: xs at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    mov rdi, rax
+    pop rsi
+    call list_append_64
+    # @line 4
+    # Subscript assignment
+    # Number: 10
+    mov rax, 10
+    push rax
+    # Number: 0
+    mov rax, 0
+    push rax
+    # Variable: xs at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    mov rdi, rax
+    pop rsi
+    pop rdx
+    call list_set_64
+    # @line 5
+    # Subscript
+    # Variable: xs at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    push rax
+    # Number: 0
+    mov rax, 0
+    mov rsi, rax
+    pop rdi
+    call list_get_64
+    call print_decimal
+    lea rdi, [str_000000000002b5c5]
+    call print_string
+    # Subscript
+    # Variable: xs at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    push rax
+    # Number: 3
+    mov rax, 3
+    mov rsi, rax
+    pop rdi
+    call list_get_64
+    call print_decimal
+    lea rdi, [str_000000000002b5c5]
+    call print_string
+    # Variable: xs at [rbp - 8]
+    mov rax, QWORD PTR [rbp - 8]
+    mov rdi, rax
+    call list_len_64
+    call print_decimal
+    call print_newline
+    # @line 6
+    # Variable declaration: ages
+    # Dict literal with 2 entries
+    call dict_create_64
+    push rax
+    # Number: 36
+    mov rax, 36
+    push rax
+    # String: "ada"
+    lea rax, [str_000000000b885ccb]
+    mov rsi, rax
+    pop rdx
+    mov rdi, QWORD PTR [rsp]
+    call dict_set_64
+    # Number: 41
+    mov rax, 41
+    push rax
+    # String: "alan"
+    lea rax, [str_000000017c9418a1]
+    mov rsi, rax
+    pop rdx
+    mov rdi, QWORD PTR [rsp]
+    call dict_set_64
+    pop rax
+    mov rdi, rax
+    call __rc_inc
+    mov rdi, QWORD PTR [rbp - 16]
+    call __rc_dec
+    mov QWORD PTR [rbp - 16], rax
+    # @line 7
+    # Subscript assignment
+    # Number: 37
+    mov rax, 37
+    push rax
+    # String: "ada"
+    lea rax, [str_000000000b885ccb]
+    push rax
+    # Variable: ages at [rbp - 16]
+    mov rax, QWORD PTR [rbp - 16]
+    mov rdi, rax
+    pop rsi
+    pop rdx
+    call dict_set_64
+    # @line 8
+    # Subscript
+    # Variable: ages at [rbp - 16]
+    mov rax, QWORD PTR [rbp - 16]
+    push rax
+    # String: "alan"
+    lea rax, [str_000000017c9418a1]
+    mov rsi, rax
+    pop rdi
+    call dict_get_64
+    test rdx, rdx
+    jz dict_key_error_64
+    call print_decimal
+    lea rdi, [str_000000000002b5c5]
+    call print_string
+    # Dictionary membership test
+    # Variable: ages at [rbp - 16]
+    mov rax, QWORD PTR [rbp - 16]
+    push rax
+    # String: "ada"
+    lea rax, [str_000000000b885ccb]
+    mov rsi, rax
+    pop rdi
+    call dict_find_index_64
+    cmp rax, -1
+    setne al
+    movzx rax, al
+    call print_decimal
+    lea rdi, [str_000000000002b5c5]
+    call print_string
+    # Dictionary membership test
+    # Variable: ages at [rbp - 16]
+    mov rax, QWORD PTR [rbp - 16]
+    push rax
+    # String: "bob"
+    lea rax, [str_000000000b886278]
+    mov rsi, rax
+    pop rdi
+    call dict_find_index_64
+    cmp rax, -1
+    sete al
+    movzx rax, al
+    call print_decimal
+    call print_newline
+    # @end
+    xor rax, rax
+
+.main_epilogue:
+    mov rsp, rbp
+    pop rbp
+    ret
+
+print_string:
+    push rax
+    push rdi
+    push rsi
+    push rdx
+    #
+    mov rsi, rdi
+    xor rdx, rdx
+.count_loop:
+    cmp BYTE PTR [rsi + rdx], 0
+    je .count_done
+    inc rdx
+    jmp .count_loop
+.count_done:
+    #
+    mov rax, 1
+    mov rdi, 1
+    syscall
+    #
+    pop rdx
+    pop rsi
+    pop rdi
+    pop rax
+    ret
+
+print_decimal:
+    # Input: rax = integer
+    push rbp
+    mov rbp, rsp
+    sub rsp, 32
+    #
+    push rbx
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    #
+    mov QWORD PTR [rbp - 8], rax
+    #
+    lea rdi, [rsp + 31]
+    mov BYTE PTR [rdi], 0
+    #
+    mov rax, QWORD PTR [rbp - 8]
+    test rax, rax
+    jns .positive
+    neg rax
+    #
+.positive:
+    mov rbx, 10
+    #
+.convert_loop:
+    xor rdx, rdx
+    div rbx
+    add dl, '0'
+    dec rdi
+    mov BYTE PTR [rdi], dl
+    test rax, rax
+    jnz .convert_loop
+    #
+    mov rax, QWORD PTR [rbp - 8]
+    test rax, rax
+    jns .print_it
+    dec rdi
+    mov BYTE PTR [rdi], '-'
+    #
+.print_it:
+    lea rsi, [rsp + 31]
+    sub rsi, rdi
+    #
+    mov rax, 1
+    mov rdx, rsi
+    mov rsi, rdi
+    mov rdi, 1
+    syscall
+    #
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    pop rbx
+    #
+    mov rsp, rbp
+    pop rbp
+    ret
+
+str_cmp:
+    # Input: rdi, rsi = strings; output: rax = difference at first mismatch
+    push rcx
+    push rdi
+    push rsi
+.str_cmp_loop:
+    movzx eax, BYTE PTR [rdi]
+    movzx ecx, BYTE PTR [rsi]
+    cmp eax, ecx
+    jne .str_cmp_done
+    test eax, eax
+    jz .str_cmp_done
+    inc rdi
+    inc rsi
+    jmp .str_cmp_loop
+.str_cmp_done:
+    sub rax, rcx
+    pop rsi
+    pop rdi
+    pop rcx
+    ret
+
+print_float:
+    # Input: rax = IEEE double, printed with up to six decimals
+    push rbp
+    mov rbp, rsp
+    sub rsp, 704
+    push rcx
+    push rdx
+    push rdi
+    push rsi
+    push r8
+    #
+    mov rcx, 0x7ff0000000000000
+    mov rdx, rax
+    btr rdx, 63
+    cmp rdx, rcx
+    jbe .float_signed
+    mov DWORD PTR [rbp - 32], 0x4e614e    # "NaN"
+    jmp .float_word
+    #
+.float_signed:
+    btr rax, 63
+    jnc .float_positive
+    mov WORD PTR [rbp - 32], 0x2d    # "-"
+    lea rdi, [rbp - 32]
+    call print_string
+.float_positive:
+    mov rcx, 0x7ff0000000000000     # print_string clobbers rcx
+    cmp rax, rcx
+    jne .float_number
+    mov DWORD PTR [rbp - 32], 0x666e69    # "inf"
+.float_word:
+    lea rdi, [rbp - 32]
+    call print_string
+    jmp .float_done
+    #
+.float_number:
+    mov rcx, rax
+    shr rcx, 52                # biased exponent
+    cmp ecx, 1076
+    jae .float_integral
+    # Below 2^53 the integer part fits a register and taking it away leaves the exact fraction
+    movq xmm0, rax
+    cvttsd2si rax, xmm0
+    cvtsi2sd xmm1, rax
+    subsd xmm0, xmm1
+    mov rcx, 1000000
+    cvtsi2sd xmm1, rcx
+    mulsd xmm0, xmm1
+    cvtsd2si rdx, xmm0         # round to the nearest millionth
+    cmp rdx, rcx
+    jb .float_split
+    inc rax                    # the fraction rounded up to a whole one
+    xor edx, edx
+.float_split:
+    mov QWORD PTR [rbp - 8], rdx
+    call print_decimal
+    mov WORD PTR [rbp - 32], 0x2e    # "."
+    lea rdi, [rbp - 32]
+    call print_string
+    #
+    # Six fraction digits go to [rbp - 24 .. rbp - 19]
+    mov rax, QWORD PTR [rbp - 8]
+    lea rdi, [rbp - 18]
+    mov BYTE PTR [rdi], 0
+    mov rcx, 10
+.float_digit_loop:
+    xor rdx, rdx
+    div rcx
+    add dl, '0'
+    dec rdi
+    mov BYTE PTR [rdi], dl
+    lea rdx, [rbp - 24]
+    cmp rdi, rdx
+    jne .float_digit_loop
+    #
+    # Drop trailing zeros but keep at least one digit
+    lea rdi, [rbp - 19]
+.float_trim_loop:
+    cmp rdi, rdx
+    je .float_print
+    cmp BYTE PTR [rdi], '0'
+    jne .float_print
+    mov BYTE PTR [rdi], 0
+    dec rdi
+    jmp .float_trim_loop
+.float_print:
+    mov rdi, rdx
+    call print_string
+    #
+    jmp .float_done
+    #
+.float_integral:
+    # From 2^53 up a double is the whole number mantissa * 2^(exponent - 1075).
+    # Its decimal digits, least significant first from [rbp - 352], start as
+    # the mantissa's and are doubled once per power of two
+    sub ecx, 1075
+    mov rdx, 0xfffffffffffff
+    and rax, rdx
+    bts rax, 52
+    lea rdi, [rbp - 352]
+    xor esi, esi
+    mov r8, 10
+.float_integral_digits:
+    xor edx, edx
+    div r8
+    mov BYTE PTR [rdi + rsi], dl
+    inc rsi
+    test rax, rax
+    jnz .float_integral_digits
+.float_double:
+    xor edx, edx               # carry
+    xor r8d, r8d
+.float_double_digit:
+    movzx eax, BYTE PTR [rdi + r8]
+    add eax, eax
+    add eax, edx
+    xor edx, edx
+    cmp eax, 10
+    jb .float_double_store
+    sub eax, 10
+    inc edx
+.float_double_store:
+    mov BYTE PTR [rdi + r8], al
+    inc r8
+    cmp r8, rsi
+    jb .float_double_digit
+    test edx, edx
+    jz .float_doubled
+    mov BYTE PTR [rdi + rsi], 1
+    inc rsi
+.float_doubled:
+    dec ecx
+    jnz .float_double
+    # Most significant digit first into [rbp - 688], then ".0"
+    lea rdx, [rbp - 688]
+.float_integral_text:
+    dec rsi
+    movzx eax, BYTE PTR [rdi + rsi]
+    add al, '0'
+    mov BYTE PTR [rdx], al
+    inc rdx
+    test rsi, rsi
+    jnz .float_integral_text
+    mov DWORD PTR [rdx], 0x302e      # ".0"
+    lea rdi, [rbp - 688]
+    call print_string
+    #
+.float_done:
+    pop r8
+    pop rsi
+    pop rdi
+    pop rdx
+    pop rcx
+    mov rsp, rbp
+    pop rbp
+    ret
+
+print_newline:
+    push rax
+    push rdi
+    push rsi
+    push rdx
+    #
+    mov rax, 1
+    mov rdi, 1
+    lea rsi, [newline]
+    mov rdx, 1
+    syscall
+    #
+    pop rdx
+    pop rsi
+    pop rdi
+    pop rax
+    ret
+    .section .data
+newline:
+    .byte 10, 0
+
+rc_enabled:
+    .byte 1
+
+# String literals
+str_000000000002b5c5:  # " "
+    .byte 0x20, 0x00
+str_000000000b885ccb:  # "ada"
+    .byte 0x61, 0x64, 0x61, 0x00
+str_000000017c9418a1:  # "alan"
+    .byte 0x61, 0x6c, 0x61, 0x6e, 0x00
+str_000000000b886278:  # "bob"
+    .byte 0x62, 0x6f, 0x62, 0x00
+
+    .att_syntax
+
+.intel_syntax noprefix
+    .section .text
+dict_create_64:
+    # Output: rax = empty dictionary with 16 slots
+    push rdi
+    mov rdi, 32
+    call heap_alloc_64
+    mov BYTE PTR [rax - 15], 2  # kind: dictionary
+    mov QWORD PTR [rax], 0
+    mov QWORD PTR [rax + 8], 16
+    mov rdi, rax
+    call dict_alloc_slots_64
+    mov rax, rdi
+    pop rdi
+    ret
+
+dict_alloc_slots_64:
+    # Input: rdi = dictionary; gives it empty key and value arrays of its capacity
+    push rax
+    push rcx
+    push rdi
+    push rsi
+    mov rsi, rdi
+    mov rdi, QWORD PTR [rsi + 8]
+    shl rdi, 3
+    call heap_alloc_64
+    mov QWORD PTR [rsi + 16], rax
+    call heap_alloc_64
+    mov QWORD PTR [rsi + 24], rax
+    mov rdi, QWORD PTR [rsi + 16]
+    mov rcx, QWORD PTR [rsi + 8]
+    xor eax, eax
+    rep stosq
+    pop rsi
+    pop rdi
+    pop rcx
+    pop rax
+    ret
+
+dict_find_slot_64:
+    # Input: rdi = dictionary, rsi = key
+    # Output: rax = slot holding the key, or the empty slot it would go in; rdx = 1 if found
+    push rcx
+    push rdi
+    push r8
+    mov r8, rdi
+    mov rdi, rsi
+    call str_hash_64
+    mov rcx, QWORD PTR [r8 + 8]
+    dec rcx
+    and rax, rcx
+.dict_probe:
+    mov rdx, QWORD PTR [r8 + 16]
+    mov rdi, QWORD PTR [rdx + rax*8]
+    test rdi, rdi
+    jz .dict_probe_empty
+    push rax
+    call str_equal_64
+    mov rdx, rax
+    pop rax
+    test rdx, rdx
+    jnz .dict_probe_found
+    inc rax
+    and rax, rcx
+    jmp .dict_probe
+.dict_probe_empty:
+    xor edx, edx
+    jmp .dict_probe_done
+.dict_probe_found:
+    mov edx, 1
+.dict_probe_done:
+    pop r8
+    pop rdi
+    pop rcx
+    ret
+
+dict_find_index_64:
+    # Input: rdi = dictionary, rsi = key; output: rax = slot index, or -1 when absent
+    push rdx
+    call dict_find_slot_64
+    test rdx, rdx
+    jnz .dict_find_done
+    mov rax, -1
+.dict_find_done:
+    pop rdx
+    ret
+
+dict_get_64:
+    # Input: rdi = dictionary, rsi = key
+    # Output: rax = value; rdx = 0 is the missing-key sentinel
+    call dict_find_slot_64
+    test rdx, rdx
+    jz .dict_get_missing
+    push rcx
+    mov rcx, QWORD PTR [rdi + 24]
+    mov rax, QWORD PTR [rcx + rax*8]
+    pop rcx
+    ret
+.dict_get_missing:
+    xor eax, eax
+    ret
+
+dict_set_64:
+    # Input: rdi = dictionary, rsi = key, rdx = value; output: rax = value
+    push rcx
+    push rdx
+    call dict_find_slot_64
+    test rdx, rdx
+    jnz .dict_set_store
+    mov rcx, QWORD PTR [rdi]
+    inc rcx
+    shl rcx, 2
+    mov rdx, QWORD PTR [rdi + 8]
+    lea rdx, [rdx + rdx*2]
+    cmp rcx, rdx
+    jbe .dict_set_insert
+    call dict_resize_64
+    call dict_find_slot_64
+.dict_set_insert:
+    inc QWORD PTR [rdi]
+    mov rcx, QWORD PTR [rdi + 16]
+    mov QWORD PTR [rcx + rax*8], rsi
+    mov rcx, QWORD PTR [rdi + 24]
+    mov QWORD PTR [rcx + rax*8], 0  # nothing to drop in a fresh slot
+    push rdi
+    mov rdi, rsi
+    call __rc_inc
+    pop rdi
+.dict_set_store:
+    mov rcx, QWORD PTR [rdi + 24]
+    lea rcx, [rcx + rax*8]
+    pop rdx
+    push rdi
+    mov rdi, rdx
+    call __rc_inc
+    mov rdi, QWORD PTR [rcx]
+    mov QWORD PTR [rcx], rdx
+    call __rc_dec               # the value it replaces
+    pop rdi
+    mov rax, rdx
+    pop rcx
+    ret
+
+dict_resize_64:
+    # Input: rdi = dictionary; doubles its capacity and reinserts every entry
+    push rax
+    push rcx
+    push rdx
+    push rsi
+    push r8
+    push r9
+    push r10
+    mov r8, QWORD PTR [rdi + 16]
+    mov r9, QWORD PTR [rdi + 24]
+    mov r10, QWORD PTR [rdi + 8]
+    shl QWORD PTR [rdi + 8], 1
+    call dict_alloc_slots_64
+    xor ecx, ecx
+.dict_resize_loop:
+    cmp rcx, r10
+    je .dict_resize_done
+    mov rsi, QWORD PTR [r8 + rcx*8]
+    test rsi, rsi
+    jz .dict_resize_next
+    call dict_find_slot_64
+    mov rdx, QWORD PTR [rdi + 16]
+    mov QWORD PTR [rdx + rax*8], rsi
+    mov rdx, QWORD PTR [r9 + rcx*8]
+    mov rsi, QWORD PTR [rdi + 24]
+    mov QWORD PTR [rsi + rax*8], rdx
+.dict_resize_next:
+    inc rcx
+    jmp .dict_resize_loop
+.dict_resize_done:
+    push rdi
+    mov rdi, r8
+    call heap_free_64
+    mov rdi, r9
+    call heap_free_64
+    pop rdi
+    pop r10
+    pop r9
+    pop r8
+    pop rsi
+    pop rdx
+    pop rcx
+    pop rax
+    ret
+
+dict_key_error_64:
+    lea rdi, [dict_key_error_message]
+    jmp runtime_error_64
+
+    .section .data
+dict_key_error_message:
+    .asciz "key not found in dictionary"
+    .section .text
+list_create_64:
+    # Input: rdi = initial capacity; output: rax = empty list
+    push rdi
+    mov rdi, 24
+    call heap_alloc_64
+    mov BYTE PTR [rax - 15], 1  # kind: list
+    pop rdi
+    test rdi, rdi
+    jnz .list_create_sized
+    mov rdi, 4
+.list_create_sized:
+    mov QWORD PTR [rax], 0
+    mov QWORD PTR [rax + 8], rdi
+    push rax
+    shl rdi, 3
+    call heap_alloc_64
+    mov rdi, rax
+    pop rax
+    mov QWORD PTR [rax + 16], rdi
+    ret
+
+list_append_64:
+    # Input: rdi = list, rsi = value; output: rax = list
+    push rcx
+    push rdi
+    mov rdi, rsi
+    call __rc_inc
+    pop rdi
+    mov rcx, QWORD PTR [rdi]
+    cmp rcx, QWORD PTR [rdi + 8]
+    jb .list_append_store
+    call list_grow_64
+.list_append_store:
+    mov rax, QWORD PTR [rdi + 16]
+    mov QWORD PTR [rax + rcx*8], rsi
+    inc rcx
+    mov QWORD PTR [rdi], rcx
+    pop rcx
+    mov rax, rdi
+    ret
+
+list_grow_64:
+    # Input: rdi = list; doubles its capacity, moving the elements if the buffer has to
+    push rsi
+    push rdi
+    mov rax, QWORD PTR [rdi + 8]
+    shl rax, 1
+    mov QWORD PTR [rdi + 8], rax
+    lea rsi, [rax*8]
+    mov rdi, QWORD PTR [rdi + 16]
+    call heap_realloc_64
+    pop rdi
+    mov QWORD PTR [rdi + 16], rax
+    pop rsi
+    ret
+
+list_get_64:
+    # Input: rdi = list, rsi = index; output: rax = element
+    cmp rsi, QWORD PTR [rdi]
+    jae .list_index_error       # unsigned, so negative indices are rejected too
+    mov rax, QWORD PTR [rdi + 16]
+    mov rax, QWORD PTR [rax + rsi*8]
+    ret
+
+list_set_64:
+    # Input: rdi = list, rsi = index, rdx = value; output: rax = value
+    cmp rsi, QWORD PTR [rdi]
+    jae .list_index_error
+    push rdi
+    mov rax, QWORD PTR [rdi + 16]
+    lea rax, [rax + rsi*8]
+    mov rdi, rdx
+    call __rc_inc
+    mov rdi, QWORD PTR [rax]
+    mov QWORD PTR [rax], rdx
+    call __rc_dec               # the element it replaces
+    pop rdi
+    mov rax, rdx
+    ret
+
+.list_index_error:
+    lea rdi, [list_index_error_message]
+    jmp runtime_error_64
+
+list_len_64:
+    # Input: rdi = list; output: rax = number of elements
+    mov rax, QWORD PTR [rdi]
+    ret
+
+str_split_64:
+    # Input: rdi = string, rsi = separator; output: rax = list of fresh copies of
+    # the pieces between separators, empty ones included
+    push rbx
+    push rdi
+    push r8
+    push r9
+    push r12
+    mov r8, rdi
+    mov r9, rsi
+    xor ebx, ebx
+.str_split_measure:
+    cmp BYTE PTR [r9 + rbx], 0
+    je .str_split_measured
+    inc rbx
+    jmp .str_split_measure
+.str_split_measured:
+    test rbx, rbx
+    jz .str_split_empty_separator
+    xor edi, edi
+    call list_create_64
+    mov r12, rax
+    mov rdi, r8
+.str_split_scan:
+    cmp BYTE PTR [rdi], 0
+    je .str_split_last
+    call .str_split_match
+    jne .str_split_next
+    call .str_split_piece
+    add rdi, rbx
+    mov r8, rdi
+    jmp .str_split_scan
+.str_split_next:
+    inc rdi
+    jmp .str_split_scan
+.str_split_last:
+    call .str_split_piece
+    mov rax, r12
+    pop r12
+    pop r9
+    pop r8
+    pop rdi
+    pop rbx
+    ret
+.str_split_empty_separator:
+    lea rdi, [str_split_error_message]
+    jmp runtime_error_64
+
+.str_split_match:
+    # Input: rdi = position in the string, r9 = separator of rbx bytes; output:
+    # ZF set when the separator starts there
+    push rax
+    push rcx
+    xor ecx, ecx
+.str_split_match_loop:
+    cmp rcx, rbx
+    je .str_split_match_done
+    mov al, BYTE PTR [r9 + rcx]
+    cmp al, BYTE PTR [rdi + rcx]
+    jne .str_split_match_done
+    inc rcx
+    jmp .str_split_match_loop
+.str_split_match_done:
+    pop rcx
+    pop rax
+    ret
+
+.str_split_piece:
+    # Input: r8 = start of a piece, rdi = its end, r12 = list; appends a copy
+    push rcx
+    push rsi
+    push rdi
+    mov rcx, rdi
+    sub rcx, r8
+    lea rdi, [rcx + 1]
+    call heap_alloc_64
+    mov rdi, rax
+    mov rsi, r8
+    rep movsb
+    mov BYTE PTR [rdi], 0
+    mov rsi, rax
+    mov rdi, r12
+    call list_append_64
+    pop rdi
+    pop rsi
+    pop rcx
+    ret
+
+    .section .data
+list_index_error_message:
+    .asciz "list index out of range"
+str_split_error_message:
+    .asciz "empty separator"
+    .section .text
+str_hash_64:
+    # Input: rdi = string; output: rax = djb2 hash of its bytes
+    push rcx
+    push rdi
+    mov rax, 5381
+.str_hash_loop:
+    movzx ecx, BYTE PTR [rdi]
+    test ecx, ecx
+    jz .str_hash_done
+    imul rax, rax, 33
+    add rax, rcx
+    inc rdi
+    jmp .str_hash_loop
+.str_hash_done:
+    pop rdi
+    pop rcx
+    ret
+
+str_equal_64:
+    # Input: rdi, rsi = strings; output: rax = 1 when their contents match
+    push rcx
+    push rdx
+    push rdi
+    push rsi
+.str_equal_loop:
+    movzx ecx, BYTE PTR [rdi]
+    movzx edx, BYTE PTR [rsi]
+    cmp ecx, edx
+    jne .str_equal_differs
+    test ecx, ecx
+    jz .str_equal_same
+    inc rdi
+    inc rsi
+    jmp .str_equal_loop
+.str_equal_same:
+    mov rax, 1
+    jmp .str_equal_done
+.str_equal_differs:
+    xor eax, eax
+.str_equal_done:
+    pop rsi
+    pop rdi
+    pop rdx
+    pop rcx
+    ret
+
+str_length_64:
+    # Input: rdi = string; output: rax = number of bytes before its NUL. len(),
+    # subscripts and slices all count bytes, so a character outside ASCII
+    # takes several positions
+    xor eax, eax
+.str_length_loop:
+    cmp BYTE PTR [rdi + rax], 0
+    je .str_length_done
+    inc rax
+    jmp .str_length_loop
+.str_length_done:
+    ret
+
+str_slice_64:
+    # Input: rdi = string, rsi = start, rdx = stop, each counted from the end
+    # when negative and clamped to the string; output: rax = fresh copy of the
+    # bytes from start up to stop, empty when stop is not after start
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    call str_length_64
+    mov rcx, rax
+    mov rax, rsi
+    call .str_clamp
+    mov rsi, rax
+    mov rax, rdx
+    call .str_clamp
+    sub rax, rsi
+    jg .str_slice_copy
+    xor eax, eax
+.str_slice_copy:
+    push rsi
+    push rax
+    lea rdi, [rax + 1]
+    call heap_alloc_64
+    pop rcx
+    pop rsi
+    add rsi, QWORD PTR [rsp]    # the string
+    mov rdi, rax
+    rep movsb
+    mov BYTE PTR [rdi], 0
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    ret
+
+.str_clamp:
+    # Input: rax = bound, rcx = length; output: rax = the bound counted from the
+    # end when negative, clamped to 0..length
+    test rax, rax
+    jns .str_clamp_upper
+    add rax, rcx
+    jns .str_clamp_upper
+    xor eax, eax
+.str_clamp_upper:
+    cmp rax, rcx
+    jle .str_clamp_done
+    mov rax, rcx
+.str_clamp_done:
+    ret
+
+.str_prefix:
+    # Input: rdi = position in a string, rsi = pattern; output: ZF set when the
+    # pattern starts there
+    push rax
+    push rcx
+    xor ecx, ecx
+.str_prefix_loop:
+    movzx eax, BYTE PTR [rsi + rcx]
+    test eax, eax
+    jz .str_prefix_done
+    cmp al, BYTE PTR [rdi + rcx]
+    jne .str_prefix_done
+    inc rcx
+    jmp .str_prefix_loop
+.str_prefix_done:
+    pop rcx
+    pop rax
+    ret
+
+str_replace_64:
+    # Input: rdi = string, rsi = old, rdx = new; output: rax = fresh copy of the
+    # string with every occurrence of old replaced, scanning left to right
+    push rbx
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    push r8
+    push r9
+    push r10
+    push r12
+    mov r8, rdi
+    mov r9, rsi
+    mov r10, rdx
+    mov rdi, r9
+    call str_length_64
+    mov rbx, rax
+    mov rdi, r10
+    call str_length_64
+    mov r12, rax
+    xor ecx, ecx                # length of the result
+    mov rdi, r8
+    mov rsi, r9
+.str_replace_measure:
+    cmp BYTE PTR [rdi], 0
+    je .str_replace_alloc
+    test rbx, rbx               # an empty old string matches nowhere
+    jz .str_replace_measure_byte
+    call .str_prefix
+    jne .str_replace_measure_byte
+    add rcx, r12
+    add rdi, rbx
+    jmp .str_replace_measure
+.str_replace_measure_byte:
+    inc rcx
+    inc rdi
+    jmp .str_replace_measure
+.str_replace_alloc:
+    lea rdi, [rcx + 1]
+    call heap_alloc_64
+    push rax
+    mov rdx, rax
+    mov rdi, r8
+.str_replace_copy:
+    movzx ecx, BYTE PTR [rdi]
+    test ecx, ecx
+    jz .str_replace_done
+    test rbx, rbx
+    jz .str_replace_copy_byte
+    call .str_prefix
+    jne .str_replace_copy_byte
+    push rsi
+    push rdi
+    mov rsi, r10
+    mov rdi, rdx
+    mov rcx, r12
+    rep movsb
+    mov rdx, rdi
+    pop rdi
+    pop rsi
+    add rdi, rbx
+    jmp .str_replace_copy
+.str_replace_copy_byte:
+    mov BYTE PTR [rdx], cl
+    inc rdx
+    inc rdi
+    jmp .str_replace_copy
+.str_replace_done:
+    mov BYTE PTR [rdx], 0
+    pop rax
+    pop r12
+    pop r10
+    pop r9
+    pop r8
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    pop rbx
+    ret
+
+str_find_64:
+    # Input: rdi = string, rsi = needle; output: rax = byte position of the
+    # needle's first occurrence, -1 when it does not occur
+    push rdi
+.str_find_loop:
+    call .str_prefix
+    je .str_find_found
+    cmp BYTE PTR [rdi], 0
+    je .str_find_missing
+    inc rdi
+    jmp .str_find_loop
+.str_find_found:
+    mov rax, rdi
+    sub rax, QWORD PTR [rsp]
+    pop rdi
+    ret
+.str_find_missing:
+    mov rax, -1
+    pop rdi
+    ret
+
+str_upper_64:
+    # Input: rdi = string; output: rax = fresh copy with a-z in uppercase
+    push rdx
+    mov edx, 0x7A61             # dl = 'a', dh = 'z'
+    jmp .str_case
+str_lower_64:
+    # Input: rdi = string; output: rax = fresh copy with A-Z in lowercase
+    push rdx
+    mov edx, 0x5A41             # dl = 'A', dh = 'Z'
+.str_case:
+    # Copies the string with the case of the letters from dl to dh flipped;
+    # other bytes, those of characters outside ASCII included, stay as they are
+    push rcx
+    push rsi
+    push rdi
+    call str_length_64
+    lea rdi, [rax + 1]
+    call heap_alloc_64
+    mov rsi, QWORD PTR [rsp]    # the string
+    mov rdi, rax
+.str_case_loop:
+    movzx ecx, BYTE PTR [rsi]
+    cmp cl, dl
+    jb .str_case_store
+    cmp cl, dh
+    ja .str_case_store
+    xor cl, 0x20
+.str_case_store:
+    mov BYTE PTR [rdi], cl
+    inc rsi
+    inc rdi
+    test ecx, ecx
+    jnz .str_case_loop
+    pop rdi
+    pop rsi
+    pop rcx
+    pop rdx
+    ret
+
+str_strip_64:
+    # Input: rdi = string; output: rax = fresh copy without the ASCII
+    # whitespace at either end
+    push rcx
+    push rsi
+    push rdi
+    call str_length_64
+    lea rsi, [rdi + rax]        # one past the last byte
+.str_strip_start:
+    cmp rdi, rsi
+    je .str_strip_copy
+    movzx ecx, BYTE PTR [rdi]
+    call .str_space
+    jnc .str_strip_end
+    inc rdi
+    jmp .str_strip_start
+.str_strip_end:
+    movzx ecx, BYTE PTR [rsi - 1]   # rdi is not whitespace, so this stops there
+    call .str_space
+    jnc .str_strip_copy
+    dec rsi
+    jmp .str_strip_end
+.str_strip_copy:
+    push rdx
+    mov rdx, rsi
+    mov rsi, rdi
+    mov rdi, QWORD PTR [rsp + 8]    # the string
+    sub rsi, rdi
+    sub rdx, rdi
+    call str_slice_64
+    pop rdx
+    pop rdi
+    pop rsi
+    pop rcx
+    ret
+
+.str_space:
+    # Input: ecx = byte; output: CF set for a space, tab, line feed, vertical
+    # tab, form feed or carriage return
+    cmp ecx, 32
+    je .str_space_found
+    push rcx
+    sub ecx, 9
+    cmp ecx, 5
+    pop rcx
+    ret
+.str_space_found:
+    stc
+    ret
+    .section .text
+str_index_64:
+    # Input: rdi = string, rsi = index, negative counting from the end;
+    # output: rax = fresh one-byte string holding the byte there
+    push rcx
+    push rsi
+    push rdi
+    call str_length_64
+    test rsi, rsi
+    jns .str_index_check
+    add rsi, rax
+.str_index_check:
+    cmp rsi, rax
+    jae .str_index_error        # unsigned, so what is still negative is rejected too
+    push rsi
+    mov edi, 2
+    call heap_alloc_64
+    pop rsi
+    mov rdi, QWORD PTR [rsp]
+    movzx ecx, BYTE PTR [rdi + rsi]
+    mov BYTE PTR [rax], cl
+    mov BYTE PTR [rax + 1], 0
+    pop rdi
+    pop rsi
+    pop rcx
+    ret
+.str_index_error:
+    lea rdi, [str_index_error_message]
+    jmp runtime_error_64
+
+    .section .data
+str_index_error_message:
+    .asciz "string index out of range"
+    .section .text
+heap_grow_64:
+    # Input: rdi = size in bytes; output: rax = 16-byte aligned memory from the
+    # program break, for heap_alloc_64 to carve blocks from
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    push r11
+    mov rax, QWORD PTR [heap_top]
+    test rax, rax
+    jnz .heap_have_top
+    mov rax, 12                 # syscall: brk(0) reports the current break
+    xor rdi, rdi
+    syscall
+    add rax, 15
+    and rax, -16
+    mov QWORD PTR [heap_base], rax
+    mov QWORD PTR [heap_top], rax
+.heap_have_top:
+    mov rdx, QWORD PTR [rsp + 8]
+    add rdx, 15
+    and rdx, -16
+    lea rdi, [rax + rdx]
+    mov rsi, rax
+    mov rax, 12                 # syscall: brk
+    syscall
+    cmp rax, rdi
+    jb .heap_exhausted
+    mov QWORD PTR [heap_top], rdi
+    mov rax, rsi
+    pop r11
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    ret
+.heap_exhausted:
+    lea rdi, [heap_error_message]
+    jmp runtime_error_64
+
+io_read_line_64:
+    # Output: rax = next line of stdin without its newline, NUL-terminated in a
+    # fresh 256-byte block; longer lines are cut short and the rest skipped
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    push r11
+    push rbx
+    push r12
+    mov rdi, 256
+    call heap_alloc_64
+    mov rbx, rax
+    xor r12, r12
+.io_read_loop:
+    xor eax, eax                # syscall: read one byte
+    xor edi, edi
+    lea rsi, [rbx + r12]
+    mov rdx, 1
+    syscall
+    cmp rax, 1
+    jne .io_read_done           # end of input
+    cmp BYTE PTR [rbx + r12], 10
+    je .io_read_done
+    cmp r12, 255
+    je .io_read_loop
+    inc r12
+    jmp .io_read_loop
+.io_read_done:
+    mov BYTE PTR [rbx + r12], 0
+    mov rax, rbx
+    pop r12
+    pop rbx
+    pop r11
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    ret
+
+sleep_ms_64:
+    # Input: rdi = milliseconds; output: rax = 0, or -errno when nanosleep fails.
+    # The request struct timespec { tv_sec, tv_nsec } is built on the stack, with
+    # the remainder after it so a signal only resumes the rest of the sleep
+    xor eax, eax
+    test rdi, rdi
+    jle .sleep_done
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    push r11
+    mov rax, rdi
+    xor edx, edx
+    mov ecx, 1000
+    div rcx
+    imul rdx, rdx, 1000000
+    sub rsp, 32
+    mov QWORD PTR [rsp], rax        # tv_sec
+    mov QWORD PTR [rsp + 8], rdx    # tv_nsec
+.sleep_again:
+    mov rdi, rsp
+    lea rsi, [rsp + 16]             # time left when interrupted
+    mov rax, 35                     # syscall: nanosleep
+    syscall
+    cmp rax, -4                     # EINTR
+    jne .sleep_slept
+    movdqu xmm0, XMMWORD PTR [rsp + 16]
+    movdqu XMMWORD PTR [rsp], xmm0
+    jmp .sleep_again
+.sleep_slept:
+    add rsp, 32
+    pop r11
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+.sleep_done:
+    ret
+
+runtime_error_64:
+    # Input: rdi = message; reports it on stderr and exits with status 1
+    push rdi
+    mov rax, 1
+    mov rdi, 2
+    lea rsi, [runtime_error_prefix]
+    mov rdx, 15
+    syscall
+    pop rsi
+    xor rdx, rdx
+.runtime_error_length:
+    cmp BYTE PTR [rsi + rdx], 0
+    je .runtime_error_write
+    inc rdx
+    jmp .runtime_error_length
+.runtime_error_write:
+    mov rax, 1
+    mov rdi, 2
+    syscall
+    mov rax, 1
+    mov rdi, 2
+    lea rsi, [runtime_error_newline]
+    mov rdx, 1
+    syscall
+    mov rax, 60                 # syscall: exit
+    mov rdi, 1
+    syscall
+
+    .section .data
+heap_base:
+    .quad 0
+heap_top:
+    .quad 0
+runtime_error_prefix:
+    .ascii "Runtime error: "
+runtime_error_newline:
+    .byte 10
+heap_error_message:
+    .asciz "out of memory"
+    .section .text
+heap_alloc_64:
+    # Input: rdi = size in bytes; output: rax = 16-byte aligned block, reused
+    # from the free list of its power-of-two size class when there is one
+    push rcx
+    push rdx
+    push rdi
+    lea rdi, [rdi + 15]         # header included, minus one
+    mov ecx, 5                  # 32 bytes at least
+    cmp rdi, 32
+    jb .heap_class_found
+    bsr rcx, rdi
+    inc ecx
+.heap_class_found:
+    lea rdx, [rip + heap_free_lists]
+    mov rax, QWORD PTR [rdx + rcx*8]
+    test rax, rax
+    jz .heap_carve
+    mov rdi, QWORD PTR [rax + 8]
+    mov QWORD PTR [rdx + rcx*8], rdi
+    jmp .heap_header
+.heap_carve:
+    mov edi, 1
+    shl rdi, cl
+    call heap_grow_64
+.heap_header:
+    mov DWORD PTR [rax], ecx    # size class, raw kind
+    mov DWORD PTR [rax + 4], 0x45475243     # magic
+    mov QWORD PTR [rax + 8], 0
+    add rax, 16
+    pop rdi
+    pop rdx
+    pop rcx
+    ret
+
+heap_free_64:
+    # Input: rdi = block from heap_alloc_64; puts it on its size class's free list
+    push rax
+    push rcx
+    push rdx
+    push rsi
+    lea rax, [rdi - 16]
+    movzx ecx, BYTE PTR [rax]
+    mov QWORD PTR [rax], rcx    # no magic: stale pointers are not objects any more
+    lea rdx, [rip + heap_free_lists]
+    mov rsi, QWORD PTR [rdx + rcx*8]
+    mov QWORD PTR [rax + 8], rsi
+    mov QWORD PTR [rdx + rcx*8], rax
+    pop rsi
+    pop rdx
+    pop rcx
+    pop rax
+    ret
+
+heap_realloc_64:
+    # Input: rdi = block from heap_alloc_64, rsi = size in bytes; output: rax =
+    # a block that large with the old contents. It stays put while its size
+    # class has room, otherwise the whole old block is copied and then freed
+    push rcx
+    push rsi
+    push rdi
+    movzx ecx, BYTE PTR [rdi - 16]
+    mov eax, 1
+    shl rax, cl
+    sub rax, 16                 # room the old block has
+    cmp rsi, rax
+    jbe .heap_realloc_fits
+    mov rcx, rax
+    shr rcx, 3
+    mov rdi, rsi
+    call heap_alloc_64
+    mov rsi, QWORD PTR [rsp]
+    mov rdi, rax
+    rep movsq
+    mov rdi, QWORD PTR [rsp]
+    call heap_free_64
+    pop rdi
+    pop rsi
+    pop rcx
+    ret
+.heap_realloc_fits:
+    mov rax, rdi
+    pop rdi
+    pop rsi
+    pop rcx
+    ret
+
+.rc_header:
+    # Input: rdi = value; output: rax = its header when it is a live heap
+    # block, otherwise 0 (integers, literals and freed blocks)
+    lea rax, [rdi - 16]
+    cmp rax, QWORD PTR [rip + heap_base]
+    jb .rc_not_object
+    cmp rax, QWORD PTR [rip + heap_top]
+    jae .rc_not_object
+    test al, 15
+    jnz .rc_not_object
+    cmp DWORD PTR [rax + 4], 0x45475243
+    jne .rc_not_object
+    ret
+.rc_not_object:
+    xor eax, eax
+    ret
+
+__rc_inc:
+    # Input: rdi = value; counts one more reference to it if it is an object
+    push rax
+    call .rc_header
+    test rax, rax
+    jz .rc_inc_done
+    inc QWORD PTR [rax + 8]
+.rc_inc_done:
+    pop rax
+    ret
+
+__rc_dec:
+    # Input: rdi = value; drops a reference and releases the object with the last one
+    cmp BYTE PTR [rip + rc_enabled], 0
+    je .rc_dec_disabled
+    push rax
+    call .rc_header
+    test rax, rax
+    jz .rc_dec_done
+    cmp QWORD PTR [rax + 8], 0  # never counted, still owned by whoever made it
+    je .rc_dec_done
+    dec QWORD PTR [rax + 8]
+    jnz .rc_dec_done
+    call .rc_release
+.rc_dec_done:
+    pop rax
+.rc_dec_disabled:
+    ret
+
+__rc_disown:
+    # Input: rdi = value; drops a reference without releasing the object, so a
+    # function can hand its result back uncounted
+    push rax
+    call .rc_header
+    test rax, rax
+    jz .rc_disown_done
+    cmp QWORD PTR [rax + 8], 0
+    je .rc_disown_done
+    dec QWORD PTR [rax + 8]
+.rc_disown_done:
+    pop rax
+    ret
+
+.rc_release:
+    # Input: rdi = object, rax = its header; drops what a list, dictionary or
+    # struct holds, frees its buffers and then the object itself
+    push rcx
+    push rsi
+    push rdi
+    mov rsi, rdi
+    movzx ecx, BYTE PTR [rax + 1]
+    cmp ecx, 1
+    je .rc_release_list
+    cmp ecx, 2
+    je .rc_release_dict
+    cmp ecx, 3
+    je .rc_release_struct
+    jmp .rc_release_free
+.rc_release_list:
+    mov rcx, QWORD PTR [rsi]
+.rc_release_elements:
+    test rcx, rcx
+    jz .rc_release_list_data
+    dec rcx
+    mov rdi, QWORD PTR [rsi + 16]
+    mov rdi, QWORD PTR [rdi + rcx*8]
+    call __rc_dec
+    jmp .rc_release_elements
+.rc_release_list_data:
+    mov rdi, QWORD PTR [rsi + 16]
+    call heap_free_64
+    jmp .rc_release_free
+.rc_release_dict:
+    mov rcx, QWORD PTR [rsi + 8]
+.rc_release_slots:
+    test rcx, rcx
+    jz .rc_release_tables
+    dec rcx
+    mov rdi, QWORD PTR [rsi + 16]
+    mov rdi, QWORD PTR [rdi + rcx*8]
+    test rdi, rdi
+    jz .rc_release_slots
+    call __rc_dec
+    mov rdi, QWORD PTR [rsi + 24]
+    mov rdi, QWORD PTR [rdi + rcx*8]
+    call __rc_dec
+    jmp .rc_release_slots
+.rc_release_tables:
+    mov rdi, QWORD PTR [rsi + 16]
+    call heap_free_64
+    mov rdi, QWORD PTR [rsi + 24]
+    call heap_free_64
+    jmp .rc_release_free
+.rc_release_struct:
+    movzx ecx, WORD PTR [rax + 2]
+.rc_release_fields:
+    test rcx, rcx
+    jz .rc_release_free
+    dec rcx
+    mov rdi, QWORD PTR [rsi + rcx*8]
+    call __rc_dec
+    jmp .rc_release_fields
+.rc_release_free:
+    mov rdi, rsi
+    call heap_free_64
+    pop rdi
+    pop rsi
+    pop rcx
+    ret
+
+    .section .data
+heap_free_lists:
+    .zero 512                   # one list head per size class
+.att_syntax
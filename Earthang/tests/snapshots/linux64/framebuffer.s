@@ -0,0 +1 @@
+# error: fb_init() draws into the BIOS framebuffer and needs --bios-mode at 2:1
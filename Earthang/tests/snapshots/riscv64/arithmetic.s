@@ -0,0 +1,332 @@
+    .text
+    .globl _start
+
+_start:
+    call main
+    li a7, 93         # exit with main's result
+    ecall
+
+main:
+    addi sp, sp, -80
+    sd ra, 72(sp)
+    sd s0, 64(sp)
+    addi s0, sp, 80
+
+    # @line 2
+    # Import: system
+    # @line 3
+    # Variable declaration: a
+    li a0, 17
+    sd a0, -24(s0)
+    # @line 4
+    # Variable declaration: b
+    li a0, 5
+    sd a0, -32(s0)
+    # @line 5
+    # Variable declaration: on
+    li a0, 1
+    sd a0, -40(s0)
+    # @line 6
+    # Assignment to a
+    ld a0, -24(s0)  # a
+    addi sp, sp, -16
+    sd a0, 0(sp)
+    li a0, 1
+    ld t0, 0(sp)
+    addi sp, sp, 16
+    add a0, t0, a0
+    sd a0, -24(s0)
+    # @line 7
+    # Augmented assignment to a
+    ld a0, -24(s0)  # a
+    addi sp, sp, -16
+    sd a0, 0(sp)
+    ld a0, -32(s0)  # b
+    ld t0, 0(sp)
+    addi sp, sp, 16
+    add a0, t0, a0
+    sd a0, -24(s0)
+    # @line 8
+    # Augmented assignment to a
+    ld a0, -24(s0)  # a
+    addi sp, sp, -16
+    sd a0, 0(sp)
+    li a0, 2
+    ld t0, 0(sp)
+    addi sp, sp, 16
+    sub a0, t0, a0
+    sd a0, -24(s0)
+    # @line 9
+    # Augmented assignment to a
+    ld a0, -24(s0)  # a
+    addi sp, sp, -16
+    sd a0, 0(sp)
+    li a0, 3
+    ld t0, 0(sp)
+    addi sp, sp, 16
+    mul a0, t0, a0
+    sd a0, -24(s0)
+    # @line 10
+    ld a0, -24(s0)  # a
+    addi sp, sp, -16
+    sd a0, 0(sp)
+    ld a0, -32(s0)  # b
+    ld t0, 0(sp)
+    addi sp, sp, 16
+    add a0, t0, a0
+    call print_decimal
+    la a0, str_000000000002b5c5
+    call print_string
+    ld a0, -24(s0)  # a
+    addi sp, sp, -16
+    sd a0, 0(sp)
+    ld a0, -32(s0)  # b
+    ld t0, 0(sp)
+    addi sp, sp, 16
+    sub a0, t0, a0
+    call print_decimal
+    la a0, str_000000000002b5c5
+    call print_string
+    ld a0, -24(s0)  # a
+    addi sp, sp, -16
+    sd a0, 0(sp)
+    ld a0, -32(s0)  # b
+    ld t0, 0(sp)
+    addi sp, sp, 16
+    mul a0, t0, a0
+    call print_decimal
+    la a0, str_000000000002b5c5
+    call print_string
+    ld a0, -24(s0)  # a
+    addi sp, sp, -16
+    sd a0, 0(sp)
+    ld a0, -32(s0)  # b
+    ld t0, 0(sp)
+    addi sp, sp, 16
+    div a0, t0, a0
+    call print_decimal
+    la a0, str_000000000002b5c5
+    call print_string
+    ld a0, -24(s0)  # a
+    addi sp, sp, -16
+    sd a0, 0(sp)
+    ld a0, -32(s0)  # b
+    ld t0, 0(sp)
+    addi sp, sp, 16
+    rem a0, t0, a0
+    call print_decimal
+    call print_newline
+    # @line 11
+    ld a0, -24(s0)  # a
+    addi sp, sp, -16
+    sd a0, 0(sp)
+    ld a0, -32(s0)  # b
+    ld t0, 0(sp)
+    addi sp, sp, 16
+    and a0, t0, a0
+    call print_decimal
+    la a0, str_000000000002b5c5
+    call print_string
+    ld a0, -24(s0)  # a
+    addi sp, sp, -16
+    sd a0, 0(sp)
+    ld a0, -32(s0)  # b
+    ld t0, 0(sp)
+    addi sp, sp, 16
+    or a0, t0, a0
+    call print_decimal
+    la a0, str_000000000002b5c5
+    call print_string
+    ld a0, -24(s0)  # a
+    addi sp, sp, -16
+    sd a0, 0(sp)
+    ld a0, -32(s0)  # b
+    ld t0, 0(sp)
+    addi sp, sp, 16
+    xor a0, t0, a0
+    call print_decimal
+    la a0, str_000000000002b5c5
+    call print_string
+    ld a0, -24(s0)  # a
+    neg a0, a0
+    call print_decimal
+    la a0, str_000000000002b5c5
+    call print_string
+    ld a0, -32(s0)  # b
+    call print_decimal
+    la a0, str_000000000002b5c5
+    call print_string
+    ld a0, -40(s0)  # on
+    seqz a0, a0
+    call print_decimal
+    call print_newline
+    # @line 12
+    ld a0, -40(s0)  # on
+    beqz a0, .Lbool_short_1
+    ld a0, -32(s0)  # b
+    beqz a0, .Lbool_short_1
+    li a0, 1
+    j .Lbool_end_1
+.Lbool_short_1:
+    li a0, 0
+.Lbool_end_1:
+    call print_decimal
+    la a0, str_000000000002b5c5
+    call print_string
+    ld a0, -40(s0)  # on
+    bnez a0, .Lbool_short_2
+    ld a0, -24(s0)  # a
+    bnez a0, .Lbool_short_2
+    li a0, 0
+    j .Lbool_end_2
+.Lbool_short_2:
+    li a0, 1
+.Lbool_end_2:
+    call print_decimal
+    la a0, str_000000000002b5c5
+    call print_string
+    la a0, str_000000017c95cc2b  # "done"
+    call print_string
+    call print_newline
+    # @line 13
+    # For loop over range() into i
+    li a0, 2
+    sd a0, -48(s0)
+    li a0, 8
+    sd a0, -56(s0)
+.Lfor_start_3:
+    ld t0, -48(s0)
+    ld t1, -56(s0)
+    bge t0, t1, .Lfor_end_3
+    sd t0, -72(s0)
+    ld a0, -72(s0)  # i
+    call print_decimal
+    call print_newline
+    li t1, 3
+    ld t0, -48(s0)
+    add t0, t0, t1
+    sd t0, -48(s0)
+    j .Lfor_start_3
+.Lfor_end_3:
+    # @line 17
+    li a0, 1
+    call sleep_ms
+    # @end
+
+    li a0, 0
+    ld ra, 72(sp)
+    ld s0, 64(sp)
+    addi sp, sp, 80
+    ret
+
+print_string:
+    mv a1, a0
+    li a2, 0
+1:
+    add t0, a1, a2
+    lbu t1, 0(t0)
+    beqz t1, 2f
+    addi a2, a2, 1
+    j 1b
+2:
+    li a0, 1
+    li a7, 64
+    ecall
+    ret
+
+print_decimal:
+    addi sp, sp, -32
+    mv t0, a0
+    addi t1, sp, 32
+    li t3, 0
+    bgez t0, 1f
+    li t3, 1
+    neg t0, t0
+1:
+    li t2, 10
+2:
+    remu t4, t0, t2
+    divu t0, t0, t2
+    addi t4, t4, 48
+    addi t1, t1, -1
+    sb t4, 0(t1)
+    bnez t0, 2b
+    beqz t3, 3f
+    li t4, 45
+    addi t1, t1, -1
+    sb t4, 0(t1)
+3:
+    li a0, 1
+    mv a1, t1
+    addi a2, sp, 32
+    sub a2, a2, t1
+    li a7, 64
+    ecall
+    addi sp, sp, 32
+    ret
+
+print_newline:
+    li a0, 1
+    la a1, newline
+    li a2, 1
+    li a7, 64
+    ecall
+    ret
+
+runtime_error:
+    mv t3, a0
+    li a0, 2
+    la a1, runtime_error_prefix
+    li a2, 15
+    li a7, 64
+    ecall
+    mv a1, t3
+    li a2, 0
+1:
+    add t0, a1, a2
+    lbu t1, 0(t0)
+    beqz t1, 2f
+    addi a2, a2, 1
+    j 1b
+2:
+    li a0, 2
+    ecall
+    li a0, 2
+    la a1, newline
+    li a2, 1
+    ecall
+    li a0, 1
+    li a7, 93
+    ecall
+
+sleep_ms:
+    bgtz a0, 1f
+    li a0, 0
+    ret
+1:
+    addi sp, sp, -16
+    li t0, 1000
+    div t1, a0, t0
+    rem t2, a0, t0
+    li t0, 1000000
+    mul t2, t2, t0
+    sd t1, 0(sp)
+    sd t2, 8(sp)
+    mv a0, sp
+    li a1, 0
+    li a7, 101
+    ecall
+    addi sp, sp, 16
+    ret
+
+    .section .rodata
+newline:
+    .byte 10, 0
+runtime_error_prefix:
+    .ascii "Runtime error: "
+
+# String literals
+str_000000000002b5c5:  # " "
+    .byte 0x20, 0x00
+str_000000017c95cc2b:  # "done"
+    .byte 0x64, 0x6f, 0x6e, 0x65, 0x00
@@ -0,0 +1 @@
+# error: function definitions are not supported by the riscv64 backend at 2:1
@@ -0,0 +1 @@
+# error: unary operations are not supported by the riscv64 backend at 9:1
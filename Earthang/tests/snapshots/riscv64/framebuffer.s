@@ -0,0 +1 @@
+# error: function calls are not supported by the riscv64 backend at 2:1
@@ -0,0 +1 @@
+# error: The string module has no code for riscv64; it supports linux64